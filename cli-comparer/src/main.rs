@@ -1,33 +1,438 @@
-use crate::comparer as comparer_logic;
 use clap::Parser;
-use lib::console::commands::Cli;
-use lib::console::commands::Commands;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+use lib::compare::many::{compare_many, expand_inputs};
+use lib::compare::nway::{compare_nway, Disagreement, NwayReport};
+use lib::compare::quick::{quick_check, QuickCheck};
+use lib::compare::report::{write_junit_report, write_report, ReportEntry};
+use lib::audit::{append_audit, AuditEntry};
+use lib::cache::ParseCache;
+use lib::compare::{compare_inputs, CompareErr, CompareOptions, CompareReport, KeyMismatch, Tolerance};
+use lib::console::commands::{Cli, Commands};
+use lib::console::i18n::{set_lang, tr, tr_args, Lang};
+use lib::console::render::{color_enabled, render_error};
+use lib::drift::{observe_shape, schema_diff};
+use lib::interrupt::install_signal_handlers;
+use lib::metrics::{push_statsd, write_prometheus_textfile, RunMetrics};
+use lib::model::normalize::DescriptionNormalization;
+use lib::model::time::TimeWindow;
+use lib::notify::notify_webhook;
+use lib::parser::set_stdin_timeout;
+use lib::prelude::{Format, ParseOptions, Resource};
+use lib::spill::SpillOptions;
 
-fn main() {
+fn main() -> ExitCode {
+    let started = Instant::now();
     let cli = Cli::parse();
+    set_lang(cli.lang.unwrap_or_else(Lang::from_env));
+    // Сравнение только читает входы: сигнал завершает его сразу с кодом 128 + номер сигнала
+    if let Err(e) = install_signal_handlers() {
+        eprintln!("{}", tr_args("signal-handlers-error", &[("error", e.to_string().into())]));
+    }
+    if cli.reads_terminal() {
+        eprintln!("{}", tr("stdin-is-terminal"));
+        return ExitCode::from(2);
+    }
+    set_stdin_timeout(cli.stdin_timeout.map(Duration::from_secs));
+    let notify_retry = cli.notify_retry();
+    let jobs = cli.worker_threads();
+    let parse_cache = cli.parse_cache.as_ref().map(ParseCache::new);
+    // Как у diff: 1 — входы различаются, 2 — сравнение не удалось
+    let mut differs = false;
+    let mut failed = false;
 
-    match cli.command {
+    let audit = match cli.command {
         Commands::CompareCommand {
             first_from,
             first_format,
             second_from,
             second_format,
+            also_from,
+            also_format,
+            since,
+            until,
+            timestamp_tolerance,
+            amount_tolerance,
+            duplicates,
+            normalize_description,
+            max_description_len,
+            report,
+            junit,
+            quick,
+            spill,
         } => {
-            println!("Comparing:");
-            println!("  Input: {:?} (format: {:?})", first_from, first_format);
-            println!("  Input: {:?} (format: {:?})", second_from, second_format);
+            println!("{}", tr("comparing"));
+            let inputs = [(&first_from, &first_format), (&second_from, &second_format)].into_iter().chain(also_from.iter().zip(&also_format));
+            for (from, format) in inputs {
+                let args = [("resource", format!("{:?}", from).into()), ("format", format!("{:?}", format).into())];
+                println!("{}", tr_args("compare-input", &args));
+            }
 
-            let res = comparer_logic::logic::process_comparer_logic(first_from, first_format, second_from, second_format);
+            let options = CompareOptions {
+                tolerance: Tolerance {
+                    timestamp_ms: timestamp_tolerance,
+                    amount: amount_tolerance,
+                },
+                duplicates,
+                parse: ParseOptions {
+                    time_window: TimeWindow { since, until },
+                    // Отчёт показывает, где во входах искать различающиеся записи
+                    provenance: report.is_some(),
+                    normalize_description: DescriptionNormalization::from_steps(&normalize_description, max_description_len),
+                    ..ParseOptions::default()
+                },
+                spill: spill.then(|| {
+                    let mut spill = SpillOptions { temp_dir: cli.temp_dir.clone(), ..SpillOptions::default() };
+                    if let Some(budget) = cli.memory_limit {
+                        spill.fit_memory(budget);
+                    }
+                    spill
+                }),
+            };
+            let expand = |from: &Resource| match from {
+                Resource::File { path } => expand_inputs(path),
+                _ => Ok(None),
+            };
+            let title = format!("{} vs {}", first_from, second_from);
+            let write_report = |entries: &[ReportEntry<'_>]| {
+                if let Some(path) = &report
+                    && let Err(e) = write_report(path, &title, entries)
+                {
+                    eprintln!("{}", tr_args("report-error", &[("error", e.to_string().into())]));
+                }
+                if let Some(path) = &junit
+                    && let Err(e) = write_junit_report(path, entries)
+                {
+                    eprintln!("{}", tr_args("report-error", &[("error", e.to_string().into())]));
+                }
+            };
+            let entry = AuditEntry::new("compare-command");
+            if !also_from.is_empty() {
+                let formats_given = also_format.len() == also_from.len();
+                let inputs: Vec<_> = [(first_from, first_format), (second_from, second_format)]
+                    .into_iter()
+                    .chain(also_from.into_iter().zip(also_format))
+                    .collect();
+                if !formats_given {
+                    println!("{}", tr("compare-also-format-count"));
+                    failed = true;
+                    None
+                } else if inputs.iter().any(|(from, _)| !matches!(expand(from), Ok(None))) {
+                    println!("{}", tr("compare-nway-files-only"));
+                    failed = true;
+                    None
+                } else {
+                    let (entry, nway_differs) = run_nway(entry, inputs, &options);
+                    differs = nway_differs;
+                    Some(entry)
+                }
+            } else {
+                let check = match (&first_from, &second_from) {
+                    (Resource::File { path: first }, Resource::File { path: second })
+                        if quick && first_format == second_format && first.is_file() && second.is_file() =>
+                    {
+                        quick_check(first, second).ok()
+                    },
+                    _ => None,
+                };
+                if let Some(check) = &check {
+                    println!("{}", quick_summary(check));
+                }
+                match (expand(&first_from), expand(&second_from)) {
+                    _ if check.as_ref().is_some_and(QuickCheck::identical) => {
+                        println!("{}", result(true));
+                        let entry = entry.input(&first_from).input(&second_from);
+                        Some(entry.count("equal", 1).count("quick_identical", 1))
+                    },
+                    (Ok(None), Ok(None)) => {
+                        let entry = entry.input(&first_from).input(&second_from);
+                        // Отчёт с происхождением записей ссылается на строки входов, а не кэша
+                        let cache = parse_cache.as_ref().filter(|_| !options.parse.provenance);
+                        let cached = |from: &Resource, format: Format, input: usize| match cache {
+                            Some(cache) => cache.resolve(from.clone(), format).map_err(|err| CompareErr::Prepare { input, err }),
+                            None => Ok((from.clone(), format)),
+                        };
+                        let res = cached(&first_from, first_format, 1).and_then(|(first, first_format)| {
+                            let (second, second_format) = cached(&second_from, second_format, 2)?;
+                            compare_inputs(first, first_format, second, second_format, &options)
+                        });
 
-            println!("result : {:?}", res)
+                        write_report(&[ReportEntry { name: title.clone(), result: &res }]);
+                        differs = res.as_ref().is_ok_and(|r| !r.equal);
+                        let entry = match &res {
+                            Ok(r) => entry
+                                .count("first_records", r.first_records)
+                                .count("second_records", r.second_records)
+                                .count("equal", r.equal as usize)
+                                .count("mismatched_keys", r.mismatches.len())
+                                .count("differing", r.differing),
+                            Err(_) => entry,
+                        };
+                        match &res {
+                            Ok(r) => {
+                                for mismatch in &r.mismatches {
+                                    println!("{}", key_mismatch(mismatch));
+                                }
+                                println!("{}", result(summary(r)));
+                            },
+                            Err(e) => {
+                                let (input, format) = match e {
+                                    CompareErr::Prepare { input: 2, .. } => (second_from, second_format),
+                                    _ => (first_from, first_format),
+                                };
+                                eprint!("{}", render_error(e, Some(&input), format, color_enabled()));
+                            },
+                        }
+                        Some(entry.outcome(&res))
+                    },
+                    (Ok(Some(first)), Ok(Some(second))) => {
+                        let res = compare_many(
+                            first,
+                            first_format,
+                            second,
+                            second_format,
+                            &options,
+                            jobs,
+                        );
+                        let mut entry = entry;
+                        for file in res.iter().flat_map(|many| &many.files) {
+                            // Файл только с одной стороны — различие, остальные ошибки — неудавшееся сравнение
+                            match &file.result {
+                                Ok(r) => differs |= !r.equal,
+                                Err(CompareErr::MissingPair { .. }) => differs = true,
+                                Err(_) => failed = true,
+                            }
+                            for path in [&file.first, &file.second].into_iter().flatten() {
+                                entry = entry.input(&Resource::File { path: path.clone() });
+                            }
+                            match &file.result {
+                                Ok(r) => {
+                                    println!("  {}: {}", file.name, summary(r));
+                                    for mismatch in &r.mismatches {
+                                        println!("  {}", key_mismatch(mismatch));
+                                    }
+                                },
+                                Err(e) => println!("  {}: {}", file.name, render_error(e, None, first_format, false).trim_end()),
+                            }
+                        }
+                        if let Ok(many) = &res {
+                            let entries: Vec<_> = many
+                                .files
+                                .iter()
+                                .map(|file| ReportEntry { name: file.name.clone(), result: &file.result })
+                                .collect();
+                            write_report(&entries);
+                            entry = entry
+                                .count("files", many.files.len())
+                                .count("equal_files", many.files.iter().filter(|f| f.equal()).count())
+                                .count("equal", many.equal as usize);
+                        }
+                        match &res {
+                            Ok(many) => println!("{}", result(many.equal)),
+                            Err(e) => eprint!("{}", render_error(e, None, first_format, color_enabled())),
+                        }
+                        Some(entry.outcome(&res))
+                    },
+                    (Err(e), _) | (_, Err(e)) => {
+                        eprint!("{}", render_error(&e, None, first_format, color_enabled()));
+                        failed = true;
+                        None
+                    },
+                    _ => {
+                        println!("{}", tr("compare-inputs-need-directories"));
+                        failed = true;
+                        None
+                    },
+                }
+            }
+        },
+
+        Commands::SchemaDiff {
+            first_from,
+            first_format,
+            second_from,
+            second_format,
+            batch_size,
+        } => {
+            println!("{}", tr("comparing"));
+            for (from, format) in [(&first_from, &first_format), (&second_from, &second_format)] {
+                let args = [("resource", format!("{:?}", from).into()), ("format", format!("{:?}", format).into())];
+                println!("{}", tr_args("compare-input", &args));
+            }
+            let batch_size = cli.memory_limit.map_or(batch_size, |budget| batch_size.min(budget.batch_size(1)));
+            let entry = AuditEntry::new("schema-diff").input(&first_from).input(&second_from);
+            let shapes = observe_shape(first_from.clone(), first_format, batch_size).map_err(|e| (e, 1)).and_then(|first| {
+                let second = observe_shape(second_from.clone(), second_format, batch_size).map_err(|e| (e, 2))?;
+                Ok((first, second))
+            });
+            match shapes {
+                Ok((first, second)) => {
+                    let drifts = schema_diff(&first, &second);
+                    for drift in &drifts {
+                        println!("{}", drift);
+                    }
+                    let args = [
+                        ("drifts", drifts.len().into()),
+                        ("first", first.records.into()),
+                        ("second", second.records.into()),
+                    ];
+                    println!("{}", result(tr_args("schema-diff-summary", &args)));
+                    Some(entry.count("drifts", drifts.len()).outcome(&Ok::<(), std::convert::Infallible>(())))
+                },
+                Err((e, input)) => {
+                    let (from, format) = if input == 1 { (&first_from, first_format) } else { (&second_from, second_format) };
+                    eprint!("{}", render_error(&e, Some(from), format, color_enabled()));
+                    Some(entry.outcome(&Err::<(), _>(e)))
+                },
+            }
         },
 
           _ => {
-              println!("Error. Work only with CompareCommand");
+              println!("{}", tr("comparer-commands-only"));
+              failed = true;
+              None
         } 
+    };
+
+    let failed = failed || audit.as_ref().is_some_and(|entry| !entry.succeeded());
+    if let Some(entry) = &audit {
+        let metrics = RunMetrics {
+            command: entry.command().to_string(),
+            duration: started.elapsed(),
+            success: entry.succeeded(),
+            counts: entry.counts().to_vec(),
+        };
+        if let Some(path) = &cli.metrics_textfile
+            && let Err(e) = write_prometheus_textfile(path, &metrics)
+        {
+            eprintln!("{}", tr_args("metrics-error", &[("error", format!("{:?}", e).into())]));
+        }
+        if let Some(addr) = &cli.metrics_statsd
+            && let Err(e) = push_statsd(addr, &metrics)
+        {
+            eprintln!("{}", tr_args("metrics-error", &[("error", format!("{:?}", e).into())]));
+        }
+        if let Some(url) = &cli.notify_webhook
+            && let Err(e) = notify_webhook(url, entry, metrics.duration, &notify_retry)
+        {
+            eprintln!("{}", tr_args("notify-error", &[("error", format!("{:?}", e).into())]));
+        }
+    }
 
-        
+    if let (Some(log), Some(entry)) = (cli.audit_log, audit)
+        && let Err(e) = append_audit(&log, &entry)
+    {
+        eprintln!("{}", tr_args("audit-log-error", &[("error", format!("{:?}", e).into())]));
+    }
+    match (failed, differs) {
+        (true, _) => ExitCode::from(2),
+        (false, true) => ExitCode::FAILURE,
+        (false, false) => ExitCode::SUCCESS,
     }
 }
 
-pub mod comparer;
\ No newline at end of file
+
+/// Сравнение трёх и более входов: расхождения по TX_ID с номерами согласных входов.
+/// Вместе с записью аудита возвращает, различаются ли входы.
+fn run_nway(
+    entry: AuditEntry,
+    inputs: Vec<(Resource, Format)>,
+    options: &CompareOptions,
+) -> (AuditEntry, bool) {
+    let entry = inputs.iter().fold(entry, |entry, (from, _)| entry.input(from));
+    let res = compare_nway(inputs.clone(), options);
+    let entry = match &res {
+        Ok(r) => {
+            for disagreement in &r.disagreements {
+                println!("{}", nway_disagreement(disagreement));
+            }
+            println!("{}", result(nway_summary(r)));
+            entry
+                .count("inputs", r.records.len())
+                .count("equal", r.equal as usize)
+                .count("mismatched_keys", r.disagreements.len())
+        },
+        Err(e) => {
+            let input = match e {
+                CompareErr::Prepare { input, .. } => *input,
+                _ => 1,
+            };
+            let (from, format) = &inputs[input - 1];
+            eprint!("{}", render_error(e, Some(from), *format, color_enabled()));
+            entry
+        },
+    };
+    (entry.outcome(&res), res.as_ref().is_ok_and(|r| !r.equal))
+}
+
+/// Строка о TX_ID, по которому входы расходятся: группы согласных входов и входы без записи.
+fn nway_disagreement(disagreement: &Disagreement) -> String {
+    let list = |inputs: &[usize]| inputs.iter().map(usize::to_string).collect::<Vec<_>>().join(",");
+    let groups = disagreement.groups.iter().map(|group| list(group)).collect::<Vec<_>>().join(" | ");
+    let missing = if disagreement.missing.is_empty() { "-".to_string() } else { list(&disagreement.missing) };
+    let args = [
+        ("id", disagreement.tx_id.to_string().into()),
+        ("groups", groups.into()),
+        ("missing", missing.into()),
+    ];
+    tr_args("compare-nway-key", &args)
+}
+
+/// Сводка сравнения нескольких входов на языке сообщений.
+fn nway_summary(report: &NwayReport) -> String {
+    let records = report.records.iter().map(usize::to_string).collect::<Vec<_>>().join("/");
+    let args = [
+        ("equal", report.equal.to_string().into()),
+        ("records", records.into()),
+        ("disagreeing", report.disagreements.len().into()),
+    ];
+    tr_args("compare-nway-summary", &args)
+}
+/// Итог быстрой проверки: совпадение или различающиеся участки обоих файлов.
+fn quick_summary(check: &QuickCheck) -> String {
+    if check.identical() {
+        return tr_args("compare-quick-identical", &[("hash", check.first_hash.clone().into())]);
+    }
+    let ranges = |ranges: &[std::ops::Range<u64>]| match ranges {
+        [] => "-".to_string(),
+        ranges => {
+            // Совсем разные файлы дали бы тысячи участков; первых достаточно, чтобы начать поиск
+            let mut shown: Vec<_> = ranges.iter().take(10).map(|r| format!("{}..{}", r.start, r.end)).collect();
+            if ranges.len() > shown.len() {
+                shown.push("…".to_string());
+            }
+            shown.join(", ")
+        },
+    };
+    let args = [
+        ("first", ranges(&check.first_only).into()),
+        ("second", ranges(&check.second_only).into()),
+    ];
+    tr_args("compare-quick-differ", &args)
+}
+
+/// Сводка сравнения пары входов на языке сообщений.
+fn summary(report: &CompareReport) -> String {
+    let args = [
+        ("equal", report.equal.to_string().into()),
+        ("first", report.first_records.into()),
+        ("second", report.second_records.into()),
+        ("differing", report.differing.into()),
+    ];
+    tr_args("compare-summary", &args)
+}
+
+/// Строка о TX_ID, записи которого не нашли пары в другом входе.
+fn key_mismatch(mismatch: &KeyMismatch) -> String {
+    let args = [
+        ("id", mismatch.tx_id.to_string().into()),
+        ("first", mismatch.first.into()),
+        ("second", mismatch.second.into()),
+    ];
+    tr_args("compare-key-mismatch", &args)
+}
+
+/// Итоговая строка команды на языке сообщений.
+fn result(value: impl std::fmt::Display) -> String {
+    tr_args("result", &[("value", value.to_string().into())])
+}
@@ -0,0 +1,55 @@
+//! Код завершения как у diff: 0 — входы совпадают, 1 — различаются, 2 — сравнение не удалось.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+const CSV: &str = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,1633036860000,SUCCESS,\"a\"\n\
+                   2,WITHDRAWAL,10,0,50,1633036920000,PENDING,\"b\"\n";
+
+fn compare(first: &Path, second: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cli-comparer"))
+        .args(["compare-command", "--first-format", "yp-bank-csv", "--second-format", "yp-bank-csv"])
+        .arg(format!("--first-from=file:{}", first.display()))
+        .arg(format!("--second-from=file:{}", second.display()))
+        .env("LANG", "C")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_exit_code_tells_equal_different_and_failed() {
+    let dir = tempfile::tempdir().unwrap();
+    let first = dir.path().join("first.csv");
+    let same = dir.path().join("same.csv");
+    let changed = dir.path().join("changed.csv");
+    fs::write(&first, CSV).unwrap();
+    fs::write(&same, CSV).unwrap();
+    fs::write(&changed, CSV.replace(",50,", ",51,")).unwrap();
+
+    let run = compare(&first, &same);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stdout));
+    let run = compare(&first, &changed);
+    assert_eq!(run.status.code(), Some(1), "{}", String::from_utf8_lossy(&run.stdout));
+    let run = compare(&first, &dir.path().join("missing.csv"));
+    assert_eq!(run.status.code(), Some(2), "{}", String::from_utf8_lossy(&run.stderr));
+}
+
+#[test]
+fn test_file_missing_from_one_directory_is_a_difference() {
+    let dir = tempfile::tempdir().unwrap();
+    let (first, second) = (dir.path().join("first"), dir.path().join("second"));
+    fs::create_dir_all(&first).unwrap();
+    fs::create_dir_all(&second).unwrap();
+    for name in ["a.csv", "b.csv"] {
+        fs::write(first.join(name), CSV).unwrap();
+    }
+    fs::write(second.join("a.csv"), CSV).unwrap();
+
+    let run = compare(&first, &second);
+    assert_eq!(run.status.code(), Some(1), "{}", String::from_utf8_lossy(&run.stdout));
+    fs::write(second.join("b.csv"), "not a csv").unwrap();
+    let run = compare(&first, &second);
+    assert_eq!(run.status.code(), Some(2), "{}", String::from_utf8_lossy(&run.stdout));
+}
@@ -1,38 +1,266 @@
-use core::error;
-use std::io::Read;
-
-use lib::model::data::TxData;
-use lib::parser::io::writer::write_to_resource;
-use lib::parser::io::reader::read;
-use lib::{
-    console::commands::{Commands, Resource},
-    model::{data::Format, errors::ParserErr},
+use std::fmt::Write as _;
+use std::path::Path;
+
+use lib::bench::{run_bench, BenchOptions, BenchResult};
+use lib::cache::ParseCache;
+use lib::console::i18n::tr_args;
+use lib::delta::{apply, diff, DeltaSummary};
+use lib::doctor::{diagnose, write_findings_report, Finding, FindingsFormat};
+use lib::parser::{find_by_id, BinProfile, ReportFormat};
+use lib::prelude::{
+    read, write_to_resource, CommonErr, ConvertErr, ConvertOptions, ConvertReport, Format, IoErr, ParseOptions, Resource, TxId, WriteOptions,
 };
+use lib::registry::{convert_with, format_by_name};
+use lib::schema::{write_schema, SchemaKind};
+use lib::screening::{screen, write_screening_report, ScreeningOptions, UserScore};
+use lib::spill::{sort_file, SortOptions, SortReport};
+use lib::stats::{collect_stats, StatsOptions, StatsReport};
+use lib::testing::{generate_corpus, CorpusEntry};
+use thiserror::Error;
 
 
-#[derive(Clone, Debug)]
-pub struct ConvertLogicResult {
-    success: bool,
+/// Сводка для человека: `read 1000 records (52340 bytes), wrote 998 records (40012 bytes), skipped 2, 0 warnings in 0.120s`.
+pub fn summary(report: &ConvertReport) -> String {
+    let mut text = tr_args("convert-summary-read", &[("records", report.records_read.into())]);
+    if let Some(bytes_in) = report.bytes_in {
+        let _ = write!(text, " ({})", tr_args("convert-summary-bytes-in", &[("bytes", bytes_in.into())]));
+    }
+    let args = [
+        ("records", report.records_written.into()),
+        ("bytes", report.bytes_out.into()),
+        ("skipped", report.skipped.into()),
+        ("warnings", report.warnings.into()),
+        ("seconds", format!("{:.3}", report.elapsed.as_secs_f64()).into()),
+    ];
+    let _ = write!(text, ", {}", tr_args("convert-summary-written", &args));
+    text
 }
 
 
-#[derive(Clone, Debug)]
+#[derive(Error, Clone, Debug)]
 pub enum ConvertLogicErr {
-    Prepare { err: ParserErr },
+    /// Не удалось прочитать, разобрать или записать данные
+    #[error("failed to convert input")]
+    Prepare {
+        #[from]
+        err: CommonErr,
+    },
+    /// Конвертация не удалась (см. [`ConvertErr`])
+    #[error(transparent)]
+    Convert(#[from] ConvertErr),
+    #[error("convert logic error")]
     Logic,
+    /// Формата нет ни среди встроенных, ни среди подключённых через `--plugin`
+    #[error("unknown format {name}")]
+    UnknownFormat { name: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct GetLogicResult {
+    pub found: bool,
+}
+
+/// Ищет транзакцию по `id` и записывает её в `to`, если она нашлась.
+pub fn process_get_logic(
+    from: Resource,
+    from_format: Format,
+    id: TxId,
+    to: Resource,
+    to_format: Format,
+) -> Result<GetLogicResult, ConvertLogicErr> {
+    let Some(tx) = find_by_id(from, from_format, &id)? else {
+        return Ok(GetLogicResult { found: false });
+    };
+
+    write_to_resource(&[tx], to, to_format)?;
+    Ok(GetLogicResult { found: true })
+}
+
+#[derive(Clone, Debug)]
+pub struct PluginConvertLogicResult {
+    /// Сколько записей записано
+    pub records: usize,
+    /// Сколько некорректных записей пропущено
+    pub skipped: usize,
+}
+
+/// Конвертирует `from` в `to` между форматами по именам, встроенными или из `--plugin`.
+pub fn process_plugin_convert_logic(
+    from: Resource,
+    from_format: &str,
+    to: Resource,
+    to_format: &str,
+    lenient: bool,
+) -> Result<PluginConvertLogicResult, ConvertLogicErr> {
+    let format = |name: &str| format_by_name(name).ok_or_else(|| ConvertLogicErr::UnknownFormat { name: name.to_string() });
+    let (from_format, to_format) = (format(from_format)?, format(to_format)?);
+    let options = ConvertOptions {
+        parse: if lenient { ParseOptions::collect() } else { ParseOptions::default() },
+        ..ConvertOptions::default()
+    };
+
+    let outcome = convert_with(from, from_format.as_ref(), to, to_format.as_ref(), &options)?;
+    Ok(PluginConvertLogicResult {
+        records: outcome.records.len(),
+        skipped: outcome.issues.len(),
+    })
+}
+
+/// Записывает в `to` дельту от снимка `base` к снимку `current`.
+pub fn process_diff_logic(
+    base: Resource,
+    base_format: Format,
+    current: Resource,
+    current_format: Format,
+    to: Resource,
+) -> Result<DeltaSummary, ConvertLogicErr> {
+    Ok(diff(base, base_format, current, current_format, to)?)
+}
+
+/// Применяет дельту `delta` к снимку `base` и записывает результат в `to`.
+pub fn process_apply_logic(
+    base: Resource,
+    base_format: Format,
+    delta: Resource,
+    to: Resource,
+    to_format: Format,
+) -> Result<DeltaSummary, ConvertLogicErr> {
+    Ok(apply(base, base_format, delta, to, to_format, &WriteOptions::default())?)
+}
+
+/// Проверяет `from` всеми эвристиками диагностики; при заданном `report` записывает
+/// находки в отчёт (см. [`write_findings_report`]).
+///
+/// # Возвращает
+/// Находки от ошибок к подсказкам.
+pub fn process_doctor_logic(
+    from: Resource,
+    from_format: Format,
+    report: Option<(Resource, FindingsFormat)>,
+) -> Result<Vec<Finding>, ConvertLogicErr> {
+    let findings = diagnose(from.clone(), from_format)?;
+    if let Some((resource, format)) = report {
+        write_findings_report(&findings, &from, resource, format)?;
+    }
+    Ok(findings)
+}
+
+/// Вход для анализа: с `cache` — разобранные записи из кэша (см. [`ParseCache::resolve`]).
+fn analysis_input(from: Resource, from_format: Format, cache: Option<&ParseCache>) -> Result<(Resource, Format), CommonErr> {
+    match cache {
+        Some(cache) => cache.resolve(from, from_format),
+        None => Ok((from, from_format)),
+    }
+}
+
+/// Оценивает пользователей `from` эвристиками подозрительной активности (см. [`screen`]).
+///
+/// Пользователи с оценкой ниже `min_score` отбрасываются; оставшиеся при заданном `report`
+/// записываются в отчёт.
+///
+/// # Возвращает
+/// Пользователей с находками по убыванию оценки.
+pub fn process_screen_logic(
+    from: Resource,
+    from_format: Format,
+    options: &ScreeningOptions,
+    min_score: u32,
+    report: Option<(Resource, ReportFormat)>,
+    cache: Option<&ParseCache>,
+) -> Result<Vec<UserScore>, ConvertLogicErr> {
+    let (from, from_format) = analysis_input(from, from_format, cache)?;
+    let records = read(from, from_format)?;
+    let mut scores = screen(&records, options);
+    scores.retain(|user| user.score >= min_score);
+    if let Some((resource, format)) = report {
+        write_screening_report(&scores, resource, format)?;
+    }
+    Ok(scores)
+}
+
+/// Строит отчёты `options` по `from` потоковым чтением (см. [`collect_stats`]).
+pub fn process_stats_logic(
+    from: Resource,
+    from_format: Format,
+    options: StatsOptions,
+    batch_size: usize,
+    cache: Option<&ParseCache>,
+) -> Result<StatsReport, ConvertLogicErr> {
+    let (from, from_format) = analysis_input(from, from_format, cache)?;
+    Ok(collect_stats(from, from_format, options, batch_size)?)
 }
 
-pub fn process_convert_logic(
+/// Сортирует `from` в `to` (см. [`sort_file`]).
+pub fn process_sort_logic(
     from: Resource,
     from_format: Format,
     to: Resource,
     to_format: Format,
-) -> Result<ConvertLogicResult, ConvertLogicErr> {
-    let data = read(from, from_format)
-        .map_err(|err| ConvertLogicErr::Prepare { err })?;
+    options: &SortOptions,
+) -> Result<SortReport, ConvertLogicErr> {
+    Ok(sort_file(from, from_format, to, to_format, options)?)
+}
 
-    write_to_resource(&data, to, to_format)
-       .map_err(|err| ConvertLogicErr::Prepare { err })?;
+/// Записывает описание форматов (см. [`write_schema`]).
+pub fn process_schema_logic(kind: SchemaKind, bin_profile: BinProfile, to: Resource) -> Result<usize, ConvertLogicErr> {
+    Ok(write_schema(kind, bin_profile, to)?)
+}
+
+/// Записывает корпус проверки совместимости форматов `formats` в каталог `to` (см. [`generate_corpus`]).
+pub fn process_gen_conformance_logic(to: &Path, formats: &[Format]) -> Result<Vec<CorpusEntry>, ConvertLogicErr> {
+    Ok(generate_corpus(to, formats)?)
+}
 
-    Ok(ConvertLogicResult { success: true })
-}
\ No newline at end of file
+/// Замеряет конвертацию синтетического файла (см. [`run_bench`]) во временном каталоге
+/// внутри `dir` (по умолчанию — системного каталога временных файлов), который затем удаляется.
+pub fn process_bench_logic(
+    dir: Option<&Path>,
+    options: &BenchOptions,
+    on_result: &mut dyn FnMut(&BenchResult),
+) -> Result<Vec<BenchResult>, ConvertLogicErr> {
+    let scratch = dir.map_or_else(std::env::temp_dir, Path::to_path_buf).join(format!("ypbank-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&scratch).map_err(|e| CommonErr::from(IoErr::output(e, Some(scratch.clone()))))?;
+    let res = run_bench(&scratch, options, on_result);
+    let _ = std::fs::remove_dir_all(&scratch);
+    Ok(res?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib::convert::IssuesReport;
+    use lib::prelude::convert;
+    use std::fs;
+
+    #[test]
+    fn test_convert_writes_errors_report_and_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("in.csv");
+        let input = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                     1,DEPOSIT,0,10,100,1700000000,SUCCESS,\"x\"\n\
+                     oops\n\
+                     2,DEPOSIT,0,10,100,1700000000,SUCCESS,\"y\"\n";
+        fs::write(&from, input).unwrap();
+        let errors = dir.path().join("errors.json");
+        let options = ConvertOptions {
+            parse: ParseOptions::collect(),
+            ..ConvertOptions::default()
+        };
+
+        let converted = convert(
+            Resource::File { path: from },
+            Format::YpBankCsv,
+            Resource::File { path: dir.path().join("out.txt") },
+            Format::YpBankText,
+            &options,
+            None,
+            &mut |_| {},
+        );
+        let report = IssuesReport { to: Resource::File { path: errors.clone() }, format: ReportFormat::Json }
+            .write(converted)
+            .unwrap();
+
+        assert!(summary(&report).starts_with("read 3 records"));
+        assert!(fs::read_to_string(errors).unwrap().contains("\"line\":3"));
+    }
+}
@@ -1 +1 @@
-pub mod logic;
\ No newline at end of file
+pub mod logic;
@@ -1,30 +1,691 @@
 use crate::converter as converter_logic;
 use clap::Parser;
-use lib::console::commands::Cli;
-use lib::console::commands::Commands;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use lib::convert::job::{run_convert_job, ConvertJob, JobEvent, JobOutcome, JobRejection, JobReport, RunSinks, SinkErr};
+use lib::audit::AuditEntry;
+use lib::bench::{BenchOptions, BENCH_HEADER};
+use lib::bundle::{unbundle, write_bundle, BundleEntry};
+use lib::cache::ParseCache;
+use lib::console::commands::{Cli, Commands};
+use lib::console::i18n::{set_lang, tr, tr_args, Lang};
+use lib::console::render::{color_enabled, render_error};
+use lib::convert::IssuesReport;
+use lib::delta::DeltaSummary;
+use lib::extract::UserFilter;
+use lib::interrupt::{cooperate, exit_code, flag, install_signal_handlers};
+use lib::migrate::migrate_or_inspect;
+use lib::model::normalize::DescriptionNormalization;
+use lib::model::time::TimeWindow;
+use lib::model::validation::TimestampBounds;
+use lib::parser::{read_metadata, set_stdin_timeout, BinCompression, MemoryBudget, TextOptions};
+use lib::plugin::load_plugin;
+use lib::prelude::{CheckpointMode, ConvertErr, ConvertOptions, ParseOptions, ReadOptions, Resource, WriteOptions};
+use lib::preview::{preview_table, Preview};
+use lib::screening::ScreeningOptions;
+use lib::spill::{SortOptions, SpillOptions};
+use lib::stats::StatsOptions;
 
-fn main() {
+fn main() -> ExitCode {
+    let started = Instant::now();
     let cli = Cli::parse();
+    set_lang(cli.lang.unwrap_or_else(Lang::from_env));
+    if let Err(e) = install_signal_handlers() {
+        eprintln!("{}", tr_args("signal-handlers-error", &[("error", e.to_string().into())]));
+    }
+    if cli.reads_terminal() {
+        eprintln!("{}", tr("stdin-is-terminal"));
+        return ExitCode::from(2);
+    }
+    for path in &cli.plugin {
+        if let Err(e) = load_plugin(path) {
+            eprintln!("{}", tr_args("plugin-error", &[("error", e.to_string().into())]));
+            return ExitCode::from(2);
+        }
+    }
+    set_stdin_timeout(cli.stdin_timeout.map(Duration::from_secs));
+    let notify_retry = cli.notify_retry();
+    let jobs = cli.worker_threads();
+    let memory_limit = cli.memory_limit;
+    let temp_dir = cli.temp_dir.clone();
+    let parse_cache = cli.parse_cache.as_ref().map(ParseCache::new);
+    // Команда не запустилась или часть входов не обработана; ошибки команд видны по журналу аудита
+    let mut failed = false;
 
-    match cli.command {
+    let audit = match cli.command {
         Commands::ReadParseWriteCommand {
             from,
             from_format,
             to,
+            in_place,
+            to_format,
+            lenient,
+            errors_report,
+            errors_report_format,
+            warnings_as_errors,
+            strict,
+            check_timestamps,
+            min_timestamp,
+            max_timestamp,
+            rules,
+            users_file,
+            exclude_users_file,
+            rates,
+            from_currency,
+            to_currency,
+            keep_original_amount,
+            transform_script,
+            empty_field_policy,
+            normalize_description,
+            max_description_len,
+            description_policy,
+            relaxed_text,
+            keep_comments,
+            amount_locale,
+            csv_reorder_ok,
+            csv_extra_columns,
+            skip_rows,
+            comment_prefix,
+            bin_profile,
+            bin_zstd_level,
+            bin_index,
+            bin_header,
+            bin_dictionary,
+            bin_seekable,
+            csv_bom,
+            csv_quote_style,
+            join_users,
+            since,
+            timestamp_unit,
+            until,
+            skip,
+            limit,
+            batch_size,
+            flush_every,
+            flush_interval_records,
+            buffer_size,
+            max_rate,
+            preview,
+            emit_manifest,
+            producer_metadata,
+            append,
+            if_absent,
+            transactional,
+            no_lossy,
+            checkpoint,
+            resume,
+            stable_order,
+            verify,
+            junit,
+            skip_processed,
+        } => {
+            let _cooperation = cooperate();
+            let to = to.unwrap_or_else(|| from.clone());
+            eprintln!("{}", tr("comparing"));
+            let args = [("resource", format!("{:?}", from).into()), ("format", format!("{:?}", from_format).into())];
+            eprintln!("{}", tr_args("convert-from", &args));
+            let args = [("resource", format!("{:?}", to).into()), ("format", format!("{:?}", to_format).into())];
+            eprintln!("{}", tr_args("convert-to", &args));
+
+            let mut parse = if lenient || errors_report.is_some() {
+                ParseOptions::collect()
+            } else {
+                ParseOptions::default()
+            };
+            parse.warnings_as_errors = warnings_as_errors;
+            parse.strict = strict;
+            parse.timestamp_bounds = check_timestamps.then(|| {
+                let default = TimestampBounds::default();
+                TimestampBounds {
+                    min: min_timestamp.unwrap_or(default.min),
+                    max: max_timestamp.unwrap_or(default.max),
+                }
+            });
+            parse.rules = rules.map(Arc::new);
+            parse.empty_field_policy = empty_field_policy;
+            parse.normalize_description = DescriptionNormalization::from_steps(&normalize_description, None);
+            if relaxed_text {
+                parse.text = TextOptions::relaxed();
+            }
+            parse.text.keep_comments = keep_comments;
+            parse.csv.amount_locale = amount_locale;
+            parse.csv.reorder_ok = csv_reorder_ok;
+            parse.csv.allow_extra_columns = csv_extra_columns;
+            parse.csv.skip_rows = skip_rows;
+            parse.csv.comment_prefix = comment_prefix;
+            parse.timestamp_unit = timestamp_unit;
+            parse.time_window = TimeWindow { since, until };
+            let mut options = ConvertOptions {
+                parse,
+                read: ReadOptions { skip, limit },
+                write: WriteOptions {
+                    bin_profile,
+                    bin_compression: bin_zstd_level
+                        .map_or(BinCompression::None, |level| BinCompression::Zstd { level }),
+                    bin_index,
+                    bin_header,
+                    bin_dictionary,
+                    bin_seek_table: bin_seekable,
+                    csv_bom,
+                    csv_quote_style,
+                    join_users: join_users.map(Arc::new),
+                    max_description_len,
+                    description_length_policy: description_policy,
+                    buffer_size,
+                    flush_interval_records,
+                    metadata: producer_metadata.then(|| lib::convert::job::producer_metadata(PRODUCER, &from)),
+                    append,
+                    if_absent,
+                    ..WriteOptions::default()
+                },
+                batch_size,
+                flush_every,
+                jobs,
+                stable_order,
+                verify,
+                transactional,
+                no_lossy,
+                interrupt: Some(flag()),
+                ..ConvertOptions::default()
+            };
+            if let Some(budget) = memory_limit {
+                options.fit_memory(budget);
+            }
+            if let Some(rate) = max_rate {
+                options.limit_rate(rate);
+            }
+            let users = UserFilter { include: users_file, exclude: exclude_users_file };
+            options.transforms = users.install(options.transforms);
+            if let Some(rates) = rates {
+                let (from_currency, to_currency) = (from_currency.unwrap_or_default(), to_currency.unwrap_or_default());
+                match rates.conversion(&from_currency, &to_currency) {
+                    Ok(conversion) => options.transforms = conversion.keep_original(keep_original_amount).install(options.transforms),
+                    Err(e) => {
+                        eprintln!("{}", tr_args("currency-error", &[("error", e.to_string().into())]));
+                        return ExitCode::from(2);
+                    }
+                }
+            }
+            if let Some(script) = &transform_script {
+                options.transforms = script.install(options.transforms);
+            }
+            let preview = preview.map(|size| {
+                Arc::new(Preview::new(size as usize, |records| {
+                    eprintln!("{}", tr_args("preview-header", &[("count", records.len().into())]));
+                    eprint!("{}", preview_table(records));
+                }))
+            });
+            if let Some(preview) = &preview {
+                options.transforms = preview.install(options.transforms);
+            }
+            let job = ConvertJob {
+                from,
+                from_format,
+                to,
+                to_format,
+                in_place,
+                options,
+                checkpoint: match (checkpoint, resume) {
+                    (_, true) => Some(CheckpointMode::Resume),
+                    (true, false) => Some(CheckpointMode::Save),
+                    (false, false) => None,
+                },
+                errors_report: errors_report.map(|to| IssuesReport {
+                    to,
+                    format: errors_report_format,
+                }),
+                skip_processed,
+                manifest_producer: emit_manifest.then(|| PRODUCER.to_string()),
+                junit,
+            };
+            let report = run_convert_job(&job, &render_job_event);
+            render_job(&job, &report);
+            failed = !report.succeeded();
+            // Вход кончился раньше, чем выборка показалась посреди конвертации
+            if let Some(preview) = &preview {
+                preview.finish();
+            }
+            if let Some(script) = &transform_script {
+                let (count, first) = script.failures();
+                if count > 0 {
+                    let args = [("count", count.into()), ("error", first.unwrap_or_default().to_string().into())];
+                    eprintln!("{}", tr_args("transform-script-failures", &args));
+                    failed = true;
+                }
+            }
+            report.audit
+        },
+
+        Commands::Get {
+            from,
+            from_format,
+            id,
+            to,
+            to_format,
+        } => {
+            let entry = AuditEntry::new("get").input(&from).output(&to);
+            let res = converter_logic::logic::process_get_logic(from.clone(), from_format, id, to, to_format);
+            let found = res.as_ref().map_or(0, |r| r.found as usize);
+            match &res {
+                Ok(r) => eprintln!("{}", result(format!("{:?}", r))),
+                Err(e) => eprint!("{}", render_error(e, Some(&from), from_format, color_enabled())),
+            }
+            Some(entry.count("records", found).outcome(&res))
+        },
+
+        Commands::DoctorCommand { from, from_format, report, report_format } => {
+            let entry = AuditEntry::new("doctor-command").input(&from);
+            let entry = match &report {
+                Some(report) => entry.output(report),
+                None => entry,
+            };
+            // Сведения о записавшей файл программе подсказывают, откуда он взялся;
+            // stdin второй раз не прочитать, поэтому только для файлов
+            let metadata = match &from {
+                Resource::File { .. } => read_metadata(from.clone(), from_format).ok().flatten(),
+                _ => None,
+            };
+            if let Some(metadata) = metadata {
+                println!("{}", tr("producer-metadata-header"));
+                for line in metadata.to_string().lines() {
+                    println!("  {}", line);
+                }
+            }
+            let report = report.map(|report| (report, report_format));
+            let res = converter_logic::logic::process_doctor_logic(from.clone(), from_format, report);
+            let found = res.as_ref().map_or(0, |findings| findings.len());
+            match &res {
+                Ok(findings) => {
+                    for finding in findings {
+                        println!("{}", finding);
+                    }
+                    println!("{}", result(format!("Ok({})", found)));
+                },
+                Err(e) => eprint!("{}", render_error(e, Some(&from), from_format, color_enabled())),
+            }
+            Some(entry.count("findings", found).outcome(&res))
+        },
+
+        Commands::ScreenCommand {
+            from,
+            from_format,
+            threshold,
+            structuring_window,
+            cycle_window,
+            min_score,
+            report,
+            report_format,
+        } => {
+            let entry = AuditEntry::new("screen-command").input(&from);
+            let entry = match &report {
+                Some(report) => entry.output(report),
+                None => entry,
+            };
+            let options = ScreeningOptions {
+                threshold,
+                structuring_window_ms: structuring_window,
+                cycle_window_ms: cycle_window,
+                ..ScreeningOptions::default()
+            };
+            let report = report.map(|report| (report, report_format));
+            let res = converter_logic::logic::process_screen_logic(from.clone(), from_format, &options, min_score, report, parse_cache.as_ref());
+            let flagged = res.as_ref().map_or(0, |scores| scores.len());
+            match &res {
+                Ok(scores) => {
+                    for user in scores {
+                        println!("{}", user);
+                    }
+                    println!("{}", result(format!("Ok({})", flagged)));
+                },
+                Err(e) => eprint!("{}", render_error(e, Some(&from), from_format, color_enabled())),
+            }
+            Some(entry.count("users", flagged).outcome(&res))
+        },
+
+        Commands::StatsCommand {
+            from,
+            from_format,
+            top_senders,
+            top_receivers,
+            largest,
+            series,
+            histogram,
+            output_format,
+            batch_size,
+        } => {
+            let entry = AuditEntry::new("stats-command").input(&from);
+            let options = StatsOptions {
+                top_senders: top_senders.unwrap_or(0),
+                top_receivers: top_receivers.unwrap_or(0),
+                largest: largest.unwrap_or(0),
+                series,
+                histogram,
+            };
+            let res = converter_logic::logic::process_stats_logic(
+                from.clone(),
+                from_format,
+                options,
+                fit_batch(batch_size, memory_limit),
+                parse_cache.as_ref(),
+            );
+            let records = res.as_ref().map_or(0, |report| report.records);
+            match &res {
+                Ok(report) => println!("{}", report.render(output_format)),
+                Err(e) => eprint!("{}", render_error(e, Some(&from), from_format, color_enabled())),
+            }
+            Some(entry.count("records", records).outcome(&res))
+        },
+
+        Commands::MigrateCommand { from, format, to } => {
+            let entry = AuditEntry::new("migrate-command").input(&from);
+            let entry = match &to {
+                Some(to) => entry.output(to),
+                None => entry,
+            };
+            let res = migrate_or_inspect(from.clone(), format, to, &WriteOptions::default());
+            let records = res.as_ref().map_or(0, |r| r.records);
+            match &res {
+                Ok(r) => eprintln!("{}", result(format!("{:?}", r))),
+                Err(e) => eprint!("{}", render_error(e, Some(&from), format, color_enabled())),
+            }
+            Some(entry.count("records", records).outcome(&res))
+        },
+
+        Commands::PluginConvertCommand { from, from_format, to, to_format, lenient } => {
+            let entry = AuditEntry::new("plugin-convert-command").input(&from).output(&to);
+            let res = converter_logic::logic::process_plugin_convert_logic(from, &from_format, to, &to_format, lenient);
+            eprintln!("{}", result(format!("{:?}", res)));
+            let entry = match &res {
+                Ok(r) => entry.count("records", r.records).count("skipped", r.skipped),
+                Err(_) => entry,
+            };
+            Some(entry.outcome(&res))
+        },
+
+        Commands::Diff {
+            base,
+            base_format,
+            current,
+            current_format,
+            to,
+        } => {
+            let entry = AuditEntry::new("diff").input(&base).input(&current).output(&to);
+            let res = converter_logic::logic::process_diff_logic(base, base_format, current, current_format, to);
+            eprintln!("{}", result(format!("{:?}", res)));
+            Some(delta_counts(entry, &res).outcome(&res))
+        },
+
+        Commands::Apply {
+            base,
+            base_format,
+            delta,
+            to,
             to_format,
         } => {
-            println!("Comparing:");
-            println!("  File1: {:?} (format: {:?})", from, from_format);
-            println!("  File2: {:?} (format: {:?})", to, to_format);
+            let entry = AuditEntry::new("apply").input(&base).input(&delta).output(&to);
+            let res = converter_logic::logic::process_apply_logic(base, base_format, delta, to, to_format);
+            eprintln!("{}", result(format!("{:?}", res)));
+            Some(delta_counts(entry, &res).outcome(&res))
+        },
+
+        Commands::Bundle { files, to } => {
+            let entry = files
+                .iter()
+                .fold(AuditEntry::new("bundle"), |entry, file| entry.input(&Resource::File { path: file.clone() }))
+                .output(&Resource::File { path: to.clone() });
+            let res = write_bundle(&files, &to);
+            let entry = bundle_counts(entry, &res).outcome(&res);
+            println!("{}", result(format!("{:?}", res)));
+            Some(entry)
+        },
+
+        Commands::Unbundle { from, to } => {
+            let res = unbundle(&from, to.as_deref());
+            let mut entry = AuditEntry::new("unbundle").input(&Resource::File { path: from.clone() });
+            if let (Some(to), Ok(entries)) = (&to, &res) {
+                for file in entries {
+                    entry = entry.output(&Resource::File { path: to.join(&file.name) });
+                }
+            }
+            let entry = bundle_counts(entry, &res).outcome(&res);
+            println!("{}", result(format!("{:?}", res)));
+            Some(entry)
+        },
 
-               let res  = converter_logic::logic::process_convert_logic(from, from_format, to, to_format);
-                  println!("result : {:?}", res)
-        }, 
+        Commands::Schema { kind, bin_profile, to } => {
+            let entry = AuditEntry::new("schema").output(&to);
+            let res = converter_logic::logic::process_schema_logic(kind, bin_profile, to.clone());
+            if let Err(e) = &res {
+                eprintln!("{}", result(format!("{:?}", e)));
+            }
+            Some(entry.outcome(&res))
+        },
+
+        Commands::Sort {
+            from,
+            from_format,
+            to,
+            to_format,
+            by,
+            dedupe,
+            run_size,
+        } => {
+            let entry = AuditEntry::new("sort").input(&from).output(&to);
+            let mut spill = SpillOptions { run_records: run_size, temp_dir };
+            if let Some(budget) = memory_limit {
+                spill.fit_memory(budget);
+            }
+            let options = SortOptions { key: by, dedupe, spill, ..SortOptions::default() };
+            let res = converter_logic::logic::process_sort_logic(from.clone(), from_format, to, to_format, &options);
+            let entry = match &res {
+                Ok(r) => {
+                    eprintln!("{}", result(format!("{:?}", r)));
+                    entry
+                        .count("records", r.records_written)
+                        .count("bytes", r.bytes_out)
+                        .count("duplicates", r.duplicates)
+                        .count("runs", r.runs)
+                },
+                Err(e) => {
+                    eprint!("{}", render_error(e, Some(&from), from_format, color_enabled()));
+                    entry
+                },
+            };
+            Some(entry.outcome(&res))
+        },
+
+        Commands::GenConformance { to, formats } => {
+            let res = converter_logic::logic::process_gen_conformance_logic(&to, &formats);
+            let mut entry = AuditEntry::new("gen-conformance");
+            if let Ok(entries) = &res {
+                for file in entries {
+                    entry = entry.output(&Resource::File { path: to.join(&file.path) });
+                }
+            }
+            let valid = res.as_ref().map_or(0, |entries| entries.iter().filter(|file| file.expect_valid).count());
+            let invalid = res.as_ref().map_or(0, |entries| entries.len() - valid);
+            println!("{}", result(format!("{:?}", res.as_ref().map(|entries| entries.len()))));
+            Some(entry.count("valid", valid).count("invalid", invalid).outcome(&res))
+        },
+
+        Commands::Bench {
+            records,
+            formats,
+            dir,
+            batch_size,
+        } => {
+            let _cooperation = cooperate();
+            let options = BenchOptions {
+                records,
+                formats,
+                batch_size: fit_batch(batch_size, memory_limit),
+                interrupt: Some(flag()),
+            };
+            println!("{}", BENCH_HEADER);
+            let dir = dir.or(temp_dir);
+            let res = converter_logic::logic::process_bench_logic(dir.as_deref(), &options, &mut |row| println!("{}", row));
+            if let Err(e) = &res {
+                eprintln!("{}", result(format!("{:?}", e)));
+            }
+            let pairs = res.as_ref().map_or(0, |results| results.len());
+            Some(AuditEntry::new("bench").count("records", records).count("pairs", pairs).outcome(&res))
+        },
 
         _ => {
-              println!("Error. Work only with ReadParseWriteCommand");
+              println!("{}", tr("converter-commands-only"));
+              failed = true;
+              None
         } 
+    };
+
+    let failed = failed || audit.as_ref().is_some_and(|entry| !entry.succeeded());
+    if let Some(entry) = &audit {
+        let sinks = RunSinks {
+            audit_log: cli.audit_log,
+            metrics_textfile: cli.metrics_textfile,
+            metrics_statsd: cli.metrics_statsd,
+            notify_webhook: cli.notify_webhook,
+            notify_retry,
+        };
+        for error in sinks.publish(entry, started.elapsed()) {
+            let (key, e) = match &error {
+                SinkErr::Metrics(e) => ("metrics-error", format!("{:?}", e)),
+                SinkErr::Notify(e) => ("notify-error", format!("{:?}", e)),
+                SinkErr::Audit(e) => ("audit-log-error", format!("{:?}", e)),
+            };
+            eprintln!("{}", tr_args(key, &[("error", e.into())]));
+        }
+    }
+
+    if let Some(code) = exit_code() {
+        eprintln!("{}", tr_args("interrupted-exit", &[("code", code.to_string().into())]));
+        return ExitCode::from(code as u8);
+    }
+    if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
+
+/// Показывает событие конвертации, как только оно случилось.
+fn render_job_event(event: JobEvent<'_>) {
+    match event {
+        JobEvent::Warning(warning) => eprintln!("{}", warning),
+        JobEvent::Unchanged(path) => eprintln!("{}", tr_args("input-unchanged", &[("path", path.display().to_string().into())])),
+        JobEvent::File(file) => {
+            for warning in &file.warnings {
+                eprintln!("{}: {}", file.from.display(), warning);
+            }
+            match &file.result {
+                Ok(r) => eprintln!("  {} -> {}: {}", file.from.display(), file.to.display(), converter_logic::logic::summary(r)),
+                Err(e) => eprintln!("  {} -> {}: {:?}", file.from.display(), file.to.display(), e),
+            }
+        }
+        JobEvent::PartitionOutput(output) => {
+            let args = [
+                ("path", output.path.display().to_string().into()),
+                ("records", output.records.into()),
+                ("bytes", output.bytes.into()),
+            ];
+            eprintln!("{}", tr_args("partition-output", &args));
+        }
+        JobEvent::Manifest { result: Ok(path), .. } => {
+            eprintln!("{}", tr_args("manifest-written", &[("path", path.display().to_string().into())]));
+        }
+        JobEvent::Manifest { output, result: Err(e) } => {
+            let args = [("path", output.display().to_string().into()), ("error", format!("{:?}", e).into())];
+            eprintln!("{}", tr_args("manifest-error", &args));
+        }
+        JobEvent::LedgerNotSaved(e) => eprintln!("{}", tr_args("ledger-error", &[("error", e.to_string().into())])),
+        JobEvent::JunitNotWritten(e) => eprintln!("{}", tr_args("report-error", &[("error", format!("{:?}", e).into())])),
     }
 }
 
-pub mod converter;
\ No newline at end of file
+/// Показывает итог `read-parse-write-command`.
+fn render_job(job: &ConvertJob, report: &JobReport) {
+    let rolled_back = || {
+        if job.options.transactional {
+            eprintln!("{}", tr("outputs-rolled-back"));
+        }
+    };
+    match &report.outcome {
+        JobOutcome::Rejected(rejection) => {
+            let key = match rejection {
+                JobRejection::InPlaceNeedsFile => "in-place-single-file",
+                JobRejection::TemplateNeedsSingleInput => "template-single-input",
+                JobRejection::CheckpointNeedsSingleInput => "checkpoint-single-input",
+                JobRejection::InputsNeedDirectory => "inputs-need-directory",
+            };
+            eprintln!("{}", tr(key));
+        }
+        JobOutcome::Failed(e) => eprintln!("{}", result(format!("{:?}", Err::<(), _>(e)))),
+        JobOutcome::Unchanged => {}
+        JobOutcome::Single { result: Ok(r), backup } => {
+            if let Some(backup) = backup {
+                eprintln!("{}", tr_args("in-place-backup", &[("path", backup.display().to_string().into())]));
+            }
+            eprintln!("{}", result(converter_logic::logic::summary(r)));
+        }
+        JobOutcome::Single { result: Err(ConvertErr::Interrupted { .. }), .. } if job.in_place => {
+            eprintln!("{}", tr("in-place-interrupted"));
+        }
+        JobOutcome::Single { result: Err(ConvertErr::Interrupted { partial }), .. } => {
+            let summary = converter_logic::logic::summary(partial);
+            eprintln!("{}", tr_args("convert-interrupted", &[("summary", summary.into())]));
+            if job.checkpoint.is_some() {
+                eprintln!("{}", tr("convert-interrupted-resume"));
+            }
+        }
+        JobOutcome::Partition(Ok(partitioned)) => eprintln!("{}", result(converter_logic::logic::summary(&partitioned.total))),
+        JobOutcome::Partition(Err(ConvertErr::Interrupted { partial })) => {
+            let summary = converter_logic::logic::summary(partial);
+            eprintln!("{}", tr_args("convert-interrupted", &[("summary", summary.into())]));
+            rolled_back();
+        }
+        JobOutcome::Partition(Err(e)) => {
+            eprint!("{}", render_error(e, Some(&job.from), job.from_format, color_enabled()));
+            rolled_back();
+        }
+        JobOutcome::Single { result: Err(e), .. } => {
+            eprint!("{}", render_error(e, Some(&job.from), job.from_format, color_enabled()));
+        }
+        JobOutcome::Many { result: res, .. } => {
+            if res.as_ref().is_ok_and(|many| many.rolled_back) {
+                eprintln!("{}", tr("outputs-rolled-back"));
+            }
+            eprintln!("{}", result(format!("{:?}", res.as_ref().map(|many| (many.success, many.skipped, many.warnings)))));
+        }
+    }
+}
+
+/// Программа и версия для манифеста и сведений о записавшей файл программе.
+const PRODUCER: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
+fn delta_counts<E>(entry: AuditEntry, res: &Result<DeltaSummary, E>) -> AuditEntry {
+    match res {
+        Ok(summary) => entry
+            .count("added", summary.added)
+            .count("updated", summary.updated)
+            .count("removed", summary.removed),
+        Err(_) => entry,
+    }
+}
+
+fn bundle_counts<E>(entry: AuditEntry, res: &Result<Vec<BundleEntry>, E>) -> AuditEntry {
+    match res {
+        Ok(entries) => entry
+            .count("files", entries.len())
+            .count("records", entries.iter().map(|e| e.records).sum()),
+        Err(_) => entry,
+    }
+}
+
+pub mod converter;
+
+/// Размер порции потоковой команды, уменьшенный до бюджета `--memory-limit`.
+fn fit_batch(batch_size: usize, memory_limit: Option<MemoryBudget>) -> usize {
+    memory_limit.map_or(batch_size, |budget| batch_size.min(budget.batch_size(1)))
+}
+
+/// Итоговая строка команды на языке сообщений.
+fn result(value: impl std::fmt::Display) -> String {
+    tr_args("result", &[("value", value.to_string().into())])
+}
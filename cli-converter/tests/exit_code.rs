@@ -0,0 +1,93 @@
+//! Код завершения: сценарии и CI узнают о неудачной конвертации без разбора вывода.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+const CSV: &str = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,1633036860000,SUCCESS,\"a\"\n\
+                   2,WITHDRAWAL,10,0,50,1633036920000,PENDING,\"b\"\n";
+
+fn convert(from: &Path, to: &Path, args: &[&str]) -> Output {
+    convert_formats(from, "yp-bank-csv", to, "yp-bank-text", args)
+}
+
+fn convert_formats(from: &Path, from_format: &str, to: &Path, to_format: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cli-converter"))
+        .args(["read-parse-write-command", "--from-format", from_format, "--to-format", to_format])
+        .arg(format!("--from=file:{}", from.display()))
+        .arg(format!("--to=file:{}", to.display()))
+        .args(args)
+        .env("LANG", "C")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_successful_conversion_exits_zero() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.csv");
+    fs::write(&input, CSV).unwrap();
+
+    let run = convert(&input, &dir.path().join("out.txt"), &[]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+}
+
+#[test]
+fn test_missing_input_exits_non_zero() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let run = convert(&dir.path().join("missing.csv"), &dir.path().join("out.txt"), &[]);
+    assert_eq!(run.status.code(), Some(1), "{}", String::from_utf8_lossy(&run.stderr));
+}
+
+#[test]
+fn test_warnings_as_errors_exits_non_zero() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.csv");
+    // Пробелы в конце строки — предупреждение, с --warnings-as-errors — ошибка
+    fs::write(&input, CSV.replacen("\"a\"", "\"a\"  ", 1)).unwrap();
+
+    let run = convert(&input, &dir.path().join("out.txt"), &[]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    let run = convert(&input, &dir.path().join("strict.txt"), &["--warnings-as-errors"]);
+    assert_eq!(run.status.code(), Some(1), "{}", String::from_utf8_lossy(&run.stdout));
+}
+
+#[test]
+fn test_verify_mismatch_exits_non_zero() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.csv");
+    fs::write(&input, CSV.replacen("\"a\"", "\"abcdef\"", 1)).unwrap();
+
+    let run = convert(&input, &dir.path().join("out.txt"), &["--verify"]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    // Обрезанное описание сверяется с так же обрезанным входом
+    let run = convert(&input, &dir.path().join("short.txt"), &["--verify", "--max-description-len", "3"]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    // Результат на stdout не перечитать
+    let run = Command::new(env!("CARGO_BIN_EXE_cli-converter"))
+        .args(["read-parse-write-command", "--from-format", "yp-bank-csv", "--to-format", "yp-bank-text", "--to=console", "--verify"])
+        .arg(format!("--from=file:{}", input.display()))
+        .env("LANG", "C")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&run.stderr);
+    assert!(stderr.contains("verification failed"), "{}", stderr);
+    assert_eq!(run.status.code(), Some(1));
+}
+
+#[test]
+fn test_no_lossy_abort_exits_non_zero() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.txt");
+    let text = "TX_ID: A-1\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 10\nAMOUNT: 100\n\
+                TIMESTAMP: 1633036860000\nSTATUS: SUCCESS\nDESCRIPTION: \"a\"\n";
+    fs::write(&input, text).unwrap();
+
+    // Строковый TX_ID не записать в бинарный формат без заголовка
+    let run = convert_formats(&input, "yp-bank-text", &dir.path().join("out.bin"), "yp-bank-bin", &["--no-lossy"]);
+    let stderr = String::from_utf8_lossy(&run.stderr);
+    assert!(stderr.contains("lossy conversion"), "{}", stderr);
+    assert_eq!(run.status.code(), Some(1));
+}
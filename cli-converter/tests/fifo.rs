@@ -0,0 +1,124 @@
+//! Конвертация через каналы (именованные и stdin/stdout): CLI стоит в конвейере между производителем и потребителем.
+#![cfg(unix)]
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+
+const CSV: &str = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,1633036860000,SUCCESS,\"a\"\n\
+                   2,WITHDRAWAL,10,0,50,1633036920000,PENDING,\"b\"\n";
+
+fn mkfifo(path: &Path) {
+    let status = Command::new("mkfifo").arg(path).status().unwrap();
+    assert!(status.success());
+}
+
+fn convert(from: &Path, from_format: &str, to: &Path, to_format: &str) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_cli-converter"))
+        .args(["read-parse-write-command", "--from-format", from_format, "--to-format", to_format])
+        .arg(format!("--from=file:{}", from.display()))
+        .arg(format!("--to=file:{}", to.display()))
+        .env("LANG", "C")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_convert_between_pipes() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.fifo");
+    let output = dir.path().join("out.fifo");
+    mkfifo(&input);
+    mkfifo(&output);
+
+    // Производитель пишет по байту, чтобы чтение получало неполные куски (и BOM по частям)
+    let producer = {
+        let input = input.clone();
+        thread::spawn(move || {
+            let mut pipe = fs::OpenOptions::new().write(true).open(input).unwrap();
+            for byte in "\u{feff}".bytes().chain(CSV.bytes()) {
+                pipe.write_all(&[byte]).unwrap();
+            }
+        })
+    };
+    let consumer = {
+        let output = output.clone();
+        thread::spawn(move || {
+            let mut text = String::new();
+            fs::File::open(output).unwrap().read_to_string(&mut text).unwrap();
+            text
+        })
+    };
+
+    let run = convert(&input, "yp-bank-csv", &output, "yp-bank-text");
+    producer.join().unwrap();
+    let text = consumer.join().unwrap();
+
+    let stderr = String::from_utf8_lossy(&run.stderr);
+    assert!(stderr.contains("wrote 2 records"), "{}", stderr);
+    assert!(run.stdout.is_empty(), "{}", String::from_utf8_lossy(&run.stdout));
+    assert!(text.starts_with("TX_ID: 1\n"), "{}", text);
+    assert!(text.trim_end().ends_with("DESCRIPTION: \"b\""), "{}", text);
+}
+
+#[test]
+fn test_truncated_record_from_pipe_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("full.bin");
+    let csv = dir.path().join("in.csv");
+    fs::write(&csv, CSV).unwrap();
+    assert!(convert(&csv, "yp-bank-csv", &bin, "yp-bank-bin").status.success());
+
+    let input = dir.path().join("in.fifo");
+    mkfifo(&input);
+    let mut bytes = fs::read(&bin).unwrap();
+    // Производитель закрыл канал посреди второй записи
+    bytes.truncate(bytes.len() - 5);
+    let producer = {
+        let input = input.clone();
+        thread::spawn(move || fs::write(input, bytes).unwrap())
+    };
+
+    let run = convert(&input, "yp-bank-bin", &dir.path().join("out.csv"), "yp-bank-csv");
+    producer.join().unwrap();
+
+    let stderr = String::from_utf8_lossy(&run.stderr);
+    assert!(stderr.lines().any(|line| line.starts_with("error: ")), "{}", stderr);
+    assert_eq!(run.status.code(), Some(1));
+}
+
+/// Конвертирует `input` со стандартного ввода на стандартный вывод.
+fn pipe(input: &[u8], from_format: &str, to_format: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cli-converter"))
+        .args(["read-parse-write-command", "--from=console", "--to=console", "--from-format", from_format, "--to-format", to_format])
+        .env("LANG", "C")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_vec();
+    let producer = thread::spawn(move || stdin.write_all(&input).unwrap());
+    let output = child.wait_with_output().unwrap();
+    producer.join().unwrap();
+    output
+}
+
+#[test]
+fn test_stdout_carries_only_records() {
+    // Бинарный поток на stdout начинается с записи, а не с сообщений о ходе конвертации
+    let bin = pipe(CSV.as_bytes(), "yp-bank-csv", "yp-bank-bin");
+    assert!(bin.status.success(), "{}", String::from_utf8_lossy(&bin.stderr));
+    assert!(bin.stdout.starts_with(b"YPB"), "{:?}", &bin.stdout[..bin.stdout.len().min(16)]);
+    assert!(String::from_utf8_lossy(&bin.stderr).contains("wrote 2 records"));
+
+    let text = pipe(&bin.stdout, "yp-bank-bin", "yp-bank-text");
+    assert!(text.status.success(), "{}", String::from_utf8_lossy(&text.stderr));
+    let text = String::from_utf8(text.stdout).unwrap();
+    assert!(text.starts_with("TX_ID: 1\n"), "{}", text);
+    assert!(text.trim_end().ends_with("DESCRIPTION: \"b\""), "{}", text);
+}
@@ -0,0 +1,38 @@
+//! Единицы TIMESTAMP: без `--timestamp-unit` метки переносятся без изменений.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// Метка меньше 10^11: `auto` приняла бы её за секунды.
+const CSV: &str = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,5000,SUCCESS,\"a\"\n";
+
+fn convert(from: &Path, to: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cli-converter"))
+        .args(["read-parse-write-command", "--from-format", "yp-bank-csv", "--to-format", "yp-bank-csv"])
+        .arg(format!("--from=file:{}", from.display()))
+        .arg(format!("--to=file:{}", to.display()))
+        .args(args)
+        .env("LANG", "C")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_small_millisecond_timestamp_round_trips_unchanged() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.csv");
+    fs::write(&input, CSV).unwrap();
+
+    let output = dir.path().join("out.csv");
+    let run = convert(&input, &output, &[]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    assert!(fs::read_to_string(&output).unwrap().contains(",100,5000,SUCCESS,"));
+
+    // auto включается явно и считает такую метку секундами
+    let scaled = dir.path().join("auto.csv");
+    let run = convert(&input, &scaled, &["--timestamp-unit", "auto"]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    assert!(fs::read_to_string(&scaled).unwrap().contains(",100,5000000,SUCCESS,"));
+}
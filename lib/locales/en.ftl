@@ -0,0 +1,53 @@
+# Сообщения CLI на английском; ключи должны совпадать с ru.ftl
+
+comparing = Comparing:
+convert-from = {"  "}File1: { $resource } (format: { $format })
+convert-to = {"  "}File2: { $resource } (format: { $format })
+compare-input = {"  "}Input: { $resource } (format: { $format })
+result = result : { $value }
+error = error
+fix = fix
+
+convert-summary-read = read { $records } records
+convert-summary-bytes-in = { $bytes } bytes
+convert-summary-written = wrote { $records } records ({ $bytes } bytes), skipped { $skipped }, { $warnings } warnings in { $seconds }s
+convert-interrupted = interrupted, output flushed: { $summary }
+convert-interrupted-resume = {"  "}Run the same command with --resume to continue
+input-unchanged = {"  "}{ $path }: unchanged since the last conversion, skipped
+in-place-backup = {"  "}Original saved as { $path }
+in-place-interrupted = interrupted, the file was left unchanged
+preview-header = Preview: { $count } randomly sampled records
+manifest-written = {"  "}Manifest: { $path }
+manifest-error = {"  "}{ $path }: manifest not written: { $error }
+partition-output = {"  "}{ $path }: { $records } records ({ $bytes } bytes)
+producer-metadata-header = Written by:
+outputs-rolled-back = Outputs were not replaced: not every output was written (--transactional)
+
+compare-summary = equal: { $equal }, first: { $first } records, second: { $second } records, differences: { $differing }
+schema-diff-summary = { $drifts } drift(s) between { $first } and { $second } records
+compare-key-mismatch = {"  "}TX_ID { $id }: { $first } in first, { $second } in second
+compare-nway-key = {"  "}TX_ID { $id }: agreeing inputs { $groups }, missing in { $missing }
+compare-nway-summary = equal: { $equal }, records per input: { $records }, { $disagreeing } keys disagree
+compare-quick-identical = {"  "}Files are byte-identical (sha256 { $hash }), full compare skipped
+compare-quick-differ = {"  "}Checksums differ, differing byte ranges: first { $first }, second { $second }; running full compare
+
+checkpoint-single-input = Error. --checkpoint and --resume work with a single input file
+inputs-need-directory = Error. Several input files need a directory in --to (file:<dir>)
+in-place-single-file = Error. --in-place needs a single regular file in --from
+template-single-input = Error. A templated --to works with a single input and without --checkpoint, --resume, --verify and --skip-processed
+compare-inputs-need-directories = Error. To compare files by name both inputs must be directories or glob patterns (file:<dir>)
+compare-also-format-count = Error. Each --also-from needs its own --also-format
+compare-nway-files-only = Error. Comparing three or more inputs works only with files, not directories or glob patterns
+converter-commands-only = Error. Work only with ReadParseWriteCommand, MigrateCommand, Diff, Apply, Bundle, Unbundle, Get and DoctorCommand
+comparer-commands-only = Error. Work only with CompareCommand and SchemaDiff
+stdin-is-terminal = Error. The command reads stdin (console), but stdin is a terminal: pipe the data in (cat records.csv | cli ...) or pass file:<path>
+currency-error = Error. Currency conversion: { $error }
+plugin-error = Error. Format plugin: { $error }
+transform-script-failures = Error. The transform script failed on { $count } records, they were dropped; first error: { $error }
+metrics-error = metrics error: { $error }
+report-error = report error: { $error }
+audit-log-error = audit log error: { $error }
+ledger-error = processed ledger error: { $error }
+notify-error = webhook notification error: { $error }
+interrupted-exit = Interrupted by signal, exit code { $code }
+signal-handlers-error = signal handlers error: { $error }
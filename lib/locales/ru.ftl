@@ -0,0 +1,61 @@
+# Сообщения CLI на русском; ключи должны совпадать с en.ftl
+
+comparing = Сравнение:
+convert-from = {"  "}Файл1: { $resource } (формат: { $format })
+convert-to = {"  "}Файл2: { $resource } (формат: { $format })
+compare-input = {"  "}Вход: { $resource } (формат: { $format })
+result = результат : { $value }
+error = ошибка
+fix = исправление
+
+convert-summary-read = прочитано { $records } { $records ->
+    [one] запись
+    [few] записи
+   *[many] записей
+}
+convert-summary-bytes-in = { $bytes } байт
+convert-summary-written = записано { $records } { $records ->
+    [one] запись
+    [few] записи
+   *[many] записей
+} ({ $bytes } байт), пропущено { $skipped }, предупреждений { $warnings } за { $seconds } с
+convert-interrupted = прервано, вывод сброшен: { $summary }
+convert-interrupted-resume = {"  "}Чтобы продолжить, запустите ту же команду с --resume
+input-unchanged = {"  "}{ $path }: не изменился с прошлой конвертации, пропущен
+in-place-backup = {"  "}Исходный файл сохранён как { $path }
+in-place-interrupted = прервано, файл не изменён
+preview-header = Предпросмотр: { $count } случайных записей
+manifest-written = {"  "}Манифест: { $path }
+manifest-error = {"  "}{ $path }: манифест не записан: { $error }
+producer-metadata-header = Кем записан:
+outputs-rolled-back = Выходные файлы не заменены: записаны не все (--transactional)
+partition-output = {"  "}{ $path }: записей { $records } ({ $bytes } байт)
+
+compare-summary = равны: { $equal }, записей в первом: { $first }, во втором: { $second }, различий: { $differing }
+schema-diff-summary = расхождений вида: { $drifts }, записей в первом: { $first }, во втором: { $second }
+compare-key-mismatch = {"  "}TX_ID { $id }: в первом { $first }, во втором { $second }
+compare-nway-key = {"  "}TX_ID { $id }: согласные входы { $groups }, нет во входах { $missing }
+compare-nway-summary = равны: { $equal }, записей во входах: { $records }, расходятся TX_ID: { $disagreeing }
+compare-quick-identical = {"  "}Файлы совпадают побайтно (sha256 { $hash }), полное сравнение пропущено
+compare-quick-differ = {"  "}Контрольные суммы различаются, различающиеся участки в байтах: в первом { $first }, во втором { $second }; выполняется полное сравнение
+
+checkpoint-single-input = Ошибка. --checkpoint и --resume работают только с одним входным файлом
+inputs-need-directory = Ошибка. Для нескольких входных файлов в --to нужен каталог (file:<dir>)
+in-place-single-file = Ошибка. Для --in-place в --from нужен один обычный файл
+template-single-input = Ошибка. Шаблон в --to работает с одним входом и без --checkpoint, --resume, --verify и --skip-processed
+compare-inputs-need-directories = Ошибка. Для сравнения файлов по именам оба входа должны быть каталогами или glob-шаблонами (file:<dir>)
+compare-also-format-count = Ошибка. Для каждого --also-from нужен свой --also-format
+compare-nway-files-only = Ошибка. Сравнение трёх и более входов работает только с файлами, без каталогов и glob-шаблонов
+converter-commands-only = Ошибка. Поддерживаются только ReadParseWriteCommand, MigrateCommand, Diff, Apply, Bundle, Unbundle, Get и DoctorCommand
+comparer-commands-only = Ошибка. Поддерживаются только CompareCommand и SchemaDiff
+stdin-is-terminal = Ошибка. Команда читает stdin (console), но stdin — терминал: передайте данные конвейером (cat records.csv | cli ...) или укажите file:<path>
+currency-error = Ошибка. Пересчёт валют: { $error }
+plugin-error = Ошибка. Модуль формата: { $error }
+transform-script-failures = Ошибка. Сценарий преобразования упал на записях ({ $count }), они отброшены; первая ошибка: { $error }
+metrics-error = ошибка метрик: { $error }
+report-error = ошибка отчёта: { $error }
+audit-log-error = ошибка журнала аудита: { $error }
+ledger-error = ошибка файла состояния: { $error }
+notify-error = ошибка уведомления на webhook: { $error }
+interrupted-exit = Прервано сигналом, код завершения { $code }
+signal-handlers-error = ошибка установки обработчиков сигналов: { $error }
@@ -0,0 +1,225 @@
+//! Журнал аудита операций CLI: одна JSON-строка на запуск, файл только дописывается.
+//!
+//! ```json
+//! {"timestamp":1700000000000,"command":"read-parse-write-command",
+//!  "inputs":[{"resource":"file:in.csv","sha256":"…"}],
+//!  "outputs":[{"resource":"file:out.bin","sha256":"…"}],
+//!  "counts":{"records":1000,"skipped":0},"outcome":"ok"}
+//! ```
+//!
+//! `timestamp` — Unix-время записи в миллисекундах. SHA-256 считается для файлов
+//! (в том числе файлов в zip-архиве) в момент записи в журнал; для консоли и памяти — `null`.
+//! При ошибке `outcome` равен `"error"`, а текст ошибки с её причинами через `: ` лежит в поле `error`.
+//! Ошибки отдельных входов при обработке нескольких файлов перечислены в `failures`
+//! (`[{"resource":"file:bad.csv","error":"…"}]`); без таких ошибок поля нет.
+
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+
+use crate::model::errors::{describe_error, CommonErr, IoErr};
+use crate::model::resource::Resource;
+use crate::parser::io::reader::open;
+
+/// Запись журнала аудита об одной операции.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    command: String,
+    inputs: Vec<Resource>,
+    outputs: Vec<Resource>,
+    counts: Vec<(&'static str, u64)>,
+    /// Входы, которые не удалось обработать, с описанием ошибки
+    failures: Vec<(Resource, String)>,
+    /// `None` — операция завершилась успешно
+    error: Option<String>,
+}
+
+impl AuditEntry {
+    /// Новая запись об операции `command` (имя подкоманды CLI).
+    pub fn new(command: &str) -> Self {
+        AuditEntry {
+            command: command.to_string(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            counts: Vec::new(),
+            failures: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Добавляет прочитанный ресурс.
+    pub fn input(mut self, resource: &Resource) -> Self {
+        self.inputs.push(resource.clone());
+        self
+    }
+
+    /// Добавляет записанный ресурс.
+    pub fn output(mut self, resource: &Resource) -> Self {
+        self.outputs.push(resource.clone());
+        self
+    }
+
+    /// Добавляет счётчик (число записей, пропусков и т.п.).
+    pub fn count(mut self, name: &'static str, value: usize) -> Self {
+        self.counts.push((name, value as u64));
+        self
+    }
+
+    /// Добавляет вход, который не удалось обработать; ошибка записывается с цепочкой причин.
+    pub fn failure<E: Error>(mut self, resource: &Resource, error: &E) -> Self {
+        self.failures.push((resource.clone(), describe_error(error)));
+        self
+    }
+
+    /// Запоминает итог операции; ошибка записывается с цепочкой причин.
+    pub fn outcome<T, E: Error>(mut self, result: &Result<T, E>) -> Self {
+        self.error = result.as_ref().err().map(|e| describe_error(e));
+        self
+    }
+
+    /// Имя операции.
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Счётчики в порядке добавления.
+    pub fn counts(&self) -> &[(&'static str, u64)] {
+        &self.counts
+    }
+
+    /// Операция завершилась успешно.
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Запись в виде JSON-объекта журнала; `timestamp` — Unix-время в миллисекундах.
+    pub(crate) fn to_json(&self, timestamp: u64) -> serde_json::Value {
+        let resources = |resources: &[Resource]| -> Vec<serde_json::Value> {
+            resources
+                .iter()
+                .map(|resource| {
+                    serde_json::json!({
+                        "resource": resource.to_string(),
+                        "sha256": checksum(resource),
+                    })
+                })
+                .collect()
+        };
+        let counts: serde_json::Map<_, _> = self
+            .counts
+            .iter()
+            .map(|(name, value)| (name.to_string(), (*value).into()))
+            .collect();
+
+        let mut entry = serde_json::json!({
+            "timestamp": timestamp,
+            "command": self.command,
+            "inputs": resources(&self.inputs),
+            "outputs": resources(&self.outputs),
+            "counts": counts,
+            "outcome": if self.error.is_none() { "ok" } else { "error" },
+        });
+        if let Some(error) = &self.error {
+            entry["error"] = error.clone().into();
+        }
+        if !self.failures.is_empty() {
+            let failures: Vec<serde_json::Value> = self
+                .failures
+                .iter()
+                .map(|(resource, error)| serde_json::json!({ "resource": resource.to_string(), "error": error }))
+                .collect();
+            entry["failures"] = failures.into();
+        }
+        entry
+    }
+}
+
+/// Дописывает запись в конец журнала `log`, создавая его при необходимости.
+///
+/// Запись пишется одним вызовом `write`, поэтому строки параллельных запусков не перемешиваются.
+pub fn append_audit(log: &Path, entry: &AuditEntry) -> Result<(), CommonErr> {
+    let mut line = entry.to_json(now_ms()).to_string();
+    line.push('\n');
+
+    let output_err = |e| IoErr::output(e, Some(log.to_path_buf()));
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log)
+        .map_err(output_err)?;
+    file.write_all(line.as_bytes()).map_err(output_err)?;
+    Ok(())
+}
+
+/// Текущее Unix-время в миллисекундах.
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+/// SHA-256 содержимого файла; `None` для консоли и памяти или если файл не читается.
+fn checksum(resource: &Resource) -> Option<String> {
+    if matches!(resource, Resource::Console | Resource::Memory { .. }) {
+        return None;
+    }
+    let mut reader = open(resource.clone()).ok()?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut reader, &mut hasher).ok()?;
+    Some(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_append_audit_writes_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.csv");
+        fs::write(&input, "abc").unwrap();
+        let log = dir.path().join("audit.jsonl");
+
+        let ok = AuditEntry::new("read-parse-write-command")
+            .input(&Resource::File { path: input.clone() })
+            .output(&Resource::Console)
+            .count("records", 3)
+            .outcome(&Ok::<(), std::io::Error>(()));
+        append_audit(&log, &ok).unwrap();
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        let failed = AuditEntry::new("get")
+            .failure(&Resource::File { path: "bad.csv".into() }, &CommonErr::from(IoErr::input(missing, Some("bad.csv".into()))))
+            .outcome(&Err::<(), _>(std::io::Error::other("not found")));
+        append_audit(&log, &failed).unwrap();
+
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&log)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["inputs"][0]["resource"], format!("file:{}", input.display()));
+        // sha256("abc")
+        assert_eq!(
+            lines[0]["inputs"][0]["sha256"],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(lines[0]["outputs"][0]["sha256"].is_null());
+        assert_eq!(lines[0]["counts"]["records"], 3);
+        assert_eq!(lines[0]["outcome"], "ok");
+        assert_eq!(lines[1]["outcome"], "error");
+        assert_eq!(lines[1]["error"], "not found");
+        assert!(lines[0].get("failures").is_none());
+        assert_eq!(lines[1]["failures"][0]["resource"], "file:bad.csv");
+        // Текст ошибки с причинами, а не отладочный вид
+        assert_eq!(
+            lines[1]["failures"][0]["error"],
+            "io error: io -> input error (bad.csv): entity not found: no such file"
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! Эталонный замер скорости: синтетический файл одинаковый на любой машине (генератор
+//! с фиксированным зерном), поэтому итоги можно сравнивать между машинами и версиями.
+//!
+//! Для каждого исходного формата файл записывается один раз и конвертируется во все
+//! форматы замера, включая тот же; время записи исходного файла не учитывается.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::convert::{convert, ConvertErr, ConvertReport};
+use crate::model::data::{Format, Status, TxData, TxType};
+use crate::model::errors::{CommonErr, IoErr};
+use crate::model::resource::Resource;
+use crate::model::tx_id::TxId;
+use crate::parser::{write_batches, ConvertOptions, WriteOptions};
+
+/// Заголовок таблицы, строки которой выводит `Display` у [`BenchResult`].
+pub const BENCH_HEADER: &str = "FROM          TO               RECORDS   SECONDS     RECORDS/S      MB/S";
+
+/// Настройки замера.
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// Сколько записей в синтетическом файле
+    pub records: usize,
+    /// Форматы замера: конвертируется каждая упорядоченная пара
+    pub formats: Vec<Format>,
+    /// Сколько записей читается и записывается за один шаг конвертации
+    pub batch_size: usize,
+    /// Флаг прерывания: замер останавливается после текущей порции и удаляет свои файлы
+    pub interrupt: Option<Arc<AtomicBool>>,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        BenchOptions {
+            records: 1_000_000,
+            formats: vec![Format::YpBankCsv, Format::YpBankText, Format::YpBankBin],
+            batch_size: ConvertOptions::default().batch_size,
+            interrupt: None,
+        }
+    }
+}
+
+/// Итог конвертации одной пары форматов.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchResult {
+    pub from: Format,
+    pub to: Format,
+    pub records: usize,
+    /// Размер исходного файла в байтах
+    pub bytes_in: u64,
+    pub bytes_out: usize,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    /// Записей в секунду.
+    pub fn records_per_sec(&self) -> f64 {
+        self.records as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Мегабайт (10^6 байт) исходного файла в секунду.
+    pub fn mb_per_sec(&self) -> f64 {
+        self.bytes_in as f64 / 1_000_000.0 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<13} {:<13} {:>10} {:>9.3} {:>13.0} {:>9.1}",
+            self.from.name(),
+            self.to.name(),
+            self.records,
+            self.elapsed.as_secs_f64(),
+            self.records_per_sec(),
+            self.mb_per_sec()
+        )
+    }
+}
+
+/// Таблица итогов замера с заголовком [`BENCH_HEADER`].
+pub fn render_bench(results: &[BenchResult]) -> String {
+    let mut out = format!("{}\n", BENCH_HEADER);
+    for result in results {
+        out.push_str(&format!("{}\n", result));
+    }
+    out
+}
+
+/// Запись номер `index` синтетического файла; одна и та же при любом запуске.
+pub fn synthetic_record(index: u64) -> TxData {
+    // splitmix64: зерно — номер записи, поэтому запись не зависит от размера файла
+    let mut state = index.wrapping_add(0x5EED);
+    let mut next = || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+
+    let tx_type = TxType::ALL[(next() % 3) as usize];
+    let user = |n: u64| 1 + n % 100_000;
+    let (from_user_id, to_user_id) = match tx_type {
+        TxType::Deposit => (0, user(next())),
+        TxType::Transfer => (user(next()), user(next())),
+        TxType::Withdrawal => (user(next()), 0),
+    };
+    // Успешных операций больше всего, как в настоящих выгрузках
+    let status = match next() % 10 {
+        0 => Status::Failure,
+        1 => Status::Pending,
+        _ => Status::Success,
+    };
+    TxData {
+        tx_id: TxId::U64(1_000_000_000 + index),
+        tx_type,
+        from_user_id,
+        to_user_id,
+        amount: 1 + (next() % 10_000_000) as i64,
+        timestamp: 1_700_000_000_000 + index * 1_000,
+        status,
+        description: format!("Synthetic {} #{}", tx_type.name().to_lowercase(), index).into(),
+        format: Format::YpBankCsv,
+    }
+}
+
+/// Замеряет конвертацию синтетического файла между всеми парами форматов `options.formats`.
+///
+/// Файлы создаются в каталоге `dir` и удаляются после замера; `on_result` получает итог
+/// каждой пары сразу после её конвертации. После прерывания (`options.interrupt`) файлы
+/// тоже удаляются, а возвращается `ConvertErr::Interrupted`.
+///
+/// # Errors
+/// `ConvertErr`, если файл не удалось записать или конвертировать.
+pub fn run_bench(dir: &Path, options: &BenchOptions, on_result: &mut dyn FnMut(&BenchResult)) -> Result<Vec<BenchResult>, ConvertErr> {
+    let convert_options = ConvertOptions {
+        batch_size: options.batch_size,
+        interrupt: options.interrupt.clone(),
+        ..ConvertOptions::default()
+    };
+    let mut results = Vec::new();
+    for &from in &options.formats {
+        let source = dir.join(format!("bench-source.{}", from.extension()));
+        let written = write_synthetic(&source, from, options);
+        if written.is_err() {
+            let _ = fs::remove_file(&source);
+        }
+        written?;
+        for &to in &options.formats {
+            let target = dir.join(format!("bench-target.{}", to.extension()));
+            let measured = convert(
+                Resource::File { path: source.clone() },
+                from,
+                Resource::File { path: target.clone() },
+                to,
+                &convert_options,
+                None,
+                &mut |_| {},
+            );
+            let _ = fs::remove_file(&target);
+            let report = match measured {
+                Ok(report) => report,
+                Err(e) => {
+                    let _ = fs::remove_file(&source);
+                    return Err(e);
+                },
+            };
+            let result = BenchResult {
+                from,
+                to,
+                records: report.records_written,
+                bytes_in: report.bytes_in.unwrap_or_default(),
+                bytes_out: report.bytes_out,
+                elapsed: report.elapsed,
+            };
+            on_result(&result);
+            results.push(result);
+        }
+        fs::remove_file(&source).map_err(|e| CommonErr::from(IoErr::output(e, Some(source.clone()))))?;
+    }
+    Ok(results)
+}
+
+fn write_synthetic(path: &Path, format: Format, options: &BenchOptions) -> Result<(), ConvertErr> {
+    let started = Instant::now();
+    let mut writer = write_batches(Resource::File { path: path.to_path_buf() }, format, &WriteOptions::default())?;
+    let batch_size = options.batch_size.max(1) as u64;
+    let total = options.records as u64;
+    let mut start = 0;
+    while start < total {
+        if options.interrupt.as_ref().is_some_and(|flag| flag.load(Ordering::SeqCst)) {
+            let partial = ConvertReport {
+                records_read: start as usize,
+                records_written: start as usize,
+                skipped: 0,
+                warnings: 0,
+                bytes_in: None,
+                bytes_out: writer.finish()?,
+                elapsed: started.elapsed(),
+                issues: Vec::new(),
+            };
+            return Err(ConvertErr::Interrupted { partial: Box::new(partial) });
+        }
+        let end = (start + batch_size).min(total);
+        let batch: Vec<TxData> = (start..end).map(synthetic_record).collect();
+        writer.write_batch(&batch, &[])?;
+        start = end;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "csv", feature = "text", feature = "bin"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthetic_records_are_stable() {
+        assert_eq!(synthetic_record(42), synthetic_record(42));
+        assert_ne!(synthetic_record(42), synthetic_record(43));
+        let tx = synthetic_record(7);
+        assert_eq!(tx.tx_id, TxId::U64(1_000_000_007));
+        assert!((1..=10_000_000).contains(&tx.amount));
+    }
+
+    #[test]
+    fn test_run_bench_converts_every_pair() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = BenchOptions { records: 250, batch_size: 100, ..BenchOptions::default() };
+        let mut seen = 0;
+        let results = run_bench(dir.path(), &options, &mut |_| seen += 1).unwrap();
+
+        assert_eq!((results.len(), seen), (9, 9));
+        assert!(results.iter().all(|result| result.records == 250 && result.bytes_in > 0));
+        assert!(results.iter().any(|result| result.from == Format::YpBankText && result.to == Format::YpBankBin));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let table = render_bench(&results);
+        assert_eq!(table.lines().count(), 10);
+        assert!(table.lines().nth(1).unwrap().starts_with("yp-bank-csv   yp-bank-csv          250"));
+    }
+
+    #[test]
+    fn test_interrupted_bench_removes_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let interrupt = Some(Arc::new(AtomicBool::new(true)));
+        let options = BenchOptions { records: 250, batch_size: 100, interrupt, ..BenchOptions::default() };
+        let err = run_bench(dir.path(), &options, &mut |_| {});
+
+        assert!(matches!(err, Err(ConvertErr::Interrupted { .. })));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
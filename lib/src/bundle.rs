@@ -0,0 +1,391 @@
+//! Архивы `.tar` и `.zip` с несколькими файлами транзакций и манифестом.
+//!
+//! Манифест лежит в архиве под именем [`MANIFEST_NAME`] и описывает каждый файл:
+//!
+//! ```json
+//! {"version": 1, "files": [
+//!   {"name": "2024-01.csv", "format": "yp-bank-csv", "sha256": "…", "records": 1000}
+//! ]}
+//! ```
+//!
+//! При чтении архив проверяется целиком: в нём должны быть ровно файлы из манифеста,
+//! с совпадающими SHA-256 и числом записей.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::model::data::{Format, TxData};
+use crate::model::errors::{CommonErr, IoErr, ParserErr};
+use crate::model::resource::Resource;
+use crate::parser::io::reader::read_with;
+use crate::parser::options::ParseOptions;
+
+/// Имя файла манифеста внутри архива.
+pub const MANIFEST_NAME: &str = "manifest.json";
+/// Версия манифеста, которую пишет и понимает эта сборка.
+const MANIFEST_VERSION: u64 = 1;
+
+/// Вид архива; определяется по расширению пути.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundleKind {
+    Tar,
+    Zip,
+}
+
+impl BundleKind {
+    /// Вид архива по расширению `.tar` или `.zip`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "tar" => Some(BundleKind::Tar),
+            "zip" => Some(BundleKind::Zip),
+            _ => None,
+        }
+    }
+}
+
+/// Описание одного файла архива в манифесте.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleEntry {
+    /// Имя файла внутри архива
+    pub name: String,
+    pub format: Format,
+    /// SHA-256 содержимого в шестнадцатеричном виде
+    pub sha256: String,
+    /// Сколько записей в файле
+    pub records: usize,
+}
+
+/// Файл архива вместе с разобранными записями.
+#[derive(Debug, Clone)]
+pub struct BundleFile {
+    pub entry: BundleEntry,
+    pub records: Vec<TxData>,
+}
+
+/// Собирает архив `to` из файлов `files` и пишет в него манифест.
+///
+/// Формат каждого файла определяется по расширению (см. [`Format::extension`]); файл
+/// разбирается целиком, чтобы посчитать записи, и кладётся в архив без изменений.
+///
+/// # Возвращает
+/// * `Ok(Vec<BundleEntry>)` — записанный манифест
+/// * `Err(CommonErr)` — неизвестное расширение, два файла с одним именем, ошибка разбора или записи
+pub fn write_bundle(files: &[PathBuf], to: &Path) -> Result<Vec<BundleEntry>, CommonErr> {
+    let kind = bundle_kind(to)?;
+    let mut members = Vec::new();
+    let mut entries: Vec<BundleEntry> = Vec::new();
+    for path in files {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let format = path
+            .extension()
+            .and_then(|ext| Format::from_extension(&ext.to_string_lossy()))
+            .ok_or_else(|| member_err(&name, "unknown file extension"))?;
+        if name == MANIFEST_NAME || entries.iter().any(|entry| entry.name == name) {
+            return Err(member_err(&name, "duplicate file name").into());
+        }
+
+        let data = fs::read(path).map_err(|e| IoErr::input(e, Some(path.clone())))?;
+        entries.push(BundleEntry {
+            records: count_records(&data, format)?,
+            sha256: sha256(&data),
+            name: name.clone(),
+            format,
+        });
+        members.push((name, data));
+    }
+    members.push((MANIFEST_NAME.to_string(), render_manifest(&entries).into_bytes()));
+
+    let output_err = |e: std::io::Error| IoErr::output(e, Some(to.to_path_buf()));
+    let file = BufWriter::new(File::create(to).map_err(output_err)?);
+    match kind {
+        BundleKind::Tar => {
+            let mut builder = tar::Builder::new(file);
+            for (name, data) in &members {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(data.len() as u64);
+                header.set_mode(0o644);
+                header.set_cksum();
+                builder.append_data(&mut header, name, data.as_slice()).map_err(output_err)?;
+            }
+            builder.into_inner().and_then(|mut file| file.flush()).map_err(output_err)?;
+        }
+        BundleKind::Zip => {
+            let mut zip = ZipWriter::new(file);
+            let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+            for (name, data) in &members {
+                zip.start_file(name.as_str(), options).map_err(zip_err)?;
+                zip.write_all(data).map_err(output_err)?;
+            }
+            zip.finish().map_err(zip_err)?.flush().map_err(output_err)?;
+        }
+    }
+    Ok(entries)
+}
+
+/// Читает архив, проверяет его по манифесту и разбирает все файлы.
+///
+/// Архив читается в память целиком.
+///
+/// # Errors
+/// `ParserErr::BundleMember`, если файла из манифеста нет в архиве, в архиве есть лишний
+/// файл, не совпали SHA-256 или число записей; ошибки разбора файлов.
+pub fn read_bundle(path: &Path, options: &ParseOptions) -> Result<Vec<BundleFile>, CommonErr> {
+    let (entries, mut members) = verified_members(path)?;
+    entries
+        .into_iter()
+        .map(|entry| {
+            let data = members.remove(&entry.name).unwrap_or_default();
+            let records = read_with(memory(data), entry.format, options)?.records;
+            Ok(BundleFile { entry, records })
+        })
+        .collect()
+}
+
+/// Проверяет архив по манифесту и распаковывает его файлы без изменений в каталог `out_dir`.
+///
+/// Если проверка не прошла, ничего не распаковывается.
+pub fn extract_bundle(path: &Path, out_dir: &Path) -> Result<Vec<BundleEntry>, CommonErr> {
+    let (entries, members) = verified_members(path)?;
+    fs::create_dir_all(out_dir).map_err(|e| IoErr::output(e, Some(out_dir.to_path_buf())))?;
+    for (name, data) in members {
+        let to = out_dir.join(name);
+        fs::write(&to, data).map_err(|e| IoErr::output(e, Some(to)))?;
+    }
+    Ok(entries)
+}
+
+/// Проверяет архив по манифесту и возвращает манифест.
+pub fn verify_bundle(path: &Path) -> Result<Vec<BundleEntry>, CommonErr> {
+    verified_members(path).map(|(entries, _)| entries)
+}
+
+/// Проверяет архив по манифесту и, если задан `out_dir`, распаковывает его туда
+/// (см. [`extract_bundle`] и [`verify_bundle`]).
+pub fn unbundle(path: &Path, out_dir: Option<&Path>) -> Result<Vec<BundleEntry>, CommonErr> {
+    match out_dir {
+        Some(out_dir) => extract_bundle(path, out_dir),
+        None => verify_bundle(path),
+    }
+}
+
+/// Содержимое файлов архива по именам.
+type Members = HashMap<String, Vec<u8>>;
+
+/// Читает манифест и файлы архива и сверяет их.
+fn verified_members(path: &Path) -> Result<(Vec<BundleEntry>, Members), CommonErr> {
+    let mut members = read_members(path)?;
+    let manifest = members
+        .remove(MANIFEST_NAME)
+        .ok_or_else(|| member_err(MANIFEST_NAME, "missing"))?;
+    let entries = parse_manifest(&manifest)?;
+
+    for entry in &entries {
+        // Имена без каталогов: распаковка не должна выходить за пределы каталога назначения
+        if Path::new(&entry.name).file_name() != Some(entry.name.as_ref()) {
+            return Err(member_err(&entry.name, "not a plain file name").into());
+        }
+        let data = members
+            .get(&entry.name)
+            .ok_or_else(|| member_err(&entry.name, "listed in manifest but missing"))?;
+        let sha256 = sha256(data);
+        if sha256 != entry.sha256 {
+            let reason = format!("SHA-256 {} does not match manifest {}", sha256, entry.sha256);
+            return Err(member_err(&entry.name, reason).into());
+        }
+        let records = count_records(data, entry.format).map_err(|e| member_err(&entry.name, e.to_string()))?;
+        if records != entry.records {
+            let reason = format!("{} records, manifest says {}", records, entry.records);
+            return Err(member_err(&entry.name, reason).into());
+        }
+    }
+    if let Some(extra) = members.keys().find(|name| !entries.iter().any(|entry| &entry.name == *name)) {
+        return Err(member_err(extra, "not listed in manifest").into());
+    }
+    Ok((entries, members))
+}
+
+/// Все файлы архива по именам.
+fn read_members(path: &Path) -> Result<Members, CommonErr> {
+    let kind = bundle_kind(path)?;
+    let input_err = |e: std::io::Error| IoErr::input(e, Some(path.to_path_buf()));
+    let file = File::open(path).map_err(input_err)?;
+
+    let mut members = Members::new();
+    match kind {
+        BundleKind::Tar => {
+            let mut archive = tar::Archive::new(file);
+            for entry in archive.entries().map_err(ParserErr::from)? {
+                let mut entry = entry.map_err(ParserErr::from)?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                let name = entry.path().map_err(ParserErr::from)?.to_string_lossy().into_owned();
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data).map_err(ParserErr::from)?;
+                members.insert(name, data);
+            }
+        }
+        BundleKind::Zip => {
+            let mut archive = ZipArchive::new(file).map_err(zip_err)?;
+            for i in 0..archive.len() {
+                let mut member = archive.by_index(i).map_err(zip_err)?;
+                if !member.is_file() {
+                    continue;
+                }
+                let mut data = Vec::new();
+                member.read_to_end(&mut data).map_err(ParserErr::from)?;
+                members.insert(member.name().to_string(), data);
+            }
+        }
+    }
+    Ok(members)
+}
+
+fn render_manifest(entries: &[BundleEntry]) -> String {
+    let files: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            serde_json::json!({
+                "name": entry.name,
+                "format": entry.format.name(),
+                "sha256": entry.sha256,
+                "records": entry.records,
+            })
+        })
+        .collect();
+    let manifest = serde_json::json!({ "version": MANIFEST_VERSION, "files": files });
+    serde_json::to_string_pretty(&manifest).unwrap_or_default()
+}
+
+fn parse_manifest(data: &[u8]) -> Result<Vec<BundleEntry>, ParserErr> {
+    let malformed = |msg: &str| member_err(MANIFEST_NAME, msg);
+    let manifest: serde_json::Value =
+        serde_json::from_slice(data).map_err(|e| member_err(MANIFEST_NAME, e.to_string()))?;
+
+    let version = manifest["version"].as_u64().ok_or_else(|| malformed("no version"))?;
+    if version != MANIFEST_VERSION {
+        return Err(malformed(&format!("unsupported version {}", version)));
+    }
+    let files = manifest["files"].as_array().ok_or_else(|| malformed("no files"))?;
+    files
+        .iter()
+        .map(|file| {
+            let text = |key: &str| file[key].as_str().ok_or_else(|| malformed(&format!("file without {}", key)));
+            Ok(BundleEntry {
+                name: text("name")?.to_string(),
+                format: Format::from_name(text("format")?).ok_or_else(|| malformed("unknown format"))?,
+                sha256: text("sha256")?.to_string(),
+                records: file["records"].as_u64().ok_or_else(|| malformed("file without records"))? as usize,
+            })
+        })
+        .collect()
+}
+
+fn count_records(data: &[u8], format: Format) -> Result<usize, CommonErr> {
+    Ok(read_with(memory(data.to_vec()), format, &ParseOptions::default())?.records.len())
+}
+
+fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+fn memory(data: Vec<u8>) -> Resource {
+    Resource::Memory { data: Cursor::new(data) }
+}
+
+fn bundle_kind(path: &Path) -> Result<BundleKind, ParserErr> {
+    BundleKind::from_path(path).ok_or_else(|| ParserErr::InvalidValue {
+        field: "BUNDLE",
+        value: path.display().to_string(),
+    })
+}
+
+fn member_err(member: &str, reason: impl ToString) -> ParserErr {
+    ParserErr::BundleMember {
+        member: member.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// Ошибки ввода-вывода внутри `zip` остаются `ParserErr::Io`.
+fn zip_err(e: zip::result::ZipError) -> ParserErr {
+    match e {
+        zip::result::ZipError::Io(io) => io.into(),
+        other => ParserErr::Malformed { msg: other.to_string() },
+    }
+}
+
+#[cfg(all(test, feature = "csv", feature = "text"))]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                       1,DEPOSIT,0,10,100,1700000000,SUCCESS,\"a\"\n\
+                       2,DEPOSIT,0,10,100,1700000000,SUCCESS,\"b\"\n";
+    const TEXT: &str = "TX_ID: 3\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 10\n\
+                        AMOUNT: 100\nTIMESTAMP: 1700000000\nSTATUS: SUCCESS\nDESCRIPTION: \"c\"\n";
+
+    fn inputs(dir: &Path) -> Vec<PathBuf> {
+        let files = vec![dir.join("jan.csv"), dir.join("feb.txt")];
+        fs::write(&files[0], CSV).unwrap();
+        fs::write(&files[1], TEXT).unwrap();
+        files
+    }
+
+    #[test]
+    fn test_bundle_roundtrip_tar_and_zip() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = inputs(dir.path());
+        for name in ["month.tar", "month.zip"] {
+            let bundle = dir.path().join(name);
+            let written = write_bundle(&files, &bundle).unwrap();
+            assert_eq!(written.iter().map(|e| e.records).collect::<Vec<_>>(), vec![2, 1]);
+
+            let read = read_bundle(&bundle, &ParseOptions::default()).unwrap();
+            let entries: Vec<_> = read.iter().map(|file| file.entry.clone()).collect();
+            assert_eq!(entries, written);
+            assert_eq!(read[1].records[0].tx_id, 3);
+
+            let out = dir.path().join(format!("{}.out", name));
+            extract_bundle(&bundle, &out).unwrap();
+            assert_eq!(fs::read_to_string(out.join("jan.csv")).unwrap(), CSV);
+        }
+    }
+
+    #[test]
+    fn test_bundle_with_tampered_member_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = inputs(dir.path());
+        let entries = write_bundle(&files, &dir.path().join("ok.tar")).unwrap();
+
+        // Тот же манифест, но в файле на одну запись меньше
+        let bundle = dir.path().join("bad.tar");
+        let mut builder = tar::Builder::new(File::create(&bundle).unwrap());
+        let truncated = CSV.lines().take(2).collect::<Vec<_>>().join("\n");
+        for (name, data) in [
+            ("jan.csv", truncated.into_bytes()),
+            ("feb.txt", TEXT.as_bytes().to_vec()),
+            (MANIFEST_NAME, render_manifest(&entries).into_bytes()),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            builder.append_data(&mut header, name, data.as_slice()).unwrap();
+        }
+        builder.finish().unwrap();
+
+        let err = verify_bundle(&bundle).unwrap_err();
+        if let CommonErr::Parser(ParserErr::BundleMember { member, reason }) = err {
+            assert_eq!(member, "jan.csv");
+            assert!(reason.contains("SHA-256"));
+        } else {
+            panic!()
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Кэш разобранных входов: записи файла сохраняются в бинарном формате под SHA-256 его
+//! содержимого, и повторный анализ того же файла читает их оттуда, не разбирая CSV или
+//! текст заново.
+//!
+//! Ключ — содержимое входа, а не путь или время изменения: переименованный или заново
+//! скопированный файл находится в кэше, а изменённый разбирается заново. В кэш попадает
+//! весь вход, разобранный с настройками по умолчанию; окно времени, нормализация описаний
+//! и прочие настройки команды применяются уже при чтении из кэша. Каталог кэша можно
+//! удалить в любой момент.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::model::data::Format;
+use crate::model::errors::{CommonErr, IoErr};
+use crate::model::resource::Resource;
+use crate::parser::{read_batches, write_batches, ParseOptions, WriteOptions};
+
+/// Версия содержимого кэша; входит в имя файла, поэтому записи старых версий не читаются.
+pub const CACHE_VERSION: u32 = 1;
+
+/// Сколько записей разбирается за шаг при заполнении кэша.
+const CACHE_BATCH_SIZE: usize = 10_000;
+
+/// Кэш разобранных входов в каталоге `dir` (см. описание модуля).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCache {
+    dir: PathBuf,
+}
+
+impl ParseCache {
+    /// Кэш в каталоге `dir`; каталог создаётся при первой записи.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ParseCache { dir: dir.into() }
+    }
+
+    /// Каталог кэша.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Ресурс, из которого читать `from`: файл кэша в бинарном формате, если вход — обычный
+    /// файл не в бинарном формате; при промахе файл кэша сначала создаётся. Остальные
+    /// входы (консоль, архивы, бинарные файлы) возвращаются как есть.
+    ///
+    /// # Errors
+    /// `CommonErr`, если вход не удалось прочитать или разобрать (с настройками по умолчанию)
+    /// или файл кэша — записать; при ошибке кэш не меняется.
+    pub fn resolve(&self, from: Resource, format: Format) -> Result<(Resource, Format), CommonErr> {
+        let path = match &from {
+            Resource::File { path } if format != Format::YpBankBin && path.is_file() => path.clone(),
+            _ => return Ok((from, format)),
+        };
+        let checksum = checksum(&path).map_err(|e| IoErr::input(e, Some(path.clone())))?;
+        let cached = self.dir.join(format!("{}.{}.v{}.bin", checksum, format.name(), CACHE_VERSION));
+        if !cached.is_file() {
+            self.store(from, format, &cached)?;
+        }
+        Ok((Resource::File { path: cached }, Format::YpBankBin))
+    }
+
+    /// Разбирает `from` и записывает его записи в `cached` через временный файл рядом,
+    /// чтобы параллельный запуск не прочитал файл кэша недописанным.
+    fn store(&self, from: Resource, format: Format, cached: &Path) -> Result<(), CommonErr> {
+        let output_err = |e, path: &Path| CommonErr::from(IoErr::output(e, Some(path.to_path_buf())));
+        fs::create_dir_all(&self.dir).map_err(|e| output_err(e, &self.dir))?;
+        let tmp = cached.with_extension(format!("bin.{}.tmp", std::process::id()));
+        // Заголовок нужен для нечисловых TX_ID
+        let options = WriteOptions {
+            bin_header: true,
+            ..WriteOptions::default()
+        };
+        let written = write_batches(Resource::File { path: tmp.clone() }, Format::YpBankBin, &options).and_then(|mut writer| {
+            for batch in read_batches(from, format, &ParseOptions::default(), CACHE_BATCH_SIZE)? {
+                writer.write_batch(&batch?.records, &[])?;
+            }
+            writer.finish()
+        });
+        if let Err(e) = written {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+        fs::rename(&tmp, cached).map_err(|e| output_err(e, cached))
+    }
+}
+
+/// SHA-256 содержимого файла в шестнадцатеричном виде.
+fn checksum(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use super::*;
+    use crate::parser::read;
+
+    #[test]
+    fn test_parse_cache_reuses_records_by_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ParseCache::new(dir.path().join("cache"));
+        let input = dir.path().join("in.csv");
+        let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,1700000000,SUCCESS,\"x\"\n\
+                   2,TRANSFER,10,11,50,1700000001,PENDING,\"y\"\n";
+        fs::write(&input, csv).unwrap();
+
+        let (cached, format) = cache.resolve(Resource::File { path: input.clone() }, Format::YpBankCsv).unwrap();
+        assert_eq!(format, Format::YpBankBin);
+        let original = read(Resource::File { path: input.clone() }, Format::YpBankCsv).unwrap();
+        let from_cache = read(cached.clone(), format).unwrap();
+        assert_eq!(original.len(), from_cache.len());
+        assert!(original.iter().zip(&from_cache).all(|(a, b)| a.same_content(b)));
+
+        // Копия с тем же содержимым берётся из кэша, изменённый файл разбирается заново
+        let copy = dir.path().join("copy.csv");
+        fs::copy(&input, &copy).unwrap();
+        let (again, _) = cache.resolve(Resource::File { path: copy }, Format::YpBankCsv).unwrap();
+        assert_eq!(format!("{}", again), format!("{}", cached));
+        fs::write(&input, csv.replace("\"y\"", "\"z\"")).unwrap();
+        let (changed, _) = cache.resolve(Resource::File { path: input.clone() }, Format::YpBankCsv).unwrap();
+        assert_ne!(format!("{}", changed), format!("{}", cached));
+        assert_eq!(fs::read_dir(cache.dir()).unwrap().count(), 2);
+
+        // Ошибка разбора не оставляет файлов в кэше
+        fs::write(&input, "not a csv\n").unwrap();
+        assert!(cache.resolve(Resource::File { path: input }, Format::YpBankCsv).is_err());
+        assert_eq!(fs::read_dir(cache.dir()).unwrap().count(), 2);
+
+        let console = cache.resolve(Resource::Console, Format::YpBankCsv).unwrap();
+        assert!(matches!(console, (Resource::Console, Format::YpBankCsv)));
+    }
+}
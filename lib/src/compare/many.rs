@@ -0,0 +1,164 @@
+//! Сравнение двух каталогов (или glob-шаблонов) попарно по именам файлов в несколько потоков.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::compare::{compare_inputs, CompareErr, CompareOptions, CompareReport};
+use crate::convert::{many, ConvertErr};
+use crate::model::data::Format;
+use crate::model::resource::Resource;
+
+/// Итог сравнения одной пары файлов.
+#[derive(Clone, Debug)]
+pub struct FileCompareResult {
+    /// Имя файла без расширения, по которому подобрана пара
+    pub name: String,
+    pub first: Option<PathBuf>,
+    pub second: Option<PathBuf>,
+    pub result: Result<CompareReport, CompareErr>,
+}
+
+impl FileCompareResult {
+    /// Файлы пары совпали.
+    pub fn equal(&self) -> bool {
+        self.result.as_ref().is_ok_and(|r| r.equal)
+    }
+}
+
+/// Итог сравнения двух каталогов.
+#[derive(Clone, Debug)]
+pub struct CompareManyReport {
+    /// Результаты по парам в порядке имён
+    pub files: Vec<FileCompareResult>,
+    /// У каждого файла есть пара, и все пары совпали
+    pub equal: bool,
+}
+
+/// Раскрывает вход в список файлов, как [`many::expand_inputs`] для конвертации.
+pub fn expand_inputs(path: &Path) -> Result<Option<Vec<PathBuf>>, CompareErr> {
+    many::expand_inputs(path).map_err(|e| match e {
+        ConvertErr::Inputs { reason } => CompareErr::Inputs { reason },
+        e => inputs_err(e),
+    })
+}
+
+/// Сравнивает файлы двух входов попарно: пару составляют файлы с одинаковым именем
+/// без расширения (`a.csv` и `a.bin`), поэтому форматы входов могут различаться.
+///
+/// Файл без пары — расхождение `CompareErr::MissingPair`; ошибка в одной паре
+/// не останавливает сравнение остальных. Пары сравниваются в `jobs` потоков,
+/// результаты всегда идут в порядке имён.
+pub fn compare_many(
+    first: Vec<PathBuf>,
+    first_format: Format,
+    second: Vec<PathBuf>,
+    second_format: Format,
+    options: &CompareOptions,
+    jobs: usize,
+) -> Result<CompareManyReport, CompareErr> {
+    let mut pairs: BTreeMap<String, (Option<PathBuf>, Option<PathBuf>)> = BTreeMap::new();
+    for (input, files) in [(1, first), (2, second)] {
+        for file in files {
+            let name = file.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            let pair = pairs.entry(name).or_default();
+            let slot = if input == 1 { &mut pair.0 } else { &mut pair.1 };
+            if let Some(other) = slot.replace(file.clone()) {
+                return Err(inputs_err(format!(
+                    "{} and {} in input {} have the same name",
+                    other.display(),
+                    file.display(),
+                    input
+                )));
+            }
+        }
+    }
+
+    let count = pairs.len();
+    let queue = Mutex::new(pairs.into_iter().enumerate());
+    let done: Mutex<Vec<Option<FileCompareResult>>> = Mutex::new(vec![None; count]);
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, count.max(1)) {
+            scope.spawn(|| loop {
+                let Some((i, (name, (first, second)))) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                let result = match (&first, &second) {
+                    (Some(first), Some(second)) => compare_inputs(
+                        Resource::File { path: first.clone() },
+                        first_format,
+                        Resource::File { path: second.clone() },
+                        second_format,
+                        options,
+                    ),
+                    (Some(_), None) => Err(CompareErr::MissingPair { input: 2, name: name.clone() }),
+                    _ => Err(CompareErr::MissingPair { input: 1, name: name.clone() }),
+                };
+                done.lock().unwrap()[i] = Some(FileCompareResult { name, first, second, result });
+            });
+        }
+    });
+    let files: Vec<FileCompareResult> = done.into_inner().unwrap().into_iter().flatten().collect();
+
+    Ok(CompareManyReport {
+        equal: files.iter().all(FileCompareResult::equal),
+        files,
+    })
+}
+
+fn inputs_err(reason: impl ToString) -> CompareErr {
+    CompareErr::Inputs {
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_compare_directories_pairs_files_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let header = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n";
+        let row = |id: u64, amount: u64| format!("{},DEPOSIT,0,10,{},1700000000,SUCCESS,\"x\"\n", id, amount);
+        let (primary, mirror) = (dir.path().join("primary"), dir.path().join("mirror"));
+        fs::create_dir_all(&primary).unwrap();
+        fs::create_dir_all(&mirror).unwrap();
+        for (side, name, rows) in [
+            (&primary, "a.csv", row(1, 100)),
+            (&mirror, "a.csv", row(1, 100)),
+            (&primary, "b.csv", row(2, 100)),
+            (&mirror, "b.csv", row(2, 999)),
+            (&primary, "c.csv", row(3, 100)),
+        ] {
+            fs::write(side.join(name), format!("{}{}", header, rows)).unwrap();
+        }
+
+        let compare = |first: &Path, second: &Path| {
+            compare_many(
+                expand_inputs(first).unwrap().unwrap(),
+                Format::YpBankCsv,
+                expand_inputs(second).unwrap().unwrap(),
+                Format::YpBankCsv,
+                &CompareOptions::default(),
+                2,
+            )
+        };
+
+        let many = compare(&primary, &mirror.join("*.csv")).unwrap();
+        let verdicts: Vec<_> = many.files.iter().map(|f| (f.name.as_str(), f.equal())).collect();
+        assert_eq!(verdicts, [("a", true), ("b", false), ("c", false)]);
+        assert!(!many.equal);
+        let Err(CompareErr::MissingPair { input, .. }) = &many.files[2].result else {
+            panic!("expected a missing pair");
+        };
+        assert_eq!(*input, 2);
+
+        fs::remove_file(primary.join("b.csv")).unwrap();
+        fs::remove_file(primary.join("c.csv")).unwrap();
+        fs::remove_file(mirror.join("b.csv")).unwrap();
+        assert!(compare(&primary, &mirror).unwrap().equal);
+    }
+}
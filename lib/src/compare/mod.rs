@@ -0,0 +1,635 @@
+//! Сравнение двух наборов транзакций: одна реализация для CLI и внешних сервисов.
+//!
+//! [`compare`] сравнивает уже прочитанные записи, [`compare_inputs`] сначала читает оба входа.
+//! Рядом — сравнение каталогов попарно ([`many`]), трёх и более входов ([`nway`]), быстрая
+//! проверка по контрольным суммам ([`quick`]) и отчёт о сравнении в HTML, Markdown и JUnit XML
+//! ([`report`]).
+//! Настройки — допуски, стратегия для повторяющихся TX_ID и параметры разбора — собраны
+//! в [`CompareOptions`]:
+//!
+//! ```
+//! use lib::compare::{compare, CompareOptions, DuplicateStrategy, Tolerance};
+//!
+//! let options = CompareOptions {
+//!     tolerance: Tolerance { timestamp_ms: 1000, amount: 0 },
+//!     duplicates: DuplicateStrategy::Multiset,
+//!     ..CompareOptions::default()
+//! };
+//! let report = compare(&[], &[], &options);
+//! assert!(report.equal);
+//! ```
+
+pub mod many;
+pub mod nway;
+pub mod quick;
+pub mod report;
+
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use crate::model::data::{Format, TxData};
+use crate::model::errors::CommonErr;
+use crate::model::resource::Resource;
+use crate::model::tx_id::TxId;
+use crate::parser::io::reader::read_with;
+use crate::parser::options::{ParseOptions, Provenance};
+#[cfg(feature = "spill")]
+use crate::parser::read_batches;
+#[cfg(feature = "spill")]
+use crate::spill::{sort_input, RecordStream, SortKey, SortedRecords, SpillOptions};
+#[cfg(feature = "spill")]
+use std::iter::Peekable;
+
+/// Сколько различающихся записей сохраняется в отчёте.
+pub const MAX_RECORD_DIFFS: usize = 1000;
+
+/// Как сравнивать входы, в которых TX_ID повторяются (например, повторные отправки).
+#[cfg_attr(feature = "console", derive(clap::ValueEnum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateStrategy {
+    /// Записи сравниваются попарно в порядке следования
+    #[default]
+    Ordered,
+    /// Записи сравниваются по TX_ID без учёта порядка: у каждого TX_ID все записи
+    /// одного входа должны найти пару в другом
+    Multiset,
+    /// Как `Multiset`, но из повторов TX_ID учитывается только первая запись
+    First,
+    /// Как `Multiset`, но повтор TX_ID в любом входе — расхождение по этому TX_ID
+    Reject,
+}
+
+/// Допустимые расхождения, при которых записи всё ещё считаются равными:
+/// известный безобидный дрейф между системами (округление времени, копейки).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Tolerance {
+    /// Наибольшая разница TIMESTAMP в миллисекундах
+    pub timestamp_ms: u64,
+    /// Наибольшая разница AMOUNT
+    pub amount: u64,
+}
+
+impl Tolerance {
+    /// Совпадают ли записи с учётом допусков; формат, из которого запись прочитана, не важен.
+    pub fn matches(&self, first: &TxData, second: &TxData) -> bool {
+        first.timestamp.abs_diff(second.timestamp) <= self.timestamp_ms
+            && first.amount.abs_diff(second.amount) <= self.amount
+            && TxData {
+                timestamp: second.timestamp,
+                amount: second.amount,
+                ..first.clone()
+            }
+            .same_content(second)
+    }
+}
+
+/// Настройки сравнения.
+#[derive(Clone, Debug, Default)]
+pub struct CompareOptions {
+    /// Допуски, при которых записи считаются равными
+    pub tolerance: Tolerance,
+    /// Как обрабатывать повторяющиеся TX_ID
+    pub duplicates: DuplicateStrategy,
+    /// Настройки разбора входов для [`compare_inputs`] (например, только записи из `time_window`)
+    pub parse: ParseOptions,
+    /// Сравнивать входы [`compare_inputs`] с ограниченной памятью: для сравнения по TX_ID они
+    /// сортируются через временные файлы (см. [`crate::spill`]), попарно — читаются потоком.
+    /// Происхождение записей в различиях при этом не указывается
+    #[cfg(feature = "spill")]
+    pub spill: Option<SpillOptions>,
+}
+
+/// Итог сравнения двух наборов записей.
+#[derive(Clone, Debug)]
+pub struct CompareReport {
+    /// Наборы совпали
+    pub equal: bool,
+    /// Сколько записей в первом наборе
+    pub first_records: usize,
+    /// Сколько записей во втором наборе
+    pub second_records: usize,
+    /// TX_ID, по которым наборы расходятся (при сравнении по TX_ID), по возрастанию
+    pub mismatches: Vec<KeyMismatch>,
+    /// Сколько различий: TX_ID с расхождением при сравнении по TX_ID, а при попарном
+    /// сравнении — позиций с разными записями (все, а не только сохранённые в `diffs`)
+    pub differing: usize,
+    /// Первые [`MAX_RECORD_DIFFS`] различающихся записей
+    pub diffs: Vec<RecordDiff>,
+}
+
+/// Расхождение по одному TX_ID: не все его записи нашли пару в другом наборе.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyMismatch {
+    pub tx_id: TxId,
+    /// Сколько записей с этим TX_ID в первом наборе
+    pub first: usize,
+    /// Сколько записей с этим TX_ID во втором наборе
+    pub second: usize,
+}
+
+/// Различие записей: пара записей с разными полями или запись без пары в другом наборе.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordDiff {
+    pub first: Option<TxData>,
+    pub second: Option<TxData>,
+    /// Откуда взята запись первого набора, если разбор отслеживал происхождение (`ParseOptions::provenance`)
+    pub first_at: Option<Provenance>,
+    /// Откуда взята запись второго набора
+    pub second_at: Option<Provenance>,
+}
+
+impl RecordDiff {
+    /// Поля записи в порядке спецификации: имя, значение в первом и во втором наборе.
+    pub fn fields(&self) -> Vec<(&'static str, Option<String>, Option<String>)> {
+        let values = |tx: &Option<TxData>| tx.as_ref().map(field_values);
+        let (first, second) = (values(&self.first), values(&self.second));
+        TxData::FIELDS
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let value = |values: &Option<[String; 8]>| values.as_ref().map(|v| v[i].clone());
+                (*name, value(&first), value(&second))
+            })
+            .collect()
+    }
+}
+
+fn field_values(tx: &TxData) -> [String; 8] {
+    [
+        tx.tx_id.to_string(),
+        tx.tx_type.name().to_string(),
+        tx.from_user_id.to_string(),
+        tx.to_user_id.to_string(),
+        tx.amount.to_string(),
+        tx.timestamp.to_string(),
+        tx.status.name().to_string(),
+        tx.description.to_string(),
+    ]
+}
+
+#[derive(Error, Clone, Debug)]
+pub enum CompareErr {
+    /// Не удалось прочитать или разобрать один из сравниваемых входов
+    #[error("failed to prepare compare input {input}")]
+    Prepare {
+        /// Номер входа, начиная с 1
+        input: usize,
+        #[source]
+        err: CommonErr,
+    },
+    /// Не удалось раскрыть каталог или glob-шаблон входа
+    #[error("failed to list inputs: {reason}")]
+    Inputs { reason: String },
+    /// Файлу одного входа нет пары с тем же именем в другом
+    #[error("no file named {name} in compare input {input}")]
+    MissingPair {
+        /// Номер входа, в котором файла нет: 1 — первый, 2 — второй
+        input: usize,
+        name: String,
+    },
+}
+
+/// Сравнивает два набора записей с настройками `options`.
+pub fn compare(first: &[TxData], second: &[TxData], options: &CompareOptions) -> CompareReport {
+    compare_sides(Side { records: first, at: &[] }, Side { records: second, at: &[] }, options)
+}
+
+/// Читает оба входа с настройками разбора `options.parse` и сравнивает их записи.
+///
+/// С `options.parse.provenance` различия в отчёте указывают, откуда взята каждая запись.
+pub fn compare_inputs(
+    first_from: Resource,
+    first_format: Format,
+    second_from: Resource,
+    second_format: Format,
+    options: &CompareOptions,
+) -> Result<CompareReport, CompareErr> {
+    #[cfg(feature = "spill")]
+    if let Some(spill) = &options.spill {
+        return compare_spilled((first_from, first_format), (second_from, second_format), options, spill);
+    }
+    let first = read_with(first_from, first_format, &options.parse).map_err(|err| CompareErr::Prepare { input: 1, err })?;
+    let second =
+        read_with(second_from, second_format, &options.parse).map_err(|err| CompareErr::Prepare { input: 2, err })?;
+    Ok(compare_sides(
+        Side { records: &first.records, at: &first.provenance },
+        Side { records: &second.records, at: &second.provenance },
+        options,
+    ))
+}
+
+/// Записи одного набора и, если известно, их происхождение (по индексу записи).
+#[derive(Clone, Copy)]
+struct Side<'a> {
+    records: &'a [TxData],
+    at: &'a [Provenance],
+}
+
+impl Side<'_> {
+    fn record(&self, i: Option<usize>) -> (Option<TxData>, Option<Provenance>) {
+        match i {
+            Some(i) => (self.records.get(i).cloned(), self.at.get(i).cloned()),
+            None => (None, None),
+        }
+    }
+}
+
+fn compare_sides(first: Side<'_>, second: Side<'_>, options: &CompareOptions) -> CompareReport {
+    let mut diffs = Vec::new();
+    let (differing, mismatches) = match options.duplicates {
+        DuplicateStrategy::Ordered => (compare_in_order(first, second, &options.tolerance, &mut diffs), Vec::new()),
+        keyed => {
+            let mismatches = compare_by_key(first, second, keyed, &options.tolerance, &mut diffs);
+            (mismatches.len(), mismatches)
+        },
+    };
+
+    CompareReport {
+        equal: differing == 0,
+        first_records: first.records.len(),
+        second_records: second.records.len(),
+        mismatches,
+        differing,
+        diffs,
+    }
+}
+
+/// Сохраняет различие записей с индексами `ours` и `theirs`, пока различий меньше [`MAX_RECORD_DIFFS`].
+fn push_diff(diffs: &mut Vec<RecordDiff>, (first, ours): (Side<'_>, Option<usize>), (second, theirs): (Side<'_>, Option<usize>)) {
+    if diffs.len() < MAX_RECORD_DIFFS {
+        let (first, first_at) = first.record(ours);
+        let (second, second_at) = second.record(theirs);
+        diffs.push(RecordDiff { first, second, first_at, second_at });
+    }
+}
+
+/// Сравнивает записи попарно в порядке следования; возвращает число различающихся позиций.
+fn compare_in_order(first: Side<'_>, second: Side<'_>, tolerance: &Tolerance, diffs: &mut Vec<RecordDiff>) -> usize {
+    let (len, other) = (first.records.len(), second.records.len());
+    let mut differing = 0;
+    for i in 0..len.max(other) {
+        if let (Some(ours), Some(theirs)) = (first.records.get(i), second.records.get(i)) {
+            if tolerance.matches(ours, theirs) {
+                continue;
+            }
+        }
+        differing += 1;
+        push_diff(diffs, (first, (i < len).then_some(i)), (second, (i < other).then_some(i)));
+    }
+    differing
+}
+
+/// Сравнивает наборы по TX_ID как мультимножества: записи с одним TX_ID сопоставляются
+/// между собой без учёта порядка, и каждый TX_ID с несопоставленными записями — расхождение.
+fn compare_by_key(
+    first: Side<'_>,
+    second: Side<'_>,
+    duplicates: DuplicateStrategy,
+    tolerance: &Tolerance,
+    diffs: &mut Vec<RecordDiff>,
+) -> Vec<KeyMismatch> {
+    let mut groups: BTreeMap<&TxId, (Vec<usize>, Vec<usize>)> = BTreeMap::new();
+    for (i, tx) in first.records.iter().enumerate() {
+        groups.entry(&tx.tx_id).or_default().0.push(i);
+    }
+    for (i, tx) in second.records.iter().enumerate() {
+        groups.entry(&tx.tx_id).or_default().1.push(i);
+    }
+
+    groups
+        .into_iter()
+        .filter_map(|(tx_id, (ours, theirs))| compare_group(tx_id, (first, ours), (second, theirs), duplicates, tolerance, diffs))
+        .collect()
+}
+
+/// Сопоставляет записи одного TX_ID: `ours` из первого набора, `theirs` из второго;
+/// `Some`, если по TX_ID есть расхождение.
+fn compare_group(
+    tx_id: &TxId,
+    (first, mut ours): (Side<'_>, Vec<usize>),
+    (second, mut theirs): (Side<'_>, Vec<usize>),
+    duplicates: DuplicateStrategy,
+    tolerance: &Tolerance,
+    diffs: &mut Vec<RecordDiff>,
+) -> Option<KeyMismatch> {
+    let counts = (ours.len(), theirs.len());
+    if duplicates == DuplicateStrategy::First {
+        ours.truncate(1);
+        theirs.truncate(1);
+    }
+    let matches = |i: usize, j: usize| tolerance.matches(&first.records[i], &second.records[j]);
+    let (ours, theirs) = unpaired(&ours, &theirs, matches);
+    for i in 0..ours.len().max(theirs.len()) {
+        push_diff(diffs, (first, ours.get(i).copied()), (second, theirs.get(i).copied()));
+    }
+    let rejected = duplicates == DuplicateStrategy::Reject && (counts.0 > 1 || counts.1 > 1);
+    (rejected || !ours.is_empty() || !theirs.is_empty()).then(|| KeyMismatch {
+        tx_id: tx_id.clone(),
+        first: counts.0,
+        second: counts.1,
+    })
+}
+
+/// Сравнивает входы с ограниченной памятью (см. `CompareOptions::spill`).
+#[cfg(feature = "spill")]
+fn compare_spilled(
+    (first_from, first_format): (Resource, Format),
+    (second_from, second_format): (Resource, Format),
+    options: &CompareOptions,
+    spill: &SpillOptions,
+) -> Result<CompareReport, CompareErr> {
+    if options.duplicates == DuplicateStrategy::Ordered {
+        let open = |from, format, input| {
+            read_batches(from, format, &options.parse, spill.run_records.max(1))
+                .map(RecordStream::new)
+                .map_err(|err| CompareErr::Prepare { input, err })
+        };
+        let first = open(first_from, first_format, 1)?;
+        let second = open(second_from, second_format, 2)?;
+        return compare_streams_in_order(first, second, &options.tolerance);
+    }
+
+    let sort = |from, format, input| {
+        sort_input(from, format, SortKey::TxId, &options.parse, spill).map_err(|err| CompareErr::Prepare { input, err })
+    };
+    let mut first = sort(first_from, first_format, 1)?.peekable();
+    let mut second = sort(second_from, second_format, 2)?.peekable();
+
+    let (mut counts, mut mismatches, mut diffs) = ((0, 0), Vec::new(), Vec::new());
+    loop {
+        let ids = [next_id(&mut first, 1)?, next_id(&mut second, 2)?];
+        let Some(tx_id) = ids.into_iter().flatten().min() else {
+            break;
+        };
+        let ours = take_group(&mut first, &tx_id, 1)?;
+        let theirs = take_group(&mut second, &tx_id, 2)?;
+        counts = (counts.0 + ours.len(), counts.1 + theirs.len());
+        let sides = (Side { records: &ours, at: &[] }, Side { records: &theirs, at: &[] });
+        mismatches.extend(compare_group(
+            &tx_id,
+            (sides.0, (0..ours.len()).collect()),
+            (sides.1, (0..theirs.len()).collect()),
+            options.duplicates,
+            &options.tolerance,
+            &mut diffs,
+        ));
+    }
+    Ok(CompareReport {
+        equal: mismatches.is_empty(),
+        first_records: counts.0,
+        second_records: counts.1,
+        differing: mismatches.len(),
+        mismatches,
+        diffs,
+    })
+}
+
+/// Сравнивает потоки записей попарно в порядке следования.
+#[cfg(feature = "spill")]
+fn compare_streams_in_order(
+    mut first: impl Iterator<Item = Result<TxData, CommonErr>>,
+    mut second: impl Iterator<Item = Result<TxData, CommonErr>>,
+    tolerance: &Tolerance,
+) -> Result<CompareReport, CompareErr> {
+    let (mut differing, mut counts, mut diffs) = (0, (0, 0), Vec::new());
+    loop {
+        let ours = first.next().transpose().map_err(|err| CompareErr::Prepare { input: 1, err })?;
+        let theirs = second.next().transpose().map_err(|err| CompareErr::Prepare { input: 2, err })?;
+        counts = (counts.0 + ours.is_some() as usize, counts.1 + theirs.is_some() as usize);
+        match (ours, theirs) {
+            (None, None) => break,
+            (Some(ours), Some(theirs)) if tolerance.matches(&ours, &theirs) => {},
+            (ours, theirs) => {
+                differing += 1;
+                if diffs.len() < MAX_RECORD_DIFFS {
+                    diffs.push(RecordDiff { first: ours, second: theirs, first_at: None, second_at: None });
+                }
+            },
+        }
+    }
+    Ok(CompareReport {
+        equal: differing == 0,
+        first_records: counts.0,
+        second_records: counts.1,
+        mismatches: Vec::new(),
+        differing,
+        diffs,
+    })
+}
+
+/// TX_ID следующей записи упорядоченного входа `input`; `None` в конце входа.
+#[cfg(feature = "spill")]
+fn next_id(records: &mut Peekable<SortedRecords>, input: usize) -> Result<Option<TxId>, CompareErr> {
+    match records.peek() {
+        None => Ok(None),
+        Some(Ok(tx)) => Ok(Some(tx.tx_id.clone())),
+        Some(Err(_)) => match records.next() {
+            Some(Err(err)) => Err(CompareErr::Prepare { input, err }),
+            _ => Ok(None),
+        },
+    }
+}
+
+/// Все идущие подряд записи входа `input` с TX_ID `tx_id`.
+#[cfg(feature = "spill")]
+fn take_group(records: &mut Peekable<SortedRecords>, tx_id: &TxId, input: usize) -> Result<Vec<TxData>, CompareErr> {
+    let mut group = Vec::new();
+    while next_id(records, input)?.as_ref() == Some(tx_id) {
+        if let Some(Ok(tx)) = records.next() {
+            group.push(tx);
+        }
+    }
+    Ok(group)
+}
+
+/// Индексы записей `ours` и `theirs`, не нашедших себе пары в другом наборе.
+///
+/// С допусками запись может подходить нескольким записям другого набора, и первая подходящая
+/// может понадобиться другой записи; поэтому пары подбираются наибольшим паросочетанием
+/// (увеличивающими путями), а не по первому совпадению.
+fn unpaired(ours: &[usize], theirs: &[usize], matches: impl Fn(usize, usize) -> bool) -> (Vec<usize>, Vec<usize>) {
+    let candidates: Vec<Vec<usize>> = ours
+        .iter()
+        .map(|&i| (0..theirs.len()).filter(|&j| matches(i, theirs[j])).collect())
+        .collect();
+    // owner[j] — какая запись `ours` (по позиции) получила в пару theirs[j]
+    let mut owner = vec![None; theirs.len()];
+    for k in 0..ours.len() {
+        augment(k, &candidates, &mut owner, &mut vec![false; theirs.len()]);
+    }
+
+    let mut paired = vec![false; ours.len()];
+    for &k in owner.iter().flatten() {
+        paired[k] = true;
+    }
+    let ours = ours.iter().zip(paired).filter(|(_, paired)| !paired).map(|(i, _)| *i).collect();
+    let theirs = theirs.iter().zip(&owner).filter(|(_, owner)| owner.is_none()).map(|(j, _)| *j).collect();
+    (ours, theirs)
+}
+
+/// Ищет пару записи `k`, при необходимости передавая уже занятую запись другой паре.
+fn augment(k: usize, candidates: &[Vec<usize>], owner: &mut [Option<usize>], visited: &mut [bool]) -> bool {
+    for &j in &candidates[k] {
+        if visited[j] {
+            continue;
+        }
+        visited[j] = true;
+        let free = match owner[j] {
+            Some(other) => augment(other, candidates, owner, visited),
+            None => true,
+        };
+        if free {
+            owner[j] = Some(k);
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::data::{Status, TxType};
+    #[cfg(all(feature = "spill", feature = "csv"))]
+    use std::fs;
+
+    fn tx(timestamp: u64, amount: i64, format: Format) -> TxData {
+        TxData {
+            tx_id: TxId::U64(1),
+            tx_type: TxType::Deposit,
+            from_user_id: 0,
+            to_user_id: 10,
+            amount,
+            timestamp,
+            status: Status::Success,
+            description: "fee".into(),
+            format,
+        }
+    }
+
+    #[test]
+    fn test_tolerance_allows_known_drift_only() {
+        let exact = Tolerance::default();
+        let drift = Tolerance { timestamp_ms: 60_000, amount: 1 };
+        let seconds = tx(1633036861000, 100, Format::YpBankCsv);
+        let minutes = tx(1633036860000, 101, Format::YpBankBin);
+
+        assert!(exact.matches(&seconds, &tx(1633036861000, 100, Format::YpBankBin)));
+        assert!(!exact.matches(&seconds, &minutes));
+        assert!(drift.matches(&seconds, &minutes));
+        assert!(!drift.matches(&seconds, &tx(1633036860000, 102, Format::YpBankBin)));
+        assert!(!drift.matches(&seconds, &TxData { status: Status::Failure, ..minutes }));
+    }
+
+    fn with_id(id: u64, amount: i64) -> TxData {
+        TxData {
+            tx_id: TxId::U64(id),
+            ..tx(1633036860000, amount, Format::YpBankCsv)
+        }
+    }
+
+    #[test]
+    fn test_compare_by_key_reports_count_mismatches() {
+        let options = |duplicates| CompareOptions { duplicates, ..CompareOptions::default() };
+        // Повторная отправка 1 есть в обоих входах, но в другом порядке; 2 повторена только в первом
+        let first = [with_id(1, 100), with_id(2, 50), with_id(1, 100), with_id(2, 50), with_id(3, 7)];
+        let second = [with_id(3, 7), with_id(1, 100), with_id(2, 50), with_id(1, 100)];
+
+        let report = compare(&first, &second, &options(DuplicateStrategy::Multiset));
+        assert!(!report.equal);
+        assert_eq!(report.mismatches, vec![KeyMismatch { tx_id: TxId::U64(2), first: 2, second: 1 }]);
+
+        assert!(compare(&first, &second, &options(DuplicateStrategy::First)).equal);
+        // Попарно различаются все четыре общие позиции и лишняя запись первого входа
+        let ordered = compare(&first, &second, &options(DuplicateStrategy::Ordered));
+        assert!(!ordered.equal);
+        assert_eq!((ordered.differing, ordered.mismatches.len(), ordered.diffs.len()), (5, 0, 5));
+
+        let rejected = compare(&first, &second, &options(DuplicateStrategy::Reject));
+        let ids: Vec<_> = rejected.mismatches.iter().map(|m| m.tx_id.clone()).collect();
+        assert_eq!(ids, vec![TxId::U64(1), TxId::U64(2)]);
+
+        // Одинаковое число записей, но содержимое повтора другое
+        let second = [with_id(1, 100), with_id(1, 101)];
+        let first = [with_id(1, 100), with_id(1, 100)];
+        let report = compare(&first, &second, &options(DuplicateStrategy::Multiset));
+        assert_eq!(report.mismatches, vec![KeyMismatch { tx_id: TxId::U64(1), first: 2, second: 2 }]);
+        assert_eq!(report.diffs, vec![RecordDiff { first: Some(with_id(1, 100)), second: Some(with_id(1, 101)), first_at: None, second_at: None }]);
+        let changed: Vec<_> = report.diffs[0].fields().into_iter().filter(|(_, a, b)| a != b).collect();
+        assert_eq!(changed, vec![("AMOUNT", Some("100".to_string()), Some("101".to_string()))]);
+    }
+
+    #[test]
+    fn test_tolerance_pairs_all_records_when_first_fit_would_not() {
+        // 1000 подходит к 2000, но тогда 2500 осталась бы без пары; полное сопоставление — 1000↔0, 2500↔2000
+        let options = CompareOptions {
+            tolerance: Tolerance { timestamp_ms: 0, amount: 1000 },
+            duplicates: DuplicateStrategy::Multiset,
+            ..CompareOptions::default()
+        };
+        let first = [with_id(1, 1000), with_id(1, 2500)];
+        let second = [with_id(1, 2000), with_id(1, 0)];
+
+        let report = compare(&first, &second, &options);
+        assert!(report.equal, "{:?}", report.diffs);
+        assert!(report.mismatches.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "csv")]
+    fn test_compare_inputs_reports_failing_input() {
+        let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,1633036860000,SUCCESS,\"fee\"\n";
+        let memory = |data: &str| Resource::Memory { data: std::io::Cursor::new(data.as_bytes().to_vec()) };
+        let options = CompareOptions::default();
+
+        let report = compare_inputs(memory(csv), Format::YpBankCsv, memory(csv), Format::YpBankCsv, &options).unwrap();
+        assert!(report.equal);
+        assert_eq!((report.first_records, report.second_records), (1, 1));
+
+        let broken = compare_inputs(memory(csv), Format::YpBankCsv, memory("garbage"), Format::YpBankCsv, &options);
+        let Err(CompareErr::Prepare { input, .. }) = broken else {
+            panic!("expected a prepare error");
+        };
+        assert_eq!(input, 2);
+
+        // Описания, различающиеся только пробелами, совпадают после нормализации
+        let spaced = csv.replace("\"fee\"", "\" fee  \"");
+        let report = compare_inputs(memory(csv), Format::YpBankCsv, memory(&spaced), Format::YpBankCsv, &options).unwrap();
+        assert!(!report.equal);
+        let mut normalized = CompareOptions::default();
+        normalized.parse.normalize_description.trim = true;
+        let report = compare_inputs(memory(csv), Format::YpBankCsv, memory(&spaced), Format::YpBankCsv, &normalized).unwrap();
+        assert!(report.equal);
+    }
+
+    #[cfg(all(feature = "spill", feature = "csv"))]
+    #[test]
+    fn test_spilled_compare_matches_in_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv = |rows: &[(u64, i64)]| {
+            let mut csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n".to_string();
+            for (id, amount) in rows {
+                csv.push_str(&format!("{},DEPOSIT,0,10,{},1633036860000,SUCCESS,\"fee\"\n", id, amount));
+            }
+            Resource::Memory { data: std::io::Cursor::new(csv.into_bytes()) }
+        };
+        let first = [(1, 100), (2, 50), (1, 100), (2, 50), (3, 7), (4, 1)];
+        let second = [(3, 7), (1, 100), (2, 50), (1, 100), (4, 2), (5, 9)];
+        let spill = SpillOptions { run_records: 2, temp_dir: Some(dir.path().to_path_buf()) };
+
+        for duplicates in [DuplicateStrategy::Ordered, DuplicateStrategy::Multiset, DuplicateStrategy::Reject] {
+            let options = CompareOptions { duplicates, ..CompareOptions::default() };
+            let expected = compare_inputs(csv(&first), Format::YpBankCsv, csv(&second), Format::YpBankCsv, &options).unwrap();
+            let options = CompareOptions { spill: Some(spill.clone()), ..options };
+            let report = compare_inputs(csv(&first), Format::YpBankCsv, csv(&second), Format::YpBankCsv, &options).unwrap();
+
+            assert_eq!((report.equal, report.first_records, report.second_records), (false, 6, 6));
+            assert_eq!(report.mismatches, expected.mismatches);
+            assert_eq!(report.differing, expected.differing);
+            assert_eq!(report.diffs, expected.diffs);
+        }
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,128 @@
+//! Сравнение трёх и более входов: для каждого TX_ID — какие входы согласны между собой,
+//! а каких записи нет (например, основная база, реплика и архив).
+
+use std::collections::BTreeMap;
+
+use crate::compare::{CompareErr, CompareOptions, Tolerance};
+use crate::model::data::{Format, TxData};
+use crate::model::resource::Resource;
+use crate::model::tx_id::TxId;
+use crate::parser::io::reader::read_with;
+
+/// Расхождение входов по одному TX_ID.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Disagreement {
+    pub tx_id: TxId,
+    /// Группы согласных между собой входов (номера с 1); первая — группа первого входа с записью
+    pub groups: Vec<Vec<usize>>,
+    /// Входы, в которых записи с этим TX_ID нет
+    pub missing: Vec<usize>,
+}
+
+/// Итог сравнения нескольких входов.
+#[derive(Clone, Debug)]
+pub struct NwayReport {
+    /// Все входы содержат одни и те же записи
+    pub equal: bool,
+    /// Сколько записей прочитано из каждого входа
+    pub records: Vec<usize>,
+    /// TX_ID, по которым входы расходятся, по возрастанию
+    pub disagreements: Vec<Disagreement>,
+}
+
+/// Сравнивает входы по TX_ID и для каждого расходящегося TX_ID сообщает,
+/// какие входы согласны между собой (с допусками `options.tolerance`), а в каких записи нет.
+///
+/// Из повторяющихся в одном входе TX_ID берётся первая запись, поэтому `options.duplicates` не учитывается.
+pub fn compare_nway(inputs: Vec<(Resource, Format)>, options: &CompareOptions) -> Result<NwayReport, CompareErr> {
+    let inputs = inputs
+        .into_iter()
+        .enumerate()
+        .map(|(i, (from, format))| {
+            read_with(from, format, &options.parse)
+                .map(|parsed| parsed.records)
+                .map_err(|err| CompareErr::Prepare { input: i + 1, err })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let disagreements = disagreements(&inputs, &options.tolerance);
+    Ok(NwayReport {
+        equal: disagreements.is_empty(),
+        records: inputs.iter().map(Vec::len).collect(),
+        disagreements,
+    })
+}
+
+fn disagreements(inputs: &[Vec<TxData>], tolerance: &Tolerance) -> Vec<Disagreement> {
+    let mut keys: BTreeMap<&TxId, Vec<Option<&TxData>>> = BTreeMap::new();
+    for (i, records) in inputs.iter().enumerate() {
+        for tx in records {
+            let slots = keys.entry(&tx.tx_id).or_insert_with(|| vec![None; inputs.len()]);
+            slots[i].get_or_insert(tx);
+        }
+    }
+
+    let mut disagreements = Vec::new();
+    for (tx_id, slots) in keys {
+        let mut groups: Vec<(&TxData, Vec<usize>)> = Vec::new();
+        let mut missing = Vec::new();
+        for (i, slot) in slots.into_iter().enumerate() {
+            let Some(tx) = slot else {
+                missing.push(i + 1);
+                continue;
+            };
+            match groups.iter_mut().find(|(first, _)| tolerance.matches(first, tx)) {
+                Some((_, group)) => group.push(i + 1),
+                None => groups.push((tx, vec![i + 1])),
+            }
+        }
+        if groups.len() > 1 || !missing.is_empty() {
+            disagreements.push(Disagreement {
+                tx_id: tx_id.clone(),
+                groups: groups.into_iter().map(|(_, group)| group).collect(),
+                missing,
+            });
+        }
+    }
+    disagreements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::data::{Status, TxType};
+
+    fn tx(id: u64, amount: i64) -> TxData {
+        TxData {
+            tx_id: TxId::U64(id),
+            tx_type: TxType::Deposit,
+            from_user_id: 0,
+            to_user_id: 10,
+            amount,
+            timestamp: 1633036860000,
+            status: Status::Success,
+            description: "fee".into(),
+            format: Format::YpBankCsv,
+        }
+    }
+
+    #[test]
+    fn test_nway_reports_disagreeing_inputs_per_key() {
+        let primary = vec![tx(1, 100), tx(2, 50), tx(3, 7)];
+        let replica = vec![tx(1, 100), tx(2, 51), tx(3, 7)];
+        let archive = vec![tx(1, 100), tx(2, 51)];
+
+        let found = disagreements(&[primary.clone(), replica, archive], &Tolerance::default());
+        assert_eq!(
+            found,
+            vec![
+                Disagreement { tx_id: TxId::U64(2), groups: vec![vec![1], vec![2, 3]], missing: vec![] },
+                Disagreement { tx_id: TxId::U64(3), groups: vec![vec![1, 2]], missing: vec![3] },
+            ]
+        );
+
+        let drift = Tolerance { timestamp_ms: 0, amount: 1 };
+        let found = disagreements(&[primary.clone(), vec![tx(3, 7), tx(2, 51), tx(1, 100)], primary], &drift);
+        assert!(found.is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+//! Быстрая проверка по контрольным суммам: одинаковые файлы не разбираются вовсе,
+//! а у разных суммы участков показывают, где искать различия.
+//!
+//! Границы участков выбирает скользящий хеш по содержимому, а не фиксированное смещение,
+//! поэтому вставка записи в середину архива меняет только соседние участки.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+/// Участок не короче, чтобы не дробить файл на мелочь.
+const MIN_CHUNK: u64 = 16 * 1024;
+/// Граница ставится, когда младшие биты скользящего хеша нулевые: в среднем раз в 64 КиБ.
+const BOUNDARY_MASK: u64 = 0xFFFF;
+/// Участок не длиннее, даже если граница не нашлась.
+const MAX_CHUNK: u64 = 256 * 1024;
+
+/// Итог быстрой проверки пары файлов.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuickCheck {
+    /// SHA-256 первого файла, hex
+    pub first_hash: String,
+    /// SHA-256 второго файла, hex
+    pub second_hash: String,
+    /// Участки первого файла (смещения в байтах), которых нет во втором
+    pub first_only: Vec<Range<u64>>,
+    /// Участки второго файла, которых нет в первом
+    pub second_only: Vec<Range<u64>>,
+}
+
+impl QuickCheck {
+    /// Файлы совпадают побайтно, и полное сравнение не нужно.
+    pub fn identical(&self) -> bool {
+        self.first_hash == self.second_hash
+    }
+}
+
+/// Считает контрольные суммы обоих файлов целиком и по участкам.
+pub fn quick_check(first: &Path, second: &Path) -> io::Result<QuickCheck> {
+    let (first_hash, first_chunks) = chunk_hashes(File::open(first)?)?;
+    let (second_hash, second_chunks) = chunk_hashes(File::open(second)?)?;
+    let only = |ours: &[Chunk], theirs: &[Chunk]| -> Vec<Range<u64>> {
+        let known: std::collections::HashSet<_> = theirs.iter().map(|chunk| chunk.hash).collect();
+        let mut ranges: Vec<Range<u64>> = Vec::new();
+        for chunk in ours.iter().filter(|chunk| !known.contains(&chunk.hash)) {
+            // Соседние различающиеся участки показываются одним диапазоном
+            match ranges.last_mut() {
+                Some(last) if last.end == chunk.range.start => last.end = chunk.range.end,
+                _ => ranges.push(chunk.range.clone()),
+            }
+        }
+        ranges
+    };
+    Ok(QuickCheck {
+        first_only: only(&first_chunks, &second_chunks),
+        second_only: only(&second_chunks, &first_chunks),
+        first_hash,
+        second_hash,
+    })
+}
+
+struct Chunk {
+    range: Range<u64>,
+    hash: [u8; 32],
+}
+
+/// SHA-256 всего потока и его участков.
+fn chunk_hashes(mut reader: impl Read) -> io::Result<(String, Vec<Chunk>)> {
+    let (mut whole, mut part) = (Sha256::new(), Sha256::new());
+    let mut chunks = Vec::new();
+    let (mut offset, mut start, mut rolling) = (0u64, 0u64, 0u64);
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        let data = &buf[..read];
+        whole.update(data);
+        let mut from = 0;
+        for (i, &byte) in data.iter().enumerate() {
+            rolling = (rolling << 1).wrapping_add(GEAR[byte as usize]);
+            offset += 1;
+            let len = offset - start;
+            if (len >= MIN_CHUNK && rolling & BOUNDARY_MASK == 0) || len >= MAX_CHUNK {
+                part.update(&data[from..=i]);
+                from = i + 1;
+                chunks.push(Chunk { range: start..offset, hash: part.finalize_reset().into() });
+                start = offset;
+            }
+        }
+        part.update(&data[from..]);
+    }
+    if offset > start {
+        chunks.push(Chunk { range: start..offset, hash: part.finalize().into() });
+    }
+    let hex = whole.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    Ok((hex, chunks))
+}
+
+/// Таблица скользящего gear-хеша: псевдослучайное число на каждый байт (splitmix64).
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Неповторяющиеся данные, как у настоящей выгрузки.
+    fn data(len: usize) -> Vec<u8> {
+        let mut state = 1u64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 32) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_quick_check_localizes_inserted_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = data(2 << 20);
+        let mut changed = original.clone();
+        changed.splice(1_000_000..1_000_000, *b"inserted record");
+        let (first, second) = (dir.path().join("a.bin"), dir.path().join("b.bin"));
+        std::fs::write(&first, &original).unwrap();
+        std::fs::write(&second, &changed).unwrap();
+
+        assert!(quick_check(&first, &first).unwrap().identical());
+
+        let check = quick_check(&first, &second).unwrap();
+        assert!(!check.identical());
+        // Вставка меняет один-два участка вокруг себя, а не весь хвост файла
+        let [range] = check.second_only.as_slice() else {
+            panic!("expected one differing range, got {:?}", check.second_only);
+        };
+        assert!(range.contains(&1_000_000), "{:?}", range);
+        assert!(range.end - range.start <= 2 * MAX_CHUNK, "{:?}", range);
+        assert_eq!(check.first_only.len(), 1);
+    }
+}
@@ -0,0 +1,307 @@
+//! Отчёт о сравнении для тикета об инциденте: сводная таблица по файлам и различия
+//! записей по полям, в HTML (с цветом) или в Markdown; итоги пар в JUnit XML для CI.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::compare::{CompareErr, CompareReport, RecordDiff};
+use crate::junit::{write_junit, CaseOutcome, TestCase};
+use crate::model::errors::{describe_error, CommonErr};
+
+/// Результат сравнения одной пары входов для отчёта.
+pub struct ReportEntry<'a> {
+    /// Как пара называется в отчёте (имя файла или описание входов)
+    pub name: String,
+    pub result: &'a Result<CompareReport, CompareErr>,
+}
+
+/// Записывает отчёт в `path`: Markdown для расширения `.md`, иначе HTML.
+pub fn write_report(path: &Path, title: &str, entries: &[ReportEntry<'_>]) -> io::Result<()> {
+    let markdown = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("md"));
+    let report = if markdown {
+        render_markdown(title, entries)
+    } else {
+        render_html(title, entries)
+    };
+    fs::write(path, report)
+}
+
+/// Записывает итоги пар в JUnit XML: пара с различиями — `failure`, ошибка сравнения — `error`.
+pub fn write_junit_report(path: &Path, entries: &[ReportEntry<'_>]) -> Result<(), CommonErr> {
+    let cases: Vec<TestCase> = entries
+        .iter()
+        .map(|entry| TestCase {
+            name: entry.name.clone(),
+            time: None,
+            outcome: junit_outcome(entry.result),
+        })
+        .collect();
+    write_junit(path, "compare", &cases)
+}
+
+fn junit_outcome(result: &Result<CompareReport, CompareErr>) -> CaseOutcome {
+    match result {
+        Ok(r) if r.equal => CaseOutcome::Passed,
+        Ok(r) => {
+            let mut details = String::new();
+            for mismatch in &r.mismatches {
+                let _ = writeln!(details, "TX_ID {}: {} vs {} records", mismatch.tx_id, mismatch.first, mismatch.second);
+            }
+            for diff in &r.diffs {
+                let _ = match (&diff.first, &diff.second) {
+                    (Some(tx), None) => writeln!(details, "TX_ID {}: only in the first input", tx.tx_id),
+                    (None, Some(tx)) => writeln!(details, "TX_ID {}: only in the second input", tx.tx_id),
+                    (Some(tx), Some(_)) => {
+                        let fields = diff.fields();
+                        let changed: Vec<&str> = fields.iter().filter(|(_, first, second)| first != second).map(|(name, ..)| *name).collect();
+                        writeln!(details, "TX_ID {}: {} differ", tx.tx_id, changed.join(", "))
+                    },
+                    (None, None) => Ok(()),
+                };
+            }
+            let [first, second, differing] = counts(result);
+            CaseOutcome::Failed {
+                message: format!("{} vs {} records, differences: {}", first, second, differing),
+                details,
+            }
+        },
+        Err(e) => CaseOutcome::Error {
+            message: e.to_string(),
+            details: describe_error(e),
+        },
+    }
+}
+
+/// Итог пары в одну строку: совпала, различается или ошибка.
+fn verdict(result: &Result<CompareReport, CompareErr>) -> &'static str {
+    match result {
+        Ok(r) if r.equal => "equal",
+        Ok(_) => "differ",
+        Err(_) => "error",
+    }
+}
+
+/// Сводные числа пары: записей в первом и втором входе, различий (см. `CompareReport::differing`).
+fn counts(result: &Result<CompareReport, CompareErr>) -> [String; 3] {
+    match result {
+        Ok(r) => [r.first_records, r.second_records, r.differing].map(|n| n.to_string()),
+        Err(_) => Default::default(),
+    }
+}
+
+/// Откуда взяты записи различия, если разбор это отслеживал.
+fn origin(diff: &RecordDiff) -> Option<[String; 2]> {
+    if diff.first_at.is_none() && diff.second_at.is_none() {
+        return None;
+    }
+    Some([&diff.first_at, &diff.second_at].map(|at| at.as_ref().map(ToString::to_string).unwrap_or_default()))
+}
+
+fn render_html(title: &str, entries: &[ReportEntry<'_>]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">");
+    let _ = writeln!(out, "<title>{}</title>", escape_html(title));
+    out.push_str(
+        "<style>\n\
+         body { font-family: sans-serif; }\n\
+         table { border-collapse: collapse; margin-bottom: 1em; }\n\
+         th, td { border: 1px solid #ccc; padding: 2px 8px; text-align: left; }\n\
+         .equal { background: #d4f7d4; }\n\
+         .differ, .changed { background: #ffd6d6; }\n\
+         .error { background: #ffe9b3; }\n\
+         .missing, .origin { color: #999; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+    let _ = writeln!(out, "<h1>{}</h1>", escape_html(title));
+
+    out.push_str("<table>\n<tr><th>Input</th><th>Verdict</th><th>First records</th><th>Second records</th><th>Differences</th></tr>\n");
+    for entry in entries {
+        let verdict = verdict(entry.result);
+        let [first, second, keys] = counts(entry.result);
+        let _ = writeln!(
+            out,
+            "<tr><td>{}</td><td class=\"{}\">{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape_html(&entry.name),
+            verdict,
+            verdict,
+            first,
+            second,
+            keys
+        );
+    }
+    out.push_str("</table>\n");
+
+    for entry in entries {
+        match entry.result {
+            Ok(r) if r.diffs.is_empty() => continue,
+            Ok(r) => {
+                let _ = writeln!(out, "<h2>{}</h2>", escape_html(&entry.name));
+                for diff in &r.diffs {
+                    html_diff(&mut out, diff);
+                }
+            },
+            Err(e) => {
+                let _ = writeln!(out, "<h2>{}</h2>", escape_html(&entry.name));
+                let _ = writeln!(out, "<p class=\"error\">{}</p>", escape_html(&describe_error(e)));
+            },
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn html_diff(out: &mut String, diff: &RecordDiff) {
+    out.push_str("<table>\n<tr><th>Field</th><th>First</th><th>Second</th></tr>\n");
+    for (name, first, second) in diff.fields() {
+        let class = if first != second { " class=\"changed\"" } else { "" };
+        let cell = |value: &Option<String>| match value {
+            Some(value) => format!("<td>{}</td>", escape_html(value)),
+            None => "<td class=\"missing\">(no record)</td>".to_string(),
+        };
+        let _ = writeln!(out, "<tr{}><td>{}</td>{}{}</tr>", class, name, cell(&first), cell(&second));
+    }
+    if let Some([first, second]) = origin(diff) {
+        let _ = writeln!(
+            out,
+            "<tr class=\"origin\"><td>ORIGIN</td><td>{}</td><td>{}</td></tr>",
+            escape_html(&first),
+            escape_html(&second)
+        );
+    }
+    out.push_str("</table>\n");
+}
+
+fn render_markdown(title: &str, entries: &[ReportEntry<'_>]) -> String {
+    let mark = |verdict: &str| match verdict {
+        "equal" => "✅ equal",
+        "differ" => "❌ differ",
+        _ => "⚠️ error",
+    };
+
+    let mut out = format!("# {}\n\n", title);
+    out.push_str("| Input | Verdict | First records | Second records | Differences |\n");
+    out.push_str("|---|---|---|---|---|\n");
+    for entry in entries {
+        let [first, second, keys] = counts(entry.result);
+        let _ = writeln!(
+            out,
+            "| {} | {} | {} | {} | {} |",
+            escape_markdown(&entry.name),
+            mark(verdict(entry.result)),
+            first,
+            second,
+            keys
+        );
+    }
+
+    for entry in entries {
+        match entry.result {
+            Ok(r) if r.diffs.is_empty() => continue,
+            Ok(r) => {
+                let _ = write!(out, "\n## {}\n", escape_markdown(&entry.name));
+                for diff in &r.diffs {
+                    out.push_str("\n| Field | First | Second |\n|---|---|---|\n");
+                    for (name, first, second) in diff.fields() {
+                        let cell = |value: &Option<String>| match value {
+                            Some(value) if first != second => format!("**{}**", escape_markdown(value)),
+                            Some(value) => escape_markdown(value),
+                            None => "_(no record)_".to_string(),
+                        };
+                        let _ = writeln!(out, "| {} | {} | {} |", name, cell(&first), cell(&second));
+                    }
+                    if let Some([first, second]) = origin(diff) {
+                        let _ = writeln!(out, "| _ORIGIN_ | _{}_ | _{}_ |", escape_markdown(&first), escape_markdown(&second));
+                    }
+                }
+            },
+            Err(e) => {
+                let _ = write!(out, "\n## {}\n\n{}\n", escape_markdown(&entry.name), escape_markdown(&describe_error(e)));
+            },
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Экранирует то, что ломает ячейку таблицы Markdown.
+fn escape_markdown(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('|', "\\|").replace('\n', "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::data::{Format, Status, TxData, TxType};
+    use crate::model::tx_id::TxId;
+    use crate::parser::options::Provenance;
+
+    fn tx(amount: i64, description: &str) -> TxData {
+        TxData {
+            tx_id: TxId::U64(7),
+            tx_type: TxType::Transfer,
+            from_user_id: 1,
+            to_user_id: 2,
+            amount,
+            timestamp: 1633036860000,
+            status: Status::Success,
+            description: description.into(),
+            format: Format::YpBankCsv,
+        }
+    }
+
+    #[test]
+    fn test_report_highlights_changed_fields() {
+        let differ = Ok(CompareReport {
+            equal: false,
+            first_records: 1,
+            second_records: 0,
+            mismatches: Vec::new(),
+            differing: 2,
+            diffs: vec![
+                RecordDiff {
+                    first: Some(tx(100, "<fee>")),
+                    second: Some(tx(101, "<fee>")),
+                    first_at: Some(Provenance { source: Some("a.csv".into()), record: 3, line: 4, offset: Some(120) }),
+                    second_at: Some(Provenance { source: Some("b.txt".into()), record: 3, line: 19, offset: None }),
+                },
+                RecordDiff { first: Some(tx(5, "a|b")), second: None, first_at: None, second_at: None },
+            ],
+        });
+        let missing = Err(CompareErr::MissingPair { input: 2, name: "b".to_string() });
+        let entries = [
+            ReportEntry { name: "a".to_string(), result: &differ },
+            ReportEntry { name: "b".to_string(), result: &missing },
+        ];
+
+        let html = render_html("primary vs mirror", &entries);
+        assert!(html.contains("<td class=\"differ\">differ</td>"));
+        assert!(html.contains("<tr class=\"changed\"><td>AMOUNT</td><td>100</td><td>101</td></tr>"));
+        assert!(html.contains("<tr><td>DESCRIPTION</td><td>&lt;fee&gt;</td><td>&lt;fee&gt;</td></tr>"));
+        assert!(html.contains("<td class=\"missing\">(no record)</td>"));
+        assert!(html.contains("no file named b in compare input 2"));
+        assert!(html.contains(
+            "<tr class=\"origin\"><td>ORIGIN</td><td>a.csv, record 3, line 4, byte 120</td><td>b.txt, record 3, line 19</td></tr>"
+        ));
+
+        let markdown = render_markdown("primary vs mirror", &entries);
+        assert!(markdown.contains("| a | ❌ differ | 1 | 0 | 2 |"));
+        assert!(markdown.contains("| AMOUNT | **100** | **101** |"));
+        assert!(markdown.contains("| DESCRIPTION | **a\\|b** | _(no record)_ |"));
+        assert!(markdown.contains("| b | ⚠️ error |  |  |  |"));
+
+        let Some(CaseOutcome::Failed { message, details }) = entries.first().map(|entry| junit_outcome(entry.result)) else {
+            panic!("expected a failed case");
+        };
+        assert_eq!(message, "1 vs 0 records, differences: 2");
+        assert_eq!(details, "TX_ID 7: AMOUNT differ\nTX_ID 7: only in the first input\n");
+        assert!(matches!(junit_outcome(&missing), CaseOutcome::Error { .. }));
+    }
+}
@@ -1,31 +1,168 @@
+use crate::compare::DuplicateStrategy;
+use crate::console::i18n::Lang;
+use crate::doctor::FindingsFormat;
+use crate::currency::RateTable;
+#[cfg(feature = "enrich")]
+use crate::enrich::UserReference;
+use crate::extract::UserList;
+use crate::model::amount::AmountLocale;
 use crate::model::data::Format;
+use crate::model::normalize::NormalizeStep;
+use crate::model::time::{parse_duration_ms, parse_time_bound, TimestampUnit};
+use crate::model::tx_id::TxId;
+use crate::parser::io::report::ReportFormat;
+use crate::parser::options::{BinProfile, DescriptionLengthPolicy, EmptyFieldPolicy, MemoryBudget, QuoteStyle};
+use crate::rules::RulePack;
+use crate::schema::SchemaKind;
+#[cfg(feature = "script")]
+use crate::script::TransformScript;
+use crate::spill::SortKey;
+use crate::stats::{Bucket, StatsFormat};
 use clap::{Parser, Subcommand};
-use std::{io::Cursor, path::PathBuf, sync::{Arc, Mutex}};
+use std::io::{stdin, IsTerminal};
+use std::path::PathBuf;
+
+pub use crate::model::resource::Resource;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Дописать в журнал аудита (JSON lines) запись о запуске: входы и выходы с SHA-256, счётчики, итог
+    #[arg(long, global = true)]
+    pub audit_log: Option<PathBuf>,
+
+    /// Записать метрики запуска (записи, байты, длительность, ошибки) в файл Prometheus textfile collector
+    #[arg(long, global = true)]
+    pub metrics_textfile: Option<PathBuf>,
+
+    /// Отправить метрики запуска на statsd-сервер (host:port) по UDP
+    #[arg(long, global = true)]
+    pub metrics_statsd: Option<String>,
+
+    /// Отправить итог запуска (JSON как в журнале аудита, с ошибками по входам) POST-запросом на webhook
+    #[arg(long, global = true)]
+    pub notify_webhook: Option<String>,
+
+    /// Сколько всего попыток отправить уведомление при временных сбоях (нет соединения, таймаут, 429, 5xx)
+    #[arg(long, global = true, default_value_t = 3)]
+    pub notify_retries: u32,
+
+    /// Пауза перед первым повтором уведомления (500ms, 2s); перед каждым следующим она удваивается
+    #[arg(long, global = true, value_parser = parse_duration_ms, default_value = "500ms")]
+    pub notify_backoff: u64,
+
+    /// Язык сообщений; по умолчанию из LC_ALL, LC_MESSAGES или LANG
+    #[arg(long, value_enum, global = true)]
+    pub lang: Option<Lang>,
+
+    /// Прервать чтение 'console' с ошибкой, если на stdin нет данных дольше N секунд
+    #[arg(long, global = true)]
+    pub stdin_timeout: Option<u64>,
+
+    /// Сколько файлов обрабатывать одновременно, если вход — каталог или glob-шаблон
+    /// (конвертация и сравнение каталогов); 0 — по числу ядер
+    #[arg(long, global = true, default_value_t = 1)]
+    pub jobs: usize,
+
+    /// Приблизительный бюджет памяти (512M, 2G), по которому подбираются размеры порций
+    /// и буферов потоковых команд; заданные меньшие --batch-size и --buffer-size сохраняются
+    #[arg(long, global = true, value_parser = MemoryBudget::parse)]
+    pub memory_limit: Option<MemoryBudget>,
+
+    /// Каталог временных файлов (сортировка, сравнение с --spill, замер); по умолчанию системный
+    #[arg(long, global = true)]
+    pub temp_dir: Option<PathBuf>,
+
+    /// Каталог кэша разобранных входов: stats-command, screen-command и compare-command читают
+    /// записи уже разобранного файла (по SHA-256 содержимого) из кэша в бинарном формате
+    #[arg(long, global = true)]
+    pub parse_cache: Option<PathBuf>,
+
+    /// Подключить формат партнёра из WASM-модуля (см. модуль `plugin`) под именем файла без
+    /// расширения; такие форматы принимает plugin-convert-command. Можно указать несколько раз
+    #[cfg(feature = "plugin")]
+    #[arg(long, global = true)]
+    pub plugin: Vec<PathBuf>,
+}
+
+impl Cli {
+    /// Команда читает 'console', а stdin — терминал: данные никто не передаёт,
+    /// и без проверки команда молча ждала бы ввода.
+    pub fn reads_terminal(&self) -> bool {
+        self.command.inputs().iter().any(|input| matches!(input, Resource::Console)) && stdin().is_terminal()
+    }
+
+    /// Повторы уведомления по `--notify-retries` и `--notify-backoff`.
+    #[cfg(feature = "notify")]
+    pub fn notify_retry(&self) -> crate::notify::RetryPolicy {
+        crate::notify::RetryPolicy {
+            attempts: self.notify_retries,
+            backoff: std::time::Duration::from_millis(self.notify_backoff),
+            ..crate::notify::RetryPolicy::default()
+        }
+    }
+
+    /// Число потоков по `--jobs`: 0 — по числу ядер.
+    pub fn worker_threads(&self) -> usize {
+        match self.jobs {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            jobs => jobs,
+        }
+    }
+}
+
+fn parse_ascii_byte(s: &str) -> Result<u8, String> {
+    match s.as_bytes() {
+        [b] if b.is_ascii() => Ok(*b),
+        _ => Err("expected a single ASCII character".into()),
+    }
+}
+
+/// Читает набор правил `--rules` сразу, чтобы ошибка в файле правил была ошибкой аргумента.
+fn parse_rules(s: &str) -> Result<RulePack, String> {
+    RulePack::load(s.as_ref()).map_err(|e| e.to_string())
+}
+
+fn parse_user_list(s: &str) -> Result<UserList, String> {
+    UserList::load(s.as_ref()).map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "enrich")]
+fn parse_user_reference(s: &str) -> Result<UserReference, String> {
+    UserReference::load(s.as_ref()).map_err(|e| e.to_string())
+}
+
+fn parse_rates(s: &str) -> Result<RateTable, String> {
+    RateTable::load(s.as_ref()).map_err(|e| e.to_string())
 }
 
-#[derive(Clone, Debug)]
-pub enum Resource {
-    Console,
-    File { path: PathBuf },
-    Memory { data: Cursor<Vec<u8>> },
+#[cfg(feature = "script")]
+fn parse_transform_script(s: &str) -> Result<TransformScript, String> {
+    TransformScript::load(s.as_ref()).map_err(|e| e.to_string())
 }
 
 fn parse_resource(s: &str) -> Result<Resource, String> {
     if s == "console" {
         Ok(Resource::Console)
     } else if let Some(path) = s.strip_prefix("file:") {
+        #[cfg(feature = "zip")]
+        if let Some((archive, member)) = path.split_once(".zip!") {
+            return Ok(Resource::ZipMember {
+                archive: format!("{}.zip", archive).into(),
+                member: member.to_string(),
+            });
+        }
         Ok(Resource::File { path: path.into() })
     } else {
-        Err("Resource must be 'console' or 'file:<path>'".into())
+        Err("Resource must be 'console', 'file:<path>' or 'file:<archive>.zip!<member>'".into())
     }
 }
 
+// Команда разбирается один раз за запуск, размер варианта с настройками конвертации не важен
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum Commands {
     CompareCommand {
@@ -40,6 +177,80 @@ pub enum Commands {
 
         #[arg(long, required = true)]
         second_format: Format,
+
+        /// Ещё входы, чтобы проверить согласованность трёх и более источников (можно повторять)
+        #[arg(long, value_parser = parse_resource)]
+        also_from: Vec<Resource>,
+
+        /// Форматы входов --also-from в том же порядке
+        #[arg(long, requires = "also_from")]
+        also_format: Vec<Format>,
+
+        /// Сравнивать только записи с TIMESTAMP не раньше метки или даты (YYYY-MM-DD[THH:MM:SS])
+        #[arg(long, value_parser = parse_time_bound)]
+        since: Option<u64>,
+
+        /// Сравнивать только записи с TIMESTAMP раньше метки или даты (не включительно)
+        #[arg(long, value_parser = parse_time_bound)]
+        until: Option<u64>,
+
+        /// Считать равными TIMESTAMP, различающиеся не больше чем на столько (500ms, 2s, 1m)
+        #[arg(long, default_value = "0", value_parser = parse_duration_ms)]
+        timestamp_tolerance: u64,
+
+        /// Считать равными AMOUNT, различающиеся не больше чем на столько единиц
+        #[arg(long, default_value_t = 0)]
+        amount_tolerance: u64,
+
+        /// Как сравнивать повторяющиеся TX_ID
+        #[arg(long, value_enum, default_value_t = DuplicateStrategy::Ordered)]
+        duplicates: DuplicateStrategy,
+
+        /// Приводить описания обоих входов к единому виду перед сравнением (через запятую: trim,collapse,nfc)
+        #[arg(long, value_enum, value_delimiter = ',')]
+        normalize_description: Vec<NormalizeStep>,
+
+        /// Сравнивать только первые N символов описаний (длинные обрезаются с маркером '…')
+        #[arg(long)]
+        max_description_len: Option<usize>,
+
+        /// Записать отчёт о сравнении с различиями по полям: HTML, для расширения .md — Markdown
+        #[arg(long, conflicts_with = "also_from")]
+        report: Option<PathBuf>,
+
+        /// Записать итог по каждой паре файлов в JUnit XML для CI
+        #[arg(long, conflicts_with = "also_from")]
+        junit: Option<PathBuf>,
+
+        /// Сначала сверить контрольные суммы файлов: одинаковые файлы не разбираются,
+        /// у разных показываются различающиеся участки перед полным сравнением
+        #[arg(long, conflicts_with_all = ["also_from", "report", "junit"])]
+        quick: bool,
+
+        /// Сравнивать входы больше памяти: для --duplicates кроме ordered они сортируются
+        /// по TX_ID участками во временных файлах (--temp-dir, размер участка — по --memory-limit)
+        #[arg(long, conflicts_with = "also_from")]
+        spill: bool,
+    },
+
+    /// Сравнить вид двух входов (колонки и ключи, значения TX_TYPE и STATUS, виды TX_ID,
+    /// порядки величин чисел) и вывести дрейф — быстрее сравнения записей, когда меняется выгрузка
+    SchemaDiff {
+        #[arg(long, required = true, value_parser = parse_resource)]
+        first_from: Resource,
+
+        #[arg(long, required = true)]
+        first_format: Format,
+
+        #[arg(long, required = true, value_parser = parse_resource)]
+        second_from: Resource,
+
+        #[arg(long, required = true)]
+        second_format: Format,
+
+        /// Сколько записей читать за один шаг
+        #[arg(long, default_value_t = 10_000)]
+        batch_size: usize,
     },
 
     ReadParseWriteCommand {
@@ -49,10 +260,589 @@ pub enum Commands {
         #[arg(long, required = true)]
         from_format: Format,
 
+        #[arg(long, required_unless_present = "in_place", value_parser = parse_resource)]
+        to: Option<Resource>,
+
+        /// Заменить файл --from результатом (вместо --to): исходный файл сохраняется как
+        /// `<from>.bak`, а результат занимает его место одним переименованием
+        #[arg(long, conflicts_with_all = ["to", "checkpoint", "resume", "skip_processed"])]
+        in_place: bool,
+
+        #[arg(long, required = true)]
+        to_format: Format,
+
+        /// Пропускать некорректные записи вместо остановки на первой ошибке
+        #[arg(long)]
+        lenient: bool,
+
+        /// Куда записать отчёт о пропущенных записях ('console' или 'file:<path>'), включает --lenient
+        #[arg(long, value_parser = parse_resource)]
+        errors_report: Option<Resource>,
+
+        /// Формат отчёта о пропущенных записях
+        #[arg(long, value_enum, default_value_t = ReportFormat::Json)]
+        errors_report_format: ReportFormat,
+
+        /// Считать предупреждения (пустое описание, описание без кавычек и т.п.) ошибками записи
+        #[arg(long)]
+        warnings_as_errors: bool,
+
+        /// Отклонять синтаксически верные, но невозможные записи (нулевой TX_ID, перевод самому себе и т.п.)
+        #[arg(long)]
+        strict: bool,
+
+        /// С --strict отклонять и записи с TIMESTAMP вне интервала (по умолчанию с 2000-01-01
+        /// до суток после текущего момента): так ловятся секунды вместо миллисекунд
+        #[arg(long, requires = "strict")]
+        check_timestamps: bool,
+
+        /// Нижняя граница TIMESTAMP для --check-timestamps: метка или дата (YYYY-MM-DD[THH:MM:SS])
+        #[arg(long, value_parser = parse_time_bound, requires = "check_timestamps")]
+        min_timestamp: Option<u64>,
+
+        /// Верхняя граница TIMESTAMP для --check-timestamps (включительно)
+        #[arg(long, value_parser = parse_time_bound, requires = "check_timestamps")]
+        max_timestamp: Option<u64>,
+
+        /// Дополнительно проверять записи по набору правил из TOML-файла (наибольшие суммы,
+        /// диапазоны ID пользователей, статусы старых записей)
+        #[arg(long, value_parser = parse_rules)]
+        rules: Option<RulePack>,
+
+        /// Оставить только записи, в которых отправитель или получатель есть в файле со списком
+        /// ID пользователей (по одному в строке, `#` — комментарий): выборка для расследования
+        #[arg(long, value_parser = parse_user_list)]
+        users_file: Option<UserList>,
+
+        /// Отбросить записи, в которых отправитель или получатель есть в файле со списком ID
+        #[arg(long, value_parser = parse_user_list)]
+        exclude_users_file: Option<UserList>,
+
+        /// Пересчитать суммы из --from-currency в --to-currency по файлу курсов
+        /// (строки «код курс», курс — стоимость единицы валюты в общей базе)
+        #[arg(long, value_parser = parse_rates, requires_all = ["from_currency", "to_currency"])]
+        rates: Option<RateTable>,
+
+        /// Валюта сумм во входе (записи не хранят валюту)
+        #[arg(long, requires = "rates")]
+        from_currency: Option<String>,
+
+        /// Валюта сумм в результате
+        #[arg(long, requires = "rates")]
+        to_currency: Option<String>,
+
+        /// Дописывать к описанию пересчитанной записи исходную сумму и валюту
+        #[arg(long, requires = "rates")]
+        keep_original_amount: bool,
+
+        /// Сценарий на Rhai, который меняет поля каждой записи (`tx.description = ...`) или
+        /// отбрасывает её (`return false`); выполняется после фильтров пользователей и пересчёта валют
+        #[cfg(feature = "script")]
+        #[arg(long, value_parser = parse_transform_script)]
+        transform_script: Option<TransformScript>,
+
+        /// Что делать с пустым описанием и отсутствующим ключом DESCRIPTION
+        /// (пустые числа, тип и статус — всегда ошибка)
+        #[arg(long, value_enum, default_value_t = EmptyFieldPolicy::AllowEmptyDescription)]
+        empty_field_policy: EmptyFieldPolicy,
+
+        /// Приводить описания к единому виду при чтении (через запятую: trim,collapse,nfc)
+        #[arg(long, value_enum, value_delimiter = ',')]
+        normalize_description: Vec<NormalizeStep>,
+
+        /// Наибольшая длина описания в результате, в символах (например, 255 по требованию получателя)
+        #[arg(long)]
+        max_description_len: Option<usize>,
+
+        /// Что делать с более длинным описанием: обрезать с маркером '…' в конце, остановиться
+        /// с ошибкой или записать как есть с предупреждением
+        #[arg(long, value_enum, default_value_t = DescriptionLengthPolicy::Truncate, requires = "max_description_len")]
+        description_policy: DescriptionLengthPolicy,
+
+        /// Разрешить в текстовом формате `KEY = value`, ключи в любом регистре и псевдонимы (TXID, DESC, …)
+        #[arg(long)]
+        relaxed_text: bool,
+
+        /// Сохранить комментарии `#` и пустые строки текстового файла при записи в текстовый формат
+        #[arg(long)]
+        keep_comments: bool,
+
+        /// Разбирать AMOUNT в CSV как сумму в записи локали (en: 1,000.50; de: 1.000,50; fr: 1 000,50)
+        /// и переводить её в минимальные единицы; без опции AMOUNT — целое число минимальных единиц
+        #[arg(long, value_enum)]
+        amount_locale: Option<AmountLocale>,
+
+        /// Принимать колонки CSV в любом порядке (по заголовку); неизвестные и отсутствующие колонки — ошибка
+        #[arg(long)]
+        csv_reorder_ok: bool,
+
+        /// Отбрасывать колонки CSV после канонических с предупреждением вместо ошибки
+        #[arg(long)]
+        csv_extra_columns: bool,
+
+        /// Пропустить первые N строк CSV (баннер перед заголовком)
+        #[arg(long, default_value_t = 0)]
+        skip_rows: usize,
+
+        /// Считать строки CSV, начинающиеся с этого символа (например '#'), комментариями
+        #[arg(long, value_parser = parse_ascii_byte)]
+        comment_prefix: Option<u8>,
+
+        /// Профиль кодирования бинарного формата при записи
+        #[arg(long, value_enum, default_value_t = BinProfile::BigEndian)]
+        bin_profile: BinProfile,
+
+        /// Сжимать бинарный формат zstd с указанным уровнем (1–22). Блок сжатия (1024 записи)
+        /// набирается из нескольких порций, но закрывается при каждом сбросе вывода (--flush-every)
+        #[arg(long)]
+        bin_zstd_level: Option<i32>,
+
+        /// Дописать в бинарный файл индекс для команды `get`
+        #[arg(long)]
+        bin_index: bool,
+
+        /// Писать бинарный файл с заголовком (версия схемы 2) даже без сжатия и индекса;
+        /// нужно для нечисловых TX_ID
+        #[arg(long)]
+        bin_header: bool,
+
+        /// Хранить частые описания словарём в начале бинарного файла, а в записях — их номера.
+        /// Словарь собирается по первой порции (--batch-size): описаний, которых в ней нет
+        /// или которые встретились там однажды, в словаре не будет
+        #[arg(long)]
+        bin_dictionary: bool,
+
+        /// Дописать в сжатый бинарный файл таблицу блоков для чтения диапазона записей без распаковки предыдущих
+        #[arg(long, requires = "bin_zstd_level")]
+        bin_seekable: bool,
+
+        /// Начинать CSV-результат с UTF-8 BOM (для Excel)
+        #[arg(long)]
+        csv_bom: bool,
+
+        /// Когда заключать поля CSV-результата в кавычки
+        #[arg(long, value_enum, default_value_t = QuoteStyle::Necessary)]
+        csv_quote_style: QuoteStyle,
+
+        /// Добавить к CSV-результату колонки FROM_<АТРИБУТ> и TO_<АТРИБУТ> из справочника
+        /// пользователей (CSV с заголовком: ID, затем атрибуты — имя, сегмент и т.п.)
+        #[cfg(feature = "enrich")]
+        #[arg(long, value_parser = parse_user_reference, conflicts_with = "verify")]
+        join_users: Option<UserReference>,
+
+        /// Единицы TIMESTAMP во входе; метки приводятся к миллисекундам. По умолчанию метки
+        /// не меняются; auto определяет единицы по величине каждой метки: меньше 10^11 — секунды
+        #[arg(long, value_enum, default_value_t = TimestampUnit::Milliseconds)]
+        timestamp_unit: TimestampUnit,
+
+        /// Конвертировать только записи с TIMESTAMP не раньше метки или даты (YYYY-MM-DD[THH:MM:SS])
+        #[arg(long, value_parser = parse_time_bound)]
+        since: Option<u64>,
+
+        /// Конвертировать только записи с TIMESTAMP раньше метки или даты (не включительно)
+        #[arg(long, value_parser = parse_time_bound)]
+        until: Option<u64>,
+
+        /// Пропустить первые N записей входа
+        #[arg(long, default_value_t = 0)]
+        skip: usize,
+
+        /// Записать не больше M записей (после --skip) и не читать вход дальше
+        #[arg(long)]
+        limit: Option<usize>,
+
+        /// Сколько записей читать и записывать за один шаг
+        #[arg(long, default_value_t = 10_000)]
+        batch_size: usize,
+
+        /// Сбрасывать вывод на диск каждые N порций (0 — только в конце)
+        #[arg(long, default_value_t = 1)]
+        flush_every: usize,
+
+        /// Сбрасывать вывод, как только записано N записей с прошлого сброса (проверяется после порции)
+        #[arg(long)]
+        flush_interval_records: Option<usize>,
+
+        /// Размер буфера вывода в байтах (по умолчанию 64 KiB)
+        #[arg(long)]
+        buffer_size: Option<usize>,
+
+        /// Писать не быстрее N записей в секунду (на все --jobs вместе), например при выводе
+        /// в console, который передаётся в сервис приёма данных; уменьшает порцию и сбрасывает вывод после каждой
+        #[arg(long, value_name = "RECORDS_PER_SEC", value_parser = clap::value_parser!(u64).range(1..))]
+        max_rate: Option<u64>,
+
+        /// Показать в stderr таблицу из N случайных записей результата (равномерная выборка по всему
+        /// входу): на долгой конвертации — примерно через 2 секунды после начала, иначе в конце
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        preview: Option<u64>,
+
+        /// Записать рядом с каждым файлом результата `<файл>.manifest.json`: число записей, SHA-256,
+        /// первый и последний TX_ID, диапазон времени, формат и версию конвертера
+        #[arg(long)]
+        emit_manifest: bool,
+
+        /// Записать в начало результата сведения о конвертере: версию, время записи и SHA-256 входа
+        /// (строки `#@` в CSV и тексте, блок после заголовка в бинарном файле)
+        #[arg(long)]
+        producer_metadata: bool,
+
+        /// Дописать записи в конец существующего бинарного файла --to (профиль, сжатие и индекс
+        /// берутся из файла, индекс переписывается); нет файла — он создаётся
+        #[arg(long, conflicts_with_all = ["in_place", "checkpoint", "resume", "verify"])]
+        append: bool,
+
+        /// С --append пропускать записи, TX_ID которых уже есть в файле (по индексу, а без него —
+        /// прочитав файл): повторная доставка от источника «хотя бы один раз» не даёт повторов
+        #[arg(long, requires = "append")]
+        if_absent: bool,
+
+        /// Для нескольких выходных файлов (шаблон --to, каталог входов): писать их во временные
+        /// файлы и переносить на место, только если записаны все; иначе прежние файлы не меняются
+        #[arg(long)]
+        transactional: bool,
+
+        /// Остановиться с ошибкой, если результат теряет часть входа (нечисловые TX_ID в бинарном
+        /// файле без заголовка, комментарии, лишние колонки CSV); без флага — предупреждение
+        /// о каждой затронутой записи
+        #[arg(long)]
+        no_lossy: bool,
+
+        /// Сохранять контрольную точку `<to>.checkpoint` при каждом сбросе вывода (только для файлов)
+        #[arg(long)]
+        checkpoint: bool,
+
+        /// Продолжить прерванную конвертацию с контрольной точки `<to>.checkpoint`
+        #[arg(long, conflicts_with = "checkpoint")]
+        resume: bool,
+
+        /// Выводить предупреждения и итоги по файлам в порядке входных файлов, а не по мере готовности
+        /// (записи в результатах всегда идут в порядке входа)
+        #[arg(long)]
+        stable_order: bool,
+
+        /// Перечитать результат после записи и сверить его записи со входом; расхождение — ошибка
+        /// (вход и выход должны быть файлами)
+        #[arg(long)]
+        verify: bool,
+
+        /// Записать итог по каждому входному файлу в JUnit XML для CI: файл с пропущенными
+        /// записями — failure, неудавшаяся конвертация — error
+        #[arg(long)]
+        junit: Option<PathBuf>,
+
+        /// Файл состояния с SHA-256 уже сконвертированных входов: вход, не изменившийся
+        /// с прошлой успешной конвертации в тот же выход, пропускается (только для файлов)
+        #[arg(long)]
+        skip_processed: Option<PathBuf>,
+    },
+
+    /// Перевести файл, записанный старой версией, на текущую версию схемы формата
+    MigrateCommand {
+        #[arg(long, required = true, value_parser = parse_resource)]
+        from: Resource,
+
+        #[arg(long, required = true)]
+        format: Format,
+
+        /// Куда записать результат; без --to только показать версию схемы --from
+        #[arg(long, value_parser = parse_resource)]
+        to: Option<Resource>,
+    },
+
+    /// Сконвертировать между форматами по именам: встроенными (yp-bank-csv и т.п.) и подключёнными
+    /// через --plugin. Вход читается целиком
+    #[cfg(feature = "plugin")]
+    PluginConvertCommand {
+        #[arg(long, required = true, value_parser = parse_resource)]
+        from: Resource,
+
+        /// Имя формата входа
+        #[arg(long, required = true)]
+        from_format: String,
+
+        #[arg(long, required = true, value_parser = parse_resource)]
+        to: Resource,
+
+        /// Имя формата результата
+        #[arg(long, required = true)]
+        to_format: String,
+
+        /// Пропускать некорректные записи вместо остановки на первой ошибке
+        #[arg(long)]
+        lenient: bool,
+    },
+
+    /// Записать дельту (добавленные, изменённые и удалённые по TX_ID записи) от --base к --current
+    Diff {
+        #[arg(long, required = true, value_parser = parse_resource)]
+        base: Resource,
+
+        #[arg(long, required = true)]
+        base_format: Format,
+
+        #[arg(long, required = true, value_parser = parse_resource)]
+        current: Resource,
+
+        #[arg(long, required = true)]
+        current_format: Format,
+
+        /// Куда записать файл дельты
+        #[arg(long, value_parser = parse_resource, default_value = "console")]
+        to: Resource,
+    },
+
+    /// Применить дельту, записанную командой `diff`, к снимку --base
+    Apply {
+        #[arg(long, required = true, value_parser = parse_resource)]
+        base: Resource,
+
+        #[arg(long, required = true)]
+        base_format: Format,
+
+        /// Файл дельты
+        #[arg(long, required = true, value_parser = parse_resource)]
+        delta: Resource,
+
+        #[arg(long, required = true, value_parser = parse_resource)]
+        to: Resource,
+
+        #[arg(long, required = true)]
+        to_format: Format,
+    },
+
+    /// Собрать архив .tar или .zip из файлов транзакций с манифестом (формат файла — по расширению)
+    Bundle {
+        /// Файлы для архива (.csv, .txt, .bin)
+        #[arg(long, required = true, num_args = 1..)]
+        files: Vec<PathBuf>,
+
+        /// Путь архива; вид архива — по расширению .tar или .zip
+        #[arg(long, required = true)]
+        to: PathBuf,
+    },
+
+    /// Проверить архив по манифесту и распаковать его файлы
+    Unbundle {
+        #[arg(long, required = true)]
+        from: PathBuf,
+
+        /// Каталог для файлов архива; без --to архив только проверяется
+        #[arg(long)]
+        to: Option<PathBuf>,
+    },
+
+    /// Найти транзакцию по TX_ID (в бинарном файле с индексом — без чтения всего файла)
+    Get {
+        #[arg(long, required = true, value_parser = parse_resource)]
+        from: Resource,
+
+        #[arg(long, required = true)]
+        from_format: Format,
+
+        /// TX_ID искомой транзакции: число, UUID или строка
+        #[arg(long, required = true)]
+        id: TxId,
+
+        #[arg(long, value_parser = parse_resource, default_value = "console")]
+        to: Resource,
+
+        #[arg(long, default_value = "yp-bank-text")]
+        to_format: Format,
+    },
+
+    /// Проверить файл всеми эвристиками (кодировка, заголовок, кавычки, повторы TX_ID,
+    /// метки времени, длины описаний) и вывести находки по важности с исправлениями
+    DoctorCommand {
+        #[arg(long, required = true, value_parser = parse_resource)]
+        from: Resource,
+
+        #[arg(long, required = true)]
+        from_format: Format,
+
+        /// Записать находки в файл отчёта для систем контроля качества данных
+        #[arg(long, value_parser = parse_resource)]
+        report: Option<Resource>,
+
+        /// JSON стабильной схемы (rule_id, severity, locator, message) или SARIF 2.1.0
+        #[arg(long, value_enum, default_value_t = FindingsFormat::Json)]
+        report_format: FindingsFormat,
+    },
+
+    /// Найти признаки подозрительной активности (дробление сумм чуть ниже порога, быстрый
+    /// оборот средств) и вывести пользователей по убыванию оценки
+    ScreenCommand {
+        #[arg(long, required = true, value_parser = parse_resource)]
+        from: Resource,
+
+        #[arg(long, required = true)]
+        from_format: Format,
+
+        /// Порог отчётности в минимальных единицах суммы; операции чуть ниже него — признак дробления
+        #[arg(long, default_value_t = 1_000_000)]
+        threshold: i64,
+
+        /// Окно, в котором ищутся операции чуть ниже порога (1h, 168h)
+        #[arg(long, default_value = "168h", value_parser = parse_duration_ms)]
+        structuring_window: u64,
+
+        /// Сколько может пройти от поступления до вывода средств (30m, 24h)
+        #[arg(long, default_value = "24h", value_parser = parse_duration_ms)]
+        cycle_window: u64,
+
+        /// Выводить только пользователей с оценкой не ниже этой
+        #[arg(long, default_value_t = 0)]
+        min_score: u32,
+
+        /// Записать оценки с находками в файл отчёта
+        #[arg(long, value_parser = parse_resource)]
+        report: Option<Resource>,
+
+        #[arg(long, value_enum, default_value_t = ReportFormat::Json)]
+        report_format: ReportFormat,
+    },
+
+    /// Вывести крупнейших отправителей, получателей и записи, число и сумму записей по часам
+    /// или дням, гистограмму сумм; файл читается порциями, поэтому команда работает и с файлами
+    /// больше оперативной памяти
+    #[command(group = clap::ArgGroup::new("reports").required(true).multiple(true))]
+    StatsCommand {
+        #[arg(long, required = true, value_parser = parse_resource)]
+        from: Resource,
+
+        #[arg(long, required = true)]
+        from_format: Format,
+
+        /// Сколько пользователей с наибольшей суммой исходящих операций вывести
+        #[arg(long, value_name = "N", group = "reports")]
+        top_senders: Option<usize>,
+
+        /// Сколько пользователей с наибольшей суммой входящих операций вывести
+        #[arg(long, value_name = "N", group = "reports")]
+        top_receivers: Option<usize>,
+
+        /// Сколько записей с наибольшим AMOUNT вывести
+        #[arg(long, value_name = "N", group = "reports")]
+        largest: Option<usize>,
+
+        /// Число и сумма записей по часам или дням; пустые интервалы показывают пропуски в выгрузке
+        #[arg(long, value_enum, group = "reports")]
+        series: Option<Bucket>,
+
+        /// Гистограмма AMOUNT по десятичным порядкам
+        #[arg(long, group = "reports")]
+        histogram: bool,
+
+        /// Вид вывода: text (спарклайн и столбцы для терминала) или csv
+        #[arg(long, value_enum, default_value_t = StatsFormat::Text)]
+        output_format: StatsFormat,
+
+        /// Сколько записей читать за один шаг
+        #[arg(long, default_value_t = 10_000)]
+        batch_size: usize,
+    },
+
+    /// Вывести описание форматов для интеграторов: JSON Schema записи (CSV и текстовый формат),
+    /// раскладку байтов бинарного формата или таблицу возможностей форматов
+    Schema {
+        #[arg(long, value_enum, default_value_t = SchemaKind::Json)]
+        kind: SchemaKind,
+
+        /// Профиль бинарного формата для --kind bin
+        #[arg(long, value_enum, default_value_t = BinProfile::BigEndian)]
+        bin_profile: BinProfile,
+
+        #[arg(long, value_parser = parse_resource, default_value = "console")]
+        to: Resource,
+    },
+
+    /// Записать корпус для проверки совместимости реализаций форматов: корректные файлы
+    /// с крайними значениями, длинами и Unicode, испорченные файлы и манифест ожидаемых итогов
+    GenConformance {
+        /// Каталог корпуса (подкаталоги valid/ и invalid/, manifest.json); создаётся при необходимости
+        #[arg(long, required = true)]
+        to: PathBuf,
+
+        /// Форматы корпуса через запятую; по умолчанию все
+        #[arg(long, value_delimiter = ',', default_value = "yp-bank-csv,yp-bank-text,yp-bank-bin")]
+        formats: Vec<Format>,
+    },
+
+    /// Упорядочить записи по TX_ID или TIMESTAMP и при необходимости убрать повторы TX_ID;
+    /// вход больше памяти сортируется участками во временных файлах (--temp-dir) и сливается
+    Sort {
+        #[arg(long, required = true, value_parser = parse_resource)]
+        from: Resource,
+
+        #[arg(long, required = true)]
+        from_format: Format,
+
         #[arg(long, required = true, value_parser = parse_resource)]
         to: Resource,
 
         #[arg(long, required = true)]
         to_format: Format,
+
+        /// Поле, по которому упорядочить записи; записи с равным полем остаются в порядке входа
+        #[arg(long, value_enum, default_value_t = SortKey::TxId)]
+        by: SortKey,
+
+        /// Оставить из записей с одним TX_ID только первую во входе
+        #[arg(long)]
+        dedupe: bool,
+
+        /// Сколько записей сортировать в памяти, прежде чем сбросить участок во временный файл
+        #[arg(long, default_value_t = 1_000_000)]
+        run_size: usize,
     },
+
+    /// Замерить скорость: синтетический файл конвертируется между всеми парами форматов,
+    /// для каждой выводятся записи/с и МБ/с — чтобы сравнивать машины и версии
+    Bench {
+        /// Сколько записей в синтетическом файле
+        #[arg(long, default_value_t = 1_000_000)]
+        records: usize,
+
+        /// Форматы замера через запятую; по умолчанию все
+        #[arg(long, value_delimiter = ',', default_value = "yp-bank-csv,yp-bank-text,yp-bank-bin")]
+        formats: Vec<Format>,
+
+        /// Каталог для временных файлов замера; по умолчанию --temp-dir или системный каталог временных файлов
+        #[arg(long)]
+        dir: Option<PathBuf>,
+
+        /// Сколько записей читать и записывать за один шаг
+        #[arg(long, default_value_t = 10_000)]
+        batch_size: usize,
+    },
+}
+
+impl Commands {
+    /// Ресурсы, которые команда читает.
+    pub fn inputs(&self) -> Vec<&Resource> {
+        match self {
+            Commands::CompareCommand {
+                first_from,
+                second_from,
+                also_from,
+                ..
+            } => [first_from, second_from].into_iter().chain(also_from).collect(),
+            Commands::SchemaDiff { first_from, second_from, .. } => vec![first_from, second_from],
+            Commands::ReadParseWriteCommand { from, .. }
+            | Commands::MigrateCommand { from, .. }
+            | Commands::Get { from, .. }
+            | Commands::DoctorCommand { from, .. }
+            | Commands::ScreenCommand { from, .. }
+            | Commands::StatsCommand { from, .. }
+            | Commands::Sort { from, .. } => vec![from],
+            #[cfg(feature = "plugin")]
+            Commands::PluginConvertCommand { from, .. } => vec![from],
+            Commands::Diff { base, current, .. } => vec![base, current],
+            Commands::Apply { base, delta, .. } => vec![base, delta],
+            Commands::Bundle { .. } | Commands::Unbundle { .. } | Commands::Schema { .. } | Commands::GenConformance { .. }
+            | Commands::Bench { .. } => {
+                Vec::new()
+            },
+        }
+    }
 }
@@ -0,0 +1,164 @@
+//! Каталог сообщений CLI на английском и русском (Fluent, `lib/locales/*.ftl`).
+//!
+//! Язык выбирается один раз при запуске: `--lang` или переменные окружения локали
+//! ([`Lang::from_env`]); затем сообщения берутся через [`tr`] и [`tr_args`].
+//! Тексты ошибок разбора остаются английскими: по ним ищут в журналах и отчётах.
+
+use std::sync::{OnceLock, RwLock};
+
+use clap::ValueEnum;
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use unic_langid::LanguageIdentifier;
+
+/// Язык сообщений CLI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Lang {
+    #[default]
+    En,
+    Ru,
+}
+
+impl Lang {
+    /// Язык из локали окружения: первая непустая из `LC_ALL`, `LC_MESSAGES`, `LANG`;
+    /// `ru*` — русский, всё остальное (в том числе `C`) — английский.
+    pub fn from_env() -> Lang {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty());
+        match locale {
+            Some(locale) if locale.to_ascii_lowercase().starts_with("ru") => Lang::Ru,
+            _ => Lang::En,
+        }
+    }
+
+    fn source(self) -> (&'static str, &'static str) {
+        match self {
+            Lang::En => ("en", include_str!("../../locales/en.ftl")),
+            Lang::Ru => ("ru", include_str!("../../locales/ru.ftl")),
+        }
+    }
+}
+
+/// Значение подстановки в сообщение: числа участвуют в выборе формы множественного числа.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MsgArg {
+    Text(String),
+    Number(f64),
+}
+
+impl From<&str> for MsgArg {
+    fn from(value: &str) -> Self {
+        MsgArg::Text(value.to_string())
+    }
+}
+
+impl From<String> for MsgArg {
+    fn from(value: String) -> Self {
+        MsgArg::Text(value)
+    }
+}
+
+impl From<usize> for MsgArg {
+    fn from(value: usize) -> Self {
+        MsgArg::Number(value as f64)
+    }
+}
+
+impl From<u64> for MsgArg {
+    fn from(value: u64) -> Self {
+        MsgArg::Number(value as f64)
+    }
+}
+
+static LANG: RwLock<Lang> = RwLock::new(Lang::En);
+
+/// Выбирает язык сообщений для всего процесса.
+pub fn set_lang(lang: Lang) {
+    *LANG.write().unwrap_or_else(|e| e.into_inner()) = lang;
+}
+
+/// Текущий язык сообщений.
+pub fn lang() -> Lang {
+    *LANG.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn bundle(lang: Lang) -> &'static FluentBundle<FluentResource> {
+    static BUNDLES: [OnceLock<FluentBundle<FluentResource>>; 2] = [OnceLock::new(), OnceLock::new()];
+    BUNDLES[lang as usize].get_or_init(|| {
+        let (id, source) = lang.source();
+        let langid: LanguageIdentifier = id.parse().expect("valid language id");
+        let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+        // Без символов изоляции направления вокруг подстановок: вывод читают в терминале и скриптами
+        bundle.set_use_isolating(false);
+        let resource = FluentResource::try_new(source.to_string()).expect("valid catalog");
+        bundle.add_resource(resource).expect("unique message ids");
+        bundle
+    })
+}
+
+/// Сообщение `id` на текущем языке.
+pub fn tr(id: &str) -> String {
+    tr_args(id, &[])
+}
+
+/// Сообщение `id` на текущем языке с подстановками `{ $name }`.
+///
+/// Сообщение, которого нет в каталоге текущего языка, берётся из английского;
+/// если нет и там — возвращается сам `id`.
+pub fn tr_args(id: &str, args: &[(&str, MsgArg)]) -> String {
+    tr_in(lang(), id, args)
+}
+
+fn tr_in(lang: Lang, id: &str, args: &[(&str, MsgArg)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        let value = match value {
+            MsgArg::Text(text) => FluentValue::from(text.as_str()),
+            MsgArg::Number(number) => FluentValue::from(*number),
+        };
+        fluent_args.set(*name, value);
+    }
+
+    for lang in [lang, Lang::En] {
+        let bundle = bundle(lang);
+        if let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) {
+            let mut errors = Vec::new();
+            return bundle.format_pattern(pattern, Some(&fluent_args), &mut errors).into_owned();
+        }
+    }
+    id.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(lang: Lang) -> Vec<&'static str> {
+        let (_, source) = lang.source();
+        let mut ids: Vec<&str> = source
+            .lines()
+            .filter_map(|line| line.split_once(" = ").map(|(id, _)| id))
+            .filter(|id| id.starts_with(|c: char| c.is_ascii_lowercase()))
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_catalogs_have_same_messages_and_plurals() {
+        assert_eq!(ids(Lang::En), ids(Lang::Ru));
+
+        let records = |lang: Lang, n: usize| tr_in(lang, "convert-summary-read", &[("records", n.into())]);
+        assert_eq!(records(Lang::Ru, 1), "прочитано 1 запись");
+        assert_eq!(records(Lang::Ru, 3), "прочитано 3 записи");
+        assert_eq!(records(Lang::Ru, 11), "прочитано 11 записей");
+        assert_eq!(records(Lang::En, 3), "read 3 records");
+        assert_eq!(tr_in(Lang::Ru, "no-such-message", &[]), "no-such-message");
+        assert_eq!(
+            tr_in(Lang::En, "compare-input", &[("resource", "console".into()), ("format", "YpBankCsv".into())]),
+            "  Input: console (format: YpBankCsv)"
+        );
+    }
+}
@@ -1 +1,3 @@
-pub mod commands;
\ No newline at end of file
+pub mod commands;
+pub mod i18n;
+pub mod render;
@@ -0,0 +1,213 @@
+//! Понятный человеку вывод ошибок CLI: строка входа с ошибкой, подчёркнутое поле и цвет.
+//!
+//! ```text
+//! error: Invalid STATUS: SUCCES
+//!  --> records.csv:4:68
+//!   |
+//! 4 | 1000000000000002,WITHDRAWAL,599094029349995112,0,300,1633036980000,SUCCES,"Record number 3"
+//!   |                                                                    ^^^^^^ STATUS
+//! ```
+
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::Path;
+
+use anstyle::{AnsiColor, Style};
+
+use crate::console::i18n::tr;
+use crate::model::data::Format;
+use crate::model::errors::ParserErr;
+use crate::model::resource::Resource;
+
+const ERROR: Style = AnsiColor::Red.on_default().bold();
+const GUTTER: Style = AnsiColor::Blue.on_default().bold();
+const CARET: Style = AnsiColor::Red.on_default().bold();
+
+/// Раскрашивать ли вывод в stderr: только в терминал и без переменной `NO_COLOR`
+/// (см. <https://no-color.org>).
+pub fn color_enabled() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && std::io::stderr().is_terminal()
+}
+
+/// Отрисовывает ошибку команды, прочитавшей `input` в формате `format`.
+///
+/// Если в цепочке причин есть ошибка разбора с номером строки, а `input` — файл CSV
+/// или текстового формата, к сообщению добавляется эта строка с подчёркнутым полем.
+pub fn render_error(err: &(dyn Error + 'static), input: Option<&Resource>, format: Format, color: bool) -> String {
+    let paint = |style: Style, text: &str| {
+        if color {
+            format!("{}{}{}", style.render(), text, style.render_reset())
+        } else {
+            text.to_string()
+        }
+    };
+
+    let mut chain = vec![err];
+    while let Some(source) = chain[chain.len() - 1].source() {
+        chain.push(source);
+    }
+    let Some(parse_err) = chain.iter().find_map(|e| e.downcast_ref::<ParserErr>()) else {
+        // Ошибки без строки входа: вся цепочка причин в одну строку
+        let message: Vec<String> = chain.iter().map(|e| e.to_string()).collect();
+        return format!("{}: {}\n", paint(ERROR, &tr("error")), message.join(": "));
+    };
+
+    let mut out = match parse_err.root() {
+        // У ошибки ввода-вывода суть — в исходной ошибке (например, истекло ожидание stdin)
+        ParserErr::Io { source, .. } => format!("{}: {}: {}\n", paint(ERROR, &tr("error")), parse_err.root(), source),
+        root => format!("{}: {}\n", paint(ERROR, &tr("error")), root),
+    };
+    let path = match input {
+        Some(Resource::File { path }) => path.as_path(),
+        _ => return out,
+    };
+    let Some(line) = parse_err.line() else {
+        return out;
+    };
+    if format == Format::YpBankBin {
+        let _ = writeln!(out, "  {} {}: record {}", paint(GUTTER, "-->"), path.display(), line);
+        return out;
+    }
+    let Some(excerpt) = excerpt(path, line, format, parse_err.field()) else {
+        let _ = writeln!(out, "  {} {}:{}", paint(GUTTER, "-->"), path.display(), line);
+        return out;
+    };
+
+    let number = excerpt.line.to_string();
+    let pad = " ".repeat(number.len());
+    let (start, len) = excerpt.span;
+    let column = excerpt.text[..start].chars().count();
+    let _ = writeln!(out, "{}{} {}:{}:{}", pad, paint(GUTTER, "-->"), path.display(), excerpt.line, column + 1);
+    let _ = writeln!(out, "{} {}", pad, paint(GUTTER, "|"));
+    let _ = writeln!(out, "{} {} {}", paint(GUTTER, &number), paint(GUTTER, "|"), excerpt.text);
+    let carets = "^".repeat(excerpt.text[start..start + len].chars().count().max(1));
+    let label = parse_err.field().map_or(String::new(), |field| format!(" {}", field));
+    let _ = writeln!(
+        out,
+        "{} {} {}{}",
+        pad,
+        paint(GUTTER, "|"),
+        " ".repeat(column),
+        paint(CARET, &format!("{}{}", carets, label))
+    );
+    out
+}
+
+/// Строка входа с ошибкой и байтовый диапазон подчёркиваемого значения в ней.
+struct Excerpt {
+    line: usize,
+    text: String,
+    span: (usize, usize),
+}
+
+fn excerpt(path: &Path, line: usize, format: Format, field: Option<&str>) -> Option<Excerpt> {
+    let content = fs::read_to_string(path).ok()?;
+    let content = content.strip_prefix('\u{feff}').unwrap_or(&content);
+    let lines: Vec<&str> = content.lines().collect();
+    let whole = |line: usize| {
+        let text = lines.get(line - 1)?.to_string();
+        let span = (0, text.len());
+        Some(Excerpt { line, text, span })
+    };
+    let Some(field) = field else {
+        return whole(line);
+    };
+
+    match format {
+        Format::YpBankCsv => {
+            let column = csv_fields(lines.first()?).into_iter().position(|(start, len)| {
+                lines[0][start..start + len].trim_matches('"') == field
+            })?;
+            let text = lines.get(line - 1)?.to_string();
+            let span = csv_fields(&text).get(column).copied().unwrap_or((0, text.len()));
+            Some(Excerpt { line, text, span })
+        },
+        // Текстовый формат сообщает первую строку записи; ищем строку с ключом поля до конца записи
+        _ => {
+            let found = (line..=lines.len())
+                .take_while(|&n| !lines[n - 1].trim().is_empty() || n == line)
+                .find(|&n| lines[n - 1].split([':', '=']).next().is_some_and(|key| key.trim() == field));
+            let Some(found) = found else {
+                return whole(line);
+            };
+            let text = lines[found - 1].to_string();
+            let value = text.find([':', '=']).map_or(0, |sep| {
+                sep + 1 + text[sep + 1..].len() - text[sep + 1..].trim_start().len()
+            });
+            let span = (value, text.len() - value);
+            Some(Excerpt { line: found, text, span })
+        },
+    }
+}
+
+/// Байтовые диапазоны полей строки CSV с учётом кавычек.
+fn csv_fields(line: &str) -> Vec<(usize, usize)> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                fields.push((start, i - start));
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    fields.push((start, line.len() - start));
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::errors::CommonErr;
+
+    #[test]
+    fn test_csv_error_points_at_field() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.csv");
+        fs::write(
+            &path,
+            "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+             1,DEPOSIT,0,10,100,1700000000,SUCCES,\"a, b\"\n",
+        )
+        .unwrap();
+        let err = CommonErr::Parser(
+            ParserErr::InvalidValue {
+                field: "STATUS",
+                value: "SUCCES".to_string(),
+            }
+            .at_line(2),
+        );
+
+        let out = render_error(&err, Some(&Resource::File { path: path.clone() }), Format::YpBankCsv, false);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "error: Invalid STATUS: SUCCES");
+        assert!(lines[1].ends_with("bad.csv:2:31"));
+        assert_eq!(lines[3], "2 | 1,DEPOSIT,0,10,100,1700000000,SUCCES,\"a, b\"");
+        assert_eq!(lines[4], "  |                               ^^^^^^ STATUS");
+        assert!(!out.contains('\u{1b}'));
+
+        let colored = render_error(&err, None, Format::YpBankCsv, true);
+        assert!(colored.contains('\u{1b}'));
+    }
+
+    #[test]
+    fn test_text_error_points_at_key_of_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.txt");
+        fs::write(&path, "# Record 1\nTX_ID: 1\nAMOUNT: x200\nSTATUS: SUCCESS\n").unwrap();
+        let err = ParserErr::InvalidValue {
+            field: "AMOUNT",
+            value: "x200".to_string(),
+        }
+        .at_line(2);
+
+        let out = render_error(&err, Some(&Resource::File { path }), Format::YpBankText, false);
+        assert!(out.contains("3 | AMOUNT: x200\n  |         ^^^^ AMOUNT\n"));
+    }
+}
@@ -0,0 +1,185 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::parser::{ReadCheckpoint, ReadOptions, WriteCheckpoint};
+
+/// Состояние долгой конвертации, сохраняемое после каждого сброса вывода.
+///
+/// Хранится рядом с выходным файлом в виде строк `KEY: value`. Данные до контрольной
+/// точки уже лежат во временном файле `part`; при продолжении разбор входа начинается
+/// с места `input`, а временный файл дописывается с места `output.bytes`. Если место во входе
+/// неизвестно (сжатый бинарный файл, контрольная точка старой версии), `input_batches` порций
+/// входа читаются заново и пропускаются.
+///
+/// Продолжить можно только тот же вход: его путь, размер и время изменения сверяются
+/// с сохранёнными в `source`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Какой вход конвертировался; нет у контрольных точек старых версий
+    pub source: Option<InputIdentity>,
+    /// Временный файл, в который пишется результат до завершения конвертации
+    pub part: PathBuf,
+    /// Размер порции, с которым читался вход (смещение входа считается в порциях)
+    pub batch_size: usize,
+    /// Сколько порций входа уже прочитано и записано
+    pub input_batches: usize,
+    /// Докуда прочитан вход, если с этого места можно продолжить разбор
+    pub input: Option<ReadCheckpoint>,
+    /// Сколько записей пропущено из-за ошибок до контрольной точки
+    pub skipped: usize,
+    /// Сколько предупреждений выдано до контрольной точки
+    pub warnings: usize,
+    /// Сколько результата записано
+    pub output: WriteCheckpoint,
+}
+
+/// Путь, размер и время изменения входного файла.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputIdentity {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Время изменения в миллисекундах от начала эпохи Unix
+    pub modified_ms: u128,
+}
+
+impl InputIdentity {
+    /// Снимает путь, размер и время изменения файла `path`.
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Ok(InputIdentity {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            modified_ms: modified.as_millis(),
+        })
+    }
+}
+
+impl Checkpoint {
+    /// Путь файла контрольной точки для выходного файла `to`.
+    pub fn path_for(to: &Path) -> PathBuf {
+        with_suffix(to, ".checkpoint")
+    }
+
+    /// Путь временного файла с результатом для выходного файла `to`.
+    pub fn part_for(to: &Path) -> PathBuf {
+        with_suffix(to, ".part")
+    }
+
+    /// Читает контрольную точку; `Ok(None)`, если файла нет.
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Self::parse(&content).map(Some).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("malformed checkpoint {}", path.display()))
+        })
+    }
+
+    /// Сохраняет контрольную точку: пишет во временный файл и переименовывает его,
+    /// чтобы после падения на диске оставалась целая предыдущая или новая версия.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp = with_suffix(path, ".tmp");
+        fs::write(&tmp, self.to_text())?;
+        fs::rename(&tmp, path)
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = format!(
+            "PART: {}\n\
+             BATCH_SIZE: {}\n\
+             INPUT_BATCHES: {}\n\
+             SKIPPED: {}\n\
+             WARNINGS: {}\n\
+             OUTPUT_BYTES: {}\n\
+             OUTPUT_RECORDS: {}\n\
+             AFTER_RECORD: {}\n",
+            self.part.display(),
+            self.batch_size,
+            self.input_batches,
+            self.skipped,
+            self.warnings,
+            self.output.bytes,
+            self.output.records,
+            self.output.after_record,
+        );
+        if let Some(source) = &self.source {
+            text.push_str(&format!(
+                "SOURCE_PATH: {}\n\
+                 SOURCE_SIZE: {}\n\
+                 SOURCE_MODIFIED_MS: {}\n",
+                source.path.display(),
+                source.size,
+                source.modified_ms,
+            ));
+        }
+        if let Some(input) = &self.input {
+            text.push_str(&format!(
+                "INPUT_OFFSET: {}\n\
+                 INPUT_LINES: {}\n\
+                 INPUT_RECORDS: {}\n\
+                 READ_SKIP: {}\n",
+                input.offset, input.lines, input.records, input.window.skip,
+            ));
+            if let Some(limit) = input.window.limit {
+                text.push_str(&format!("READ_LIMIT: {}\n", limit));
+            }
+        }
+        text
+    }
+
+    fn parse(content: &str) -> Option<Self> {
+        let get = |key: &str| {
+            content
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix(": "))
+        };
+
+        // Контрольные точки старых версий не хранят место во входе
+        let input = match get("INPUT_OFFSET") {
+            Some(offset) => Some(ReadCheckpoint {
+                offset: offset.parse().ok()?,
+                lines: get("INPUT_LINES")?.parse().ok()?,
+                records: get("INPUT_RECORDS")?.parse().ok()?,
+                window: ReadOptions {
+                    skip: get("READ_SKIP")?.parse().ok()?,
+                    limit: get("READ_LIMIT").map(str::parse).transpose().ok()?,
+                },
+            }),
+            None => None,
+        };
+        let source = match get("SOURCE_PATH") {
+            Some(path) => Some(InputIdentity {
+                path: path.into(),
+                size: get("SOURCE_SIZE")?.parse().ok()?,
+                modified_ms: get("SOURCE_MODIFIED_MS")?.parse().ok()?,
+            }),
+            None => None,
+        };
+
+        Some(Checkpoint {
+            source,
+            part: get("PART")?.into(),
+            batch_size: get("BATCH_SIZE")?.parse().ok()?,
+            input_batches: get("INPUT_BATCHES")?.parse().ok()?,
+            input,
+            skipped: get("SKIPPED")?.parse().ok()?,
+            warnings: get("WARNINGS")?.parse().ok()?,
+            output: WriteCheckpoint {
+                bytes: get("OUTPUT_BYTES")?.parse().ok()?,
+                records: get("OUTPUT_RECORDS")?.parse().ok()?,
+                after_record: get("AFTER_RECORD")?.parse().ok()?,
+            },
+        })
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    name.into()
+}
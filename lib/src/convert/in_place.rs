@@ -0,0 +1,92 @@
+//! Конвертация файла на месте с резервной копией исходного.
+//!
+//! Результат пишется во временный файл рядом; после успешной конвертации исходный файл
+//! сохраняется как [`backup_path`], а результат с правами исходного атомарно занимает его
+//! место. При ошибке или прерывании исходный файл не меняется, а временный удаляется.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::convert::{convert, ConvertErr, ConvertReport};
+use crate::model::data::Format;
+use crate::model::errors::{CommonErr, IoErr};
+use crate::model::resource::Resource;
+use crate::parser::ConvertOptions;
+
+/// Конвертирует файл `path` на месте (см. описание модуля и [`convert`]).
+///
+/// Контрольные точки не поддерживаются: их заменяет нетронутый исходный файл.
+pub fn convert_in_place(
+    path: &Path,
+    from_format: Format,
+    to_format: Format,
+    options: &ConvertOptions,
+    on_warning: &mut dyn FnMut(String),
+) -> Result<ConvertReport, ConvertErr> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".in-place.tmp");
+    let tmp = PathBuf::from(tmp);
+    let from = Resource::File { path: path.to_path_buf() };
+    let converted = convert(from, from_format, Resource::File { path: tmp.clone() }, to_format, options, None, on_warning);
+    let swapped = match &converted {
+        Ok(_) => replace_keeping_backup(path, &tmp),
+        Err(_) => Ok(()),
+    };
+    if converted.is_err() || swapped.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    swapped.map_err(|e| CommonErr::from(IoErr::output(e, Some(path.to_path_buf()))))?;
+    converted
+}
+
+/// Куда [`convert_in_place`] сохраняет исходный файл: `<path>.bak`.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    backup.into()
+}
+
+/// Сохраняет `path` как резервную копию и ставит `replacement` на его место.
+///
+/// Копия — жёсткая ссылка (или копия, если ссылку создать нельзя), поэтому `path`
+/// существует всё время, а замена — одно переименование.
+fn replace_keeping_backup(path: &Path, replacement: &Path) -> io::Result<()> {
+    fs::set_permissions(replacement, fs::metadata(path)?.permissions())?;
+    let backup = backup_path(path);
+    match fs::remove_file(&backup) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    if fs::hard_link(path, &backup).is_err() {
+        fs::copy(path, &backup)?;
+    }
+    fs::rename(replacement, path)
+}
+
+#[cfg(all(test, feature = "csv", feature = "text"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_place_keeps_backup_and_original_on_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.csv");
+        let input = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                     1,DEPOSIT,0,10,100,1700000000,SUCCESS,x\n";
+        fs::write(&path, input).unwrap();
+        let options = ConvertOptions::default();
+
+        let report = convert_in_place(&path, Format::YpBankCsv, Format::YpBankCsv, &options, &mut |_| {}).unwrap();
+        assert_eq!(report.records_written, 1);
+        assert!(fs::read_to_string(&path).unwrap().ends_with(",SUCCESS,x\n"));
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), input);
+
+        // Вход не разбирается как текст: файл и прежняя копия не меняются, временного файла нет
+        let normalized = fs::read(&path).unwrap();
+        assert!(convert_in_place(&path, Format::YpBankText, Format::YpBankCsv, &options, &mut |_| {}).is_err());
+        assert_eq!(fs::read(&path).unwrap(), normalized);
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), input);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
@@ -0,0 +1,569 @@
+//! Конвертация `read-parse-write-command` целиком.
+//!
+//! [`run_convert_job`] по входу и выходу выбирает режим (один вход, конвертация на месте,
+//! разбиение по шаблону имён или каталог входов) и ведёт всё, что окружает конвертацию:
+//! файл состояния `--skip-processed`, манифесты, JUnit XML и запись журнала аудита. Бинарнику
+//! остаётся разобрать аргументы, показать события и итог и выбрать код выхода.
+//! [`RunSinks`] сообщает итог запуска любой команды в журнал аудита, метрики и на webhook.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::audit::{append_audit, AuditEntry};
+use crate::convert::in_place::{backup_path, convert_in_place};
+use crate::convert::ledger::{checksum, Ledger};
+use crate::convert::many::{convert_many, expand_inputs, ConvertManyReport, FileConvertResult};
+use crate::convert::{convert, CheckpointMode, ConvertErr, ConvertReport, IssuesReport};
+use crate::junit::{write_junit, CaseOutcome, TestCase};
+use crate::manifest::write_manifest;
+use crate::metrics::{push_statsd, write_prometheus_textfile, RunMetrics};
+use crate::model::data::Format;
+use crate::model::errors::{describe_error, CommonErr};
+use crate::model::metadata::ProducerMetadata;
+use crate::model::resource::Resource;
+#[cfg(feature = "notify")]
+use crate::notify::{notify_webhook, NotifyErr, RetryPolicy};
+use crate::parser::ConvertOptions;
+use crate::partition::{partition_convert, OutputTemplate, PartitionOutput, PartitionReport};
+
+/// Конвертация `read-parse-write-command` со всем, что её окружает.
+#[derive(Clone, Debug)]
+pub struct ConvertJob {
+    /// Файл, каталог или glob-шаблон входов (см. [`expand_inputs`]), консоль или память
+    pub from: Resource,
+    pub from_format: Format,
+    /// Файл, каталог для нескольких входов или шаблон имён файлов (см. [`OutputTemplate`])
+    pub to: Resource,
+    pub to_format: Format,
+    /// Конвертировать файл `from` на месте (см. [`convert_in_place`]); `to` совпадает с `from`
+    pub in_place: bool,
+    pub options: ConvertOptions,
+    /// Контрольная точка; только для одного входа
+    pub checkpoint: Option<CheckpointMode>,
+    /// Куда записать пропущенные записи
+    pub errors_report: Option<IssuesReport>,
+    /// Файл состояния `--skip-processed` (см. [`Ledger`])
+    pub skip_processed: Option<PathBuf>,
+    /// Программа для манифестов рядом с результатами; `None` — манифесты не пишутся
+    pub manifest_producer: Option<String>,
+    /// Куда записать итоги входов в JUnit XML
+    pub junit: Option<PathBuf>,
+}
+
+/// Событие конвертации, которое стоит показать сразу, не дожидаясь итога.
+#[derive(Debug)]
+pub enum JobEvent<'a> {
+    /// Предупреждение разбора единственного входа
+    Warning(String),
+    /// Вход не изменился с прошлой конвертации и пропущен (`--skip-processed`)
+    Unchanged(&'a Path),
+    /// Файл из нескольких сконвертирован или не сконвертирован (с его предупреждениями)
+    File(&'a FileConvertResult),
+    /// Записан файл разбиения по шаблону
+    PartitionOutput(&'a PartitionOutput),
+    /// Манифест результата `output` записан или не записан
+    Manifest {
+        output: &'a Path,
+        result: &'a Result<PathBuf, CommonErr>,
+    },
+    /// Файл состояния `--skip-processed` не удалось записать
+    LedgerNotSaved(&'a ConvertErr),
+    /// JUnit XML не удалось записать
+    JunitNotWritten(&'a CommonErr),
+}
+
+/// Сочетание параметров, с которым конвертация не начинается.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobRejection {
+    /// Конвертация на месте — только для одного существующего файла
+    InPlaceNeedsFile,
+    /// Шаблон имён — только для одного входа, без контрольных точек, проверки и `--skip-processed`
+    TemplateNeedsSingleInput,
+    /// Контрольные точки — только для одного входа
+    CheckpointNeedsSingleInput,
+    /// Несколько входов конвертируются только в каталог
+    InputsNeedDirectory,
+}
+
+/// Чем закончилась конвертация.
+#[derive(Clone, Debug)]
+pub enum JobOutcome {
+    /// Конвертация не начиналась
+    Rejected(JobRejection),
+    /// Входы не удалось перечислить или файл состояния `--skip-processed` прочитать
+    Failed(ConvertErr),
+    /// Единственный вход не изменился с прошлой конвертации
+    Unchanged,
+    /// Один вход; `backup` — резервная копия исходного файла после конвертации на месте
+    Single {
+        result: Result<ConvertReport, ConvertErr>,
+        backup: Option<PathBuf>,
+    },
+    /// Вход разбит на файлы по шаблону имён
+    Partition(Result<PartitionReport, ConvertErr>),
+    /// Несколько входов; `unchanged` — пропущенные входы, которые не изменились
+    Many {
+        unchanged: Vec<PathBuf>,
+        result: Result<ConvertManyReport, ConvertErr>,
+    },
+}
+
+/// Итог [`run_convert_job`].
+#[derive(Clone, Debug)]
+pub struct JobReport {
+    pub outcome: JobOutcome,
+    /// Запись журнала аудита; `None`, если конвертация не начиналась
+    pub audit: Option<AuditEntry>,
+}
+
+impl JobReport {
+    /// Конвертация прошла без ошибок; для нескольких входов — сконвертированы все файлы.
+    pub fn succeeded(&self) -> bool {
+        let converted = match &self.outcome {
+            JobOutcome::Rejected(_) => false,
+            JobOutcome::Many { result: Ok(many), .. } => many.success,
+            _ => true,
+        };
+        converted && self.audit.as_ref().is_some_and(AuditEntry::succeeded)
+    }
+}
+
+/// Конвертирует `job.from` в `job.to` (см. описание модуля).
+///
+/// * `to` — шаблон имён (см. [`OutputTemplate::is_template`]): [`partition_convert`]
+/// * `from` — каталог или glob-шаблон: [`convert_many`] в каталог `to`
+/// * `in_place`: [`convert_in_place`]
+/// * иначе [`convert`] с контрольной точкой `checkpoint`
+///
+/// С `skip_processed` входы, которые не изменились с прошлой успешной конвертации в тот же
+/// выход, пропускаются, а успешно сконвертированные записываются в файл состояния.
+/// `on_event` вызывается по ходу конвертации, в том числе из потоков [`convert_many`].
+pub fn run_convert_job(job: &ConvertJob, on_event: &(dyn Fn(JobEvent<'_>) + Sync)) -> JobReport {
+    let inputs = match &job.from {
+        Resource::File { path } => expand_inputs(path),
+        _ => Ok(None),
+    };
+    let (inputs, ledger) = match job.skip_processed.as_deref().map(Ledger::load).transpose() {
+        Ok(ledger) => (inputs, ledger),
+        Err(e) => (Err(e), None),
+    };
+    let template = match &job.to {
+        Resource::File { path } if OutputTemplate::is_template(path) => Some(path.as_path()),
+        _ => None,
+    };
+    let entry = AuditEntry::new("read-parse-write-command");
+    let rejected = |rejection| JobReport {
+        outcome: JobOutcome::Rejected(rejection),
+        audit: None,
+    };
+
+    match (inputs, template) {
+        _ if job.in_place && !matches!(&job.from, Resource::File { path } if path.is_file()) => {
+            rejected(JobRejection::InPlaceNeedsFile)
+        }
+        (Ok(inputs), Some(_)) if inputs.is_some() || job.checkpoint.is_some() || job.options.verify || ledger.is_some() => {
+            rejected(JobRejection::TemplateNeedsSingleInput)
+        }
+        (Ok(None), Some(template)) => run_partition(job, template, entry, on_event),
+        (Ok(Some(_)), _) if job.checkpoint.is_some() => rejected(JobRejection::CheckpointNeedsSingleInput),
+        (Ok(Some(inputs)), _) => match &job.to {
+            Resource::File { path } => run_many(job, inputs, path, ledger, entry, on_event),
+            _ => rejected(JobRejection::InputsNeedDirectory),
+        },
+        (Err(e), _) => JobReport {
+            audit: Some(entry.input(&job.from).outcome(&Err::<(), _>(&e))),
+            outcome: JobOutcome::Failed(e),
+        },
+        (Ok(None), None) => run_single(job, ledger, entry, on_event),
+    }
+}
+
+fn run_single(job: &ConvertJob, mut ledger: Option<Ledger>, entry: AuditEntry, on_event: &(dyn Fn(JobEvent<'_>) + Sync)) -> JobReport {
+    let entry = entry.input(&job.from).output(&job.to);
+    let input = match &job.from {
+        Resource::File { path } => Some(path.as_path()),
+        _ => None,
+    };
+    let output = match &job.to {
+        Resource::File { path } => Some(path.as_path()),
+        _ => None,
+    };
+    // Состояние --skip-processed для одного входа ведётся только между файлами
+    if let (Some(ledger), Some(input), Some(output)) = (&mut ledger, input, output) {
+        if ledger.is_unchanged(input, output) {
+            on_event(JobEvent::Unchanged(input));
+            return JobReport {
+                outcome: JobOutcome::Unchanged,
+                audit: Some(entry.count("unchanged", 1)),
+            };
+        }
+    }
+
+    let mut on_warning = |warning| on_event(JobEvent::Warning(warning));
+    let res = match output {
+        Some(path) if job.in_place => convert_in_place(path, job.from_format, job.to_format, &job.options, &mut on_warning),
+        _ => convert(job.from.clone(), job.from_format, job.to.clone(), job.to_format, &job.options, job.checkpoint, &mut on_warning),
+    };
+    let res = match &job.errors_report {
+        Some(report) => report.write(res),
+        None => res,
+    };
+    if let (Some(ledger), Some(input), Some(output), Ok(_)) = (&mut ledger, input, output, &res) {
+        ledger.record(input, output);
+        save_ledger(ledger, on_event);
+    }
+
+    let mut backup = None;
+    let entry = match &res {
+        Ok(report) => {
+            if let Some(output) = output {
+                backup = job.in_place.then(|| backup_path(output));
+                emit_manifest(job, output, on_event);
+            }
+            convert_counts(entry, report)
+        }
+        Err(ConvertErr::Interrupted { .. }) if job.in_place => entry,
+        Err(ConvertErr::Interrupted { partial }) => convert_counts(entry, partial),
+        Err(_) => entry,
+    };
+    write_junit_report(job, &[junit_case(job.from.to_string(), &res)], on_event);
+    JobReport {
+        audit: Some(entry.outcome(&res)),
+        outcome: JobOutcome::Single { result: res, backup },
+    }
+}
+
+fn run_partition(job: &ConvertJob, template: &Path, entry: AuditEntry, on_event: &(dyn Fn(JobEvent<'_>) + Sync)) -> JobReport {
+    let mut on_warning = |warning| on_event(JobEvent::Warning(warning));
+    let res = OutputTemplate::parse(template)
+        .and_then(|template| partition_convert(job.from.clone(), job.from_format, &template, job.to_format, &job.options, &mut on_warning));
+    let res = match &job.errors_report {
+        Some(report) => report.write(res),
+        None => res,
+    };
+
+    let mut entry = entry.input(&job.from);
+    for output in res.iter().flat_map(|report| &report.outputs) {
+        entry = entry.output(&Resource::File { path: output.path.clone() });
+    }
+    let entry = match &res {
+        Ok(report) => {
+            for output in &report.outputs {
+                on_event(JobEvent::PartitionOutput(output));
+                emit_manifest(job, &output.path, on_event);
+            }
+            convert_counts(entry.count("files", report.outputs.len()), &report.total)
+        }
+        Err(ConvertErr::Interrupted { partial }) => convert_counts(entry, partial),
+        Err(_) => entry,
+    };
+    let total = res.as_ref().map(|report| report.total.clone()).map_err(Clone::clone);
+    write_junit_report(job, &[junit_case(job.from.to_string(), &total)], on_event);
+    JobReport {
+        audit: Some(entry.outcome(&res)),
+        outcome: JobOutcome::Partition(res),
+    }
+}
+
+fn run_many(
+    job: &ConvertJob,
+    mut inputs: Vec<PathBuf>,
+    out_dir: &Path,
+    mut ledger: Option<Ledger>,
+    entry: AuditEntry,
+    on_event: &(dyn Fn(JobEvent<'_>) + Sync),
+) -> JobReport {
+    let unchanged = match &mut ledger {
+        Some(ledger) => ledger.skip_unchanged(&mut inputs, out_dir, job.to_format),
+        None => Vec::new(),
+    };
+    for input in &unchanged {
+        on_event(JobEvent::Unchanged(input));
+    }
+    let transactional = job.options.transactional;
+    let res = convert_many(
+        inputs,
+        job.from_format,
+        out_dir,
+        job.to_format,
+        &job.options,
+        &|path| checksum(path).ok(),
+        &|file| {
+            on_event(JobEvent::File(file));
+            // С transactional файл ещё не на месте, манифест пишется после переноса
+            if file.result.is_ok() && !transactional {
+                emit_manifest(job, &file.to, on_event);
+            }
+        },
+    );
+    let res = match &job.errors_report {
+        Some(report) => report.write_files(res),
+        None => res,
+    };
+    if let Ok(many) = &res {
+        if transactional && !many.rolled_back {
+            for file in &many.files {
+                emit_manifest(job, &file.to, on_event);
+            }
+        }
+    }
+    if let (Some(ledger), Ok(many)) = (&mut ledger, &res) {
+        ledger.record_files(&many.files);
+        save_ledger(ledger, on_event);
+    }
+
+    let mut entry = entry;
+    for file in res.iter().flat_map(|many| &many.files) {
+        let input = Resource::File { path: file.from.clone() };
+        entry = entry.input(&input).output(&Resource::File { path: file.to.clone() });
+        if let Err(e) = &file.result {
+            entry = entry.failure(&input, e);
+        }
+    }
+    if let Ok(many) = &res {
+        let converted = || many.files.iter().filter_map(|f| f.result.as_ref().ok());
+        entry = entry
+            .count("files", many.files.len())
+            .count("records", converted().map(|r| r.records_written).sum())
+            .count("bytes", converted().map(|r| r.bytes_out).sum())
+            .count("skipped", many.skipped)
+            .count("warnings", many.warnings)
+            .count("unchanged", unchanged.len());
+    }
+    let entry = entry.outcome(&res);
+    let cases = match &res {
+        Ok(many) => many
+            .files
+            .iter()
+            .map(|file| junit_case(file.from.display().to_string(), &file.result))
+            .collect(),
+        Err(e) => vec![junit_case(job.from.to_string(), &Err(e.clone()))],
+    };
+    write_junit_report(job, &cases, on_event);
+    JobReport {
+        audit: Some(entry),
+        outcome: JobOutcome::Many { unchanged, result: res },
+    }
+}
+
+/// Итог конвертации файла `name` для JUnit XML.
+///
+/// Пропущенные записи, ошибка разбора и несовпадение при `verify` — `failure` (файл не прошёл
+/// проверку), остальные ошибки (чтение, запись, контрольная точка) — `error`.
+pub fn junit_case(name: String, result: &Result<ConvertReport, ConvertErr>) -> TestCase {
+    let outcome = match result {
+        Ok(report) if report.skipped == 0 => CaseOutcome::Passed,
+        Ok(report) => CaseOutcome::Failed {
+            message: format!("{} of {} records skipped", report.skipped, report.records_read),
+            details: report
+                .issues
+                .iter()
+                .map(|issue| match issue.line {
+                    Some(line) => format!("line {}: {}\n", line, issue.reason),
+                    None => format!("{}\n", issue.reason),
+                })
+                .collect(),
+        },
+        Err(e) => {
+            let details = describe_error(e);
+            let message = e.to_string();
+            match e {
+                ConvertErr::Prepare { err: CommonErr::Parser(_) } | ConvertErr::Verify { .. } => CaseOutcome::Failed { message, details },
+                _ => CaseOutcome::Error { message, details },
+            }
+        }
+    };
+    TestCase {
+        name,
+        time: result.as_ref().ok().map(|report| report.elapsed),
+        outcome,
+    }
+}
+
+/// Сведения о программе `producer` для результата конвертации `from`: SHA-256 входа
+/// считается, только если вход — один файл (для каталога он считается по каждому файлу отдельно).
+pub fn producer_metadata(producer: &str, from: &Resource) -> ProducerMetadata {
+    let created_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64);
+    let source_sha256 = match from {
+        Resource::File { path } if path.is_file() => checksum(path).ok(),
+        _ => None,
+    };
+    ProducerMetadata {
+        producer: producer.to_string(),
+        created_ms,
+        source_sha256,
+    }
+}
+
+fn emit_manifest(job: &ConvertJob, output: &Path, on_event: &(dyn Fn(JobEvent<'_>) + Sync)) {
+    if let Some(producer) = &job.manifest_producer {
+        let result = write_manifest(output, job.to_format, producer);
+        on_event(JobEvent::Manifest { output, result: &result });
+    }
+}
+
+fn save_ledger(ledger: &Ledger, on_event: &(dyn Fn(JobEvent<'_>) + Sync)) {
+    if let Err(e) = ledger.save() {
+        on_event(JobEvent::LedgerNotSaved(&e));
+    }
+}
+
+fn write_junit_report(job: &ConvertJob, cases: &[TestCase], on_event: &(dyn Fn(JobEvent<'_>) + Sync)) {
+    if let Some(path) = &job.junit {
+        if let Err(e) = write_junit(path, "convert", cases) {
+            on_event(JobEvent::JunitNotWritten(&e));
+        }
+    }
+}
+
+fn convert_counts(entry: AuditEntry, report: &ConvertReport) -> AuditEntry {
+    entry
+        .count("records", report.records_written)
+        .count("bytes", report.bytes_out)
+        .count("skipped", report.skipped)
+        .count("warnings", report.warnings)
+}
+
+/// Куда сообщить итог запуска любой команды CLI.
+#[derive(Clone, Debug, Default)]
+pub struct RunSinks {
+    /// Журнал аудита (см. [`append_audit`])
+    pub audit_log: Option<PathBuf>,
+    /// Файл Prometheus textfile collector
+    pub metrics_textfile: Option<PathBuf>,
+    /// Адрес statsd-сервера (host:port)
+    pub metrics_statsd: Option<String>,
+    /// Webhook для итога запуска
+    #[cfg(feature = "notify")]
+    pub notify_webhook: Option<String>,
+    #[cfg(feature = "notify")]
+    pub notify_retry: RetryPolicy,
+}
+
+/// Итог запуска не удалось сообщить; сам запуск от этого неудачным не считается.
+#[derive(Debug)]
+pub enum SinkErr {
+    Metrics(CommonErr),
+    #[cfg(feature = "notify")]
+    Notify(NotifyErr),
+    Audit(CommonErr),
+}
+
+impl RunSinks {
+    /// Сообщает итог `entry` запуска длительностью `duration` всем заданным получателям.
+    ///
+    /// # Возвращает
+    /// Ошибки в порядке отправки: метрики, webhook, журнал аудита.
+    pub fn publish(&self, entry: &AuditEntry, duration: Duration) -> Vec<SinkErr> {
+        let mut errors = Vec::new();
+        let metrics = RunMetrics {
+            command: entry.command().to_string(),
+            duration,
+            success: entry.succeeded(),
+            counts: entry.counts().to_vec(),
+        };
+        if let Some(path) = &self.metrics_textfile {
+            errors.extend(write_prometheus_textfile(path, &metrics).err().map(SinkErr::Metrics));
+        }
+        if let Some(addr) = &self.metrics_statsd {
+            errors.extend(push_statsd(addr, &metrics).err().map(SinkErr::Metrics));
+        }
+        #[cfg(feature = "notify")]
+        if let Some(url) = &self.notify_webhook {
+            errors.extend(notify_webhook(url, entry, duration, &self.notify_retry).err().map(SinkErr::Notify));
+        }
+        if let Some(log) = &self.audit_log {
+            errors.extend(append_audit(log, entry).err().map(SinkErr::Audit));
+        }
+        errors
+    }
+}
+
+#[cfg(all(test, feature = "csv", feature = "text"))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::Mutex;
+
+    const HEADER: &str = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n";
+
+    fn csv_to_text(from: &Path, to: &Path) -> ConvertJob {
+        ConvertJob {
+            from: Resource::File { path: from.to_path_buf() },
+            from_format: Format::YpBankCsv,
+            to: Resource::File { path: to.to_path_buf() },
+            to_format: Format::YpBankText,
+            in_place: false,
+            options: ConvertOptions::default(),
+            checkpoint: None,
+            errors_report: None,
+            skip_processed: None,
+            manifest_producer: None,
+            junit: None,
+        }
+    }
+
+    #[test]
+    fn test_run_convert_job_skips_processed_and_rejects_combinations() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("in.csv");
+        fs::write(&from, format!("{}1,DEPOSIT,0,10,100,1700000000,SUCCESS,\"x\"\n", HEADER)).unwrap();
+        let job = ConvertJob {
+            skip_processed: Some(dir.path().join("processed.tsv")),
+            manifest_producer: Some("test".to_string()),
+            junit: Some(dir.path().join("junit.xml")),
+            ..csv_to_text(&from, &dir.path().join("out.txt"))
+        };
+        let events = Mutex::new(Vec::new());
+        let on_event = |event: JobEvent<'_>| events.lock().unwrap().push(format!("{:?}", event));
+
+        let report = run_convert_job(&job, &on_event);
+        let JobOutcome::Single { result: Ok(converted), backup: None } = &report.outcome else {
+            panic!()
+        };
+        assert_eq!(converted.records_written, 1);
+        assert!(report.succeeded());
+        assert!(events.lock().unwrap()[0].starts_with("Manifest"));
+        assert!(fs::read_to_string(dir.path().join("junit.xml")).unwrap().contains("<testcase"));
+
+        let report = run_convert_job(&job, &on_event);
+        assert!(matches!(report.outcome, JobOutcome::Unchanged));
+        assert!(report.succeeded());
+
+        let template = ConvertJob {
+            checkpoint: Some(CheckpointMode::Save),
+            ..csv_to_text(&from, &dir.path().join("{type}.txt"))
+        };
+        let report = run_convert_job(&template, &on_event);
+        let JobOutcome::Rejected(rejection) = report.outcome else {
+            panic!()
+        };
+        assert_eq!(rejection, JobRejection::TemplateNeedsSingleInput);
+        assert!(report.audit.is_none());
+    }
+
+    #[test]
+    fn test_junit_case_tells_bad_data_from_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("in.csv");
+        fs::write(&from, format!("{}oops\n", HEADER)).unwrap();
+        let run = |from: PathBuf| {
+            convert(
+                Resource::File { path: from },
+                Format::YpBankCsv,
+                Resource::File { path: dir.path().join("out.txt") },
+                Format::YpBankText,
+                &ConvertOptions::default(),
+                None,
+                &mut |_| {},
+            )
+        };
+
+        let case = junit_case("in.csv".to_string(), &run(from));
+        assert!(matches!(case.outcome, CaseOutcome::Failed { .. }));
+        let case = junit_case("missing.csv".to_string(), &run(dir.path().join("missing.csv")));
+        assert!(matches!(case.outcome, CaseOutcome::Error { .. }));
+    }
+}
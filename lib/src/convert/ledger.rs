@@ -0,0 +1,174 @@
+//! Файл состояния `--skip-processed`: какие входы уже сконвертированы и с тех пор не менялись.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::convert::many::{output_path, FileConvertResult};
+use crate::convert::ConvertErr;
+use crate::model::data::Format;
+use crate::model::errors::{CommonErr, IoErr};
+
+/// Первая строка файла состояния.
+const HEADER: &str = "# ypbank processed inputs v1: sha256<TAB>input<TAB>output";
+
+/// Файл состояния `--skip-processed`: SHA-256 входов, уже успешно сконвертированных в свой выход.
+///
+/// Вход пропускается, если его содержимое не изменилось с прошлой конвертации в тот же выход,
+/// а выход всё ещё существует: повторный запуск ночного задания ничего не делает заново.
+#[derive(Debug)]
+pub struct Ledger {
+    path: PathBuf,
+    /// (вход, выход) -> SHA-256 входа при последней успешной конвертации
+    entries: BTreeMap<(PathBuf, PathBuf), String>,
+    /// Суммы входов, посчитанные в [`Ledger::is_unchanged`] до конвертации
+    pending: HashMap<PathBuf, String>,
+}
+
+impl Ledger {
+    /// Читает файл состояния `path`; если файла ещё нет, состояние пустое.
+    ///
+    /// # Errors
+    /// * `ConvertErr::Prepare` — файл не удалось прочитать
+    /// * `ConvertErr::Ledger` — в файле неверная строка
+    pub fn load(path: &Path) -> Result<Ledger, ConvertErr> {
+        let mut ledger = Ledger {
+            path: path.to_path_buf(),
+            entries: BTreeMap::new(),
+            pending: HashMap::new(),
+        };
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(ledger),
+            Err(e) => return Err(CommonErr::from(IoErr::input(e, Some(path.to_path_buf()))).into()),
+        };
+        for (n, line) in text.lines().enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split('\t');
+            let (Some(checksum), Some(input), Some(output), None) = (fields.next(), fields.next(), fields.next(), fields.next()) else {
+                return Err(ConvertErr::Ledger {
+                    reason: format!("{}: line {}: expected sha256, input and output separated by tabs", path.display(), n + 1),
+                });
+            };
+            ledger.entries.insert((input.into(), output.into()), checksum.to_string());
+        }
+        Ok(ledger)
+    }
+
+    /// Вход `input` уже сконвертирован в `output` и с тех пор не менялся.
+    ///
+    /// Посчитанная сумма запоминается для [`Ledger::record`]; вход, который не удалось
+    /// прочитать, считается изменённым — ошибку покажет конвертация.
+    pub fn is_unchanged(&mut self, input: &Path, output: &Path) -> bool {
+        let (input, output) = (absolute(input), absolute(output));
+        let Ok(checksum) = checksum(&input) else {
+            return false;
+        };
+        let unchanged = output.is_file() && self.entries.get(&(input.clone(), output)) == Some(&checksum);
+        self.pending.insert(input, checksum);
+        unchanged
+    }
+
+    /// Убирает из `inputs` входы, которые не изменились с прошлой конвертации в каталог
+    /// `out_dir` в формате `to_format`, и возвращает их.
+    pub fn skip_unchanged(&mut self, inputs: &mut Vec<PathBuf>, out_dir: &Path, to_format: Format) -> Vec<PathBuf> {
+        let (unchanged, changed) = std::mem::take(inputs)
+            .into_iter()
+            .partition(|input| self.is_unchanged(input, &output_path(input, out_dir, to_format)));
+        *inputs = changed;
+        unchanged
+    }
+
+    /// Отмечает вход `input` успешно сконвертированным в `output` с суммой из [`Ledger::is_unchanged`].
+    pub fn record(&mut self, input: &Path, output: &Path) {
+        let input = absolute(input);
+        if let Some(checksum) = self.pending.remove(&input) {
+            self.entries.insert((input, absolute(output)), checksum);
+        }
+    }
+
+    /// Отмечает успешно сконвертированные файлы из `files`.
+    pub fn record_files(&mut self, files: &[FileConvertResult]) {
+        for file in files.iter().filter(|file| file.result.is_ok()) {
+            self.record(&file.from, &file.to);
+        }
+    }
+
+    /// Записывает состояние: сначала во временный файл рядом, затем переименовывает,
+    /// чтобы прерванная запись не испортила прежнее состояние.
+    ///
+    /// # Errors
+    /// `ConvertErr::Prepare`, если файл не удалось записать.
+    pub fn save(&self) -> Result<(), ConvertErr> {
+        let mut text = format!("{}\n", HEADER);
+        for ((input, output), checksum) in &self.entries {
+            text.push_str(&format!("{}\t{}\t{}\n", checksum, input.display(), output.display()));
+        }
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let output_err = |e| CommonErr::from(IoErr::output(e, Some(self.path.clone())));
+        fs::write(&tmp, text).map_err(output_err)?;
+        fs::rename(&tmp, &self.path).map_err(output_err)?;
+        Ok(())
+    }
+}
+
+/// Абсолютный путь без обращения к файловой системе: выход может ещё не существовать.
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// SHA-256 содержимого файла в шестнадцатеричном виде.
+pub fn checksum(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ledger_skips_only_unchanged_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = dir.path().join("processed.tsv");
+        let (a, b) = (dir.path().join("a.csv"), dir.path().join("b.csv"));
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+        let out_dir = dir.path().join("out");
+        fs::create_dir(&out_dir).unwrap();
+
+        let mut ledger = Ledger::load(&state).unwrap();
+        let mut inputs = vec![a.clone(), b.clone()];
+        assert!(ledger.skip_unchanged(&mut inputs, &out_dir, Format::YpBankBin).is_empty());
+        for input in &inputs {
+            let output = output_path(input, &out_dir, Format::YpBankBin);
+            fs::write(&output, "converted").unwrap();
+            ledger.record(input, &output);
+        }
+        ledger.save().unwrap();
+
+        // Изменился a, пропал выход b; в другой формат оба конвертируются заново
+        let mut ledger = Ledger::load(&state).unwrap();
+        let mut inputs = vec![a.clone(), b.clone()];
+        assert_eq!(ledger.skip_unchanged(&mut inputs, &out_dir, Format::YpBankBin), vec![a.clone(), b.clone()]);
+        fs::write(&a, "changed").unwrap();
+        fs::remove_file(out_dir.join("b.bin")).unwrap();
+        let mut inputs = vec![a.clone(), b.clone()];
+        assert!(ledger.skip_unchanged(&mut inputs, &out_dir, Format::YpBankBin).is_empty());
+        assert!(!ledger.is_unchanged(&b, &out_dir.join("b.txt")));
+
+        fs::write(&state, "not a ledger line\n").unwrap();
+        let Err(ConvertErr::Ledger { reason }) = Ledger::load(&state) else {
+            panic!()
+        };
+        assert!(reason.ends_with("line 1: expected sha256, input and output separated by tabs"));
+    }
+}
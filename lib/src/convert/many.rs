@@ -0,0 +1,302 @@
+//! Конвертация нескольких файлов (каталога или glob-шаблона) в выходной каталог в несколько потоков.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::convert::staging::StagedOutputs;
+use crate::convert::{convert, ConvertErr, ConvertReport};
+use crate::model::data::Format;
+use crate::model::metadata::ProducerMetadata;
+use crate::model::resource::Resource;
+use crate::parser::{ConvertOptions, WriteOptions};
+
+/// Итог конвертации одного файла из нескольких.
+#[derive(Clone, Debug)]
+pub struct FileConvertResult {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// Итог конвертации файла вместе с его пропущенными записями
+    pub result: Result<ConvertReport, ConvertErr>,
+    /// Предупреждения разбора в порядке входных данных файла
+    pub warnings: Vec<String>,
+}
+
+/// Готовые файлы по индексу входа.
+struct Done {
+    files: Vec<Option<FileConvertResult>>,
+    /// Сколько первых файлов уже передано в `on_file` (для `stable_order`)
+    reported: usize,
+}
+
+/// Итог конвертации нескольких файлов.
+#[derive(Clone, Debug)]
+pub struct ConvertManyReport {
+    /// Результаты в порядке входных файлов
+    pub files: Vec<FileConvertResult>,
+    /// Все файлы сконвертированы без ошибок
+    pub success: bool,
+    /// Сколько записей пропущено во всех файлах
+    pub skipped: usize,
+    /// Сколько предупреждений выдано во всех файлах
+    pub warnings: usize,
+    /// С `options.transactional`: выходные файлы не перенесены на место, потому что какой-то
+    /// файл не сконвертирован или конвертация прервана
+    pub rolled_back: bool,
+}
+
+/// Раскрывает вход в список файлов, если это каталог или glob-шаблон (`*`, `?`, `[`).
+///
+/// Файлы каталога берутся без подкаталогов. Возвращает `Ok(None)` для обычного пути —
+/// его конвертирует [`convert`].
+pub fn expand_inputs(path: &Path) -> Result<Option<Vec<PathBuf>>, ConvertErr> {
+    let mut files = if path.is_dir() {
+        fs::read_dir(path)
+            .map_err(inputs_err)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(inputs_err)?
+    } else if path.to_string_lossy().contains(['*', '?', '[']) {
+        glob::glob(&path.to_string_lossy())
+            .map_err(inputs_err)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(inputs_err)?
+    } else {
+        return Ok(None);
+    };
+
+    files.retain(|file| file.is_file());
+    files.sort();
+    Ok(Some(files))
+}
+
+/// Конвертирует файлы `inputs` в каталог `out_dir` в `options.jobs` потоков.
+///
+/// Выходной файл называется как входной, с расширением формата `to_format`
+/// (`a.csv` -> `out_dir/a.bin`). Ошибка в одном файле не останавливает остальные:
+/// она попадает в его [`FileConvertResult`]; [`ConvertManyReport::files`] всегда идут
+/// в порядке `inputs`. С `options.write.metadata` сведения о программе у каждого результата
+/// получают SHA-256 своего входа из `source_sha256`.
+///
+/// `on_file` вызывается для каждого готового файла: по мере готовности или, с
+/// `options.stable_order`, строго в порядке `inputs`. После прерывания (`options.interrupt`)
+/// файлы, которые не успели начаться, в итог не попадают.
+///
+/// С `options.transactional` файлы пишутся во временные и переносятся на место, только если
+/// сконвертированы все входы; иначе прежние файлы `out_dir` не меняются. В `on_file` файл
+/// ещё не на месте.
+///
+/// # Errors
+/// * `ConvertErr::Inputs` — два входа дают один выходной файл или каталог не удалось создать
+/// * `ConvertErr::Prepare` — временные файлы не удалось создать или перенести на место
+pub fn convert_many(
+    inputs: Vec<PathBuf>,
+    from_format: Format,
+    out_dir: &Path,
+    to_format: Format,
+    options: &ConvertOptions,
+    source_sha256: &(dyn Fn(&Path) -> Option<String> + Sync),
+    on_file: &(dyn Fn(&FileConvertResult) + Sync),
+) -> Result<ConvertManyReport, ConvertErr> {
+    let outputs = output_paths(&inputs, out_dir, to_format)?;
+    fs::create_dir_all(out_dir).map_err(inputs_err)?;
+    let mut staged = options.transactional.then(StagedOutputs::new);
+    let written = match &mut staged {
+        Some(staged) => outputs.iter().map(|to| staged.stage(to, options.write.append)).collect::<Result<Vec<_>, _>>()?,
+        None => outputs.clone(),
+    };
+
+    let queue = Mutex::new(inputs.iter().zip(outputs.iter().zip(&written)).enumerate());
+    let done = Mutex::new(Done {
+        files: vec![None; inputs.len()],
+        reported: 0,
+    });
+
+    thread::scope(|scope| {
+        for _ in 0..options.jobs.clamp(1, inputs.len().max(1)) {
+            scope.spawn(|| loop {
+                // После прерывания новые файлы не начинаются, начатые останавливаются сами
+                if options.interrupted() {
+                    break;
+                }
+                let Some((i, (from, (to, written)))) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                let mut warnings = Vec::new();
+                // Сведения о программе у каждого результата ссылаются на свой входной файл
+                let file_options = options.write.metadata.as_ref().map(|metadata| ConvertOptions {
+                    write: WriteOptions {
+                        metadata: Some(ProducerMetadata { source_sha256: source_sha256(from), ..metadata.clone() }),
+                        ..options.write.clone()
+                    },
+                    ..options.clone()
+                });
+                let result = convert(
+                    Resource::File { path: from.clone() },
+                    from_format,
+                    Resource::File { path: written.clone() },
+                    to_format,
+                    file_options.as_ref().unwrap_or(options),
+                    None,
+                    &mut |warning| warnings.push(warning),
+                );
+                let file = FileConvertResult {
+                    from: from.clone(),
+                    to: to.clone(),
+                    result,
+                    warnings,
+                };
+
+                let mut done = done.lock().unwrap();
+                if !options.stable_order {
+                    on_file(&file);
+                }
+                done.files[i] = Some(file);
+                if options.stable_order {
+                    // Сообщаем обо всех готовых файлах подряд, начиная с первого несообщённого
+                    while let Some(Some(file)) = done.files.get(done.reported) {
+                        on_file(file);
+                        done.reported += 1;
+                    }
+                }
+            });
+        }
+    });
+
+    let files: Vec<_> = done.into_inner().unwrap().files.into_iter().flatten().collect();
+    let converted = files.iter().filter_map(|f| f.result.as_ref().ok());
+    let (skipped, warnings) = converted.fold((0, 0), |(s, w), r| (s + r.skipped, w + r.warnings));
+    let success = files.iter().all(|f| f.result.is_ok());
+    // Без переноса временные файлы удаляются вместе с `staged`
+    let complete = success && files.len() == inputs.len();
+    let rolled_back = staged.is_some() && !complete;
+    if let Some(staged) = staged.filter(|_| complete) {
+        staged.commit()?;
+    }
+    Ok(ConvertManyReport {
+        success,
+        skipped,
+        warnings,
+        rolled_back,
+        files,
+    })
+}
+
+/// Выходной файл входа `input` в каталоге `out_dir`: то же имя с расширением формата `to_format`.
+pub fn output_path(input: &Path, out_dir: &Path, to_format: Format) -> PathBuf {
+    let mut name = input.file_stem().unwrap_or(input.as_os_str()).to_owned();
+    name.push(".");
+    name.push(to_format.extension());
+    out_dir.join(name)
+}
+
+/// Пути выходных файлов; два входа с одним именем без расширения — ошибка.
+fn output_paths(inputs: &[PathBuf], out_dir: &Path, to_format: Format) -> Result<Vec<PathBuf>, ConvertErr> {
+    let mut seen = HashSet::new();
+    inputs
+        .iter()
+        .map(|input| {
+            let to = output_path(input, out_dir, to_format);
+            if !seen.insert(to.clone()) {
+                return Err(inputs_err(format!("several inputs map to {}", to.display())));
+            }
+            Ok(to)
+        })
+        .collect()
+}
+
+fn inputs_err(reason: impl ToString) -> ConvertErr {
+    ConvertErr::Inputs {
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(all(test, feature = "csv", feature = "text"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_paths_keep_names_and_reject_collisions() {
+        let inputs = vec![PathBuf::from("in/a.csv"), PathBuf::from("in/b.2024.txt")];
+        let outputs = output_paths(&inputs, Path::new("out"), Format::YpBankText).unwrap();
+        assert_eq!(outputs, vec![PathBuf::from("out/a.txt"), PathBuf::from("out/b.2024.txt")]);
+
+        let inputs = vec![PathBuf::from("in/a.csv"), PathBuf::from("in/a.txt")];
+        let err = output_paths(&inputs, Path::new("out"), Format::YpBankText).unwrap_err();
+        if let ConvertErr::Inputs { reason } = err {
+            assert!(reason.contains("out/a.txt"));
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn test_convert_many_in_parallel() {
+        let dir = tempfile::tempdir().unwrap();
+        let header = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n";
+        for (name, rows) in [("a.csv", 2), ("b.csv", 3)] {
+            let mut content = header.to_string();
+            for id in 1..=rows {
+                content.push_str(&format!("{},DEPOSIT,0,10,100,1700000000,SUCCESS,\"x\"\n", id));
+            }
+            fs::write(dir.path().join(name), content).unwrap();
+        }
+        fs::write(dir.path().join("c.csv"), format!("{}oops\n", header)).unwrap();
+
+        let inputs = expand_inputs(&dir.path().join("*.csv")).unwrap().unwrap();
+        let out_dir = dir.path().join("out");
+        let reported = Mutex::new(Vec::new());
+        let result = convert_many(
+            inputs,
+            Format::YpBankCsv,
+            &out_dir,
+            Format::YpBankText,
+            &ConvertOptions {
+                jobs: 2,
+                stable_order: true,
+                ..ConvertOptions::default()
+            },
+            &|_| None,
+            &|file| reported.lock().unwrap().push(file.from.file_name().unwrap().to_owned()),
+        )
+        .unwrap();
+
+        let names: Vec<_> = result.files.iter().map(|f| f.to.file_name().unwrap().to_owned()).collect();
+        assert_eq!(names, ["a.txt", "b.txt", "c.txt"]);
+        assert_eq!(*reported.lock().unwrap(), ["a.csv", "b.csv", "c.csv"]);
+        assert!(!result.success);
+        assert!(result.files[2].result.is_err());
+        assert!(out_dir.join("b.txt").is_file());
+        assert!(!result.rolled_back);
+
+        // С transactional ошибка в c.csv оставляет прежние результаты, и временных файлов не остаётся
+        let previous = fs::read_to_string(out_dir.join("b.txt")).unwrap();
+        let listing = || {
+            let mut names: Vec<_> = fs::read_dir(&out_dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+            names.sort();
+            names
+        };
+        let before = listing();
+        fs::write(dir.path().join("b.csv"), format!("{}9,DEPOSIT,0,10,100,1700000000,SUCCESS,\"y\"\n", header)).unwrap();
+        let options = ConvertOptions {
+            transactional: true,
+            ..ConvertOptions::default()
+        };
+        let convert_all = |options: &ConvertOptions| {
+            let inputs = expand_inputs(&dir.path().join("*.csv")).unwrap().unwrap();
+            convert_many(inputs, Format::YpBankCsv, &out_dir, Format::YpBankText, options, &|_| None, &|_| {}).unwrap()
+        };
+        let result = convert_all(&options);
+        assert!(result.rolled_back);
+        assert_eq!(fs::read_to_string(out_dir.join("b.txt")).unwrap(), previous);
+        assert_eq!(listing(), before);
+
+        fs::remove_file(dir.path().join("c.csv")).unwrap();
+        let result = convert_all(&options);
+        assert!(result.success && !result.rolled_back);
+        assert!(fs::read_to_string(out_dir.join("b.txt")).unwrap().contains("TX_ID: 9"));
+        assert_eq!(listing(), before);
+    }
+}
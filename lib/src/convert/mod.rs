@@ -0,0 +1,753 @@
+//! Конвертация входа в другой формат порциями, с контрольными точками и проверкой результата.
+//!
+//! [`convert`] — одна реализация для `cli-converter` и внешних сервисов; бинарнику остаётся
+//! разобрать аргументы и показать [`ConvertReport`]. Так же устроены конвертация на месте
+//! ([`in_place`]) и конвертация нескольких файлов в каталог ([`many`]). Всё, что окружает
+//! конвертацию в `read-parse-write-command` (выбор режима, `--skip-processed`, манифесты,
+//! JUnit, журнал аудита), собрано в [`job`] (фича `job`).
+
+pub mod checkpoint;
+pub mod in_place;
+#[cfg(feature = "job")]
+pub mod job;
+#[cfg(feature = "job")]
+pub mod ledger;
+pub mod many;
+pub mod staging;
+
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::model::data::{Format, TxData};
+use crate::model::errors::{CommonErr, ParseIssue, ParseWarning, WarningKind};
+use crate::model::resource::Resource;
+#[cfg(feature = "report")]
+use crate::parser::{write_files_issues_report, write_issues_report, ReportFormat};
+use crate::parser::capabilities::{conversion_losses, Loss};
+use crate::parser::io::writer::as_written;
+use crate::parser::{read_batches, read_batches_from, resume_batches, write_batches, ConvertOptions, ParseOptions, ParseOutcome, WriteCheckpoint};
+
+use checkpoint::{Checkpoint, InputIdentity};
+#[cfg(feature = "report")]
+use many::ConvertManyReport;
+
+/// Итог конвертации одного входа.
+#[derive(Clone, Debug)]
+pub struct ConvertReport {
+    /// Сколько записей прочитано, включая пропущенные (вместе с прочитанными до контрольной точки)
+    pub records_read: usize,
+    /// Сколько записей записано (вместе с записанными до контрольной точки)
+    pub records_written: usize,
+    /// Сколько записей пропущено из-за ошибок разбора (только с `ErrorMode::Collect`)
+    pub skipped: usize,
+    /// Сколько предупреждений выдал разбор
+    pub warnings: usize,
+    /// Размер входа в байтах; `None`, если он неизвестен заранее (стандартный ввод, zip-архив)
+    pub bytes_in: Option<u64>,
+    /// Размер результата в байтах
+    pub bytes_out: usize,
+    /// Время конвертации
+    pub elapsed: Duration,
+    /// Пропущенные записи, найденные в этом запуске (после продолжения — только после контрольной точки)
+    pub issues: Vec<ParseIssue>,
+}
+
+#[derive(Error, Clone, Debug)]
+pub enum ConvertErr {
+    /// Не удалось прочитать, разобрать или записать данные
+    #[error("failed to convert input")]
+    Prepare {
+        #[from]
+        err: CommonErr,
+    },
+    /// Контрольную точку нельзя создать, прочитать или продолжить
+    #[error("checkpoint error: {reason}")]
+    Checkpoint { reason: String },
+    /// Перечитанный результат не совпал со входом (см. `ConvertOptions::verify`)
+    #[error("verification failed: {reason}")]
+    Verify { reason: String },
+    /// Конвертация остановлена по флагу `ConvertOptions::interrupt`; записанные порции
+    /// сброшены, а с контрольной точкой её можно продолжить
+    #[error("interrupted after {} records", partial.records_written)]
+    Interrupted { partial: Box<ConvertReport> },
+    /// Шаблон имён выходных файлов неверен или даёт слишком много файлов
+    #[error("output template error: {reason}")]
+    Partition { reason: String },
+    /// Результат потерял бы часть входа (см. `ConvertOptions::no_lossy`); `records` — затронутые
+    /// записи порции, на которой конвертация остановлена (пусто для потерь всего входа)
+    #[error("lossy conversion: {loss}{}", listed(records))]
+    Lossy { loss: Loss, records: Vec<String> },
+    /// Входные файлы не удалось перечислить или сопоставить выходным (см. [`many`])
+    #[error("inputs error: {reason}")]
+    Inputs { reason: String },
+    /// Файл состояния `--skip-processed` повреждён (см. `ledger`)
+    #[error("processed ledger error: {reason}")]
+    Ledger { reason: String },
+    /// Вход изменился (или не записан в контрольной точке) с момента её сохранения:
+    /// продолжение дало бы результат из двух разных входов
+    #[error("input {} changed since the checkpoint was saved", path.display())]
+    InputChanged { path: PathBuf },
+}
+
+impl AsRef<ConvertReport> for ConvertReport {
+    fn as_ref(&self) -> &ConvertReport {
+        self
+    }
+}
+
+/// Куда и в каком формате записать отчёт о пропущенных записях конвертации.
+#[cfg(feature = "report")]
+#[derive(Clone, Debug)]
+pub struct IssuesReport {
+    pub to: Resource,
+    pub format: ReportFormat,
+}
+
+#[cfg(feature = "report")]
+impl IssuesReport {
+    /// Записывает пропущенные записи конвертации `converted` (после прерывания — по уже
+    /// прочитанной части) и возвращает её итог; после других ошибок отчёт не пишется.
+    pub fn write<R: AsRef<ConvertReport>>(&self, converted: Result<R, ConvertErr>) -> Result<R, ConvertErr> {
+        let report = match &converted {
+            Ok(report) => report.as_ref(),
+            Err(ConvertErr::Interrupted { partial }) => partial,
+            Err(_) => return converted,
+        };
+        write_issues_report(&report.issues, self.to.clone(), self.format)?;
+        converted
+    }
+
+    /// Как [`IssuesReport::write`], но для нескольких файлов: у каждой проблемы указан её файл.
+    pub fn write_files(&self, converted: Result<ConvertManyReport, ConvertErr>) -> Result<ConvertManyReport, ConvertErr> {
+        let Ok(many) = &converted else {
+            return converted;
+        };
+        let issues: Vec<_> = many
+            .files
+            .iter()
+            .filter_map(|file| Some((file.from.clone(), file.result.as_ref().ok()?.issues.clone())))
+            .collect();
+        write_files_issues_report(&issues, self.to.clone(), self.format)?;
+        converted
+    }
+}
+
+/// Сколько затронутых записей перечисляется в `ConvertErr::Lossy`.
+const LISTED_RECORDS: usize = 10;
+
+/// Перечень затронутых записей для сообщения `ConvertErr::Lossy`.
+fn listed(records: &[String]) -> String {
+    match records.len() {
+        0 => String::new(),
+        n if n <= LISTED_RECORDS => format!(" ({})", records.join("; ")),
+        n => format!(" ({}; and {} more)", records[..LISTED_RECORDS].join("; "), n - LISTED_RECORDS),
+    }
+}
+
+/// Как обращаться с контрольной точкой долгой конвертации.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckpointMode {
+    /// Начать заново, сохраняя контрольную точку после каждого сброса вывода
+    Save,
+    /// Продолжить с сохранённой контрольной точки
+    Resume,
+}
+
+/// Конвертирует `from` в `to` порциями по `options.batch_size` записей.
+///
+/// Память ограничена размером порции; вывод сбрасывается каждые `options.flush_every` порций.
+/// При ошибке разбора в середине входных данных уже записанные порции остаются в `to`.
+/// Предупреждения передаются в `on_warning` готовыми строками в порядке входных данных.
+///
+/// С `checkpoint` результат пишется во временный файл рядом с `to`, а после каждого сброса
+/// сохраняется [`Checkpoint`]; `to` появляется только после успешного завершения.
+pub fn convert(
+    from: Resource,
+    from_format: Format,
+    to: Resource,
+    to_format: Format,
+    options: &ConvertOptions,
+    checkpoint: Option<CheckpointMode>,
+    on_warning: &mut dyn FnMut(String),
+) -> Result<ConvertReport, ConvertErr> {
+    let started = Instant::now();
+    let bytes_in = input_size(&from);
+    if checkpoint.is_some() && options.write.append {
+        return Err(checkpoint_err("checkpoints cannot append to an existing output"));
+    }
+    let verify_with = match (options.verify, &from, &to) {
+        (false, _, _) => None,
+        (true, _, _) if options.write.append => return Err(verify_err("--verify cannot check an appended output")),
+        (true, from, to @ Resource::File { .. }) if !from.is_stream() && !to.is_stream() => {
+            Some((from.clone(), to.clone()))
+        }
+        (true, _, _) => return Err(verify_err("--verify needs re-readable input and file output")),
+    };
+    let mut progress = match checkpoint {
+        Some(mode) => Some(Progress::start(&from, &to, mode, options.batch_size)?),
+        None => None,
+    };
+    let batch_size = progress.as_ref().map_or(options.batch_size, |p| p.state.batch_size);
+
+    let mut losses = LossCheck::new(from_format, to_format, options);
+    let parse = losses.parse_options(&options.parse);
+    let resumed = match progress.as_ref().and_then(|p| p.state.input.as_ref()) {
+        Some(input) => read_batches_from(from.clone(), from_format, &parse, batch_size, input)?,
+        None => None,
+    };
+    let done = progress.as_ref().map_or(0, |p| p.state.input_batches);
+    // Без места во входе порции, уже записанные до контрольной точки, читаются заново и пропускаются
+    let (mut batches, skip) = match resumed {
+        Some(batches) => (batches, 0),
+        None => (read_batches(from, from_format, &parse, batch_size)?.with_window(&options.read), done),
+    };
+    let mut writer = match &progress {
+        Some(p) if p.resumed => resume_batches(p.state.part.clone(), to_format, &options.write, &p.state.output)?,
+        Some(p) => write_batches(Resource::File { path: p.state.part.clone() }, to_format, &options.write)?,
+        None => write_batches(to, to_format, &options.write)?,
+    };
+
+    for batch in batches.by_ref().take(skip) {
+        batch?;
+    }
+
+    let mut issues = Vec::new();
+    let (skipped_before, mut warnings) = progress.as_ref().map_or((0, 0), |p| (p.state.skipped, p.state.warnings));
+    let written_before = progress.as_ref().map_or(0, |p| p.state.output.records as usize);
+    let mut parsed = 0;
+    let mut interrupted = false;
+    let mut throttle = options.max_rate.map(Throttle::new);
+
+    for n in 0.. {
+        let Some(batch) = batches.next() else { break };
+        let mut batch = batch?;
+        parsed += batch.records.len();
+        options.transforms.apply_batch(&mut batch);
+        for warning in &batch.warnings {
+            on_warning(warning_message(warning));
+        }
+        losses.check(&batch, on_warning)?;
+
+        writer.write_batch(&batch.records, &batch.annotations)?;
+        warnings += batch.warnings.len();
+        for warning in writer.take_warnings() {
+            on_warning(warning_message(&warning));
+            warnings += 1;
+        }
+        issues.extend(batch.issues);
+
+        let batches_done = done + n + 1;
+        interrupted = options.interrupted();
+        if interrupted || (options.flush_every > 0 && batches_done % options.flush_every == 0) {
+            writer.flush()?;
+            if let Some(p) = &mut progress {
+                p.state.input_batches = batches_done;
+                p.state.input = batches.checkpoint();
+                p.state.skipped = skipped_before + issues.len();
+                p.state.warnings = warnings;
+                p.state.output = writer.checkpoint();
+                p.save()?;
+            }
+        }
+        if interrupted {
+            break;
+        }
+        if let Some(throttle) = &mut throttle {
+            throttle.wait(batch.records.len());
+        }
+    }
+    let records_written = writer.checkpoint().records as usize;
+    // После прерывания с контрольной точкой незавершённый файл остаётся для продолжения,
+    // без неё — дописывается до корректного файла с уже прочитанными записями
+    let bytes_out = match &progress {
+        Some(_) if interrupted => writer.checkpoint().bytes as usize,
+        _ => writer.finish()?,
+    };
+
+    if !interrupted {
+        if let Some(p) = progress {
+            p.complete()?;
+        }
+        if let Some((from, to)) = verify_with {
+            verify(from, from_format, to, to_format, options)?;
+        }
+    }
+
+    let skipped = skipped_before + issues.len();
+    let report = ConvertReport {
+        records_read: written_before + parsed + skipped,
+        records_written,
+        skipped,
+        warnings,
+        bytes_in,
+        bytes_out,
+        elapsed: started.elapsed(),
+        issues,
+    };
+    if interrupted {
+        return Err(ConvertErr::Interrupted { partial: Box::new(report) });
+    }
+    Ok(report)
+}
+
+/// Перечитывает вход и записанный результат и сверяет их записи по порядку без учёта формата.
+///
+/// Входные записи сверяются в том виде, в каком их записал писатель (см. `WriteOptions::max_description_len`).
+fn verify(
+    from: Resource,
+    from_format: Format,
+    to: Resource,
+    to_format: Format,
+    options: &ConvertOptions,
+) -> Result<(), ConvertErr> {
+    let expected = read_batches(from, from_format, &options.parse, options.batch_size)?.with_window(&options.read);
+    let actual = read_batches(to, to_format, &ParseOptions::default(), options.batch_size)?;
+    let expected = expected.map(|batch| {
+        batch.map(|mut batch| {
+            options.transforms.apply_batch(&mut batch);
+            batch.records = batch.records.into_iter().map(|tx| as_written(tx, &options.write)).collect();
+            batch
+        })
+    });
+    let mut expected = records(expected);
+    let mut actual = records(actual);
+
+    for n in 1.. {
+        match (expected.next().transpose()?, actual.next().transpose()?) {
+            (None, None) => break,
+            (Some(input), Some(output)) if input.same_content(&output) => {}
+            (Some(input), Some(_)) => {
+                return Err(verify_err(format!("record {} (TX_ID {}) differs from the input", n, input.tx_id)));
+            }
+            (Some(_), None) => return Err(verify_err(format!("output ends after {} records", n - 1))),
+            (None, Some(_)) => return Err(verify_err(format!("output has more than {} records", n - 1))),
+        }
+    }
+    Ok(())
+}
+
+/// Записи порций по одной; ошибка чтения порции становится последним элементом.
+fn records(batches: impl Iterator<Item = Result<ParseOutcome, CommonErr>>) -> impl Iterator<Item = Result<TxData, CommonErr>> {
+    batches.flat_map(|batch| match batch {
+        Ok(batch) => batch.records.into_iter().map(Ok).collect::<Vec<_>>(),
+        Err(e) => vec![Err(e)],
+    })
+}
+
+fn verify_err(reason: impl ToString) -> ConvertErr {
+    ConvertErr::Verify {
+        reason: reason.to_string(),
+    }
+}
+
+/// Выдерживает среднюю скорость записи не выше `ConvertOptions::max_rate` с начала конвертации.
+pub(crate) struct Throttle {
+    rate: u64,
+    started: Instant,
+    records: u64,
+}
+
+impl Throttle {
+    pub(crate) fn new(rate: u64) -> Self {
+        Throttle {
+            rate: rate.max(1),
+            started: Instant::now(),
+            records: 0,
+        }
+    }
+
+    /// Учитывает `records` записанных записей и ждёт, пока средняя скорость не опустится до предела.
+    pub(crate) fn wait(&mut self, records: usize) {
+        self.records += records as u64;
+        let due = Duration::from_secs_f64(self.records as f64 / self.rate as f64);
+        if let Some(ahead) = due.checked_sub(self.started.elapsed()) {
+            thread::sleep(ahead);
+        }
+    }
+}
+
+/// Предупреждение разбора готовой строкой для `on_warning`.
+pub(crate) fn warning_message(warning: &ParseWarning) -> String {
+    match warning.line {
+        Some(line) => format!("warning: line {}: {}", line, warning.kind),
+        None => format!("warning: {}", warning.kind),
+    }
+}
+
+/// Проверка порций на потери при конвертации (см. `parser::capabilities`): о каждой записи,
+/// которую результат не сохранит, сообщается предупреждением, а с `ConvertOptions::no_lossy`
+/// конвертация останавливается до записи порции.
+pub(crate) struct LossCheck {
+    /// Потери, возможные для этой пары форматов и настроек
+    losses: Vec<Loss>,
+    /// Потери всего входа, о которых уже сообщено
+    reported: Vec<Loss>,
+    no_lossy: bool,
+    /// Сколько записей уже проверено: номер записи, если у порции нет происхождения
+    checked: usize,
+}
+
+impl LossCheck {
+    pub(crate) fn new(from_format: Format, to_format: Format, options: &ConvertOptions) -> LossCheck {
+        LossCheck {
+            losses: conversion_losses(from_format, &options.parse, to_format, &options.write),
+            reported: Vec::new(),
+            no_lossy: options.no_lossy,
+            checked: 0,
+        }
+    }
+
+    /// Настройки разбора: если потери возможны, записи помечаются происхождением,
+    /// чтобы назвать затронутые по месту во входе.
+    pub(crate) fn parse_options(&self, parse: &ParseOptions) -> ParseOptions {
+        ParseOptions { provenance: parse.provenance || !self.losses.is_empty(), ..parse.clone() }
+    }
+
+    /// Проверяет порцию перед записью.
+    ///
+    /// # Errors
+    /// `ConvertErr::Lossy` с `ConvertOptions::no_lossy`, если порция что-то теряет.
+    pub(crate) fn check(&mut self, batch: &ParseOutcome, on_warning: &mut dyn FnMut(String)) -> Result<(), ConvertErr> {
+        for &loss in &self.losses {
+            let whole_input = match loss {
+                Loss::Comments => Some(!batch.annotations.is_empty()),
+                Loss::ExtraColumns => {
+                    Some(batch.warnings.iter().any(|w| matches!(w.kind, WarningKind::ExtraColumns { .. })))
+                }
+                Loss::StringTxIds | Loss::LongDescriptions => None,
+            };
+            let records: Vec<String> = match whole_input {
+                Some(found) if !found || self.reported.contains(&loss) => continue,
+                Some(_) => Vec::new(),
+                None => batch
+                    .records
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, tx)| loss.affects(tx))
+                    .map(|(i, _)| match batch.provenance.get(i) {
+                        Some(at) => at.to_string(),
+                        None => format!("record {}", self.checked + i + 1),
+                    })
+                    .collect(),
+            };
+            if whole_input.is_none() && records.is_empty() {
+                continue;
+            }
+            if self.no_lossy {
+                return Err(ConvertErr::Lossy { loss, records });
+            }
+            self.reported.push(loss);
+            if records.is_empty() {
+                on_warning(format!("warning: lossy conversion: {}", loss));
+            }
+            for at in records {
+                on_warning(format!("warning: lossy conversion: {}: {}", at, loss));
+            }
+        }
+        self.checked += batch.records.len();
+        Ok(())
+    }
+}
+
+/// Размер входа, если его можно узнать до чтения.
+pub(crate) fn input_size(from: &Resource) -> Option<u64> {
+    match from {
+        // У именованного канала размер нулевой, а не неизвестный
+        Resource::File { path } => fs::metadata(path).ok().filter(|m| m.is_file()).map(|m| m.len()),
+        Resource::Memory { data } => Some(data.get_ref().len() as u64),
+        _ => None,
+    }
+}
+
+/// Контрольная точка текущей конвертации и пути, к которым она относится.
+struct Progress {
+    /// Итоговый выходной файл
+    to: PathBuf,
+    /// Файл контрольной точки
+    path: PathBuf,
+    state: Checkpoint,
+    /// Конвертация продолжается с сохранённой точки
+    resumed: bool,
+}
+
+impl Progress {
+    fn start(from: &Resource, to: &Resource, mode: CheckpointMode, batch_size: usize) -> Result<Self, ConvertErr> {
+        // Вход перечитывается при продолжении, поэтому он должен быть файлом
+        if [from, to].iter().any(|r| matches!(r, Resource::File { .. }) && r.is_stream()) {
+            return Err(checkpoint_err("checkpoints need regular files, not pipes or devices"));
+        }
+        let (Resource::File { path: from }, Resource::File { path: to }) = (from, to) else {
+            return Err(checkpoint_err("checkpoints need file input and output"));
+        };
+        let path = Checkpoint::path_for(to);
+        let source = InputIdentity::of(from).map_err(checkpoint_err)?;
+
+        let (state, resumed) = match mode {
+            CheckpointMode::Save => {
+                let state = Checkpoint {
+                    source: Some(source),
+                    part: Checkpoint::part_for(to),
+                    batch_size,
+                    input_batches: 0,
+                    input: None,
+                    skipped: 0,
+                    warnings: 0,
+                    output: WriteCheckpoint::default(),
+                };
+                (state, false)
+            }
+            CheckpointMode::Resume => match Checkpoint::load(&path).map_err(checkpoint_err)? {
+                Some(state) if state.source.as_ref() == Some(&source) => (state, true),
+                Some(_) => return Err(ConvertErr::InputChanged { path: from.clone() }),
+                None => return Err(checkpoint_err(format!("no checkpoint at {}", path.display()))),
+            },
+        };
+
+        Ok(Progress {
+            to: to.clone(),
+            path,
+            state,
+            resumed,
+        })
+    }
+
+    fn save(&self) -> Result<(), ConvertErr> {
+        self.state.save(&self.path).map_err(checkpoint_err)
+    }
+
+    /// Переносит готовый результат на место и удаляет контрольную точку.
+    fn complete(self) -> Result<(), ConvertErr> {
+        fs::rename(&self.state.part, &self.to).map_err(checkpoint_err)?;
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(checkpoint_err(e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn checkpoint_err(reason: impl ToString) -> ConvertErr {
+    ConvertErr::Checkpoint {
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use super::*;
+    use crate::parser::Transforms;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    fn convert_file(
+        from: PathBuf,
+        from_format: Format,
+        to: PathBuf,
+        to_format: Format,
+        options: &ConvertOptions,
+    ) -> Result<ConvertReport, ConvertErr> {
+        let (from, to) = (Resource::File { path: from }, Resource::File { path: to });
+        convert(from, from_format, to, to_format, options, None, &mut |_| {})
+    }
+
+    #[test]
+    #[cfg(feature = "text")]
+    fn test_convert_reports_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("in.csv");
+        let input = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                     1,DEPOSIT,0,10,100,1700000000,SUCCESS,\"x\"\n\
+                     oops\n\
+                     2,DEPOSIT,0,10,100,1700000000,SUCCESS,\"y\"\n";
+        fs::write(&from, input).unwrap();
+        let to = dir.path().join("out.txt");
+        let options = ConvertOptions {
+            parse: ParseOptions::collect(),
+            ..ConvertOptions::default()
+        };
+
+        let report = convert_file(from, Format::YpBankCsv, to.clone(), Format::YpBankText, &options).unwrap();
+
+        assert_eq!(report.records_read, 3);
+        assert_eq!(report.records_written, 2);
+        assert_eq!(report.skipped, 1);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.bytes_in, Some(input.len() as u64));
+        assert_eq!(report.bytes_out as u64, fs::metadata(&to).unwrap().len());
+    }
+
+    #[test]
+    fn test_transforms_between_read_and_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("in.csv");
+        fs::write(
+            &from,
+            "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+             1,DEPOSIT,0,10,100,1700000000,SUCCESS,\"x\"\n\
+             2,DEPOSIT,0,10,0,1700000000,SUCCESS,\"y\"\n\
+             3,DEPOSIT,0,10,300,1700000000,SUCCESS,\"z\"\n",
+        )
+        .unwrap();
+        let to = dir.path().join("out.csv");
+        let options = ConvertOptions {
+            verify: true,
+            transforms: Transforms::default()
+                .then(|tx| (tx.amount != 0).then_some(tx))
+                .then(|tx| Some(TxData { description: tx.description.to_uppercase().into(), ..tx })),
+            ..ConvertOptions::default()
+        };
+
+        let report = convert_file(from, Format::YpBankCsv, to.clone(), Format::YpBankCsv, &options).unwrap();
+
+        assert_eq!((report.records_read, report.records_written), (3, 2));
+        let written = fs::read_to_string(&to).unwrap();
+        assert!(written.ends_with("1,DEPOSIT,0,10,100,1700000000,SUCCESS,X\n3,DEPOSIT,0,10,300,1700000000,SUCCESS,Z\n"));
+    }
+
+    #[test]
+    fn test_interrupt_stops_after_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("in.csv");
+        let mut input = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n".to_string();
+        for id in 1..=3 {
+            input.push_str(&format!("{},DEPOSIT,0,10,100,1700000000,SUCCESS,\"x\"\n", id));
+        }
+        fs::write(&from, &input).unwrap();
+        let flag = Arc::new(AtomicBool::new(true));
+        let options = ConvertOptions {
+            batch_size: 1,
+            interrupt: Some(flag.clone()),
+            ..ConvertOptions::default()
+        };
+
+        let to = dir.path().join("out.csv");
+        let err = convert_file(from.clone(), Format::YpBankCsv, to.clone(), Format::YpBankCsv, &options);
+        let Err(ConvertErr::Interrupted { partial }) = err else { panic!() };
+        assert_eq!((partial.records_written, partial.bytes_out as u64), (1, fs::metadata(&to).unwrap().len()));
+        assert!(fs::read_to_string(&to).unwrap().ends_with("SUCCESS,x\n"));
+
+        // С контрольной точкой результат появляется только после продолжения
+        let (source, target) = (Resource::File { path: from.clone() }, Resource::File { path: dir.path().join("resumed.csv") });
+        let err = convert(source.clone(), Format::YpBankCsv, target.clone(), Format::YpBankCsv, &options, Some(CheckpointMode::Save), &mut |_| {});
+        assert!(matches!(err, Err(ConvertErr::Interrupted { .. })));
+        assert!(!dir.path().join("resumed.csv").exists());
+        let saved = Checkpoint::load(&Checkpoint::path_for(&dir.path().join("resumed.csv"))).unwrap().unwrap();
+        let second_row = input.find("2,DEPOSIT").unwrap() as u64;
+        assert_eq!(saved.input.map(|input| (input.offset, input.lines, input.records)), Some((second_row, 2, 1)));
+
+        let saved_source = saved.source.unwrap();
+        assert_eq!((saved_source.path, saved_source.size), (from.clone(), input.len() as u64));
+
+        // Изменённый вход не продолжается, даже если размер тот же
+        fs::write(&from, input.replacen("1,DEPOSIT", "broken!!!", 1)).unwrap();
+        let set_modified = |ms: u128| {
+            let time = std::time::UNIX_EPOCH + Duration::from_millis(ms as u64);
+            fs::File::options().write(true).open(&from).unwrap().set_modified(time).unwrap();
+        };
+        set_modified(saved_source.modified_ms + 1000);
+        flag.store(false, Ordering::SeqCst);
+        let resume = |source: Resource, target: Resource| {
+            convert(source, Format::YpBankCsv, target, Format::YpBankCsv, &options, Some(CheckpointMode::Resume), &mut |_| {})
+        };
+        let Err(ConvertErr::InputChanged { path }) = resume(source.clone(), target.clone()) else { panic!() };
+        assert_eq!(path, from);
+
+        // Продолжение не перечитывает уже записанное начало входа
+        set_modified(saved_source.modified_ms);
+        let report = resume(source, target).unwrap();
+        assert_eq!(report.records_written, 3);
+    }
+
+    #[test]
+    fn test_limit_rate_paces_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("in.csv");
+        let mut input = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n".to_string();
+        for id in 1..=30 {
+            input.push_str(&format!("{},DEPOSIT,0,10,100,1700000000,SUCCESS,\"x\"\n", id));
+        }
+        fs::write(&from, input).unwrap();
+        let mut options = ConvertOptions {
+            flush_every: 0,
+            jobs: 2,
+            ..ConvertOptions::default()
+        };
+        options.limit_rate(200);
+        assert_eq!((options.max_rate, options.batch_size, options.flush_every), (Some(100), 10, 1));
+
+        let report = convert_file(from, Format::YpBankCsv, dir.path().join("out.csv"), Format::YpBankCsv, &options).unwrap();
+        assert_eq!(report.records_written, 30);
+        assert!(report.elapsed >= Duration::from_millis(300), "{:?}", report.elapsed);
+    }
+
+    #[test]
+    #[cfg(all(feature = "bin", feature = "text"))]
+    fn test_lossy_records_warn_or_stop() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("in.text");
+        let record = |id: &str| format!("TX_ID: {}\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 10\nAMOUNT: 100\nTIMESTAMP: 1700000000\nSTATUS: SUCCESS\nDESCRIPTION: \"x\"\n", id);
+        fs::write(&from, format!("# kept\n{}\n{}", record("1"), record("abc"))).unwrap();
+        let mut options = ConvertOptions::default();
+        options.parse.text.keep_comments = true;
+        let (source, target) = (Resource::File { path: from }, Resource::File { path: dir.path().join("out.csv") });
+
+        // В CSV теряются только комментарии: одно предупреждение на весь вход
+        let mut warnings = Vec::new();
+        convert(source.clone(), Format::YpBankText, target, Format::YpBankCsv, &options, None, &mut |w| warnings.push(w)).unwrap();
+        assert_eq!(warnings, ["warning: lossy conversion: comments and blank lines are dropped"]);
+
+        options.no_lossy = true;
+        options.parse.text.keep_comments = false;
+        let target = Resource::File { path: dir.path().join("out.bin") };
+        let err = convert(source, Format::YpBankText, target, Format::YpBankBin, &options, None, &mut |_| {});
+        if let Err(ConvertErr::Lossy { loss: Loss::StringTxIds, records }) = err {
+            assert_eq!(records.len(), 1);
+            assert!(records[0].contains("record 2, line 11"), "{}", records[0]);
+        } else {
+            panic!()
+        }
+        assert_eq!(fs::metadata(dir.path().join("out.bin")).unwrap().len(), 0);
+    }
+
+    #[test]
+    #[cfg(all(feature = "bin", feature = "text"))]
+    fn test_verify_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let header = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n";
+        let row = |amount: i64| format!("1,DEPOSIT,0,10,{},1700000000,SUCCESS,\"x\"\n", amount);
+        let from = dir.path().join("in.csv");
+        fs::write(&from, format!("{}{}", header, row(100))).unwrap();
+        let options = ConvertOptions {
+            verify: true,
+            ..ConvertOptions::default()
+        };
+
+        convert_file(from.clone(), Format::YpBankCsv, dir.path().join("out.bin"), Format::YpBankBin, &options).unwrap();
+
+        let changed = dir.path().join("changed.csv");
+        fs::write(&changed, format!("{}{}", header, row(200))).unwrap();
+        let from = Resource::File { path: from };
+        let err = verify(from.clone(), Format::YpBankCsv, Resource::File { path: changed }, Format::YpBankCsv, &options);
+        if let Err(ConvertErr::Verify { reason }) = err {
+            assert_eq!(reason, "record 1 (TX_ID 1) differs from the input");
+        } else {
+            panic!()
+        }
+
+        let err = convert(from.clone(), Format::YpBankCsv, Resource::Console, Format::YpBankText, &options, None, &mut |_| {});
+        assert!(matches!(err, Err(ConvertErr::Verify { .. })));
+
+        // Обрезанное писателем описание сверяется с таким же обрезанным входом
+        let long = dir.path().join("long.csv");
+        fs::write(&long, format!("{}1,DEPOSIT,0,10,100,1700000000,SUCCESS,\"abcdef\"\n", header)).unwrap();
+        let options = ConvertOptions {
+            verify: true,
+            write: crate::parser::WriteOptions { max_description_len: Some(3), ..Default::default() },
+            ..ConvertOptions::default()
+        };
+        convert_file(long, Format::YpBankCsv, dir.path().join("short.bin"), Format::YpBankBin, &options).unwrap();
+    }
+}
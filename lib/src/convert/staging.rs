@@ -0,0 +1,152 @@
+//! Запись нескольких выходных файлов по принципу «всё или ничего».
+//!
+//! Каждый выходной файл сначала пишется во временный файл рядом с ним. Только когда записаны
+//! все, [`StagedOutputs::commit`] переименовывает их на место; иначе временные файлы удаляются,
+//! и прежние файлы остаются нетронутыми: следующий шаг конвейера не увидит половину выгрузки.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::model::errors::{CommonErr, IoErr};
+
+/// Временные файлы выходных файлов одной команды; см. описание модуля.
+///
+/// Если набор удаляется без [`StagedOutputs::commit`] (ошибка, прерывание), временные файлы
+/// удаляются вместе с ним.
+#[derive(Debug, Default)]
+pub struct StagedOutputs {
+    /// Пары (временный файл, выходной файл) в порядке [`StagedOutputs::stage`]
+    files: Vec<(PathBuf, PathBuf)>,
+}
+
+impl StagedOutputs {
+    pub fn new() -> StagedOutputs {
+        StagedOutputs::default()
+    }
+
+    /// Временный файл для выходного файла `target`: `<target>.<pid>.staged`.
+    ///
+    /// С `copy_existing` временный файл начинается с копии существующего `target`, чтобы
+    /// дописывание (`WriteOptions::append`) продолжало его, а не начинало заново.
+    ///
+    /// # Errors
+    /// `CommonErr::IO`, если существующий файл не удалось скопировать.
+    pub fn stage(&mut self, target: &Path, copy_existing: bool) -> Result<PathBuf, CommonErr> {
+        let mut name = target.as_os_str().to_owned();
+        name.push(format!(".{}.staged", std::process::id()));
+        let staged = PathBuf::from(name);
+        if copy_existing && target.is_file() {
+            fs::copy(target, &staged).map_err(|e| IoErr::output(e, Some(staged.clone())))?;
+        }
+        self.files.push((staged.clone(), target.to_path_buf()));
+        Ok(staged)
+    }
+
+    /// Выходные файлы в порядке [`StagedOutputs::stage`].
+    pub fn targets(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(_, target)| target.as_path())
+    }
+
+    /// Переносит все временные файлы на место выходных.
+    ///
+    /// Прежние выходные файлы сначала отодвигаются в `<target>.<pid>.prev`: если какой-то
+    /// перенос не удался, уже перенесённые файлы убираются, а прежние возвращаются на место.
+    ///
+    /// # Errors
+    /// `CommonErr::IO` с путём файла, который не удалось перенести.
+    pub fn commit(mut self) -> Result<(), CommonErr> {
+        let files = std::mem::take(&mut self.files);
+        let previous: Vec<PathBuf> = files.iter().map(|(staged, _)| staged.with_extension("prev")).collect();
+        let mut moved_aside = Vec::new();
+        let mut placed = Vec::new();
+        let result = (|| {
+            for ((_, target), previous) in files.iter().zip(&previous) {
+                match fs::rename(target, previous) {
+                    Ok(()) => moved_aside.push((previous, target)),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(IoErr::output(e, Some(target.clone()))),
+                }
+            }
+            for (staged, target) in &files {
+                fs::rename(staged, target).map_err(|e| IoErr::output(e, Some(target.clone())))?;
+                placed.push(target);
+            }
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => {
+                for (previous, _) in moved_aside {
+                    let _ = fs::remove_file(previous);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                for target in placed {
+                    let _ = fs::remove_file(target);
+                }
+                for (previous, target) in moved_aside {
+                    let _ = fs::rename(previous, target);
+                }
+                for (staged, _) in &files {
+                    let _ = fs::remove_file(staged);
+                }
+                Err(e.into())
+            }
+        }
+    }
+}
+
+impl Drop for StagedOutputs {
+    fn drop(&mut self) {
+        for (staged, _) in &self.files {
+            let _ = fs::remove_file(staged);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_replaces_all_and_drop_rolls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.csv"), dir.path().join("b.csv"));
+        fs::write(&a, "old a").unwrap();
+
+        // Без commit прежние файлы остаются, временные удаляются
+        let mut staged = StagedOutputs::new();
+        fs::write(staged.stage(&a, false).unwrap(), "new a").unwrap();
+        fs::write(staged.stage(&b, false).unwrap(), "new b").unwrap();
+        drop(staged);
+        assert_eq!(fs::read_to_string(&a).unwrap(), "old a");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let mut staged = StagedOutputs::new();
+        let copy = staged.stage(&a, true).unwrap();
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "old a");
+        fs::write(&copy, "new a").unwrap();
+        fs::write(staged.stage(&b, false).unwrap(), "new b").unwrap();
+        assert_eq!(staged.targets().collect::<Vec<_>>(), [a.as_path(), b.as_path()]);
+        staged.commit().unwrap();
+        assert_eq!(fs::read_to_string(&a).unwrap(), "new a");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "new b");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        // Перенос не удался (временного файла нет): прежние файлы возвращаются на место
+        let mut staged = StagedOutputs::new();
+        fs::write(staged.stage(&a, false).unwrap(), "newer a").unwrap();
+        staged.stage(&b, false).unwrap();
+        let err = staged.commit().unwrap_err();
+        if let CommonErr::IO(IoErr::OutputErr { path: Some(path), .. }) = err {
+            assert_eq!(path, b);
+        } else {
+            panic!()
+        }
+        assert_eq!(fs::read_to_string(&a).unwrap(), "new a");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "new b");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
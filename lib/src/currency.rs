@@ -0,0 +1,236 @@
+//! Пересчёт сумм в одну валюту, чтобы сводки по выгрузкам в разных валютах были сопоставимы.
+//!
+//! Файл курсов — текст со строками «код валюты, курс»: курс — стоимость единицы валюты в общей
+//! базе (любой, но одной для всего файла). Пустые строки и строки, начинающиеся с `#`,
+//! пропускаются:
+//!
+//! ```text
+//! # курсы на 2024-01-01, база — USD
+//! USD 1
+//! EUR 1.1
+//! RUB 0.011
+//! ```
+//!
+//! Записи не хранят валюту, поэтому валюта входа задаётся для всего входа.
+//! [`CurrencyConversion`] подключается в [`Transforms`] и переписывает `AMOUNT`
+//! (в минимальных единицах, с округлением половины от нуля).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::model::data::TxData;
+use crate::parser::Transforms;
+
+/// Наибольшее число цифр до и после точки в курсе: произведения курсов помещаются в i128.
+const MAX_RATE_DIGITS: usize = 9;
+
+#[derive(Error, Debug)]
+pub enum CurrencyErr {
+    /// Файл не удалось прочитать
+    #[error("failed to read rates {}: {err}", path.display())]
+    Io { path: PathBuf, err: std::io::Error },
+    /// Строка — не «код валюты, курс» или повторяет код
+    #[error("rates line {line}: {reason}")]
+    Invalid { line: usize, reason: String },
+    /// Валюты нет в файле курсов
+    #[error("no rate for currency {code}")]
+    UnknownCurrency { code: String },
+}
+
+/// Курс как дробь `numerator / denominator` — без потерь точности `f64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rate {
+    numerator: i128,
+    denominator: i128,
+}
+
+impl Rate {
+    fn parse(s: &str) -> Option<Rate> {
+        let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+        let digits = |part: &str| part.len() <= MAX_RATE_DIGITS && part.bytes().all(|b| b.is_ascii_digit());
+        if int.is_empty() || !digits(int) || !digits(frac) {
+            return None;
+        }
+        let denominator = 10i128.pow(frac.len() as u32);
+        let numerator = format!("{}{}", int, frac).parse::<i128>().ok()?;
+        (numerator > 0).then_some(Rate { numerator, denominator })
+    }
+}
+
+/// Курсы валют из файла (см. описание модуля).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateTable(HashMap<String, Rate>);
+
+impl RateTable {
+    /// Читает курсы из файла `path`.
+    pub fn load(path: &Path) -> Result<RateTable, CurrencyErr> {
+        let text = fs::read_to_string(path).map_err(|err| CurrencyErr::Io { path: path.to_path_buf(), err })?;
+        RateTable::parse(&text)
+    }
+
+    /// Разбирает курсы из текста.
+    pub fn parse(text: &str) -> Result<RateTable, CurrencyErr> {
+        let mut rates = HashMap::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: String| CurrencyErr::Invalid { line: n + 1, reason };
+            let (code, rate) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [code, rate] => (code.to_uppercase(), rate),
+                _ => return Err(invalid(format!("expected '<CODE> <RATE>', got '{}'", line))),
+            };
+            let rate = Rate::parse(rate).ok_or_else(|| invalid(format!("not a positive decimal rate: '{}'", rate)))?;
+            if rates.insert(code.clone(), rate).is_some() {
+                return Err(invalid(format!("duplicate currency {}", code)));
+            }
+        }
+        Ok(RateTable(rates))
+    }
+
+    pub fn contains(&self, code: &str) -> bool {
+        self.0.contains_key(&code.to_uppercase())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Пересчёт из валюты `from` в валюту `to`.
+    pub fn conversion(&self, from: &str, to: &str) -> Result<CurrencyConversion, CurrencyErr> {
+        let rate = |code: &str| {
+            let code = code.to_uppercase();
+            match self.0.get(&code) {
+                Some(rate) => Ok((code, *rate)),
+                None => Err(CurrencyErr::UnknownCurrency { code }),
+            }
+        };
+        let ((from, source), (to, target)) = (rate(from)?, rate(to)?);
+        let (numerator, denominator) = (source.numerator * target.denominator, source.denominator * target.numerator);
+        let gcd = gcd(numerator, denominator);
+        Ok(CurrencyConversion {
+            from,
+            to,
+            numerator: numerator / gcd,
+            denominator: denominator / gcd,
+            keep_original: false,
+        })
+    }
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Пересчёт сумм записей из одной валюты в другую.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurrencyConversion {
+    from: String,
+    to: String,
+    /// Множитель суммы `numerator / denominator`
+    numerator: i128,
+    denominator: i128,
+    /// Дописывать к описанию исходную сумму и валюту (см. [`CurrencyConversion::keep_original`])
+    keep_original: bool,
+}
+
+impl CurrencyConversion {
+    /// Дописывать к описанию пересчитанной записи исходную сумму и валюту:
+    /// `"Оплата (original: 1250 EUR)"`.
+    pub fn keep_original(mut self, keep: bool) -> Self {
+        self.keep_original = keep;
+        self
+    }
+
+    /// Сумма `amount` в валюте назначения; `None`, если она не помещается в i64.
+    pub fn convert(&self, amount: i64) -> Option<i64> {
+        let scaled = (amount as i128).checked_mul(self.numerator)?;
+        // Округление половины от нуля
+        let half = self.denominator / 2;
+        let rounded = if scaled >= 0 { scaled.checked_add(half)? } else { scaled.checked_sub(half)? };
+        i64::try_from(rounded / self.denominator).ok()
+    }
+
+    /// Пересчитанная запись; `None`, если сумма не помещается в i64.
+    pub fn apply(&self, mut tx: TxData) -> Option<TxData> {
+        let amount = self.convert(tx.amount)?;
+        if self.keep_original {
+            tx.description = format!("{} (original: {} {})", tx.description, tx.amount, self.from).into();
+        }
+        tx.amount = amount;
+        Some(tx)
+    }
+
+    /// Добавляет пересчёт в конец цепочки `transforms`; запись, сумма которой не помещается
+    /// в i64 после пересчёта, отбрасывается.
+    pub fn install(self, transforms: Transforms) -> Transforms {
+        transforms.then(move |tx| self.apply(tx))
+    }
+}
+
+impl fmt::Display for CurrencyConversion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.from, self.to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::data::{Format, Status, TxType};
+
+    #[test]
+    fn test_conversion_rewrites_amounts() {
+        let rates = RateTable::parse("# база — USD\nUSD 1\neur 1.1\n\nRUB 0.011\n").unwrap();
+        assert_eq!(rates.len(), 3);
+        assert!(rates.contains("EUR"));
+
+        let eur_to_usd = rates.conversion("EUR", "usd").unwrap();
+        assert_eq!(eur_to_usd.to_string(), "EUR -> USD");
+        assert_eq!(eur_to_usd.convert(1000), Some(1100));
+        let rub_to_eur = rates.conversion("RUB", "EUR").unwrap();
+        assert_eq!((rub_to_eur.convert(1000), rub_to_eur.convert(-50), rub_to_eur.convert(49)), (Some(10), Some(-1), Some(0)));
+        assert_eq!(rates.conversion("USD", "EUR").unwrap().convert(i64::MAX), Some(8384883669867978006));
+        assert_eq!(eur_to_usd.convert(i64::MAX), None);
+
+        let tx = TxData {
+            tx_id: 1.into(),
+            tx_type: TxType::Transfer,
+            from_user_id: 10,
+            to_user_id: 11,
+            amount: 1250,
+            timestamp: 1700000000000,
+            status: Status::Success,
+            description: "Оплата".into(),
+            format: Format::YpBankCsv,
+        };
+        let transforms = eur_to_usd.keep_original(true).install(Transforms::default());
+        let converted = transforms.apply(tx).unwrap();
+        assert_eq!((converted.amount, &*converted.description), (1375, "Оплата (original: 1250 EUR)"));
+
+        if let Err(CurrencyErr::UnknownCurrency { code }) = rates.conversion("GBP", "USD") {
+            assert_eq!(code, "GBP");
+        } else {
+            panic!()
+        }
+        if let Err(CurrencyErr::Invalid { line, reason }) = RateTable::parse("USD 1\nUSD 2\n") {
+            assert_eq!((line, reason.as_str()), (2, "duplicate currency USD"));
+        } else {
+            panic!()
+        }
+        assert!(matches!(RateTable::parse("EUR -1"), Err(CurrencyErr::Invalid { line: 1, .. })));
+        assert!(matches!(RateTable::parse("EUR 0"), Err(CurrencyErr::Invalid { line: 1, .. })));
+    }
+}
@@ -0,0 +1,352 @@
+//! Дельта между двумя снимками транзакций: добавленные, изменённые и удалённые записи.
+//!
+//! Файл дельты — CSV, в котором перед колонками записи стоит колонка `OP`:
+//!
+//! ```csv
+//! OP,TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION
+//! ADDED,7,DEPOSIT,0,10,100,1700000000,SUCCESS,"new"
+//! UPDATED,3,TRANSFER,10,20,50,1700000000,SUCCESS,"changed"
+//! REMOVED,5
+//! ```
+//!
+//! Записи сравниваются по `TX_ID`. Снимки могут быть в разных форматах.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+
+use csv::{ReaderBuilder, StringRecord};
+
+use crate::model::data::{Format, TxData};
+use crate::model::errors::{CommonErr, IoErr, ParserErr};
+use crate::model::resource::Resource;
+use crate::model::tx_id::TxId;
+use crate::parser::concrete::csv_parser::{csv_err, from_csv_record, TxnToCsv};
+use crate::parser::io::reader::{open, read_batches};
+use crate::parser::io::writer::{write, write_batches};
+use crate::parser::options::{ConvertOptions, CsvOptions, ParseOptions, WriteOptions};
+
+const DELTA_HEADER_LINE: &str =
+    "OP,TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION";
+
+/// Одно изменение снимка.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeltaOp {
+    /// Записи с таким `TX_ID` в базовом снимке не было
+    Added(TxData),
+    /// Запись с тем же `TX_ID` изменилась; хранится новая версия
+    Updated(TxData),
+    /// Записи больше нет
+    Removed { tx_id: TxId },
+}
+
+/// Сколько изменений каждого вида в дельте.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeltaSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+impl DeltaSummary {
+    fn of(ops: &[DeltaOp]) -> Self {
+        let mut summary = DeltaSummary::default();
+        for op in ops {
+            match op {
+                DeltaOp::Added(_) => summary.added += 1,
+                DeltaOp::Updated(_) => summary.updated += 1,
+                DeltaOp::Removed { .. } => summary.removed += 1,
+            }
+        }
+        summary
+    }
+}
+
+/// Строит дельту от снимка `base` к снимку `current` и записывает её в `to`.
+///
+/// Базовый снимок держится в памяти целиком, текущий читается порциями. Добавленные
+/// и изменённые записи идут в порядке `current`, удалённые — в конце, в порядке `base`.
+pub fn diff(
+    base: Resource,
+    base_format: Format,
+    current: Resource,
+    current_format: Format,
+    to: Resource,
+) -> Result<DeltaSummary, CommonErr> {
+    let batch_size = ConvertOptions::default().batch_size;
+    let mut remaining = HashMap::new();
+    for batch in read_batches(base, base_format, &ParseOptions::default(), batch_size)? {
+        for tx in batch?.records {
+            let index = remaining.len();
+            remaining.insert(tx.tx_id.clone(), (index, tx));
+        }
+    }
+
+    let mut ops = Vec::new();
+    for batch in read_batches(current, current_format, &ParseOptions::default(), batch_size)? {
+        for tx in batch?.records {
+            match remaining.remove(&tx.tx_id) {
+                None => ops.push(DeltaOp::Added(tx)),
+                Some((_, old)) if !same_record(&old, &tx) => ops.push(DeltaOp::Updated(tx)),
+                Some(_) => {}
+            }
+        }
+    }
+
+    let mut removed: Vec<_> = remaining.into_values().map(|(index, tx)| (index, tx.tx_id)).collect();
+    removed.sort_unstable();
+    ops.extend(removed.into_iter().map(|(_, tx_id)| DeltaOp::Removed { tx_id }));
+
+    write_delta(&ops, to)?;
+    Ok(DeltaSummary::of(&ops))
+}
+
+/// Применяет дельту `delta` к снимку `base` и записывает новый снимок в `to`.
+///
+/// Снимок читается и пишется порциями, в памяти держится только дельта. Изменённые
+/// записи остаются на своих местах, добавленные дописываются в конец в порядке дельты.
+///
+/// # Errors
+/// `ParserErr::DeltaConflict`, если добавленная запись уже есть в снимке, а изменённой
+/// или удалённой в нём нет. Записанное до обнаружения конфликта остаётся в `to`.
+pub fn apply(
+    base: Resource,
+    base_format: Format,
+    delta: Resource,
+    to: Resource,
+    to_format: Format,
+    options: &WriteOptions,
+) -> Result<DeltaSummary, CommonErr> {
+    let ops = read_delta(delta)?;
+    let summary = DeltaSummary::of(&ops);
+
+    let mut added = Vec::new();
+    let mut added_ids = HashSet::new();
+    let mut updated = HashMap::new();
+    let mut removed = HashSet::new();
+    for op in ops {
+        match op {
+            DeltaOp::Added(tx) => {
+                added_ids.insert(tx.tx_id.clone());
+                added.push(tx);
+            }
+            DeltaOp::Updated(tx) => {
+                updated.insert(tx.tx_id.clone(), tx);
+            }
+            DeltaOp::Removed { tx_id } => {
+                removed.insert(tx_id);
+            }
+        }
+    }
+
+    let batch_size = ConvertOptions::default().batch_size;
+    let mut writer = write_batches(to, to_format, options)?;
+    for batch in read_batches(base, base_format, &ParseOptions::default(), batch_size)? {
+        let mut records = Vec::new();
+        for tx in batch?.records {
+            if added_ids.contains(&tx.tx_id) {
+                return Err(conflict("ADDED", tx.tx_id));
+            }
+            if removed.remove(&tx.tx_id) {
+                continue;
+            }
+            records.push(updated.remove(&tx.tx_id).unwrap_or(tx));
+        }
+        writer.write_batch(&records, &[])?;
+    }
+
+    if let Some(tx_id) = updated.into_keys().min() {
+        return Err(conflict("UPDATED", tx_id));
+    }
+    if let Some(tx_id) = removed.into_iter().min() {
+        return Err(conflict("REMOVED", tx_id));
+    }
+    writer.write_batch(&added, &[])?;
+    writer.finish()?;
+    Ok(summary)
+}
+
+/// Записывает дельту в ресурс. Возвращает размер записанных данных.
+pub fn write_delta(ops: &[DeltaOp], resource: Resource) -> Result<usize, CommonErr> {
+    let mut content = String::from(DELTA_HEADER_LINE);
+    content.push('\n');
+    for op in ops {
+        match op {
+            DeltaOp::Added(tx) => content.push_str(&format!("ADDED,{}\n", tx.to_csv()?)),
+            DeltaOp::Updated(tx) => content.push_str(&format!("UPDATED,{}\n", tx.to_csv()?)),
+            DeltaOp::Removed { tx_id } => content.push_str(&format!("REMOVED,{}\n", tx_id)),
+        }
+    }
+
+    let path = match &resource {
+        Resource::File { path } => Some(path.clone()),
+        _ => None,
+    };
+    let mut output = write(resource)?;
+    output
+        .write_all(content.as_bytes())
+        .and_then(|_| output.flush())
+        .map_err(|e| IoErr::output(e, path))?;
+    Ok(content.len())
+}
+
+/// Читает дельту из ресурса.
+///
+/// # Errors
+/// Ошибка разбора содержит номер строки файла дельты.
+pub fn read_delta(resource: Resource) -> Result<Vec<DeltaOp>, CommonErr> {
+    Ok(parse_delta(open(resource)?)?)
+}
+
+fn parse_delta(reader: Box<dyn Read>) -> Result<Vec<DeltaOp>, ParserErr> {
+    let mut rdr = ReaderBuilder::new().has_headers(true).flexible(true).from_reader(reader);
+    let header = rdr.headers().map_err(|e| csv_err(e).at_line(1))?;
+    let header = header.iter().collect::<Vec<_>>().join(",");
+    if header != DELTA_HEADER_LINE {
+        return Err(ParserErr::InvalidHeader {
+            expected: DELTA_HEADER_LINE.to_string(),
+            actual: header,
+        });
+    }
+
+    let mut ops = Vec::new();
+    for (i, record) in rdr.into_records().enumerate() {
+        let line = i + 2;
+        let record = record.map_err(|e| csv_err(e).at_line(line))?;
+        ops.push(parse_op(&record).map_err(|e| e.at_line(line))?);
+    }
+    Ok(ops)
+}
+
+fn parse_op(record: &StringRecord) -> Result<DeltaOp, ParserErr> {
+    let tx = || from_csv_record(&record.iter().skip(1).collect(), &CsvOptions::default());
+    match record.get(0).unwrap_or_default() {
+        "ADDED" => Ok(DeltaOp::Added(tx()?)),
+        "UPDATED" => Ok(DeltaOp::Updated(tx()?)),
+        "REMOVED" => {
+            let tx_id = record.get(1).ok_or(ParserErr::MissingField { name: "TX_ID" })?;
+            Ok(DeltaOp::Removed {
+                tx_id: TxId::parse(tx_id)?,
+            })
+        }
+        op => Err(ParserErr::InvalidValue {
+            field: "OP",
+            value: op.to_string(),
+        }),
+    }
+}
+
+/// Записи совпадают по содержимому; формат, из которого их прочитали, не важен.
+fn same_record(a: &TxData, b: &TxData) -> bool {
+    a == &TxData { format: a.format, ..b.clone() }
+}
+
+fn conflict(op: &'static str, tx_id: TxId) -> CommonErr {
+    ParserErr::DeltaConflict { op, tx_id }.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::data::{Status, TxType};
+    use std::io::Cursor;
+
+    fn tx(tx_id: u64, amount: i64) -> TxData {
+        TxData {
+            tx_id: tx_id.into(),
+            tx_type: TxType::Deposit,
+            from_user_id: 0,
+            to_user_id: 10,
+            amount,
+            timestamp: 1700000000,
+            status: Status::Success,
+            description: "a, \"b\"".into(),
+            format: Format::YpBankCsv,
+        }
+    }
+
+    fn csv(records: &[TxData]) -> Resource {
+        Resource::Memory {
+            data: Cursor::new(TxData::to_csv_many(records).unwrap().into_bytes()),
+        }
+    }
+
+    #[test]
+    fn test_diff_then_apply_restores_current_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = vec![tx(1, 100), tx(2, 200), tx(3, 300)];
+        let current = vec![tx(3, 300), tx(1, 150), tx(4, 400)];
+
+        let delta = dir.path().join("delta.csv");
+        let summary = diff(
+            csv(&base),
+            Format::YpBankCsv,
+            csv(&current),
+            Format::YpBankCsv,
+            Resource::File { path: delta.clone() },
+        )
+        .unwrap();
+        assert_eq!(
+            summary,
+            DeltaSummary {
+                added: 1,
+                updated: 1,
+                removed: 1
+            }
+        );
+        let ops = read_delta(Resource::File { path: delta.clone() }).unwrap();
+        assert_eq!(
+            ops,
+            vec![DeltaOp::Updated(tx(1, 150)), DeltaOp::Added(tx(4, 400)), DeltaOp::Removed { tx_id: TxId::U64(2) }]
+        );
+
+        let to = dir.path().join("current.csv");
+        apply(
+            csv(&base),
+            Format::YpBankCsv,
+            Resource::File { path: delta },
+            Resource::File { path: to.clone() },
+            Format::YpBankCsv,
+            &WriteOptions::default(),
+        )
+        .unwrap();
+        let ids: Vec<_> = crate::parser::io::reader::read(Resource::File { path: to }, Format::YpBankCsv)
+            .unwrap()
+            .iter()
+            .map(|tx| (tx.tx_id.as_u64().unwrap(), tx.amount))
+            .collect();
+        assert_eq!(ids, vec![(1, 150), (3, 300), (4, 400)]);
+    }
+
+    #[test]
+    fn test_apply_rejects_conflicting_delta() {
+        let delta = Resource::Memory {
+            data: Cursor::new(format!("{}\nREMOVED,9\n", DELTA_HEADER_LINE).into_bytes()),
+        };
+        let err = apply(
+            csv(&[tx(1, 100)]),
+            Format::YpBankCsv,
+            delta,
+            Resource::Memory { data: Cursor::new(Vec::new()) },
+            Format::YpBankCsv,
+            &WriteOptions::default(),
+        )
+        .unwrap_err();
+        if let CommonErr::Parser(ParserErr::DeltaConflict { op, tx_id }) = err {
+            assert_eq!(op, "REMOVED");
+            assert_eq!(tx_id, 9);
+        } else {
+            panic!()
+        }
+
+        let delta = Resource::Memory {
+            data: Cursor::new(format!("{}\nMOVED,9\n", DELTA_HEADER_LINE).into_bytes()),
+        };
+        let err = read_delta(delta).unwrap_err();
+        if let CommonErr::Parser(err) = err {
+            assert_eq!(err.line(), Some(2));
+            assert_eq!(err.field(), Some("OP"));
+        } else {
+            panic!()
+        }
+    }
+}
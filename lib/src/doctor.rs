@@ -0,0 +1,583 @@
+//! Диагностика файла транзакций: все известные эвристики за один проход.
+//!
+//! [`diagnose`] проверяет кодировку, заголовок, кавычки, повторяющиеся TX_ID, подозрительные
+//! метки времени и длины описаний и возвращает находки по убыванию важности, каждую —
+//! с предлагаемым исправлением. Однотипные находки сворачиваются в одну со счётчиком.
+//!
+//! [`write_findings_report`] записывает находки для систем контроля качества данных:
+//! в JSON стабильной схемы или в SARIF 2.1.0.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Cursor, Read};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::model::data::{Format, TxData};
+use crate::model::errors::{CommonErr, IoErr, ParseIssue, ParseWarning, ParserErr, WarningKind};
+use crate::model::resource::Resource;
+use crate::model::tx_id::TxId;
+use crate::model::time::SECONDS_LIMIT;
+use crate::model::validation::{validate_with, FUTURE_SLACK_MS};
+use crate::parser::io::reader::{open, read_from_resource};
+use crate::parser::io::report::write_report;
+use crate::parser::options::ParseOptions;
+
+/// Описания длиннее обрезаются многими получателями выгрузок (`VARCHAR(255)`).
+pub const MAX_DESCRIPTION_LEN: usize = 255;
+
+
+/// Важность находки; находки упорядочены от ошибок к подсказкам.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Записи теряются или конвертация невозможна
+    Error,
+    /// Файл читается, но данные, скорее всего, испорчены
+    Warning,
+    /// Безвредная особенность, о которой стоит знать
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        })
+    }
+}
+
+/// Место находки во входе.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Locator {
+    /// Номер строки (для бинарного формата — порядковый номер записи)
+    Line(usize),
+    /// Запись с этим `TX_ID`
+    TxId(TxId),
+}
+
+impl fmt::Display for Locator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Locator::Line(line) => write!(f, "line {}", line),
+            Locator::TxId(tx_id) => write!(f, "TX_ID {}", tx_id),
+        }
+    }
+}
+
+/// Одна находка диагностики.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Имя эвристики (`encoding`, `duplicate-id`, …)
+    pub check: &'static str,
+    /// Что не так
+    pub message: String,
+    /// Как исправить
+    pub suggestion: String,
+    /// Сколько раз встретилась такая находка
+    pub count: usize,
+    /// Где встретилась впервые; `None` — относится ко всему файлу
+    pub first_at: Option<Locator>,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: [{}] {}", self.severity, self.check, self.message)?;
+        match (&self.first_at, self.count) {
+            (Some(at), 1) => write!(f, " ({})", at)?,
+            (Some(at), count) => write!(f, " ({} times, first at {})", count, at)?,
+            (None, 1) => {},
+            (None, count) => write!(f, " ({} times)", count)?,
+        }
+        write!(f, "\n  fix: {}", self.suggestion)
+    }
+}
+
+/// Находки, сгруппированные по ключу в порядке обнаружения.
+#[derive(Default)]
+struct Findings {
+    list: Vec<Finding>,
+    by_key: HashMap<String, usize>,
+}
+
+impl Findings {
+    fn add(&mut self, key: String, severity: Severity, check: &'static str, message: String, suggestion: &str, at: Option<Locator>) {
+        if let Some(&index) = self.by_key.get(&key) {
+            self.list[index].count += 1;
+            return;
+        }
+        self.by_key.insert(key, self.list.len());
+        self.list.push(Finding {
+            severity,
+            check,
+            message,
+            suggestion: suggestion.to_string(),
+            count: 1,
+            first_at: at,
+        });
+    }
+
+    fn into_sorted(mut self) -> Vec<Finding> {
+        // Сортировка устойчивая: внутри одной важности — порядок обнаружения
+        self.list.sort_by_key(|finding| finding.severity);
+        self.list
+    }
+}
+
+/// Проверяет ресурс в формате `format` всеми эвристиками.
+///
+/// Ошибки разбора, в том числе неверный заголовок, становятся находками, а не ошибкой функции.
+///
+/// # Errors
+/// `CommonErr::IO`, если ресурс не удалось прочитать.
+pub fn diagnose(resource: Resource, format: Format) -> Result<Vec<Finding>, CommonErr> {
+    let path = match &resource {
+        Resource::File { path } => Some(path.clone()),
+        _ => None,
+    };
+    let mut bytes = Vec::new();
+    open(resource)?.read_to_end(&mut bytes).map_err(|e| IoErr::input(e, path))?;
+
+    let mut findings = Findings::default();
+    if bytes.is_empty() {
+        findings.add(
+            "empty".to_string(),
+            Severity::Error,
+            "empty-input",
+            "input is empty".to_string(),
+            "check that the producer wrote the file or piped data to stdin",
+            None,
+        );
+        return Ok(findings.into_sorted());
+    }
+    if format != Format::YpBankBin {
+        check_encoding(&bytes, format, &mut findings);
+    }
+
+    match read_from_resource(Box::new(Cursor::new(bytes)), format, &ParseOptions::collect()) {
+        Ok(outcome) => {
+            for issue in &outcome.issues {
+                add_issue(issue, &mut findings);
+            }
+            for warning in &outcome.warnings {
+                add_warning(warning, &mut findings);
+            }
+            check_records(&outcome.records, &mut findings);
+        },
+        Err(ParserErr::Io { source, path }) => {
+            return Err(IoErr::InputErr {
+                path,
+                kind: source.kind(),
+                source,
+            }
+            .into())
+        },
+        Err(e) => add_issue(&ParseIssue::from(e), &mut findings),
+    }
+
+    Ok(findings.into_sorted())
+}
+
+fn check_encoding(bytes: &[u8], format: Format, findings: &mut Findings) {
+    if bytes.starts_with(b"\xEF\xBB\xBF") {
+        let (severity, suggestion) = match format {
+            Format::YpBankCsv => (Severity::Info, "nothing to do: the CSV reader skips the BOM"),
+            _ => (Severity::Warning, "remove the BOM: the text reader treats it as part of the first key"),
+        };
+        findings.add("bom".to_string(), severity, "encoding", "file starts with a UTF-8 BOM".to_string(), suggestion, None);
+    }
+    if let Err(e) = std::str::from_utf8(bytes) {
+        let line = bytes[..e.valid_up_to()].iter().filter(|&&b| b == b'\n').count() + 1;
+        findings.add(
+            "utf8".to_string(),
+            Severity::Error,
+            "encoding",
+            "file is not valid UTF-8".to_string(),
+            "re-encode the file to UTF-8 (e.g. `iconv -f cp1251 -t utf-8`)",
+            Some(Locator::Line(line)),
+        );
+    }
+    if bytes.windows(2).any(|pair| pair == b"\r\n") {
+        findings.add(
+            "crlf".to_string(),
+            Severity::Info,
+            "encoding",
+            "file uses CRLF line endings".to_string(),
+            "nothing to do: both readers accept CRLF; output is written with LF",
+            None,
+        );
+    }
+}
+
+fn at_line(line: Option<usize>) -> Option<Locator> {
+    line.map(Locator::Line)
+}
+
+fn add_issue(issue: &ParseIssue, findings: &mut Findings) {
+    let (check, suggestion) = match issue.error.root() {
+        ParserErr::InvalidHeader { .. } | ParserErr::FieldCount { .. } => (
+            "header",
+            "fix the header; pass --csv-reorder-ok for reordered columns or --csv-extra-columns for additional ones",
+        ),
+        ParserErr::InvalidUtf8 { .. } => ("encoding", "re-encode the file to UTF-8"),
+        ParserErr::Malformed { .. } => (
+            "syntax",
+            "fix the record syntax (unclosed quotes, missing separator) or convert with --lenient to skip it",
+        ),
+        ParserErr::EmptyField { .. } | ParserErr::MissingField { .. } => (
+            "empty-field",
+            "fill the field; an empty DESCRIPTION is accepted with --empty-field-policy",
+        ),
+        ParserErr::InvalidNumber { .. } | ParserErr::NumberOverflow { .. } => (
+            "number",
+            "fix the value; amounts with separators need --amount-locale",
+        ),
+        ParserErr::UnsupportedVersion { .. } | ParserErr::IdNeedsSchema { .. } => (
+            "schema",
+            "run migrate-command to bring the file to the current schema",
+        ),
+        ParserErr::InvalidMagic { .. } | ParserErr::LengthExceedsBody { .. } => (
+            "corruption",
+            "the file is damaged; convert with --lenient to keep the readable records",
+        ),
+        ParserErr::InvalidValue { .. } => ("value", "use one of the values allowed for the field"),
+        _ => ("record", "fix the record or convert with --lenient to skip it"),
+    };
+    // Ошибки одного вида в одном поле сворачиваются, текст берётся у первой
+    let key = format!("issue:{}:{:?}", check, issue.field);
+    findings.add(key, Severity::Error, check, issue.reason.clone(), suggestion, at_line(issue.line));
+}
+
+fn add_warning(warning: &ParseWarning, findings: &mut Findings) {
+    let (check, suggestion) = match &warning.kind {
+        WarningKind::UnquotedDescription => ("quoting", "quote DESCRIPTION; converting to yp-bank-text rewrites it quoted"),
+        WarningKind::TrailingWhitespace => ("whitespace", "strip trailing whitespace from the lines"),
+        WarningKind::EmptyDescription => (
+            "description",
+            "fill DESCRIPTION or pass --empty-field-policy reject to stop on such records",
+        ),
+        WarningKind::ExtraColumns { .. } => ("header", "drop the extra columns or keep passing --csv-extra-columns"),
+        WarningKind::LongDescription { .. } => ("description", "shorten DESCRIPTION or pass --description-policy truncate"),
+    };
+    let key = format!("warning:{}", check);
+    findings.add(key, Severity::Warning, check, warning.kind.to_string(), suggestion, at_line(warning.line));
+}
+
+fn at_id(tx_id: &TxId) -> Option<Locator> {
+    Some(Locator::TxId(tx_id.clone()))
+}
+
+fn check_records(records: &[TxData], findings: &mut Findings) {
+    let mut seen: HashMap<&TxId, usize> = HashMap::new();
+    for tx in records {
+        *seen.entry(&tx.tx_id).or_default() += 1;
+    }
+    for tx in records {
+        if seen.get(&tx.tx_id).is_some_and(|&count| count > 1) {
+            findings.add(
+                format!("duplicate:{}", tx.tx_id),
+                Severity::Error,
+                "duplicate-id",
+                format!("TX_ID {} is not unique", tx.tx_id),
+                "keep one record per TX_ID; `get` and `diff` see only one of them",
+                at_id(&tx.tx_id),
+            );
+        }
+    }
+
+    check_timestamps(records, findings);
+
+    for tx in records {
+        let len = tx.description.chars().count();
+        if len > MAX_DESCRIPTION_LEN {
+            findings.add(
+                "long-description".to_string(),
+                Severity::Warning,
+                "description",
+                format!("DESCRIPTION is longer than {} characters", MAX_DESCRIPTION_LEN),
+                "shorten DESCRIPTION; many consumers truncate it",
+                at_id(&tx.tx_id),
+            );
+        }
+        if tx.description.chars().any(char::is_control) {
+            findings.add(
+                "control-description".to_string(),
+                Severity::Warning,
+                "description",
+                "DESCRIPTION contains control characters".to_string(),
+                "replace tabs and line breaks in DESCRIPTION with spaces",
+                at_id(&tx.tx_id),
+            );
+        }
+        // Метки времени проверяет `check_timestamps`
+        for violation in validate_with(tx, None) {
+            findings.add(
+                format!("rule:{}", violation.field()),
+                Severity::Warning,
+                "rule",
+                violation.to_string(),
+                "fix the record; such records are rejected with --strict",
+                at_id(&tx.tx_id),
+            );
+        }
+    }
+}
+
+fn check_timestamps(records: &[TxData], findings: &mut Findings) {
+    // Единицы определяются большинством ненулевых меток
+    let (seconds, millis): (Vec<&TxData>, Vec<&TxData>) =
+        records.iter().filter(|tx| tx.timestamp != 0).partition(|tx| tx.timestamp < SECONDS_LIMIT);
+    let odd = if millis.len() >= seconds.len() { &seconds } else { &millis };
+    let (unit, other) = if millis.len() >= seconds.len() {
+        ("milliseconds", "seconds")
+    } else {
+        ("seconds", "milliseconds")
+    };
+    for tx in odd {
+        findings.add(
+            "timestamp-unit".to_string(),
+            Severity::Warning,
+            "timestamp",
+            format!("TIMESTAMP looks like {} while the file uses {}", other, unit),
+            "convert the TIMESTAMP to the unit of the rest of the file",
+            at_id(&tx.tx_id),
+        );
+    }
+
+    let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis() as u64);
+    for tx in records {
+        if tx.timestamp == 0 {
+            findings.add(
+                "timestamp-zero".to_string(),
+                Severity::Warning,
+                "timestamp",
+                "TIMESTAMP is zero".to_string(),
+                "set the real operation time; zero usually means a missing value",
+                at_id(&tx.tx_id),
+            );
+        } else if tx.timestamp >= SECONDS_LIMIT && tx.timestamp > now_ms + FUTURE_SLACK_MS {
+            findings.add(
+                "timestamp-future".to_string(),
+                Severity::Warning,
+                "timestamp",
+                "TIMESTAMP is in the future".to_string(),
+                "check the clock of the producer and the TIMESTAMP unit",
+                at_id(&tx.tx_id),
+            );
+        }
+    }
+}
+
+/// Версия схемы JSON-отчёта [`write_findings_report`]; меняется только при несовместимых изменениях.
+pub const FINDINGS_SCHEMA_VERSION: u32 = 1;
+
+/// Формат машиночитаемого отчёта о находках.
+#[cfg_attr(feature = "console", derive(clap::ValueEnum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FindingsFormat {
+    /// `{"schema_version": 1, "input": .., "findings": [{"rule_id", "severity", "locator",
+    /// "message", "suggestion", "count"}]}`; `locator` — `{"line": ..}`, `{"tx_id": ..}` или `null`
+    #[default]
+    Json,
+    /// SARIF 2.1.0: правило — эвристика, уровень — важность, место — файл и строка
+    Sarif,
+}
+
+/// Записывает находки [`diagnose`] по входу `input` в ресурс `resource`.
+///
+/// Возвращает размер записанных данных.
+pub fn write_findings_report(
+    findings: &[Finding],
+    input: &Resource,
+    resource: Resource,
+    format: FindingsFormat,
+) -> Result<usize, CommonErr> {
+    let content = match format {
+        FindingsFormat::Json => render_json(findings, input),
+        FindingsFormat::Sarif => render_sarif(findings, input),
+    };
+    write_report(&content, resource)
+}
+
+fn input_path(input: &Resource) -> Option<String> {
+    match input {
+        Resource::File { path } => Some(path.display().to_string()),
+        _ => None,
+    }
+}
+
+fn render_json(findings: &[Finding], input: &Resource) -> String {
+    let findings: Vec<serde_json::Value> = findings
+        .iter()
+        .map(|finding| {
+            let locator = match &finding.first_at {
+                Some(Locator::Line(line)) => serde_json::json!({ "line": line }),
+                Some(Locator::TxId(tx_id)) => serde_json::json!({ "tx_id": tx_id.to_string() }),
+                None => serde_json::Value::Null,
+            };
+            serde_json::json!({
+                "rule_id": finding.check,
+                "severity": finding.severity.to_string(),
+                "locator": locator,
+                "message": finding.message,
+                "suggestion": finding.suggestion,
+                "count": finding.count,
+            })
+        })
+        .collect();
+    let report = serde_json::json!({
+        "schema_version": FINDINGS_SCHEMA_VERSION,
+        "input": input_path(input),
+        "findings": findings,
+    });
+    format!("{}\n", report)
+}
+
+fn render_sarif(findings: &[Finding], input: &Resource) -> String {
+    let mut rules: Vec<&str> = findings.iter().map(|finding| finding.check).collect();
+    rules.sort_unstable();
+    rules.dedup();
+    let uri = input_path(input);
+    let results: Vec<serde_json::Value> = findings
+        .iter()
+        .map(|finding| {
+            let level = match finding.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Info => "note",
+            };
+            let mut location = serde_json::Map::new();
+            if let Some(uri) = &uri {
+                let mut physical = serde_json::json!({ "artifactLocation": { "uri": uri } });
+                if let Some(Locator::Line(line)) = finding.first_at {
+                    physical["region"] = serde_json::json!({ "startLine": line });
+                }
+                location.insert("physicalLocation".to_string(), physical);
+            }
+            // Запись и строку во входе без файла (stdin) SARIF описывает только логическим местом
+            if let Some(at) = finding.first_at.as_ref().filter(|at| uri.is_none() || matches!(at, Locator::TxId(_))) {
+                location.insert("logicalLocations".to_string(), serde_json::json!([{ "name": at.to_string() }]));
+            }
+            let locations = if location.is_empty() { vec![] } else { vec![serde_json::Value::Object(location)] };
+            serde_json::json!({
+                "ruleId": finding.check,
+                "level": level,
+                "message": { "text": format!("{}; fix: {}", finding.message, finding.suggestion) },
+                "locations": locations,
+                "occurrenceCount": finding.count,
+            })
+        })
+        .collect();
+    let report = serde_json::json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "doctor",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules.iter().map(|id| serde_json::json!({ "id": id })).collect::<Vec<_>>(),
+                }
+            },
+            "results": results,
+        }],
+    });
+    format!("{}\n", report)
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use super::*;
+
+    fn memory(content: &[u8]) -> Resource {
+        Resource::Memory {
+            data: Cursor::new(content.to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_findings_are_prioritized_and_grouped() {
+        let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\r\n\
+                   1,DEPOSIT,0,10,100,1633036860000,SUCCESS,a\r\n\
+                   1,DEPOSIT,0,10,100,1633036860000,SUCCESS,b\r\n\
+                   2,DEPOSIT,0,10,x,1633036860000,SUCCESS,c\r\n\
+                   3,DEPOSIT,0,10,100,1633036860,SUCCESS,d\r\n\
+                   4,DEPOSIT,0,10,100,1633036860000,SUCCESS,e\r\n";
+        let findings = diagnose(memory(csv.as_bytes()), Format::YpBankCsv).unwrap();
+
+        let checks: Vec<(Severity, &str)> = findings.iter().map(|f| (f.severity, f.check)).collect();
+        assert_eq!(
+            checks,
+            vec![
+                (Severity::Error, "number"),
+                (Severity::Error, "duplicate-id"),
+                (Severity::Warning, "timestamp"),
+                (Severity::Info, "encoding"),
+            ]
+        );
+        let duplicate = &findings[1];
+        assert_eq!(duplicate.count, 2);
+        assert_eq!(duplicate.first_at, Some(Locator::TxId(TxId::U64(1))));
+        assert!(findings[2].message.contains("seconds"));
+        assert!(findings[0].to_string().contains("fix: "));
+    }
+
+    #[test]
+    fn test_broken_header_and_encoding_are_findings() {
+        let findings = diagnose(memory(b"TX_ID,KIND\n1,\xff\n"), Format::YpBankCsv).unwrap();
+        assert_eq!(findings[0].check, "encoding");
+        assert_eq!(findings[0].first_at, Some(Locator::Line(2)));
+        assert_eq!(findings[0].first_at.as_ref().map(ToString::to_string).as_deref(), Some("line 2"));
+        assert!(findings.iter().any(|f| f.check == "header" && f.severity == Severity::Error));
+    }
+
+    #[test]
+    fn test_findings_report_formats() {
+        let findings = vec![
+            Finding {
+                severity: Severity::Error,
+                check: "duplicate-id",
+                message: "TX_ID 1 is not unique".to_string(),
+                suggestion: "keep one record".to_string(),
+                count: 2,
+                first_at: Some(Locator::TxId(TxId::U64(1))),
+            },
+            Finding {
+                severity: Severity::Info,
+                check: "encoding",
+                message: "CRLF".to_string(),
+                suggestion: "nothing".to_string(),
+                count: 1,
+                first_at: Some(Locator::Line(3)),
+            },
+        ];
+        let input = Resource::File { path: "in.csv".into() };
+
+        let json: serde_json::Value = serde_json::from_str(&render_json(&findings, &input)).unwrap();
+        assert_eq!(json["schema_version"], 1);
+        assert_eq!(json["input"], "in.csv");
+        assert_eq!(
+            json["findings"][0],
+            serde_json::json!({
+                "rule_id": "duplicate-id",
+                "severity": "error",
+                "locator": { "tx_id": "1" },
+                "message": "TX_ID 1 is not unique",
+                "suggestion": "keep one record",
+                "count": 2,
+            })
+        );
+        assert_eq!(json["findings"][1]["locator"], serde_json::json!({ "line": 3 }));
+
+        let sarif: serde_json::Value = serde_json::from_str(&render_sarif(&findings, &input)).unwrap();
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"], serde_json::json!([{ "id": "duplicate-id" }, { "id": "encoding" }]));
+        let (duplicate, crlf) = (&run["results"][0], &run["results"][1]);
+        assert_eq!((&duplicate["level"], &crlf["level"]), (&"error".into(), &"note".into()));
+        assert_eq!(duplicate["locations"][0]["logicalLocations"][0]["name"], "TX_ID 1");
+        assert_eq!(crlf["locations"][0]["physicalLocation"]["region"]["startLine"], 3);
+        assert_eq!(crlf["locations"][0]["physicalLocation"]["artifactLocation"]["uri"], "in.csv");
+        assert!(crlf["locations"][0].get("logicalLocations").is_none());
+    }
+}
@@ -0,0 +1,404 @@
+//! Дрейф схемы между двумя входами: сравнивается не содержимое записей, а их вид.
+//!
+//! [`observe_shape`] за один проход собирает [`Shape`] входа: колонки CSV или ключи
+//! текстового формата, встреченные значения TX_TYPE, STATUS и видов TX_ID, диапазоны чисел.
+//! [`schema_diff`] сравнивает два вида и возвращает расхождения — быстрее сравнения записей,
+//! когда нужно понять, что поменялось в выгрузке. Диапазоны сравниваются по порядку величины
+//! (числу цифр с допуском в одну цифру и знаку): новые даты и суммы не считаются дрейфом,
+//! а секунды вместо миллисекунд или суммы в рублях вместо копеек — считаются.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Read};
+use std::rc::Rc;
+
+use crate::model::data::{Format, TxData};
+use crate::model::errors::{CommonErr, IoErr, ParserErr};
+use crate::model::resource::Resource;
+use crate::model::tx_id::TxId;
+use crate::parser::io::reader::{open, BatchReader};
+use crate::parser::options::{ParseOptions, TextOptions};
+
+/// На сколько цифр может разойтись порядок величины без дрейфа: 99 и 100 — ещё не дрейф,
+/// а рубли и копейки (две цифры) — уже.
+const MAGNITUDE_SLACK: usize = 1;
+
+/// Сколько байт начала строки хранится, чтобы найти в ней заголовок CSV или ключ.
+const MAX_LINE_PREFIX: usize = 4096;
+
+/// Наблюдаемый вид входа.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shape {
+    pub format: Format,
+    /// Колонки CSV в порядке заголовка, ключи текстового формата в порядке появления;
+    /// у бинарного формата раскладка фиксирована — поля [`TxData::FIELDS`]
+    pub fields: Vec<String>,
+    /// Сколько записей разобрано
+    pub records: usize,
+    /// Сколько записей не удалось разобрать
+    pub skipped: usize,
+    /// Причина, по которой пропущена первая такая запись
+    pub first_skip: Option<String>,
+    /// Почему вход не удалось дочитать; собранное до ошибки остаётся в остальных полях
+    pub unreadable: Option<String>,
+    /// Число записей по значению поля: `TX_TYPE`, `STATUS` и `TX_ID` (вид: number, uuid, string)
+    pub values: BTreeMap<&'static str, BTreeMap<&'static str, usize>>,
+    /// Диапазоны числовых полей; для `DESCRIPTION` — длины в символах, для `TX_ID` — только числовые
+    pub ranges: BTreeMap<&'static str, ValueRange>,
+}
+
+/// Наименьшее и наибольшее значение поля.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValueRange {
+    pub min: i128,
+    pub max: i128,
+}
+
+impl ValueRange {
+    fn include(range: &mut Option<ValueRange>, value: i128) {
+        let range = range.get_or_insert(ValueRange { min: value, max: value });
+        range.min = range.min.min(value);
+        range.max = range.max.max(value);
+    }
+
+    /// Порядок величины: число цифр наименьшего и наибольшего по модулю значения и есть ли
+    /// отрицательные.
+    pub fn magnitude(&self) -> (usize, usize, bool) {
+        let digits = |value: u128| value.checked_ilog10().map_or(1, |log| log as usize + 1);
+        let (low, high) = (self.min.unsigned_abs(), self.max.unsigned_abs());
+        let smallest = if self.min <= 0 && self.max >= 0 { 0 } else { low.min(high) };
+        (digits(smallest), digits(low.max(high)), self.min < 0)
+    }
+}
+
+impl fmt::Display for ValueRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..={}", self.min, self.max)
+    }
+}
+
+/// Одно расхождение вида двух входов.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Drift {
+    /// Что расходится: `fields`, `parse`, `values` или `magnitude`
+    pub check: &'static str,
+    /// Колонка, ключ или поле записи
+    pub field: String,
+    /// Описание расхождения
+    pub message: String,
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.check, self.field, self.message)
+    }
+}
+
+/// Собирает вид входа `from` в формате `format`, читая его порциями по `batch_size` записей.
+///
+/// Записи разбираются терпимо к дрейфу: переставленные и лишние колонки CSV, псевдонимы ключей
+/// текстового формата допускаются, ошибочные записи пропускаются и считаются.
+///
+/// # Errors
+/// `CommonErr::IO`, если вход не удалось открыть или прочитать. Ошибка разбора, после
+/// которой чтение невозможно (например, в заголовке нет обязательной колонки), попадает
+/// в [`Shape::unreadable`].
+pub fn observe_shape(from: Resource, format: Format, batch_size: usize) -> Result<Shape, CommonErr> {
+    let path = match &from {
+        Resource::File { path } => Some(path.clone()),
+        _ => None,
+    };
+    let fields = Rc::new(RefCell::new(Vec::new()));
+    let tap = FieldTap {
+        inner: open(from)?,
+        format,
+        fields: Rc::clone(&fields),
+        line: Vec::new(),
+        done: format == Format::YpBankBin,
+    };
+    let mut options = ParseOptions {
+        text: TextOptions::relaxed(),
+        ..ParseOptions::collect()
+    };
+    options.csv.reorder_ok = true;
+    options.csv.allow_extra_columns = true;
+
+    let mut shape = Shape {
+        format,
+        fields: Vec::new(),
+        records: 0,
+        skipped: 0,
+        first_skip: None,
+        unreadable: None,
+        values: BTreeMap::new(),
+        ranges: BTreeMap::new(),
+    };
+    let mut ranges: BTreeMap<&'static str, Option<ValueRange>> = BTreeMap::new();
+    let batches = BatchReader::from_reader(Box::new(tap), format, &options, batch_size);
+    let result = batches.and_then(|batches| {
+        for batch in batches {
+            let batch = batch?;
+            shape.skipped += batch.issues.len();
+            if let (None, Some(issue)) = (&shape.first_skip, batch.issues.first()) {
+                shape.first_skip = Some(match issue.line {
+                    Some(line) => format!("line {}: {}", line, issue.reason),
+                    None => issue.reason.clone(),
+                });
+            }
+            for tx in &batch.records {
+                observe(&mut shape, &mut ranges, tx);
+            }
+        }
+        Ok(())
+    });
+    match result {
+        Err(CommonErr::Parser(ParserErr::Io { source, path: read_path })) => {
+            return Err(IoErr::InputErr {
+                path: read_path.or(path),
+                kind: source.kind(),
+                source,
+            }
+            .into())
+        },
+        Err(e) => shape.unreadable = Some(reason(&e)),
+        Ok(()) => {},
+    }
+    shape.fields = match format {
+        Format::YpBankBin => TxData::FIELDS.iter().map(|field| field.to_string()).collect(),
+        _ => fields.take(),
+    };
+    shape.ranges = ranges.into_iter().filter_map(|(field, range)| Some((field, range?))).collect();
+    Ok(shape)
+}
+
+fn observe(shape: &mut Shape, ranges: &mut BTreeMap<&'static str, Option<ValueRange>>, tx: &TxData) {
+    shape.records += 1;
+    let id_kind = match &tx.tx_id {
+        TxId::U64(id) => {
+            ValueRange::include(ranges.entry("TX_ID").or_default(), *id as i128);
+            "number"
+        },
+        TxId::Uuid(_) => "uuid",
+        TxId::String(_) => "string",
+    };
+    for (field, value) in [("TX_ID", id_kind), ("TX_TYPE", tx.tx_type.name()), ("STATUS", tx.status.name())] {
+        *shape.values.entry(field).or_default().entry(value).or_default() += 1;
+    }
+    let numbers = [
+        ("AMOUNT", tx.amount as i128),
+        ("TIMESTAMP", tx.timestamp as i128),
+        ("FROM_USER_ID", tx.from_user_id as i128),
+        ("TO_USER_ID", tx.to_user_id as i128),
+        ("DESCRIPTION", tx.description.chars().count() as i128),
+    ];
+    for (field, value) in numbers {
+        ValueRange::include(ranges.entry(field).or_default(), value);
+    }
+}
+
+fn reason(e: &CommonErr) -> String {
+    match e {
+        CommonErr::Parser(e) => e.to_string(),
+        CommonErr::IO(e) => e.to_string(),
+        CommonErr::Unexpected => "unexpected error".to_string(),
+    }
+}
+
+/// Расхождения вида `second` относительно `first`: сначала колонки и ключи, затем
+/// разбор, значения перечислений и порядки величин.
+pub fn schema_diff(first: &Shape, second: &Shape) -> Vec<Drift> {
+    let mut drifts = Vec::new();
+    let drift = |check, field: &str, message: String| Drift {
+        check,
+        field: field.to_string(),
+        message,
+    };
+
+    let only = |a: &Shape, b: &Shape| -> Vec<String> { a.fields.iter().filter(|f| !b.fields.contains(f)).cloned().collect() };
+    for field in only(first, second) {
+        drifts.push(drift("fields", &field, "only in the first input".to_string()));
+    }
+    for field in only(second, first) {
+        drifts.push(drift("fields", &field, "only in the second input".to_string()));
+    }
+    let common = |a: &Shape, b: &Shape| -> Vec<String> { a.fields.iter().filter(|f| b.fields.contains(f)).cloned().collect() };
+    let (first_order, second_order) = (common(first, second), common(second, first));
+    if first.format == Format::YpBankCsv && second.format == Format::YpBankCsv && first_order != second_order {
+        let message = format!("columns reordered: {} -> {}", first_order.join(","), second_order.join(","));
+        drifts.push(drift("fields", "header", message));
+    }
+
+    for (side, shape) in [("first", first), ("second", second)] {
+        if let Some(reason) = &shape.unreadable {
+            drifts.push(drift("parse", "input", format!("the {} input cannot be read to the end: {}", side, reason)));
+        }
+    }
+    if (first.skipped == 0) != (second.skipped == 0) {
+        let mut message = format!(
+            "{} of {} records skipped in the first input, {} of {} in the second",
+            first.skipped,
+            first.records + first.skipped,
+            second.skipped,
+            second.records + second.skipped
+        );
+        if let Some(reason) = first.first_skip.as_ref().or(second.first_skip.as_ref()) {
+            message.push_str(&format!(" (first: {})", reason));
+        }
+        drifts.push(drift("parse", "records", message));
+    }
+
+    // Пустой вход не говорит о том, какие значения пропали
+    let both_read = first.records > 0 && second.records > 0;
+    let no_values = BTreeMap::new();
+    for field in ["TX_ID", "TX_TYPE", "STATUS"].into_iter().filter(|_| both_read) {
+        let first_values = first.values.get(field).unwrap_or(&no_values);
+        let second_values = second.values.get(field).unwrap_or(&no_values);
+        for (value, count) in first_values.iter().filter(|(value, _)| !second_values.contains_key(*value)) {
+            drifts.push(drift("values", field, format!("{} only in the first input ({} records)", value, count)));
+        }
+        for (value, count) in second_values.iter().filter(|(value, _)| !first_values.contains_key(*value)) {
+            drifts.push(drift("values", field, format!("{} only in the second input ({} records)", value, count)));
+        }
+    }
+
+    for (field, first_range) in &first.ranges {
+        let Some(second_range) = second.ranges.get(field) else {
+            continue;
+        };
+        let ((first_low, first_high, first_negative), (second_low, second_high, second_negative)) =
+            (first_range.magnitude(), second_range.magnitude());
+        if first_low.abs_diff(second_low) > MAGNITUDE_SLACK
+            || first_high.abs_diff(second_high) > MAGNITUDE_SLACK
+            || first_negative != second_negative
+        {
+            let field = match *field {
+                "DESCRIPTION" => "DESCRIPTION length",
+                field => field,
+            };
+            let message = format!("{} in the first input, {} in the second", first_range, second_range);
+            drifts.push(drift("magnitude", field, message));
+        }
+    }
+    drifts
+}
+
+/// Поток чтения, который по пути к разборщику запоминает заголовок CSV или ключи
+/// текстового формата.
+struct FieldTap {
+    inner: Box<dyn Read>,
+    format: Format,
+    fields: Rc<RefCell<Vec<String>>>,
+    /// Начало текущей строки (не больше [`MAX_LINE_PREFIX`] байт)
+    line: Vec<u8>,
+    /// Больше ничего искать не нужно: заголовок CSV прочитан или формат бинарный
+    done: bool,
+}
+
+impl Read for FieldTap {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 {
+            self.end_line();
+        }
+        for &byte in &buf[..n] {
+            if self.done {
+                break;
+            }
+            match byte {
+                b'\n' => self.end_line(),
+                _ if self.line.len() < MAX_LINE_PREFIX => self.line.push(byte),
+                _ => {},
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl FieldTap {
+    fn end_line(&mut self) {
+        if self.done {
+            return;
+        }
+        let line = String::from_utf8_lossy(&std::mem::take(&mut self.line)).into_owned();
+        let line = line.trim_start_matches('\u{feff}').trim();
+        if line.is_empty() || (self.format == Format::YpBankText && line.starts_with('#')) {
+            return;
+        }
+        let mut fields = self.fields.borrow_mut();
+        match self.format {
+            Format::YpBankCsv => {
+                fields.extend(line.split(',').map(|column| column.trim().trim_matches('"').to_string()));
+                self.done = true;
+            },
+            _ => {
+                let key = line.split_once(':').map(|(key, _)| key.trim());
+                if let Some(key) = key.filter(|key| !key.is_empty() && !fields.iter().any(|field| field == key)) {
+                    fields.push(key.to_string());
+                }
+            },
+        }
+    }
+}
+
+#[cfg(all(test, feature = "csv", feature = "text"))]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION";
+
+    fn shape(data: &str, format: Format) -> Shape {
+        let resource = Resource::Memory {
+            data: io::Cursor::new(data.as_bytes().to_vec()),
+        };
+        observe_shape(resource, format, 2).unwrap()
+    }
+
+    #[test]
+    fn test_schema_diff_reports_fields_values_and_magnitude() {
+        let first = shape(
+            &format!("{}\n1,DEPOSIT,0,10,100,1700000000000,SUCCESS,\"a\"\n2,TRANSFER,10,11,250,1700000001000,SUCCESS,\"b\"\n", HEADER),
+            Format::YpBankCsv,
+        );
+        assert_eq!(first.fields.len(), 8);
+        assert_eq!(first.records, 2);
+        assert_eq!(first.ranges["AMOUNT"], ValueRange { min: 100, max: 250 });
+
+        // Колонки переставлены и добавлена новая, метки времени в секундах, появился PENDING
+        let second = shape(
+            "TX_TYPE,TX_ID,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION,CHANNEL\n\
+             DEPOSIT,3,0,10,300,1700000002,PENDING,\"c\",web\n",
+            Format::YpBankCsv,
+        );
+        assert!(second.unreadable.is_none());
+        let drifts: Vec<String> = schema_diff(&first, &second).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            drifts,
+            [
+                "[fields] CHANNEL: only in the second input",
+                "[fields] header: columns reordered: TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION -> \
+                 TX_TYPE,TX_ID,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION",
+                "[values] TX_TYPE: TRANSFER only in the first input (1 records)",
+                "[values] STATUS: SUCCESS only in the first input (2 records)",
+                "[values] STATUS: PENDING only in the second input (1 records)",
+                "[magnitude] TIMESTAMP: 1700000000000..=1700000001000 in the first input, 1700000002..=1700000002 in the second",
+            ]
+        );
+        assert!(schema_diff(&first, &first).is_empty());
+    }
+
+    #[test]
+    fn test_observe_shape_keeps_fields_of_unreadable_input() {
+        // Без обязательной колонки CSV не читается, но заголовок виден
+        let csv = shape("TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS\n1,DEPOSIT,0,10,100,1,SUCCESS\n", Format::YpBankCsv);
+        assert_eq!(csv.fields.len(), 7);
+        assert!(csv.unreadable.is_some());
+
+        let text = shape(
+            "# schema: 2\nTX_ID: 1\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 10\nAMOUNT: 100\nTIMESTAMP: 1\nSTATUS: SUCCESS\nDESCRIPTION: \"x\"\nNOTE: y\n",
+            Format::YpBankText,
+        );
+        assert_eq!(text.fields.last().map(String::as_str), Some("NOTE"));
+        assert_eq!(text.records + text.skipped, 1);
+    }
+}
@@ -0,0 +1,142 @@
+//! Обогащение результата данными о пользователях из справочника, чтобы отчёты не требовали
+//! отдельного шага объединения таблиц.
+//!
+//! Справочник — CSV с заголовком: первая колонка — ID пользователя, остальные — его
+//! атрибуты (имя, сегмент и т.п.):
+//!
+//! ```text
+//! id,name,segment
+//! 10,Alice,retail
+//! 11,Bob,business
+//! ```
+//!
+//! С `WriteOptions::join_users` CSV-результат получает после `DESCRIPTION` колонки
+//! `FROM_<АТРИБУТ>` и `TO_<АТРИБУТ>` для отправителя и получателя; у пользователя, которого
+//! нет в справочнике, они пустые. Такой файл читается обратно с `CsvOptions::allow_extra_columns`.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use csv::ReaderBuilder;
+use thiserror::Error;
+
+use crate::model::data::TxData;
+
+#[derive(Error, Debug)]
+pub enum UserReferenceErr {
+    /// Файл не удалось прочитать
+    #[error("failed to read user reference {}: {err}", path.display())]
+    Io { path: PathBuf, err: std::io::Error },
+    /// Файл — не CSV с заголовком и хотя бы одной колонкой атрибутов
+    #[error("user reference is not valid: {reason}")]
+    Invalid { reason: String },
+    /// Строка с неверным или повторным ID пользователя
+    #[error("user reference line {line}: {reason}")]
+    Row { line: u64, reason: String },
+}
+
+/// Справочник пользователей (см. описание модуля).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserReference {
+    /// Названия атрибутов в порядке колонок справочника
+    attributes: Vec<String>,
+    /// Атрибуты пользователей в том же порядке
+    users: HashMap<u64, Vec<String>>,
+}
+
+impl UserReference {
+    /// Читает справочник из CSV-файла `path`.
+    pub fn load(path: &Path) -> Result<UserReference, UserReferenceErr> {
+        let file = File::open(path).map_err(|err| UserReferenceErr::Io { path: path.to_path_buf(), err })?;
+        UserReference::from_reader(file)
+    }
+
+    /// Читает справочник из CSV.
+    pub fn from_reader(reader: impl Read) -> Result<UserReference, UserReferenceErr> {
+        let invalid = |reason: String| UserReferenceErr::Invalid { reason };
+        let mut rdr = ReaderBuilder::new().has_headers(true).flexible(false).from_reader(reader);
+        let headers = rdr.headers().map_err(|e| invalid(e.to_string()))?;
+        if headers.len() < 2 {
+            return Err(invalid("expected a user id column and at least one attribute column".to_string()));
+        }
+        let attributes = headers.iter().skip(1).map(|name| name.trim().to_uppercase()).collect();
+
+        let mut users = HashMap::new();
+        for row in rdr.records() {
+            let row = row.map_err(|e| invalid(e.to_string()))?;
+            let line = row.position().map_or(0, |pos| pos.line());
+            let row_err = |reason: String| UserReferenceErr::Row { line, reason };
+            let id = row[0].trim().parse().map_err(|_| row_err(format!("not a user id: '{}'", &row[0])))?;
+            let values = row.iter().skip(1).map(str::to_string).collect();
+            if users.insert(id, values).is_some() {
+                return Err(row_err(format!("duplicate user id {}", id)));
+            }
+        }
+        Ok(UserReference { attributes, users })
+    }
+
+    /// Сколько пользователей в справочнике.
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    /// Колонки, которые добавляются к записи: `FROM_<АТРИБУТ>`, затем `TO_<АТРИБУТ>`.
+    pub fn joined_columns(&self) -> Vec<String> {
+        ["FROM", "TO"]
+            .iter()
+            .flat_map(|side| self.attributes.iter().map(move |name| format!("{}_{}", side, name)))
+            .collect()
+    }
+
+    /// Значения колонок [`UserReference::joined_columns`] для записи `tx`.
+    pub fn join(&self, tx: &TxData) -> Vec<&str> {
+        let mut joined = Vec::with_capacity(2 * self.attributes.len());
+        for user_id in [tx.from_user_id, tx.to_user_id] {
+            match self.users.get(&user_id) {
+                Some(values) => joined.extend(values.iter().map(String::as_str)),
+                None => joined.extend(std::iter::repeat_n("", self.attributes.len())),
+            }
+        }
+        joined
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::data::{Format, Status, TxType};
+
+    #[test]
+    fn test_join_adds_sender_and_receiver_columns() {
+        let reference = UserReference::from_reader("id,name,segment\n10,Alice,retail\n11,\"Bob, Jr.\",business\n".as_bytes()).unwrap();
+        assert_eq!(reference.len(), 2);
+        assert_eq!(reference.joined_columns(), ["FROM_NAME", "FROM_SEGMENT", "TO_NAME", "TO_SEGMENT"]);
+
+        let tx = TxData {
+            tx_id: 1.into(),
+            tx_type: TxType::Transfer,
+            from_user_id: 11,
+            to_user_id: 99,
+            amount: 100,
+            timestamp: 1700000000000,
+            status: Status::Success,
+            description: "join".into(),
+            format: Format::YpBankCsv,
+        };
+        assert_eq!(reference.join(&tx), ["Bob, Jr.", "business", "", ""]);
+
+        let err = UserReference::from_reader("id,name\n10,Alice\n10,Again\n".as_bytes()).unwrap_err();
+        if let UserReferenceErr::Row { line, reason } = err {
+            assert_eq!((line, reason.as_str()), (3, "duplicate user id 10"));
+        } else {
+            panic!()
+        }
+        assert!(matches!(UserReference::from_reader("id\n10\n".as_bytes()), Err(UserReferenceErr::Invalid { .. })));
+    }
+}
@@ -0,0 +1,155 @@
+//! Выборки для расследований: только записи, в которых участвуют пользователи из списка,
+//! или все записи, кроме них.
+//!
+//! Список — текстовый файл с одним ID пользователя в строке; пустые строки и строки,
+//! начинающиеся с `#`, пропускаются:
+//!
+//! ```text
+//! # дело 2024-117
+//! 9223372036854775807
+//! 1024
+//! ```
+//!
+//! [`UserFilter`] подключается в [`Transforms`] и отбрасывает записи до записи результата.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::model::data::TxData;
+use crate::parser::Transforms;
+
+#[derive(Error, Debug)]
+pub enum UserListErr {
+    /// Файл не удалось прочитать
+    #[error("failed to read user list {}: {err}", path.display())]
+    Io { path: PathBuf, err: std::io::Error },
+    /// Строка — не ID пользователя
+    #[error("user list line {line}: not a user id: '{value}'")]
+    Invalid { line: usize, value: String },
+}
+
+/// Множество ID пользователей из списка (см. описание модуля).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserList(HashSet<u64>);
+
+impl UserList {
+    /// Читает список из файла `path`.
+    pub fn load(path: &Path) -> Result<UserList, UserListErr> {
+        let text = fs::read_to_string(path).map_err(|err| UserListErr::Io { path: path.to_path_buf(), err })?;
+        UserList::parse(&text)
+    }
+
+    /// Разбирает список из текста.
+    pub fn parse(text: &str) -> Result<UserList, UserListErr> {
+        let mut ids = HashSet::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let id = line.parse().map_err(|_| UserListErr::Invalid { line: n + 1, value: line.to_string() })?;
+            ids.insert(id);
+        }
+        Ok(UserList(ids))
+    }
+
+    pub fn contains(&self, user_id: u64) -> bool {
+        self.0.contains(&user_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Участвует ли пользователь из списка в записи как отправитель или получатель.
+    pub fn involved_in(&self, tx: &TxData) -> bool {
+        self.contains(tx.from_user_id) || self.contains(tx.to_user_id)
+    }
+}
+
+impl FromIterator<u64> for UserList {
+    fn from_iter<I: IntoIterator<Item = u64>>(iter: I) -> Self {
+        UserList(iter.into_iter().collect())
+    }
+}
+
+/// Отбор записей по участникам.
+#[derive(Debug, Clone, Default)]
+pub struct UserFilter {
+    /// Оставлять только записи с пользователями из списка; `None` — все записи
+    pub include: Option<UserList>,
+    /// Отбрасывать записи с пользователями из списка (сильнее `include`)
+    pub exclude: Option<UserList>,
+}
+
+impl UserFilter {
+    /// Ни один список не задан.
+    pub fn is_noop(&self) -> bool {
+        self.include.is_none() && self.exclude.is_none()
+    }
+
+    /// Попадает ли запись в выборку.
+    pub fn matches(&self, tx: &TxData) -> bool {
+        self.include.as_ref().is_none_or(|users| users.involved_in(tx))
+            && !self.exclude.as_ref().is_some_and(|users| users.involved_in(tx))
+    }
+
+    /// Добавляет отбор в конец цепочки `transforms`.
+    pub fn install(self, transforms: Transforms) -> Transforms {
+        if self.is_noop() {
+            return transforms;
+        }
+        transforms.then(move |tx| self.matches(&tx).then_some(tx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::data::{Format, Status, TxType};
+
+    fn tx(from_user_id: u64, to_user_id: u64) -> TxData {
+        TxData {
+            tx_id: 1.into(),
+            tx_type: TxType::Transfer,
+            from_user_id,
+            to_user_id,
+            amount: 100,
+            timestamp: 1700000000000,
+            status: Status::Success,
+            description: "extract".into(),
+            format: Format::YpBankCsv,
+        }
+    }
+
+    #[test]
+    fn test_user_filter_include_and_exclude() {
+        let include = UserList::parse("# дело\n10\n\n 11 \n").unwrap();
+        assert_eq!(include.len(), 2);
+        let filter = UserFilter {
+            include: Some(include),
+            exclude: Some([20].into_iter().collect()),
+        };
+        assert!(filter.matches(&tx(10, 30)));
+        assert!(filter.matches(&tx(30, 11)));
+        assert!(!filter.matches(&tx(30, 31)));
+        assert!(!filter.matches(&tx(10, 20)));
+
+        let transforms = filter.install(Transforms::default());
+        assert!(transforms.apply(tx(30, 10)).is_some());
+        assert!(transforms.apply(tx(20, 30)).is_none());
+
+        if let Err(UserListErr::Invalid { line, value }) = UserList::parse("10\nabc\n") {
+            assert_eq!((line, value.as_str()), (2, "abc"));
+        } else {
+            panic!()
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Корректное прерывание по SIGINT и SIGTERM.
+//!
+//! Команда, которая пишет файлы, на время работы берёт [`Cooperation`] и передаёт
+//! [`flag`] в `ConvertOptions::interrupt`: сигнал только поднимает флаг, конвертация
+//! останавливается после текущей порции, сбрасывает вывод и сообщает, сколько успела
+//! записать. Без `Cooperation` сигнал завершает процесс сразу — читающим командам
+//! нечего дописывать. Повторный сигнал завершает процесс сразу в любом случае.
+//!
+//! Код завершения после сигнала — `128 + номер сигнала` (130 для SIGINT, 143 для SIGTERM),
+//! см. [`exit_code`].
+
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// Номер пришедшего сигнала; 0 — сигнала не было.
+static SIGNAL: AtomicI32 = AtomicI32::new(0);
+/// Сколько живых [`Cooperation`]: пока их больше нуля, сигнал не завершает процесс.
+static COOPERATING: AtomicUsize = AtomicUsize::new(0);
+static FLAG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+/// Флаг прерывания процесса, который поднимает обработчик сигналов.
+pub fn flag() -> Arc<AtomicBool> {
+    FLAG.get_or_init(|| Arc::new(AtomicBool::new(false))).clone()
+}
+
+/// Пришёл ли сигнал прерывания.
+pub fn interrupted() -> bool {
+    SIGNAL.load(Ordering::SeqCst) != 0
+}
+
+/// Код завершения процесса после сигнала: `128 + номер сигнала`; `None`, если сигнала не было.
+pub fn exit_code() -> Option<i32> {
+    match SIGNAL.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(128 + signal),
+    }
+}
+
+/// Пока значение живо, сигнал прерывания поднимает [`flag`] вместо немедленного завершения.
+#[must_use = "signals terminate the process once the guard is dropped"]
+pub struct Cooperation(());
+
+/// Начинает участок, который сам останавливается по [`flag`].
+pub fn cooperate() -> Cooperation {
+    COOPERATING.fetch_add(1, Ordering::SeqCst);
+    Cooperation(())
+}
+
+impl Drop for Cooperation {
+    fn drop(&mut self) {
+        COOPERATING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Устанавливает обработчики SIGINT и SIGTERM (см. описание модуля).
+///
+/// # Errors
+/// Ошибка ОС, если обработчик не удалось установить.
+#[cfg(feature = "signals")]
+pub fn install_signal_handlers() -> std::io::Result<()> {
+    use signal_hook::consts::{SIGINT, SIGTERM};
+    use signal_hook::low_level;
+
+    let flag = flag();
+    for signal in [SIGINT, SIGTERM] {
+        let flag = flag.clone();
+        // В обработчике только атомарные операции и `_exit`: они безопасны для сигналов
+        let action = move || {
+            let first = SIGNAL.compare_exchange(0, signal, Ordering::SeqCst, Ordering::SeqCst).is_ok();
+            if !first || COOPERATING.load(Ordering::SeqCst) == 0 {
+                low_level::exit(128 + signal);
+            }
+            flag.store(true, Ordering::SeqCst);
+        };
+        // SAFETY: действие не выделяет память, не берёт блокировок и не паникует
+        unsafe { low_level::register(signal, action) }?;
+    }
+    Ok(())
+}
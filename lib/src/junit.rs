@@ -0,0 +1,133 @@
+//! Результаты проверок в формате JUnit XML, который CI-системы (Jenkins, GitLab, GitHub Actions)
+//! показывают без разбора вывода: один `testcase` на файл или пару файлов.
+//!
+//! ```xml
+//! <testsuites name="compare" tests="2" failures="1" errors="0" time="0.120">
+//!   <testsuite name="compare" tests="2" failures="1" errors="0" time="0.120">
+//!     <testcase classname="compare" name="a.csv" time="0.070"/>
+//!     <testcase classname="compare" name="b.csv" time="0.050">
+//!       <failure message="1 vs 0 records, differences: 1">TX_ID 7: 1 vs 0</failure>
+//!     </testcase>
+//!   </testsuite>
+//! </testsuites>
+//! ```
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::model::errors::{CommonErr, IoErr};
+
+/// Итог одной проверки.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaseOutcome {
+    Passed,
+    /// Проверка выполнена, но данные не прошли её (`<failure>`)
+    Failed { message: String, details: String },
+    /// Проверку выполнить не удалось (`<error>`)
+    Error { message: String, details: String },
+}
+
+/// Одна проверка: файл или пара файлов.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+    pub name: String,
+    /// Длительность; `None` — не измерялась
+    pub time: Option<Duration>,
+    pub outcome: CaseOutcome,
+}
+
+/// JUnit XML с одним набором `suite` из проверок `cases`.
+pub fn render_junit(suite: &str, cases: &[TestCase]) -> String {
+    let failures = cases.iter().filter(|case| matches!(case.outcome, CaseOutcome::Failed { .. })).count();
+    let errors = cases.iter().filter(|case| matches!(case.outcome, CaseOutcome::Error { .. })).count();
+    let total: Duration = cases.iter().filter_map(|case| case.time).sum();
+    let attrs = format!(
+        "name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\"",
+        escape(suite),
+        cases.len(),
+        failures,
+        errors,
+        total.as_secs_f64()
+    );
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(out, "<testsuites {}>", attrs);
+    let _ = writeln!(out, "  <testsuite {}>", attrs);
+    for case in cases {
+        let _ = write!(out, "    <testcase classname=\"{}\" name=\"{}\"", escape(suite), escape(&case.name));
+        if let Some(time) = case.time {
+            let _ = write!(out, " time=\"{:.3}\"", time.as_secs_f64());
+        }
+        let (tag, message, details) = match &case.outcome {
+            CaseOutcome::Passed => {
+                out.push_str("/>\n");
+                continue;
+            },
+            CaseOutcome::Failed { message, details } => ("failure", message, details),
+            CaseOutcome::Error { message, details } => ("error", message, details),
+        };
+        let _ = writeln!(out, ">\n      <{} message=\"{}\">{}</{}>", tag, escape(message), escape(details), tag);
+        out.push_str("    </testcase>\n");
+    }
+    out.push_str("  </testsuite>\n</testsuites>\n");
+    out
+}
+
+/// Записывает [`render_junit`] в файл `path`.
+///
+/// # Errors
+/// `CommonErr::IO`, если файл не удалось записать.
+pub fn write_junit(path: &Path, suite: &str, cases: &[TestCase]) -> Result<(), CommonErr> {
+    fs::write(path, render_junit(suite, cases)).map_err(|e| IoErr::output(e, Some(path.to_path_buf())).into())
+}
+
+/// Экранирует текст для XML; управляющие символы, недопустимые в XML 1.0, заменяются на `?`.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\n' | '\t' | '\r' => out.push(c),
+            c if c.is_control() => out.push('?'),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_junit() {
+        let cases = [
+            TestCase { name: "a.csv".to_string(), time: Some(Duration::from_millis(70)), outcome: CaseOutcome::Passed },
+            TestCase {
+                name: "b<1>.csv".to_string(),
+                time: None,
+                outcome: CaseOutcome::Failed {
+                    message: "2 records skipped".to_string(),
+                    details: "line 3: Invalid \"AMOUNT\"\u{1}".to_string(),
+                },
+            },
+            TestCase {
+                name: "c.csv".to_string(),
+                time: Some(Duration::from_millis(5)),
+                outcome: CaseOutcome::Error { message: "no such file".to_string(), details: String::new() },
+            },
+        ];
+        let xml = render_junit("convert", &cases);
+        assert!(xml.contains("<testsuites name=\"convert\" tests=\"3\" failures=\"1\" errors=\"1\" time=\"0.075\">"));
+        assert!(xml.contains("<testcase classname=\"convert\" name=\"a.csv\" time=\"0.070\"/>\n"));
+        assert!(xml.contains(
+            "name=\"b&lt;1&gt;.csv\">\n      <failure message=\"2 records skipped\">line 3: Invalid &quot;AMOUNT&quot;?</failure>\n    </testcase>"
+        ));
+        assert!(xml.contains("<error message=\"no such file\"></error>"));
+    }
+}
@@ -1,3 +1,62 @@
+#[cfg(not(any(feature = "bin", feature = "csv", feature = "text")))]
+compile_error!("enable at least one format feature: `bin`, `csv` or `text`");
+
 pub mod parser;
+#[cfg(feature = "console")]
 pub mod console;
-pub mod model;
\ No newline at end of file
+#[cfg(feature = "audit")]
+pub mod audit;
+#[cfg(feature = "bench")]
+pub mod bench;
+#[cfg(feature = "bundle")]
+pub mod bundle;
+#[cfg(feature = "cache")]
+pub mod cache;
+#[cfg(feature = "compare")]
+pub mod compare;
+pub mod convert;
+#[cfg(feature = "delta")]
+pub mod delta;
+#[cfg(feature = "currency")]
+pub mod currency;
+#[cfg(feature = "doctor")]
+pub mod doctor;
+#[cfg(feature = "enrich")]
+pub mod enrich;
+pub mod interrupt;
+#[cfg(feature = "drift")]
+pub mod drift;
+#[cfg(feature = "extract")]
+pub mod extract;
+#[cfg(feature = "junit")]
+pub mod junit;
+#[cfg(feature = "manifest")]
+pub mod manifest;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod migrate;
+pub mod model;
+#[cfg(feature = "notify")]
+pub mod notify;
+#[cfg(feature = "partition")]
+pub mod partition;
+#[cfg(feature = "plugin")]
+pub mod plugin;
+pub mod prelude;
+#[cfg(feature = "preview")]
+pub mod preview;
+pub mod registry;
+#[cfg(feature = "rules")]
+pub mod rules;
+#[cfg(feature = "schema")]
+pub mod schema;
+#[cfg(feature = "script")]
+pub mod script;
+#[cfg(feature = "screening")]
+pub mod screening;
+#[cfg(feature = "spill")]
+pub mod spill;
+#[cfg(feature = "stats")]
+pub mod stats;
+#[cfg(feature = "testing")]
+pub mod testing;
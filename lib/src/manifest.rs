@@ -0,0 +1,162 @@
+//! Манифест результата: файл `<результат>.manifest.json` рядом с каждым записанным файлом,
+//! по которому загрузчик проверяет, что получил результат целиком, прежде чем его загружать:
+//!
+//! ```json
+//! {"version":1,"format":"yp-bank-csv","records":1000,"sha256":"…","first_tx_id":"1000000000000000",
+//!  "last_tx_id":"1000000000000999","time_range":{"from":1633036860000,"to":1633122660000},
+//!  "producer":"cli-converter 0.1.0"}
+//! ```
+//!
+//! Манифест строится по уже записанному файлу: записи читаются заново, поэтому манифест
+//! описывает то, что лежит на диске, а не то, что конвертер собирался записать.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::model::data::Format;
+use crate::model::errors::{CommonErr, IoErr};
+use crate::model::resource::Resource;
+use crate::model::tx_id::TxId;
+use crate::parser::{read_batches, ParseOptions};
+
+/// Версия содержимого манифеста.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Сколько записей читается за шаг при подсчёте манифеста.
+const MANIFEST_BATCH_SIZE: usize = 10_000;
+
+/// Описание записанного файла для проверки перед загрузкой.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub format: Format,
+    pub records: usize,
+    /// SHA-256 содержимого файла в шестнадцатеричном виде
+    pub sha256: String,
+    /// TX_ID первой и последней записи файла; `None` для файла без записей
+    pub first_tx_id: Option<TxId>,
+    pub last_tx_id: Option<TxId>,
+    /// Наименьший и наибольший TIMESTAMP записей
+    pub time_range: Option<(u64, u64)>,
+    /// Программа и её версия, записавшие файл
+    pub producer: String,
+}
+
+impl Manifest {
+    /// Манифест файла `path` в формате `format`, записанного программой `producer`.
+    ///
+    /// # Errors
+    /// `CommonErr`, если файл не удалось прочитать или разобрать.
+    pub fn of_file(path: &Path, format: Format, producer: &str) -> Result<Manifest, CommonErr> {
+        let sha256 = checksum(path).map_err(|e| IoErr::input(e, Some(path.to_path_buf())))?;
+        let mut manifest = Manifest {
+            format,
+            records: 0,
+            sha256,
+            first_tx_id: None,
+            last_tx_id: None,
+            time_range: None,
+            producer: producer.to_string(),
+        };
+        let from = Resource::File { path: path.to_path_buf() };
+        for batch in read_batches(from, format, &ParseOptions::default(), MANIFEST_BATCH_SIZE)? {
+            for tx in batch?.records {
+                manifest.records += 1;
+                if manifest.first_tx_id.is_none() {
+                    manifest.first_tx_id = Some(tx.tx_id.clone());
+                }
+                manifest.time_range = Some(match manifest.time_range {
+                    Some((from, to)) => (from.min(tx.timestamp), to.max(tx.timestamp)),
+                    None => (tx.timestamp, tx.timestamp),
+                });
+                manifest.last_tx_id = Some(tx.tx_id);
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Манифест в JSON (см. описание модуля).
+    pub fn to_json(&self) -> String {
+        let tx_id = |id: &Option<TxId>| id.as_ref().map(TxId::to_string);
+        let manifest = serde_json::json!({
+            "version": MANIFEST_VERSION,
+            "format": self.format.name(),
+            "records": self.records,
+            "sha256": self.sha256,
+            "first_tx_id": tx_id(&self.first_tx_id),
+            "last_tx_id": tx_id(&self.last_tx_id),
+            "time_range": self.time_range.map(|(from, to)| serde_json::json!({ "from": from, "to": to })),
+            "producer": self.producer,
+        });
+        serde_json::to_string_pretty(&manifest).unwrap_or_default()
+    }
+}
+
+/// Путь манифеста для результата `output`: `out.csv` -> `out.csv.manifest.json`.
+pub fn manifest_path(output: &Path) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(".manifest.json");
+    PathBuf::from(name)
+}
+
+/// Строит манифест результата `output` и записывает его рядом (см. [`manifest_path`])
+/// через временный файл, чтобы загрузчик не увидел манифест недописанным.
+///
+/// # Errors
+/// `CommonErr`, если результат не удалось прочитать или манифест — записать.
+pub fn write_manifest(output: &Path, format: Format, producer: &str) -> Result<PathBuf, CommonErr> {
+    let manifest = Manifest::of_file(output, format, producer)?;
+    let path = manifest_path(output);
+    let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    let output_err = |e| CommonErr::from(IoErr::output(e, Some(path.clone())));
+    fs::write(&tmp, manifest.to_json() + "\n").map_err(output_err)?;
+    fs::rename(&tmp, &path).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        output_err(e)
+    })?;
+    Ok(path)
+}
+
+/// SHA-256 содержимого файла в шестнадцатеричном виде.
+fn checksum(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_manifest_describes_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("out.csv");
+        let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   7,DEPOSIT,0,10,100,1700000005,SUCCESS,\"x\"\n\
+                   3,TRANSFER,10,11,50,1700000001,PENDING,\"y\"\n\
+                   5,WITHDRAWAL,11,0,20,1700000003,FAILURE,\"z\"\n";
+        fs::write(&output, csv).unwrap();
+
+        let path = write_manifest(&output, Format::YpBankCsv, "cli-converter 0.1.0").unwrap();
+        assert_eq!(path, dir.path().join("out.csv.manifest.json"));
+        let manifest: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(manifest["version"], MANIFEST_VERSION);
+        assert_eq!(manifest["format"], "yp-bank-csv");
+        assert_eq!(manifest["records"], 3);
+        assert_eq!(manifest["first_tx_id"], "7");
+        assert_eq!(manifest["last_tx_id"], "5");
+        assert_eq!(manifest["time_range"]["from"], 1700000001);
+        assert_eq!(manifest["time_range"]["to"], 1700000005);
+        assert_eq!(manifest["producer"], "cli-converter 0.1.0");
+        assert_eq!(manifest["sha256"], checksum(&output).unwrap());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        // Пустой результат: записей нет, диапазонов тоже
+        fs::write(&output, "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n").unwrap();
+        let manifest = Manifest::of_file(&output, Format::YpBankCsv, "test").unwrap();
+        assert_eq!((manifest.records, manifest.first_tx_id, manifest.time_range), (0, None, None));
+    }
+}
@@ -0,0 +1,129 @@
+//! Метрики запуска для мониторинга пакетных задач: Prometheus textfile или statsd.
+//!
+//! Каждый счётчик запуска (записи, байты, пропущенные записи, …) становится метрикой
+//! `ypbank_<имя>` с меткой `command`; к ним добавляются длительность, признак успеха
+//! и время запуска.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::net::UdpSocket;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::model::errors::{CommonErr, IoErr};
+
+/// Итоги одного запуска команды.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunMetrics {
+    /// Имя подкоманды CLI
+    pub command: String,
+    pub duration: Duration,
+    pub success: bool,
+    /// Счётчики запуска по именам
+    pub counts: Vec<(&'static str, u64)>,
+}
+
+impl RunMetrics {
+    /// Метрики в текстовом формате Prometheus (для textfile collector node_exporter).
+    pub fn to_prometheus(&self) -> String {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let mut metrics = vec![
+            ("duration_seconds", "Duration of the last run", self.duration.as_secs_f64().to_string()),
+            ("success", "1 if the last run succeeded", u8::from(self.success).to_string()),
+            ("last_run_timestamp_seconds", "Unix time of the last run", timestamp.to_string()),
+        ];
+        for (name, value) in &self.counts {
+            metrics.push((name, "Counter of the last run", value.to_string()));
+        }
+
+        let mut out = String::new();
+        for (name, help, value) in metrics {
+            let _ = writeln!(out, "# HELP ypbank_{} {}", name, help);
+            let _ = writeln!(out, "# TYPE ypbank_{} gauge", name);
+            let _ = writeln!(out, "ypbank_{}{{command=\"{}\"}} {}", name, self.command, value);
+        }
+        out
+    }
+
+    /// Метрики в протоколе statsd, по одной на строку.
+    pub fn to_statsd(&self) -> String {
+        let prefix = format!("ypbank.{}", self.command.replace('-', "_"));
+        let mut out = String::new();
+        let _ = writeln!(out, "{}.runs:1|c", prefix);
+        let _ = writeln!(out, "{}.failures:{}|c", prefix, u8::from(!self.success));
+        let _ = writeln!(out, "{}.duration:{}|ms", prefix, self.duration.as_millis());
+        for (name, value) in &self.counts {
+            let _ = writeln!(out, "{}.{}:{}|c", prefix, name, value);
+        }
+        out
+    }
+}
+
+/// Записывает метрики в файл для textfile collector.
+///
+/// Файл заменяется атомарно (запись во временный файл и переименование),
+/// чтобы collector не прочитал его наполовину записанным.
+pub fn write_prometheus_textfile(path: &Path, metrics: &RunMetrics) -> Result<(), CommonErr> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let output_err = |e| IoErr::output(e, Some(path.to_path_buf()));
+    fs::write(&tmp, metrics.to_prometheus()).map_err(output_err)?;
+    fs::rename(&tmp, path).map_err(output_err)?;
+    Ok(())
+}
+
+/// Отправляет метрики одним UDP-пакетом на statsd-сервер `addr` (`host:port`).
+pub fn push_statsd(addr: &str, metrics: &RunMetrics) -> Result<(), CommonErr> {
+    let output_err = |e| IoErr::output(e, None);
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(output_err)?;
+    socket
+        .send_to(metrics.to_statsd().as_bytes(), addr)
+        .map_err(output_err)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics() -> RunMetrics {
+        RunMetrics {
+            command: "read-parse-write-command".to_string(),
+            duration: Duration::from_millis(1500),
+            success: true,
+            counts: vec![("records", 1000), ("skipped", 2)],
+        }
+    }
+
+    #[test]
+    fn test_prometheus_textfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ypbank.prom");
+        write_prometheus_textfile(&path, &metrics()).unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.contains("# TYPE ypbank_records gauge\n"));
+        assert!(text.contains("ypbank_records{command=\"read-parse-write-command\"} 1000\n"));
+        assert!(text.contains("ypbank_duration_seconds{command=\"read-parse-write-command\"} 1.5\n"));
+        assert!(text.contains("ypbank_success{command=\"read-parse-write-command\"} 1\n"));
+    }
+
+    #[test]
+    fn test_statsd_push() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        push_statsd(&server.local_addr().unwrap().to_string(), &metrics()).unwrap();
+
+        let mut buf = [0u8; 1024];
+        let n = server.recv(&mut buf).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&buf[..n]).unwrap(),
+            "ypbank.read_parse_write_command.runs:1|c\n\
+             ypbank.read_parse_write_command.failures:0|c\n\
+             ypbank.read_parse_write_command.duration:1500|ms\n\
+             ypbank.read_parse_write_command.records:1000|c\n\
+             ypbank.read_parse_write_command.skipped:2|c\n"
+        );
+    }
+}
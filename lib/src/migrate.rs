@@ -0,0 +1,233 @@
+//! Версии схемы форматов и перевод файлов, записанных старыми версиями крейта, на текущую схему.
+//!
+//! Версия схемы — это набор полей записи. Все форматы разделяют одну модель [`TxData`],
+//! поэтому и версия у них общая; в файле она указана так:
+//! - `YpBankBin` — байт версии в заголовке `YPBF`; файлы без заголовка — версия 1;
+//! - `YpBankCsv` — набор колонок в строке заголовка;
+//! - `YpBankText` — необязательный комментарий `# SCHEMA: N` в первой строке; без него — версия 1.
+//!
+//! Версия 2 разрешает нечисловые идентификаторы ([`TxId`]). В CSV и тексте идентификатор
+//! записан строкой, поэтому файлы версии 1 читаются как версия 2 без изменений;
+//! в бинарном формате версия 2 меняет кодирование TX_ID.
+//!
+//! Новое поле добавляется так: [`CURRENT_SCHEMA`] увеличивается, форматы учатся писать
+//! новую версию, а чтение прежней версии (с значениями по умолчанию для новых полей)
+//! добавляется в [`migrate`].
+//!
+//! [`TxData`]: crate::model::data::TxData
+//! [`TxId`]: crate::model::tx_id::TxId
+
+use std::io::{Cursor, Read};
+
+use crate::model::data::Format;
+use crate::model::errors::{CommonErr, ParserErr};
+use crate::model::resource::Resource;
+use crate::parser::io::reader::{open, BatchReader};
+use crate::parser::io::writer::write_batches;
+use crate::parser::options::{ConvertOptions, ParseOptions, WriteOptions};
+
+/// Версия схемы, которую пишет эта сборка.
+pub const CURRENT_SCHEMA: u32 = 2;
+
+/// Сколько байт начала файла достаточно, чтобы определить версию схемы.
+const PREFIX_LEN: u64 = 4096;
+
+/// Итог миграции файла.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrateOutcome {
+    /// Версия схемы исходного файла
+    pub from_version: u32,
+    /// Версия схемы записанного файла (всегда [`CURRENT_SCHEMA`])
+    pub to_version: u32,
+    /// Сколько записей перенесено
+    pub records: usize,
+}
+
+/// Итог [`migrate_or_inspect`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrateReport {
+    /// Версия схемы `from`
+    pub from_version: u32,
+    /// Версия схемы записанного файла; `None`, если файл только проверялся
+    pub to_version: Option<u32>,
+    /// Сколько записей перенесено
+    pub records: usize,
+}
+
+/// Определяет версию схемы ресурса.
+///
+/// # Возвращает
+/// * `Ok(u32)` — версия схемы; она может быть новее [`CURRENT_SCHEMA`]
+/// * `Err(CommonErr)` — ресурс не удалось открыть или по его началу версию не определить
+pub fn schema_version(resource: Resource, format: Format) -> Result<u32, CommonErr> {
+    detect(open(resource)?, format).map(|(version, _)| version)
+}
+
+/// Переписывает `from` в `to` в том же формате, но в текущей версии схемы.
+///
+/// Записи читаются и пишутся порциями. Файл текущей версии просто переписывается
+/// с настройками `options`. Комментарии текстового формата не переносятся.
+///
+/// # Возвращает
+/// * `Ok(MigrateOutcome)` — версии схемы и число перенесённых записей
+/// * `Err(CommonErr::Parser)` — `ParserErr::UnsupportedVersion` для версии новее текущей
+///   или ошибка разбора записи
+/// * `Err(CommonErr::IO)` — ресурс не удалось открыть или записать
+pub fn migrate(
+    from: Resource,
+    to: Resource,
+    format: Format,
+    options: &WriteOptions,
+) -> Result<MigrateOutcome, CommonErr> {
+    let (version, reader) = detect(open(from)?, format)?;
+    let batch_size = ConvertOptions::default().batch_size;
+    let batches = match version {
+        // Версия 1 отличается от 2 только допустимыми TX_ID: её читает тот же разбор
+        1..=CURRENT_SCHEMA => BatchReader::from_reader(reader, format, &ParseOptions::default(), batch_size)?,
+        version => return Err(ParserErr::UnsupportedVersion { format, version }.into()),
+    };
+
+    // Бинарный файл без заголовка остался бы версии 1
+    let options = WriteOptions {
+        bin_header: true,
+        ..options.clone()
+    };
+    let mut writer = write_batches(to, format, &options)?;
+    let mut records = 0;
+    for batch in batches {
+        let batch = batch?;
+        records += batch.records.len();
+        writer.write_batch(&batch.records, &[])?;
+    }
+    writer.finish()?;
+
+    Ok(MigrateOutcome {
+        from_version: version,
+        to_version: CURRENT_SCHEMA,
+        records,
+    })
+}
+
+/// Переводит `from` на текущую схему в `to` (см. [`migrate`]); без `to` только определяет
+/// версию схемы `from` (см. [`schema_version`]).
+pub fn migrate_or_inspect(
+    from: Resource,
+    format: Format,
+    to: Option<Resource>,
+    options: &WriteOptions,
+) -> Result<MigrateReport, CommonErr> {
+    let Some(to) = to else {
+        return Ok(MigrateReport {
+            from_version: schema_version(from, format)?,
+            to_version: None,
+            records: 0,
+        });
+    };
+
+    let outcome = migrate(from, to, format, options)?;
+    Ok(MigrateReport {
+        from_version: outcome.from_version,
+        to_version: Some(outcome.to_version),
+        records: outcome.records,
+    })
+}
+
+/// Читает начало потока и определяет по нему версию схемы.
+///
+/// Возвращает поток, который снова начинается с прочитанных байт.
+fn detect(mut reader: Box<dyn Read>, format: Format) -> Result<(u32, Box<dyn Read>), CommonErr> {
+    let mut prefix = Vec::new();
+    (&mut reader)
+        .take(PREFIX_LEN)
+        .read_to_end(&mut prefix)
+        .map_err(ParserErr::from)?;
+
+    let version = match format {
+        #[cfg(feature = "bin")]
+        Format::YpBankBin => crate::parser::concrete::bin_psrser::schema_version(&prefix),
+        #[cfg(feature = "csv")]
+        Format::YpBankCsv => crate::parser::concrete::csv_parser::schema_version(&prefix)?,
+        #[cfg(feature = "text")]
+        Format::YpBankText => crate::parser::concrete::text_parser::schema_version(&prefix)?,
+        #[allow(unreachable_patterns)]
+        disabled => return Err(crate::parser::io::format_disabled(disabled).into()),
+    };
+    Ok((version, Box::new(Cursor::new(prefix).chain(reader))))
+}
+
+#[cfg(all(test, feature = "text"))]
+mod tests {
+    use super::*;
+
+    const RECORD: &str = "TX_ID: 1\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 10\n\
+                          AMOUNT: 100\nTIMESTAMP: 1700000000\nSTATUS: SUCCESS\nDESCRIPTION: \"a\"\n";
+
+    fn memory(data: &str) -> Resource {
+        Resource::Memory {
+            data: Cursor::new(data.as_bytes().to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_schema_version_of_text() {
+        assert_eq!(schema_version(memory(RECORD), Format::YpBankText).unwrap(), 1);
+        let marked = format!("# SCHEMA: 7\n{}", RECORD);
+        assert_eq!(schema_version(memory(&marked), Format::YpBankText).unwrap(), 7);
+
+        let err = schema_version(memory("# SCHEMA: two\n"), Format::YpBankText).unwrap_err();
+        if let CommonErr::Parser(ParserErr::InvalidValue { field, value }) = err {
+            assert_eq!(field, "SCHEMA");
+            assert_eq!(value, "two");
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn test_migrate_rewrites_current_and_rejects_newer_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let to = dir.path().join("out.txt");
+        let marked = format!("# SCHEMA: 1\n{}", RECORD);
+
+        let outcome = migrate(
+            memory(&marked),
+            Resource::File { path: to.clone() },
+            Format::YpBankText,
+            &WriteOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            outcome,
+            MigrateOutcome {
+                from_version: 1,
+                to_version: CURRENT_SCHEMA,
+                records: 1
+            }
+        );
+        assert_eq!(std::fs::read_to_string(&to).unwrap(), RECORD.trim_end());
+
+        let newer = format!("# SCHEMA: {}\n{}", CURRENT_SCHEMA + 1, RECORD);
+        let err = migrate(memory(&newer), Resource::File { path: to }, Format::YpBankText, &WriteOptions::default())
+            .unwrap_err();
+        if let CommonErr::Parser(ParserErr::UnsupportedVersion { version, .. }) = err {
+            assert_eq!(version, CURRENT_SCHEMA + 1);
+        } else {
+            panic!()
+        }
+    }
+
+    #[cfg(all(feature = "bin", feature = "csv"))]
+    #[test]
+    fn test_schema_version_of_bin_and_csv() {
+        let header = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n";
+        assert_eq!(schema_version(memory(header), Format::YpBankCsv).unwrap(), 1);
+        let err = schema_version(memory("TX_ID,CURRENCY\n"), Format::YpBankCsv).unwrap_err();
+        assert!(matches!(err, CommonErr::Parser(ParserErr::InvalidHeader { .. })));
+
+        assert_eq!(schema_version(memory("YPBN"), Format::YpBankBin).unwrap(), 1);
+        let header = Resource::Memory {
+            data: Cursor::new(b"YPBF\x02\x00\x00\x00".to_vec()),
+        };
+        assert_eq!(schema_version(header, Format::YpBankBin).unwrap(), 2);
+    }
+}
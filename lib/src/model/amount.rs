@@ -0,0 +1,158 @@
+use crate::model::errors::ParserErr;
+
+/// Запись суммы с разделителями разрядов и дробной частью, принятая в конкретной локали.
+///
+/// Такие суммы записаны в целых единицах валюты и переводятся в минимальные единицы
+/// (сотые доли), в которых хранится `TxData::amount`: `1.000.000,50` -> `100000050`.
+/// Дробная часть необязательна и содержит одну или две цифры; группы разрядов, если они есть,
+/// должны быть по три цифры.
+#[cfg_attr(feature = "console", derive(clap::ValueEnum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmountLocale {
+    /// `1,000,000.50`
+    En,
+    /// `1.000.000,50`
+    De,
+    /// `1 000 000,50`; разделитель разрядов — пробел, в том числе неразрывный
+    Fr,
+}
+
+impl AmountLocale {
+    /// Допустимые разделители разрядов и десятичный разделитель.
+    fn separators(self) -> (&'static [char], char) {
+        match self {
+            AmountLocale::En => (&[','], '.'),
+            AmountLocale::De => (&['.'], ','),
+            AmountLocale::Fr => (&[' ', '\u{a0}', '\u{202f}'], ','),
+        }
+    }
+
+    /// Разбирает сумму и переводит её в минимальные единицы.
+    ///
+    /// # Errors
+    /// `ParserErr::InvalidValue` для поля `AMOUNT`, если запись не соответствует локали;
+    /// `ParserErr::NumberOverflow`, если сумма не помещается в `i64`.
+    pub fn parse(self, value: &str) -> Result<i64, ParserErr> {
+        let invalid = || ParserErr::InvalidValue {
+            field: "AMOUNT",
+            value: value.to_string(),
+        };
+        let (negative, digits, cents) = self.split(value.trim()).ok_or_else(invalid)?;
+
+        // Цифр может быть сколько угодно, поэтому переполнение проверяется на i128
+        let units: i128 = digits.parse().map_err(|_| ParserErr::NumberOverflow {
+            field: "AMOUNT",
+            value: value.to_string(),
+        })?;
+        let amount = units.checked_mul(100).and_then(|a| a.checked_add(cents));
+        let amount = amount.map(|a| if negative { -a } else { a });
+        amount
+            .and_then(|a| i64::try_from(a).ok())
+            .ok_or_else(|| ParserErr::NumberOverflow {
+                field: "AMOUNT",
+                value: value.to_string(),
+            })
+    }
+
+    /// Проверяет запись и делит её на знак, цифры целой части без разделителей и сотые доли.
+    fn split(self, value: &str) -> Option<(bool, String, i128)> {
+        let (groups, decimal) = self.separators();
+        let (negative, value) = match value.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, value),
+        };
+        let (integer, fraction) = match value.split_once(decimal) {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (value, None),
+        };
+
+        let parts: Vec<&str> = integer.split(groups).collect();
+        let grouped = parts.len() > 1;
+        for (i, part) in parts.iter().enumerate() {
+            let valid_len = match (grouped, i) {
+                (false, _) => !part.is_empty(),
+                (true, 0) => (1..=3).contains(&part.len()),
+                (true, _) => part.len() == 3,
+            };
+            if !valid_len || !part.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+        }
+
+        let cents = match fraction {
+            None => 0,
+            Some(f) if (1..=2).contains(&f.len()) && f.bytes().all(|b| b.is_ascii_digit()) => {
+                let cents: i128 = f.parse().ok()?;
+                if f.len() == 1 { cents * 10 } else { cents }
+            }
+            Some(_) => return None,
+        };
+        Some((negative, parts.concat(), cents))
+    }
+}
+
+/// Итог сумм транзакций без переполнения.
+///
+/// Сумма даже двух значений около `i64::MAX` не помещается в `i64`, поэтому агрегаты
+/// (статистика, балансы) копят суммы в `i128`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AmountTotal(i128);
+
+impl AmountTotal {
+    /// Добавляет сумму транзакции.
+    pub fn add(&mut self, amount: i64) {
+        self.0 += i128::from(amount);
+    }
+
+    /// Итог без потери точности.
+    pub fn get(self) -> i128 {
+        self.0
+    }
+
+    /// Итог как `i64`; `None`, если он в `i64` не помещается.
+    pub fn to_i64(self) -> Option<i64> {
+        i64::try_from(self.0).ok()
+    }
+}
+
+impl FromIterator<i64> for AmountTotal {
+    fn from_iter<I: IntoIterator<Item = i64>>(iter: I) -> Self {
+        let mut total = AmountTotal::default();
+        iter.into_iter().for_each(|amount| total.add(amount));
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_locale_amounts() {
+        assert_eq!(AmountLocale::De.parse("1.000.000,50").unwrap(), 100_000_050);
+        assert_eq!(AmountLocale::Fr.parse("1 000 000").unwrap(), 100_000_000);
+        assert_eq!(AmountLocale::Fr.parse("1\u{a0}234,5").unwrap(), 123_450);
+        assert_eq!(AmountLocale::En.parse("-1,234.05").unwrap(), -123_405);
+        assert_eq!(AmountLocale::En.parse("42").unwrap(), 4_200);
+
+        for bad in ["1.000.000,50", "1,00,000", "12,3456", "1.5.", "", "1,000.123"] {
+            if let Err(ParserErr::InvalidValue { field, value }) = AmountLocale::En.parse(bad) {
+                assert_eq!(field, "AMOUNT");
+                assert_eq!(value, bad);
+            } else {
+                panic!("{}", bad)
+            }
+        }
+    }
+
+    #[test]
+    fn test_overflow_is_reported() {
+        let err = AmountLocale::En.parse("92,233,720,368,547,758.08").unwrap_err();
+        assert!(matches!(err, ParserErr::NumberOverflow { field: "AMOUNT", .. }));
+        assert_eq!(AmountLocale::En.parse("-92,233,720,368,547,758.08").unwrap(), i64::MIN);
+
+        let total: AmountTotal = [i64::MAX, i64::MAX].into_iter().collect();
+        assert_eq!(total.get(), 2 * i128::from(i64::MAX));
+        assert_eq!(total.to_i64(), None);
+    }
+}
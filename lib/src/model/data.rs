@@ -1,11 +1,12 @@
-use clap::ValueEnum;
+use std::sync::Arc;
 
+use crate::model::tx_id::TxId;
 
 /// Представляет одну финансовую транзакцию в системе 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TxData {
     /// Уникальный идентификатор транзакции
-    pub tx_id: u64,
+    pub tx_id: TxId,
     /// Тип операции
     pub tx_type: TxType,
     /// Идентификатор отправителя
@@ -19,11 +20,33 @@ pub struct TxData {
     /// Текущий статус транзакции
     pub status: Status,
     /// Описание или комментарий к транзакции
-    pub description: String,
+    ///
+    /// Разделяемая строка: одинаковые описания могут ссылаться на одну копию
+    /// (см. `ParseOptions::intern_descriptions`)
+    pub description: Arc<str>,
     /// Формат, связанный с этой транзакцией
     pub format: Format,
 }
 
+impl TxData {
+    /// Имена полей в порядке спецификации (колонки CSV, ключи текстового формата).
+    pub const FIELDS: [&'static str; 8] = [
+        "TX_ID",
+        "TX_TYPE",
+        "FROM_USER_ID",
+        "TO_USER_ID",
+        "AMOUNT",
+        "TIMESTAMP",
+        "STATUS",
+        "DESCRIPTION",
+    ];
+
+    /// Совпадают ли записи по всем полям, кроме формата, из которого они прочитаны.
+    pub fn same_content(&self, other: &TxData) -> bool {
+        TxData { format: other.format, ..self.clone() } == *other
+    }
+}
+
 /// Тип финансовой операции.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TxType {
@@ -35,6 +58,20 @@ pub enum TxType {
     Withdrawal,
 }
 
+impl TxType {
+    /// Все типы в порядке их кодов в бинарном формате.
+    pub const ALL: [TxType; 3] = [TxType::Deposit, TxType::Transfer, TxType::Withdrawal];
+
+    /// Значение поля `TX_TYPE` в текстовых форматах.
+    pub fn name(self) -> &'static str {
+        match self {
+            TxType::Deposit => "DEPOSIT",
+            TxType::Transfer => "TRANSFER",
+            TxType::Withdrawal => "WITHDRAWAL",
+        }
+    }
+}
+
 /// Статус выполнения транзакции.
 ///
 /// Отражает текущее состояние обработки операции в системе.
@@ -48,8 +85,23 @@ pub enum Status {
     Pending,
 }
 
+impl Status {
+    /// Все статусы в порядке их кодов в бинарном формате.
+    pub const ALL: [Status; 3] = [Status::Success, Status::Failure, Status::Pending];
+
+    /// Значение поля `STATUS` в текстовых форматах.
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Success => "SUCCESS",
+            Status::Failure => "FAILURE",
+            Status::Pending => "PENDING",
+        }
+    }
+}
+
 /// Поддерживаемые форматы сериализации транзакций.
-#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "console", derive(clap::ValueEnum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Format {
     /// CSV-формат с заголовком и разделителем-запятой
     ///
@@ -67,4 +119,35 @@ pub enum Format {
     ///
     /// Компактное представление без избыточных символов
     YpBankBin,
-}
\ No newline at end of file
+}
+impl Format {
+    const ALL: [Format; 3] = [Format::YpBankCsv, Format::YpBankText, Format::YpBankBin];
+
+    /// Расширение файла, под которым формат принято сохранять (как у файлов в `test-data`).
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::YpBankCsv => "csv",
+            Format::YpBankText => "txt",
+            Format::YpBankBin => "bin",
+        }
+    }
+
+    /// Формат файла с расширением `extension` (см. [`Format::extension`]).
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        Self::ALL.into_iter().find(|format| format.extension() == extension)
+    }
+
+    /// Имя формата, как в аргументах CLI.
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::YpBankCsv => "yp-bank-csv",
+            Format::YpBankText => "yp-bank-text",
+            Format::YpBankBin => "yp-bank-bin",
+        }
+    }
+
+    /// Формат по имени из [`Format::name`].
+    pub fn from_name(name: &str) -> Option<Format> {
+        Self::ALL.into_iter().find(|format| format.name() == name)
+    }
+}
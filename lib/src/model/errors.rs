@@ -1,5 +1,15 @@
+use std::io::ErrorKind;
+use std::num::ParseIntError;
+use std::path::PathBuf;
+use std::string::FromUtf8Error;
+use std::sync::Arc;
+
 use thiserror::Error;
 
+use crate::model::data::Format;
+use crate::model::tx_id::TxId;
+use crate::model::validation::RuleViolation;
+
 #[derive(Error, Debug, Clone)]
 pub enum CommonErr {
     /// Ошибка ввода-вывода (чтение/запись файлов, консоли и т.д.)
@@ -16,25 +26,341 @@ pub enum CommonErr {
 }
 
 /// Ошибки, связанные с операциями ввода-вывода.
+///
+/// Хранят путь к ресурсу (если это файл), вид ошибки и исходную `io::Error`.
 #[derive(Error, Debug, Clone)]
 pub enum IoErr {
     /// Ошибка при чтении входных данных
-    #[error("io -> input error")]
-    InputErr,
+    #[error("io -> input error{}: {kind}", path_suffix(.path))]
+    InputErr {
+        path: Option<PathBuf>,
+        kind: ErrorKind,
+        #[source]
+        source: Arc<std::io::Error>,
+    },
 
     /// Ошибка при записи выходных данных
-    #[error("io -> output error")]
-    OutputErr,
+    #[error("io -> output error{}: {kind}", path_suffix(.path))]
+    OutputErr {
+        path: Option<PathBuf>,
+        kind: ErrorKind,
+        #[source]
+        source: Arc<std::io::Error>,
+    },
+}
+
+impl IoErr {
+    /// Ошибка чтения ресурса `path` (`None` — консоль или память).
+    pub fn input(source: std::io::Error, path: Option<PathBuf>) -> Self {
+        IoErr::InputErr {
+            path,
+            kind: source.kind(),
+            source: Arc::new(source),
+        }
+    }
+
+    /// Ошибка записи в ресурс `path` (`None` — консоль или память).
+    pub fn output(source: std::io::Error, path: Option<PathBuf>) -> Self {
+        IoErr::OutputErr {
+            path,
+            kind: source.kind(),
+            source: Arc::new(source),
+        }
+    }
+}
+
+fn path_suffix(path: &Option<PathBuf>) -> String {
+    path.as_ref()
+        .map(|p| format!(" ({})", p.display()))
+        .unwrap_or_default()
 }
 
 /// Ошибки, связанные с парсингом и сериализацией данных.
+///
+/// Каждый вариант несёт структурированные данные, чтобы вызывающий код мог
+/// сопоставлять ошибки через `match`, а не разбирать строки. Исходные ошибки
+/// (`io::Error`, `ParseIntError`, …) доступны через `source()`.
 #[derive(Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum ParserErr {
-    /// Ошибка при десериализации (парсинге) входных данных
-    #[error("parser -> global error")]
-    ParseErr { msg: String },
-
-    /// Ошибка при сериализации данных для вывода
-    #[error("serealize -> global error")]  // Примечание: опечатка в "serialize"
-    SerializeErr { msg: String },
-}
\ No newline at end of file
+    /// Ошибка чтения или записи потока во время разбора или сериализации.
+    ///
+    /// Ошибки открытия и создания ресурсов описываются [`IoErr`].
+    #[error("io error{}", path_suffix(.path))]
+    Io {
+        #[source]
+        source: Arc<std::io::Error>,
+        path: Option<PathBuf>,
+    },
+
+    /// В записи отсутствует обязательное поле
+    #[error("Missing field: {name}")]
+    MissingField { name: &'static str },
+
+    /// Поле есть, но его значение пустое, а политика `EmptyFieldPolicy` это запрещает
+    #[error("Empty field: {field}")]
+    EmptyField { field: &'static str },
+
+    /// Поле присутствует, но его значение недопустимо (например, неизвестный TX_TYPE)
+    #[error("Invalid {field}: {value}")]
+    InvalidValue { field: &'static str, value: String },
+
+    /// Числовое поле не удалось разобрать
+    #[error("Invalid {field}: {value}")]
+    InvalidNumber {
+        field: &'static str,
+        value: String,
+        #[source]
+        source: ParseIntError,
+    },
+
+    /// Число записано верно, но не помещается в тип поля
+    #[error("{field} is out of range: {value}")]
+    NumberOverflow { field: &'static str, value: String },
+
+    /// Строковое поле содержит некорректный UTF-8
+    #[error("Invalid UTF-8 in {field}")]
+    InvalidUtf8 {
+        field: &'static str,
+        #[source]
+        source: FromUtf8Error,
+    },
+
+    /// Заголовок файла не совпадает с ожидаемым
+    #[error("Invalid header: expected '{expected}', got '{actual}'")]
+    InvalidHeader { expected: String, actual: String },
+
+    /// Ключ повторяется внутри одной текстовой записи (часто — две записи без пустой строки между ними)
+    #[error("Duplicate key: {key}")]
+    DuplicateKey { key: String },
+
+    /// В текстовой записи есть ключи, не входящие в формат
+    #[error("Record {record}: unexpected keys {}", .keys.join(", "))]
+    UnexpectedKeys { record: usize, keys: Vec<String> },
+
+    /// Число полей в записи не совпадает с ожидаемым
+    #[error("Expected {expected} fields, got {actual}")]
+    FieldCount { expected: usize, actual: usize },
+
+    /// Бинарная запись не начинается с магического числа `YPBN`
+    #[error("Invalid MAGIC number: {found:?}")]
+    InvalidMagic { found: [u8; 4] },
+
+    /// Заявленная длина поля выходит за пределы записи
+    #[error("{field} length exceeds body: {len} > {available}")]
+    LengthExceedsBody {
+        field: &'static str,
+        len: usize,
+        available: usize,
+    },
+
+    /// Синтаксически некорректный ввод, не подпадающий под более точные варианты
+    #[error("Malformed input: {msg}")]
+    Malformed { msg: String },
+
+    /// Сжатый блок бинарного файла или его распакованные записи больше `limit` байт
+    #[error("{what} zstd block is {len} bytes, the limit is {limit}")]
+    BlockTooLarge { what: &'static str, len: u64, limit: u64 },
+
+    /// Версия формата не поддерживается этой сборкой
+    #[error("Unsupported {format:?} version: {version}")]
+    UnsupportedVersion { format: Format, version: u32 },
+
+    /// Имя формата уже занято встроенным или зарегистрированным форматом
+    #[error("Format {name} is already registered")]
+    DuplicateFormat { name: String },
+
+    /// WASM-модуль формата (см. модуль `plugin`) не справился с данными
+    #[error("Plugin {name} failed: {reason}")]
+    Plugin { name: String, reason: String },
+
+    /// Поддержка формата отключена cargo-фичами
+    #[error("Format {format:?} is disabled at compile time")]
+    UnsupportedFormat { format: Format },
+
+    /// Данные требуют возможности, отключённой cargo-фичами (например, сжатия zstd)
+    #[error("Feature `{feature}` is disabled at compile time")]
+    FeatureDisabled { feature: &'static str },
+
+    /// Запись нарушает смысловое правило (проверяется в строгом режиме разбора)
+    #[error("{violation}")]
+    Rule { violation: RuleViolation },
+
+    /// Запись в формате с такими настройками нельзя продолжить после перезапуска
+    #[error("Writing {format:?} with these options cannot be resumed")]
+    NotResumable { format: Format },
+
+    /// В файл такого формата нельзя дописывать записи
+    #[error("Appending to {format:?} is not supported")]
+    NotAppendable { format: Format },
+
+    /// Колонки из справочника пользователей пишутся только в CSV
+    #[error("Joined user columns cannot be written to {format:?}, use YpBankCsv")]
+    NotJoinable { format: Format },
+
+    /// Файл архива не совпадает с манифестом или отсутствует
+    #[error("Bundle member {member}: {reason}")]
+    BundleMember { member: String, reason: String },
+
+    /// Операция дельты не согласуется со снимком, к которому её применяют
+    #[error("Delta {op} conflicts with base snapshot: TX_ID {tx_id}")]
+    DeltaConflict { op: &'static str, tx_id: TxId },
+
+    /// Нечисловой идентификатор нельзя записать в файл версии схемы 1
+    #[error("TX_ID {tx_id} needs schema version 2; binary files without a YPBF header are version 1")]
+    IdNeedsSchema { tx_id: TxId },
+
+    /// Описание длиннее `WriteOptions::max_description_len` при `DescriptionLengthPolicy::Reject`
+    #[error("DESCRIPTION of TX_ID {tx_id} is {chars} characters, the limit is {limit}")]
+    DescriptionTooLong { tx_id: TxId, chars: usize, limit: usize },
+
+    /// Предупреждение, которое по настройкам разбора считается ошибкой
+    #[error("{warning} (warnings are treated as errors)")]
+    Escalated { warning: WarningKind },
+
+    /// Ошибка в конкретной строке (или записи) входных данных
+    #[error("error on line {line}")]
+    AtLine {
+        line: usize,
+        #[source]
+        source: Box<ParserErr>,
+    },
+}
+
+impl ParserErr {
+    /// Оборачивает ошибку указанием строки (или порядкового номера записи).
+    pub fn at_line(self, line: usize) -> Self {
+        ParserErr::AtLine {
+            line,
+            source: Box::new(self),
+        }
+    }
+
+    /// Возвращает исходную ошибку без обёрток [`ParserErr::AtLine`].
+    pub fn root(&self) -> &ParserErr {
+        match self {
+            ParserErr::AtLine { source, .. } => source.root(),
+            other => other,
+        }
+    }
+
+    /// Номер строки, если ошибка к ней привязана.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            ParserErr::AtLine { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
+
+    /// Поле записи, к которому относится ошибка, если его можно определить.
+    pub fn field(&self) -> Option<&'static str> {
+        match self.root() {
+            ParserErr::MissingField { name } => Some(name),
+            ParserErr::EmptyField { field }
+            | ParserErr::InvalidValue { field, .. }
+            | ParserErr::InvalidNumber { field, .. }
+            | ParserErr::NumberOverflow { field, .. }
+            | ParserErr::InvalidUtf8 { field, .. }
+            | ParserErr::LengthExceedsBody { field, .. } => Some(field),
+            ParserErr::DescriptionTooLong { .. } => Some("DESCRIPTION"),
+            ParserErr::Rule { violation } => Some(violation.field()),
+            _ => None,
+        }
+    }
+}
+
+/// Проблема в отдельной записи, пропущенной при разборе в режиме `ErrorMode::Collect`.
+#[derive(Debug, Clone)]
+pub struct ParseIssue {
+    /// Номер строки (для бинарного формата — порядковый номер записи)
+    pub line: Option<usize>,
+    /// Поле, к которому относится проблема
+    pub field: Option<&'static str>,
+    /// Описание причины без номера строки
+    pub reason: String,
+    /// Исходная ошибка для программной обработки
+    pub error: ParserErr,
+}
+
+impl From<ParserErr> for ParseIssue {
+    fn from(error: ParserErr) -> Self {
+        ParseIssue {
+            line: error.line(),
+            field: error.field(),
+            reason: error.root().to_string(),
+            error,
+        }
+    }
+}
+
+/// Вид некритичной странности во входных данных.
+///
+/// Запись с такой странностью разбирается успешно, но источник данных, скорее всего,
+/// стоит поправить.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WarningKind {
+    /// Описание в текстовом формате записано без кавычек
+    #[error("DESCRIPTION is not quoted")]
+    UnquotedDescription,
+
+    /// Строка или значение заканчивается пробельными символами
+    #[error("trailing whitespace")]
+    TrailingWhitespace,
+
+    /// Описание пустое
+    #[error("DESCRIPTION is empty")]
+    EmptyDescription,
+
+    /// В CSV есть колонки после канонических; их значения отброшены
+    #[error("extra columns ignored: {columns}")]
+    ExtraColumns { columns: String },
+
+    /// Описание длиннее `WriteOptions::max_description_len` записано как есть
+    /// (`DescriptionLengthPolicy::Warn`)
+    #[error("DESCRIPTION of TX_ID {tx_id} is {chars} characters, longer than {limit}")]
+    LongDescription { tx_id: TxId, chars: usize, limit: usize },
+}
+
+/// Предупреждение разбора, возвращаемое отдельно от ошибок.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    /// Номер строки (для бинарного формата — порядковый номер записи)
+    pub line: Option<usize>,
+    /// Что именно не так
+    pub kind: WarningKind,
+}
+
+impl ParseWarning {
+    pub(crate) fn at(line: usize, kind: WarningKind) -> Self {
+        ParseWarning {
+            line: Some(line),
+            kind,
+        }
+    }
+}
+
+impl From<std::io::Error> for ParserErr {
+    fn from(source: std::io::Error) -> Self {
+        // Ошибка разбора, прошедшая через `Read` (например, распаковку сжатых блоков), возвращается как есть
+        match source.downcast::<ParserErr>() {
+            Ok(err) => err,
+            Err(source) => ParserErr::Io {
+                source: Arc::new(source),
+                path: None,
+            },
+        }
+    }
+}
+
+/// Ошибка вместе с причинами из `source()` через `: ` (`error on line 2: Invalid number ...`).
+pub fn describe_error(err: &dyn std::error::Error) -> String {
+    let mut text = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        text.push_str(": ");
+        text.push_str(&err.to_string());
+        source = err.source();
+    }
+    text
+}
@@ -0,0 +1,104 @@
+//! Сведения о том, кто и из чего записал файл: по ним находят происхождение файла
+//! неизвестного происхождения.
+//!
+//! В CSV и текстовом формате сведения пишутся строками-комментариями перед данными:
+//!
+//! ```text
+//! #@ producer: cli-converter 0.0.1
+//! #@ created: 1700000000000
+//! #@ source-sha256: 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+//! ```
+//!
+//! Текстовый формат и так пропускает комментарии, а читатель CSV пропускает строки `#@`
+//! в начале файла. В бинарном формате те же пары «ключ — значение» лежат сразу после
+//! заголовка файла. Неизвестные ключи при чтении пропускаются.
+
+use std::fmt;
+
+/// Начало строки сведений о файле в CSV и текстовом формате.
+pub const METADATA_PREFIX: &str = "#@";
+
+/// Сведения о программе, записавшей файл.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProducerMetadata {
+    /// Программа и её версия
+    pub producer: String,
+    /// Когда файл записан, мс от начала эпохи Unix
+    pub created_ms: u64,
+    /// SHA-256 входа, из которого получен файл; `None`, если вход не файл
+    pub source_sha256: Option<String>,
+}
+
+impl ProducerMetadata {
+    /// Пары «ключ — значение» в порядке записи.
+    pub(crate) fn pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = vec![("producer", self.producer.clone()), ("created", self.created_ms.to_string())];
+        if let Some(sha256) = &self.source_sha256 {
+            pairs.push(("source-sha256", sha256.clone()));
+        }
+        pairs
+    }
+
+    /// Сведения из пар «ключ — значение»; `None`, если нет программы или времени записи.
+    pub(crate) fn from_pairs<'a>(pairs: impl IntoIterator<Item = (&'a str, &'a str)>) -> Option<ProducerMetadata> {
+        let (mut producer, mut created_ms, mut source_sha256) = (None, None, None);
+        for (key, value) in pairs {
+            match key {
+                "producer" => producer = Some(value.to_string()),
+                "created" => created_ms = value.parse().ok(),
+                "source-sha256" => source_sha256 = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        Some(ProducerMetadata {
+            producer: producer?,
+            created_ms: created_ms?,
+            source_sha256,
+        })
+    }
+
+    /// Строки-комментарии для CSV и текстового формата, без перевода строки.
+    #[cfg(any(feature = "csv", feature = "text"))]
+    pub(crate) fn comment_lines(&self) -> Vec<String> {
+        self.pairs().into_iter().map(|(key, value)| format!("{} {}: {}", METADATA_PREFIX, key, value)).collect()
+    }
+
+    /// Сведения из строк-комментариев в начале файла; прочие строки пропускаются.
+    pub(crate) fn from_comment_lines<'a>(lines: impl IntoIterator<Item = &'a str>) -> Option<ProducerMetadata> {
+        Self::from_pairs(lines.into_iter().filter_map(|line| {
+            let (key, value) = line.strip_prefix(METADATA_PREFIX)?.split_once(':')?;
+            Some((key.trim(), value.trim()))
+        }))
+    }
+}
+
+impl fmt::Display for ProducerMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines = self.pairs().into_iter().map(|(key, value)| format!("{}: {}", key, value)).collect::<Vec<_>>();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(all(test, any(feature = "csv", feature = "text")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comment_lines_roundtrip() {
+        let metadata = ProducerMetadata {
+            producer: "cli-converter 0.0.1".to_string(),
+            created_ms: 1700000000000,
+            source_sha256: Some("abc".to_string()),
+        };
+        let lines = metadata.comment_lines();
+        assert_eq!(lines[0], "#@ producer: cli-converter 0.0.1");
+
+        let mut with_noise: Vec<&str> = lines.iter().map(String::as_str).collect();
+        with_noise.insert(1, "#@ future-key: ignored");
+        with_noise.push("# ordinary comment");
+        assert_eq!(ProducerMetadata::from_comment_lines(with_noise), Some(metadata));
+
+        // Без времени записи сведения неполны
+        assert_eq!(ProducerMetadata::from_comment_lines(["#@ producer: x"]), None);
+    }
+}
@@ -1,2 +1,9 @@
+pub mod amount;
 pub mod errors;
-pub mod data;
\ No newline at end of file
+pub mod metadata;
+pub mod normalize;
+pub mod data;
+pub mod resource;
+pub mod time;
+pub mod tx_id;
+pub mod validation;
@@ -0,0 +1,158 @@
+use std::borrow::Cow;
+
+#[cfg(feature = "nfc")]
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+use crate::model::data::TxData;
+
+/// Чем заменяется отрезанный хвост слишком длинного описания.
+pub const TRUNCATION_MARKER: &str = "…";
+
+/// Приведение описаний к единому виду, чтобы описания, различающиеся только пробелами
+/// или способом записи символов, считались одинаковыми.
+///
+/// Шаги применяются в порядке полей; по умолчанию описание не меняется.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DescriptionNormalization {
+    /// Убрать пробельные символы в начале и в конце
+    pub trim: bool,
+    /// Заменить каждую последовательность пробельных символов внутри описания одним пробелом
+    pub collapse_whitespace: bool,
+    /// Привести Unicode к форме NFC (`е` + `◌̈` и `ё` становятся одним символом)
+    #[cfg(feature = "nfc")]
+    pub nfc: bool,
+    /// Обрезать описание до стольких символов, считая [`TRUNCATION_MARKER`] в конце
+    pub max_len: Option<usize>,
+}
+
+/// Шаг нормализации для списка в командной строке (`--normalize-description trim,collapse`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "console", derive(clap::ValueEnum))]
+pub enum NormalizeStep {
+    /// `DescriptionNormalization::trim`
+    Trim,
+    /// `DescriptionNormalization::collapse_whitespace`
+    Collapse,
+    /// `DescriptionNormalization::nfc`
+    #[cfg(feature = "nfc")]
+    Nfc,
+}
+
+impl DescriptionNormalization {
+    /// Нормализация из шагов командной строки и ограничения длины.
+    pub fn from_steps(steps: &[NormalizeStep], max_len: Option<usize>) -> Self {
+        let mut normalization = DescriptionNormalization { max_len, ..Self::default() };
+        for step in steps {
+            match step {
+                NormalizeStep::Trim => normalization.trim = true,
+                NormalizeStep::Collapse => normalization.collapse_whitespace = true,
+                #[cfg(feature = "nfc")]
+                NormalizeStep::Nfc => normalization.nfc = true,
+            }
+        }
+        normalization
+    }
+
+    /// Ни один шаг не включён.
+    pub fn is_noop(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Нормализованное описание; `Cow::Borrowed` — исходное описание или его часть после `trim`.
+    pub fn apply<'a>(&self, description: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(description);
+        if self.trim {
+            text = match text {
+                Cow::Borrowed(s) => Cow::Borrowed(s.trim()),
+                Cow::Owned(s) => Cow::Owned(s.trim().to_string()),
+            };
+        }
+        if self.collapse_whitespace && needs_collapse(&text) {
+            let mut collapsed = String::with_capacity(text.len());
+            let mut in_space = false;
+            for c in text.chars() {
+                if c.is_whitespace() {
+                    if !in_space {
+                        collapsed.push(' ');
+                    }
+                    in_space = true;
+                } else {
+                    collapsed.push(c);
+                    in_space = false;
+                }
+            }
+            text = Cow::Owned(collapsed);
+        }
+        #[cfg(feature = "nfc")]
+        if self.nfc && is_nfc_quick(text.chars()) != IsNormalized::Yes {
+            let normalized: String = text.nfc().collect();
+            if normalized != text {
+                text = Cow::Owned(normalized);
+            }
+        }
+        if let Some(max_len) = self.max_len {
+            if let Some((cut, _)) = text.char_indices().nth(max_len) {
+                // Описание длиннее max_len: оставляем место для маркера
+                let keep = max_len.saturating_sub(TRUNCATION_MARKER.chars().count());
+                let end = text.char_indices().nth(keep).map_or(cut, |(i, _)| i);
+                text = Cow::Owned(format!("{}{}", &text[..end], TRUNCATION_MARKER));
+            }
+        }
+        text
+    }
+
+    /// Запись с нормализованным описанием; описание не копируется, если оно не изменилось.
+    pub fn apply_tx(&self, mut tx: TxData) -> TxData {
+        let description = match self.apply(&tx.description) {
+            Cow::Owned(description) => description.into(),
+            // Заимствуется только часть исходного описания (после trim)
+            Cow::Borrowed(description) if description.len() != tx.description.len() => description.into(),
+            Cow::Borrowed(_) => return tx,
+        };
+        tx.description = description;
+        tx
+    }
+}
+
+/// Есть ли в тексте пробельные символы, которые схлопывание изменит.
+fn needs_collapse(text: &str) -> bool {
+    let mut prev_space = false;
+    for c in text.chars() {
+        let space = c.is_whitespace();
+        if space && (prev_space || c != ' ') {
+            return true;
+        }
+        prev_space = space;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalization_steps() {
+        let all = DescriptionNormalization::from_steps(&[NormalizeStep::Trim, NormalizeStep::Collapse], Some(8));
+        assert_eq!(all.apply("  Monthly \t  fee "), "Monthly…");
+        assert_eq!(all.apply(" fee\n"), "fee");
+        assert!(matches!(all.apply("fee"), Cow::Borrowed("fee")));
+
+        let collapse = DescriptionNormalization { collapse_whitespace: true, ..Default::default() };
+        assert_eq!(collapse.apply(" a \t b "), " a b ");
+        assert!(matches!(collapse.apply(" a b "), Cow::Borrowed(_)));
+
+        let short = DescriptionNormalization { max_len: Some(4), ..Default::default() };
+        assert_eq!(short.apply("Возврат"), "Воз…");
+        assert_eq!(short.apply("Плат"), "Плат");
+        assert!(DescriptionNormalization::default().is_noop());
+    }
+
+    #[cfg(feature = "nfc")]
+    #[test]
+    fn test_nfc_joins_combining_marks() {
+        let nfc = DescriptionNormalization { nfc: true, ..Default::default() };
+        assert_eq!(nfc.apply("e\u{0301}clair"), "\u{00e9}clair");
+        assert!(matches!(nfc.apply("\u{00e9}clair"), Cow::Borrowed(_)));
+    }
+}
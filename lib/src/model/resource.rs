@@ -0,0 +1,44 @@
+use std::io::Cursor;
+use std::path::PathBuf;
+
+/// Источник или приёмник данных для чтения и записи транзакций.
+#[derive(Clone, Debug)]
+pub enum Resource {
+    /// Стандартный ввод при чтении, стандартный вывод при записи
+    Console,
+    /// Файл на диске
+    File { path: PathBuf },
+    /// Буфер в памяти (используется в тестах и при встраивании библиотеки)
+    Memory { data: Cursor<Vec<u8>> },
+    /// Файл `member` внутри zip-архива `archive`; только для чтения, без распаковки на диск
+    #[cfg(feature = "zip")]
+    ZipMember { archive: PathBuf, member: String },
+}
+
+impl Resource {
+    /// Ресурс — поток, который нельзя перечитать или перемотать: консоль или файл,
+    /// не являющийся обычным файлом (именованный канал, устройство, сокет).
+    ///
+    /// Ещё не созданный файл потоком не считается: он будет обычным файлом.
+    pub fn is_stream(&self) -> bool {
+        match self {
+            Resource::Console => true,
+            Resource::File { path } => std::fs::metadata(path).is_ok_and(|meta| !meta.is_file() && !meta.is_dir()),
+            _ => false,
+        }
+    }
+}
+
+/// Ресурс в том же виде, в каком его принимает CLI: `console`, `file:<path>`,
+/// `file:<archive>!<member>`; буфер в памяти — `memory`.
+impl std::fmt::Display for Resource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Resource::Console => write!(f, "console"),
+            Resource::File { path } => write!(f, "file:{}", path.display()),
+            Resource::Memory { .. } => write!(f, "memory"),
+            #[cfg(feature = "zip")]
+            Resource::ZipMember { archive, member } => write!(f, "file:{}!{}", archive.display(), member),
+        }
+    }
+}
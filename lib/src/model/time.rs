@@ -0,0 +1,206 @@
+/// Полуоткрытый интервал времени `[since, until)` для отбора записей по `TIMESTAMP`.
+///
+/// Границы — Unix timestamp в миллисекундах, как поле `TIMESTAMP` в спецификации YPBank.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeWindow {
+    /// Первая подходящая метка (включительно); `None` — без нижней границы
+    pub since: Option<u64>,
+    /// Первая неподходящая метка (не включительно); `None` — без верхней границы
+    pub until: Option<u64>,
+}
+
+impl TimeWindow {
+    /// Попадает ли метка в интервал.
+    pub fn contains(&self, timestamp: u64) -> bool {
+        self.since.is_none_or(|since| timestamp >= since) && self.until.is_none_or(|until| timestamp < until)
+    }
+}
+
+/// Метки меньше этой в миллисекундах — до марта 1973 года, в секундах — после 5000 года,
+/// поэтому по ней различаются единицы `TIMESTAMP`.
+pub const SECONDS_LIMIT: u64 = 100_000_000_000;
+
+/// В каких единицах `TIMESTAMP` во входных данных; при чтении метки приводятся к миллисекундам.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "console", derive(clap::ValueEnum))]
+pub enum TimestampUnit {
+    /// Миллисекунды, как в спецификации: метка не меняется
+    #[default]
+    Milliseconds,
+    /// Секунды: метка умножается на 1000
+    Seconds,
+    /// По величине каждой метки: меньше [`SECONDS_LIMIT`] — секунды, иначе миллисекунды
+    Auto,
+}
+
+impl TimestampUnit {
+    /// Метка в миллисекундах.
+    pub fn to_millis(self, timestamp: u64) -> u64 {
+        match self {
+            TimestampUnit::Milliseconds => timestamp,
+            TimestampUnit::Auto if timestamp >= SECONDS_LIMIT => timestamp,
+            TimestampUnit::Seconds | TimestampUnit::Auto => timestamp.saturating_mul(1000),
+        }
+    }
+}
+
+/// Разбирает границу интервала: число (метка как есть), дату `YYYY-MM-DD`
+/// или дату со временем `YYYY-MM-DDTHH:MM:SS` (UTC, допускается `Z` в конце).
+///
+/// Даты переводятся в миллисекунды Unix.
+pub fn parse_time_bound(s: &str) -> Result<u64, String> {
+    if let Ok(timestamp) = s.parse::<u64>() {
+        return Ok(timestamp);
+    }
+
+    let invalid = || format!("expected a timestamp, YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS, got '{}'", s);
+    let s = s.strip_suffix('Z').unwrap_or(s);
+    let (date, time) = s.split_once('T').unwrap_or((s, "00:00:00"));
+
+    let number = |part: Option<&str>, range: std::ops::RangeInclusive<u64>| {
+        part.and_then(|p| p.parse::<u64>().ok())
+            .filter(|n| range.contains(n))
+            .ok_or_else(invalid)
+    };
+
+    let mut date = date.split('-');
+    let year = number(date.next(), 1970..=9999)?;
+    let month = number(date.next(), 1..=12)?;
+    let day = number(date.next(), 1..=days_in_month(year, month))?;
+    let mut time = time.split(':');
+    let hour = number(time.next(), 0..=23)?;
+    let minute = number(time.next(), 0..=59)?;
+    let second = number(time.next(), 0..=59)?;
+    if date.next().is_some() || time.next().is_some() {
+        return Err(invalid());
+    }
+
+    let days = days_from_civil(year, month, day);
+    Ok((((days * 24 + hour) * 60 + minute) * 60 + second) * 1000)
+}
+
+/// Разбирает длительность в миллисекундах: число с единицей `ms`, `s`, `m` или `h`
+/// (`500ms`, `2s`, `1m`); число без единицы — миллисекунды, как `TIMESTAMP`.
+pub fn parse_duration_ms(s: &str) -> Result<u64, String> {
+    let invalid = || format!("expected a duration like 500ms, 2s, 1m or 1h, got '{}'", s);
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let scale = match unit {
+        "" | "ms" => 1,
+        "s" => 1000,
+        "m" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        _ => return Err(invalid()),
+    };
+    number.checked_mul(scale).ok_or_else(invalid)
+}
+
+/// Записывает метку в миллисекундах как `YYYY-MM-DDTHH:MM:SSZ` (UTC), в виде,
+/// который принимает [`parse_time_bound`].
+pub fn format_timestamp(timestamp: u64) -> String {
+    let seconds = timestamp / 1000;
+    let mut days = seconds / 86_400;
+    let mut year = 1970;
+    while days >= 365 + u64::from(is_leap(year)) {
+        days -= 365 + u64::from(is_leap(year));
+        year += 1;
+    }
+    let mut month = 1;
+    while days >= days_in_month(year, month) {
+        days -= days_in_month(year, month);
+        month += 1;
+    }
+    let time = seconds % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        days + 1,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+fn is_leap(year: u64) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Число дней от 1970-01-01 до даты (для лет не раньше 1970).
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let leap_days = |y: u64| y / 4 - y / 100 + y / 400;
+    let years = (1970..year).count() as u64;
+    let mut days = years * 365 + leap_days(year - 1) - leap_days(1969);
+    days += (1..month).map(|m| days_in_month(year, m)).sum::<u64>();
+    days + day - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time_bound() {
+        assert_eq!(parse_time_bound("1633036860000"), Ok(1633036860000));
+        assert_eq!(parse_time_bound("1970-01-01"), Ok(0));
+        assert_eq!(parse_time_bound("2021-09-30T21:21:00Z"), Ok(1633036860000));
+        assert_eq!(parse_time_bound("2024-03-01"), Ok(1709251200000));
+        assert!(parse_time_bound("2023-02-29").is_err());
+        assert!(parse_time_bound("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_timestamp_unit_to_millis() {
+        // Оба вида меток из наших примеров CSV
+        assert_eq!(TimestampUnit::Auto.to_millis(1700000000), 1700000000000);
+        assert_eq!(TimestampUnit::Auto.to_millis(1633037580000), 1633037580000);
+        assert_eq!(TimestampUnit::Auto.to_millis(0), 0);
+        assert_eq!(TimestampUnit::Seconds.to_millis(1633037580), 1633037580000);
+        assert_eq!(TimestampUnit::Milliseconds.to_millis(1700000000), 1700000000);
+        assert_eq!(TimestampUnit::Seconds.to_millis(u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn test_format_timestamp_round_trips() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(1633036860000), "2021-09-30T21:21:00Z");
+        assert_eq!(format_timestamp(1709251199999), "2024-02-29T23:59:59Z");
+        assert_eq!(parse_time_bound(&format_timestamp(1709251200000)), Ok(1709251200000));
+    }
+
+    #[test]
+    fn test_parse_duration_ms() {
+        assert_eq!(parse_duration_ms("0"), Ok(0));
+        assert_eq!(parse_duration_ms("250"), Ok(250));
+        assert_eq!(parse_duration_ms("500ms"), Ok(500));
+        assert_eq!(parse_duration_ms("2s"), Ok(2000));
+        assert_eq!(parse_duration_ms("1m"), Ok(60_000));
+        assert_eq!(parse_duration_ms("1h"), Ok(3_600_000));
+        assert!(parse_duration_ms("2 s").is_err());
+        assert!(parse_duration_ms("s").is_err());
+        assert!(parse_duration_ms("1d").is_err());
+    }
+
+    #[test]
+    fn test_time_window_is_half_open() {
+        let window = TimeWindow {
+            since: Some(10),
+            until: Some(20),
+        };
+        assert!(!window.contains(9));
+        assert!(window.contains(10));
+        assert!(window.contains(19));
+        assert!(!window.contains(20));
+        assert!(TimeWindow::default().contains(0));
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::model::errors::ParserErr;
+
+/// Идентификатор транзакции.
+///
+/// До версии схемы 2 идентификатор был только числом. Выгрузки новой платформы
+/// используют UUID, а часть источников — произвольные строки, поэтому с версии схемы 2
+/// допустимы все три вида. Из текста вид определяется по значению: цифры — [`TxId::U64`],
+/// UUID вида `8-4-4-4-12` (шестнадцатеричные цифры в любом регистре) — [`TxId::Uuid`],
+/// остальное — [`TxId::String`].
+///
+/// Идентификаторы упорядочены сначала по виду (числа, UUID, строки), затем по значению.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TxId {
+    U64(u64),
+    /// UUID, 128 бит в порядке записи
+    Uuid(u128),
+    String(String),
+}
+
+/// Длины групп шестнадцатеричных цифр UUID.
+const UUID_GROUPS: [usize; 5] = [8, 4, 4, 4, 12];
+
+impl TxId {
+    /// Разбирает идентификатор из текстового представления.
+    ///
+    /// # Errors
+    /// `ParserErr::EmptyField` для пустой строки;
+    /// `ParserErr::NumberOverflow`, если число не помещается в `u64`.
+    pub fn parse(value: &str) -> Result<TxId, ParserErr> {
+        if value.is_empty() {
+            return Err(ParserErr::EmptyField { field: "TX_ID" });
+        }
+        if value.bytes().all(|b| b.is_ascii_digit()) {
+            // Из одних цифр u64 не разбирается только при переполнении
+            return value.parse().map(TxId::U64).map_err(|_| ParserErr::NumberOverflow {
+                field: "TX_ID",
+                value: value.to_string(),
+            });
+        }
+        Ok(parse_uuid(value).map_or_else(|| TxId::String(value.to_string()), TxId::Uuid))
+    }
+
+    /// Число, если идентификатор числовой (его понимают файлы версии схемы 1).
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            TxId::U64(id) => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+/// UUID вида `8-4-4-4-12`; `None`, если строка им не является.
+fn parse_uuid(value: &str) -> Option<u128> {
+    let groups: Vec<&str> = value.split('-').collect();
+    let valid = groups.len() == UUID_GROUPS.len()
+        && groups
+            .iter()
+            .zip(UUID_GROUPS)
+            .all(|(group, len)| group.len() == len && group.bytes().all(|b| b.is_ascii_hexdigit()));
+    if !valid {
+        return None;
+    }
+    u128::from_str_radix(&groups.concat(), 16).ok()
+}
+
+impl FromStr for TxId {
+    type Err = ParserErr;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        TxId::parse(value)
+    }
+}
+
+impl fmt::Display for TxId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxId::U64(id) => write!(f, "{}", id),
+            TxId::Uuid(id) => {
+                let hex = format!("{:032x}", id);
+                write!(f, "{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+            }
+            TxId::String(id) => f.write_str(id),
+        }
+    }
+}
+
+impl From<u64> for TxId {
+    fn from(id: u64) -> Self {
+        TxId::U64(id)
+    }
+}
+
+impl PartialEq<u64> for TxId {
+    fn eq(&self, other: &u64) -> bool {
+        self.as_u64() == Some(*other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display_round_trip() {
+        assert_eq!(TxId::parse("42").unwrap(), TxId::U64(42));
+        let uuid = TxId::parse("123E4567-e89b-12d3-A456-426614174000").unwrap();
+        assert_eq!(uuid, TxId::Uuid(0x123e4567_e89b_12d3_a456_426614174000));
+        assert_eq!(uuid.to_string(), "123e4567-e89b-12d3-a456-426614174000");
+        assert_eq!(TxId::parse("ord-7").unwrap(), TxId::String("ord-7".to_string()));
+        // Похоже на UUID, но группы другой длины
+        assert!(matches!(TxId::parse("123e4567-e89b-12d3-a456-4266141740").unwrap(), TxId::String(_)));
+
+        assert!(matches!(TxId::parse(""), Err(ParserErr::EmptyField { field: "TX_ID" })));
+        assert!(matches!(
+            TxId::parse("18446744073709551616"),
+            Err(ParserErr::NumberOverflow { field: "TX_ID", .. })
+        ));
+        assert!(TxId::U64(9) < uuid && uuid < TxId::String("0".to_string()));
+    }
+}
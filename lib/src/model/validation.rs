@@ -0,0 +1,186 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use thiserror::Error;
+
+use crate::model::data::{Status, TxData, TxType};
+use crate::model::time::format_timestamp;
+
+/// 2000-01-01T00:00:00Z в миллисекундах: нижняя граница [`TimestampBounds::default`].
+pub const MIN_SANE_TIMESTAMP: u64 = 946_684_800_000;
+
+/// Насколько [`TimestampBounds::default`] допускает метки в будущем: сутки.
+pub const FUTURE_SLACK_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Допустимый интервал `TIMESTAMP` в миллисекундах, границы включительно.
+///
+/// Метка в секундах вместо миллисекунд попадает в январь 1970 года, а в микросекундах —
+/// в год 53970; обе выходят за интервал по умолчанию.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampBounds {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl TimestampBounds {
+    /// Попадает ли метка в интервал.
+    pub fn contains(&self, timestamp: u64) -> bool {
+        (self.min..=self.max).contains(&timestamp)
+    }
+}
+
+impl Default for TimestampBounds {
+    /// С 2000-01-01 до суток после текущего момента.
+    fn default() -> Self {
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis() as u64);
+        TimestampBounds {
+            min: MIN_SANE_TIMESTAMP,
+            max: now_ms + FUTURE_SLACK_MS,
+        }
+    }
+}
+
+/// Нарушение смыслового правила: запись синтаксически корректна,
+/// но описывает невозможную операцию.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RuleViolation {
+    /// Нулевой идентификатор транзакции зарезервирован
+    #[error("TX_ID must not be zero")]
+    ZeroTxId,
+
+    /// Пополнение на отрицательную сумму
+    #[error("DEPOSIT amount must not be negative: {amount}")]
+    NegativeDeposit { amount: i64 },
+
+    /// Перевод самому себе
+    #[error("TRANSFER sender and receiver are the same user: {user_id}")]
+    SelfTransfer { user_id: u64 },
+
+    /// Сумма больше допустимой для типа операции (набор правил `rules`)
+    #[error("{} amount {amount} exceeds the maximum {max}", tx_type.name())]
+    AmountAboveMax { tx_type: TxType, amount: i64, max: i64 },
+
+    /// ID пользователя вне допустимых диапазонов (набор правил `rules`)
+    #[error("{field} {user_id} is outside the allowed user id ranges")]
+    UserIdOutOfRange { field: &'static str, user_id: u64 },
+
+    /// Статус, недопустимый для старой записи (набор правил `rules`)
+    #[error("STATUS {} is not allowed for records older than the rules allow: TIMESTAMP {timestamp}", status.name())]
+    StaleStatus { status: Status, timestamp: u64 },
+
+    /// Метка времени вне допустимого интервала (чаще всего секунды вместо миллисекунд)
+    #[error("TIMESTAMP {timestamp} is outside {}..{}", format_timestamp(*min), format_timestamp(*max))]
+    TimestampOutOfRange { timestamp: u64, min: u64, max: u64 },
+}
+
+impl RuleViolation {
+    /// Поле записи, к которому относится нарушение.
+    pub fn field(&self) -> &'static str {
+        match self {
+            RuleViolation::ZeroTxId => "TX_ID",
+            RuleViolation::NegativeDeposit { .. } => "AMOUNT",
+            RuleViolation::SelfTransfer { .. } => "TO_USER_ID",
+            RuleViolation::AmountAboveMax { .. } => "AMOUNT",
+            RuleViolation::UserIdOutOfRange { field, .. } => field,
+            RuleViolation::StaleStatus { .. } => "STATUS",
+            RuleViolation::TimestampOutOfRange { .. } => "TIMESTAMP",
+        }
+    }
+}
+
+/// Проверяет транзакцию по всем правилам, `TIMESTAMP` — по [`TimestampBounds::default`],
+/// и возвращает найденные нарушения.
+///
+/// Пустой результат означает, что запись корректна.
+pub fn validate(tx: &TxData) -> Vec<RuleViolation> {
+    validate_with(tx, Some(&TimestampBounds::default()))
+}
+
+/// Как [`validate`], но `TIMESTAMP` проверяется по `timestamps`; `None` — не проверяется.
+pub fn validate_with(tx: &TxData, timestamps: Option<&TimestampBounds>) -> Vec<RuleViolation> {
+    let mut violations = Vec::new();
+
+    if tx.tx_id == 0 {
+        violations.push(RuleViolation::ZeroTxId);
+    }
+    if tx.tx_type == TxType::Deposit && tx.amount < 0 {
+        violations.push(RuleViolation::NegativeDeposit { amount: tx.amount });
+    }
+    if tx.tx_type == TxType::Transfer && tx.from_user_id == tx.to_user_id {
+        violations.push(RuleViolation::SelfTransfer {
+            user_id: tx.from_user_id,
+        });
+    }
+    if let Some(bounds) = timestamps.filter(|bounds| !bounds.contains(tx.timestamp)) {
+        violations.push(RuleViolation::TimestampOutOfRange {
+            timestamp: tx.timestamp,
+            min: bounds.min,
+            max: bounds.max,
+        });
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::data::{Format, Status};
+
+    fn tx(tx_id: u64, tx_type: TxType, from: u64, to: u64, amount: i64) -> TxData {
+        TxData {
+            tx_id: tx_id.into(),
+            tx_type,
+            from_user_id: from,
+            to_user_id: to,
+            amount,
+            timestamp: 1700000000000,
+            status: Status::Success,
+            description: "rule".into(),
+            format: Format::YpBankCsv,
+        }
+    }
+
+    #[test]
+    fn test_validate_accepts_regular_records() {
+        assert!(validate(&tx(1, TxType::Deposit, 0, 10, 100)).is_empty());
+        assert!(validate(&tx(2, TxType::Transfer, 10, 20, 50)).is_empty());
+        assert!(validate(&tx(3, TxType::Withdrawal, 20, 0, -5)).is_empty());
+    }
+
+    #[test]
+    fn test_validate_reports_every_violation() {
+        let violations = validate(&tx(0, TxType::Deposit, 0, 10, -100));
+        assert_eq!(
+            violations,
+            vec![
+                RuleViolation::ZeroTxId,
+                RuleViolation::NegativeDeposit { amount: -100 }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_self_transfer() {
+        let violations = validate(&tx(5, TxType::Transfer, 7, 7, 10));
+        assert_eq!(violations, vec![RuleViolation::SelfTransfer { user_id: 7 }]);
+        assert_eq!(violations[0].field(), "TO_USER_ID");
+    }
+
+    #[test]
+    fn test_validate_timestamp_bounds() {
+        // Секунды вместо миллисекунд и микросекунды (год 53970) вне интервала по умолчанию
+        for timestamp in [1700000000, 1700000000000000] {
+            let record = TxData { timestamp, ..tx(6, TxType::Deposit, 0, 10, 100) };
+            let violations = validate(&record);
+            assert!(matches!(violations[..], [RuleViolation::TimestampOutOfRange { .. }]), "{:?}", violations);
+        }
+
+        let record = TxData { timestamp: 1700000000, ..tx(7, TxType::Deposit, 0, 10, 100) };
+        assert!(validate_with(&record, None).is_empty());
+        let bounds = TimestampBounds { min: 0, max: 2000000000 };
+        assert!(validate_with(&record, Some(&bounds)).is_empty());
+        let violation = &validate_with(&tx(8, TxType::Deposit, 0, 10, 100), Some(&bounds))[0];
+        assert_eq!(violation.to_string(), "TIMESTAMP 1700000000000 is outside 1970-01-01T00:00:00Z..1970-01-24T03:33:20Z");
+    }
+}
@@ -0,0 +1,223 @@
+//! Уведомление о завершении запуска: итог отправляется JSON-ом на webhook.
+//!
+//! Тело запроса — запись журнала аудита (см. [`crate::audit`]) с длительностью запуска:
+//!
+//! ```json
+//! {"timestamp":1700000000000,"command":"read-parse-write-command","inputs":[…],"outputs":[…],
+//!  "counts":{"records":1000},"outcome":"error","error":"…","failures":[…],"duration_ms":1520}
+//! ```
+//!
+//! Временные сбои (нет соединения, таймаут, ответ 429 или 5xx) повторяются по [`RetryPolicy`]
+//! с экспоненциально растущей паузой; остальные ответы сервера не повторяются.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::audit::{now_ms, AuditEntry};
+
+/// Сколько ждать ответа webhook, прежде чем считать отправку неудачной.
+pub const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Тело уведомления о запуске `entry`, длившемся `duration`.
+pub fn webhook_payload(entry: &AuditEntry, duration: Duration) -> String {
+    let mut payload = entry.to_json(now_ms());
+    payload["duration_ms"] = (duration.as_millis() as u64).into();
+    payload.to_string()
+}
+
+/// Сколько раз и с какими паузами повторять отправку после временного сбоя.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Сколько всего попыток, включая первую; 0 и 1 — без повторов
+    pub attempts: u32,
+    /// Пауза перед первым повтором; перед каждым следующим она удваивается
+    pub backoff: Duration,
+    /// Наибольшая пауза между попытками
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 3,
+            backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Пауза после неудачной попытки номер `attempt` (с 1).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// Одна неудачная попытка отправки.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attempt {
+    /// Описание сбоя
+    pub error: String,
+    /// Сколько длилась попытка
+    pub elapsed: Duration,
+    /// Сбой временный, и попытку можно было повторить
+    pub transient: bool,
+}
+
+#[derive(Error, Debug, Clone)]
+pub enum NotifyErr {
+    /// Все попытки не удались или сбой не временный; `attempts` — история попыток по порядку
+    #[error("webhook {url} failed after {} attempt(s): {}", attempts.len(), attempts.last().map_or("", |a| a.error.as_str()))]
+    Failed { url: String, attempts: Vec<Attempt> },
+}
+
+/// Отправляет итог запуска POST-запросом на `url` (`http://` или `https://`), повторяя
+/// временные сбои по `retry`.
+///
+/// # Errors
+/// `NotifyErr::Failed` с историей попыток, если адрес неверен, сервер ответил статусом
+/// не из 2xx или все попытки исчерпаны (сервер недоступен, не ответил за [`WEBHOOK_TIMEOUT`]).
+pub fn notify_webhook(url: &str, entry: &AuditEntry, duration: Duration, retry: &RetryPolicy) -> Result<(), NotifyErr> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(WEBHOOK_TIMEOUT))
+        .build()
+        .into();
+    let payload = webhook_payload(entry, duration);
+    let mut attempts = Vec::new();
+    loop {
+        let started = Instant::now();
+        let sent = agent.post(url).header("Content-Type", "application/json").send(payload.as_str());
+        let Err(e) = sent else {
+            return Ok(());
+        };
+        let transient = is_transient(&e);
+        attempts.push(Attempt {
+            error: e.to_string(),
+            elapsed: started.elapsed(),
+            transient,
+        });
+        let tried = attempts.len() as u32;
+        if !transient || tried >= retry.attempts {
+            return Err(NotifyErr::Failed { url: url.to_string(), attempts });
+        }
+        thread::sleep(retry.delay(tried));
+    }
+}
+
+/// Сбой, после которого повтор может помочь.
+fn is_transient(e: &ureq::Error) -> bool {
+    match e {
+        ureq::Error::StatusCode(code) => *code == 429 || *code >= 500,
+        ureq::Error::Io(_)
+        | ureq::Error::Timeout(_)
+        | ureq::Error::HostNotFound
+        | ureq::Error::ConnectionFailed
+        | ureq::Error::BodyStalled => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::resource::Resource;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Отвечает на запросы по очереди статусами `statuses` и возвращает тела запросов.
+    fn serve(statuses: Vec<&'static str>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+                bodies.push(String::from_utf8(body).unwrap());
+            }
+            bodies
+        });
+        (url, server)
+    }
+
+    fn quick_retry(attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            attempts,
+            backoff: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[test]
+    fn test_notify_webhook_posts_summary() {
+        let (url, server) = serve(vec!["200 OK"]);
+        let entry = AuditEntry::new("read-parse-write-command")
+            .input(&Resource::Console)
+            .count("records", 3)
+            .failure(&Resource::File { path: "bad.csv".into() }, &std::io::Error::other("broken"))
+            .outcome(&Err::<(), _>(std::io::Error::other("failed")));
+        notify_webhook(&url, &entry, Duration::from_millis(1520), &RetryPolicy::default()).unwrap();
+
+        let body: serde_json::Value = serde_json::from_str(&server.join().unwrap()[0]).unwrap();
+        assert_eq!(body["command"], "read-parse-write-command");
+        assert_eq!(body["counts"]["records"], 3);
+        assert_eq!(body["outcome"], "error");
+        assert_eq!(body["failures"][0]["resource"], "file:bad.csv");
+        assert_eq!(body["duration_ms"], 1520);
+    }
+
+    #[test]
+    fn test_notify_webhook_retries_transient_failures() {
+        let entry = AuditEntry::new("get").outcome(&Ok::<(), std::io::Error>(()));
+
+        let (url, server) = serve(vec!["503 Service Unavailable", "200 OK"]);
+        notify_webhook(&url, &entry, Duration::ZERO, &quick_retry(3)).unwrap();
+        let bodies = server.join().unwrap();
+        assert_eq!(bodies[0], bodies[1]);
+
+        let (url, server) = serve(vec!["500 Internal Server Error", "502 Bad Gateway"]);
+        let Err(NotifyErr::Failed { attempts, .. }) = notify_webhook(&url, &entry, Duration::ZERO, &quick_retry(2)) else {
+            panic!()
+        };
+        server.join().unwrap();
+        let errors: Vec<&str> = attempts.iter().map(|a| a.error.as_str()).collect();
+        assert_eq!(errors, ["http status: 500", "http status: 502"]);
+
+        // Ошибка клиента не повторяется
+        let (url, server) = serve(vec!["400 Bad Request"]);
+        let Err(NotifyErr::Failed { attempts, .. }) = notify_webhook(&url, &entry, Duration::ZERO, &quick_retry(3)) else {
+            panic!()
+        };
+        server.join().unwrap();
+        assert_eq!((attempts.len(), attempts[0].transient), (1, false));
+    }
+
+    #[test]
+    fn test_retry_delay_doubles_up_to_max() {
+        let policy = RetryPolicy {
+            attempts: 10,
+            backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(3),
+        };
+        let delays: Vec<u128> = (1..=5).map(|attempt| policy.delay(attempt).as_millis()).collect();
+        assert_eq!(delays, [500, 1000, 2000, 3000, 3000]);
+    }
+}
@@ -0,0 +1,162 @@
+//! Что каждый формат умеет хранить и как его можно читать и писать.
+//!
+//! [`Format::capabilities`] отвечает на вопрос для формата в целом, а
+//! [`Format::capabilities_with`] — для файла, записанного с конкретными настройками: бинарный
+//! файл без заголовка (версии схемы 1) хранит только числовые `TX_ID`, а без индекса не даёт
+//! искать записи по `TX_ID`. По этим ответам [`conversion_losses`] говорит заранее, что
+//! конвертация может потерять часть входа, а [`Loss::affects`] — какие записи её теряют.
+
+use std::fmt;
+
+use crate::model::data::{Format, TxData};
+use crate::parser::options::{BinProfile, ParseOptions, WriteOptions};
+
+/// Возможности формата (см. описание модуля).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Любая запись, в том числе с нечисловым `TX_ID`, читается обратно без изменений
+    pub lossless: bool,
+    /// Сохраняются комментарии и пустые строки входа (`TextOptions::keep_comments`)
+    pub keeps_comments: bool,
+    /// Сохраняются колонки CSV сверх спецификации (`CsvOptions::allow_extra_columns`)
+    pub keeps_extra_columns: bool,
+    /// Хранятся описания длиннее `u32::MAX` байт (в профиле `BigEndian` длина описания — u32)
+    pub long_descriptions: bool,
+    /// Хранятся сведения о записавшей файл программе (`WriteOptions::metadata`)
+    pub metadata: bool,
+    /// Читается и пишется порциями, без всего входа в памяти (`read_batches`, `write_batches`)
+    pub streaming: bool,
+    /// В существующий файл можно дописывать (`WriteOptions::append`)
+    pub append: bool,
+    /// Запись достаётся по `TX_ID` без чтения всего файла (`BinIndex`)
+    pub random_access: bool,
+}
+
+impl Format {
+    /// Возможности формата при самых полных настройках записи.
+    pub fn capabilities(self) -> Capabilities {
+        let full = WriteOptions { bin_header: true, bin_index: true, ..WriteOptions::default() };
+        self.capabilities_with(&full)
+    }
+
+    /// Возможности файла в этом формате, записанного с настройками `options`.
+    pub fn capabilities_with(self, options: &WriteOptions) -> Capabilities {
+        let bin = self == Format::YpBankBin;
+        let legacy = bin && options.writes_legacy_bin();
+        Capabilities {
+            lossless: !legacy,
+            keeps_comments: self == Format::YpBankText,
+            keeps_extra_columns: false,
+            long_descriptions: !(bin && options.bin_profile == BinProfile::BigEndian),
+            metadata: !legacy,
+            streaming: true,
+            append: bin,
+            random_access: bin && options.bin_index,
+        }
+    }
+}
+
+/// Что теряется при конвертации (см. [`conversion_losses`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Loss {
+    /// Сохранённые комментарии и пустые строки не записываются
+    Comments,
+    /// Колонки CSV сверх спецификации отбрасываются
+    ExtraColumns,
+    /// Записи с нечисловым `TX_ID` не записать: конвертация остановится на первой такой
+    StringTxIds,
+    /// Описания длиннее `u32::MAX` байт не записать в профиле `BigEndian`
+    LongDescriptions,
+}
+
+impl Loss {
+    /// Теряет ли запись `tx` что-то при этой потере; `false` для потерь всего входа
+    /// (комментарии, лишние колонки CSV).
+    pub fn affects(self, tx: &TxData) -> bool {
+        match self {
+            Loss::StringTxIds => tx.tx_id.as_u64().is_none(),
+            Loss::LongDescriptions => tx.description.len() as u64 > u32::MAX as u64,
+            Loss::Comments | Loss::ExtraColumns => false,
+        }
+    }
+}
+
+impl fmt::Display for Loss {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Loss::Comments => write!(f, "comments and blank lines are dropped"),
+            Loss::ExtraColumns => write!(f, "extra CSV columns are dropped"),
+            Loss::StringTxIds => write!(f, "non-numeric TX_ID cannot be written (write a header with --bin-header)"),
+            Loss::LongDescriptions => write!(f, "description longer than 4 GiB cannot be written (use --bin-profile compact-le)"),
+        }
+    }
+}
+
+/// Что потеряет конвертация входа `from`, разобранного с `parse`, в формат `to` с настройками `write`.
+///
+/// Учитывается только то, что разбор действительно сохраняет: комментарии — только с
+/// `TextOptions::keep_comments`, лишние колонки CSV — только с `CsvOptions::allow_extra_columns`.
+pub fn conversion_losses(from: Format, parse: &ParseOptions, to: Format, write: &WriteOptions) -> Vec<Loss> {
+    let (source, target) = (from.capabilities(), to.capabilities_with(write));
+    let mut losses = Vec::new();
+    if parse.text.keep_comments && source.keeps_comments && !target.keeps_comments {
+        losses.push(Loss::Comments);
+    }
+    if parse.csv.allow_extra_columns && from == Format::YpBankCsv && !target.keeps_extra_columns {
+        losses.push(Loss::ExtraColumns);
+    }
+    if source.lossless && !target.lossless {
+        losses.push(Loss::StringTxIds);
+    }
+    if source.long_descriptions && !target.long_descriptions {
+        losses.push(Loss::LongDescriptions);
+    }
+    losses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::data::{Status, TxType};
+    use crate::model::tx_id::TxId;
+
+    #[test]
+    fn test_capabilities_and_losses() {
+        let legacy = Format::YpBankBin.capabilities_with(&WriteOptions::default());
+        assert!(!legacy.lossless && !legacy.metadata && legacy.append && !legacy.random_access);
+        let full = Format::YpBankBin.capabilities();
+        assert!(full.lossless && full.random_access);
+        assert!(Format::YpBankText.capabilities().keeps_comments);
+        assert!(!Format::YpBankCsv.capabilities().append);
+        assert!(!full.long_descriptions);
+
+        let mut parse = ParseOptions::default();
+        let write = WriteOptions::default();
+        assert_eq!(conversion_losses(Format::YpBankCsv, &parse, Format::YpBankText, &write), []);
+        let bin = conversion_losses(Format::YpBankCsv, &parse, Format::YpBankBin, &write);
+        assert_eq!(bin, [Loss::StringTxIds, Loss::LongDescriptions]);
+        let compact = WriteOptions { bin_header: true, bin_profile: BinProfile::CompactLe, ..WriteOptions::default() };
+        assert_eq!(conversion_losses(Format::YpBankCsv, &parse, Format::YpBankBin, &compact), []);
+
+        parse.text.keep_comments = true;
+        parse.csv.allow_extra_columns = true;
+        assert_eq!(conversion_losses(Format::YpBankText, &parse, Format::YpBankText, &write), []);
+        assert_eq!(conversion_losses(Format::YpBankText, &parse, Format::YpBankCsv, &write), [Loss::Comments]);
+        assert_eq!(conversion_losses(Format::YpBankCsv, &parse, Format::YpBankCsv, &write), [Loss::ExtraColumns]);
+
+        let mut tx = TxData {
+            tx_id: TxId::U64(1),
+            tx_type: TxType::Deposit,
+            from_user_id: 0,
+            to_user_id: 1,
+            amount: 100,
+            timestamp: 1700000000000,
+            status: Status::Success,
+            description: "x".into(),
+            format: Format::YpBankCsv,
+        };
+        assert!(!Loss::StringTxIds.affects(&tx) && !Loss::Comments.affects(&tx));
+        tx.tx_id = TxId::parse("abc").unwrap();
+        assert!(Loss::StringTxIds.affects(&tx) && !Loss::LongDescriptions.affects(&tx));
+    }
+}
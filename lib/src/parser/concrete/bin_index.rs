@@ -0,0 +1,563 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::model::data::TxData;
+use crate::model::errors::ParserErr;
+use crate::model::tx_id::TxId;
+use crate::parser::concrete::bin_psrser::{
+    read_file_header, read_record, read_tx_id, read_varint, read_varint_from, zstd_blocks, BinAppend, Dictionary,
+    FileHeader, SeekEntry, BIN_MAGIC, FILE_VERSION, FLAG_INDEX, FLAG_SEEK_TABLE, FLAG_ZSTD, INDEX_MAGIC, LEGACY_VERSION, SEEKABLE_MAGIC,
+    SEEK_ENTRY_LEN, SEEK_FOOTER_LEN, SKIPPABLE_MAGIC,
+};
+use crate::parser::options::BinProfile;
+
+/// Размер хвоста индекса: смещение индекса (u64), число записей (u64), `YPBI`.
+const TRAILER_LEN: u64 = 8 + 8 + 4;
+
+/// Индекс `tx_id -> смещение`, дописанный в конец бинарного файла при записи
+/// с `WriteOptions::bin_index`.
+///
+/// Позволяет достать запись по `tx_id`, не читая весь архив.
+#[derive(Debug, Clone)]
+pub struct BinIndex {
+    profile: BinProfile,
+    /// Версия схемы файла
+    version: u8,
+    /// Словарь описаний файла
+    dictionary: Option<Arc<Dictionary>>,
+    compressed: bool,
+    /// Пары (tx_id, смещение), отсортированные по tx_id
+    entries: Vec<(TxId, u64)>,
+}
+
+impl BinIndex {
+    /// Читает индекс из файла. Возвращает `None`, если файл записан без индекса.
+    ///
+    /// # Errors
+    /// Возвращает [`ParserErr`], если заголовок или индекс повреждены.
+    pub fn read_from<R: Read + Seek>(reader: &mut R) -> Result<Option<BinIndex>, ParserErr> {
+        reader.seek(SeekFrom::Start(0))?;
+        let header = match read_file_header(reader)? {
+            Some(header) if header.flags & FLAG_INDEX != 0 => header,
+            _ => return Ok(None),
+        };
+        let FileHeader { version, profile, flags, dictionary, .. } = header;
+
+        let (index_offset, count) = read_trailer(reader, flags)?;
+        let mut magic = [0u8; 4];
+        reader.seek(SeekFrom::Start(index_offset))?;
+        reader.read_exact(&mut magic)?;
+        if magic != INDEX_MAGIC {
+            return Err(ParserErr::InvalidMagic { found: magic });
+        }
+
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            let tx_id = read_tx_id::<LittleEndian, _>(reader, version, |reader| read_varint(reader))?;
+            let offset = reader.read_u64::<LittleEndian>()?;
+            entries.push((tx_id, offset));
+        }
+        // Стабильная сортировка: при повторе tx_id первой остаётся более ранняя запись
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        Ok(Some(BinIndex {
+            profile,
+            version,
+            dictionary,
+            compressed: flags & FLAG_ZSTD != 0,
+            entries,
+        }))
+    }
+
+    /// Смещение записи (для сжатых файлов — её блока) от начала файла.
+    pub fn lookup(&self, tx_id: &TxId) -> Option<u64> {
+        let pos = self.entries.partition_point(|(id, _)| id < tx_id);
+        match self.entries.get(pos) {
+            Some((id, offset)) if id == tx_id => Some(*offset),
+            _ => None,
+        }
+    }
+
+    /// Число записей в индексе.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Индекс не содержит ни одной записи.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Читает запись с заданным `tx_id`, переходя сразу к её смещению.
+    ///
+    /// # Errors
+    /// Возвращает [`ParserErr`], если запись по смещению не читается.
+    pub fn get<R: Read + Seek>(&self, reader: &mut R, tx_id: &TxId) -> Result<Option<TxData>, ParserErr> {
+        let Some(offset) = self.lookup(tx_id) else {
+            return Ok(None);
+        };
+        reader.seek(SeekFrom::Start(offset))?;
+
+        let mut buf = Vec::new();
+        if !self.compressed {
+            let tx = read_record(reader, self.profile, self.version, self.dictionary.as_deref(), &mut buf)?;
+            return Ok(tx.filter(|tx| tx.tx_id == *tx_id));
+        }
+
+        // Сжатый блок просматривается до нужной записи; чтение заканчивается вместе с блоком
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        let compressed_len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let block = header.as_slice().chain(reader.take(compressed_len.into()));
+
+        let mut records = zstd_blocks(Box::new(block))?;
+        while let Some(tx) = read_record(&mut records, self.profile, self.version, self.dictionary.as_deref(), &mut buf)? {
+            if tx.tx_id == *tx_id {
+                return Ok(Some(tx));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Смещения записей (для сжатых файлов — блоков) в порядке следования в файле.
+    fn offsets_in_file_order(&self) -> Vec<u64> {
+        let mut offsets: Vec<u64> = self.entries.iter().map(|&(_, offset)| offset).collect();
+        offsets.sort_unstable();
+        offsets
+    }
+}
+
+/// Читает хвост индекса: смещение индекса и число записей в файле. Индекс заканчивается
+/// в конце файла или, если за ним идёт таблица блоков, — перед ней.
+fn read_trailer<R: Read + Seek>(reader: &mut R, flags: u8) -> Result<(u64, usize), ParserErr> {
+    let end = match flags & FLAG_SEEK_TABLE {
+        0 => reader.seek(SeekFrom::End(0))?,
+        _ => seek_table_start(reader)?,
+    };
+    let start = end.checked_sub(TRAILER_LEN).ok_or_else(|| ParserErr::Malformed {
+        msg: "file is too short for the index trailer".to_string(),
+    })?;
+    reader.seek(SeekFrom::Start(start))?;
+    let index_offset = reader.read_u64::<LittleEndian>()?;
+    let count = reader.read_u64::<LittleEndian>()? as usize;
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != INDEX_MAGIC {
+        return Err(ParserErr::InvalidMagic { found: magic });
+    }
+    Ok((index_offset, count))
+}
+
+/// Смещение начала таблицы блоков в конце файла по её хвосту.
+fn seek_table_start<R: Read + Seek>(reader: &mut R) -> Result<u64, ParserErr> {
+    let malformed = || ParserErr::Malformed {
+        msg: "file is too short for the seek table".to_string(),
+    };
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(len.checked_sub(SEEK_FOOTER_LEN).ok_or_else(malformed)?))?;
+    let blocks = u64::from(reader.read_u32::<LittleEndian>()?);
+    let _descriptor = reader.read_u8()?;
+    let magic = reader.read_u32::<LittleEndian>()?;
+    if magic != SEEKABLE_MAGIC {
+        return Err(ParserErr::InvalidMagic { found: magic.to_le_bytes() });
+    }
+    len.checked_sub(8 + blocks * SEEK_ENTRY_LEN + SEEK_FOOTER_LEN).ok_or_else(malformed)
+}
+
+/// Читает таблицу сжатых блоков в конце файла (см. `WriteOptions::bin_seek_table`).
+fn read_seek_table<R: Read + Seek>(reader: &mut R) -> Result<Vec<SeekEntry>, ParserErr> {
+    let start = seek_table_start(reader)?;
+    reader.seek(SeekFrom::Start(start))?;
+    let magic = reader.read_u32::<LittleEndian>()?;
+    if magic != SKIPPABLE_MAGIC {
+        return Err(ParserErr::InvalidMagic { found: magic.to_le_bytes() });
+    }
+    let frame_len = u64::from(reader.read_u32::<LittleEndian>()?);
+    let count = (frame_len.saturating_sub(SEEK_FOOTER_LEN) / SEEK_ENTRY_LEN) as usize;
+    let mut blocks = Vec::with_capacity(count);
+    for _ in 0..count {
+        blocks.push(SeekEntry {
+            block_len: reader.read_u32::<LittleEndian>()?,
+            decompressed_len: reader.read_u32::<LittleEndian>()?,
+            records: reader.read_u32::<LittleEndian>()?,
+        });
+    }
+    Ok(blocks)
+}
+
+/// Читает заголовок, индекс и таблицу блоков непустого бинарного файла, чтобы дописать в него записи.
+///
+/// # Errors
+/// Возвращает [`ParserErr`], если заголовок или хвост файла повреждены, а также
+/// `ParserErr::UnsupportedVersion` для файла с заголовком прежней версии схемы:
+/// новые записи в нём пришлось бы кодировать по-старому.
+pub(crate) fn append_state<R: Read + Seek>(reader: &mut R) -> Result<BinAppend, ParserErr> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    let Some(header) = read_file_header(reader)? else {
+        // Файл без заголовка — только записи, дописываются прямо в конец
+        return Ok(BinAppend { header: None, records_end: len, index: None, blocks: Vec::new() });
+    };
+    if header.version != FILE_VERSION {
+        return Err(ParserErr::UnsupportedVersion {
+            format: crate::model::data::Format::YpBankBin,
+            version: header.version.into(),
+        });
+    }
+
+    // Маркер конца записей перед индексом и таблицей блоков (см. `BinSink::finish`)
+    let marker = match (header.flags & FLAG_ZSTD, header.profile) {
+        (0, BinProfile::BigEndian) => 0,
+        (0, BinProfile::CompactLe) => 1,
+        _ => 4,
+    };
+    let blocks = match header.flags & FLAG_SEEK_TABLE {
+        0 => Vec::new(),
+        _ => read_seek_table(reader)?,
+    };
+    let (records_end, index) = if header.flags & FLAG_INDEX != 0 {
+        let (index_offset, _) = read_trailer(reader, header.flags)?;
+        let index = BinIndex::read_from(reader)?.map(|index| index.entries);
+        (index_offset.checked_sub(marker), index)
+    } else if header.flags & FLAG_SEEK_TABLE != 0 {
+        (seek_table_start(reader)?.checked_sub(marker), None)
+    } else {
+        // Без индекса и таблицы блоков маркер конца не пишется
+        (Some(len), None)
+    };
+    let records_end = records_end.ok_or_else(|| ParserErr::Malformed {
+        msg: "file is too short for the end of records marker".to_string(),
+    })?;
+    Ok(BinAppend { header: Some(header), records_end, index, blocks })
+}
+
+/// Число записей в файле по заголовку и хвосту индекса, не читая сам индекс.
+///
+/// `None`, если файл записан без индекса или его хвост не читается: число служит
+/// только подсказкой для выделения памяти.
+pub(crate) fn record_count<R: Read + Seek>(reader: &mut R) -> Option<usize> {
+    reader.seek(SeekFrom::Start(0)).ok()?;
+    match read_file_header(reader) {
+        Ok(Some(header)) if header.flags & FLAG_INDEX != 0 => {
+            read_trailer(reader, header.flags).ok().map(|(_, count)| count)
+        }
+        Ok(Some(header)) if header.flags & FLAG_SEEK_TABLE != 0 => read_seek_table(reader)
+            .ok()
+            .map(|blocks| blocks.iter().map(|block| block.records as usize).sum()),
+        _ => None,
+    }
+}
+
+/// Читает `count` записей бинарного файла, начиная с записи номер `offset` (с нуля).
+///
+/// Пропускаемые записи не разбираются: для файла с индексом чтение начинается сразу
+/// с нужного смещения, для сжатого файла с таблицей блоков — сразу с нужного блока,
+/// иначе записи (или сжатые блоки целиком) перескакиваются через `Seek`.
+/// Подходит для постраничного просмотра больших архивов.
+///
+/// # Errors
+/// Возвращает [`ParserErr`], если заголовок или прочитанные записи повреждены.
+/// Ошибка записи привязана к её порядковому номеру в файле (с единицы).
+pub fn read_range<R: Read + Seek>(
+    mut reader: R,
+    offset: usize,
+    count: usize,
+) -> Result<Vec<TxData>, ParserErr> {
+    let mut records = Vec::with_capacity(count);
+    if count == 0 {
+        return Ok(records);
+    }
+
+    reader.seek(SeekFrom::Start(0))?;
+    let header = match read_file_header(&mut reader) {
+        Ok(header) => header,
+        Err(e) if is_eof(&e) => return Ok(records),
+        Err(e) => return Err(e),
+    };
+    let (header, records_start) = match header {
+        Some(header) => {
+            let len = header.len();
+            (header, len)
+        }
+        None => {
+            reader.seek(SeekFrom::Start(0))?;
+            let header = FileHeader {
+                version: LEGACY_VERSION,
+                profile: BinProfile::BigEndian,
+                flags: 0,
+                metadata: None,
+                dictionary: None,
+            };
+            (header, 0)
+        }
+    };
+    let FileHeader { version, profile, flags, dictionary, .. } = header;
+    let dictionary = dictionary.as_deref();
+
+    let mut skip = offset;
+    if flags & FLAG_INDEX != 0 && flags & FLAG_ZSTD == 0 {
+        let index = BinIndex::read_from(&mut reader)?;
+        match index.and_then(|index| index.offsets_in_file_order().get(offset).copied()) {
+            Some(start) => {
+                reader.seek(SeekFrom::Start(start))?;
+                skip = 0;
+            }
+            None => return Ok(records),
+        }
+    }
+    if flags & FLAG_ZSTD != 0 && flags & FLAG_SEEK_TABLE != 0 {
+        // Блок с первой нужной записью находится по таблице, предыдущие блоки не читаются
+        let mut start = records_start;
+        let mut before = 0;
+        for block in read_seek_table(&mut reader)? {
+            if before + block.records as usize > offset {
+                break;
+            }
+            before += block.records as usize;
+            start += u64::from(block.block_len);
+        }
+        reader.seek(SeekFrom::Start(start))?;
+        skip = offset - before;
+    }
+
+    let mut buf = Vec::new();
+    let mut position = offset;
+    if flags & FLAG_ZSTD != 0 {
+        // Сжатые блоки, целиком попадающие в пропуск, перескакиваются без распаковки
+        loop {
+            let mut block_header = [0u8; 4];
+            if reader.read_exact(&mut block_header).is_err() {
+                return Ok(records);
+            }
+            let compressed_len = u32::from_le_bytes(block_header);
+            if compressed_len == 0 {
+                return Ok(records);
+            }
+            let in_block = reader.read_u32::<LittleEndian>()? as usize;
+            if skip < in_block {
+                reader.seek(SeekFrom::Current(-8))?;
+                break;
+            }
+            skip -= in_block;
+            reader.seek(SeekFrom::Current(compressed_len.into()))?;
+        }
+
+        let mut stream = zstd_blocks(Box::new(&mut reader))?;
+        for _ in 0..skip {
+            if read_record(&mut stream, profile, version, dictionary, &mut buf)?.is_none() {
+                return Ok(records);
+            }
+        }
+        collect_records(&mut stream, profile, version, dictionary, &mut position, count, &mut records)?;
+    } else {
+        for _ in 0..skip {
+            if !skip_frame(&mut reader, profile)? {
+                return Ok(records);
+            }
+        }
+        collect_records(&mut reader, profile, version, dictionary, &mut position, count, &mut records)?;
+    }
+
+    Ok(records)
+}
+
+/// Дочитывает до `count` записей, привязывая ошибки к порядковому номеру записи.
+fn collect_records(
+    reader: &mut dyn Read,
+    profile: BinProfile,
+    version: u8,
+    dictionary: Option<&Dictionary>,
+    position: &mut usize,
+    count: usize,
+    records: &mut Vec<TxData>,
+) -> Result<(), ParserErr> {
+    let mut buf = Vec::new();
+    while records.len() < count {
+        *position += 1;
+        match read_record(reader, profile, version, dictionary, &mut buf).map_err(|e| e.at_line(*position))? {
+            Some(tx) => records.push(tx),
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+/// Перескакивает через запись без чтения тела; `false` в конце последовательности записей.
+fn skip_frame<R: Read + Seek>(reader: &mut R, profile: BinProfile) -> Result<bool, ParserErr> {
+    let body_len = match profile {
+        BinProfile::BigEndian => {
+            let mut magic = [0u8; 4];
+            if reader.read_exact(&mut magic).is_err() || magic == INDEX_MAGIC {
+                return Ok(false);
+            }
+            if magic != BIN_MAGIC {
+                return Err(ParserErr::InvalidMagic { found: magic });
+            }
+            u64::from(reader.read_u32::<byteorder::BigEndian>()?)
+        }
+        BinProfile::CompactLe => {
+            let Ok(first) = reader.read_u8() else {
+                return Ok(false);
+            };
+            read_varint_from(first, reader)?
+        }
+    };
+    if body_len == 0 {
+        return Ok(false);
+    }
+    reader.seek(SeekFrom::Current(body_len as i64))?;
+    Ok(true)
+}
+
+fn is_eof(err: &ParserErr) -> bool {
+    matches!(err, ParserErr::Io { source, .. } if source.kind() == std::io::ErrorKind::UnexpectedEof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::model::data::{Format, Status, TxType};
+    use crate::parser::concrete::bin_psrser::{TxnFromBin, TxnToBin};
+    use crate::parser::options::{BinCompression, WriteOptions};
+
+    fn txs() -> Vec<TxData> {
+        (0..3000)
+            .map(|id| TxData {
+                tx_id: TxId::U64(10_000 - id),
+                tx_type: TxType::Transfer,
+                from_user_id: id,
+                to_user_id: id + 1,
+                amount: 42,
+                timestamp: 1700000000 + id,
+                status: Status::Success,
+                description: format!("Payment {}", id).into(),
+                format: Format::YpBankBin,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_index_lookup_for_every_layout() {
+        let txs = txs();
+        let compressions: Vec<BinCompression> = [BinCompression::None, BinCompression::Zstd { level: 1 }]
+            .into_iter()
+            .filter(|c| cfg!(feature = "zstd") || *c == BinCompression::None)
+            .collect();
+
+        for bin_profile in [BinProfile::BigEndian, BinProfile::CompactLe] {
+            for &bin_compression in &compressions {
+                let options = WriteOptions {
+                    bin_profile,
+                    bin_compression,
+                    bin_index: true,
+                    ..WriteOptions::default()
+                };
+                let data = TxData::to_bin_many_with(&txs, &options).unwrap();
+
+                // Потоковое чтение останавливается перед индексом
+                let streamed = TxData::from_bin_reader(Box::new(Cursor::new(data.clone()))).unwrap();
+                assert_eq!(streamed, txs);
+
+                let mut reader = Cursor::new(data);
+                let index = BinIndex::read_from(&mut reader).unwrap().unwrap();
+                assert_eq!(index.len(), txs.len());
+                for expected in [&txs[0], &txs[1500], &txs[2999]] {
+                    let found = index.get(&mut reader, &expected.tx_id).unwrap();
+                    assert_eq!(found.as_ref(), Some(expected));
+                }
+                assert!(index.get(&mut reader, &TxId::U64(1)).unwrap().is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_range_pages_for_every_layout() {
+        let txs = txs();
+        let layouts = [
+            (BinProfile::BigEndian, BinCompression::None, false, false),
+            (BinProfile::BigEndian, BinCompression::None, true, false),
+            (BinProfile::CompactLe, BinCompression::None, false, false),
+            (BinProfile::CompactLe, BinCompression::Zstd { level: 1 }, true, false),
+            (BinProfile::BigEndian, BinCompression::Zstd { level: 1 }, false, false),
+            (BinProfile::CompactLe, BinCompression::Zstd { level: 1 }, false, true),
+            (BinProfile::BigEndian, BinCompression::Zstd { level: 1 }, true, true),
+        ];
+
+        for (bin_profile, bin_compression, bin_index, bin_seek_table) in layouts {
+            if bin_compression != BinCompression::None && !cfg!(feature = "zstd") {
+                continue;
+            }
+            let options = WriteOptions {
+                bin_profile,
+                bin_compression,
+                bin_index,
+                bin_seek_table,
+                ..WriteOptions::default()
+            };
+            let data = TxData::to_bin_many_with(&txs, &options).unwrap();
+
+            for (offset, count) in [(0, 10), (1020, 10), (2048, 1000), (2995, 10), (5000, 10)] {
+                let page = read_range(Cursor::new(data.clone()), offset, count).unwrap();
+                let end = (offset + count).min(txs.len());
+                let expected = txs.get(offset..end).unwrap_or(&[]);
+                assert_eq!(page, expected, "{:?} {:?} {}", bin_profile, bin_compression, offset);
+            }
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_seek_table_skips_earlier_blocks() {
+        use crate::parser::concrete::bin_psrser::BinSink;
+        use crate::parser::concrete::RecordSink;
+
+        let txs = txs();
+        let options = WriteOptions {
+            bin_profile: BinProfile::CompactLe,
+            bin_compression: BinCompression::Zstd { level: 1 },
+            bin_index: true,
+            bin_seek_table: true,
+            ..WriteOptions::default()
+        };
+        // Порции не кратны размеру блока, поэтому блоки получаются разной длины
+        let mut sink = BinSink::new(options);
+        let mut data = Vec::new();
+        for batch in txs.chunks(700) {
+            sink.write_batch(&mut data, batch, &[]).unwrap();
+        }
+        sink.finish(&mut data).unwrap();
+
+        assert_eq!(record_count(&mut Cursor::new(&data)), Some(txs.len()));
+        let index = BinIndex::read_from(&mut Cursor::new(&data)).unwrap().unwrap();
+        assert_eq!(index.get(&mut Cursor::new(&data), &txs[2100].tx_id).unwrap().as_ref(), Some(&txs[2100]));
+
+        // Первый блок испорчен: чтение с середины файла его не распаковывает
+        data[8 + 8] ^= 0xff;
+        let page = read_range(Cursor::new(&data), 1500, 20).unwrap();
+        assert_eq!(page, txs[1500..1520]);
+        assert!(read_range(Cursor::new(&data), 0, 20).is_err());
+    }
+
+    #[test]
+    fn test_read_range_reports_absolute_record_number() {
+        let mut data = TxData::to_bin_many(&txs()[..5]).unwrap();
+        let record_len = data.len() / 5;
+        data[3 * record_len + 8 + 8] = 9; // неизвестный TX_TYPE в четвёртой записи
+
+        let err = read_range(Cursor::new(data), 2, 3).unwrap_err();
+
+        assert_eq!(err.line(), Some(4));
+    }
+
+    #[test]
+    fn test_index_absent() {
+        let data = TxData::to_bin_many(&txs()[..2]).unwrap();
+
+        let index = BinIndex::read_from(&mut Cursor::new(data)).unwrap();
+
+        assert!(index.is_none());
+    }
+}
@@ -1,14 +1,64 @@
-use csv::{ReaderBuilder, StringRecord};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::io::Read;
 use std::io::Write;
-use std::io::{Cursor, Read};
+use std::sync::Arc;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::model::data::Format;
 use crate::model::data::Status;
 use crate::model::data::TxData;
+use crate::model::tx_id::TxId;
 use crate::model::data::TxType;
-use crate::model::errors::ParserErr;
+use crate::model::errors::{ParseIssue, ParserErr};
+use crate::model::metadata::ProducerMetadata;
+use crate::parser::concrete::{batch_full, estimated_len, DescriptionArena, RecordSink, RecordSource};
+use crate::parser::options::{
+    BinCompression, BinProfile, ErrorMode, ParseOptions, ParseOutcome, ReadCheckpoint, RecordPos, TextAnnotation, WriteCheckpoint,
+    WriteOptions,
+};
 
-const BIN_MAGIC: [u8; 4] = *b"YPBN";
+/// Магическое число в начале каждой записи профиля `BinProfile::BigEndian`.
+pub(crate) const BIN_MAGIC: [u8; 4] = *b"YPBN";
+/// Магическое число заголовка файла. Файлы без заголовка читаются как `BinProfile::BigEndian`.
+pub(crate) const FILE_MAGIC: [u8; 4] = *b"YPBF";
+/// Версия заголовка файла, которую пишет эта сборка, — текущая версия схемы.
+pub(super) const FILE_VERSION: u8 = crate::migrate::CURRENT_SCHEMA as u8;
+/// Версия схемы файлов без заголовка; в ней TX_ID — всегда u64.
+pub(super) const LEGACY_VERSION: u8 = 1;
+/// Теги вида TX_ID в записях версии схемы 2 и новее (см. [`TxId`]).
+pub(crate) const ID_U64: u8 = 0;
+pub(crate) const ID_UUID: u8 = 1;
+pub(crate) const ID_STRING: u8 = 2;
+/// Флаг заголовка: записи сгруппированы в блоки, сжатые zstd.
+pub(crate) const FLAG_ZSTD: u8 = 0b0000_0001;
+/// Флаг заголовка: после записей идёт индекс `tx_id -> смещение` (см. `BinIndex`).
+pub(crate) const FLAG_INDEX: u8 = 0b0000_0010;
+/// Флаг заголовка: сразу после заголовка идёт словарь частых описаний (см. [`Dictionary`]).
+pub(crate) const FLAG_DICTIONARY: u8 = 0b0000_0100;
+/// Флаг заголовка: файл заканчивается таблицей сжатых блоков (см. [`write_seek_table`]).
+pub(crate) const FLAG_SEEK_TABLE: u8 = 0b0000_1000;
+/// Флаг заголовка: сразу после заголовка идут сведения о записавшей файл программе
+/// (см. [`write_metadata`]).
+pub(crate) const FLAG_METADATA: u8 = 0b0001_0000;
+/// Магическое число пропускаемого кадра zstd, в котором лежит таблица блоков.
+pub(crate) const SKIPPABLE_MAGIC: u32 = 0x184D_2A5E;
+/// Магическое число в конце таблицы блоков, как в zstd seekable format.
+pub(crate) const SEEKABLE_MAGIC: u32 = 0x8F92_EAB1;
+/// Размер строки таблицы блоков: размер блока, размер распакованных данных, число записей (u32 LE).
+pub(super) const SEEK_ENTRY_LEN: u64 = 12;
+/// Размер хвоста таблицы блоков: число блоков (u32), дескриптор (u8), магическое число (u32).
+pub(super) const SEEK_FOOTER_LEN: u64 = 9;
+/// Наибольшее число описаний в словаре файла.
+pub(crate) const DICTIONARY_MAX: usize = 4096;
+/// Магическое число индекса; в профиле `BigEndian` оно же завершает последовательность записей.
+pub(crate) const INDEX_MAGIC: [u8; 4] = *b"YPBI";
+/// Сколько записей попадает в один сжатый блок.
+const RECORDS_PER_BLOCK: usize = 1024;
+/// Наибольший размер сжатого блока и его распакованных записей: длина блока в файле
+/// не заслуживает доверия, и без предела битый файл заставил бы выделить гигабайты.
+pub(super) const MAX_BLOCK_LEN: usize = 256 << 20;
 
 /// Трейт для парсинга транзакций из бинарного представления.
 ///
@@ -32,6 +82,21 @@ pub trait TxnFromBin {
     /// # Errors
     /// Возвращает `ParserErr`, если чтение или парсинг любой из транзакций завершилось неудачей.
     fn from_bin_reader(reader: Box<dyn Read>) -> Result<Vec<TxData>, ParserErr>;
+
+    /// Парсит последовательность транзакций из потока с заданными настройками.
+    ///
+    /// Профиль кодирования определяется по заголовку файла. В режиме `ErrorMode::Collect`
+    /// записи с некорректным содержимым пропускаются и попадают в `ParseOutcome::issues`.
+    /// Если нарушена сама структура потока (неверное магическое число, обрезанная запись),
+    /// продолжить чтение нельзя: проблема добавляется в отчёт, а разбор завершается.
+    ///
+    /// # Errors
+    /// В режиме `FailFast` возвращает `ParserErr` для первой некорректной записи.
+    /// Неизвестная версия или профиль в заголовке — ошибка в любом режиме.
+    fn from_bin_reader_with(
+        reader: Box<dyn Read>,
+        options: &ParseOptions,
+    ) -> Result<ParseOutcome, ParserErr>;
 }
 
 /// Трейт для сериализации транзакций в бинарное представление.
@@ -53,189 +118,1291 @@ pub trait TxnToBin {
     fn to_bin_many(many: &[Self]) -> Result<Vec<u8>, ParserErr>
     where
         Self: Sized;
+
+    /// Сериализует множество транзакций в файл с заданными настройками.
+    ///
+    /// Для `BinProfile::BigEndian` результат совпадает с [`TxnToBin::to_bin_many`]; остальные
+    /// профили записываются с заголовком файла, по которому их узнаёт читатель.
+    ///
+    /// # Errors
+    /// Возвращает `ParserErr`, если сериализация хотя бы одной транзакции завершилась неудачей.
+    fn to_bin_many_with(many: &[Self], options: &WriteOptions) -> Result<Vec<u8>, ParserErr>
+    where
+        Self: Sized;
+}
+
+/// Трейт для потоковой записи транзакций в бинарном формате прямо в `Write`,
+/// без промежуточного буфера на весь файл.
+pub trait TxnWriteToBin {
+    /// Записывает одну транзакцию в `w` так же, как её сериализует [`TxnToBin::to_bin`].
+    ///
+    /// # Errors
+    /// Возвращает `ParserErr` при ошибке записи.
+    fn to_bin_writer(&self, w: &mut dyn Write) -> Result<(), ParserErr>;
+
+    /// Записывает транзакции в `w` так же, как [`TxnToBin::to_bin_many_with`].
+    ///
+    /// В памяти держится не больше одного блока записей (для сжатия) и индекс.
+    ///
+    /// # Errors
+    /// Возвращает `ParserErr` при ошибке сериализации или записи.
+    fn to_bin_writer_many(
+        many: &[Self],
+        options: &WriteOptions,
+        w: &mut dyn Write,
+    ) -> Result<(), ParserErr>
+    where
+        Self: Sized;
 }
 
 impl TxnFromBin for TxData {
     fn from_bin(body: &[u8]) -> Result<Self, ParserErr> {
-        use byteorder::{BigEndian, ReadBytesExt};
-        let mut cursor = std::io::Cursor::new(body);
-
-        let tx_id = cursor
-            .read_u64::<BigEndian>()
-            .map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?;
-        let tx_type = match cursor
-            .read_u8()
-            .map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?
-        {
-            0 => TxType::Deposit,
-            1 => TxType::Transfer,
-            2 => TxType::Withdrawal,
-            v => {
-                return Err(ParserErr::ParseErr {
-                    msg: format!("Invalid TX_TYPE: {}", v),
-                });
+        decode(body, BinProfile::BigEndian, LEGACY_VERSION, None)
+    }
+
+    fn from_bin_reader(reader: Box<dyn Read>) -> Result<Vec<Self>, ParserErr> {
+        Self::from_bin_reader_with(reader, &ParseOptions::default()).map(|outcome| outcome.records)
+    }
+
+    fn from_bin_reader_with(
+        reader: Box<dyn Read>,
+        options: &ParseOptions,
+    ) -> Result<ParseOutcome, ParserErr> {
+        let outcome = BinSource::new(reader, options.clone())?.next_batch(usize::MAX)?;
+        Ok(outcome.unwrap_or_default())
+    }
+}
+
+/// Потоковый разбор бинарного формата: заголовок читается при создании, записи — порциями.
+pub(crate) struct BinSource {
+    reader: Box<dyn Read>,
+    profile: BinProfile,
+    /// Версия схемы: от неё зависит кодирование TX_ID
+    version: u8,
+    /// Словарь описаний файла, если он записан со словарём
+    dictionary: Option<Arc<Dictionary>>,
+    /// Магическое число первой записи файла без заголовка, уже прочитанное при создании
+    pending_magic: Option<[u8; 4]>,
+    /// Порядковый номер следующей записи
+    index: usize,
+    /// Смещение следующей записи в файле; `None` для сжатых блоков, где смещения в файле нет
+    offset: Option<u64>,
+    buf: Vec<u8>,
+    done: bool,
+    options: ParseOptions,
+    descriptions: DescriptionArena,
+    /// Сколько записей ещё ожидается по индексу файла
+    expected: Option<usize>,
+}
+
+impl BinSource {
+    pub(crate) fn new(mut reader: Box<dyn Read>, options: ParseOptions) -> Result<Self, ParserErr> {
+        let mut first = [0u8; 4];
+        let done = reader.read_exact(&mut first).is_err();
+        // Без заголовка первые четыре байта — магическое число первой записи
+        let (profile, version, dictionary, pending_magic, offset) = if done {
+            (BinProfile::BigEndian, LEGACY_VERSION, None, None, Some(0))
+        } else if first == FILE_MAGIC {
+            let header = read_header(&mut reader)?;
+            let compressed = header.flags & FLAG_ZSTD != 0;
+            if compressed {
+                reader = zstd_blocks(reader)?;
             }
+            let offset = (!compressed).then_some(header.len());
+            (header.profile, header.version, header.dictionary, None, offset)
+        } else {
+            (BinProfile::BigEndian, LEGACY_VERSION, None, Some(first), Some(0))
         };
-        let from_user_id = cursor
-            .read_u64::<BigEndian>()
-            .map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?;
-        let to_user_id = cursor
-            .read_u64::<BigEndian>()
-            .map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?;
-        let amount = cursor
-            .read_i64::<BigEndian>()
-            .map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?;
-        let timestamp = cursor
-            .read_u64::<BigEndian>()
-            .map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?;
-        let status = match cursor
-            .read_u8()
-            .map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?
-        {
-            0 => Status::Success,
-            1 => Status::Failure,
-            2 => Status::Pending,
-            v => {
-                return Err(ParserErr::ParseErr {
-                    msg: format!("Invalid STATUS: {}", v),
-                });
+
+        Ok(BinSource {
+            reader,
+            profile,
+            version,
+            dictionary,
+            pending_magic,
+            index: 1,
+            offset,
+            buf: Vec::new(),
+            done,
+            options,
+            descriptions: DescriptionArena::default(),
+            expected: None,
+        })
+    }
+}
+
+impl RecordSource for BinSource {
+    fn next_batch(&mut self, max: usize) -> Result<Option<ParseOutcome>, ParserErr> {
+        if self.done {
+            return Ok(None);
+        }
+        let options = &self.options;
+        let mut outcome = ParseOutcome::default();
+        if let Some(expected) = self.expected {
+            outcome.records.reserve_exact(expected.min(max));
+        }
+        let first = self.index;
+        while !batch_full(&outcome, max) {
+            let index = self.index;
+            self.index += 1;
+            let frame = match self.profile {
+                BinProfile::BigEndian => {
+                    read_frame(&mut self.reader, self.pending_magic.take(), &mut self.buf)
+                }
+                BinProfile::CompactLe => read_compact_frame(&mut self.reader, &mut self.buf),
+            };
+
+            match frame {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.done = true;
+                    break;
+                }
+                Err(e) => {
+                    // После битой рамки граница следующей записи неизвестна — дальше не читаем
+                    self.done = true;
+                    let e = e.at_line(index);
+                    if options.error_mode == ErrorMode::Collect {
+                        outcome.issues.push(ParseIssue::from(e));
+                        break;
+                    }
+                    return Err(e);
+                }
+            }
+
+            let offset = self.offset;
+            if let Some(next) = self.offset.as_mut() {
+                *next += frame_len(self.profile, self.buf.len()) as u64;
             }
+            match decode(&self.buf, self.profile, self.version, self.dictionary.as_deref()) {
+                Ok(tx) => {
+                    let pos = RecordPos { record: index, line: index, offset };
+                    options.accept(tx, &mut self.descriptions, pos, Vec::new(), &mut outcome)?
+                },
+                Err(e) => options.recover(e.at_line(index), &mut outcome.issues)?,
+            }
+        }
+
+        if let Some(expected) = self.expected.as_mut() {
+            *expected = expected.saturating_sub(self.index - first);
+        }
+        Ok(Some(outcome))
+    }
+
+    fn expect_records(&mut self, count: usize) {
+        self.expected = Some(count);
+    }
+
+    fn checkpoint(&self) -> Option<ReadCheckpoint> {
+        let offset = self.offset.filter(|_| !self.done)?;
+        let records = self.index - 1;
+        Some(ReadCheckpoint { offset, lines: records, records, ..ReadCheckpoint::default() })
+    }
+
+    fn resume(&mut self, reader: Box<dyn Read>, at: &ReadCheckpoint) -> bool {
+        // Заголовок и словарь уже прочитаны из начала файла, записи идут с сохранённого смещения
+        if self.offset.is_none() || self.done {
+            return false;
+        }
+        self.reader = reader;
+        self.pending_magic = None;
+        self.index = at.records + 1;
+        self.offset = Some(at.offset);
+        true
+    }
+}
+
+/// Заголовок файла: `YPBF`, версия (u8), профиль (u8), флаги (u8), резерв (u8);
+/// с флагом [`FLAG_METADATA`] за ним следуют сведения о программе, с флагом
+/// [`FLAG_DICTIONARY`] — словарь описаний.
+pub(super) struct FileHeader {
+    pub(super) version: u8,
+    pub(super) profile: BinProfile,
+    pub(super) flags: u8,
+    pub(super) metadata: Option<ProducerMetadata>,
+    pub(super) dictionary: Option<Arc<Dictionary>>,
+}
+
+impl FileHeader {
+    fn to_bytes(&self) -> Vec<u8> {
+        let profile = match self.profile {
+            BinProfile::BigEndian => 0,
+            BinProfile::CompactLe => 1,
         };
-        let desc_len = cursor
-            .read_u32::<BigEndian>()
-            .map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?
-            as usize;
-        if cursor.position() as usize + desc_len > body.len() {
-            return Err(ParserErr::ParseErr {
-                msg: "DESCRIPTION length exceeds body".into(),
+        let mut bytes = FILE_MAGIC.to_vec();
+        bytes.extend_from_slice(&[self.version, profile, self.flags, 0]);
+        if let Some(metadata) = &self.metadata {
+            write_metadata(&mut bytes, metadata);
+        }
+        if let Some(dictionary) = &self.dictionary {
+            dictionary.write_to(&mut bytes);
+        }
+        bytes
+    }
+
+    /// Сколько байт занимает заголовок вместе со словарём: с этого смещения начинаются записи.
+    pub(super) fn len(&self) -> u64 {
+        let metadata = self.metadata.as_ref().map_or(0, |metadata| {
+            let mut bytes = Vec::new();
+            write_metadata(&mut bytes, metadata);
+            bytes.len() as u64
+        });
+        FILE_HEADER_LEN + metadata + self.dictionary.as_ref().map_or(0, |dictionary| dictionary.encoded_len)
+    }
+}
+
+/// Сведения о программе после заголовка: число пар (varint), затем ключ и значение
+/// каждой пары — длина (varint) и UTF-8.
+fn write_metadata(out: &mut Vec<u8>, metadata: &ProducerMetadata) {
+    let pairs = metadata.pairs();
+    write_varint(out, pairs.len() as u64);
+    for (key, value) in &pairs {
+        for text in [*key, value.as_str()] {
+            write_varint(out, text.len() as u64);
+            out.extend_from_slice(text.as_bytes());
+        }
+    }
+}
+
+/// Читает сведения, записанные [`write_metadata`]; `None`, если в них нет обязательных пар.
+fn read_metadata(reader: &mut dyn Read) -> Result<Option<ProducerMetadata>, ParserErr> {
+    let count = read_varint(reader)?;
+    let mut pairs = Vec::new();
+    for _ in 0..count {
+        let key = read_string(reader, "METADATA")?;
+        let value = read_string(reader, "METADATA")?;
+        pairs.push((key, value));
+    }
+    Ok(ProducerMetadata::from_pairs(pairs.iter().map(|(key, value)| (key.as_str(), value.as_str()))))
+}
+
+/// Читает строку: длина (varint) и UTF-8.
+fn read_string(reader: &mut dyn Read, field: &'static str) -> Result<String, ParserErr> {
+    let len = read_varint(reader)?;
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+    }
+    String::from_utf8(bytes).map_err(|source| ParserErr::InvalidUtf8 { field, source })
+}
+
+/// Словарь частых описаний файла: каждое описание записано один раз после заголовка,
+/// а записи ссылаются на него по номеру.
+///
+/// Словарь: число описаний (varint), затем каждое описание — длина (varint) и UTF-8.
+/// В записях файла со словарём поле длины описания хранит `длина << 1` для описания,
+/// записанного в самой записи, или `номер << 1 | 1` для описания из словаря.
+#[derive(Debug, Default)]
+pub(super) struct Dictionary {
+    entries: Vec<Arc<str>>,
+    numbers: HashMap<Arc<str>, u64>,
+    /// Сколько байт словарь занимает в файле
+    encoded_len: u64,
+}
+
+impl Dictionary {
+    /// Словарь из не больше [`DICTIONARY_MAX`] самых частых описаний `records`, встретившихся
+    /// хотя бы дважды, — описанию из одной записи ссылка ничего не экономит. Частые идут первыми,
+    /// чтобы номер ссылки занимал меньше байт.
+    fn build(records: &[TxData]) -> Dictionary {
+        let mut counts: HashMap<&Arc<str>, usize> = HashMap::new();
+        for tx in records.iter().filter(|tx| !tx.description.is_empty()) {
+            *counts.entry(&tx.description).or_default() += 1;
+        }
+        let mut frequent: Vec<(&Arc<str>, usize)> = counts.into_iter().filter(|&(_, count)| count > 1).collect();
+        frequent.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+        Dictionary::new(frequent.into_iter().take(DICTIONARY_MAX).map(|(description, _)| Arc::clone(description)).collect())
+    }
+
+    fn new(entries: Vec<Arc<str>>) -> Dictionary {
+        let mut encoded = Vec::new();
+        write_varint(&mut encoded, entries.len() as u64);
+        let mut numbers = HashMap::with_capacity(entries.len());
+        for (number, entry) in entries.iter().enumerate() {
+            write_varint(&mut encoded, entry.len() as u64);
+            numbers.insert(Arc::clone(entry), number as u64);
+        }
+        let encoded_len = (encoded.len() + entries.iter().map(|entry| entry.len()).sum::<usize>()) as u64;
+        Dictionary { entries, numbers, encoded_len }
+    }
+
+    /// Читает словарь, записанный после заголовка файла.
+    fn read_from(reader: &mut dyn Read) -> Result<Dictionary, ParserErr> {
+        let count = read_varint(reader)?;
+        if count > DICTIONARY_MAX as u64 {
+            return Err(ParserErr::InvalidValue {
+                field: "DICTIONARY",
+                value: count.to_string(),
             });
         }
-        let mut desc_bytes = vec![0u8; desc_len];
-        cursor
-            .read_exact(&mut desc_bytes)
-            .map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?;
-        let description = String::from_utf8(desc_bytes).map_err(|e| ParserErr::ParseErr {
-            msg: format!("Invalid UTF-8 in DESCRIPTION: {}", e),
-        })?;
+        let mut entries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            entries.push(read_string(reader, "DICTIONARY")?.into());
+        }
+        Ok(Dictionary::new(entries))
+    }
+
+    fn write_to(&self, out: &mut Vec<u8>) {
+        write_varint(out, self.entries.len() as u64);
+        for entry in &self.entries {
+            write_varint(out, entry.len() as u64);
+            out.extend_from_slice(entry.as_bytes());
+        }
+    }
+
+    /// Поле длины описания в записи: ссылка на словарь или длина описания в самой записи.
+    fn description_field(&self, description: &str) -> u64 {
+        match self.numbers.get(description) {
+            Some(number) => number << 1 | 1,
+            None => (description.len() as u64) << 1,
+        }
+    }
+
+    /// Описание по полю длины: `Ok(описание)` для ссылки на словарь, `Err(длина)` — описание в записи.
+    fn resolve(&self, field: u64) -> Result<Result<Arc<str>, usize>, ParserErr> {
+        if field & 1 == 0 {
+            return Ok(Err((field >> 1) as usize));
+        }
+        match self.entries.get((field >> 1) as usize) {
+            Some(entry) => Ok(Ok(Arc::clone(entry))),
+            None => Err(ParserErr::InvalidValue {
+                field: "DESCRIPTION",
+                value: format!("dictionary entry {}", field >> 1),
+            }),
+        }
+    }
+}
+
+/// Читает заголовок файла целиком; `None`, если файл записан без заголовка.
+pub(super) fn read_file_header(reader: &mut dyn Read) -> Result<Option<FileHeader>, ParserErr> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != FILE_MAGIC {
+        return Ok(None);
+    }
+    read_header(reader).map(Some)
+}
+
+/// Сведения о программе из заголовка файла; `None` для файла без заголовка или без сведений.
+pub(crate) fn read_file_metadata(mut reader: impl Read) -> Result<Option<ProducerMetadata>, ParserErr> {
+    let mut magic = [0u8; 4];
+    if reader.read_exact(&mut magic).is_err() || magic != FILE_MAGIC {
+        return Ok(None);
+    }
+    Ok(read_header(&mut reader)?.metadata)
+}
 
-        Ok(TxData {
-            tx_id: tx_id,
-            tx_type: tx_type,
-            from_user_id: from_user_id,
-            to_user_id: to_user_id,
-            amount: amount,
-            timestamp: timestamp,
-            status: status,
-            description: description,
+/// Длина заголовка файла: магическое число, версия, профиль, флаги, резерв.
+const FILE_HEADER_LEN: u64 = 8;
+
+/// Сколько байт занимает в файле запись с телом длиной `body`.
+fn frame_len(profile: BinProfile, body: usize) -> usize {
+    match profile {
+        BinProfile::BigEndian => BIN_MAGIC.len() + 4 + body,
+        BinProfile::CompactLe => (usize::BITS - body.leading_zeros()).div_ceil(7).max(1) as usize + body,
+    }
+}
+
+/// Читает остаток заголовка файла после магического числа.
+fn read_header(reader: &mut dyn Read) -> Result<FileHeader, ParserErr> {
+    let mut header = [0u8; 4];
+    reader.read_exact(&mut header)?;
+    let [version, profile, flags, _reserved] = header;
+
+    // Файлы прежних версий читаются: они отличаются только кодированием TX_ID
+    if !(LEGACY_VERSION..=FILE_VERSION).contains(&version) {
+        return Err(ParserErr::UnsupportedVersion {
             format: Format::YpBankBin,
-        })
+            version: version.into(),
+        });
+    }
+
+    let profile = match profile {
+        0 => BinProfile::BigEndian,
+        1 => BinProfile::CompactLe,
+        v => {
+            return Err(ParserErr::InvalidValue {
+                field: "PROFILE",
+                value: v.to_string(),
+            })
+        }
+    };
+
+    if flags & !(FLAG_ZSTD | FLAG_INDEX | FLAG_DICTIONARY | FLAG_SEEK_TABLE | FLAG_METADATA) != 0 {
+        return Err(ParserErr::InvalidValue {
+            field: "FLAGS",
+            value: format!("{:#010b}", flags),
+        });
     }
 
-    fn from_bin_reader(mut reader: Box<dyn Read>) -> Result<Vec<Self>, ParserErr> {
-        let mut transactions = Vec::new();
-        let mut buf = Vec::new();
+    let metadata = if flags & FLAG_METADATA != 0 { read_metadata(reader)? } else { None };
+    let dictionary = if flags & FLAG_DICTIONARY != 0 {
+        Some(Arc::new(Dictionary::read_from(reader)?))
+    } else {
+        None
+    };
+    Ok(FileHeader { version, profile, flags, metadata, dictionary })
+}
+
+/// Версия схемы бинарного потока по его началу: байт версии заголовка `YPBF`.
+///
+/// Файлы без заголовка записаны версией схемы 1.
+pub(crate) fn schema_version(prefix: &[u8]) -> u32 {
+    prefix
+        .strip_prefix(&FILE_MAGIC)
+        .and_then(|header| header.first())
+        .map_or(1, |&version| version.into())
+}
+
+/// Оборачивает поток сжатых блоков в поток расжатых записей.
+#[cfg(feature = "zstd")]
+pub(super) fn zstd_blocks<'a>(reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, ParserErr> {
+    Ok(Box::new(ZstdBlocks {
+        inner: reader,
+        block: Vec::new(),
+        pos: 0,
+    }))
+}
+
+#[cfg(not(feature = "zstd"))]
+pub(super) fn zstd_blocks<'a>(_reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, ParserErr> {
+    Err(ParserErr::FeatureDisabled { feature: "zstd" })
+}
+
+/// Поток записей из последовательности блоков: длина сжатых данных (u32 LE),
+/// число записей в блоке (u32 LE) и кадр zstd.
+///
+/// Блок нулевой длины (без числа записей) завершает последовательность.
+#[cfg(feature = "zstd")]
+struct ZstdBlocks<'a> {
+    inner: Box<dyn Read + 'a>,
+    block: Vec<u8>,
+    pos: usize,
+}
+
+#[cfg(feature = "zstd")]
+impl Read for ZstdBlocks<'_> {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.block.len() {
+            let compressed_len = match self.inner.read_u32::<LittleEndian>() {
+                Ok(0) => return Ok(0),
+                Ok(len) => len as usize,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(0),
+                Err(e) => return Err(e),
+            };
+            let _records = self.inner.read_u32::<LittleEndian>()?;
+            if compressed_len > MAX_BLOCK_LEN {
+                return Err(invalid_data(block_too_large("compressed", compressed_len)));
+            }
+            // Буфер растёт по мере чтения: длина за пределами файла не выделяет память заранее
+            let mut compressed = Vec::new();
+            (&mut self.inner).take(compressed_len as u64).read_to_end(&mut compressed)?;
+            if compressed.len() < compressed_len {
+                let available = compressed.len();
+                return Err(invalid_data(ParserErr::LengthExceedsBody { field: "zstd block", len: compressed_len, available }));
+            }
+            self.block.clear();
+            zstd::stream::read::Decoder::with_buffer(compressed.as_slice())?
+                .take(MAX_BLOCK_LEN as u64 + 1)
+                .read_to_end(&mut self.block)?;
+            if self.block.len() > MAX_BLOCK_LEN {
+                return Err(invalid_data(block_too_large("decompressed", self.block.len())));
+            }
+            self.pos = 0;
+        }
 
-        loop {
+        let n = out.len().min(self.block.len() - self.pos);
+        out[..n].copy_from_slice(&self.block[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Ошибка блока больше [`MAX_BLOCK_LEN`]; `what` — сжатый он или распакованный.
+fn block_too_large(what: &'static str, len: usize) -> ParserErr {
+    ParserErr::BlockTooLarge { what, len: len as u64, limit: MAX_BLOCK_LEN as u64 }
+}
+
+/// Ошибка разбора для `Read`: `From<io::Error> for ParserErr` вернёт её без обёртки.
+#[cfg(feature = "zstd")]
+fn invalid_data(err: ParserErr) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, err)
+}
+
+/// Читает запись профиля `BigEndian` в `buf`: магическое число, длина (u32 BE), тело.
+///
+/// `magic` — уже прочитанное магическое число. Возвращает `false`, если поток закончился
+/// ровно на границе записи или дошёл до индекса.
+pub(super) fn read_frame(
+    reader: &mut dyn Read,
+    magic: Option<[u8; 4]>,
+    buf: &mut Vec<u8>,
+) -> Result<bool, ParserErr> {
+    let magic = match magic {
+        Some(magic) => magic,
+        None => {
             let mut magic = [0u8; 4];
-            if reader.read_exact(&mut magic).is_err() {
-                break;
+            match reader.read_exact(&mut magic) {
+                Ok(()) => magic,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(false),
+                Err(e) => return Err(e.into()),
             }
-            if magic != BIN_MAGIC {
-                return Err(ParserErr::ParseErr {
-                    msg: "Invalid MAGIC number".into(),
+        }
+    };
+    if magic == INDEX_MAGIC {
+        return Ok(false);
+    }
+    if magic != BIN_MAGIC {
+        return Err(ParserErr::InvalidMagic { found: magic });
+    }
+
+    let record_size = reader.read_u32::<BigEndian>()? as usize;
+    buf.resize(record_size, 0);
+    reader.read_exact(buf)?;
+    Ok(true)
+}
+
+/// Читает запись профиля `CompactLe` в `buf`: длина (varint), тело.
+///
+/// Возвращает `false`, если поток закончился ровно на границе записи или встретилась
+/// запись нулевой длины, которая отделяет записи от индекса.
+pub(super) fn read_compact_frame(reader: &mut dyn Read, buf: &mut Vec<u8>) -> Result<bool, ParserErr> {
+    let first = match reader.read_u8() {
+        Ok(byte) => byte,
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+
+    let record_size = read_varint_from(first, reader)? as usize;
+    if record_size == 0 {
+        return Ok(false);
+    }
+    buf.resize(record_size, 0);
+    reader.read_exact(buf)?;
+    Ok(true)
+}
+
+/// Дочитывает беззнаковый LEB128 varint, первый байт которого уже прочитан.
+pub(super) fn read_varint_from(first: u8, reader: &mut dyn Read) -> Result<u64, ParserErr> {
+    let mut value = u64::from(first & 0x7f);
+    let mut byte = first;
+    let mut shift = 7;
+    while byte & 0x80 != 0 {
+        if shift >= 64 {
+            return Err(ParserErr::Malformed {
+                msg: "varint is longer than 10 bytes".to_string(),
+            });
+        }
+        byte = reader.read_u8()?;
+        value |= u64::from(byte & 0x7f) << shift;
+        shift += 7;
+    }
+    Ok(value)
+}
+
+pub(super) fn read_varint(reader: &mut dyn Read) -> Result<u64, ParserErr> {
+    let first = reader.read_u8()?;
+    read_varint_from(first, reader)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Читает следующую запись в заданном профиле и версии схемы; `None` в конце последовательности записей.
+pub(super) fn read_record(
+    reader: &mut dyn Read,
+    profile: BinProfile,
+    version: u8,
+    dictionary: Option<&Dictionary>,
+    buf: &mut Vec<u8>,
+) -> Result<Option<TxData>, ParserErr> {
+    let found = match profile {
+        BinProfile::BigEndian => read_frame(reader, None, buf)?,
+        BinProfile::CompactLe => read_compact_frame(reader, buf)?,
+    };
+    if !found {
+        return Ok(None);
+    }
+    decode(buf, profile, version, dictionary).map(Some)
+}
+
+/// Разбирает тело записи в заданном профиле и версии схемы; `dictionary` — словарь описаний файла.
+pub(super) fn decode(
+    body: &[u8],
+    profile: BinProfile,
+    version: u8,
+    dictionary: Option<&Dictionary>,
+) -> Result<TxData, ParserErr> {
+    match profile {
+        BinProfile::BigEndian => match dictionary.is_none().then(|| decode_fixed_be(body, version)).flatten() {
+            Some(tx) => Ok(tx),
+            None => decode_with::<BigEndian>(body, version, dictionary, |cursor| {
+                Ok(cursor.read_u32::<BigEndian>()?.into())
+            }),
+        },
+        BinProfile::CompactLe => decode_with::<LittleEndian>(body, version, dictionary, |cursor| read_varint(cursor)),
+    }
+}
+
+/// Читает TX_ID: в версии схемы 1 — u64, в следующих — тег вида и значение.
+/// Длина строкового идентификатора читается `read_len`.
+pub(super) fn read_tx_id<B: ByteOrder, R: Read>(
+    reader: &mut R,
+    version: u8,
+    read_len: impl Fn(&mut R) -> Result<u64, ParserErr>,
+) -> Result<TxId, ParserErr> {
+    if version == LEGACY_VERSION {
+        return Ok(TxId::U64(reader.read_u64::<B>()?));
+    }
+    match reader.read_u8()? {
+        ID_U64 => Ok(TxId::U64(reader.read_u64::<B>()?)),
+        ID_UUID => Ok(TxId::Uuid(reader.read_u128::<B>()?)),
+        ID_STRING => {
+            let len = read_len(reader)? as usize;
+            let mut bytes = Vec::new();
+            reader.take(len as u64).read_to_end(&mut bytes)?;
+            if bytes.len() < len {
+                return Err(ParserErr::LengthExceedsBody {
+                    field: "TX_ID",
+                    len,
+                    available: bytes.len(),
                 });
             }
+            let id = String::from_utf8(bytes).map_err(|source| ParserErr::InvalidUtf8 {
+                field: "TX_ID",
+                source,
+            })?;
+            Ok(TxId::String(id))
+        }
+        v => Err(ParserErr::InvalidValue {
+            field: "TX_ID",
+            value: format!("kind {}", v),
+        }),
+    }
+}
 
-            let record_size = {
-                let mut size_bytes = [0u8; 4];
-                reader
-                    .read_exact(&mut size_bytes)
-                    .map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?;
-                u32::from_be_bytes(size_bytes) as usize
-            };
+/// Пишет TX_ID так, как его читает [`read_tx_id`]; длина строкового идентификатора пишется `write_len`.
+///
+/// # Errors
+/// `ParserErr::IdNeedsSchema`, если нечисловой идентификатор пишется в версии схемы 1.
+pub(super) fn write_tx_id<B: ByteOrder>(
+    out: &mut Vec<u8>,
+    tx_id: &TxId,
+    version: u8,
+    write_len: impl Fn(&mut Vec<u8>, usize) -> Result<(), ParserErr>,
+) -> Result<(), ParserErr> {
+    if version == LEGACY_VERSION {
+        let id = tx_id.as_u64().ok_or_else(|| ParserErr::IdNeedsSchema { tx_id: tx_id.clone() })?;
+        return Ok(out.write_u64::<B>(id)?);
+    }
+    match tx_id {
+        TxId::U64(id) => {
+            out.write_u8(ID_U64)?;
+            out.write_u64::<B>(*id)?;
+        }
+        TxId::Uuid(id) => {
+            out.write_u8(ID_UUID)?;
+            out.write_u128::<B>(*id)?;
+        }
+        TxId::String(id) => {
+            out.write_u8(ID_STRING)?;
+            write_len(out, id.len())?;
+            out.write_all(id.as_bytes())?;
+        }
+    }
+    Ok(())
+}
 
-            buf.resize(record_size, 0);
-            reader
-                .read_exact(&mut buf)
-                .map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?;
+/// Поля тела `BigEndian` после TX_ID: тип, отправитель, получатель, сумма, время, статус, длина описания.
+const FIXED_FIELDS_LEN: usize = 1 + 8 + 8 + 8 + 8 + 1 + 4;
+
+/// Размер записи `BigEndian` с числовым TX_ID без описания: рамка, вид и значение TX_ID, поля.
+const FRAME_LEN: usize = 8 + 1 + 8 + FIXED_FIELDS_LEN;
+
+/// Быстрый разбор тела записи профиля `BigEndian` с числовым TX_ID: поля фиксированной
+/// ширины читаются прямо из среза, без `Cursor` и вызова `byteorder` на каждое поле.
+///
+/// Возвращает `None` для всего, что требует общего разбора (UUID и строковые TX_ID,
+/// неизвестные значения, нехватка байт, неверный UTF-8); такую запись разбирает
+/// [`decode_with`] и сообщает ту же ошибку, что и без быстрого пути.
+fn decode_fixed_be(body: &[u8], version: u8) -> Option<TxData> {
+    let rest = if version == LEGACY_VERSION { body } else { body.strip_prefix(&[ID_U64])? };
+    let (id, rest) = rest.split_first_chunk::<8>()?;
+    let (fields, rest) = rest.split_first_chunk::<FIXED_FIELDS_LEN>()?;
+    let u64_at = |at: usize| u64::from_be_bytes(fields[at..at + 8].try_into().expect("8 bytes"));
+
+    let tx_type = match fields[0] {
+        0 => TxType::Deposit,
+        1 => TxType::Transfer,
+        2 => TxType::Withdrawal,
+        _ => return None,
+    };
+    let status = match fields[33] {
+        0 => Status::Success,
+        1 => Status::Failure,
+        2 => Status::Pending,
+        _ => return None,
+    };
+    let desc_len = u32::from_be_bytes(fields[34..38].try_into().expect("4 bytes")) as usize;
+    let description = std::str::from_utf8(rest.get(..desc_len)?).ok()?;
+
+    Some(TxData {
+        tx_id: TxId::U64(u64::from_be_bytes(*id)),
+        tx_type,
+        from_user_id: u64_at(1),
+        to_user_id: u64_at(9),
+        amount: u64_at(17) as i64,
+        timestamp: u64_at(25),
+        status,
+        description: description.into(),
+        format: Format::YpBankBin,
+    })
+}
 
-            let tx = Self::from_bin(&buf)?;
-            transactions.push(tx);
+/// Разбирает тело записи: целые в порядке байт `B`, длины строк читаются `read_len`.
+fn decode_with<B: ByteOrder>(
+    body: &[u8],
+    version: u8,
+    dictionary: Option<&Dictionary>,
+    read_len: impl Fn(&mut std::io::Cursor<&[u8]>) -> Result<u64, ParserErr>,
+) -> Result<TxData, ParserErr> {
+    let mut cursor = std::io::Cursor::new(body);
+
+    let tx_id = read_tx_id::<B, _>(&mut cursor, version, &read_len)?;
+    let tx_type = match cursor.read_u8()? {
+        0 => TxType::Deposit,
+        1 => TxType::Transfer,
+        2 => TxType::Withdrawal,
+        v => {
+            return Err(ParserErr::InvalidValue {
+                field: "TX_TYPE",
+                value: v.to_string(),
+            });
+        }
+    };
+    let from_user_id = cursor.read_u64::<B>()?;
+    let to_user_id = cursor.read_u64::<B>()?;
+    let amount = cursor.read_i64::<B>()?;
+    let timestamp = cursor.read_u64::<B>()?;
+    let status = match cursor.read_u8()? {
+        0 => Status::Success,
+        1 => Status::Failure,
+        2 => Status::Pending,
+        v => {
+            return Err(ParserErr::InvalidValue {
+                field: "STATUS",
+                value: v.to_string(),
+            });
         }
+    };
+    let field = read_len(&mut cursor)?;
+    let description = match dictionary.map(|dictionary| dictionary.resolve(field)).transpose()? {
+        Some(Ok(description)) => description,
+        Some(Err(len)) => read_description(&mut cursor, len)?,
+        None => read_description(&mut cursor, field as usize)?,
+    };
 
-        Ok(transactions)
+    Ok(TxData {
+        tx_id,
+        tx_type,
+        from_user_id,
+        to_user_id,
+        amount,
+        timestamp,
+        status,
+        description,
+        format: Format::YpBankBin,
+    })
+}
+
+/// Читает описание длиной `desc_len` из оставшейся части тела записи.
+fn read_description(cursor: &mut std::io::Cursor<&[u8]>, desc_len: usize) -> Result<Arc<str>, ParserErr> {
+    let available = cursor.get_ref().len() - cursor.position() as usize;
+    if desc_len > available {
+        return Err(ParserErr::LengthExceedsBody {
+            field: "DESCRIPTION",
+            len: desc_len,
+            available,
+        });
     }
+    let mut desc_bytes = vec![0u8; desc_len];
+    cursor.read_exact(&mut desc_bytes)?;
+    let description =
+        String::from_utf8(desc_bytes).map_err(|source| ParserErr::InvalidUtf8 {
+            field: "DESCRIPTION",
+            source,
+        })?;
+    Ok(description.into())
 }
 
-impl TxnToBin for TxData {
-    fn to_bin(&self) -> Result<Vec<u8>, ParserErr> {
-        use byteorder::{BigEndian, WriteBytesExt};
-        let mut body = Vec::new();
-
-        body.write_u64::<BigEndian>(self.tx_id)
-            .map_err(|e| ParserErr::SerializeErr { msg: e.to_string() })?;
-        body.write_u8(match self.tx_type {
-            TxType::Deposit => 0,
-            TxType::Transfer => 1,
-            TxType::Withdrawal => 2,
-        })
-        .map_err(|e| ParserErr::SerializeErr { msg: e.to_string() })?;
-        body.write_u64::<BigEndian>(self.from_user_id)
-            .map_err(|e| ParserErr::SerializeErr { msg: e.to_string() })?;
-        body.write_u64::<BigEndian>(self.to_user_id)
-            .map_err(|e| ParserErr::SerializeErr { msg: e.to_string() })?;
-        body.write_i64::<BigEndian>(self.amount)
-            .map_err(|e| ParserErr::SerializeErr { msg: e.to_string() })?;
-        body.write_u64::<BigEndian>(self.timestamp)
-            .map_err(|e| ParserErr::SerializeErr { msg: e.to_string() })?;
-        body.write_u8(match self.status {
-            Status::Success => 0,
-            Status::Failure => 1,
-            Status::Pending => 2,
-        })
-        .map_err(|e| ParserErr::SerializeErr { msg: e.to_string() })?;
+/// Кодирует тело записи в версии схемы `version`: целые в порядке байт `B`,
+/// длины строк пишутся `write_len`; описание из `dictionary` пишется ссылкой на него.
+fn encode_with<B: ByteOrder>(
+    tx: &TxData,
+    version: u8,
+    dictionary: Option<&Dictionary>,
+    write_len: impl Fn(&mut Vec<u8>, usize) -> Result<(), ParserErr>,
+) -> Result<Vec<u8>, ParserErr> {
+    let mut body = Vec::new();
+
+    write_tx_id::<B>(&mut body, &tx.tx_id, version, &write_len)?;
+    body.write_u8(tx_type_code(tx.tx_type))?;
+    body.write_u64::<B>(tx.from_user_id)?;
+    body.write_u64::<B>(tx.to_user_id)?;
+    body.write_i64::<B>(tx.amount)?;
+    body.write_u64::<B>(tx.timestamp)?;
+    body.write_u8(status_code(tx.status))?;
+
+    let desc_bytes = tx.description.as_bytes();
+    match dictionary.map(|dictionary| dictionary.description_field(&tx.description)) {
+        None => {
+            write_len(&mut body, desc_bytes.len())?;
+            body.write_all(desc_bytes)?;
+        }
+        Some(field) => {
+            write_len(&mut body, field as usize)?;
+            if field & 1 == 0 {
+                body.write_all(desc_bytes)?;
+            }
+        }
+    }
 
-        let desc_bytes = self.description.as_bytes();
-        body.write_u32::<BigEndian>(desc_bytes.len() as u32)
-            .map_err(|e| ParserErr::SerializeErr { msg: e.to_string() })?;
-        body.write_all(desc_bytes)
-            .map_err(|e| ParserErr::SerializeErr { msg: e.to_string() })?;
+    Ok(body)
+}
 
-        // Теперь формируем полную запись: MAGIC + RECORD_SIZE + body
-        let mut full = Vec::with_capacity(8 + body.len());
-        full.extend_from_slice(b"YPBN");
-        full.write_u32::<BigEndian>(body.len() as u32)
-            .map_err(|e| ParserErr::SerializeErr { msg: e.to_string() })?;
-        full.extend_from_slice(&body);
+/// Код `TX_TYPE` в теле записи.
+pub(crate) fn tx_type_code(tx_type: TxType) -> u8 {
+    match tx_type {
+        TxType::Deposit => 0,
+        TxType::Transfer => 1,
+        TxType::Withdrawal => 2,
+    }
+}
 
-        Ok(full)
+/// Код `STATUS` в теле записи.
+pub(crate) fn status_code(status: Status) -> u8 {
+    match status {
+        Status::Success => 0,
+        Status::Failure => 1,
+        Status::Pending => 2,
+    }
+}
+
+/// Запись профиля `BigEndian` в версии схемы `version`: магическое число, длина тела (u32 BE), тело.
+fn big_endian_frame(tx: &TxData, version: u8, dictionary: Option<&Dictionary>) -> Result<Vec<u8>, ParserErr> {
+    // Длины в профиле `BigEndian` — u32: более длинную строку не записать, а не обрезать молча
+    let u32_len = |field: &'static str, len: usize| {
+        u32::try_from(len).map_err(|_| ParserErr::NumberOverflow { field, value: format!("{} bytes", len) })
+    };
+    let body = encode_with::<BigEndian>(tx, version, dictionary, |body, len| {
+        Ok(body.write_u32::<BigEndian>(u32_len("DESCRIPTION", len)?)?)
+    })?;
+
+    let mut full = Vec::with_capacity(8 + body.len());
+    full.extend_from_slice(&BIN_MAGIC);
+    full.write_u32::<BigEndian>(u32_len("RECORD_SIZE", body.len())?)?;
+    full.extend_from_slice(&body);
+    Ok(full)
+}
+
+/// Полная запись файла с заголовком в заданном профиле, готовая к записи в поток.
+fn frame(tx: &TxData, profile: BinProfile, dictionary: Option<&Dictionary>) -> Result<Vec<u8>, ParserErr> {
+    match profile {
+        BinProfile::BigEndian => big_endian_frame(tx, FILE_VERSION, dictionary),
+        // Длина тела (varint) + тело
+        BinProfile::CompactLe => {
+            let body = encode_with::<LittleEndian>(tx, FILE_VERSION, dictionary, |body, len| {
+                write_varint(body, len as u64);
+                Ok(())
+            })?;
+            let mut out = Vec::with_capacity(body.len() + 2);
+            write_varint(&mut out, body.len() as u64);
+            out.extend_from_slice(&body);
+            Ok(out)
+        }
+    }
+}
+
+/// Дописывает индекс: `YPBI`, пары (tx_id, смещение), затем смещение индекса,
+/// число пар (все u64 LE) и снова `YPBI`, чтобы индекс находился чтением с конца файла.
+///
+/// TX_ID кодируется как в записях профиля `CompactLe` (см. [`write_tx_id`]).
+fn write_index(out: &mut Counted<'_>, index: &[(TxId, u64)]) -> Result<(), ParserErr> {
+    let index_offset = out.written;
+    let mut footer = Vec::with_capacity(index.len() * 17 + 24);
+    footer.extend_from_slice(&INDEX_MAGIC);
+    for (tx_id, offset) in index {
+        write_tx_id::<LittleEndian>(&mut footer, tx_id, FILE_VERSION, |footer, len| {
+            write_varint(footer, len as u64);
+            Ok(())
+        })?;
+        footer.write_u64::<LittleEndian>(*offset)?;
+    }
+    footer.write_u64::<LittleEndian>(index_offset)?;
+    footer.write_u64::<LittleEndian>(index.len() as u64)?;
+    footer.extend_from_slice(&INDEX_MAGIC);
+    out.put(&footer)
+}
+
+/// Дописывает таблицу сжатых блоков в раскладке zstd seekable format: пропускаемый кадр zstd
+/// (`SKIPPABLE_MAGIC`, размер кадра), по строке на блок, затем число блоков (u32), дескриптор (u8, 0)
+/// и `SEEKABLE_MAGIC`; все целые — LE.
+///
+/// В отличие от zstd seekable format, строка описывает блок целиком, вместе с его заголовком
+/// (размер в файле, размер распакованных данных), и дополнена числом записей в блоке: по ней
+/// блок с записью номер N находится без чтения заголовков предыдущих блоков.
+fn write_seek_table(out: &mut Counted<'_>, blocks: &[SeekEntry]) -> Result<(), ParserErr> {
+    let frame_len = blocks.len() as u64 * SEEK_ENTRY_LEN + SEEK_FOOTER_LEN;
+    let mut table = Vec::with_capacity(8 + frame_len as usize);
+    table.write_u32::<LittleEndian>(SKIPPABLE_MAGIC)?;
+    table.write_u32::<LittleEndian>(frame_len as u32)?;
+    for block in blocks {
+        table.write_u32::<LittleEndian>(block.block_len)?;
+        table.write_u32::<LittleEndian>(block.decompressed_len)?;
+        table.write_u32::<LittleEndian>(block.records)?;
+    }
+    table.write_u32::<LittleEndian>(blocks.len() as u32)?;
+    table.write_u8(0)?;
+    table.write_u32::<LittleEndian>(SEEKABLE_MAGIC)?;
+    out.put(&table)
+}
+
+/// Строка таблицы сжатых блоков.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct SeekEntry {
+    /// Размер блока в файле вместе с его заголовком
+    pub(super) block_len: u32,
+    /// Размер распакованных записей блока
+    pub(super) decompressed_len: u32,
+    /// Число записей в блоке
+    pub(super) records: u32,
+}
+
+#[cfg(feature = "zstd")]
+fn compress_block(block: &[u8], level: i32) -> Result<Vec<u8>, ParserErr> {
+    Ok(zstd::bulk::compress(block, level)?)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn compress_block(_block: &[u8], _level: i32) -> Result<Vec<u8>, ParserErr> {
+    Err(ParserErr::FeatureDisabled { feature: "zstd" })
+}
+
+impl TxnToBin for TxData {
+    fn to_bin(&self) -> Result<Vec<u8>, ParserErr> {
+        // Запись без заголовка файла — версии схемы 1
+        big_endian_frame(self, LEGACY_VERSION, None)
     }
 
     fn to_bin_many(transactions: &[Self]) -> Result<Vec<u8>, ParserErr> {
-        let mut all = Vec::new();
+        let mut all = Vec::with_capacity(estimated_len(transactions, FRAME_LEN));
         for tx in transactions {
             all.extend_from_slice(&tx.to_bin()?);
         }
         Ok(all)
     }
+
+    fn to_bin_many_with(
+        transactions: &[Self],
+        options: &WriteOptions,
+    ) -> Result<Vec<u8>, ParserErr> {
+        let mut all = Vec::with_capacity(estimated_len(transactions, FRAME_LEN));
+        Self::to_bin_writer_many(transactions, options, &mut all)?;
+        Ok(all)
+    }
+}
+
+impl TxnWriteToBin for TxData {
+    fn to_bin_writer(&self, w: &mut dyn Write) -> Result<(), ParserErr> {
+        w.write_all(&self.to_bin()?)?;
+        Ok(())
+    }
+
+    fn to_bin_writer_many(
+        transactions: &[Self],
+        options: &WriteOptions,
+        w: &mut dyn Write,
+    ) -> Result<(), ParserErr> {
+        let mut sink = BinSink::new(options.clone());
+        sink.write_batch(w, transactions, &[])?;
+        sink.finish(w)
+    }
+}
+
+/// Потоковая запись бинарного формата: заголовок перед первой порцией,
+/// маркер конца и индекс — в [`RecordSink::finish`].
+/// Существующий бинарный файл, в конец которого дописываются записи (см. `WriteOptions::append`).
+pub(crate) struct BinAppend {
+    /// Заголовок файла; `None` для файла без заголовка (версии схемы 1)
+    pub(super) header: Option<FileHeader>,
+    /// Где заканчиваются записи: маркер конца, индекс и таблица блоков за ними пишутся заново
+    pub(super) records_end: u64,
+    /// Индекс файла в порядке `tx_id`; `None`, если файл записан без индекса
+    pub(super) index: Option<Vec<(TxId, u64)>>,
+    /// Таблица блоков файла
+    pub(super) blocks: Vec<SeekEntry>,
+}
+
+impl BinAppend {
+    /// Смещение, с которого дописываются записи; файл обрезается до него.
+    pub(crate) fn records_end(&self) -> u64 {
+        self.records_end
+    }
+
+    /// `TX_ID` записей файла по его индексу; `None`, если индекса нет.
+    pub(crate) fn indexed_ids(&self) -> Option<impl Iterator<Item = &TxId>> {
+        self.index.as_ref().map(|index| index.iter().map(|(tx_id, _)| tx_id))
+    }
+}
+
+pub(crate) struct BinSink {
+    options: WriteOptions,
+    started: bool,
+    /// Сколько байт уже записано (для смещений в индексе)
+    written: u64,
+    /// Смещение записи, а для сжатых файлов — смещение блока, в котором она лежит
+    index: Vec<(TxId, u64)>,
+    /// Словарь описаний, собранный по первой порции
+    dictionary: Option<Arc<Dictionary>>,
+    /// Записанные сжатые блоки для таблицы блоков
+    blocks: Vec<SeekEntry>,
+    /// Записи незакрытого сжатого блока: блок набирается из нескольких порций и закрывается,
+    /// когда в нём [`RECORDS_PER_BLOCK`] записей, при сбросе вывода или в конце файла
+    pending: Vec<TxData>,
+}
+
+impl BinSink {
+    pub(crate) fn new(options: WriteOptions) -> Self {
+        BinSink {
+            options,
+            started: false,
+            written: 0,
+            index: Vec::new(),
+            dictionary: None,
+            blocks: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Приёмник, дописывающий записи в файл `file`. Профиль, сжатие, индекс, таблица блоков,
+    /// словарь и сведения о программе берутся из файла, а не из `options`: заголовок уже записан.
+    pub(crate) fn append(mut options: WriteOptions, file: BinAppend) -> Self {
+        let BinAppend { header, records_end, index, blocks } = file;
+        let flag = |flag: u8| header.as_ref().is_some_and(|header| header.flags & flag != 0);
+        options.bin_compression = match options.bin_compression {
+            _ if !flag(FLAG_ZSTD) => BinCompression::None,
+            BinCompression::Zstd { level } => BinCompression::Zstd { level },
+            // Уровень 0 — уровень zstd по умолчанию
+            BinCompression::None => BinCompression::Zstd { level: 0 },
+        };
+        options.bin_index = flag(FLAG_INDEX);
+        options.bin_dictionary = flag(FLAG_DICTIONARY);
+        options.bin_seek_table = flag(FLAG_SEEK_TABLE);
+        options.bin_header = header.is_some();
+        options.bin_profile = header.as_ref().map_or(BinProfile::BigEndian, |header| header.profile);
+        options.metadata = header.as_ref().and_then(|header| header.metadata.clone());
+        BinSink {
+            options,
+            started: true,
+            written: records_end,
+            index: index.unwrap_or_default(),
+            dictionary: header.and_then(|header| header.dictionary),
+            blocks,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Таблица блоков пишется только для сжатых файлов: несжатые записи и так перескакиваются по длине.
+    fn seek_table(&self) -> bool {
+        self.options.bin_seek_table && self.options.bin_compression != BinCompression::None
+    }
+
+    /// Исходный формат без сжатия и индекса пишется без заголовка — его понимают старые читатели.
+    /// Такой файл — версии схемы 1, поэтому `TX_ID` в нём только числовые.
+    fn legacy(&self) -> bool {
+        self.options.writes_legacy_bin()
+    }
+
+    /// Пишет заголовок перед первой порцией `records`; словарь собирается по ней же.
+    fn start(&mut self, out: &mut Counted<'_>, records: &[TxData]) -> Result<(), ParserErr> {
+        if std::mem::replace(&mut self.started, true) || self.legacy() {
+            return Ok(());
+        }
+        let mut flags = match self.options.bin_compression {
+            BinCompression::None => 0,
+            BinCompression::Zstd { .. } => FLAG_ZSTD,
+        };
+        if self.options.bin_index {
+            flags |= FLAG_INDEX;
+        }
+        let dictionary = self.options.bin_dictionary.then(|| Arc::new(Dictionary::build(records)));
+        if dictionary.is_some() {
+            flags |= FLAG_DICTIONARY;
+        }
+        if self.seek_table() {
+            flags |= FLAG_SEEK_TABLE;
+        }
+        if self.options.metadata.is_some() {
+            flags |= FLAG_METADATA;
+        }
+        let header = FileHeader {
+            version: FILE_VERSION,
+            profile: self.options.bin_profile,
+            flags,
+            metadata: self.options.metadata.clone(),
+            dictionary,
+        };
+        out.put(&header.to_bytes())?;
+        self.dictionary = header.dictionary;
+        Ok(())
+    }
+
+    /// Сжимает и пишет накопленные записи блоком.
+    fn close_block(&mut self, out: &mut Counted<'_>) -> Result<(), ParserErr> {
+        let BinCompression::Zstd { level } = self.options.bin_compression else {
+            return Ok(());
+        };
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let (profile, dictionary) = (self.options.bin_profile, self.dictionary.as_deref());
+        let mut block = Vec::new();
+        for tx in &self.pending {
+            block.extend_from_slice(&frame(tx, profile, dictionary)?);
+        }
+        // Такой блок читатель отверг бы
+        if block.len() > MAX_BLOCK_LEN {
+            return Err(block_too_large("decompressed", block.len()));
+        }
+        let records = std::mem::take(&mut self.pending).len() as u32;
+        let compressed = compress_block(&block, level)?;
+        let mut block_header = Vec::with_capacity(8);
+        block_header.write_u32::<LittleEndian>(compressed.len() as u32)?;
+        block_header.write_u32::<LittleEndian>(records)?;
+        out.put(&block_header)?;
+        out.put(&compressed)?;
+        self.blocks.push(SeekEntry {
+            block_len: (block_header.len() + compressed.len()) as u32,
+            decompressed_len: block.len() as u32,
+            records,
+        });
+        Ok(())
+    }
+}
+
+impl RecordSink for BinSink {
+    fn write_batch(
+        &mut self,
+        w: &mut dyn Write,
+        records: &[TxData],
+        _annotations: &[TextAnnotation],
+    ) -> Result<(), ParserErr> {
+        let mut out = Counted { inner: w, written: self.written };
+        self.start(&mut out, records)?;
+        if self.legacy() {
+            for tx in records {
+                out.put(&tx.to_bin()?)?;
+            }
+            self.written = out.written;
+            return Ok(());
+        }
+
+        let profile = self.options.bin_profile;
+        for tx in records {
+            // Запись сжатого файла указывает на блок, в котором она окажется: до его закрытия
+            // в файл ничего не пишется
+            self.index.push((tx.tx_id.clone(), out.written));
+            match self.options.bin_compression {
+                BinCompression::None => out.put(&frame(tx, profile, self.dictionary.as_deref())?)?,
+                BinCompression::Zstd { .. } => {
+                    self.pending.push(tx.clone());
+                    if self.pending.len() == RECORDS_PER_BLOCK {
+                        self.close_block(&mut out)?;
+                    }
+                }
+            }
+        }
+        self.written = out.written;
+        Ok(())
+    }
+
+    fn flush(&mut self, w: &mut dyn Write) -> Result<(), ParserErr> {
+        let mut out = Counted { inner: w, written: self.written };
+        self.close_block(&mut out)?;
+        self.written = out.written;
+        Ok(())
+    }
+
+    fn finish(&mut self, w: &mut dyn Write) -> Result<(), ParserErr> {
+        let mut out = Counted { inner: w, written: self.written };
+        self.start(&mut out, &[])?;
+        self.close_block(&mut out)?;
+        if self.options.bin_index || self.seek_table() {
+            // Маркер конца записей, после которого читатель останавливается
+            match (self.options.bin_compression, self.options.bin_profile) {
+                (BinCompression::Zstd { .. }, _) => out.put(&0u32.to_le_bytes())?,
+                (BinCompression::None, BinProfile::CompactLe) => out.put(&[0])?,
+                (BinCompression::None, BinProfile::BigEndian) => {}
+            }
+        }
+        if self.options.bin_index {
+            write_index(&mut out, &self.index)?;
+        }
+        if self.seek_table() {
+            write_seek_table(&mut out, &self.blocks)?;
+        }
+        self.written = out.written;
+        Ok(())
+    }
+
+    fn resume(&mut self, checkpoint: &WriteCheckpoint) -> bool {
+        // Смещения уже записанных записей и блоков не сохраняются, поэтому индекс и таблицу
+        // блоков не восстановить; словарь уже записан в начале файла, а заново его не прочитать
+        if self.options.bin_index || self.options.bin_dictionary || self.seek_table() {
+            return false;
+        }
+        self.started = checkpoint.bytes > 0;
+        self.written = checkpoint.bytes;
+        true
+    }
+}
+
+/// Поток записи, считающий записанные байты (для смещений в индексе).
+struct Counted<'a> {
+    inner: &'a mut dyn Write,
+    written: u64,
+}
+
+impl Counted<'_> {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), ParserErr> {
+        self.inner.write_all(bytes)?;
+        self.written += bytes.len() as u64;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::{f32::consts::E, io::Cursor};
+    use std::io::Cursor;
 
     #[test]
     fn test_to_bin_and_from_bin_roundtrip() {
         let original = TxData {
-            tx_id: 12345,
+            tx_id: TxId::U64(12345),
             tx_type: TxType::Transfer,
             from_user_id: 100,
             to_user_id: 200,
             amount: 999_000_000_000i64, 
             timestamp: 1700000000,
             status: Status::Success,
-            description: "Test binary transaction".to_string(),
+            description: "Test binary transaction".into(),
             format: Format::YpBankBin,
         };
 
@@ -272,8 +1439,10 @@ mod tests {
 
         let err = TxData::from_bin(&body).unwrap_err();
 
-        if let ParserErr::ParseErr { msg } = err {
-            assert!(msg.to_string().contains("DESCRIPTION length exceeds body"));
+        if let ParserErr::LengthExceedsBody { field, len, available } = err {
+            assert_eq!(field, "DESCRIPTION");
+            assert_eq!(len, 10);
+            assert_eq!(available, 5);
         } else {
             panic!()
         }
@@ -282,25 +1451,25 @@ mod tests {
     #[test]
     fn test_from_bin_reader_valid_multiple() {
         let tx1 = TxData {
-            tx_id: 1,
+            tx_id: TxId::U64(1),
             tx_type: TxType::Deposit,
             from_user_id: 0,
             to_user_id: 100,
             amount: 1000,
             timestamp: 1700000000,
             status: Status::Success,
-            description: "First deposit".to_string(),
+            description: "First deposit".into(),
             format: Format::YpBankBin,
         };
         let tx2 = TxData {
-            tx_id: 2,
+            tx_id: TxId::U64(2),
             tx_type: TxType::Withdrawal,
             from_user_id: 100,
             to_user_id: 0,
             amount: 500,
             timestamp: 1700000001,
             status: Status::Failure,
-            description: "Failed withdrawal".to_string(),
+            description: "Failed withdrawal".into(),
             format: Format::YpBankBin,
         };
 
@@ -311,8 +1480,8 @@ mod tests {
         assert_eq!(transactions.len(), 2);
         assert_eq!(transactions[0].tx_id, 1);
         assert_eq!(transactions[1].tx_id, 2);
-        assert_eq!(transactions[0].description, "First deposit");
-        assert_eq!(transactions[1].description, "Failed withdrawal");
+        assert_eq!(&*transactions[0].description, "First deposit");
+        assert_eq!(&*transactions[1].description, "Failed withdrawal");
     }
 
     #[test]
@@ -328,24 +1497,436 @@ mod tests {
         data.extend_from_slice(b"INVALID"); // wrong magic
         let reader = Box::new(Cursor::new(data));
         let err = TxData::from_bin_reader(reader).unwrap_err();
-        if let ParserErr::ParseErr { msg } = err {
-            assert!(msg.to_string().contains("Invalid MAGIC number"));
+        assert_eq!(err.line(), Some(1));
+        if let ParserErr::InvalidMagic { found } = err.root() {
+            assert_eq!(found, b"INVA");
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn test_from_bin_reader_collect_mode() {
+        let good = TxData {
+            tx_id: TxId::U64(1),
+            tx_type: TxType::Deposit,
+            from_user_id: 0,
+            to_user_id: 100,
+            amount: 1000,
+            timestamp: 1700000000,
+            status: Status::Success,
+            description: "ok".into(),
+            format: Format::YpBankBin,
+        };
+        let mut bad = good.to_bin().unwrap();
+        bad[8 + 8] = 9; // неизвестный TX_TYPE
+
+        let mut data = good.to_bin().unwrap();
+        data.extend_from_slice(&bad);
+        data.extend_from_slice(&good.to_bin().unwrap());
+        data.extend_from_slice(b"JUNKJUNK");
+        data.extend_from_slice(&good.to_bin().unwrap());
+
+        let outcome =
+            TxData::from_bin_reader_with(Box::new(Cursor::new(data)), &ParseOptions::collect()).unwrap();
+        let issues = outcome.issues;
+
+        // После нарушения структуры (JUNK) чтение прекращается
+        assert_eq!(outcome.records.len(), 2);
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].line, Some(2));
+        assert_eq!(issues[0].field, Some("TX_TYPE"));
+        assert_eq!(issues[1].line, Some(4));
+        assert!(matches!(issues[1].error.root(), ParserErr::InvalidMagic { .. }));
+    }
+
+    fn sample_txs() -> Vec<TxData> {
+        (1..=3)
+            .map(|id| TxData {
+                tx_id: TxId::U64(id),
+                tx_type: TxType::Transfer,
+                from_user_id: 100 + id,
+                to_user_id: 200,
+                amount: -5000,
+                timestamp: 1700000000 + id,
+                status: Status::Pending,
+                description: format!("Record number {}", id).into(),
+                format: Format::YpBankBin,
+            })
+            .collect()
+    }
+
+    /// Тело записи `BigEndian` без рамки, как его получает [`decode`].
+    fn big_endian_body(tx: &TxData, version: u8) -> Vec<u8> {
+        big_endian_frame(tx, version, None).unwrap().split_off(8)
+    }
+
+    #[test]
+    fn test_fixed_decoder_matches_cursor_decoder() {
+        let cursor_decode = |body: &[u8], version| {
+            decode_with::<BigEndian>(body, version, None, |cursor| Ok(cursor.read_u32::<BigEndian>()?.into()))
+        };
+        for version in [LEGACY_VERSION, FILE_VERSION] {
+            for tx in sample_txs() {
+                let body = big_endian_body(&tx, version);
+                assert_eq!(decode_fixed_be(&body, version), Some(tx.clone()));
+                assert_eq!(cursor_decode(&body, version).unwrap(), tx);
+            }
+        }
+
+        // Всё необычное уходит в общий разбор с прежними ошибками
+        let tx = TxData {
+            tx_id: TxId::String("ext-1".to_string()),
+            ..sample_txs().remove(0)
+        };
+        let body = big_endian_body(&tx, FILE_VERSION);
+        assert_eq!(decode_fixed_be(&body, FILE_VERSION), None);
+        assert_eq!(decode(&body, BinProfile::BigEndian, FILE_VERSION, None).unwrap(), tx);
+
+        let mut body = big_endian_body(&sample_txs()[0], FILE_VERSION);
+        body[1 + 8 + FIXED_FIELDS_LEN - 5] = 7;
+        assert_eq!(decode_fixed_be(&body, FILE_VERSION), None);
+        let Err(ParserErr::InvalidValue { field, .. }) = decode(&body, BinProfile::BigEndian, FILE_VERSION, None) else {
+            panic!("expected invalid STATUS");
+        };
+        assert_eq!(field, "STATUS");
+
+        let body = big_endian_body(&sample_txs()[0], FILE_VERSION);
+        let truncated = &body[..body.len() - 1];
+        assert_eq!(decode_fixed_be(truncated, FILE_VERSION), None);
+        assert!(matches!(
+            decode(truncated, BinProfile::BigEndian, FILE_VERSION, None),
+            Err(ParserErr::LengthExceedsBody { field: "DESCRIPTION", .. })
+        ));
+    }
+
+    /// Сравнение скорости быстрого и общего разбора:
+    /// `cargo test --release -p lib bench_fixed_decoder -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_fixed_decoder() {
+        use std::hint::black_box;
+        use std::time::Instant;
+
+        let bodies: Vec<Vec<u8>> = (0..1_000_000u64)
+            .map(|id| {
+                let tx = TxData {
+                    tx_id: TxId::U64(id),
+                    description: "Monthly service fee".into(),
+                    ..sample_txs().remove(0)
+                };
+                big_endian_body(&tx, FILE_VERSION)
+            })
+            .collect();
+        let measure = |name: &str, decode: &dyn Fn(&[u8]) -> Option<TxData>| {
+            let start = Instant::now();
+            for body in &bodies {
+                black_box(decode(black_box(body)));
+            }
+            let elapsed = start.elapsed();
+            println!("{}: {:?} ({:.1} ns/record)", name, elapsed, elapsed.as_nanos() as f64 / bodies.len() as f64);
+        };
+
+        measure("cursor", &|body| {
+            decode_with::<BigEndian>(body, FILE_VERSION, None, |cursor| Ok(cursor.read_u32::<BigEndian>()?.into())).ok()
+        });
+        measure("fixed", &|body| decode_fixed_be(body, FILE_VERSION));
+    }
+
+    #[test]
+    fn test_compact_profile_roundtrip_is_smaller() {
+        let txs = sample_txs();
+        let options = WriteOptions {
+            bin_profile: BinProfile::CompactLe,
+            ..WriteOptions::default()
+        };
+
+        let compact = TxData::to_bin_many_with(&txs, &options).unwrap();
+        let legacy = TxData::to_bin_many(&txs).unwrap();
+        let restored = TxData::from_bin_reader(Box::new(Cursor::new(compact.clone()))).unwrap();
+
+        assert_eq!(&compact[0..4], b"YPBF");
+        assert!(compact.len() < legacy.len());
+        assert_eq!(restored, txs);
+    }
+
+    #[test]
+    fn test_dictionary_roundtrip_is_smaller() {
+        let descriptions = ["Monthly salary payment from employer", "Card top-up via mobile app", "Rent for the apartment"];
+        let txs: Vec<TxData> = (0..300)
+            .map(|id| TxData {
+                tx_id: TxId::U64(id),
+                description: match id % 10 {
+                    // Уникальные описания остаются в записях
+                    9 => format!("Unique transfer {}", id).into(),
+                    n => descriptions[n as usize % 3].into(),
+                },
+                ..sample_txs()[0].clone()
+            })
+            .collect();
+
+        for bin_profile in [BinProfile::BigEndian, BinProfile::CompactLe] {
+            let plain = WriteOptions { bin_profile, bin_index: true, ..WriteOptions::default() };
+            let options = WriteOptions { bin_dictionary: true, ..plain.clone() };
+            // Вторая порция пишется после словаря: её новые описания хранятся в записях
+            let mut sink = BinSink::new(options);
+            let mut data = Vec::new();
+            sink.write_batch(&mut data, &txs[..200], &[]).unwrap();
+            let mut later = txs[200..].to_vec();
+            later[0].description = "Not in the dictionary".into();
+            sink.write_batch(&mut data, &later, &[]).unwrap();
+            sink.finish(&mut data).unwrap();
+
+            let expected: Vec<TxData> = txs[..200].iter().chain(&later).cloned().collect();
+            let restored = TxData::from_bin_reader(Box::new(Cursor::new(data.clone()))).unwrap();
+            assert_eq!(restored, expected);
+            let plain_len = TxData::to_bin_many_with(&expected, &plain).unwrap().len();
+            assert!(data.len() < plain_len * 3 / 4);
+
+            // Поиск по индексу и чтение диапазона тоже разворачивают ссылки на словарь
+            let mut cursor = Cursor::new(data.clone());
+            let index = crate::parser::BinIndex::read_from(&mut cursor).unwrap().unwrap();
+            let found = index.get(&mut cursor, &TxId::U64(200)).unwrap().unwrap();
+            assert_eq!(&*found.description, "Not in the dictionary");
+            let range = crate::parser::read_range(Cursor::new(data), 5, 3).unwrap();
+            assert_eq!(range, expected[5..8]);
+        }
+    }
+
+    #[test]
+    fn test_dictionary_reference_out_of_range() {
+        let options = WriteOptions { bin_dictionary: true, ..WriteOptions::default() };
+        let txs = vec![sample_txs()[0].clone(), sample_txs()[0].clone()];
+        let mut data = TxData::to_bin_many_with(&txs, &options).unwrap();
+        // Словарь из одного описания; последний байт поля длины описания — ссылка на номер 0
+        let at = data.len() - 1;
+        assert_eq!(data[at], 1);
+        data[at] = 0b11;
+
+        let Err(ParserErr::AtLine { line: 2, source }) = TxData::from_bin_reader(Box::new(Cursor::new(data))) else {
+            panic!()
+        };
+        let ParserErr::InvalidValue { field, .. } = *source else {
+            panic!()
+        };
+        assert_eq!(field, "DESCRIPTION");
+    }
+
+    #[test]
+    fn test_compact_profile_truncated_record() {
+        let options = WriteOptions {
+            bin_profile: BinProfile::CompactLe,
+            ..WriteOptions::default()
+        };
+        let mut data = TxData::to_bin_many_with(&sample_txs(), &options).unwrap();
+        data.truncate(data.len() - 3);
+
+        let outcome =
+            TxData::from_bin_reader_with(Box::new(Cursor::new(data)), &ParseOptions::collect()).unwrap();
+
+        assert_eq!(outcome.records.len(), 2);
+        assert_eq!(outcome.issues.len(), 1);
+        assert_eq!(outcome.issues[0].line, Some(3));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_blocks_roundtrip() {
+        let txs: Vec<TxData> = (0..2500)
+            .map(|id| TxData {
+                tx_id: TxId::U64(id),
+                tx_type: TxType::Deposit,
+                from_user_id: 0,
+                to_user_id: id % 7,
+                amount: 100,
+                timestamp: 1700000000 + id,
+                status: Status::Success,
+                description: "Monthly salary payment".into(),
+                format: Format::YpBankBin,
+            })
+            .collect();
+
+        for bin_profile in [BinProfile::BigEndian, BinProfile::CompactLe] {
+            let options = WriteOptions {
+                bin_profile,
+                bin_compression: BinCompression::Zstd { level: 3 },
+                ..WriteOptions::default()
+            };
+            let compressed = TxData::to_bin_many_with(&txs, &options).unwrap();
+            let plain = TxData::to_bin_many(&txs).unwrap();
+            let restored = TxData::from_bin_reader(Box::new(Cursor::new(compressed.clone()))).unwrap();
+
+            assert!(compressed.len() * 4 < plain.len());
+            assert_eq!(restored, txs);
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_block_length_is_bounded() {
+        let options = WriteOptions {
+            bin_compression: BinCompression::Zstd { level: 3 },
+            ..WriteOptions::default()
+        };
+        let data = TxData::to_bin_many_with(&sample_txs(), &options).unwrap();
+        let with_block_len = |len: u32| {
+            let mut data = data.clone();
+            let at = FILE_HEADER_LEN as usize;
+            data[at..at + 4].copy_from_slice(&len.to_le_bytes());
+            TxData::from_bin_reader(Box::new(Cursor::new(data)))
+        };
+
+        let Err(ParserErr::AtLine { source, .. }) = with_block_len(u32::MAX) else {
+            panic!()
+        };
+        let ParserErr::BlockTooLarge { what, len, limit } = *source else {
+            panic!()
+        };
+        assert_eq!((what, len, limit), ("compressed", u64::from(u32::MAX), MAX_BLOCK_LEN as u64));
+
+        // Длина в пределах нормы, но за концом файла: ошибка, а не буфер на 200 МБ
+        let Err(ParserErr::AtLine { source, .. }) = with_block_len(200 << 20) else {
+            panic!()
+        };
+        assert!(matches!(*source, ParserErr::LengthExceedsBody { field: "zstd block", len: 209715200, .. }));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_batches_across_zstd_blocks_keep_input_order() {
+        let txs: Vec<TxData> = (0..2500)
+            .flat_map(|_| sample_txs())
+            .enumerate()
+            .map(|(i, tx)| TxData { tx_id: TxId::U64(i as u64), ..tx })
+            .collect();
+        let options = WriteOptions {
+            bin_compression: BinCompression::Zstd { level: 1 },
+            ..WriteOptions::default()
+        };
+        // Порции записи и чтения не совпадают ни между собой, ни с блоками сжатия
+        let mut sink = BinSink::new(options);
+        let mut written = Vec::new();
+        for chunk in txs.chunks(700) {
+            sink.write_batch(&mut written, chunk, &[]).unwrap();
+        }
+        sink.finish(&mut written).unwrap();
+        // Блоки набираются из нескольких порций; неполный только последний
+        let (last, full) = sink.blocks.split_last().unwrap();
+        assert!(full.iter().all(|block| block.records == RECORDS_PER_BLOCK as u32));
+        assert_eq!(last.records as usize, txs.len() % RECORDS_PER_BLOCK);
+
+        // Сброс вывода закрывает неполный блок: сброшенные записи уже в файле
+        let mut sink = BinSink::new(WriteOptions { bin_compression: BinCompression::Zstd { level: 1 }, ..WriteOptions::default() });
+        let mut flushed = Vec::new();
+        sink.write_batch(&mut flushed, &txs[..10], &[]).unwrap();
+        sink.flush(&mut flushed).unwrap();
+        let restored = TxData::from_bin_reader(Box::new(Cursor::new(flushed.clone()))).unwrap();
+        assert_eq!(restored.len(), 10);
+        sink.write_batch(&mut flushed, &txs[10..20], &[]).unwrap();
+        sink.finish(&mut flushed).unwrap();
+        assert_eq!(sink.blocks.iter().map(|block| block.records).collect::<Vec<_>>(), [10, 10]);
+
+        let mut source = BinSource::new(Box::new(Cursor::new(written)), ParseOptions::default()).unwrap();
+        let mut ids = Vec::new();
+        while let Some(batch) = source.next_batch(333).unwrap() {
+            ids.extend(batch.records.iter().filter_map(|tx| tx.tx_id.as_u64()));
+        }
+
+        assert_eq!(ids, (0..txs.len() as u64).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_from_bin_reader_unknown_header_flags() {
+        let mut data = b"YPBF".to_vec();
+        data.extend_from_slice(&[1, 0, 0b1000_0000, 0]);
+
+        let err = TxData::from_bin_reader(Box::new(Cursor::new(data))).unwrap_err();
+
+        assert!(matches!(err, ParserErr::InvalidValue { field: "FLAGS", .. }));
+    }
+
+    #[test]
+    fn test_from_bin_reader_unsupported_header_version() {
+        let mut data = b"YPBF".to_vec();
+        data.extend_from_slice(&[7, 1, 0, 0]);
+
+        let err = TxData::from_bin_reader(Box::new(Cursor::new(data))).unwrap_err();
+
+        if let ParserErr::UnsupportedVersion { format, version } = err {
+            assert_eq!(format, Format::YpBankBin);
+            assert_eq!(version, 7);
         } else {
             panic!()
         }
     }
 
+    #[test]
+    fn test_generalized_ids_need_header() {
+        let mut txs = sample_txs();
+        txs[1].tx_id = TxId::parse("123e4567-e89b-12d3-a456-426614174000").unwrap();
+        txs[2].tx_id = TxId::String("ord-7".to_string());
+
+        let err = TxData::to_bin_many(&txs).unwrap_err();
+        assert!(matches!(err, ParserErr::IdNeedsSchema { .. }));
+
+        for bin_profile in [BinProfile::BigEndian, BinProfile::CompactLe] {
+            let options = WriteOptions {
+                bin_profile,
+                bin_header: true,
+                bin_index: true,
+                ..WriteOptions::default()
+            };
+            let data = TxData::to_bin_many_with(&txs, &options).unwrap();
+            assert_eq!(data[4], FILE_VERSION);
+            let restored = TxData::from_bin_reader(Box::new(Cursor::new(data.clone()))).unwrap();
+            assert_eq!(restored, txs);
+
+            let mut cursor = Cursor::new(data);
+            let index = crate::parser::concrete::bin_index::BinIndex::read_from(&mut cursor).unwrap().unwrap();
+            let found = index.get(&mut cursor, &txs[2].tx_id).unwrap();
+            assert_eq!(found.as_ref(), Some(&txs[2]));
+        }
+    }
+
+    #[test]
+    fn test_header_version_1_is_still_read() {
+        let txs = sample_txs();
+        let mut data = b"YPBF".to_vec();
+        data.extend_from_slice(&[LEGACY_VERSION, 1, 0, 0]);
+        for tx in &txs {
+            let body = encode_with::<LittleEndian>(tx, LEGACY_VERSION, None, |body, len| {
+                write_varint(body, len as u64);
+                Ok(())
+            })
+            .unwrap();
+            write_varint(&mut data, body.len() as u64);
+            data.extend_from_slice(&body);
+        }
+
+        let restored = TxData::from_bin_reader(Box::new(Cursor::new(data))).unwrap();
+        assert_eq!(restored, txs);
+    }
+
+    #[test]
+    fn test_varint_roundtrip() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut out = Vec::new();
+            write_varint(&mut out, value);
+            assert_eq!(read_varint(&mut Cursor::new(out)).unwrap(), value);
+        }
+    }
+
     #[test]
     fn test_to_bin_description_with_special_chars() {
         let tx = TxData {
-            tx_id: 999,
+            tx_id: TxId::U64(999),
             tx_type: TxType::Transfer,
             from_user_id: 123,
             to_user_id: 456,
             amount: -123456789i64,
             timestamp: 9999999999,
             status: Status::Pending,
-            description: "Special chars: 🚀\n\t\"\\'".to_string(),
+            description: "Special chars: 🚀\n\t\"\\'".into(),
             format: Format::YpBankBin,
         };
 
@@ -368,14 +1949,14 @@ mod tests {
     #[test]
     fn test_to_bin_structure() {
         let tx = TxData {
-            tx_id: 1,
+            tx_id: TxId::U64(1),
             tx_type: TxType::Deposit,
             from_user_id: 0,
             to_user_id: 1,
             amount: 100,
             timestamp: 1,
             status: Status::Success,
-            description: "test".to_string(),
+            description: "test".into(),
             format: Format::YpBankBin,
         };
 
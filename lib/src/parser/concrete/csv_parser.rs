@@ -1,11 +1,19 @@
-use csv::{ReaderBuilder, StringRecord};
-use std::io::{Cursor, Read};
+use csv::{ReaderBuilder, StringRecord, StringRecordsIntoIter, Terminator, WriterBuilder};
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+#[cfg(feature = "enrich")]
+use std::sync::Arc;
 
 use crate::model::data::Format;
 use crate::model::data::Status;
 use crate::model::data::TxData;
 use crate::model::data::TxType;
-use crate::model::errors::ParserErr;
+use crate::model::tx_id::TxId;
+#[cfg(feature = "enrich")]
+use crate::enrich::UserReference;
+use crate::model::errors::{ParseWarning, ParserErr, WarningKind};
+use crate::model::metadata::{ProducerMetadata, METADATA_PREFIX};
+use crate::parser::concrete::{batch_full, estimated_len, parse_number, DescriptionArena, RecordSink, RecordSource};
+use crate::parser::options::{CsvOptions, ParseOptions, QuoteStyle, ParseOutcome, ReadCheckpoint, RecordPos, TextAnnotation, WriteCheckpoint};
 
 const CSV_HEADERS: &[&str] = &[
     "TX_ID",
@@ -21,6 +29,33 @@ const CSV_HEADERS: &[&str] = &[
 const CSV_HEADER_LINE: &str =
     "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION";
 
+/// Метка порядка байтов UTF-8, которую Excel ставит в начало CSV.
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Колонки каждой версии схемы: версию CSV-файла определяет строка заголовка.
+const CSV_SCHEMAS: &[(u32, &[&str])] = &[(1, CSV_HEADERS)];
+
+/// Примерная длина строки CSV без описания: числа, тип, статус, запятые и кавычки.
+const CSV_ROW_LEN: usize = 96;
+
+/// Версия схемы CSV-файла по строке заголовка в начале `prefix`.
+///
+/// # Errors
+/// `ParserErr::InvalidHeader`, если заголовок не совпадает ни с одной известной версией.
+pub(crate) fn schema_version(prefix: &[u8]) -> Result<u32, ParserErr> {
+    let prefix = String::from_utf8_lossy(prefix.strip_prefix(UTF8_BOM).unwrap_or(prefix));
+    let header = prefix.lines().next().unwrap_or_default().trim();
+    let columns: Vec<_> = header.split(',').collect();
+    CSV_SCHEMAS
+        .iter()
+        .find(|(_, schema)| *schema == columns.as_slice())
+        .map(|(version, _)| *version)
+        .ok_or_else(|| ParserErr::InvalidHeader {
+            expected: CSV_HEADER_LINE.to_string(),
+            actual: header.to_string(),
+        })
+}
+
 /// Трейт для парсинга транзакций из формата YbCSV.
 ///
 /// Поддерживает три способа десериализации:
@@ -54,6 +89,20 @@ pub trait TxnFromCsv {
     /// # Errors
     /// Возвращает `ParserErr`, если произошла ошибка чтения или парсинга любой записи.
     fn from_csv_reader(reader: Box<dyn Read>) -> Result<Vec<TxData>, ParserErr>;
+
+    /// Парсит транзакции из потока с заданными настройками.
+    ///
+    /// В режиме `ErrorMode::Collect` некорректные строки пропускаются и попадают
+    /// в `ParseOutcome::issues`; ошибка возвращается только для проблем,
+    /// после которых продолжать нельзя (неверный заголовок, ошибка чтения).
+    ///
+    /// # Errors
+    /// Возвращает `ParserErr` при фатальной ошибке или, в режиме `FailFast`,
+    /// при первой некорректной записи.
+    fn from_csv_reader_with(
+        reader: Box<dyn Read>,
+        options: &ParseOptions,
+    ) -> Result<ParseOutcome, ParserErr>;
 }
 
 /// Трейт для сериализации транзакций в формат YbCSV.
@@ -82,6 +131,24 @@ pub trait TxnToCsv {
         Self: Sized;
 }
 
+/// Трейт для потоковой записи транзакций в формате YbCSV прямо в `Write`,
+/// без промежуточной строки на весь документ.
+pub trait TxnWriteToCsv {
+    /// Записывает одну транзакцию в `w` так же, как её сериализует [`TxnToCsv::to_csv`].
+    ///
+    /// # Errors
+    /// Возвращает `ParserErr` при ошибке записи.
+    fn to_csv_writer(&self, w: &mut dyn Write) -> Result<(), ParserErr>;
+
+    /// Записывает заголовок и транзакции в `w` так же, как [`TxnToCsv::to_csv_many`].
+    ///
+    /// # Errors
+    /// Возвращает `ParserErr` при ошибке записи.
+    fn to_csv_writer_many(many: &[Self], w: &mut dyn Write) -> Result<(), ParserErr>
+    where
+        Self: Sized;
+}
+
 impl TxnFromCsv for TxData {
     fn from_csv(csv_line: &str) -> Result<TxData, ParserErr> {
         let mut rdr = ReaderBuilder::new()
@@ -92,30 +159,23 @@ impl TxnFromCsv for TxData {
         let record = rdr
             .records()
             .next()
-            .ok_or_else(|| ParserErr::ParseErr {
+            .ok_or_else(|| ParserErr::Malformed {
                 msg: "Empty CSV line".into(),
             })?
-            .map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?;
+            .map_err(csv_err)?;
 
-        from_csv_record(&record)
+        from_csv_record(&record, &CsvOptions::default())
     }
 
     fn from_csv_many(csv_lines: &[String]) -> Result<Vec<TxData>, ParserErr> {
         // Первая строка — заголовок
-        if csv_lines.is_empty() {
-            return Err(ParserErr::ParseErr {
-                msg: "CSV is empty".into(),
-            });
-        }
-
-        let header = &csv_lines[0];
+        let header = csv_lines.first().map(String::as_str).unwrap_or_default();
+        let header = header.strip_prefix('\u{feff}').unwrap_or(header);
 
         if header.trim() != CSV_HEADER_LINE {
-            return Err(ParserErr::ParseErr {
-                msg: format!(
-                    "Invalid header: expected '{}', got '{}'",
-                    CSV_HEADER_LINE, header
-                ),
+            return Err(ParserErr::InvalidHeader {
+                expected: CSV_HEADER_LINE.to_string(),
+                actual: header.to_string(),
             });
         }
 
@@ -124,93 +184,283 @@ impl TxnFromCsv for TxData {
             if line.trim().is_empty() {
                 continue;
             }
-            match Self::from_csv(line) {
-                Ok(tx) => transactions.push(tx),
-                Err(e) => {
-                    return Err(ParserErr::ParseErr {
-                        msg: format!("Error parsing line {}: {}", i + 1, e),
-                    });
-                }
-            }
+            let tx = Self::from_csv(line).map_err(|e| e.at_line(i + 1))?;
+            transactions.push(tx);
         }
         Ok(transactions)
     }
 
     fn from_csv_reader(reader: Box<dyn Read>) -> Result<Vec<TxData>, ParserErr> {
-        let mut rdr = ReaderBuilder::new()
-            .has_headers(true)
-            .delimiter(b',')
-            .from_reader(reader);
+        Self::from_csv_reader_with(reader, &ParseOptions::default()).map(|outcome| outcome.records)
+    }
+
+    fn from_csv_reader_with(
+        reader: Box<dyn Read>,
+        options: &ParseOptions,
+    ) -> Result<ParseOutcome, ParserErr> {
+        let outcome = CsvSource::new(reader, options.clone())?.next_batch(usize::MAX)?;
+        Ok(outcome.unwrap_or_default())
+    }
+}
+
+/// Потоковый разбор CSV: заголовок проверяется при создании, строки читаются порциями.
+pub(crate) struct CsvSource {
+    records: StringRecordsIntoIter<BufReader<Box<dyn Read>>>,
+    /// Номер следующей строки (заголовок — первая)
+    line: usize,
+    /// Сколько строк входа пропущено до заголовка (`skip_rows` и строки сведений)
+    skipped_lines: usize,
+    /// Сколько строк данных уже прочитано (для `Provenance::record`)
+    record: usize,
+    /// Сколько байт входа прочитано до того, как его начал разбирать `csv` (BOM, пропущенные строки)
+    base_offset: u64,
+    /// Индексы колонок файла в каноническом порядке, если порядок или число колонок в файле другие
+    order: Option<Vec<usize>>,
+    /// Число колонок в заголовке файла
+    width: usize,
+    /// Строка заголовка: при продолжении с контрольной точки разбор начинается с неё
+    header: Vec<u8>,
+    /// Предупреждение о лишних колонках; выдаётся с первой порцией
+    header_warning: Option<ParseWarning>,
+    options: ParseOptions,
+    descriptions: DescriptionArena,
+}
+
+impl CsvSource {
+    pub(crate) fn new(reader: Box<dyn Read>, options: ParseOptions) -> Result<Self, ParserErr> {
+        let skip_rows = options.csv.skip_rows;
+        // Канал может отдать начало файла по байту, поэтому BOM дочитывается целиком
+        let mut reader = reader;
+        let mut head = Vec::new();
+        (&mut reader)
+            .take(UTF8_BOM.len() as u64)
+            .read_to_end(&mut head)
+            .map_err(|e| ParserErr::from(e).at_line(1))?;
+        let mut base_offset = 0;
+        if head == UTF8_BOM {
+            head.clear();
+            base_offset = UTF8_BOM.len() as u64;
+        }
+        let mut reader = BufReader::new(Box::new(Cursor::new(head).chain(reader)) as Box<dyn Read>);
+        let mut row = Vec::new();
+        for line in 1..=skip_rows {
+            row.clear();
+            let read = reader.read_until(b'\n', &mut row).map_err(|e| ParserErr::from(e).at_line(line))?;
+            if read == 0 {
+                break;
+            }
+            base_offset += read as u64;
+        }
+        // Сведения о записавшей файл программе (см. `ProducerMetadata`) идут перед заголовком
+        let mut header_line = skip_rows + 1;
+        while reader
+            .fill_buf()
+            .map_err(|e| ParserErr::from(e).at_line(header_line))?
+            .starts_with(METADATA_PREFIX.as_bytes())
+        {
+            row.clear();
+            let read = reader.read_until(b'\n', &mut row).map_err(|e| ParserErr::from(e).at_line(header_line))?;
+            base_offset += read as u64;
+            header_line += 1;
+        }
+
+        let mut rdr = csv_reader(reader, &options.csv);
+        let header = encode_header(&mut rdr).map_err(|e| csv_err(e).at_line(header_line))?;
 
         let actual_headers = rdr
             .headers()
-            .map_err(|e| ParserErr::ParseErr {
-                msg: format!("Failed to read CSV header: {}", e),
-            })?
+            .map_err(|e| csv_err(e).at_line(header_line))?
             .iter()
             .collect::<Vec<_>>();
 
-        if actual_headers != CSV_HEADERS {
-            return Err(ParserErr::ParseErr {
-                msg: format!(
-                    "Invalid CSV header. Expected: {:?}, got: {:?}",
-                    CSV_HEADERS, actual_headers
-                ),
+        let (columns, extra) = if options.csv.allow_extra_columns && actual_headers.len() > CSV_HEADERS.len() {
+            actual_headers.split_at(CSV_HEADERS.len())
+        } else {
+            (actual_headers.as_slice(), &[][..])
+        };
+
+        let order = if columns == CSV_HEADERS && extra.is_empty() {
+            None
+        } else if columns == CSV_HEADERS || (options.csv.reorder_ok && is_permutation(columns)) {
+            let position = |name| columns.iter().position(|h| *h == name).unwrap_or_default();
+            Some(CSV_HEADERS.iter().map(|name| position(*name)).collect())
+        } else {
+            return Err(ParserErr::InvalidHeader {
+                expected: CSV_HEADER_LINE.to_string(),
+                actual: actual_headers.join(","),
             });
-        }
+        };
+
+        let header_warning = if extra.is_empty() {
+            None
+        } else {
+            let kind = WarningKind::ExtraColumns {
+                columns: extra.join(","),
+            };
+            if options.warnings_as_errors {
+                return Err(ParserErr::Escalated { warning: kind }.at_line(1));
+            }
+            Some(ParseWarning::at(1, kind))
+        };
 
-        let mut transactions = Vec::new();
-        for (i, result) in rdr.records().enumerate() {
-            let record = result.map_err(|e| ParserErr::ParseErr {
-                msg: format!("CSV parse error on row {}: {}", i + 2, e),
-            })?;
+        Ok(CsvSource {
+            width: actual_headers.len(),
+            header,
+            records: rdr.into_records(),
+            line: header_line + 1,
+            skipped_lines: header_line - 1,
+            record: 0,
+            base_offset,
+            order,
+            header_warning,
+            options,
+            descriptions: DescriptionArena::default(),
+        })
+    }
+}
+
+impl RecordSource for CsvSource {
+    fn next_batch(&mut self, max: usize) -> Result<Option<ParseOutcome>, ParserErr> {
+        let options = &self.options;
+        let mut outcome = ParseOutcome::default();
+        outcome.warnings.extend(self.header_warning.take());
+        let mut any = !outcome.warnings.is_empty();
+        while !batch_full(&outcome, max) {
+            let Some(result) = self.records.next() else { break };
+            any = true;
+            let line = self.line;
+            self.line += 1;
+
+            let record = match result {
+                Ok(record) => record,
+                Err(e) if e.is_io_error() => return Err(csv_err(e).at_line(line)),
+                Err(e) => {
+                    self.record += 1;
+                    options.recover(csv_err(e).at_line(line), &mut outcome.issues)?;
+                    continue;
+                }
+            };
+            // Строки-комментарии не попадают в записи, поэтому номер берётся из позиции разбора
+            let line = record
+                .position()
+                .map_or(line, |p| p.line() as usize + self.skipped_lines);
+            self.line = line + 1;
             if record.iter().all(|f| f.is_empty()) {
                 continue;
             }
-            let tx = from_csv_record(&record).map_err(|e| ParserErr::ParseErr {
-                msg: format!("Field error on row {}: {}", i + 2, e),
-            })?;
-            transactions.push(tx);
+            self.record += 1;
+            let pos = RecordPos {
+                record: self.record,
+                line,
+                offset: record.position().map(|p| self.base_offset + p.byte()),
+            };
+            let record = match &self.order {
+                // Строку с другим числом полей отклонит from_csv_record
+                Some(order) if record.len() == self.width => order.iter().map(|&i| &record[i]).collect(),
+                _ => record,
+            };
+            match from_csv_record(&record, &options.csv) {
+                Ok(tx) => {
+                    let mut warnings = Vec::new();
+                    if tx.description.len() != tx.description.trim_end().len() {
+                        warnings.push(ParseWarning::at(line, WarningKind::TrailingWhitespace));
+                    }
+                    options.accept(tx, &mut self.descriptions, pos, warnings, &mut outcome)?;
+                }
+                Err(e) => options.recover(e.at_line(line), &mut outcome.issues)?,
+            }
         }
-        Ok(transactions)
+        Ok(any.then_some(outcome))
+    }
+
+    fn checkpoint(&self) -> Option<ReadCheckpoint> {
+        // Запись может занимать несколько строк, поэтому строки считаются по позиции разбора
+        let position = self.records.reader().position();
+        Some(ReadCheckpoint {
+            offset: self.base_offset + position.byte(),
+            lines: position.line() as usize + self.skipped_lines - 1,
+            records: self.record,
+            ..ReadCheckpoint::default()
+        })
+    }
+
+    fn resume(&mut self, reader: Box<dyn Read>, at: &ReadCheckpoint) -> bool {
+        // Перед продолжением снова идёт заголовок: так `csv` проверяет число полей по нему же
+        let (Some(base_offset), Some(skipped_lines)) =
+            (at.offset.checked_sub(self.header.len() as u64), at.lines.checked_sub(1))
+        else {
+            return false;
+        };
+        let reader = Box::new(Cursor::new(self.header.clone()).chain(reader)) as Box<dyn Read>;
+        self.records = csv_reader(BufReader::new(reader), &self.options.csv).into_records();
+        self.base_offset = base_offset;
+        self.skipped_lines = skipped_lines;
+        self.line = at.lines + 1;
+        self.record = at.records;
+        self.header_warning = None;
+        true
     }
 }
 
-fn from_csv_record(record: &StringRecord) -> Result<TxData, ParserErr> {
-    if record.len() != 8 {
-        return Err(ParserErr::ParseErr {
-            msg: format!("Expected 8 fields, got {}", record.len()),
+fn csv_reader(reader: BufReader<Box<dyn Read>>, options: &CsvOptions) -> csv::Reader<BufReader<Box<dyn Read>>> {
+    ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(b',')
+        .comment(options.comment_prefix)
+        .from_reader(reader)
+}
+
+/// Заголовок, прочитанный `rdr`, одной строкой CSV.
+fn encode_header(rdr: &mut csv::Reader<BufReader<Box<dyn Read>>>) -> Result<Vec<u8>, csv::Error> {
+    let mut header = WriterBuilder::new().from_writer(Vec::new());
+    header.write_byte_record(rdr.byte_headers()?)?;
+    header.into_inner().map_err(|e| e.into_error().into())
+}
+
+/// Те же колонки, что в спецификации, каждая по одному разу.
+fn is_permutation(headers: &[&str]) -> bool {
+    let mut sorted = headers.to_vec();
+    sorted.sort_unstable();
+    let mut canonical = CSV_HEADERS.to_vec();
+    canonical.sort_unstable();
+    sorted == canonical
+}
+
+/// Разбирает строку CSV в каноническом порядке колонок.
+pub(crate) fn from_csv_record(record: &StringRecord, options: &CsvOptions) -> Result<TxData, ParserErr> {
+    if record.len() != CSV_HEADERS.len() {
+        return Err(ParserErr::FieldCount {
+            expected: CSV_HEADERS.len(),
+            actual: record.len(),
         });
     }
 
-    let tx_id = record[0].parse().map_err(|_| ParserErr::ParseErr {
-        msg: "Invalid TX_ID".into(),
-    })?;
+    // Пустое описание проверяется политикой в `ParseOptions::accept`
+    if let Some(field) = CSV_HEADERS[..7].iter().enumerate().find_map(|(i, name)| record[i].is_empty().then_some(*name)) {
+        return Err(ParserErr::EmptyField { field });
+    }
+
+    let tx_id = TxId::parse(&record[0])?;
     let tx_type = parse_tx_type_str(&record[1])?;
-    let from_user_id = record[2].parse().map_err(|_| ParserErr::ParseErr {
-        msg: "Invalid from_user_id".into(),
-    })?;
-    let to_user_id = record[3].parse().map_err(|_| ParserErr::ParseErr {
-        msg: "Invalid to_user_id".into(),
-    })?;
-    let amount = record[4].parse().map_err(|_| ParserErr::ParseErr {
-        msg: "Invalid amount".into(),
-    })?;
-    let timestamp = record[5].parse().map_err(|_| ParserErr::ParseErr {
-        msg: "Invalid TIMESTAMP".into(),
-    })?;
+    let from_user_id = parse_number("FROM_USER_ID", &record[2])?;
+    let to_user_id = parse_number("TO_USER_ID", &record[3])?;
+    let amount = match options.amount_locale {
+        Some(locale) => locale.parse(&record[4])?,
+        None => parse_number("AMOUNT", &record[4])?,
+    };
+    let timestamp = parse_number("TIMESTAMP", &record[5])?;
     let status = parse_status_str(&record[6])?;
     let description = record[7].to_string();
 
     Ok(TxData {
-        tx_id: tx_id,
-        tx_type: tx_type,
-        from_user_id: from_user_id,
-        to_user_id: to_user_id,
-        amount: amount,
-        timestamp: timestamp,
-        status: status,
-        description: description,
+        tx_id,
+        tx_type,
+        from_user_id,
+        to_user_id,
+        amount,
+        timestamp,
+        status,
+        description: description.into(),
         format: Format::YpBankCsv,
     })
 }
@@ -221,8 +471,9 @@ fn parse_tx_type_str(s: &str) -> Result<TxType, ParserErr> {
         "DEPOSIT" => Ok(TxType::Deposit),
         "TRANSFER" => Ok(TxType::Transfer),
         "WITHDRAWAL" => Ok(TxType::Withdrawal),
-        _ => Err(ParserErr::ParseErr {
-            msg: format!("Invalid TX_TYPE: {}", s),
+        _ => Err(ParserErr::InvalidValue {
+            field: "TX_TYPE",
+            value: s.to_string(),
         }),
     }
 }
@@ -232,76 +483,208 @@ fn parse_status_str(s: &str) -> Result<Status, ParserErr> {
         "SUCCESS" => Ok(Status::Success),
         "FAILURE" => Ok(Status::Failure),
         "PENDING" => Ok(Status::Pending),
-        _ => Err(ParserErr::ParseErr {
-            msg: format!("Invalid STATUS: {}", s),
+        _ => Err(ParserErr::InvalidValue {
+            field: "STATUS",
+            value: s.to_string(),
         }),
     }
 }
 
+/// Переводит ошибку крейта `csv` в [`ParserErr`], сохраняя `io::Error` как источник.
+pub(crate) fn csv_err(e: csv::Error) -> ParserErr {
+    if !e.is_io_error() {
+        return ParserErr::Malformed { msg: e.to_string() };
+    }
+    match e.into_kind() {
+        csv::ErrorKind::Io(io) => io.into(),
+        kind => ParserErr::Malformed {
+            msg: format!("{:?}", kind),
+        },
+    }
+}
+
 impl TxnToCsv for TxData {
     fn to_csv(&self) -> Result<String, ParserErr> {
-        let tx_type_str = match self.tx_type {
-            TxType::Deposit => "DEPOSIT",
-            TxType::Transfer => "TRANSFER",
-            TxType::Withdrawal => "WITHDRAWAL",
-            _ => {
-                return Err(ParserErr::ParseErr {
-                    msg: format!("Invalid TYPE: {:?}", &self.tx_type),
-                });
-            }
-        };
+        let mut out = Vec::new();
+        self.to_csv_writer(&mut out)?;
+        String::from_utf8(out).map_err(|source| ParserErr::InvalidUtf8 {
+            field: "DESCRIPTION",
+            source,
+        })
+    }
 
-        let status_str = match self.status {
-            Status::Success => "SUCCESS",
-            Status::Failure => "FAILURE",
-            Status::Pending => "PENDING",
-            _ => {
-                return Err(ParserErr::ParseErr {
-                    msg: format!("Invalid STATUS: {:?}", &self.tx_type),
-                });
-            }
-        };
+    fn to_csv_many(transactions: &[Self]) -> Result<String, ParserErr> {
+        let mut out = Vec::with_capacity(estimated_len(transactions, CSV_ROW_LEN));
+        Self::to_csv_writer_many(transactions, &mut out)?;
+        String::from_utf8(out).map_err(|source| ParserErr::InvalidUtf8 {
+            field: "DESCRIPTION",
+            source,
+        })
+    }
+}
 
+impl TxnWriteToCsv for TxData {
+    fn to_csv_writer(&self, w: &mut dyn Write) -> Result<(), ParserErr> {
+        let mut writer = csv_writer(Vec::new(), QuoteStyle::default());
+        write_record(&mut writer, self, &[], QuoteStyle::default())?;
+        let mut line = writer.into_inner().map_err(|e| ParserErr::from(e.into_error()))?;
+        line.pop();
+        w.write_all(&line)?;
+        Ok(())
+    }
 
-        let desc_escaped = escape_csv_field(&self.description);
-        let desc_quoted = format!("\"{}\"", desc_escaped);
+    fn to_csv_writer_many(transactions: &[Self], w: &mut dyn Write) -> Result<(), ParserErr> {
+        let mut sink = CsvSink::default();
+        sink.write_batch(w, transactions, &[])?;
+        sink.finish(w)
+    }
+}
 
-        Ok(format!(
-            "{},{},{},{},{},{},{},{}",
-            self.tx_id,
-            tx_type_str,
-            self.from_user_id,
-            self.to_user_id,
-            self.amount,
-            self.timestamp,
-            status_str,
-            desc_quoted
-        ))
+/// CSV-писатель без заголовка со строками через `\n`.
+fn csv_writer<W: Write>(w: W, quote_style: QuoteStyle) -> csv::Writer<W> {
+    let quote_style = match quote_style {
+        QuoteStyle::Always => csv::QuoteStyle::Always,
+        QuoteStyle::Necessary => csv::QuoteStyle::Necessary,
+        QuoteStyle::Never => csv::QuoteStyle::Never,
+    };
+    WriterBuilder::new()
+        .has_headers(false)
+        .quote_style(quote_style)
+        .terminator(Terminator::Any(b'\n'))
+        .from_writer(w)
+}
+
+/// Пишет запись в порядке колонок `CSV_HEADERS`, а за ними — значения `joined`.
+///
+/// Без кавычек (`QuoteStyle::Never`) описание с запятой, кавычкой или переводом строки
+/// не записать однозначно, поэтому это ошибка.
+fn write_record<W: Write>(
+    writer: &mut csv::Writer<W>,
+    tx: &TxData,
+    joined: &[&str],
+    quote_style: QuoteStyle,
+) -> Result<(), ParserErr> {
+    if quote_style == QuoteStyle::Never && tx.description.contains([',', '"', '\n', '\r']) {
+        return Err(ParserErr::InvalidValue {
+            field: "DESCRIPTION",
+            value: tx.description.to_string(),
+        });
     }
 
-    fn to_csv_many(transactions: &[Self]) -> Result<String, ParserErr> {
-        let mut output = String::from(CSV_HEADER_LINE);
-        output.push('\n');
-        for tx in transactions {
-            output.push_str(&tx.to_csv()?);
-            output.push('\n');
+    let (tx_id, tx_type, status) = (tx.tx_id.to_string(), tx.tx_type.name(), tx.status.name());
+    let fields = [
+        tx_id.as_str(),
+        tx_type,
+        &tx.from_user_id.to_string(),
+        &tx.to_user_id.to_string(),
+        &tx.amount.to_string(),
+        &tx.timestamp.to_string(),
+        status,
+        &tx.description,
+    ];
+    writer.write_record(fields.iter().chain(joined)).map_err(csv_err)
+}
+
+/// Потоковая запись CSV: заголовок пишется один раз перед первой порцией.
+#[derive(Default)]
+pub(crate) struct CsvSink {
+    header_written: bool,
+    /// Писать перед заголовком UTF-8 BOM
+    bom: bool,
+    quote_style: QuoteStyle,
+    /// Сведения о программе, которые пишутся строками `#@` перед заголовком
+    metadata: Option<ProducerMetadata>,
+    /// Справочник, колонки которого пишутся после `DESCRIPTION`
+    #[cfg(feature = "enrich")]
+    join_users: Option<Arc<UserReference>>,
+}
+
+impl CsvSink {
+    pub(crate) fn new(bom: bool, quote_style: QuoteStyle, metadata: Option<ProducerMetadata>) -> Self {
+        CsvSink {
+            header_written: false,
+            bom,
+            quote_style,
+            metadata,
+            #[cfg(feature = "enrich")]
+            join_users: None,
         }
-        Ok(output)
+    }
+
+    /// Добавлять к записям колонки из справочника `reference`.
+    #[cfg(feature = "enrich")]
+    pub(crate) fn join_users(mut self, reference: Option<Arc<UserReference>>) -> Self {
+        self.join_users = reference;
+        self
+    }
+
+    /// Названия колонок из справочника; пусто без него.
+    fn joined_columns(&self) -> Vec<String> {
+        #[cfg(feature = "enrich")]
+        if let Some(reference) = &self.join_users {
+            return reference.joined_columns();
+        }
+        Vec::new()
+    }
+
+    /// Значения колонок из справочника для записи `tx`; пусто без него.
+    #[cfg_attr(not(feature = "enrich"), allow(unused_variables))]
+    fn joined<'a>(&'a self, tx: &TxData) -> Vec<&'a str> {
+        #[cfg(feature = "enrich")]
+        if let Some(reference) = &self.join_users {
+            return reference.join(tx);
+        }
+        Vec::new()
+    }
+
+    fn header(&mut self, w: &mut dyn Write) -> Result<(), ParserErr> {
+        if !std::mem::replace(&mut self.header_written, true) {
+            if self.bom {
+                w.write_all(UTF8_BOM)?;
+            }
+            for line in self.metadata.iter().flat_map(ProducerMetadata::comment_lines) {
+                writeln!(w, "{}", line)?;
+            }
+            let mut writer = csv_writer(w, self.quote_style);
+            let joined = self.joined_columns();
+            writer.write_record(CSV_HEADERS.iter().copied().chain(joined.iter().map(String::as_str))).map_err(csv_err)?;
+            writer.flush()?;
+        }
+        Ok(())
     }
 }
 
-fn escape_csv_field(s: &str) -> String {
-    if s.contains('"') || s.contains(',') || s.contains('\n') {
-        let escaped = s.replace('"', "\"\"");
-        escaped
-    } else {
-        s.to_string()
+impl RecordSink for CsvSink {
+    fn write_batch(
+        &mut self,
+        w: &mut dyn Write,
+        records: &[TxData],
+        _annotations: &[TextAnnotation],
+    ) -> Result<(), ParserErr> {
+        self.header(w)?;
+        let mut writer = csv_writer(w, self.quote_style);
+        for tx in records {
+            write_record(&mut writer, tx, &self.joined(tx), self.quote_style)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self, w: &mut dyn Write) -> Result<(), ParserErr> {
+        // Пустой документ — это один заголовок
+        self.header(w)
+    }
+
+    fn resume(&mut self, checkpoint: &WriteCheckpoint) -> bool {
+        self.header_written = checkpoint.bytes > 0;
+        true
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model::amount::AmountLocale;
     use std::io::Cursor;
 
     #[test]
@@ -316,7 +699,7 @@ mod tests {
         assert_eq!(tx.amount, 100);
         assert_eq!(tx.timestamp, 1700000000);
         assert_eq!(tx.status, Status::Success);
-        assert_eq!(tx.description, "Test transfer");
+        assert_eq!(&*tx.description, "Test transfer");
         assert_eq!(tx.format, Format::YpBankCsv);
     }
 
@@ -338,8 +721,8 @@ mod tests {
     fn test_from_csv_many_empty() {
         let lines: Vec<String> = vec![];
         let err = TxData::from_csv_many(&lines).unwrap_err();
-        if let ParserErr::ParseErr { msg } = err {
-            assert!(msg.contains("CSV is empty"));
+        if let ParserErr::InvalidHeader { actual, .. } = err {
+            assert!(actual.is_empty());
         } else {
             panic!()
         }
@@ -351,8 +734,8 @@ mod tests {
             "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS".to_string(), // missing DESCRIPTION
         ];
         let err = TxData::from_csv_many(&lines).unwrap_err();
-        if let ParserErr::ParseErr { msg } = err {
-            assert!(msg.contains("Invalid header"));
+        if let ParserErr::InvalidHeader { expected, .. } = err {
+            assert_eq!(expected, CSV_HEADER_LINE);
         } else {
             panic!()
         }
@@ -360,7 +743,7 @@ mod tests {
 
     #[test]
     fn test_from_csv_reader_valid() {
-        let csv_content = vec![
+        let csv_content = [
             CSV_HEADER_LINE.to_string(),
             "1000000000000012,DEPOSIT,0,9223372036854775807,1300,1633037580000,FAILURE,\"Record number 13\"".to_string()
         ];
@@ -372,7 +755,7 @@ mod tests {
         let txs = TxData::from_csv_reader(reader).unwrap();
         assert_eq!(txs.len(), 1);
         assert_eq!(txs[0].tx_id, 1000000000000012);
-        assert_eq!(txs[0].description, "Record number 13");
+        assert_eq!(&*txs[0].description, "Record number 13");
     }
 
     #[test]
@@ -380,45 +763,249 @@ mod tests {
         let csv_content = "TX_ID,FROM_USER_ID,TX_TYPE,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n1,100,TRANSFER,200,50,1700000000,SUCCESS,\"ok\"";
         let reader = Box::new(Cursor::new(csv_content));
         let err = TxData::from_csv_reader(reader).unwrap_err();
-        if let ParserErr::ParseErr { msg } = err {
-            assert!(msg.contains("Invalid CSV header"));
+        if let ParserErr::InvalidHeader { actual, .. } = err {
+            assert!(actual.starts_with("TX_ID,FROM_USER_ID,TX_TYPE"));
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn test_from_csv_reader_reorder_ok() {
+        let csv_content = "TX_ID,FROM_USER_ID,TX_TYPE,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n1,100,TRANSFER,200,50,1700000000,SUCCESS,\"ok\"";
+        let mut options = ParseOptions::default();
+        options.csv.reorder_ok = true;
+
+        let outcome = TxData::from_csv_reader_with(Box::new(Cursor::new(csv_content)), &options).unwrap();
+        assert_eq!(outcome.records[0].tx_type, TxType::Transfer);
+        assert_eq!(outcome.records[0].from_user_id, 100);
+
+        // Неизвестная колонка вместо канонической по-прежнему ошибка
+        let csv_content = "TX_ID,FROM_USER_ID,TX_TYPE,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,NOTE\n";
+        let err = TxData::from_csv_reader_with(Box::new(Cursor::new(csv_content)), &options).unwrap_err();
+        assert!(matches!(err, ParserErr::InvalidHeader { .. }));
+    }
+
+    #[test]
+    fn test_from_csv_number_overflow() {
+        let err = TxData::from_csv("1,DEPOSIT,0,10,9223372036854775808,1700000000,SUCCESS,\"x\"").unwrap_err();
+        if let ParserErr::NumberOverflow { field, value } = err {
+            assert_eq!(field, "AMOUNT");
+            assert_eq!(value, "9223372036854775808");
         } else {
             panic!()
         }
     }
 
+    #[test]
+    fn test_quote_styles() {
+        let tx = TxData::from_csv("1,DEPOSIT,0,10,100,1700000000,SUCCESS,\"say \"\"hi\"\", ok\"").unwrap();
+        let write = |quote_style| {
+            let mut out = Vec::new();
+            let mut sink = CsvSink::new(false, quote_style, None);
+            sink.write_batch(&mut out, std::slice::from_ref(&tx), &[]).map(|_| String::from_utf8(out).unwrap())
+        };
+
+        let necessary = write(QuoteStyle::Necessary).unwrap();
+        assert!(necessary.ends_with("\n1,DEPOSIT,0,10,100,1700000000,SUCCESS,\"say \"\"hi\"\", ok\"\n"));
+        let always = write(QuoteStyle::Always).unwrap();
+        assert!(always.starts_with("\"TX_ID\",\"TX_TYPE\","));
+        assert!(always.ends_with("\n\"1\",\"DEPOSIT\",\"0\",\"10\",\"100\",\"1700000000\",\"SUCCESS\",\"say \"\"hi\"\", ok\"\n"));
+        assert_eq!(TxData::from_csv_reader(Box::new(Cursor::new(always))).unwrap()[0], tx);
+
+        let err = write(QuoteStyle::Never).unwrap_err();
+        assert!(matches!(err, ParserErr::InvalidValue { field: "DESCRIPTION", .. }));
+    }
+
+    #[test]
+    fn test_bom_round_trip() {
+        let tx = TxData::from_csv("1,DEPOSIT,0,10,100,1700000000,SUCCESS,\"x\"").unwrap();
+        let mut out = Vec::new();
+        let mut sink = CsvSink::new(true, QuoteStyle::Necessary, None);
+        sink.write_batch(&mut out, std::slice::from_ref(&tx), &[]).unwrap();
+        sink.finish(&mut out).unwrap();
+        assert!(out.starts_with(UTF8_BOM));
+
+        let txs = TxData::from_csv_reader(Box::new(Cursor::new(out.clone()))).unwrap();
+        assert_eq!(txs[0], tx);
+        let lines: Vec<String> = String::from_utf8(out).unwrap().lines().map(String::from).collect();
+        assert_eq!(TxData::from_csv_many(&lines).unwrap(), [tx]);
+    }
+
+    #[test]
+    fn test_from_csv_reader_skip_rows_and_comments() {
+        let csv_content = format!(
+            "Generated by export v2\n==========\n# exported 2024-01-01\n{}\n# first\n1,DEPOSIT,0,10,100,1700000000,SUCCESS,\"x\"\n2,DEPOSIT,0,10,oops,1700000000,SUCCESS,\"y\"\n",
+            CSV_HEADER_LINE
+        );
+        let mut options = ParseOptions::default();
+        options.csv.skip_rows = 2;
+        options.csv.comment_prefix = Some(b'#');
+
+        let err = TxData::from_csv_reader_with(Box::new(Cursor::new(csv_content)), &options).unwrap_err();
+        // Номер строки считается от начала файла, включая баннер и комментарии
+        if let ParserErr::AtLine { line, .. } = err {
+            assert_eq!(line, 7);
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn test_from_csv_reader_extra_columns() {
+        let csv_content = format!("{},NOTE,REF\n1,DEPOSIT,0,10,100,1700000000,SUCCESS,\"x\",n,r\n", CSV_HEADER_LINE);
+        let err = TxData::from_csv_reader(Box::new(Cursor::new(csv_content.clone()))).unwrap_err();
+        assert!(matches!(err, ParserErr::InvalidHeader { .. }));
+
+        let mut options = ParseOptions::default();
+        options.csv.allow_extra_columns = true;
+        let outcome = TxData::from_csv_reader_with(Box::new(Cursor::new(csv_content)), &options).unwrap();
+        assert_eq!(outcome.records.len(), 1);
+        assert_eq!(&*outcome.records[0].description, "x");
+        assert_eq!(
+            outcome.warnings,
+            [ParseWarning::at(1, WarningKind::ExtraColumns { columns: "NOTE,REF".to_string() })]
+        );
+    }
+
+    #[test]
+    fn test_from_csv_reader_invalid_amount_keeps_line_and_source() {
+        use std::error::Error;
+
+        let csv_content = format!(
+            "{}\n1,DEPOSIT,0,10,100,1700000030,SUCCESS,\"ok\"\n2,DEPOSIT,0,10,lots,1700000030,SUCCESS,\"bad\"",
+            CSV_HEADER_LINE
+        );
+        let reader = Box::new(Cursor::new(csv_content));
+        let err = TxData::from_csv_reader(reader).unwrap_err();
+
+        assert_eq!(err.line(), Some(3));
+        if let ParserErr::InvalidNumber { field, value, .. } = err.root() {
+            assert_eq!(*field, "AMOUNT");
+            assert_eq!(value, "lots");
+        } else {
+            panic!()
+        }
+        let source = err.source().and_then(|e| e.source());
+        assert!(source.is_some_and(|e| e.is::<std::num::ParseIntError>()));
+    }
+
+    #[test]
+    fn test_from_csv_reader_collect_mode_skips_bad_rows() {
+        let csv_content = format!(
+            "{}\n1,DEPOSIT,0,10,100,1700000030,SUCCESS,\"ok\"\n2,GIFT,0,10,1,1700000030,SUCCESS,\"bad\"\n3,DEPOSIT,0,10\n4,DEPOSIT,0,10,400,1700000030,SUCCESS,\"ok\"",
+            CSV_HEADER_LINE
+        );
+        let reader = Box::new(Cursor::new(csv_content));
+        let outcome = TxData::from_csv_reader_with(reader, &ParseOptions::collect()).unwrap();
+        let issues = outcome.issues;
+
+        assert_eq!(outcome.records.iter().filter_map(|tx| tx.tx_id.as_u64()).collect::<Vec<_>>(), vec![1, 4]);
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].line, Some(3));
+        assert_eq!(issues[0].field, Some("TX_TYPE"));
+        assert_eq!(issues[1].line, Some(4));
+    }
+
+    #[test]
+    fn test_from_csv_reader_collect_mode_still_rejects_header() {
+        let reader = Box::new(Cursor::new("TX_ID,TX_TYPE\n1,DEPOSIT"));
+        let err = TxData::from_csv_reader_with(reader, &ParseOptions::collect()).unwrap_err();
+        assert!(matches!(err, ParserErr::InvalidHeader { .. }));
+    }
+
+    #[test]
+    fn test_from_csv_reader_strict_mode_rejects_rule_violations() {
+        use crate::model::validation::RuleViolation;
+
+        let csv_content = format!(
+            "{}\n1,TRANSFER,5,5,100,1700000030,SUCCESS,\"self\"\n2,DEPOSIT,0,10,100,1700000030,SUCCESS,\"ok\"",
+            CSV_HEADER_LINE
+        );
+        let lenient = TxData::from_csv_reader(Box::new(Cursor::new(csv_content.clone()))).unwrap();
+        assert_eq!(lenient.len(), 2);
+
+        let options = ParseOptions {
+            strict: true,
+            ..ParseOptions::collect()
+        };
+        let outcome = TxData::from_csv_reader_with(Box::new(Cursor::new(csv_content)), &options).unwrap();
+
+        assert_eq!(outcome.records.len(), 1);
+        assert_eq!(outcome.issues[0].line, Some(2));
+        assert_eq!(outcome.issues[0].field, Some("TO_USER_ID"));
+        assert!(matches!(
+            outcome.issues[0].error.root(),
+            ParserErr::Rule {
+                violation: RuleViolation::SelfTransfer { user_id: 5 }
+            }
+        ));
+    }
+
+    #[test]
+    fn test_from_csv_reader_normalizes_timestamp_units() {
+        use crate::model::time::{TimeWindow, TimestampUnit};
+
+        // Секунды и миллисекунды вперемешку, как в наших примерах
+        let csv_content = format!(
+            "{}\n1,DEPOSIT,0,10,100,1700000000,SUCCESS,\"s\"\n2,DEPOSIT,0,10,100,1633037580000,SUCCESS,\"ms\"",
+            CSV_HEADER_LINE
+        );
+        let options = ParseOptions {
+            timestamp_unit: TimestampUnit::Auto,
+            // Отбор по времени видит уже приведённые метки
+            time_window: TimeWindow { since: Some(1633037580000), until: None },
+            ..ParseOptions::default()
+        };
+        let outcome = TxData::from_csv_reader_with(Box::new(Cursor::new(csv_content)), &options).unwrap();
+        let timestamps: Vec<u64> = outcome.records.iter().map(|tx| tx.timestamp).collect();
+        assert_eq!(timestamps, [1700000000000, 1633037580000]);
+    }
+
+    #[test]
+    fn test_from_csv_wrong_field_count() {
+        let err = TxData::from_csv("1,DEPOSIT,0,10,100").unwrap_err();
+        assert!(matches!(
+            err,
+            ParserErr::FieldCount {
+                expected: 8,
+                actual: 5
+            }
+        ));
+    }
+
     #[test]
     fn test_to_csv_simple() {
         let tx = TxData {
-            tx_id: 42,
+            tx_id: TxId::U64(42),
             tx_type: TxType::Withdrawal,
             from_user_id: 101,
             to_user_id: 0,
             amount: 30,
             timestamp: 1700000010,
             status: Status::Success,
-            description: "Cash out".to_string(),
+            description: "Cash out".into(),
             format: Format::YpBankCsv,
         };
 
         let csv = tx.to_csv().unwrap();
         assert_eq!(
             csv,
-            "42,WITHDRAWAL,101,0,30,1700000010,SUCCESS,\"Cash out\""
+            "42,WITHDRAWAL,101,0,30,1700000010,SUCCESS,Cash out"
         );
     }
 
     #[test]
     fn test_to_csv_description_needs_escaping() {
         let tx = TxData {
-            tx_id: 99,
+            tx_id: TxId::U64(99),
             tx_type: TxType::Transfer,
             from_user_id: 1,
             to_user_id: 2,
             amount: 10,
             timestamp: 1700000020,
             status: Status::Pending,
-            description: "Amount: \"10\", note: comma, and\nnewline".to_string(),
+            description: "Amount: \"10\", note: comma, and\nnewline".into(),
             format: Format::YpBankCsv,
         };
 
@@ -433,46 +1020,52 @@ mod tests {
     fn test_to_csv_many() {
         let txs = vec![
             TxData {
-                tx_id: 1,
+                tx_id: TxId::U64(1),
                 tx_type: TxType::Deposit,
                 from_user_id: 0,
                 to_user_id: 10,
                 amount: 100,
                 timestamp: 1700000030,
                 status: Status::Success,
-                description: "Bonus".to_string(),
+                description: "Bonus".into(),
                 format: Format::YpBankCsv,
             },
             TxData {
-                tx_id: 2,
+                tx_id: TxId::U64(2),
                 tx_type: TxType::Transfer,
                 from_user_id: 10,
                 to_user_id: 20,
                 amount: 25,
                 timestamp: 1700000040,
                 status: Status::Failure,
-                description: "Blocked".to_string(),
+                description: "Blocked".into(),
                 format: Format::YpBankCsv,
             },
         ];
 
         let csv = TxData::to_csv_many(&txs).unwrap();
         let expected = format!(
-            "{}\n1,DEPOSIT,0,10,100,1700000030,SUCCESS,\"Bonus\"\n2,TRANSFER,10,20,25,1700000040,FAILURE,\"Blocked\"\n",
+            "{}\n1,DEPOSIT,0,10,100,1700000030,SUCCESS,Bonus\n2,TRANSFER,10,20,25,1700000040,FAILURE,Blocked\n",
             CSV_HEADER_LINE
         );
         assert_eq!(csv, expected);
     }
 
     #[test]
-    fn test_escape_csv_field() {
-        assert_eq!(escape_csv_field("plain"), "plain");
-        assert_eq!(escape_csv_field("with,comma"), "with,comma");
-        assert_eq!(escape_csv_field("with\nnewline"), "with\nnewline");
-        assert_eq!(escape_csv_field(r#"say "hello""#), r#"say ""hello"""#);
-        assert_eq!(
-            escape_csv_field("mixed \"quotes\", commas, and\nnewlines"),
-            "mixed \"\"quotes\"\", commas, and\nnewlines"
-        );
+    fn test_from_csv_reader_with_amount_locale() {
+        let csv_content = format!("{}\n1,DEPOSIT,0,10,\"1.000.000,50\",1700000000,SUCCESS,\"x\"\n", CSV_HEADER_LINE);
+        let mut options = ParseOptions::default();
+        options.csv.amount_locale = Some(AmountLocale::De);
+
+        let outcome = TxData::from_csv_reader_with(Box::new(Cursor::new(csv_content.clone())), &options).unwrap();
+        assert_eq!(outcome.records[0].amount, 100_000_050);
+
+        // Без явной локали сумма не угадывается
+        let err = TxData::from_csv_reader(Box::new(Cursor::new(csv_content))).unwrap_err();
+        if let ParserErr::AtLine { source, .. } = err {
+            assert!(matches!(*source, ParserErr::InvalidNumber { field: "AMOUNT", .. }));
+        } else {
+            panic!()
+        }
     }
 }
@@ -1,3 +1,132 @@
+#[cfg(feature = "bin")]
+pub mod bin_index;
+#[cfg(feature = "bin")]
 pub mod bin_psrser;
+#[cfg(feature = "csv")]
 pub mod csv_parser;
-pub mod text_parser;
\ No newline at end of file
+#[cfg(feature = "text")]
+pub mod text_parser;
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::sync::Arc;
+#[cfg(any(feature = "csv", feature = "text"))]
+use std::{num::{IntErrorKind, ParseIntError}, str::FromStr};
+
+use crate::model::data::TxData;
+use crate::model::errors::ParserErr;
+use crate::parser::options::{ParseOutcome, ReadCheckpoint, TextAnnotation, WriteCheckpoint};
+
+/// Разбирает числовое поле текстовых форматов, сохраняя имя поля и исходную ошибку.
+///
+/// Число, не помещающееся в тип поля, — отдельная ошибка `ParserErr::NumberOverflow`.
+#[cfg(any(feature = "csv", feature = "text"))]
+pub(crate) fn parse_number<T>(field: &'static str, value: &str) -> Result<T, ParserErr>
+where
+    T: FromStr<Err = ParseIntError>,
+{
+    value.parse().map_err(|source: ParseIntError| match source.kind() {
+        IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => ParserErr::NumberOverflow {
+            field,
+            value: value.to_string(),
+        },
+        _ => ParserErr::InvalidNumber {
+            field,
+            value: value.to_string(),
+            source,
+        },
+    })
+}
+
+/// Источник записей, читающий входные данные порциями.
+pub(crate) trait RecordSource {
+    /// Читает порцию, в которой записей и пропущенных проблем не больше `max`.
+    ///
+    /// Номера строк в проблемах и предупреждениях сквозные для всего потока;
+    /// `TextAnnotation::before_record` отсчитывается от начала порции.
+    /// Возвращает `None`, когда входные данные закончились.
+    fn next_batch(&mut self, max: usize) -> Result<Option<ParseOutcome>, ParserErr>;
+
+    /// Сообщает, сколько записей ожидается во входных данных (например, по индексу
+    /// бинарного файла), чтобы порции выделяли память сразу под нужное число записей.
+    fn expect_records(&mut self, _count: usize) {}
+
+    /// Место на границе записей после уже выданных порций; `None`, если продолжить
+    /// с него нельзя (сжатые блоки, запись разорвана порцией, данные закончились).
+    /// Окно чтения в нём не заполняется.
+    fn checkpoint(&self) -> Option<ReadCheckpoint> {
+        None
+    }
+
+    /// Продолжает разбор из `reader`, уже перемотанного на `at.offset`; заголовок входа
+    /// источник к этому моменту уже прочитал. `false`, если источник так не умеет.
+    fn resume(&mut self, _reader: Box<dyn Read>, _at: &ReadCheckpoint) -> bool {
+        false
+    }
+}
+
+/// Приёмник записей, сериализующий их порциями в один документ.
+pub(crate) trait RecordSink {
+    /// Записывает порцию; `annotations` отсчитываются от начала порции.
+    fn write_batch(
+        &mut self,
+        w: &mut dyn Write,
+        records: &[TxData],
+        annotations: &[TextAnnotation],
+    ) -> Result<(), ParserErr>;
+
+    /// Дописывает то, что приёмник ещё держит у себя (например, незакрытый сжатый блок):
+    /// вызывается перед сбросом вывода, чтобы все принятые записи оказались в ресурсе.
+    fn flush(&mut self, _w: &mut dyn Write) -> Result<(), ParserErr> {
+        Ok(())
+    }
+
+    /// Дописывает то, что зависит от всего документа (заголовок пустого файла, индекс).
+    fn finish(&mut self, w: &mut dyn Write) -> Result<(), ParserErr>;
+
+    /// Восстанавливает состояние для дозаписи документа, записанного до `checkpoint`.
+    ///
+    /// Возвращает `false`, если состояние не восстановить (например, индекс бинарного файла).
+    fn resume(&mut self, checkpoint: &WriteCheckpoint) -> bool;
+
+    /// Последней записанной частью документа была запись, а не строка аннотации.
+    fn after_record(&self) -> bool {
+        false
+    }
+}
+
+/// Примерный размер документа из `records`, чтобы выделить буфер вывода один раз:
+/// `per_record` байт на служебные поля каждой записи плюс её описание.
+pub(crate) fn estimated_len(records: &[TxData], per_record: usize) -> usize {
+    records.iter().map(|tx| per_record + tx.description.len()).sum()
+}
+
+/// Порция набрана: записей и пропущенных проблем не меньше `max`.
+pub(crate) fn batch_full(outcome: &ParseOutcome, max: usize) -> bool {
+    outcome.records.len() + outcome.issues.len() >= max
+}
+
+/// Сколько разных описаний хранит [`DescriptionArena`]; дальше новые описания не запоминаются,
+/// чтобы уникальные комментарии не раздували таблицу.
+const ARENA_CAPACITY: usize = 1 << 16;
+
+/// Общие копии повторяющихся описаний для `ParseOptions::intern_descriptions`.
+///
+/// Источник держит одну арену на весь разбор, поэтому одинаковые описания из разных
+/// порций ссылаются на одну строку.
+#[derive(Debug, Default)]
+pub(crate) struct DescriptionArena {
+    known: HashSet<Arc<str>>,
+}
+
+impl DescriptionArena {
+    /// Заменяет описание записи общей копией, если такое уже встречалось.
+    pub(crate) fn intern(&mut self, mut tx: TxData) -> TxData {
+        if let Some(known) = self.known.get(&*tx.description) {
+            tx.description = Arc::clone(known);
+        } else if self.known.len() < ARENA_CAPACITY {
+            self.known.insert(Arc::clone(&tx.description));
+        }
+        tx
+    }
+}
@@ -1,13 +1,15 @@
 use std::collections::HashMap;
-
-use csv::{ReaderBuilder, StringRecord};
-use std::io::{Cursor, Read};
+use std::io::{self, BufRead, BufReader, Read, Write};
 
 use crate::model::data::Format;
 use crate::model::data::Status;
 use crate::model::data::TxData;
 use crate::model::data::TxType;
-use crate::model::errors::ParserErr;
+use crate::model::tx_id::TxId;
+use crate::model::errors::{ParseWarning, ParserErr, WarningKind};
+use crate::model::metadata::ProducerMetadata;
+use crate::parser::concrete::{batch_full, estimated_len, parse_number, DescriptionArena, RecordSink, RecordSource};
+use crate::parser::options::{EmptyFieldPolicy, ParseOptions, ParseOutcome, ReadCheckpoint, RecordPos, TextAnnotation, WriteCheckpoint};
 
 /// Трейт для парсинга транзакций из текстового представления в виде пар "ключ–значение".
 ///
@@ -42,6 +44,31 @@ pub trait TxnFromText {
     /// # Errors
     /// Возвращает [`ParserErr`] при ошибке чтения или при невозможности распарсить любую из строк.
     fn from_text_reader(reader: Box<dyn Read>) -> Result<Vec<TxData>, ParserErr>;
+
+    /// Парсит транзакции из списка строк с заданными настройками.
+    ///
+    /// Разделители, регистр ключей и псевдонимы задаются `ParseOptions::text`.
+    /// Повтор ключа внутри записи считается ошибкой этой записи. В режиме
+    /// `ErrorMode::Collect` некорректные строки и записи пропускаются и попадают
+    /// в `ParseOutcome::issues`. Пробелы в конце строк и описания без кавычек
+    /// возвращаются как предупреждения.
+    ///
+    /// # Errors
+    /// В режиме `FailFast` возвращает [`ParserErr`] для первой некорректной записи.
+    fn from_text_many_with(
+        lines: &[String],
+        options: &ParseOptions,
+    ) -> Result<ParseOutcome, ParserErr>;
+
+    /// Парсит транзакции из потока с заданными настройками.
+    ///
+    /// # Errors
+    /// Возвращает [`ParserErr`] при ошибке чтения или, в режиме `FailFast`,
+    /// при первой некорректной записи.
+    fn from_text_reader_with(
+        reader: Box<dyn Read>,
+        options: &ParseOptions,
+    ) -> Result<ParseOutcome, ParserErr>;
 }
 
 /// Трейт для сериализации транзакций в человекочитаемый текстовый формат.
@@ -68,18 +95,53 @@ pub trait TxnToText {
     fn to_text_many(many: &[Self]) -> Result<String, ParserErr>
     where
         Self: Sized;
+
+    /// Сериализует транзакции, возвращая на место комментарии и пустые строки.
+    ///
+    /// Аннотации обычно получены из `ParseOutcome::annotations` при разборе с
+    /// `TextOptions::keep_comments`. Между записями без аннотации ставится пустая строка,
+    /// поэтому с пустым списком аннотаций результат совпадает с [`TxnToText::to_text_many`].
+    ///
+    /// # Errors
+    /// Возвращает [`ParserErr`], если хотя бы одна транзакция не может быть сериализована.
+    fn to_text_annotated(many: &[Self], annotations: &[TextAnnotation]) -> Result<String, ParserErr>
+    where
+        Self: Sized;
+}
+
+/// Трейт для потоковой записи транзакций в текстовом формате прямо в `Write`,
+/// без промежуточной строки на весь документ.
+pub trait TxnWriteToText {
+    /// Записывает одну транзакцию в `w` так же, как её сериализует [`TxnToText::to_text`].
+    ///
+    /// # Errors
+    /// Возвращает [`ParserErr`] при ошибке записи.
+    fn to_text_writer(&self, w: &mut dyn Write) -> Result<(), ParserErr>;
+
+    /// Записывает транзакции с аннотациями в `w` так же, как [`TxnToText::to_text_annotated`].
+    ///
+    /// # Errors
+    /// Возвращает [`ParserErr`] при ошибке записи.
+    fn to_text_writer_many(
+        many: &[Self],
+        annotations: &[TextAnnotation],
+        w: &mut dyn Write,
+    ) -> Result<(), ParserErr>
+    where
+        Self: Sized;
 }
 
 impl TxnFromText for TxData {
     fn from_text(fields: &HashMap<String, String>) -> Result<TxData, ParserErr> {
-        let get = |key: &str| {
-            fields.get(key).ok_or_else(|| ParserErr::ParseErr {
-                msg: format!("Missing field: {}", key),
-            })
+        // Пустое описание проверяется политикой в `ParseOptions::accept`
+        let get = |key: &'static str| match fields.get(key) {
+            None => Err(ParserErr::MissingField { name: key }),
+            Some(value) if value.is_empty() && key != "DESCRIPTION" => Err(ParserErr::EmptyField { field: key }),
+            Some(value) => Ok(value),
         };
 
         let unquote = |s: &str| {
-            if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') {
+            if is_quoted(s) {
                 s[1..s.len() - 1].to_string()
             } else {
                 s.to_string()
@@ -87,79 +149,320 @@ impl TxnFromText for TxData {
         };
 
         Ok(TxData {
-            tx_id: get("TX_ID")?.parse().map_err(|_| ParserErr::ParseErr {
-                msg: "Invalid TX_ID".into(),
-            })?,
+            tx_id: TxId::parse(get("TX_ID")?)?,
             tx_type: parse_tx_type_str(get("TX_TYPE")?)?,
-            from_user_id: get("FROM_USER_ID")?
-                .parse()
-                .map_err(|_| ParserErr::ParseErr {
-                    msg: "Invalid FROM_USER_ID".into(),
-                })?,
-            to_user_id: get("TO_USER_ID")?
-                .parse()
-                .map_err(|_| ParserErr::ParseErr {
-                    msg: "Invalid TO_USER_ID".into(),
-                })?,
-            amount: get("AMOUNT")?.parse().map_err(|_| ParserErr::ParseErr {
-                msg: "Invalid AMOUNT".into(),
-            })?,
-            timestamp: get("TIMESTAMP")?.parse().map_err(|_| ParserErr::ParseErr {
-                msg: "Invalid TIMESTAMP".into(),
-            })?,
+            from_user_id: parse_number("FROM_USER_ID", get("FROM_USER_ID")?)?,
+            to_user_id: parse_number("TO_USER_ID", get("TO_USER_ID")?)?,
+            amount: parse_number("AMOUNT", get("AMOUNT")?)?,
+            timestamp: parse_number("TIMESTAMP", get("TIMESTAMP")?)?,
             status: parse_status_str(get("STATUS")?)?,
-            description: unquote(get("DESCRIPTION")?),
+            description: unquote(get("DESCRIPTION")?).into(),
             format: Format::YpBankText,
         })
     }
 
     fn from_text_many(lines: &[String]) -> Result<Vec<TxData>, ParserErr> {
-        let mut transactions = Vec::new();
-        let mut current = HashMap::new();
+        Self::from_text_many_with(lines, &ParseOptions::default()).map(|outcome| outcome.records)
+    }
 
+    fn from_text_reader(reader: Box<dyn Read>) -> Result<Vec<Self>, ParserErr> {
+        Self::from_text_reader_with(reader, &ParseOptions::default()).map(|outcome| outcome.records)
+    }
+
+    fn from_text_many_with(
+        lines: &[String],
+        options: &ParseOptions,
+    ) -> Result<ParseOutcome, ParserErr> {
+        let mut outcome = ParseOutcome::default();
+        let mut assembler = RecordAssembler::default();
         for (i, line) in lines.iter().enumerate() {
-            let trimmed = line.trim();
-            if trimmed.is_empty() || trimmed.starts_with('#') {
-                if !current.is_empty() {
-                    transactions.push(Self::from_text(&current)?);
-                    current.clear();
+            assembler.line(i + 1, line, options, &mut outcome)?;
+        }
+        assembler.finish(options, &mut outcome)?;
+        Ok(outcome)
+    }
+
+    fn from_text_reader_with(
+        reader: Box<dyn Read>,
+        options: &ParseOptions,
+    ) -> Result<ParseOutcome, ParserErr> {
+        let outcome = TextSource::new(reader, options.clone()).next_batch(usize::MAX)?;
+        Ok(outcome.unwrap_or_default())
+    }
+}
+
+/// Собирает записи из строк по одной, сохраняя состояние между вызовами.
+#[derive(Default)]
+struct RecordAssembler {
+    current: HashMap<String, String>,
+    warnings: Vec<ParseWarning>,
+    /// Номер первой строки текущей записи — к нему привязываются ошибки полей
+    record_start: usize,
+    /// Порядковый номер текущей записи (с единицы, включая отклонённые)
+    record_index: usize,
+    in_record: bool,
+    /// Запись уже отклонена (в режиме Collect) и собирать её не нужно
+    rejected: bool,
+    descriptions: DescriptionArena,
+}
+
+impl RecordAssembler {
+    /// Обрабатывает строку с номером `number`; завершённая запись попадает в `outcome`.
+    fn line(
+        &mut self,
+        number: usize,
+        line: &str,
+        options: &ParseOptions,
+        outcome: &mut ParseOutcome,
+    ) -> Result<(), ParserErr> {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            self.finish(options, outcome)?;
+            if options.text.keep_comments {
+                annotate(outcome, line);
+            }
+            return Ok(());
+        }
+
+        if !self.in_record {
+            self.in_record = true;
+            self.record_index += 1;
+            self.record_start = number;
+        }
+
+        if line.len() != line.trim_end().len() {
+            self.warnings.push(ParseWarning::at(number, WarningKind::TrailingWhitespace));
+        }
+
+        if let Some((key, value)) = options.text.split(trimmed) {
+            let key = options.text.canonical_key(key);
+            let value = value.to_string();
+            if key == "DESCRIPTION" && !is_quoted(&value) {
+                self.warnings.push(ParseWarning::at(number, WarningKind::UnquotedDescription));
+            }
+            if self.current.contains_key(&key) && !self.rejected {
+                let err = ParserErr::DuplicateKey { key: key.clone() };
+                options.recover(err.at_line(number), &mut outcome.issues)?;
+                self.rejected = true;
+            }
+            self.current.insert(key, value);
+        } else {
+            let err = ParserErr::Malformed {
+                msg: format!("Invalid key-value: {}", line),
+            };
+            options.recover(err.at_line(number), &mut outcome.issues)?;
+        }
+        Ok(())
+    }
+
+    /// Завершает текущую запись (если она есть) и сбрасывает состояние.
+    fn finish(&mut self, options: &ParseOptions, outcome: &mut ParseOutcome) -> Result<(), ParserErr> {
+        if !self.current.is_empty() && !self.rejected {
+            let record = (self.record_index, self.record_start);
+            finish_record(&self.current, record, &mut self.warnings, options, &mut self.descriptions, outcome)?;
+        }
+        self.current.clear();
+        self.warnings.clear();
+        self.in_record = false;
+        self.rejected = false;
+        Ok(())
+    }
+}
+
+/// Потоковый разбор текстового формата: строки читаются по мере набора порции.
+pub(crate) struct TextSource {
+    reader: BufReader<Box<dyn Read>>,
+    assembler: RecordAssembler,
+    /// Номер последней прочитанной строки
+    line: usize,
+    /// Смещение следующей строки от начала входа
+    offset: u64,
+    done: bool,
+    options: ParseOptions,
+}
+
+impl TextSource {
+    pub(crate) fn new(reader: Box<dyn Read>, options: ParseOptions) -> Self {
+        TextSource {
+            reader: BufReader::new(reader),
+            assembler: RecordAssembler::default(),
+            line: 0,
+            offset: 0,
+            done: false,
+            options,
+        }
+    }
+
+    /// Следующая строка без перевода строки, как у `BufRead::lines`.
+    fn next_line(&mut self) -> Option<io::Result<String>> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(read) => {
+                self.offset += read as u64;
+                if line.ends_with('\n') {
+                    line.pop();
+                    if line.ends_with('\r') {
+                        line.pop();
+                    }
                 }
-                continue;
+                Some(Ok(line))
             }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
 
-            if let Some(pos) = trimmed.find(':') {
-                let key = trimmed[..pos].trim().to_string();
-                let value = trimmed[pos + 1..].trim().to_string();
-                current.insert(key, value);
-            } else {
-                return Err(ParserErr::ParseErr {
-                    msg: format!("Invalid key-value on line {}: {}", i + 1, line),
-                });
+impl RecordSource for TextSource {
+    fn next_batch(&mut self, max: usize) -> Result<Option<ParseOutcome>, ParserErr> {
+        if self.done {
+            return Ok(None);
+        }
+        let mut outcome = ParseOutcome::default();
+        while !batch_full(&outcome, max) {
+            match self.next_line() {
+                Some(line) => {
+                    self.line += 1;
+                    self.assembler.line(self.line, &line?, &self.options, &mut outcome)?;
+                }
+                None => {
+                    self.done = true;
+                    self.assembler.finish(&self.options, &mut outcome)?;
+                    break;
+                }
             }
         }
+        Ok(Some(outcome))
+    }
 
-        if !current.is_empty() {
-            transactions.push(Self::from_text(&current)?);
+    fn checkpoint(&self) -> Option<ReadCheckpoint> {
+        if self.done || self.assembler.in_record {
+            return None;
         }
+        Some(ReadCheckpoint {
+            offset: self.offset,
+            lines: self.line,
+            records: self.assembler.record_index,
+            ..ReadCheckpoint::default()
+        })
+    }
 
-        Ok(transactions)
+    fn resume(&mut self, reader: Box<dyn Read>, at: &ReadCheckpoint) -> bool {
+        self.reader = BufReader::new(reader);
+        self.offset = at.offset;
+        self.line = at.lines;
+        self.assembler.record_index = at.records;
+        true
     }
+}
 
-    fn from_text_reader(reader: Box<dyn Read>) -> Result<Vec<Self>, ParserErr> {
-        let content = std::io::read_to_string(reader)
-            .map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?;
-        let lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
-        Self::from_text_many(&lines)
+/// Ключи текстовой записи; каждый должен встретиться ровно один раз.
+const TEXT_KEYS: [&str; 8] = [
+    "TX_ID",
+    "TX_TYPE",
+    "FROM_USER_ID",
+    "TO_USER_ID",
+    "AMOUNT",
+    "TIMESTAMP",
+    "STATUS",
+    "DESCRIPTION",
+];
+
+/// Примерная длина текстовой записи без описания: ключи, значения и пустая строка после записи.
+const TEXT_RECORD_LEN: usize = 192;
+
+/// Комментарий в первой строке файла, объявляющий версию схемы: `# SCHEMA: 2`.
+const SCHEMA_MARKER: &str = "# SCHEMA:";
+
+/// Версия схемы текстового файла по комментарию `# SCHEMA: N` в первой строке `prefix`.
+///
+/// Для разбора это обычный комментарий; файлы без него записаны версией схемы 1.
+///
+/// # Errors
+/// `ParserErr::InvalidValue`, если версия в комментарии — не число.
+pub(crate) fn schema_version(prefix: &[u8]) -> Result<u32, ParserErr> {
+    let prefix = String::from_utf8_lossy(prefix);
+    let first = prefix.lines().next().unwrap_or_default().trim();
+    let Some(version) = first.strip_prefix(SCHEMA_MARKER) else {
+        return Ok(1);
+    };
+    version.trim().parse().map_err(|_| ParserErr::InvalidValue {
+        field: "SCHEMA",
+        value: version.trim().to_string(),
+    })
+}
+
+/// Собирает транзакцию из накопленных полей и передаёт её вместе с предупреждениями в `outcome`.
+///
+/// `record` — порядковый номер записи и номер её первой строки.
+fn finish_record(
+    fields: &HashMap<String, String>,
+    (record_index, record_start): (usize, usize),
+    warnings: &mut Vec<ParseWarning>,
+    options: &ParseOptions,
+    descriptions: &mut DescriptionArena,
+    outcome: &mut ParseOutcome,
+) -> Result<(), ParserErr> {
+    let warnings = std::mem::take(warnings);
+
+    let mut unexpected: Vec<String> = fields
+        .keys()
+        .filter(|key| !TEXT_KEYS.contains(&key.as_str()))
+        .cloned()
+        .collect();
+    if !unexpected.is_empty() {
+        unexpected.sort();
+        let err = ParserErr::UnexpectedKeys {
+            record: record_index,
+            keys: unexpected,
+        };
+        return options.recover(err.at_line(record_start), &mut outcome.issues);
+    }
+
+    let with_description;
+    let fields = if options.empty_field_policy == EmptyFieldPolicy::AllowMissingDescription
+        && !fields.contains_key("DESCRIPTION")
+    {
+        let description = ("DESCRIPTION".to_string(), String::new());
+        with_description = fields.clone().into_iter().chain([description]).collect::<HashMap<_, _>>();
+        &with_description
+    } else {
+        fields
+    };
+
+    match TxData::from_text(fields) {
+        Ok(tx) => {
+            let pos = RecordPos { record: record_index, line: record_start, offset: None };
+            options.accept(tx, descriptions, pos, warnings, outcome)
+        },
+        Err(e) => options.recover(e.at_line(record_start), &mut outcome.issues),
+    }
+}
+
+/// Добавляет строку к аннотации перед следующей записью.
+fn annotate(outcome: &mut ParseOutcome, line: &str) {
+    let before_record = outcome.records.len();
+    match outcome.annotations.last_mut() {
+        Some(last) if last.before_record == before_record => last.lines.push(line.to_string()),
+        _ => outcome.annotations.push(TextAnnotation {
+            before_record,
+            lines: vec![line.to_string()],
+        }),
     }
 }
 
+fn is_quoted(s: &str) -> bool {
+    s.len() >= 2 && s.starts_with('"') && s.ends_with('"')
+}
+
 fn parse_tx_type_str(s: &str) -> Result<TxType, ParserErr> {
     match s {
         "DEPOSIT" => Ok(TxType::Deposit),
         "TRANSFER" => Ok(TxType::Transfer),
         "WITHDRAWAL" => Ok(TxType::Withdrawal),
-        _ => Err(ParserErr::ParseErr {
-            msg: format!("Invalid TX_TYPE: {}", s),
+        _ => Err(ParserErr::InvalidValue {
+            field: "TX_TYPE",
+            value: s.to_string(),
         }),
     }
 }
@@ -169,40 +472,41 @@ fn parse_status_str(s: &str) -> Result<Status, ParserErr> {
         "SUCCESS" => Ok(Status::Success),
         "FAILURE" => Ok(Status::Failure),
         "PENDING" => Ok(Status::Pending),
-        _ => Err(ParserErr::ParseErr {
-            msg: format!("Invalid STATUS: {}", s),
+        _ => Err(ParserErr::InvalidValue {
+            field: "STATUS",
+            value: s.to_string(),
         }),
     }
 }
 
 impl TxnToText for TxData {
     fn to_text(&self) -> Result<String, ParserErr> {
-        let tx_type_str = match self.tx_type {
-            TxType::Deposit => "DEPOSIT",
-            TxType::Transfer => "TRANSFER",
-            TxType::Withdrawal => "WITHDRAWAL",
-            _ => {
-                return Err(ParserErr::ParseErr {
-                    msg: format!("Invalid TYPE: {:?}", &self.tx_type),
-                });
-            }
-        };
+        let mut out = Vec::new();
+        self.to_text_writer(&mut out)?;
+        into_string(out)
+    }
 
-        let status_str = match self.status {
-            Status::Success => "SUCCESS",
-            Status::Failure => "FAILURE",
-            Status::Pending => "PENDING",
-            _ => {
-                return Err(ParserErr::ParseErr {
-                    msg: format!("Invalid TYPE: {:?}", &self.tx_type),
-                });
-            }
-        };
+    fn to_text_many(transactions: &[Self]) -> Result<String, ParserErr> {
+        Self::to_text_annotated(transactions, &[])
+    }
 
-        // Описание в двойных кавычках
-        let desc_quoted = format!("\"{}\"", self.description);
+    fn to_text_annotated(
+        transactions: &[Self],
+        annotations: &[TextAnnotation],
+    ) -> Result<String, ParserErr> {
+        let mut out = Vec::with_capacity(estimated_len(transactions, TEXT_RECORD_LEN));
+        Self::to_text_writer_many(transactions, annotations, &mut out)?;
+        into_string(out)
+    }
+}
+
+impl TxnWriteToText for TxData {
+    fn to_text_writer(&self, w: &mut dyn Write) -> Result<(), ParserErr> {
+        let (tx_type_str, status_str) = (self.tx_type.name(), self.status.name());
 
-        Ok(format!(
+        // Описание в двойных кавычках
+        write!(
+            w,
             "TX_ID: {}\n\
              TX_TYPE: {}\n\
              FROM_USER_ID: {}\n\
@@ -210,7 +514,7 @@ impl TxnToText for TxData {
              AMOUNT: {}\n\
              TIMESTAMP: {}\n\
              STATUS: {}\n\
-             DESCRIPTION: {}",
+             DESCRIPTION: \"{}\"",
             self.tx_id,
             tx_type_str,
             self.from_user_id,
@@ -218,28 +522,134 @@ impl TxnToText for TxData {
             self.amount,
             self.timestamp,
             status_str,
-            desc_quoted
-        ))
+            self.description
+        )?;
+        Ok(())
     }
 
-    fn to_text_many(transactions: &[Self]) -> Result<String, ParserErr> {
-        let mut output = String::new();
-        for (i, tx) in transactions.iter().enumerate() {
-            if i > 0 {
-                output.push('\n'); // пустая строка между записями
+    fn to_text_writer_many(
+        transactions: &[Self],
+        annotations: &[TextAnnotation],
+        w: &mut dyn Write,
+    ) -> Result<(), ParserErr> {
+        let mut sink = TextSink::default();
+        sink.write_batch(w, transactions, annotations)?;
+        sink.finish(w)
+    }
+}
+
+/// Потоковая запись текстового формата с аннотациями.
+///
+/// Части документа (строки аннотаций и записи) разделяются переводом строки;
+/// между двумя записями подряд ставится пустая строка.
+#[derive(Default)]
+pub(crate) struct TextSink {
+    /// Что-то уже записано, и следующей части нужен перевод строки
+    started: bool,
+    /// Последней записанной частью была запись
+    after_record: bool,
+    /// Сведения о программе, ещё не записанные строками `#@` в начало документа
+    metadata: Option<ProducerMetadata>,
+}
+
+impl TextSink {
+    pub(crate) fn new(metadata: Option<ProducerMetadata>) -> Self {
+        TextSink {
+            metadata,
+            ..TextSink::default()
+        }
+    }
+
+    /// Пишет сведения о программе перед первой частью документа.
+    fn metadata(&mut self, w: &mut dyn Write) -> Result<(), ParserErr> {
+        for line in self.metadata.take().iter().flat_map(ProducerMetadata::comment_lines) {
+            self.separate(w)?;
+            w.write_all(line.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Ставит перевод строки перед очередной частью документа.
+    fn separate(&mut self, w: &mut dyn Write) -> Result<(), ParserErr> {
+        if std::mem::replace(&mut self.started, true) {
+            w.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn lines_before(
+        &mut self,
+        w: &mut dyn Write,
+        annotations: &[TextAnnotation],
+        index: usize,
+    ) -> Result<bool, ParserErr> {
+        let mut any = false;
+        for line in annotations
+            .iter()
+            .filter(|a| a.before_record == index)
+            .flat_map(|a| a.lines.iter())
+        {
+            self.separate(w)?;
+            w.write_all(line.as_bytes())?;
+            self.after_record = false;
+            any = true;
+        }
+        Ok(any)
+    }
+}
+
+impl RecordSink for TextSink {
+    fn write_batch(
+        &mut self,
+        w: &mut dyn Write,
+        records: &[TxData],
+        annotations: &[TextAnnotation],
+    ) -> Result<(), ParserErr> {
+        self.metadata(w)?;
+        for (i, tx) in records.iter().enumerate() {
+            if !self.lines_before(w, annotations, i)? && self.after_record {
+                self.separate(w)?; // пустая строка между записями
             }
-            output.push_str(&tx.to_text()?);
+            self.separate(w)?;
+            tx.to_text_writer(w)?;
+            self.after_record = true;
+        }
+        self.lines_before(w, annotations, records.len())?;
+        Ok(())
+    }
+
+    fn finish(&mut self, w: &mut dyn Write) -> Result<(), ParserErr> {
+        self.metadata(w)
+    }
+
+    fn resume(&mut self, checkpoint: &WriteCheckpoint) -> bool {
+        self.started = checkpoint.bytes > 0;
+        if self.started {
+            self.metadata = None;
         }
-        Ok(output)
+        self.after_record = checkpoint.after_record;
+        true
+    }
+
+    fn after_record(&self) -> bool {
+        self.after_record
     }
 }
 
+/// Собирает строку из байт, записанных сериализатором.
+fn into_string(bytes: Vec<u8>) -> Result<String, ParserErr> {
+    String::from_utf8(bytes).map_err(|source| ParserErr::InvalidUtf8 {
+        field: "DESCRIPTION",
+        source,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::collections::HashMap;
-    use std::f32::consts::E;
     use std::io::Cursor;
+    use crate::parser::options::TextOptions;
 
     #[test]
     fn test_from_text_valid() {
@@ -262,7 +672,7 @@ mod tests {
         assert_eq!(tx.amount, 100);
         assert_eq!(tx.timestamp, 1700000000);
         assert_eq!(tx.status, Status::Success);
-        assert_eq!(tx.description, "Test transfer"); 
+        assert_eq!(&*tx.description, "Test transfer"); 
         assert_eq!(tx.format, Format::YpBankText);
     }
 
@@ -279,7 +689,7 @@ mod tests {
         fields.insert("DESCRIPTION".to_string(), "No quotes here".to_string());
 
         let tx = TxData::from_text(&fields).unwrap();
-        assert_eq!(tx.description, "No quotes here");
+        assert_eq!(&*tx.description, "No quotes here");
     }
 
     #[test]
@@ -298,7 +708,56 @@ mod tests {
         );
 
         let tx = TxData::from_text(&fields).unwrap();
-        assert_eq!(tx.description, "With \"\"escaped\"\" quotes");
+        assert_eq!(&*tx.description, "With \"\"escaped\"\" quotes");
+    }
+
+    #[test]
+    fn test_empty_field_policy() {
+        let record = |description: &str| {
+            let mut lines: Vec<String> = [
+                "TX_ID: 1",
+                "TX_TYPE: DEPOSIT",
+                "FROM_USER_ID: 0",
+                "TO_USER_ID: 10",
+                "AMOUNT: 100",
+                "TIMESTAMP: 1700000000",
+                "STATUS: SUCCESS",
+            ]
+            .map(String::from)
+            .to_vec();
+            lines.extend((!description.is_empty()).then(|| description.to_string()));
+            lines
+        };
+        let parse = |lines: &[String], empty_field_policy| {
+            let options = ParseOptions {
+                empty_field_policy,
+                ..ParseOptions::default()
+            };
+            TxData::from_text_many_with(lines, &options)
+        };
+
+        let missing = record("");
+        assert!(parse(&missing, EmptyFieldPolicy::AllowEmptyDescription).is_err());
+        let outcome = parse(&missing, EmptyFieldPolicy::AllowMissingDescription).unwrap();
+        assert_eq!(&*outcome.records[0].description, "");
+
+        let empty = record("DESCRIPTION: \"\"");
+        assert_eq!(parse(&empty, EmptyFieldPolicy::AllowEmptyDescription).unwrap().records.len(), 1);
+        let err = parse(&empty, EmptyFieldPolicy::Reject).unwrap_err();
+        if let ParserErr::AtLine { source, .. } = err {
+            assert!(matches!(*source, ParserErr::EmptyField { field: "DESCRIPTION" }));
+        } else {
+            panic!()
+        }
+
+        let mut empty_amount = record("DESCRIPTION: \"x\"");
+        empty_amount[4] = "AMOUNT:".to_string();
+        let err = parse(&empty_amount, EmptyFieldPolicy::AllowEmptyDescription).unwrap_err();
+        if let ParserErr::AtLine { source, .. } = err {
+            assert!(matches!(*source, ParserErr::EmptyField { field: "AMOUNT" }));
+        } else {
+            panic!()
+        }
     }
 
     #[test]
@@ -310,8 +769,8 @@ mod tests {
 
         let err = TxData::from_text(&fields).unwrap_err();
 
-        if let ParserErr::ParseErr { msg } = err {
-            assert!(msg.to_string().contains("Missing field: FROM_USER_ID"));
+        if let ParserErr::MissingField { name } = err {
+            assert_eq!(name, "FROM_USER_ID");
         } else {
             panic!();
         }
@@ -332,8 +791,9 @@ mod tests {
 
         let err = TxData::from_text(&fields).unwrap_err();
 
-        if let ParserErr::ParseErr { msg } = err {
-            assert!(msg.to_string().contains("Invalid TX_TYPE"));
+        if let ParserErr::InvalidValue { field, value } = err {
+            assert_eq!(field, "TX_TYPE");
+            assert_eq!(value, "INVALID");
         } else {
             panic!();
         }
@@ -353,8 +813,9 @@ mod tests {
 
         let err = TxData::from_text(&fields).unwrap_err();
 
-        if let ParserErr::ParseErr { msg } = err {
-            assert!(msg.to_string().contains("Invalid STATUS"));
+        if let ParserErr::InvalidValue { field, value } = err {
+            assert_eq!(field, "STATUS");
+            assert_eq!(value, "UNKNOWN");
         } else {
             panic!()
         }
@@ -385,7 +846,7 @@ mod tests {
         let txs = TxData::from_text_many(&lines).unwrap();
         assert_eq!(txs.len(), 2);
         assert_eq!(txs[0].tx_id, 1);
-        assert_eq!(txs[0].description, "Initial deposit");
+        assert_eq!(&*txs[0].description, "Initial deposit");
         assert_eq!(txs[1].tx_id, 2);
         assert_eq!(txs[1].status, Status::Pending);
     }
@@ -400,13 +861,290 @@ mod tests {
 
         let err = TxData::from_text_many(&lines).unwrap_err();
 
-        if let ParserErr::ParseErr { msg } = err {
-            assert!(msg.to_string().contains("Invalid key-value on line 3"));
+        if let ParserErr::AtLine { line, source } = err {
+            assert_eq!(line, 3);
+            assert!(matches!(*source, ParserErr::Malformed { .. }));
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_from_text_many_collect_mode_reports_record_start() {
+        let lines: Vec<String> = [
+            "TX_ID: 1",
+            "TX_TYPE: DEPOSIT",
+            "FROM_USER_ID: 0",
+            "TO_USER_ID: 100",
+            "AMOUNT: 50",
+            "TIMESTAMP: 1700000001",
+            "STATUS: SUCCESS",
+            "DESCRIPTION: \"ok\"",
+            "",
+            "TX_ID: 2",
+            "TX_TYPE: DEPOSIT",
+            "AMOUNT: many",
+            "",
+            "TX_ID: 3",
+            "TX_TYPE: DEPOSIT",
+            "FROM_USER_ID: 0",
+            "TO_USER_ID: 100",
+            "AMOUNT: 70",
+            "TIMESTAMP: 1700000003",
+            "STATUS: SUCCESS",
+            "DESCRIPTION: \"ok\"",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let outcome = TxData::from_text_many_with(&lines, &ParseOptions::collect()).unwrap();
+        let issues = outcome.issues;
+
+        assert_eq!(outcome.records.iter().filter_map(|tx| tx.tx_id.as_u64()).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, Some(10));
+        assert_eq!(issues[0].field, Some("FROM_USER_ID"));
+    }
+
+    fn lines(raw: &[&str]) -> Vec<String> {
+        raw.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_from_text_many_reports_warnings() {
+        let lines = lines(&[
+            "TX_ID: 1",
+            "TX_TYPE: DEPOSIT",
+            "FROM_USER_ID: 0",
+            "TO_USER_ID: 100",
+            "AMOUNT: 60",
+            "TIMESTAMP: 1700000001  ",
+            "STATUS: SUCCESS",
+            "DESCRIPTION: plain",
+            "",
+            "TX_ID: 2",
+            "TX_TYPE: DEPOSIT",
+            "FROM_USER_ID: 0",
+            "TO_USER_ID: 100",
+            "AMOUNT: 70",
+            "TIMESTAMP: 1700000003",
+            "STATUS: SUCCESS",
+            "DESCRIPTION: \"\"",
+        ]);
+
+        let outcome = TxData::from_text_many_with(&lines, &ParseOptions::default()).unwrap();
+
+        assert_eq!(outcome.records.len(), 2);
+        assert_eq!(outcome.records[0].amount, 60);
+        assert_eq!(
+            outcome.warnings,
+            vec![
+                ParseWarning::at(6, WarningKind::TrailingWhitespace),
+                ParseWarning::at(8, WarningKind::UnquotedDescription),
+                ParseWarning::at(10, WarningKind::EmptyDescription),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_text_many_duplicate_key_is_error() {
+        let lines = lines(&[
+            "TX_ID: 1",
+            "TX_TYPE: DEPOSIT",
+            "AMOUNT: 50",
+            "AMOUNT: 60",
+        ]);
+
+        let err = TxData::from_text_many(&lines).unwrap_err();
+
+        assert_eq!(err.line(), Some(4));
+        if let ParserErr::DuplicateKey { key } = err.root() {
+            assert_eq!(key, "AMOUNT");
         } else {
             panic!();
         }
     }
 
+    #[test]
+    fn test_from_text_many_records_without_separator_do_not_merge() {
+        let lines = lines(&[
+            "TX_ID: 1",
+            "TX_TYPE: DEPOSIT",
+            "FROM_USER_ID: 0",
+            "TO_USER_ID: 100",
+            "AMOUNT: 50",
+            "TIMESTAMP: 1700000001",
+            "STATUS: SUCCESS",
+            "DESCRIPTION: \"first\"",
+            "TX_ID: 2",
+            "TX_TYPE: DEPOSIT",
+            "FROM_USER_ID: 0",
+            "TO_USER_ID: 100",
+            "AMOUNT: 70",
+            "TIMESTAMP: 1700000003",
+            "STATUS: SUCCESS",
+            "DESCRIPTION: \"second\"",
+            "",
+            "TX_ID: 3",
+            "TX_TYPE: DEPOSIT",
+            "FROM_USER_ID: 0",
+            "TO_USER_ID: 100",
+            "AMOUNT: 90",
+            "TIMESTAMP: 1700000005",
+            "STATUS: SUCCESS",
+            "DESCRIPTION: \"third\"",
+        ]);
+
+        let outcome = TxData::from_text_many_with(&lines, &ParseOptions::collect()).unwrap();
+
+        assert_eq!(outcome.records.len(), 1);
+        assert_eq!(outcome.records[0].tx_id, 3);
+        assert_eq!(outcome.issues.len(), 1);
+        assert_eq!(outcome.issues[0].line, Some(9));
+        assert!(matches!(outcome.issues[0].error.root(), ParserErr::DuplicateKey { .. }));
+    }
+
+    #[test]
+    fn test_text_many_roundtrip_keeps_records_apart() {
+        let txs: Vec<TxData> = (1..=3)
+            .map(|id| TxData {
+                tx_id: TxId::U64(id),
+                tx_type: TxType::Deposit,
+                from_user_id: 0,
+                to_user_id: 10,
+                amount: 100,
+                timestamp: 1700000030,
+                status: Status::Success,
+                description: format!("Record {}", id).into(),
+                format: Format::YpBankText,
+            })
+            .collect();
+
+        let text = TxData::to_text_many(&txs).unwrap();
+        let restored = TxData::from_text_many(&lines(&text.lines().collect::<Vec<_>>())).unwrap();
+
+        assert_eq!(restored, txs);
+    }
+
+    #[test]
+    fn test_from_text_many_rejects_unexpected_keys() {
+        let lines = lines(&[
+            "TX_ID: 1",
+            "TX_TYPE: DEPOSIT",
+            "FROM_USER_ID: 0",
+            "TO_USER_ID: 100",
+            "AMOUNT: 50",
+            "TIMESTAMP: 1700000001",
+            "STATUS: SUCCESS",
+            "DESCRIPTION: \"ok\"",
+            "",
+            "TX_ID: 2",
+            "TX_TYPE: DEPOSIT",
+            "FROM_USER_ID: 0",
+            "TO_USER_ID: 100",
+            "AMOUNT: 50",
+            "CURRENCY: RUB",
+            "TIMESTAMP: 1700000001",
+            "STATUS: SUCCESS",
+            "DESCRIPTION: \"extra\"",
+            "BRANCH: 7",
+        ]);
+
+        let err = TxData::from_text_many(&lines).unwrap_err();
+
+        assert_eq!(err.line(), Some(10));
+        if let ParserErr::UnexpectedKeys { record, keys } = err.root() {
+            assert_eq!(*record, 2);
+            assert_eq!(keys, &["BRANCH".to_string(), "CURRENCY".to_string()]);
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_from_text_many_relaxed_keys() {
+        let lines = lines(&[
+            "txid = 7",
+            "Type: TRANSFER",
+            "FROM_USER_ID=1",
+            "to = 2",
+            "amount: 300",
+            "TIMESTAMP = 1700000007",
+            "status: SUCCESS",
+            "desc = \"a = b: c\"",
+        ]);
+        let options = ParseOptions {
+            text: TextOptions::relaxed(),
+            ..ParseOptions::default()
+        };
+
+        let outcome = TxData::from_text_many_with(&lines, &options).unwrap();
+
+        assert_eq!(outcome.records.len(), 1);
+        let tx = &outcome.records[0];
+        assert_eq!(tx.tx_id, 7);
+        assert_eq!(tx.tx_type, TxType::Transfer);
+        assert_eq!(tx.to_user_id, 2);
+        assert_eq!(tx.amount, 300);
+        assert_eq!(&*tx.description, "a = b: c");
+    }
+
+    #[test]
+    fn test_from_text_many_default_options_reject_equals_separator() {
+        let lines = lines(&["TX_ID = 7"]);
+
+        let err = TxData::from_text_many(&lines).unwrap_err();
+
+        assert_eq!(err.line(), Some(1));
+        assert!(matches!(err.root(), ParserErr::Malformed { .. }));
+    }
+
+    #[test]
+    fn test_from_text_many_alias_duplicates_canonical_key() {
+        let lines = lines(&["TX_ID: 1", "TXID: 2"]);
+        let options = ParseOptions {
+            text: TextOptions::relaxed(),
+            ..ParseOptions::default()
+        };
+
+        let err = TxData::from_text_many_with(&lines, &options).unwrap_err();
+
+        if let ParserErr::DuplicateKey { key } = err.root() {
+            assert_eq!(key, "TX_ID");
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_from_text_many_warnings_as_errors() {
+        let lines = lines(&[
+            "TX_ID: 1",
+            "TX_TYPE: DEPOSIT",
+            "FROM_USER_ID: 0",
+            "TO_USER_ID: 100",
+            "AMOUNT: 50",
+            "TIMESTAMP: 1700000001",
+            "STATUS: SUCCESS",
+            "DESCRIPTION: plain",
+        ]);
+        let options = ParseOptions {
+            warnings_as_errors: true,
+            ..ParseOptions::default()
+        };
+
+        let err = TxData::from_text_many_with(&lines, &options).unwrap_err();
+
+        assert_eq!(err.line(), Some(8));
+        assert!(matches!(
+            err.root(),
+            ParserErr::Escalated {
+                warning: WarningKind::UnquotedDescription
+            }
+        ));
+    }
+
     #[test]
     fn test_from_text_reader_valid() {
         let text_content = r#"TX_ID: 3
@@ -434,9 +1172,9 @@ DESCRIPTION: "Second deposit"
         let txs = TxData::from_text_reader(reader).unwrap();
         assert_eq!(txs.len(), 2);
         assert_eq!(txs[0].tx_id, 3);
-        assert_eq!(txs[0].description, "Pending tx");
+        assert_eq!(&*txs[0].description, "Pending tx");
         assert_eq!(txs[1].tx_id, 4);
-        assert_eq!(txs[1].description, "Second deposit");
+        assert_eq!(&*txs[1].description, "Second deposit");
     }
 
     #[test]
@@ -449,14 +1187,14 @@ DESCRIPTION: "Second deposit"
     #[test]
     fn test_to_text_simple() {
         let tx = TxData {
-            tx_id: 42,
+            tx_id: TxId::U64(42),
             tx_type: TxType::Withdrawal,
             from_user_id: 101,
             to_user_id: 0,
             amount: 30,
             timestamp: 1700000010,
             status: Status::Success,
-            description: "Cash out".to_string(),
+            description: "Cash out".into(),
             format: Format::YpBankText,
         };
 
@@ -476,14 +1214,14 @@ DESCRIPTION: "Cash out""#;
     #[test]
     fn test_to_text_description_with_special_chars() {
         let tx = TxData {
-            tx_id: 99,
+            tx_id: TxId::U64(99),
             tx_type: TxType::Transfer,
             from_user_id: 1,
             to_user_id: 2,
             amount: 10,
             timestamp: 1700000020,
             status: Status::Pending,
-            description: "Amount: \"10\", note: with\nnewlines and\ttabs".to_string(),
+            description: "Amount: \"10\", note: with\nnewlines and\ttabs".into(),
             format: Format::YpBankText,
         };
 
@@ -496,25 +1234,25 @@ DESCRIPTION: "Cash out""#;
     fn test_to_text_many() {
         let txs = vec![
             TxData {
-                tx_id: 1,
+                tx_id: TxId::U64(1),
                 tx_type: TxType::Deposit,
                 from_user_id: 0,
                 to_user_id: 10,
                 amount: 100,
                 timestamp: 1700000030,
                 status: Status::Success,
-                description: "Bonus".to_string(),
+                description: "Bonus".into(),
                 format: Format::YpBankText,
             },
             TxData {
-                tx_id: 2,
+                tx_id: TxId::U64(2),
                 tx_type: TxType::Transfer,
                 from_user_id: 10,
                 to_user_id: 20,
                 amount: 25,
                 timestamp: 1700000040,
                 status: Status::Failure,
-                description: "Blocked".to_string(),
+                description: "Blocked".into(),
                 format: Format::YpBankText,
             },
         ];
@@ -528,6 +1266,7 @@ AMOUNT: 100
 TIMESTAMP: 1700000030
 STATUS: SUCCESS
 DESCRIPTION: "Bonus"
+
 TX_ID: 2
 TX_TYPE: TRANSFER
 FROM_USER_ID: 10
@@ -546,17 +1285,102 @@ DESCRIPTION: "Blocked""#;
         assert_eq!(text, "");
     }
 
+    const COMMENTED: &str = "# Фикстура для сверки\n\
+                             \n\
+                             TX_ID: 1\n\
+                             TX_TYPE: DEPOSIT\n\
+                             FROM_USER_ID: 0\n\
+                             TO_USER_ID: 10\n\
+                             AMOUNT: 100\n\
+                             TIMESTAMP: 1700000000\n\
+                             STATUS: SUCCESS\n\
+                             DESCRIPTION: \"first\"\n\
+                             \n\
+                             \n\
+                             # вторая запись — возврат\n\
+                             TX_ID: 2\n\
+                             TX_TYPE: WITHDRAWAL\n\
+                             FROM_USER_ID: 10\n\
+                             TO_USER_ID: 0\n\
+                             AMOUNT: 100\n\
+                             TIMESTAMP: 1700000001\n\
+                             STATUS: PENDING\n\
+                             DESCRIPTION: \"second\"\n\
+                             # конец";
+
+    fn keep_comments() -> ParseOptions {
+        ParseOptions {
+            text: TextOptions {
+                keep_comments: true,
+                ..TextOptions::default()
+            },
+            ..ParseOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_text_roundtrip_keeps_comments() {
+        let text = COMMENTED;
+        let options = keep_comments();
+
+        let outcome = TxData::from_text_many_with(&lines(&text.lines().collect::<Vec<_>>()), &options).unwrap();
+        let written = TxData::to_text_annotated(&outcome.records, &outcome.annotations).unwrap();
+
+        assert_eq!(outcome.records.len(), 2);
+        assert_eq!(outcome.annotations.len(), 3);
+        assert_eq!(outcome.annotations[2].before_record, 2);
+        assert_eq!(written, text);
+    }
+
+    #[test]
+    fn test_text_batches_of_one_keep_comments_in_place() {
+        let mut source = TextSource::new(Box::new(Cursor::new(COMMENTED)), keep_comments());
+        let mut sink = TextSink::default();
+        let mut written = Vec::new();
+        let mut records = 0;
+        while let Some(batch) = source.next_batch(1).unwrap() {
+            assert!(batch.records.len() <= 1);
+            sink.write_batch(&mut written, &batch.records, &batch.annotations).unwrap();
+            records += batch.records.len();
+        }
+        sink.finish(&mut written).unwrap();
+
+        assert_eq!(records, 2);
+        assert_eq!(String::from_utf8(written).unwrap(), COMMENTED);
+    }
+
+    #[test]
+    fn test_to_text_annotated_without_annotations_matches_many() {
+        let tx = TxData {
+            tx_id: TxId::U64(1),
+            tx_type: TxType::Deposit,
+            from_user_id: 0,
+            to_user_id: 10,
+            amount: 100,
+            timestamp: 1700000000,
+            status: Status::Success,
+            description: "same".into(),
+            format: Format::YpBankText,
+        };
+        let txs = vec![tx.clone(), tx];
+
+        assert_eq!(
+            TxData::to_text_annotated(&txs, &[]).unwrap(),
+            TxData::to_text_many(&txs).unwrap()
+        );
+    }
+
     #[test]
     fn test_roundtrip_text() {
         let original = TxData {
-            tx_id: 12345,
+            tx_id: TxId::U64(12345),
             tx_type: TxType::Transfer,
             from_user_id: 100,
             to_user_id: 200,
             amount: 999,
             timestamp: 1700000000,
             status: Status::Success,
-            description: "Roundtrip test".to_string(),
+            description: "Roundtrip test".into(),
             format: Format::YpBankText,
         };
 
@@ -1,2 +1,16 @@
 pub mod reader;
-pub mod writer;
\ No newline at end of file
+#[cfg(feature = "report")]
+pub mod report;
+pub mod stdin;
+pub mod writer;
+#[cfg(feature = "zip")]
+pub(crate) mod zip_member;
+
+use crate::model::data::Format;
+use crate::model::errors::ParserErr;
+
+/// Ошибка для формата, поддержка которого отключена cargo-фичами.
+#[cfg_attr(all(feature = "bin", feature = "csv", feature = "text"), allow(dead_code))]
+pub(crate) fn format_disabled(format: Format) -> ParserErr {
+    ParserErr::UnsupportedFormat { format }
+}
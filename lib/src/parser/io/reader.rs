@@ -1,13 +1,24 @@
 use std::fs::File;
+use std::sync::Arc;
 
-use std::io::{self, BufReader, BufWriter, Read, Write, stdin, stdout};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 
-use crate::console::commands::Resource;
 use crate::model::data::{Format, TxData};
-use crate::model::errors::ParserErr;
-use crate::parser::concrete::bin_psrser::TxnFromBin;
-use crate::parser::concrete::csv_parser::TxnFromCsv;
-use crate::parser::concrete::text_parser::TxnFromText;
+use crate::model::errors::{CommonErr, IoErr, ParserErr};
+use crate::model::metadata::{ProducerMetadata, METADATA_PREFIX};
+use crate::model::resource::Resource;
+use crate::model::tx_id::TxId;
+use crate::parser::concrete::RecordSource;
+use crate::parser::options::{ParseOptions, ParseOutcome, ReadCheckpoint, ReadOptions};
+
+#[cfg(feature = "bin")]
+use crate::parser::concrete::bin_index::{self, BinIndex};
+#[cfg(feature = "bin")]
+use crate::parser::concrete::bin_psrser::{BinSource, TxnFromBin};
+#[cfg(feature = "csv")]
+use crate::parser::concrete::csv_parser::{CsvSource, TxnFromCsv};
+#[cfg(feature = "text")]
+use crate::parser::concrete::text_parser::{TextSource, TxnFromText};
 
 
 /// Читает транзакции из указанного ресурса в заданном формате.
@@ -18,37 +29,393 @@ use crate::parser::concrete::text_parser::TxnFromText;
 ///
 /// # Возвращает
 /// * `Ok(Vec<TxData>)` — вектор распарсенных транзакций
-/// * `Err(ParserErr)` — ошибка чтения файла или парсинга данных
+/// * `Err(CommonErr::IO)` — ресурс не удалось открыть
+/// * `Err(CommonErr::Parser)` — ошибка разбора данных
+///
+pub fn read(resource: Resource, format: Format) -> Result<Vec<TxData>, CommonErr> {
+    read_with(resource, format, &ParseOptions::default()).map(|outcome| outcome.records)
+}
+
+/// Читает транзакции из ресурса с заданными настройками разбора.
+///
+/// В режиме `ErrorMode::Collect` некорректные записи пропускаются, а описания
+/// проблем возвращаются в `ParseOutcome::issues`. Предупреждения о некритичных
+/// странностях входных данных возвращаются в `ParseOutcome::warnings`.
+///
+/// # Возвращает
+/// * `Ok(ParseOutcome)` — распарсенные транзакции, проблемы и предупреждения
+/// * `Err(CommonErr)` — ресурс не удалось открыть или разбор пришлось прервать
+///
+pub fn read_with(
+    resource: Resource,
+    format: Format,
+    options: &ParseOptions,
+) -> Result<ParseOutcome, CommonErr> {
+    let expected = expected_records(&resource, format);
+    let name = options.provenance.then(|| Arc::<str>::from(resource.to_string()));
+    let mut source = source(open(resource)?, format, options)?;
+    if let Some(count) = expected {
+        source.expect_records(count);
+    }
+    let mut outcome = source.next_batch(usize::MAX)?.unwrap_or_default();
+    if let Some(name) = &name {
+        outcome.set_source(name);
+    }
+    Ok(outcome)
+}
+
+/// Читает ресурс порциями ограниченного размера.
+///
+/// Каждая порция содержит не больше `batch_size` записей вместе с пропущенными проблемами,
+/// поэтому потребление памяти не зависит от размера входных данных. Номера строк в проблемах
+/// и предупреждениях сквозные, `TextAnnotation::before_record` отсчитывается от начала порции
+/// (в таком виде порцию принимает [`BatchWriter::write_batch`](super::writer::BatchWriter::write_batch)).
+///
+/// # Возвращает
+/// * `Ok(BatchReader)` — итератор по порциям; после первой ошибки он заканчивается
+/// * `Err(CommonErr)` — ресурс не удалось открыть или у него неверный заголовок
+pub fn read_batches(
+    resource: Resource,
+    format: Format,
+    options: &ParseOptions,
+    batch_size: usize,
+) -> Result<BatchReader, CommonErr> {
+    let expected = expected_records(&resource, format);
+    let name = options.provenance.then(|| Arc::<str>::from(resource.to_string()));
+    let mut batches = BatchReader::from_reader(open(resource)?, format, options, batch_size)?;
+    batches.name = name;
+    if let (Some(count), Some(source)) = (expected, batches.source.as_mut()) {
+        source.expect_records(count);
+        batches.expected = Some(count);
+    }
+    Ok(batches)
+}
+
+/// Как [`read_batches`], но продолжает чтение с места `checkpoint`, сохранённого
+/// [`BatchReader::checkpoint`]: заголовок входа читается с начала, а разбор записей
+/// продолжается с `checkpoint.offset`, не перечитывая записи до него.
 ///
-pub fn read(resource: Resource, format: Format) -> Result<Vec<TxData>, ParserErr> {
-    let reader: Box<dyn Read> = match resource {
-        Resource::Console => Box::new(stdin()),
+/// # Возвращает
+/// * `Ok(Some(BatchReader))` — итератор по оставшимся порциям с оставшимся окном чтения
+/// * `Ok(None)` — вход не перематывается (канал, консоль) или формат так не умеет;
+///   остаётся прочитать его с начала и пропустить уже прочитанные порции
+/// * `Err(CommonErr)` — ресурс не удалось открыть или у него неверный заголовок
+pub fn read_batches_from(
+    resource: Resource,
+    format: Format,
+    options: &ParseOptions,
+    batch_size: usize,
+    checkpoint: &ReadCheckpoint,
+) -> Result<Option<BatchReader>, CommonErr> {
+    let rest: Box<dyn Read> = match &resource {
+        _ if resource.is_stream() => return Ok(None),
         Resource::File { path } => {
-            let file = File::open(path).map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?;
+            let input_err = |e| IoErr::input(e, Some(path.clone()));
+            let mut file = File::open(path).map_err(input_err)?;
+            file.seek(SeekFrom::Start(checkpoint.offset)).map_err(input_err)?;
             Box::new(BufReader::new(file))
-        },
+        }
         Resource::Memory { data } => {
+            let mut data = data.clone();
+            data.set_position(checkpoint.offset);
             Box::new(data)
         }
-        
+        _ => return Ok(None),
     };
 
-    read_from_resource(reader, format)
+    let mut batches = read_batches(resource, format, options, batch_size)?;
+    if !batches.source.as_mut().is_some_and(|source| source.resume(rest, checkpoint)) {
+        return Ok(None);
+    }
+    batches.window = checkpoint.window.clone();
+    batches.expected = batches.expected.map(|expected| expected.saturating_sub(checkpoint.records));
+    Ok(Some(batches))
 }
 
-fn read_from_resource(resource: Box<dyn Read>, format: Format) -> Result<Vec<TxData>, ParserErr> {
-    return match format {
-        Format::YpBankBin => TxData::from_bin_reader(resource),
-        Format::YpBankCsv => TxData::from_csv_reader(resource),
-        Format::YpBankText => TxData::from_text_reader(resource),
-    };
+/// Итератор по порциям записей, возвращаемый [`read_batches`].
+pub struct BatchReader {
+    /// `None` после конца данных, ошибки или исчерпания `window.limit`
+    source: Option<Box<dyn RecordSource>>,
+    batch_size: usize,
+    /// Сколько записей ещё пропустить и сколько ещё выдать
+    window: ReadOptions,
+    /// Сколько записей ещё осталось во входных данных, если это известно заранее
+    expected: Option<usize>,
+    /// Имя входа для `Provenance::source`
+    name: Option<Arc<str>>,
+}
+
+impl BatchReader {
+    /// Как [`read_batches`], но из уже открытого потока.
+    pub(crate) fn from_reader(
+        reader: Box<dyn Read>,
+        format: Format,
+        options: &ParseOptions,
+        batch_size: usize,
+    ) -> Result<Self, CommonErr> {
+        Ok(BatchReader {
+            source: Some(source(reader, format, options)?),
+            batch_size: batch_size.max(1),
+            window: ReadOptions::default(),
+            expected: None,
+            name: None,
+        })
+    }
+
+    /// Выдаёт только записи из окна `read`: пропускает первые `read.skip` записей
+    /// и останавливает чтение после `read.limit` записей.
+    pub fn with_window(mut self, read: &ReadOptions) -> Self {
+        self.window = read.clone();
+        self
+    }
+
+    /// Докуда прочитан вход после уже выданных порций, вместе с оставшимся окном чтения;
+    /// с этого места чтение продолжает [`read_batches_from`].
+    ///
+    /// `None`, если место неизвестно: чтение закончилось, вход — сжатый бинарный файл
+    /// или порция кончилась посреди текстовой записи.
+    pub fn checkpoint(&self) -> Option<ReadCheckpoint> {
+        let mut checkpoint = self.source.as_ref()?.checkpoint()?;
+        checkpoint.window = self.window.clone();
+        Some(checkpoint)
+    }
+
+    /// Сколько записей ещё ожидается во входных данных: известно для бинарного файла
+    /// с индексом (см. `WriteOptions::bin_index`), у которого число записей хранится в конце.
+    ///
+    /// Подсказка для выделения памяти: записи вне `ParseOptions::time_window` и окна чтения
+    /// в неё входят, поэтому прочитано может быть меньше.
+    pub fn expected_records(&self) -> Option<usize> {
+        self.expected.map(|expected| match self.window.limit {
+            Some(limit) => expected.min(limit),
+            None => expected,
+        })
+    }
+
+    /// Отрезает от порции записи вне окна; `true`, если окно исчерпано.
+    fn cut(&mut self, batch: &mut ParseOutcome) -> bool {
+        let skip = self.window.skip.min(batch.records.len());
+        if skip > 0 {
+            self.window.skip -= skip;
+            batch.records.drain(..skip);
+            batch.provenance.drain(..skip.min(batch.provenance.len()));
+            // Строки перед пропущенными записями уходят вместе с ними
+            batch.annotations.retain(|a| a.before_record >= skip);
+            for annotation in &mut batch.annotations {
+                annotation.before_record -= skip;
+            }
+        }
+
+        let Some(limit) = self.window.limit.as_mut() else {
+            return false;
+        };
+        if batch.records.len() >= *limit {
+            let kept = *limit;
+            let truncated = batch.records.len() > kept;
+            batch.records.truncate(kept);
+            batch.provenance.truncate(kept);
+            batch
+                .annotations
+                .retain(|a| a.before_record < kept || (a.before_record == kept && !truncated));
+            *limit = 0;
+            return true;
+        }
+        *limit -= batch.records.len();
+        false
+    }
+}
+
+impl Iterator for BatchReader {
+    type Item = Result<ParseOutcome, CommonErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.window.limit == Some(0) {
+            self.source = None;
+        }
+        loop {
+            let source = self.source.as_mut()?;
+            match source.next_batch(self.batch_size) {
+                Ok(Some(mut batch)) => {
+                    if let Some(expected) = self.expected.as_mut() {
+                        *expected = expected.saturating_sub(batch.records.len() + batch.issues.len());
+                    }
+                    if self.cut(&mut batch) {
+                        self.source = None;
+                    }
+                    if let Some(name) = &self.name {
+                        batch.set_source(name);
+                    }
+                    // Пустыми бывают порции в самом конце данных и целиком пропущенные
+                    if !is_empty(&batch) {
+                        return Some(Ok(batch));
+                    }
+                }
+                Ok(None) => {
+                    self.source = None;
+                    return None;
+                }
+                Err(e) => {
+                    self.source = None;
+                    return Some(Err(e.into()));
+                }
+            }
+        }
+    }
+
+    /// Верхняя граница известна, если известно число записей: в каждой порции, кроме
+    /// последней, ровно `batch_size` записей и проблем, плюс порция для ошибки в конце данных.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.source.is_none() {
+            return (0, Some(0));
+        }
+        let upper = self.expected_records().map(|expected| expected.div_ceil(self.batch_size) + 1);
+        (0, upper)
+    }
+}
+
+fn is_empty(batch: &ParseOutcome) -> bool {
+    batch.records.is_empty()
+        && batch.issues.is_empty()
+        && batch.warnings.is_empty()
+        && batch.annotations.is_empty()
+}
+
+/// Число записей во входных данных, если его можно узнать, не читая их: у бинарного
+/// файла (или данных в памяти) с индексом оно записано в конце.
+#[cfg(feature = "bin")]
+fn expected_records(resource: &Resource, format: Format) -> Option<usize> {
+    if format != Format::YpBankBin || resource.is_stream() {
+        return None;
+    }
+    match resource {
+        Resource::File { path } => bin_index::record_count(&mut BufReader::new(File::open(path).ok()?)),
+        Resource::Memory { data } => bin_index::record_count(&mut std::io::Cursor::new(data.get_ref())),
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "bin"))]
+fn expected_records(_resource: &Resource, _format: Format) -> Option<usize> {
+    None
+}
+
+/// Открывает поток чтения для указанного ресурса.
+pub(crate) fn open(resource: Resource) -> Result<Box<dyn Read>, IoErr> {
+    match resource {
+        Resource::Console => Ok(super::stdin::open_stdin()),
+        Resource::File { path } => {
+            let file = File::open(&path).map_err(|e| IoErr::input(e, Some(path)))?;
+            Ok(Box::new(BufReader::new(file)))
+        },
+        Resource::Memory { data } => Ok(Box::new(data)),
+        #[cfg(feature = "zip")]
+        Resource::ZipMember { archive, member } => super::zip_member::open_member(&archive, &member),
+    }
+}
+
+/// Читает сведения о программе, записавшей файл (см. `WriteOptions::metadata`), не разбирая записи.
+///
+/// # Возвращает
+/// * `Ok(Some(ProducerMetadata))` — сведения о программе
+/// * `Ok(None)` — файл записан без сведений
+/// * `Err(CommonErr)` — ресурс не удалось открыть или заголовок бинарного файла повреждён
+pub fn read_metadata(resource: Resource, format: Format) -> Result<Option<ProducerMetadata>, CommonErr> {
+    let reader = open(resource)?;
+    match format {
+        #[cfg(feature = "bin")]
+        Format::YpBankBin => Ok(crate::parser::concrete::bin_psrser::read_file_metadata(reader)?),
+        // Строки `#@` в начале CSV (после BOM) и текстового документа
+        _ => {
+            let lines: Vec<String> = BufReader::new(reader)
+                .lines()
+                .map_while(Result::ok)
+                .map(|line| line.trim_start_matches('\u{feff}').trim_end().to_string())
+                .take_while(|line| line.starts_with(METADATA_PREFIX))
+                .collect();
+            Ok(ProducerMetadata::from_comment_lines(lines.iter().map(String::as_str)))
+        }
+    }
+}
+
+/// Ищет транзакцию по `tx_id`.
+///
+/// Для бинарного файла (или памяти) с индексом (см. `WriteOptions::bin_index`) запись
+/// читается по смещению без разбора остального файла; в остальных случаях ресурс
+/// читается целиком и просматривается.
+///
+/// # Возвращает
+/// * `Ok(Some(TxData))` — первая запись с таким `tx_id`
+/// * `Ok(None)` — записи нет
+/// * `Err(CommonErr)` — ресурс не удалось открыть или разобрать
+pub fn find_by_id(resource: Resource, format: Format, tx_id: &TxId) -> Result<Option<TxData>, CommonErr> {
+    // Именованный канал не перематывается, его остаётся прочитать подряд
+    #[cfg(feature = "bin")]
+    if format == Format::YpBankBin && !resource.is_stream() {
+        match resource {
+            Resource::File { path } => {
+                let file = File::open(&path).map_err(|e| IoErr::input(e, Some(path)))?;
+                return find_in_bin(BufReader::new(file), tx_id);
+            }
+            Resource::Memory { data } => return find_in_bin(data, tx_id),
+            _ => {}
+        }
+    }
+
+    Ok(read(resource, format)?.into_iter().find(|tx| tx.tx_id == *tx_id))
+}
+
+#[cfg(feature = "bin")]
+fn find_in_bin<R: Read + Seek + 'static>(mut reader: R, tx_id: &TxId) -> Result<Option<TxData>, CommonErr> {
+    if let Some(index) = BinIndex::read_from(&mut reader)? {
+        return Ok(index.get(&mut reader, tx_id)?);
+    }
+
+    reader.seek(SeekFrom::Start(0)).map_err(ParserErr::from)?;
+    let records = TxData::from_bin_reader(Box::new(reader))?;
+    Ok(records.into_iter().find(|tx| tx.tx_id == *tx_id))
+}
+
+pub(crate) fn read_from_resource(
+    resource: Box<dyn Read>,
+    format: Format,
+    options: &ParseOptions,
+) -> Result<ParseOutcome, ParserErr> {
+    match format {
+        #[cfg(feature = "bin")]
+        Format::YpBankBin => TxData::from_bin_reader_with(resource, options),
+        #[cfg(feature = "csv")]
+        Format::YpBankCsv => TxData::from_csv_reader_with(resource, options),
+        #[cfg(feature = "text")]
+        Format::YpBankText => TxData::from_text_reader_with(resource, options),
+        #[allow(unreachable_patterns)]
+        disabled => Err(super::format_disabled(disabled)),
+    }
 }
 
 
+/// Создаёт потоковый источник записей для формата.
+fn source(
+    reader: Box<dyn Read>,
+    format: Format,
+    options: &ParseOptions,
+) -> Result<Box<dyn RecordSource>, ParserErr> {
+    let options = options.clone();
+    match format {
+        #[cfg(feature = "bin")]
+        Format::YpBankBin => Ok(Box::new(BinSource::new(reader, options)?)),
+        #[cfg(feature = "csv")]
+        Format::YpBankCsv => Ok(Box::new(CsvSource::new(reader, options)?)),
+        #[cfg(feature = "text")]
+        Format::YpBankText => Ok(Box::new(TextSource::new(reader, options))),
+        #[allow(unreachable_patterns)]
+        disabled => Err(super::format_disabled(disabled)),
+    }
+}
 
-#[cfg(test)]
+#[cfg(all(test, feature = "csv"))]
 mod tests {
     use super::*;
+    use crate::model::time::TimeWindow;
     use std::io::Cursor;
 
     #[test]
@@ -57,14 +424,291 @@ mod tests {
         let mock_csv = Cursor::new(
             "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n42,WITHDRAWAL,101,0,30,1700000010,SUCCESS,\"Cash out\""
         );
-        let result = read_from_resource(Box::new(mock_csv), Format::YpBankCsv);
-        
+        let result = read_from_resource(Box::new(mock_csv), Format::YpBankCsv, &ParseOptions::default());
+
         assert!(result.is_ok());
-        let transactions = result.unwrap();
+        let transactions = result.unwrap().records;
         assert!(!transactions.is_empty());
     }
 
 
+    #[test]
+    fn test_read_batches_keep_running_line_numbers() {
+        let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,1700000000,SUCCESS,\"a\"\n\
+                   2,DEPOSIT,0,10,100,1700000001,SUCCESS,\"b\"\n\
+                   3,DEPOSIT,0,10,x,1700000002,SUCCESS,\"c\"\n\
+                   4,DEPOSIT,0,10,100,1700000003,SUCCESS,\"d\"\n\
+                   5,DEPOSIT,0,10,100,1700000004,SUCCESS,\"e\"\n";
+        let resource = Resource::Memory { data: Cursor::new(csv.as_bytes().to_vec()) };
+
+        let batches = read_batches(resource, Format::YpBankCsv, &ParseOptions::collect(), 2)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let sizes: Vec<_> = batches.iter().map(|b| (b.records.len(), b.issues.len())).collect();
+        assert_eq!(sizes, vec![(2, 0), (1, 1), (1, 0)]);
+        assert_eq!(batches[1].issues[0].line, Some(4));
+
+        // Порции в сумме дают те же записи в том же порядке, что и чтение целиком
+        let whole = read_from_resource(Box::new(Cursor::new(csv)), Format::YpBankCsv, &ParseOptions::collect());
+        let ids: Vec<_> = batches.iter().flat_map(|b| b.records.iter().filter_map(|tx| tx.tx_id.as_u64())).collect();
+        let whole_ids: Vec<_> = whole.unwrap().records.iter().filter_map(|tx| tx.tx_id.as_u64()).collect();
+        assert_eq!(ids, whole_ids);
+
+        // Окно отсчитывается по принятым записям, поперёк границ порций
+        let resource = Resource::Memory { data: Cursor::new(csv.as_bytes().to_vec()) };
+        let window = ReadOptions { skip: 1, limit: Some(2) };
+        let ids: Vec<_> = read_batches(resource, Format::YpBankCsv, &ParseOptions::collect(), 2)
+            .unwrap()
+            .with_window(&window)
+            .flat_map(|batch| batch.unwrap().records)
+            .filter_map(|tx| tx.tx_id.as_u64())
+            .collect();
+        assert_eq!(ids, vec![2, 4]);
+    }
+
+    #[test]
+    fn test_read_drops_records_outside_time_window() {
+        let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,1000,SUCCESS,\"a\"\n\
+                   2,DEPOSIT,0,10,100,2000,SUCCESS,\"b\"\n\
+                   3,DEPOSIT,0,10,100,3000,SUCCESS,\"c\"\n";
+        let options = ParseOptions {
+            time_window: TimeWindow {
+                since: Some(2000),
+                until: Some(3000),
+            },
+            ..ParseOptions::default()
+        };
+
+        let outcome = read_from_resource(Box::new(Cursor::new(csv)), Format::YpBankCsv, &options).unwrap();
+        let ids: Vec<_> = outcome.records.iter().filter_map(|tx| tx.tx_id.as_u64()).collect();
+        assert_eq!(ids, vec![2]);
+        assert!(outcome.issues.is_empty());
+    }
+
+    #[test]
+    fn test_interned_descriptions_share_storage_across_batches() {
+        let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,1000,SUCCESS,\"Monthly fee\"\n\
+                   2,DEPOSIT,0,10,100,2000,SUCCESS,\"Refund\"\n\
+                   3,DEPOSIT,0,10,100,3000,SUCCESS,\"Monthly fee\"\n";
+        let read = |intern_descriptions| {
+            let options = ParseOptions { intern_descriptions, ..ParseOptions::default() };
+            let resource = Resource::Memory { data: Cursor::new(csv.as_bytes().to_vec()) };
+            read_batches(resource, Format::YpBankCsv, &options, 1)
+                .unwrap()
+                .flat_map(|batch| batch.unwrap().records)
+                .collect::<Vec<_>>()
+        };
+
+        let interned = read(true);
+        assert!(std::sync::Arc::ptr_eq(&interned[0].description, &interned[2].description));
+        assert_eq!(&*interned[1].description, "Refund");
+
+        let plain = read(false);
+        assert!(!std::sync::Arc::ptr_eq(&plain[0].description, &plain[2].description));
+        assert_eq!(plain, interned);
+    }
+
+    #[cfg(feature = "bin")]
+    #[test]
+    fn test_indexed_bin_gives_record_count_upfront() {
+        use crate::parser::concrete::bin_psrser::TxnToBin;
+        use crate::parser::options::WriteOptions;
+
+        let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,1000,SUCCESS,\"a\"\n\
+                   2,DEPOSIT,0,10,100,2000,SUCCESS,\"b\"\n\
+                   3,DEPOSIT,0,10,100,3000,SUCCESS,\"c\"\n";
+        let txs = read_from_resource(Box::new(Cursor::new(csv)), Format::YpBankCsv, &ParseOptions::default())
+            .unwrap()
+            .records;
+        let indexed = WriteOptions { bin_index: true, ..WriteOptions::default() };
+        let memory = |bytes: &Vec<u8>| Resource::Memory { data: Cursor::new(bytes.clone()) };
+        let with_index = TxData::to_bin_many_with(&txs, &indexed).unwrap();
+        let without_index = TxData::to_bin_many_with(&txs, &WriteOptions::default()).unwrap();
+
+        let mut batches = read_batches(memory(&with_index), Format::YpBankBin, &ParseOptions::default(), 2).unwrap();
+        assert_eq!(batches.expected_records(), Some(3));
+        assert_eq!(batches.size_hint(), (0, Some(3)));
+        assert_eq!(batches.next().unwrap().unwrap().records.len(), 2);
+        assert_eq!(batches.expected_records(), Some(1));
+        assert_eq!(batches.next().unwrap().unwrap().records.len(), 1);
+        assert!(batches.next().is_none());
+        assert_eq!(batches.size_hint(), (0, Some(0)));
+
+        let outcome = read_with(memory(&with_index), Format::YpBankBin, &ParseOptions::default()).unwrap();
+        let ids: Vec<_> = outcome.records.iter().filter_map(|tx| tx.tx_id.as_u64()).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(outcome.records.capacity(), 3);
+
+        let batches = read_batches(memory(&without_index), Format::YpBankBin, &ParseOptions::default(), 2).unwrap();
+        assert_eq!(batches.expected_records(), None);
+        assert_eq!(batches.size_hint(), (0, None));
+    }
+
+    #[test]
+    fn test_provenance_points_at_source_records() {
+        let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,1000,SUCCESS,\"a\"\n\
+                   2,DEPOSIT,0,10,x,2000,SUCCESS,\"b\"\n\
+                   3,DEPOSIT,0,10,100,3000,SUCCESS,\"c\"\n";
+        let options = ParseOptions { provenance: true, ..ParseOptions::collect() };
+        let resource = Resource::Memory { data: Cursor::new(csv.as_bytes().to_vec()) };
+
+        let outcome = read_with(resource, Format::YpBankCsv, &options).unwrap();
+        assert_eq!(outcome.provenance.len(), 2);
+        let third = &outcome.provenance[1];
+        assert_eq!((third.record, third.line), (3, 4));
+        let offset = third.offset.unwrap() as usize;
+        assert!(csv[offset..].starts_with("3,DEPOSIT"));
+        assert_eq!(third.to_string(), format!("memory, record 3, line 4, byte {}", offset));
+
+        // Без настройки происхождение не собирается
+        let outcome = read_from_resource(Box::new(Cursor::new(csv)), Format::YpBankCsv, &ParseOptions::collect()).unwrap();
+        assert!(outcome.provenance.is_empty());
+    }
+
+    #[cfg(feature = "bin")]
+    #[test]
+    fn test_provenance_gives_bin_frame_offsets() {
+        use crate::parser::concrete::bin_psrser::TxnToBin;
+        use crate::parser::options::{BinProfile, WriteOptions};
+
+        let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,1000,SUCCESS,\"a\"\n\
+                   2,DEPOSIT,0,10,100,2000,SUCCESS,\"longer description\"\n";
+        let txs = read_from_resource(Box::new(Cursor::new(csv)), Format::YpBankCsv, &ParseOptions::default())
+            .unwrap()
+            .records;
+        let options = ParseOptions { provenance: true, ..ParseOptions::default() };
+
+        let bytes = TxData::to_bin_many_with(&txs, &WriteOptions::default()).unwrap();
+        let outcome = read_with(Resource::Memory { data: Cursor::new(bytes.clone()) }, Format::YpBankBin, &options).unwrap();
+        for at in &outcome.provenance {
+            let offset = at.offset.unwrap() as usize;
+            assert_eq!(&bytes[offset..offset + 4], b"YPBN", "{}", at);
+        }
+
+        let compact = WriteOptions { bin_profile: BinProfile::CompactLe, ..WriteOptions::default() };
+        let bytes = TxData::to_bin_many_with(&txs, &compact).unwrap();
+        let outcome = read_with(Resource::Memory { data: Cursor::new(bytes.clone()) }, Format::YpBankBin, &options).unwrap();
+        // Запись начинается сразу за заголовком файла или за предыдущей записью: длина-varint и тело
+        let offsets: Vec<_> = outcome.provenance.iter().map(|at| at.offset.unwrap() as usize).collect();
+        let body = bytes[offsets[0]] as usize;
+        assert!(body < 0x80);
+        assert_eq!(offsets[1], offsets[0] + 1 + body);
+    }
+
+    #[cfg(all(feature = "bin", feature = "csv", feature = "text"))]
+    #[test]
+    fn test_metadata_roundtrip_for_every_format() {
+        use crate::parser::io::writer::write_with;
+        use crate::parser::options::{BinProfile, WriteOptions};
+
+        let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,1000,SUCCESS,\"a\"\n\
+                   2,DEPOSIT,0,10,100,2000,SUCCESS,\"b\"\n";
+        let txs = read_from_resource(Box::new(Cursor::new(csv)), Format::YpBankCsv, &ParseOptions::default())
+            .unwrap()
+            .records;
+        let metadata = ProducerMetadata {
+            producer: "cli-converter 0.0.1".to_string(),
+            created_ms: 1700000000000,
+            source_sha256: Some("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".to_string()),
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let options = WriteOptions { metadata: Some(metadata.clone()), csv_bom: true, ..WriteOptions::default() };
+        let compact = WriteOptions { bin_profile: BinProfile::CompactLe, ..options.clone() };
+
+        for (format, options) in [
+            (Format::YpBankCsv, &options),
+            (Format::YpBankText, &options),
+            (Format::YpBankBin, &options),
+            (Format::YpBankBin, &compact),
+        ] {
+            let file = || Resource::File { path: dir.path().join("out") };
+            write_with(&txs, &[], file(), format, options).unwrap();
+            assert_eq!(read_metadata(file(), format).unwrap().as_ref(), Some(&metadata), "{:?}", format);
+            // Обычные читатели пропускают сведения
+            assert_eq!(read(file(), format).unwrap(), txs.iter().map(|tx| TxData { format, ..tx.clone() }).collect::<Vec<_>>());
+
+            write_with(&txs, &[], file(), format, &WriteOptions::default()).unwrap();
+            assert_eq!(read_metadata(file(), format).unwrap(), None);
+        }
+
+        // Номера строк CSV считаются вместе со строками сведений
+        let mut lines = metadata.comment_lines().join("\n");
+        lines.push('\n');
+        lines.push_str(&csv.replace("100,2000", "x,2000"));
+        let outcome = read_from_resource(Box::new(Cursor::new(lines)), Format::YpBankCsv, &ParseOptions::collect()).unwrap();
+        assert_eq!(outcome.issues[0].line, Some(3 + 3));
+    }
+
+    #[cfg(all(feature = "bin", feature = "csv", feature = "text"))]
+    #[test]
+    fn test_read_batches_from_checkpoint_continues_every_format() {
+        use crate::parser::io::writer::write_with;
+        use crate::parser::options::{BinProfile, WriteOptions};
+
+        let mut csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n".to_string();
+        for id in 1..=5 {
+            csv.push_str(&format!("{},DEPOSIT,0,10,100,{},SUCCESS,\"multi\nline {}\"\n", id, 1000 + id, id));
+        }
+        let multiline = read_from_resource(Box::new(Cursor::new(csv)), Format::YpBankCsv, &ParseOptions::default())
+            .unwrap()
+            .records;
+        // В текстовом формате описание в одну строку
+        let txs: Vec<_> = multiline.iter().map(|tx| TxData { description: tx.description.replace('\n', " ").into(), ..tx.clone() }).collect();
+        let dir = tempfile::tempdir().unwrap();
+        let metadata = ProducerMetadata { producer: "test".to_string(), created_ms: 0, source_sha256: None };
+        let options = WriteOptions { metadata: Some(metadata), csv_bom: true, ..WriteOptions::default() };
+        let compact = WriteOptions { bin_profile: BinProfile::CompactLe, ..WriteOptions::default() };
+        let parse = ParseOptions { provenance: true, ..ParseOptions::default() };
+        let window = ReadOptions { skip: 1, limit: Some(3) };
+
+        for (format, options, txs) in [
+            (Format::YpBankCsv, &options, &multiline),
+            (Format::YpBankText, &options, &txs),
+            (Format::YpBankBin, &options, &txs),
+            (Format::YpBankBin, &compact, &txs),
+        ] {
+            let file = || Resource::File { path: dir.path().join("in") };
+            write_with(txs, &[], file(), format, options).unwrap();
+            let mut batches = read_batches(file(), format, &parse, 2).unwrap().with_window(&window);
+            batches.next().unwrap().unwrap();
+            let checkpoint = batches.checkpoint().unwrap();
+            assert_eq!((checkpoint.records, &checkpoint.window), (2, &ReadOptions { skip: 0, limit: Some(2) }));
+            let rest: Vec<_> = batches.map(Result::unwrap).collect();
+
+            // С места контрольной точки выдаются те же записи с теми же номерами строк
+            let resumed: Vec<_> = read_batches_from(file(), format, &parse, 2, &checkpoint)
+                .unwrap()
+                .unwrap()
+                .map(Result::unwrap)
+                .collect();
+            let records = |batches: &[ParseOutcome]| batches.iter().flat_map(|b| b.records.clone()).collect::<Vec<_>>();
+            let provenance = |batches: &[ParseOutcome]| batches.iter().flat_map(|b| b.provenance.clone()).collect::<Vec<_>>();
+            assert_eq!(records(&resumed), records(&rest), "{:?}", format);
+            assert_eq!(provenance(&resumed), provenance(&rest), "{:?}", format);
+            assert_eq!(records(&resumed).len(), 2);
+        }
+
+        // В сжатых блоках смещения записей в файле нет
+        #[cfg(feature = "zstd")]
+        {
+            let zstd = WriteOptions { bin_compression: crate::parser::options::BinCompression::Zstd { level: 3 }, ..WriteOptions::default() };
+            let file = Resource::File { path: dir.path().join("in.zst") };
+            write_with(&txs, &[], file.clone(), Format::YpBankBin, &zstd).unwrap();
+            let mut batches = read_batches(file, Format::YpBankBin, &parse, 2).unwrap();
+            batches.next().unwrap().unwrap();
+            assert_eq!(batches.checkpoint(), None);
+        }
+    }
+
     #[test]
     fn test_read_file_not_found_returns_error() {
         // Пытаемся прочитать несуществующий файл
@@ -72,7 +716,12 @@ mod tests {
             path: "/this/path/does/not/exist.tx".to_string().into(),
         };
         let result = read(resource, Format::YpBankCsv);
-        
-        assert!(result.is_err());
+
+        if let Err(CommonErr::IO(IoErr::InputErr { kind, path, .. })) = result {
+            assert_eq!(kind, std::io::ErrorKind::NotFound);
+            assert_eq!(path, Some("/this/path/does/not/exist.tx".into()));
+        } else {
+            panic!()
+        }
     }
 }
\ No newline at end of file
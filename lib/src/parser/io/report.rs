@@ -0,0 +1,152 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::model::errors::{CommonErr, IoErr, ParseIssue};
+use crate::model::resource::Resource;
+use crate::parser::io::writer::write;
+
+/// Формат отчёта о проблемах разбора.
+#[cfg_attr(feature = "console", derive(clap::ValueEnum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    /// Массив объектов `{"line": .., "field": .., "reason": ..}`
+    #[default]
+    Json,
+    /// Таблица с заголовком `LINE,FIELD,REASON`
+    Csv,
+}
+
+/// Записывает отчёт о проблемах, собранных в режиме `ErrorMode::Collect`.
+///
+/// Отсутствующие номер строки и поле записываются как `null` (JSON) или пустая ячейка (CSV).
+/// Возвращает размер записанных данных.
+pub fn write_issues_report(
+    issues: &[ParseIssue],
+    resource: Resource,
+    format: ReportFormat,
+) -> Result<usize, CommonErr> {
+    let rows = issues.iter().map(|issue| (None, issue));
+    let content = match format {
+        ReportFormat::Json => render_json(rows),
+        ReportFormat::Csv => render_csv(rows, false),
+    };
+    write_report(&content, resource)
+}
+
+/// Записывает общий отчёт о проблемах нескольких входных файлов.
+///
+/// Как [`write_issues_report`], но у каждой проблемы указан файл: поле `file` в JSON
+/// и первый столбец `FILE` в CSV.
+pub fn write_files_issues_report(
+    files: &[(PathBuf, Vec<ParseIssue>)],
+    resource: Resource,
+    format: ReportFormat,
+) -> Result<usize, CommonErr> {
+    let rows = files
+        .iter()
+        .flat_map(|(file, issues)| issues.iter().map(move |issue| (Some(file.as_path()), issue)));
+    let content = match format {
+        ReportFormat::Json => render_json(rows),
+        ReportFormat::Csv => render_csv(rows, true),
+    };
+    write_report(&content, resource)
+}
+
+pub(crate) fn write_report(content: &str, resource: Resource) -> Result<usize, CommonErr> {
+    let path = match &resource {
+        Resource::File { path } => Some(path.clone()),
+        _ => None,
+    };
+    let mut output = write(resource)?;
+    output
+        .write_all(content.as_bytes())
+        .and_then(|_| output.flush())
+        .map_err(|e| IoErr::output(e, path))?;
+
+    Ok(content.len())
+}
+
+fn render_json<'a>(rows: impl Iterator<Item = (Option<&'a Path>, &'a ParseIssue)>) -> String {
+    let rows: Vec<serde_json::Value> = rows
+        .map(|(file, issue)| {
+            let mut row = serde_json::json!({
+                "line": issue.line,
+                "field": issue.field,
+                "reason": issue.reason,
+            });
+            if let Some(file) = file {
+                row["file"] = file.display().to_string().into();
+            }
+            row
+        })
+        .collect();
+    let mut content = serde_json::Value::Array(rows).to_string();
+    content.push('\n');
+    content
+}
+
+fn render_csv<'a>(rows: impl Iterator<Item = (Option<&'a Path>, &'a ParseIssue)>, with_file: bool) -> String {
+    let mut content = String::from(if with_file { "FILE,LINE,FIELD,REASON\n" } else { "LINE,FIELD,REASON\n" });
+    for (file, issue) in rows {
+        if with_file {
+            let file = file.map(|f| f.display().to_string()).unwrap_or_default();
+            content.push_str(&format!("{},", quote(&file)));
+        }
+        let line = issue.line.map(|l| l.to_string()).unwrap_or_default();
+        let field = issue.field.unwrap_or_default();
+        content.push_str(&format!("{},{},{}\n", line, field, quote(&issue.reason)));
+    }
+    content
+}
+
+pub(crate) fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::errors::ParserErr;
+
+    fn sample_issues() -> Vec<ParseIssue> {
+        vec![
+            ParserErr::InvalidValue {
+                field: "TX_TYPE",
+                value: "GIFT".to_string(),
+            }
+            .at_line(3)
+            .into(),
+            ParserErr::Malformed {
+                msg: "broken \"quote\"".to_string(),
+            }
+            .into(),
+        ]
+    }
+
+    #[test]
+    fn test_render_json() {
+        let json = render_json(sample_issues().iter().map(|issue| (None, issue)));
+        assert_eq!(
+            json,
+            "[{\"field\":\"TX_TYPE\",\"line\":3,\"reason\":\"Invalid TX_TYPE: GIFT\"},\
+             {\"field\":null,\"line\":null,\"reason\":\"Malformed input: broken \\\"quote\\\"\"}]\n"
+        );
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let csv = render_csv(sample_issues().iter().map(|issue| (None, issue)), false);
+        assert_eq!(
+            csv,
+            "LINE,FIELD,REASON\n3,TX_TYPE,\"Invalid TX_TYPE: GIFT\"\n,,\"Malformed input: broken \"\"quote\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_render_csv_with_files() {
+        let issues = sample_issues();
+        let file = PathBuf::from("in/a.csv");
+        let csv = render_csv(issues.iter().take(1).map(|issue| (Some(file.as_path()), issue)), true);
+        assert_eq!(csv, "FILE,LINE,FIELD,REASON\n\"in/a.csv\",3,TX_TYPE,\"Invalid TX_TYPE: GIFT\"\n");
+    }
+}
@@ -0,0 +1,126 @@
+//! Чтение стандартного ввода с ограничением времени ожидания.
+//!
+//! В автоматизации вызов без данных на stdin (забытый конвейер, упавший производитель)
+//! не должен висеть бесконечно: с [`set_stdin_timeout`] чтение из `Resource::Console`
+//! прерывается ошибкой `TimedOut`, если данные не приходят дольше заданного времени.
+
+use std::io::{self, Read};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError};
+use std::sync::RwLock;
+use std::thread;
+use std::time::Duration;
+
+/// Сколько кусков stdin читается заранее, пока разбор их не забрал.
+const PREFETCH_CHUNKS: usize = 4;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+static STDIN_TIMEOUT: RwLock<Option<Duration>> = RwLock::new(None);
+
+/// Задаёт для всего процесса, сколько ждать очередной порции данных на stdin;
+/// `None` (по умолчанию) — ждать сколько угодно.
+pub fn set_stdin_timeout(timeout: Option<Duration>) {
+    *STDIN_TIMEOUT.write().unwrap_or_else(|e| e.into_inner()) = timeout;
+}
+
+/// Поток чтения stdin с учётом [`set_stdin_timeout`].
+pub(crate) fn open_stdin() -> Box<dyn Read> {
+    match *STDIN_TIMEOUT.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(timeout) => Box::new(TimeoutReader::new(io::stdin(), timeout)),
+        None => Box::new(io::stdin()),
+    }
+}
+
+/// Читает источник в фоновом потоке и отдаёт данные, пока очередная порция
+/// приходит не дольше чем за `timeout`.
+pub(crate) struct TimeoutReader {
+    chunks: Receiver<io::Result<Vec<u8>>>,
+    pending: Vec<u8>,
+    offset: usize,
+    timeout: Duration,
+    finished: bool,
+}
+
+impl TimeoutReader {
+    pub(crate) fn new<R: Read + Send + 'static>(mut source: R, timeout: Duration) -> Self {
+        let (tx, chunks) = sync_channel(PREFETCH_CHUNKS);
+        // Поток, заблокированный на чтении, завершится вместе с процессом
+        thread::spawn(move || loop {
+            let mut chunk = vec![0; CHUNK_SIZE];
+            let (chunk, last) = match source.read(&mut chunk) {
+                Ok(n) => {
+                    chunk.truncate(n);
+                    (Ok(chunk), n == 0)
+                },
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => (Err(e), true),
+            };
+            if tx.send(chunk).is_err() || last {
+                break;
+            }
+        });
+        TimeoutReader {
+            chunks,
+            pending: Vec::new(),
+            offset: 0,
+            timeout,
+            finished: false,
+        }
+    }
+}
+
+impl Read for TimeoutReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.offset == self.pending.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            match self.chunks.recv_timeout(self.timeout) {
+                Ok(Ok(chunk)) if chunk.is_empty() => self.finished = true,
+                Ok(Ok(chunk)) => {
+                    self.pending = chunk;
+                    self.offset = 0;
+                },
+                Ok(Err(e)) => {
+                    self.finished = true;
+                    return Err(e);
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("no data on stdin for {:?}", self.timeout),
+                    ))
+                },
+                Err(RecvTimeoutError::Disconnected) => self.finished = true,
+            }
+        }
+        let n = buf.len().min(self.pending.len() - self.offset);
+        buf[..n].copy_from_slice(&self.pending[self.offset..self.offset + n]);
+        self.offset += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_reader_times_out_without_data() {
+        let (rx, mut tx) = io::pipe().unwrap();
+        let mut reader = TimeoutReader::new(rx, Duration::from_millis(50));
+
+        tx.write_all(b"TX_ID: 1\n").unwrap();
+        let mut buf = [0; 64];
+        assert_eq!(reader.read(&mut buf).unwrap(), 9);
+        let Err(e) = reader.read(&mut buf) else {
+            panic!("expected a timeout");
+        };
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+
+        drop(tx);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+    }
+}
@@ -1,12 +1,21 @@
-use std::fs::File;
-use std::io::{self, BufReader, BufWriter, Cursor, Read, Write, stdin, stdout};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom, Write, stdout};
+use std::path::PathBuf;
 
-use crate::console::commands::Resource;
 use crate::model::data::{Format, TxData};
-use crate::model::errors::ParserErr;
-use crate::parser::concrete::bin_psrser::{TxnFromBin, TxnToBin};
-use crate::parser::concrete::csv_parser::{TxnFromCsv, TxnToCsv};
-use crate::parser::concrete::text_parser::{TxnFromText, TxnToText};
+use crate::model::tx_id::TxId;
+use crate::model::errors::{CommonErr, IoErr, ParseWarning, ParserErr, WarningKind};
+use crate::model::normalize::DescriptionNormalization;
+use crate::model::resource::Resource;
+use crate::parser::concrete::RecordSink;
+use crate::parser::options::{DescriptionLengthPolicy, TextAnnotation, WriteCheckpoint, WriteOptions, DEFAULT_BUFFER_SIZE};
+#[cfg(feature = "bin")]
+use crate::parser::concrete::bin_psrser::BinSink;
+#[cfg(feature = "csv")]
+use crate::parser::concrete::csv_parser::CsvSink;
+#[cfg(feature = "text")]
+use crate::parser::concrete::text_parser::TextSink;
 
 
 /// Записывает коллекцию транзакций в указанный ресурс в заданном формате.
@@ -18,52 +27,399 @@ use crate::parser::concrete::text_parser::{TxnFromText, TxnToText};
 /// Возвращаемое значение
 ///
 /// Возвращает `Ok(usize)` при успешной записи и сбросе буфера. Возвращает размер записанных данных
-/// При возникновении ошибок возвращает ParserErr::ParseErr, содержащий
-/// строковое описание ошибки ввода-вывода или сериализации.
+/// При возникновении ошибок возвращает `CommonErr::IO` (ресурс не удалось создать или записать)
+/// или `CommonErr::Parser`, описывающий ошибку сериализации.
 pub fn write_to_resource(
     txns: &[TxData],
     resource: Resource,
     format: Format,
-) -> Result<usize, ParserErr> {
-    let mut output = write(resource)?;
+) -> Result<usize, CommonErr> {
+    write_with(txns, &[], resource, format, &WriteOptions::default())
+}
 
-    let data_to_write = match format {
-        Format::YpBankBin => {
-            let mut buffer = Vec::new();
-            for txn in txns {
-                let bytes = txn.to_bin()?;
-                buffer.extend(bytes);
+/// Как [`write_to_resource`], но с настройками записи.
+///
+/// `annotations` (комментарии и пустые строки из `ParseOutcome::annotations`) возвращаются
+/// на место в текстовом формате; для остальных форматов они игнорируются.
+pub fn write_with(
+    txns: &[TxData],
+    annotations: &[TextAnnotation],
+    resource: Resource,
+    format: Format,
+    options: &WriteOptions,
+) -> Result<usize, CommonErr> {
+    let mut writer = write_batches(resource, format, options)?;
+    writer.write_batch(txns, annotations)?;
+    writer.finish()
+}
+
+/// Открывает ресурс для записи порциями (см. [`read_batches`](super::reader::read_batches)).
+///
+/// Записи сериализуются сразу в поток, без промежуточного буфера на весь документ.
+/// Документ считается записанным только после [`BatchWriter::finish`]: для бинарного
+/// формата там дописываются маркер конца и индекс.
+pub fn write_batches(
+    resource: Resource,
+    format: Format,
+    options: &WriteOptions,
+) -> Result<BatchWriter, CommonErr> {
+    if options.append {
+        if format != Format::YpBankBin {
+            return Err(ParserErr::NotAppendable { format }.into());
+        }
+        if let Resource::File { path } = &resource {
+            if fs::metadata(path).is_ok_and(|meta| meta.len() > 0) {
+                return append_batches(path.clone(), format, options);
             }
-            buffer
         }
-        Format::YpBankCsv => {
-            let mut lines = Vec::new();
-            for txn in txns {
-                let line = txn.to_csv()?;
-                lines.push(line);
+    }
+    let sink = sink(format, options)?;
+    let path = match &resource {
+        Resource::File { path } => Some(path.clone()),
+        _ => None,
+    };
+    Ok(BatchWriter {
+        output: CountingWriter {
+            inner: write_buffered(resource, options.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE))?,
+            written: 0,
+        },
+        sink,
+        path,
+        records: 0,
+        flush_interval: options.flush_interval_records,
+        unflushed: 0,
+        normalize: options.normalize_description.clone(),
+        known: (options.append && options.if_absent).then(HashSet::new),
+        description_limit: options.max_description_len.map(|limit| (limit, options.description_length_policy)),
+        warnings: Vec::new(),
+    })
+}
+
+/// Дописывает записи в конец непустого бинарного файла `path` (см. `WriteOptions::append`).
+///
+/// Маркер конца, индекс и таблица блоков файла отбрасываются и пишутся заново в
+/// [`BatchWriter::finish`] вместе с новыми записями. С `WriteOptions::if_absent` записи
+/// с `TX_ID`, которые уже есть в файле, пропускаются.
+///
+/// # Возвращает
+/// * `Ok(BatchWriter)` — документ, готовый к записи следующих порций
+/// * `Err(CommonErr::IO)` — файл не удалось открыть или обрезать
+/// * `Err(CommonErr::Parser)` — `ParserErr::NotAppendable` для формата, в который нельзя дописывать,
+///   или ошибка чтения заголовка и хвоста файла
+pub fn append_batches(path: PathBuf, format: Format, options: &WriteOptions) -> Result<BatchWriter, CommonErr> {
+    let output_err = |e| IoErr::output(e, Some(path.clone()));
+    let mut file = OpenOptions::new().read(true).write(true).open(&path).map_err(output_err)?;
+    let Appended { sink, records_end, known } = append_sink(&mut file, &path, format, options)?;
+    file.set_len(records_end).map_err(output_err)?;
+    file.seek(SeekFrom::End(0)).map_err(output_err)?;
+
+    Ok(BatchWriter {
+        output: CountingWriter {
+            inner: Box::new(BufWriter::with_capacity(options.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE), file)),
+            written: records_end as usize,
+        },
+        sink,
+        path: Some(path),
+        records: 0,
+        flush_interval: options.flush_interval_records,
+        unflushed: 0,
+        normalize: options.normalize_description.clone(),
+        known,
+        description_limit: options.max_description_len.map(|limit| (limit, options.description_length_policy)),
+        warnings: Vec::new(),
+    })
+}
+
+/// Сколько записей читается за шаг, когда `TX_ID` файла без индекса собираются чтением.
+#[cfg(feature = "bin")]
+const SCAN_BATCH_SIZE: usize = 10_000;
+
+/// Файл, открытый для дописывания.
+struct Appended {
+    /// Приёмник, продолжающий файл
+    sink: Box<dyn RecordSink>,
+    /// Смещение конца записей файла
+    records_end: u64,
+    /// С `WriteOptions::if_absent` — `TX_ID` уже записанных записей
+    known: Option<HashSet<TxId>>,
+}
+
+/// Читает, как продолжить файл формата `format` (см. [`append_batches`]).
+#[cfg_attr(not(feature = "bin"), allow(unused_variables))]
+fn append_sink(
+    file: &mut File,
+    path: &std::path::Path,
+    format: Format,
+    options: &WriteOptions,
+) -> Result<Appended, CommonErr> {
+    match format {
+        #[cfg(feature = "bin")]
+        Format::YpBankBin => {
+            let state = crate::parser::concrete::bin_index::append_state(file)?;
+            let known = match (options.if_absent, state.indexed_ids()) {
+                (false, _) => None,
+                (true, Some(ids)) => Some(ids.cloned().collect()),
+                // Без индекса `TX_ID` собираются чтением всего файла
+                (true, None) => {
+                    let mut known = HashSet::new();
+                    let from = Resource::File { path: path.to_path_buf() };
+                    for batch in super::reader::read_batches(from, format, &Default::default(), SCAN_BATCH_SIZE)? {
+                        known.extend(batch?.records.into_iter().map(|tx| tx.tx_id));
+                    }
+                    Some(known)
+                }
+            };
+            let records_end = state.records_end();
+            Ok(Appended { sink: Box::new(BinSink::append(options.clone(), state)), records_end, known })
+        }
+        _ => Err(ParserErr::NotAppendable { format }.into()),
+    }
+}
+
+/// Продолжает документ в файле `path`, записанный до `checkpoint` (см. [`BatchWriter::checkpoint`]).
+///
+/// Всё, что записано в файл после контрольной точки, отбрасывается. Бинарный файл с индексом
+/// продолжить нельзя — смещения записанных записей в контрольной точке не хранятся.
+///
+/// # Возвращает
+/// * `Ok(BatchWriter)` — документ, готовый к записи следующих порций
+/// * `Err(CommonErr::IO)` — файл не удалось открыть или обрезать
+/// * `Err(CommonErr::Parser)` — `ParserErr::NotResumable` для формата, который нельзя продолжить
+pub fn resume_batches(
+    path: PathBuf,
+    format: Format,
+    options: &WriteOptions,
+    checkpoint: &WriteCheckpoint,
+) -> Result<BatchWriter, CommonErr> {
+    let mut sink = sink(format, options)?;
+    if !sink.resume(checkpoint) {
+        return Err(ParserErr::NotResumable { format }.into());
+    }
+
+    let output_err = |e| IoErr::output(e, Some(path.clone()));
+    let mut file = OpenOptions::new().write(true).open(&path).map_err(output_err)?;
+    file.set_len(checkpoint.bytes).map_err(output_err)?;
+    file.seek(SeekFrom::End(0)).map_err(output_err)?;
+
+    Ok(BatchWriter {
+        output: CountingWriter {
+            inner: Box::new(BufWriter::with_capacity(options.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE), file)),
+            written: checkpoint.bytes as usize,
+        },
+        sink,
+        path: Some(path),
+        records: checkpoint.records,
+        flush_interval: options.flush_interval_records,
+        unflushed: 0,
+        normalize: options.normalize_description.clone(),
+        known: None,
+        description_limit: options.max_description_len.map(|limit| (limit, options.description_length_policy)),
+        warnings: Vec::new(),
+    })
+}
+
+/// Документ, записываемый порциями; создаётся [`write_batches`].
+pub struct BatchWriter {
+    output: CountingWriter,
+    sink: Box<dyn RecordSink>,
+    path: Option<PathBuf>,
+    records: u64,
+    /// `WriteOptions::flush_interval_records`
+    flush_interval: Option<usize>,
+    /// Записей с прошлого сброса
+    unflushed: usize,
+    /// `WriteOptions::normalize_description`
+    normalize: DescriptionNormalization,
+    /// С `WriteOptions::if_absent` — `TX_ID`, которые уже есть в файле; записи с ними пропускаются
+    known: Option<HashSet<TxId>>,
+    /// `WriteOptions::max_description_len` и `WriteOptions::description_length_policy`
+    description_limit: Option<(usize, DescriptionLengthPolicy)>,
+    /// Предупреждения записи, ещё не забранные [`BatchWriter::take_warnings`]
+    warnings: Vec<ParseWarning>,
+}
+
+/// Запись `tx` в том виде, в каком её запишет [`BatchWriter`] с `options`: с нормализованным
+/// описанием, обрезанным по `max_description_len` при `DescriptionLengthPolicy::Truncate`.
+pub(crate) fn as_written(tx: TxData, options: &WriteOptions) -> TxData {
+    let tx = if options.normalize_description.is_noop() { tx } else { options.normalize_description.apply_tx(tx) };
+    match (options.max_description_len, options.description_length_policy) {
+        (Some(limit), DescriptionLengthPolicy::Truncate) if too_long(&tx, limit) => truncate_description(tx, limit),
+        _ => tx,
+    }
+}
+
+/// Описание `tx` длиннее `limit` символов.
+fn too_long(tx: &TxData, limit: usize) -> bool {
+    // Символов не больше, чем байт: у коротких описаний символы не считаются
+    tx.description.len() > limit && tx.description.chars().count() > limit
+}
+
+/// Описание, обрезанное до `limit` символов вместе с `TRUNCATION_MARKER`.
+fn truncate_description(tx: TxData, limit: usize) -> TxData {
+    DescriptionNormalization { max_len: Some(limit), ..Default::default() }.apply_tx(tx)
+}
+
+impl BatchWriter {
+    /// Дописывает порцию записей; `annotations` отсчитываются от начала порции.
+    pub fn write_batch(&mut self, txns: &[TxData], annotations: &[TextAnnotation]) -> Result<(), CommonErr> {
+        let normalized: Vec<TxData>;
+        let txns = if self.normalize.is_noop() {
+            txns
+        } else {
+            normalized = txns.iter().map(|tx| self.normalize.apply_tx(tx.clone())).collect();
+            &normalized
+        };
+        let absent: Vec<TxData>;
+        let txns = match &mut self.known {
+            Some(known) => {
+                absent = txns.iter().filter(|tx| known.insert(tx.tx_id.clone())).cloned().collect();
+                &absent
             }
-            let content = lines.join("\n");
-            content.into_bytes()
-        }
-        Format::YpBankText => {
-            let mut lines = Vec::new();
-            for txn in txns {
-                let line = txn.to_text()?;
-                lines.push(line);
+            None => txns,
+        };
+        let limited: Vec<TxData>;
+        let txns = match self.limit_descriptions(txns)? {
+            Some(txns) => {
+                limited = txns;
+                &limited
             }
-            let content = lines.join("\n");
-            content.into_bytes()
+            None => txns,
+        };
+        let written = self.sink.write_batch(&mut self.output, txns, annotations);
+        written.map_err(|e| self.output_err(e))?;
+        self.records += txns.len() as u64;
+        self.unflushed += txns.len();
+        if self.flush_interval.is_some_and(|interval| self.unflushed >= interval) {
+            self.flush()?;
         }
-    };
+        Ok(())
+    }
+
+    /// Предупреждения, появившиеся при записи с прошлого вызова (`DescriptionLengthPolicy::Warn`).
+    pub fn take_warnings(&mut self) -> Vec<ParseWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Применяет к порции предел длины описания; `None`, если менять порцию не нужно.
+    fn limit_descriptions(&mut self, txns: &[TxData]) -> Result<Option<Vec<TxData>>, ParserErr> {
+        let Some((limit, policy)) = self.description_limit else {
+            return Ok(None);
+        };
+        if !txns.iter().any(|tx| too_long(tx, limit)) {
+            return Ok(None);
+        }
+        let mut limited = Vec::with_capacity(txns.len());
+        for tx in txns {
+            let mut tx = tx.clone();
+            if too_long(&tx, limit) {
+                let chars = tx.description.chars().count();
+                match policy {
+                    DescriptionLengthPolicy::Truncate => tx = truncate_description(tx, limit),
+                    DescriptionLengthPolicy::Reject => {
+                        return Err(ParserErr::DescriptionTooLong { tx_id: tx.tx_id, chars, limit });
+                    }
+                    DescriptionLengthPolicy::Warn => {
+                        let kind = WarningKind::LongDescription { tx_id: tx.tx_id.clone(), chars, limit };
+                        self.warnings.push(ParseWarning { line: None, kind });
+                    }
+                }
+            }
+            limited.push(tx);
+        }
+        Ok(Some(limited))
+    }
+
+    /// Сбрасывает буфер вывода, чтобы записанные порции оказались в ресурсе.
+    pub fn flush(&mut self) -> Result<(), CommonErr> {
+        let flushed = self.sink.flush(&mut self.output);
+        flushed.map_err(|e| self.output_err(e))?;
+        self.unflushed = 0;
+        self.output
+            .flush()
+            .map_err(|e| IoErr::output(e, self.path.clone()).into())
+    }
+
+    /// Сколько байт уже записано.
+    pub fn written(&self) -> usize {
+        self.output.written
+    }
+
+    /// Контрольная точка для [`resume_batches`].
+    ///
+    /// Имеет смысл только после [`BatchWriter::flush`]: иначе часть данных может
+    /// остаться в буфере и не попасть в файл.
+    pub fn checkpoint(&self) -> WriteCheckpoint {
+        WriteCheckpoint {
+            bytes: self.output.written as u64,
+            records: self.records,
+            after_record: self.sink.after_record(),
+        }
+    }
+
+    /// Завершает документ и сбрасывает буфер.
+    ///
+    /// # Возвращает
+    /// Размер записанных данных.
+    pub fn finish(mut self) -> Result<usize, CommonErr> {
+        let finished = self.sink.finish(&mut self.output);
+        finished.map_err(|e| self.output_err(e))?;
+        self.flush()?;
+        Ok(self.output.written)
+    }
+
+    /// Ошибки потока — это ошибки записи в ресурс, а не сериализации.
+    fn output_err(&self, e: ParserErr) -> CommonErr {
+        match e {
+            ParserErr::Io { source, .. } => CommonErr::IO(IoErr::OutputErr {
+                path: self.path.clone(),
+                kind: source.kind(),
+                source,
+            }),
+            other => CommonErr::Parser(other),
+        }
+    }
+}
+
+/// Создаёт потоковый приёмник записей для формата.
+#[cfg_attr(not(any(feature = "bin", feature = "csv")), allow(unused_variables))]
+pub(crate) fn sink(format: Format, options: &WriteOptions) -> Result<Box<dyn RecordSink>, ParserErr> {
+    #[cfg(feature = "enrich")]
+    if options.join_users.is_some() && format != Format::YpBankCsv {
+        return Err(ParserErr::NotJoinable { format });
+    }
+    match format {
+        #[cfg(feature = "bin")]
+        Format::YpBankBin => Ok(Box::new(BinSink::new(options.clone()))),
+        #[cfg(feature = "csv")]
+        Format::YpBankCsv => {
+            let sink = CsvSink::new(options.csv_bom, options.csv_quote_style, options.metadata.clone());
+            #[cfg(feature = "enrich")]
+            let sink = sink.join_users(options.join_users.clone());
+            Ok(Box::new(sink))
+        }
+        #[cfg(feature = "text")]
+        Format::YpBankText => Ok(Box::new(TextSink::new(options.metadata.clone()))),
+        #[allow(unreachable_patterns)]
+        disabled => Err(super::format_disabled(disabled)),
+    }
+}
 
-    output.write_all(&data_to_write)
-      .map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?;
+/// Поток записи, считающий записанные байты.
+pub(crate) struct CountingWriter {
+    pub(crate) inner: Box<dyn Write>,
+    pub(crate) written: usize,
+}
 
-    output
-        .flush()
-        .map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?;
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n;
+        Ok(n)
+    }
 
-    Ok(data_to_write.len())
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
 }
 
 
@@ -74,19 +430,30 @@ pub fn write_to_resource(
 ///
 /// # Возвращает
 /// * `Ok(Box<dyn Write>)` — готовый к записи поток
-/// * `Err(ParserErr)` — ошибка создания файла
+/// * `Err(IoErr)` — ошибка создания файла
+///
+pub(crate) fn write(resource: Resource) -> Result<Box<dyn Write>, IoErr> {
+    write_buffered(resource, DEFAULT_BUFFER_SIZE)
+}
+
+/// Как [`write`], но с буфером `buffer_size` байт для файла и консоли.
 ///
-fn write(resource: Resource) -> Result<Box<dyn Write>, ParserErr> {
+/// Консоль тоже буферизуется: `stdout()` сам по себе сбрасывает вывод на каждом переводе строки.
+pub(crate) fn write_buffered(resource: Resource, buffer_size: usize) -> Result<Box<dyn Write>, IoErr> {
     match resource {
-        Resource::Console => Ok(Box::new(stdout())),
+        Resource::Console => Ok(Box::new(BufWriter::with_capacity(buffer_size, stdout()))),
         Resource::File { path } => {
-            let file =
-                File::create(path).map_err(|e| ParserErr::ParseErr { msg: e.to_string() })?;
-            Ok(Box::new(BufWriter::new(file)))
+            let file = File::create(&path).map_err(|e| IoErr::output(e, Some(path)))?;
+            Ok(Box::new(BufWriter::with_capacity(buffer_size, file)))
         },
         Resource::Memory{ data } => {
             Ok(Box::new(data))
         }
+        #[cfg(feature = "zip")]
+        Resource::ZipMember { archive, member } => {
+            let msg = format!("cannot write {} into zip archive", member);
+            Err(IoErr::output(std::io::Error::new(std::io::ErrorKind::Unsupported, msg), Some(archive)))
+        }
     }
 }
 
@@ -94,43 +461,42 @@ fn write(resource: Resource) -> Result<Box<dyn Write>, ParserErr> {
 #[cfg(test)]
 mod tests {
     use crate::model::data::{Status, TxType};
-    use crate::console::commands::Resource;
+    use crate::model::tx_id::TxId;
 
     use super::*;
-    use mockall::automock;
     use std::io::Cursor;
-    use std::sync::{Arc, Mutex};
 
 
 
     fn sample_txns() -> Vec<TxData> {
         vec![
             TxData {
-                tx_id: 1,
+                tx_id: TxId::U64(1),
                 tx_type: TxType::Deposit,
                 from_user_id: 0,
                 to_user_id: 100,
                 amount: 1000,
                 timestamp: 1700000000,
                 status: Status::Success,
-                description: "Initial deposit".to_string(),
+                description: "Initial deposit".into(),
                 format: Format::YpBankCsv,
             },
             TxData {
-                tx_id: 2,
+                tx_id: TxId::U64(2),
                 tx_type: TxType::Transfer,
                 from_user_id: 100,
                 to_user_id: 200,
                 amount: 500,
                 timestamp: 1700000060,
                 status: Status::Pending,
-                description: "Friend payment".to_string(),
+                description: "Friend payment".into(),
                 format: Format::YpBankCsv,
             },
         ]
     }
 
-       #[test]
+    #[test]
+    #[cfg(feature = "bin")]
     fn test_ypbank_bin_format() {
         let txns = sample_txns();
 
@@ -140,24 +506,281 @@ mod tests {
         assert_eq!(size, 137)
     }
 
-        #[test]
+    #[test]
+    #[cfg(feature = "csv")]
     fn test_ypbank_csv_format() {
         let txns = sample_txns();
 
         let size = write_to_resource(&txns, Resource::Memory{data: Cursor::new(vec![])}, Format::YpBankCsv)
             .expect("binary write should succeed");
 
-        assert_eq!(size, 116)
+        // Заголовок + две строки, как у `to_csv_many`: файл читается обратно через `read`
+        assert_eq!(size, 187)
     }
 
     #[test]
+    #[cfg(feature = "text")]
     fn test_ypbank_text_format() {
         let txns = sample_txns();
 
         let size = write_to_resource(&txns, Resource::Memory{data: Cursor::new(vec![])}, Format::YpBankText)
             .expect("binary write should succeed");
 
-        assert_eq!(size, 280)
+        assert_eq!(size, 281)
+    }
+
+    /// Пишет записи через приёмник формата порциями по `batch_size`.
+    #[cfg(any(feature = "bin", feature = "csv"))]
+    fn write_in_batches(txns: &[TxData], format: Format, options: &WriteOptions, batch_size: usize) -> Vec<u8> {
+        let mut sink = sink(format, options).unwrap();
+        let mut out = Vec::new();
+        for chunk in txns.chunks(batch_size) {
+            sink.write_batch(&mut out, chunk, &[]).unwrap();
+        }
+        sink.finish(&mut out).unwrap();
+        out
     }
-    
-}
\ No newline at end of file
+
+    #[test]
+    #[cfg(feature = "csv")]
+    fn test_csv_batches_write_header_once() {
+        let txns: Vec<TxData> = (0..3).flat_map(|_| sample_txns()).collect();
+        let options = WriteOptions::default();
+
+        let batched = write_in_batches(&txns, Format::YpBankCsv, &options, 4);
+
+        assert_eq!(batched, write_in_batches(&txns, Format::YpBankCsv, &options, txns.len()));
+        assert_eq!(write_in_batches(&[], Format::YpBankCsv, &options, 1).len(), 74);
+    }
+
+    #[test]
+    #[cfg(feature = "csv")]
+    fn test_description_limit_policies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.csv");
+        let write = |policy| {
+            let options = WriteOptions {
+                max_description_len: Some(14),
+                description_length_policy: policy,
+                ..WriteOptions::default()
+            };
+            let mut writer = write_batches(Resource::File { path: path.clone() }, Format::YpBankCsv, &options)?;
+            writer.write_batch(&sample_txns(), &[])?;
+            let warnings = writer.take_warnings();
+            writer.finish()?;
+            Ok::<_, CommonErr>((crate::parser::io::reader::read(Resource::File { path: path.clone() }, Format::YpBankCsv)?, warnings))
+        };
+
+        // "Initial deposit" — 15 символов, "Friend payment" — ровно 14
+        let (txns, warnings) = write(DescriptionLengthPolicy::Truncate).unwrap();
+        assert_eq!((&*txns[0].description, &*txns[1].description, warnings.len()), ("Initial depos…", "Friend payment", 0));
+
+        let (txns, warnings) = write(DescriptionLengthPolicy::Warn).unwrap();
+        assert_eq!(&*txns[0].description, "Initial deposit");
+        let expected = WarningKind::LongDescription { tx_id: TxId::U64(1), chars: 15, limit: 14 };
+        assert_eq!(warnings, [ParseWarning { line: None, kind: expected }]);
+
+        let err = write(DescriptionLengthPolicy::Reject).unwrap_err();
+        if let CommonErr::Parser(ParserErr::DescriptionTooLong { tx_id, chars, limit }) = err {
+            assert_eq!((tx_id, chars, limit), (TxId::U64(1), 15, 14));
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "enrich")]
+    fn test_join_users_adds_csv_columns() {
+        use crate::enrich::UserReference;
+        use crate::parser::options::ParseOptions;
+        use std::sync::Arc;
+
+        let reference = UserReference::from_reader("id,segment\n100,retail\n".as_bytes()).unwrap();
+        let options = WriteOptions { join_users: Some(Arc::new(reference)), ..WriteOptions::default() };
+        let out = String::from_utf8(write_in_batches(&sample_txns(), Format::YpBankCsv, &options, 1)).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines[0].ends_with(",DESCRIPTION,FROM_SEGMENT,TO_SEGMENT"));
+        assert!(lines[1].ends_with(",Initial deposit,,retail"));
+        assert!(lines[2].ends_with(",Friend payment,retail,"));
+
+        let mut parse = ParseOptions::default();
+        parse.csv.allow_extra_columns = true;
+        let txns = crate::parser::io::reader::read_with(Resource::Memory { data: Cursor::new(out.into_bytes()) }, Format::YpBankCsv, &parse).unwrap();
+        assert_eq!(txns.records, sample_txns());
+
+        assert!(matches!(sink(Format::YpBankText, &options), Err(ParserErr::NotJoinable { format: Format::YpBankText })));
+    }
+
+    #[test]
+    #[cfg(feature = "bin")]
+    fn test_bin_batches_keep_index_offsets() {
+        use crate::parser::concrete::bin_index::BinIndex;
+        use crate::parser::concrete::bin_psrser::TxnFromBin;
+        use crate::parser::options::{BinCompression, BinProfile};
+
+        let txns: Vec<TxData> = (0..3).flat_map(|_| sample_txns()).collect();
+        let options = WriteOptions {
+            bin_profile: BinProfile::CompactLe,
+            bin_index: true,
+            ..WriteOptions::default()
+        };
+
+        let batched = write_in_batches(&txns, Format::YpBankBin, &options, 4);
+        assert_eq!(batched, write_in_batches(&txns, Format::YpBankBin, &options, txns.len()));
+
+        if cfg!(feature = "zstd") {
+            // Блоки сжатия режутся по порциям, но индекс и записи остаются согласованными
+            let options = WriteOptions {
+                bin_compression: BinCompression::Zstd { level: 3 },
+                ..options
+            };
+            let batched = write_in_batches(&txns, Format::YpBankBin, &options, 4);
+            let mut cursor = Cursor::new(batched.clone());
+            let index = BinIndex::read_from(&mut cursor).unwrap().unwrap();
+            let found = index.get(&mut cursor, &TxId::U64(2)).unwrap().unwrap();
+            let records = TxData::from_bin_reader(Box::new(Cursor::new(batched))).unwrap();
+
+            assert_eq!(&*found.description, "Friend payment");
+            assert_eq!(records.len(), txns.len());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "text")]
+    fn test_resume_discards_tail_after_checkpoint() {
+        let txns = sample_txns();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        let options = WriteOptions::default();
+
+        let mut writer = write_batches(Resource::File { path: path.clone() }, Format::YpBankText, &options).unwrap();
+        writer.write_batch(&txns[..1], &[]).unwrap();
+        writer.flush().unwrap();
+        let checkpoint = writer.checkpoint();
+        // Порция после контрольной точки, записанная до «падения»
+        writer.write_batch(&txns[1..], &[]).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        let mut writer = resume_batches(path.clone(), Format::YpBankText, &options, &checkpoint).unwrap();
+        writer.write_batch(&txns[1..], &[]).unwrap();
+        let size = writer.finish().unwrap();
+
+        assert_eq!(checkpoint.records, 1);
+        assert!(checkpoint.after_record);
+        assert_eq!(size, 281);
+        assert_eq!(std::fs::read(&path).unwrap().len(), 281);
+    }
+
+    #[test]
+    #[cfg(feature = "bin")]
+    fn test_resume_bin_with_index_is_rejected() {
+        let options = WriteOptions {
+            bin_index: true,
+            ..WriteOptions::default()
+        };
+        let err = resume_batches("out.bin".into(), Format::YpBankBin, &options, &WriteCheckpoint::default())
+            .err()
+            .unwrap();
+
+        if let CommonErr::Parser(ParserErr::NotResumable { format }) = err {
+            assert_eq!(format, Format::YpBankBin);
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "bin")]
+    fn test_append_bin_skips_present_tx_ids() {
+        use crate::parser::concrete::bin_index::{read_range, BinIndex};
+        use crate::parser::options::{BinCompression, BinProfile};
+        use crate::parser::io::reader::read;
+
+        let txns = sample_txns();
+        let third = TxData { tx_id: TxId::U64(3), ..txns[1].clone() };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.bin");
+        let indexed = WriteOptions { bin_profile: BinProfile::CompactLe, bin_index: true, ..WriteOptions::default() };
+        let mut variants = vec![
+            WriteOptions::default(),
+            indexed.clone(),
+            WriteOptions { bin_header: true, bin_dictionary: true, ..WriteOptions::default() },
+        ];
+        if cfg!(feature = "zstd") {
+            let compression = BinCompression::Zstd { level: 3 };
+            variants.push(WriteOptions { bin_compression: compression, bin_seek_table: true, ..indexed });
+        }
+
+        for options in variants {
+            write_with(&txns, &[], Resource::File { path: path.clone() }, Format::YpBankBin, &options).unwrap();
+            // Повторная доставка: первая запись уже в файле, третья приходит дважды
+            let append = WriteOptions { append: true, if_absent: true, ..WriteOptions::default() };
+            let batch = [txns[0].clone(), third.clone(), third.clone()];
+            let mut writer = write_batches(Resource::File { path: path.clone() }, Format::YpBankBin, &append).unwrap();
+            writer.write_batch(&batch, &[]).unwrap();
+            assert_eq!(writer.checkpoint().records, 1);
+            let size = writer.finish().unwrap();
+
+            let data = std::fs::read(&path).unwrap();
+            assert_eq!(size, data.len(), "{:?}", options);
+            let ids: Vec<TxId> = read(Resource::File { path: path.clone() }, Format::YpBankBin)
+                .unwrap()
+                .into_iter()
+                .map(|tx| tx.tx_id)
+                .collect();
+            assert_eq!(ids, [TxId::U64(1), TxId::U64(2), TxId::U64(3)], "{:?}", options);
+            if options.bin_index {
+                let mut cursor = Cursor::new(data.clone());
+                let index = BinIndex::read_from(&mut cursor).unwrap().unwrap();
+                assert_eq!(index.len(), 3);
+                assert_eq!(index.get(&mut cursor, &TxId::U64(3)).unwrap().unwrap().amount, third.amount);
+            }
+            assert_eq!(read_range(Cursor::new(data), 2, 5).unwrap()[0].tx_id, TxId::U64(3));
+        }
+
+        // Дописывать можно только в бинарный формат
+        let append = WriteOptions { append: true, ..WriteOptions::default() };
+        let err = write_batches(Resource::File { path }, Format::YpBankText, &append).err().unwrap();
+        if let CommonErr::Parser(ParserErr::NotAppendable { format }) = err {
+            assert_eq!(format, Format::YpBankText);
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "csv")]
+    fn test_write_to_missing_directory_reports_output_path() {
+        let path: std::path::PathBuf = "/this/dir/does/not/exist/out.csv".into();
+        let err = write_to_resource(&sample_txns(), Resource::File { path: path.clone() }, Format::YpBankCsv)
+            .unwrap_err();
+
+        if let CommonErr::IO(IoErr::OutputErr { path: Some(p), kind, .. }) = err {
+            assert_eq!(p, path);
+            assert_eq!(kind, std::io::ErrorKind::NotFound);
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "text")]
+    fn test_flush_interval_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        let options = WriteOptions {
+            buffer_size: Some(1 << 20),
+            flush_interval_records: Some(3),
+            ..WriteOptions::default()
+        };
+        let txns = sample_txns();
+        let mut writer = write_batches(Resource::File { path: path.clone() }, Format::YpBankText, &options).unwrap();
+
+        writer.write_batch(&txns[..1], &[]).unwrap();
+        writer.write_batch(&txns[..1], &[]).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+        writer.write_batch(&txns[..1], &[]).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len() as usize, writer.written());
+    }
+}
@@ -0,0 +1,114 @@
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use flate2::read::DeflateDecoder;
+use zip::result::ZipError;
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::model::errors::IoErr;
+
+/// Открывает файл `member` zip-архива `archive` для потокового чтения.
+///
+/// Читаются только сжатые данные этого файла: архив не распаковывается ни на диск,
+/// ни в память. Поддерживаются методы сжатия Stored и Deflated; CRC-32 проверяется
+/// в конце файла.
+pub(crate) fn open_member(archive: &Path, member: &str) -> Result<Box<dyn Read>, IoErr> {
+    let input_err = |e: Error| IoErr::input(e, Some(archive.to_path_buf()));
+    let file = File::open(archive).map_err(input_err)?;
+
+    let mut zip = ZipArchive::new(BufReader::new(file)).map_err(|e| input_err(zip_io(e, member)))?;
+    let (method, start, compressed, size, crc) = {
+        let entry = zip.by_name(member).map_err(|e| input_err(zip_io(e, member)))?;
+        (entry.compression(), entry.data_start(), entry.compressed_size(), entry.size(), entry.crc32())
+    };
+
+    let mut file = zip.into_inner();
+    file.seek(SeekFrom::Start(start)).map_err(input_err)?;
+    let data = file.take(compressed);
+    let data: Box<dyn Read> = match method {
+        CompressionMethod::Stored => Box::new(data),
+        CompressionMethod::Deflated => Box::new(DeflateDecoder::new(data)),
+        other => {
+            let msg = format!("{}: compression method {} is not supported", member, other);
+            return Err(input_err(Error::new(ErrorKind::Unsupported, msg)));
+        }
+    };
+
+    Ok(Box::new(CrcReader {
+        inner: data,
+        hasher: crc32fast::Hasher::new(),
+        expected_crc: crc,
+        expected_size: size,
+        read: 0,
+    }))
+}
+
+/// Поток, сверяющий CRC-32 и размер прочитанного с заголовком архива.
+struct CrcReader {
+    inner: Box<dyn Read>,
+    hasher: crc32fast::Hasher,
+    expected_crc: u32,
+    expected_size: u64,
+    read: u64,
+}
+
+impl Read for CrcReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.read += n as u64;
+        if n == 0 && !buf.is_empty() {
+            let crc = self.hasher.clone().finalize();
+            if crc != self.expected_crc || self.read != self.expected_size {
+                return Err(Error::new(ErrorKind::InvalidData, "zip member is corrupted (CRC-32 mismatch)"));
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Ошибка `zip` как `io::Error`: отсутствующий файл архива — `NotFound`.
+fn zip_io(e: ZipError, member: &str) -> Error {
+    match e {
+        ZipError::Io(io) => io,
+        ZipError::FileNotFound => Error::new(ErrorKind::NotFound, format!("no {} in zip archive", member)),
+        other => Error::new(ErrorKind::InvalidData, other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    #[test]
+    fn test_open_member_stored_and_deflated() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("dump.zip");
+        let content = "TX_ID: 1\n".repeat(1000);
+
+        let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+        for (name, method) in [("a.txt", CompressionMethod::Stored), ("b.txt", CompressionMethod::Deflated)] {
+            zip.start_file(name, SimpleFileOptions::default().compression_method(method))
+                .unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        for name in ["a.txt", "b.txt"] {
+            let mut read = String::new();
+            open_member(&archive, name).unwrap().read_to_string(&mut read).unwrap();
+            assert_eq!(read, content);
+        }
+
+        let err = open_member(&archive, "c.txt").err().unwrap();
+        if let IoErr::InputErr { kind, path, .. } = err {
+            assert_eq!(kind, ErrorKind::NotFound);
+            assert_eq!(path, Some(archive));
+        } else {
+            panic!()
+        }
+    }
+}
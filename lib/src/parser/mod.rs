@@ -1,2 +1,23 @@
-pub mod concrete;
-pub mod io;
\ No newline at end of file
+pub mod capabilities;
+pub(crate) mod concrete;
+pub(crate) mod io;
+pub mod options;
+
+#[cfg(feature = "bin")]
+pub use concrete::bin_index::{read_range, BinIndex};
+#[cfg(feature = "bin")]
+pub use concrete::bin_psrser::{TxnFromBin, TxnToBin, TxnWriteToBin};
+#[cfg(feature = "csv")]
+pub use concrete::csv_parser::{TxnFromCsv, TxnToCsv, TxnWriteToCsv};
+#[cfg(feature = "text")]
+pub use concrete::text_parser::{TxnFromText, TxnToText, TxnWriteToText};
+pub use capabilities::{conversion_losses, Capabilities, Loss};
+pub use io::reader::{find_by_id, read, read_batches, read_batches_from, read_metadata, read_with, BatchReader};
+pub use io::stdin::set_stdin_timeout;
+#[cfg(feature = "report")]
+pub use io::report::{write_files_issues_report, write_issues_report, ReportFormat};
+pub use io::writer::{append_batches, resume_batches, write_batches, write_to_resource, write_with, BatchWriter};
+pub use options::{
+    BinCompression, BinProfile, ConvertOptions, CsvOptions, DescriptionLengthPolicy, EmptyFieldPolicy, ErrorMode, MemoryBudget, ParseOptions, ParseOutcome, Provenance, QuoteStyle, ReadOptions,
+    ReadCheckpoint, TextAnnotation, TextOptions, Transforms, TxHook, WriteCheckpoint, WriteOptions, DEFAULT_BUFFER_SIZE,
+};
@@ -0,0 +1,754 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::model::amount::AmountLocale;
+use crate::model::data::TxData;
+use crate::model::errors::{ParseIssue, ParseWarning, ParserErr, WarningKind};
+use crate::model::metadata::ProducerMetadata;
+use crate::model::normalize::DescriptionNormalization;
+use crate::model::time::{TimeWindow, TimestampUnit};
+use crate::model::validation::{validate_with, TimestampBounds};
+use crate::parser::concrete::DescriptionArena;
+#[cfg(feature = "enrich")]
+use crate::enrich::UserReference;
+#[cfg(feature = "rules")]
+use crate::rules::RulePack;
+
+/// Поведение парсера при ошибке в отдельной записи.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorMode {
+    /// Остановиться на первой ошибке и вернуть её (поведение по умолчанию)
+    #[default]
+    FailFast,
+    /// Пропустить некорректную запись, сохранить проблему в отчёт и продолжить
+    Collect,
+}
+
+/// Что делать с пустыми значениями и отсутствующими ключами.
+///
+/// Пустое значение числового поля, типа или статуса — всегда ошибка `ParserErr::EmptyField`,
+/// отсутствующий ключ текстового формата — `ParserErr::MissingField`. Политика определяет
+/// только обращение с описанием, единственным необязательным по смыслу полем.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "console", derive(clap::ValueEnum))]
+pub enum EmptyFieldPolicy {
+    /// Пустое описание допустимо (с предупреждением `WarningKind::EmptyDescription`)
+    #[default]
+    AllowEmptyDescription,
+    /// Как `AllowEmptyDescription`, и запись текстового формата без ключа `DESCRIPTION`
+    /// читается с пустым описанием
+    AllowMissingDescription,
+    /// Пустое описание — ошибка `ParserErr::EmptyField`
+    Reject,
+}
+
+/// Настройки разбора входных данных, общие для всех форматов.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Что делать с ошибками в отдельных записях
+    pub error_mode: ErrorMode,
+    /// Считать предупреждения ошибками записи (для строгих проверок в CI)
+    pub warnings_as_errors: bool,
+    /// Отклонять записи, нарушающие смысловые правила из `model::validation`
+    pub strict: bool,
+    /// В строгом режиме отклонять и записи с `TIMESTAMP` вне интервала
+    /// (например, `TimestampBounds::default()`); `None` — метки не проверяются
+    pub timestamp_bounds: Option<TimestampBounds>,
+    /// Особенности текстового формата конкретного источника
+    pub text: TextOptions,
+    /// Особенности CSV конкретного источника
+    pub csv: CsvOptions,
+    /// В каких единицах `TIMESTAMP` во входных данных; метки приводятся к миллисекундам
+    /// до всех проверок и отбора по `time_window`
+    pub timestamp_unit: TimestampUnit,
+    /// Принимать только записи с `TIMESTAMP` из интервала; остальные молча отбрасываются
+    pub time_window: TimeWindow,
+    /// Что делать с пустыми значениями и отсутствующими ключами
+    pub empty_field_policy: EmptyFieldPolicy,
+    /// Хранить одинаковые описания одной общей строкой: экономит память при загрузке
+    /// больших файлов с типовыми комментариями
+    pub intern_descriptions: bool,
+    /// Запоминать, откуда взята каждая запись (см. [`ParseOutcome::provenance`]),
+    /// чтобы отчёты могли указать на неё во входных данных
+    pub provenance: bool,
+    /// Приводить описания к единому виду при чтении (до проверки на пустое описание)
+    pub normalize_description: DescriptionNormalization,
+    /// Дополнительные правила проверки из TOML-файла; нарушившая их запись обрабатывается
+    /// как ошибочная, как в строгом режиме
+    #[cfg(feature = "rules")]
+    pub rules: Option<Arc<RulePack>>,
+}
+
+/// Настройки разбора текстового формата `KEY: value`.
+///
+/// По умолчанию принимается только канонический вид: разделитель `:` и ключи
+/// в верхнем регистре без псевдонимов.
+#[derive(Debug, Clone)]
+pub struct TextOptions {
+    /// Допустимые разделители ключа и значения; используется первый встреченный в строке
+    pub separators: Vec<char>,
+    /// Сравнивать ключи без учёта регистра (`tx_id` == `TX_ID`)
+    pub case_insensitive_keys: bool,
+    /// Псевдонимы ключей: альтернативное имя -> каноническое (`TXID` -> `TX_ID`)
+    pub aliases: HashMap<String, String>,
+    /// Сохранять комментарии `#` и пустые строки в `ParseOutcome::annotations`
+    pub keep_comments: bool,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions {
+            separators: vec![':'],
+            case_insensitive_keys: false,
+            aliases: HashMap::new(),
+            keep_comments: false,
+        }
+    }
+}
+
+impl TextOptions {
+    /// Настройки для файлов партнёров: `:` или `=`, любой регистр ключей
+    /// и распространённые псевдонимы.
+    pub fn relaxed() -> Self {
+        let aliases = [
+            ("TXID", "TX_ID"),
+            ("TYPE", "TX_TYPE"),
+            ("FROM", "FROM_USER_ID"),
+            ("TO", "TO_USER_ID"),
+            ("DESC", "DESCRIPTION"),
+        ]
+        .into_iter()
+        .map(|(alias, key)| (alias.to_string(), key.to_string()))
+        .collect();
+
+        TextOptions {
+            separators: vec![':', '='],
+            case_insensitive_keys: true,
+            aliases,
+            keep_comments: false,
+        }
+    }
+
+    #[cfg_attr(not(feature = "text"), allow(dead_code))]
+    /// Делит строку на ключ и значение по первому допустимому разделителю.
+    pub(crate) fn split<'a>(&self, line: &'a str) -> Option<(&'a str, &'a str)> {
+        let pos = line.find(self.separators.as_slice())?;
+        let sep_len = line[pos..].chars().next().map_or(1, char::len_utf8);
+        Some((line[..pos].trim(), line[pos + sep_len..].trim()))
+    }
+
+    #[cfg_attr(not(feature = "text"), allow(dead_code))]
+    /// Приводит ключ к каноническому имени с учётом регистра и псевдонимов.
+    pub(crate) fn canonical_key(&self, key: &str) -> String {
+        let key = if self.case_insensitive_keys {
+            key.to_ascii_uppercase()
+        } else {
+            key.to_string()
+        };
+
+        let alias = self.aliases.iter().find(|(alias, _)| {
+            if self.case_insensitive_keys {
+                alias.eq_ignore_ascii_case(&key)
+            } else {
+                **alias == key
+            }
+        });
+
+        match alias {
+            Some((_, canonical)) => canonical.clone(),
+            None => key,
+        }
+    }
+}
+
+/// Настройки разбора CSV.
+///
+/// По умолчанию принимается только канонический вид из спецификации.
+#[derive(Debug, Clone, Default)]
+pub struct CsvOptions {
+    /// Разбирать `AMOUNT` как сумму в записи этой локали (`1.000.000,50`) и переводить
+    /// в минимальные единицы; `None` — целое число минимальных единиц. Локаль никогда не угадывается.
+    pub amount_locale: Option<AmountLocale>,
+    /// Принимать канонические колонки в любом порядке (сопоставляя их по заголовку);
+    /// неизвестные, повторяющиеся и отсутствующие колонки по-прежнему ошибка
+    pub reorder_ok: bool,
+    /// Принимать колонки после канонических, отбрасывая их значения, с одним предупреждением
+    /// на файл вместо ошибки `Expected 8 fields`
+    pub allow_extra_columns: bool,
+    /// Сколько строк в начале файла (баннер перед заголовком) пропустить не читая
+    pub skip_rows: usize,
+    /// Строки, начинающиеся с этого ASCII-символа (например `b'#'`), — комментарии,
+    /// в том числе до заголовка
+    pub comment_prefix: Option<u8>,
+}
+
+/// Профиль кодирования бинарного формата.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "console", derive(clap::ValueEnum))]
+pub enum BinProfile {
+    /// Исходный формат: big-endian, длины u32, магическое число у каждой записи, без заголовка файла
+    #[default]
+    BigEndian,
+    /// Little-endian, длины в varint, заголовок файла вместо магического числа у записей
+    CompactLe,
+}
+
+/// Сжатие блоков записей в бинарном формате.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BinCompression {
+    /// Записи пишутся как есть
+    #[default]
+    None,
+    /// Блоки записей сжимаются zstd с заданным уровнем (требует фичу `zstd` при чтении и записи)
+    Zstd { level: i32 },
+}
+
+/// Когда заключать поля CSV в кавычки при записи.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "console", derive(clap::ValueEnum))]
+pub enum QuoteStyle {
+    /// Все поля, включая заголовок
+    Always,
+    /// Только поля с запятой, кавычкой или переводом строки
+    #[default]
+    Necessary,
+    /// Никогда; описание, которое без кавычек не записать, — ошибка
+    Never,
+}
+
+/// Что делать при записи с описанием длиннее `WriteOptions::max_description_len`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "console", derive(clap::ValueEnum))]
+pub enum DescriptionLengthPolicy {
+    /// Обрезать описание до предела, считая `TRUNCATION_MARKER` в конце
+    #[default]
+    Truncate,
+    /// Ошибка `ParserErr::DescriptionTooLong`; порция с такой записью не записывается
+    Reject,
+    /// Записать как есть с предупреждением `WarningKind::LongDescription`
+    /// (см. `BatchWriter::take_warnings`)
+    Warn,
+}
+
+/// Настройки записи данных.
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Профиль кодирования бинарного формата (читатель определяет его по заголовку файла)
+    pub bin_profile: BinProfile,
+    /// Сжатие бинарного формата (отмечается флагом в заголовке файла)
+    pub bin_compression: BinCompression,
+    /// Дописать в конец бинарного файла индекс `tx_id -> смещение` для поиска без полного чтения
+    pub bin_index: bool,
+    /// Писать заголовок файла и без сжатия и индекса в профиле `BigEndian`: только файлы
+    /// с заголовком имеют текущую версию схемы и могут хранить нечисловые `TX_ID`
+    pub bin_header: bool,
+    /// Записать после заголовка бинарного файла словарь самых частых описаний первой порции
+    /// и ссылаться на него из записей вместо повтора строки; читатель разворачивает ссылки сам.
+    /// Заголовок пишется до остальных порций, поэтому описания, частые только в них, в словарь
+    /// не попадают: для выборки побольше нужна порция побольше
+    pub bin_dictionary: bool,
+    /// Дописать в конец сжатого бинарного файла таблицу блоков (в духе zstd seekable format),
+    /// чтобы `read_range` переходил сразу к блоку с нужной записью; без сжатия не действует
+    pub bin_seek_table: bool,
+    /// Сведения о программе, записавшей файл: строки `#@` перед данными CSV и текстового
+    /// формата, пары «ключ — значение» после заголовка бинарного файла (см. `read_metadata`)
+    pub metadata: Option<ProducerMetadata>,
+    /// Дописывать записи в конец существующего бинарного файла (только `YpBankBin`): профиль,
+    /// сжатие, индекс, таблица блоков и словарь берутся из файла, индекс и таблица блоков
+    /// переписываются в конце. Если файла нет или он пуст, он записывается заново
+    pub append: bool,
+    /// Вместе с `append` не записывать записи, `TX_ID` которых уже есть в файле (по его индексу,
+    /// а без индекса — прочитав файл) или уже записан в этот раз
+    pub if_absent: bool,
+    /// Начинать CSV с UTF-8 BOM, чтобы Excel узнал кодировку
+    pub csv_bom: bool,
+    /// Когда заключать поля CSV в кавычки
+    pub csv_quote_style: QuoteStyle,
+    /// Размер буфера вывода в байтах для файла и консоли; `None` — `DEFAULT_BUFFER_SIZE`
+    pub buffer_size: Option<usize>,
+    /// Сбрасывать буфер, как только с прошлого сброса записано столько записей
+    /// (проверяется после каждой порции); `None` — только по `BatchWriter::flush` и в конце
+    pub flush_interval_records: Option<usize>,
+    /// Приводить описания к единому виду перед записью
+    pub normalize_description: DescriptionNormalization,
+    /// Наибольшая длина описания в символах для всех форматов (например, 255 по требованию
+    /// получателя); `None` — без ограничения
+    pub max_description_len: Option<usize>,
+    /// Что делать с более длинным описанием
+    pub description_length_policy: DescriptionLengthPolicy,
+    /// Добавить к записям CSV колонки отправителя и получателя из справочника пользователей
+    /// (только `YpBankCsv`, иначе `ParserErr::NotJoinable`)
+    #[cfg(feature = "enrich")]
+    pub join_users: Option<Arc<UserReference>>,
+}
+
+impl WriteOptions {
+    /// Бинарный файл пишется в исходном формате без заголовка (версии схемы 1), который понимают
+    /// старые читатели: без сжатия, индекса, словаря и сведений о программе. `TX_ID` в нём только числовые.
+    pub fn writes_legacy_bin(&self) -> bool {
+        self.bin_compression == BinCompression::None
+            && self.bin_profile == BinProfile::BigEndian
+            && !self.bin_index
+            && !self.bin_header
+            && !self.bin_dictionary
+            && self.metadata.is_none()
+    }
+}
+
+/// Размер буфера вывода по умолчанию.
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Какие записи входных данных выдавать при потоковом чтении.
+///
+/// Записи считаются после разбора: пропущенные всё равно читаются и разбираются
+/// (иначе не найти границы записей), но не передаются дальше. После `limit` записей
+/// чтение прекращается, остаток входа не читается.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadOptions {
+    /// Сколько первых записей пропустить
+    pub skip: usize,
+    /// Сколько записей выдать после пропуска; `None` — все
+    pub limit: Option<usize>,
+}
+
+/// Настройки конвертации порциями: ограничивают память и задают точки сброса вывода.
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    /// Настройки разбора входных данных
+    pub parse: ParseOptions,
+    /// Окно записей, которые попадут в результат
+    pub read: ReadOptions,
+    /// Настройки записи выходных данных
+    pub write: WriteOptions,
+    /// Сколько записей (вместе с пропущенными) читается и записывается за один шаг
+    pub batch_size: usize,
+    /// Через сколько порций сбрасывать буфер вывода; `0` — только в конце
+    pub flush_every: usize,
+    /// Сколько входных файлов конвертировать одновременно
+    pub jobs: usize,
+    /// Выдавать всё, что зависит от параллельной работы (предупреждения, итоги по файлам),
+    /// в порядке входных данных, а не по мере готовности.
+    ///
+    /// Записи в выходных данных всегда идут в порядке входных: порции читаются и пишутся
+    /// последовательно, а каждый входной файл пишется в свой выходной.
+    pub stable_order: bool,
+    /// Перечитать записанный результат и сверить его записи со входом
+    pub verify: bool,
+    /// Писать несколько выходных файлов (разбиение по шаблону, каталог входов) во временные
+    /// файлы и переносить их на место, только если записаны все; иначе прежние файлы
+    /// остаются как были (см. `convert::staging`)
+    pub transactional: bool,
+    /// Остановить конвертацию, если результат теряет часть входа (см. `parser::capabilities`),
+    /// а не только предупредить о каждой затронутой записи
+    pub no_lossy: bool,
+    /// Преобразования записей между чтением и записью
+    pub transforms: Transforms,
+    /// Флаг прерывания (например, `interrupt::flag`): когда он поднят, конвертация
+    /// останавливается после текущей порции и возвращает `ConvertErr::Interrupted`
+    pub interrupt: Option<Arc<AtomicBool>>,
+    /// Наибольшая скорость записи одной конвертации, записей в секунду; `None` — без ограничения
+    /// (см. [`ConvertOptions::limit_rate`])
+    pub max_rate: Option<u64>,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        ConvertOptions {
+            parse: ParseOptions::default(),
+            read: ReadOptions::default(),
+            write: WriteOptions::default(),
+            batch_size: 10_000,
+            flush_every: 1,
+            jobs: 1,
+            stable_order: false,
+            verify: false,
+            transactional: false,
+            no_lossy: false,
+            transforms: Transforms::default(),
+            interrupt: None,
+            max_rate: None,
+        }
+    }
+}
+
+/// Приблизительный бюджет памяти команды, по которому подбираются размеры порций и буферов:
+/// одни и те же аргументы работают и на машине с 2 ГБ, и на сервере с сотнями гигабайт.
+///
+/// Бюджет делится поровну между одновременными конвертациями. Он не ограничивает команды,
+/// которым нужен весь вход в памяти (сравнение, поиск подозрительной активности).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBudget {
+    pub bytes: u64,
+}
+
+impl MemoryBudget {
+    /// Оценка памяти на запись в порции: сама запись, её описание и сериализованная копия
+    pub const BYTES_PER_RECORD: u64 = 512;
+    /// Границы размера порции, в которых он подбирается
+    pub const BATCH_SIZE_RANGE: (usize, usize) = (100, 1_000_000);
+    /// Границы размера буфера вывода, в которых он подбирается
+    pub const BUFFER_SIZE_RANGE: (usize, usize) = (8 * 1024, 8 * 1024 * 1024);
+
+    /// Разбирает размер: число байт или число с двоичной единицей `K`, `M`, `G`, `T`
+    /// (`512M`, `2GiB`, `64kb`).
+    pub fn parse(s: &str) -> Result<MemoryBudget, String> {
+        let invalid = || format!("expected a size like 512M, 2G or 1048576, got '{}'", s);
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let number: u64 = number.parse().map_err(|_| invalid())?;
+        let unit = unit.to_ascii_uppercase();
+        let unit = unit.strip_suffix("IB").or_else(|| unit.strip_suffix('B')).unwrap_or(&unit);
+        let scale: u64 = match unit {
+            "" => 1,
+            "K" => 1 << 10,
+            "M" => 1 << 20,
+            "G" => 1 << 30,
+            "T" => 1 << 40,
+            _ => return Err(invalid()),
+        };
+        let bytes = number.checked_mul(scale).filter(|&bytes| bytes > 0).ok_or_else(invalid)?;
+        Ok(MemoryBudget { bytes })
+    }
+
+    /// Доля бюджета одной из `jobs` одновременных конвертаций.
+    fn share(self, jobs: usize) -> u64 {
+        self.bytes / jobs.max(1) as u64
+    }
+
+    /// Размер буфера вывода для одной из `jobs` конвертаций: 1/16 её доли.
+    pub fn buffer_size(self, jobs: usize) -> usize {
+        let (min, max) = Self::BUFFER_SIZE_RANGE;
+        usize::try_from(self.share(jobs) / 16).unwrap_or(max).clamp(min, max)
+    }
+
+    /// Размер порции для одной из `jobs` конвертаций: половина доли после буфера вывода,
+    /// вторая половина — запас на чтение и преобразования.
+    pub fn batch_size(self, jobs: usize) -> usize {
+        let (min, max) = Self::BATCH_SIZE_RANGE;
+        let records = self.share(jobs).saturating_sub(self.buffer_size(jobs) as u64) / 2 / Self::BYTES_PER_RECORD;
+        usize::try_from(records).unwrap_or(max).clamp(min, max)
+    }
+}
+
+impl ConvertOptions {
+    /// Поднят ли флаг [`ConvertOptions::interrupt`].
+    pub fn interrupted(&self) -> bool {
+        self.interrupt.as_ref().is_some_and(|flag| flag.load(Ordering::SeqCst))
+    }
+
+    /// Уменьшает размер порции и буфер вывода так, чтобы `jobs` одновременных конвертаций
+    /// укладывались в `budget`; заданные меньшие значения сохраняются.
+    pub fn fit_memory(&mut self, budget: MemoryBudget) {
+        self.batch_size = self.batch_size.min(budget.batch_size(self.jobs));
+        let buffer_size = budget.buffer_size(self.jobs);
+        self.write.buffer_size = Some(self.write.buffer_size.map_or(buffer_size, |size| size.min(buffer_size)));
+    }
+
+    /// Ограничивает запись `max_rate` записями в секунду на все `jobs` одновременных конвертаций
+    /// (каждой достаётся равная доля), чтобы не перегрузить принимающую сторону.
+    ///
+    /// Порция уменьшается до десятой доли секунды записи, а вывод сбрасывается после каждой
+    /// порции: записи уходят равномерно, а не пачками раз в несколько секунд.
+    pub fn limit_rate(&mut self, max_rate: u64) {
+        let rate = (max_rate / self.jobs.max(1) as u64).max(1);
+        self.max_rate = Some(rate);
+        self.batch_size = self.batch_size.min(usize::try_from(rate / 10).unwrap_or(usize::MAX).max(1));
+        self.flush_every = 1;
+    }
+}
+
+/// Преобразование одной записи: `None` отбрасывает запись.
+pub type TxHook = Arc<dyn Fn(TxData) -> Option<TxData> + Send + Sync>;
+
+/// Цепочка преобразований, которые применяются к каждой записи по порядку
+/// (обогащение, исправление полей, фильтрация) после чтения и перед записью.
+///
+/// ```
+/// # use lib::parser::Transforms;
+/// let transforms = Transforms::default()
+///     .then(|tx| (tx.amount != 0).then_some(tx))
+///     .then(|mut tx| {
+///         tx.description = tx.description.trim().into();
+///         Some(tx)
+///     });
+/// # assert_eq!(transforms.len(), 2);
+/// ```
+#[derive(Clone, Default)]
+pub struct Transforms(Vec<TxHook>);
+
+impl Transforms {
+    /// Добавляет преобразование в конец цепочки.
+    pub fn then<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(TxData) -> Option<TxData> + Send + Sync + 'static,
+    {
+        self.0.push(Arc::new(hook));
+        self
+    }
+
+    /// Число преобразований в цепочке.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// В цепочке нет преобразований.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Пропускает запись через цепочку; `None`, если какое-то преобразование её отбросило.
+    pub fn apply(&self, tx: TxData) -> Option<TxData> {
+        self.0.iter().try_fold(tx, |tx, hook| hook(tx))
+    }
+
+    /// Преобразует записи порции. Строки текстового формата перед отброшенной записью
+    /// остаются перед следующей.
+    pub fn apply_batch(&self, batch: &mut ParseOutcome) {
+        if self.is_empty() {
+            return;
+        }
+        // Сколько записей осталось перед каждой исходной записью
+        let mut kept_before = Vec::with_capacity(batch.records.len() + 1);
+        let mut records = Vec::with_capacity(batch.records.len());
+        let mut origins = std::mem::take(&mut batch.provenance).into_iter();
+        for tx in std::mem::take(&mut batch.records) {
+            kept_before.push(records.len());
+            let origin = origins.next();
+            if let Some(tx) = self.apply(tx) {
+                records.push(tx);
+                batch.provenance.extend(origin);
+            }
+        }
+        kept_before.push(records.len());
+        for annotation in &mut batch.annotations {
+            annotation.before_record = kept_before[annotation.before_record.min(kept_before.len() - 1)];
+        }
+        batch.records = records;
+    }
+}
+
+impl fmt::Debug for Transforms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Transforms({} hooks)", self.0.len())
+    }
+}
+
+/// Докуда прочитан вход: по этим данным чтение продолжается после перезапуска с того же места.
+///
+/// Смещение указывает на границу записей; заголовок входа (колонки CSV, заголовок и словарь
+/// бинарного файла) при продолжении перечитывается с начала.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ReadCheckpoint {
+    /// Смещение в байтах от начала входа, с которого продолжается разбор
+    pub offset: u64,
+    /// Сколько строк входа до смещения (для сквозных номеров строк)
+    pub lines: usize,
+    /// Сколько записей до смещения, включая отклонённые
+    pub records: usize,
+    /// Какая часть окна чтения ещё не выдана
+    pub window: ReadOptions,
+}
+
+/// Сколько документа уже записано: по этим данным запись продолжается после перезапуска.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WriteCheckpoint {
+    /// Размер записанных данных в байтах; всё, что дальше, при продолжении отбрасывается
+    pub bytes: u64,
+    /// Сколько записей записано
+    pub records: u64,
+    /// Последней частью текстового документа была запись (от этого зависит разделитель)
+    pub after_record: bool,
+}
+
+/// Результат разбора с настройками: записи, пропущенные проблемы и предупреждения.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOutcome {
+    /// Успешно разобранные записи в порядке следования во входных данных
+    pub records: Vec<TxData>,
+    /// Проблемы в пропущенных записях (только в режиме `ErrorMode::Collect`)
+    pub issues: Vec<ParseIssue>,
+    /// Некритичные странности в принятых записях
+    pub warnings: Vec<ParseWarning>,
+    /// Комментарии и пустые строки текстового формата (только при `TextOptions::keep_comments`)
+    pub annotations: Vec<TextAnnotation>,
+    /// Происхождение записей: `provenance[i]` относится к `records[i]`
+    /// (только при `ParseOptions::provenance`, иначе пусто)
+    pub provenance: Vec<Provenance>,
+}
+
+/// Откуда во входных данных взята запись.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// Вход в виде `file:<path>`; `None`, если записи разобраны из потока без имени
+    pub source: Option<Arc<str>>,
+    /// Номер записи во входе с единицы, считая пропущенные
+    pub record: usize,
+    /// Строка, с которой начинается запись (для бинарного формата — номер записи, как в ошибках)
+    pub line: usize,
+    /// Смещение начала записи в байтах; `None`, если формат его не знает (текстовый, сжатый бинарный)
+    pub offset: Option<u64>,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(source) = &self.source {
+            write!(f, "{}, ", source)?;
+        }
+        write!(f, "record {}, line {}", self.record, self.line)?;
+        if let Some(offset) = self.offset {
+            write!(f, ", byte {}", offset)?;
+        }
+        Ok(())
+    }
+}
+
+/// Где во входе начинается разбираемая запись.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RecordPos {
+    /// Номер записи с единицы, считая пропущенные
+    pub record: usize,
+    pub line: usize,
+    pub offset: Option<u64>,
+}
+
+impl ParseOutcome {
+    /// Проставляет вход записям, чьё происхождение известно.
+    pub(crate) fn set_source(&mut self, source: &Arc<str>) {
+        for provenance in &mut self.provenance {
+            provenance.source = Some(source.clone());
+        }
+    }
+}
+
+/// Строки-комментарии и пустые строки, стоявшие перед записью во входном тексте.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextAnnotation {
+    /// Индекс записи в `ParseOutcome::records`, перед которой стояли строки;
+    /// равен числу записей для строк в конце файла
+    pub before_record: usize,
+    /// Строки в исходном виде
+    pub lines: Vec<String>,
+}
+
+impl ParseOptions {
+    /// Настройки для режима, в котором разбор продолжается после ошибок.
+    pub fn collect() -> Self {
+        ParseOptions {
+            error_mode: ErrorMode::Collect,
+            ..ParseOptions::default()
+        }
+    }
+
+    /// Обрабатывает ошибку одной записи согласно [`ErrorMode`].
+    ///
+    /// В режиме `FailFast` возвращает ошибку обратно, в режиме `Collect`
+    /// добавляет её в `issues` и позволяет продолжить разбор.
+    pub(crate) fn recover(&self, err: ParserErr, issues: &mut Vec<ParseIssue>) -> Result<(), ParserErr> {
+        match self.error_mode {
+            ErrorMode::FailFast => Err(err),
+            ErrorMode::Collect => {
+                issues.push(ParseIssue::from(err));
+                Ok(())
+            }
+        }
+    }
+
+    /// Принимает разобранную запись вместе с её предупреждениями.
+    ///
+    /// `TIMESTAMP` приводится к миллисекундам (`timestamp_unit`), запись вне `time_window`
+    /// отбрасывается до всех проверок, затем нормализуется описание.
+    ///
+    /// Добавляет общие для всех форматов проверки (пустое описание). В строгом режиме
+    /// запись, нарушающая смысловое правило, обрабатывается как ошибочная; только в нём
+    /// проверяются и границы `timestamp_bounds`. Так же обрабатывается запись
+    /// с предупреждениями, если включён `warnings_as_errors`.
+    pub(crate) fn accept(
+        &self,
+        mut tx: TxData,
+        descriptions: &mut DescriptionArena,
+        pos: RecordPos,
+        mut warnings: Vec<ParseWarning>,
+        outcome: &mut ParseOutcome,
+    ) -> Result<(), ParserErr> {
+        let line = pos.line;
+        tx.timestamp = self.timestamp_unit.to_millis(tx.timestamp);
+        if !self.time_window.contains(tx.timestamp) {
+            return Ok(());
+        }
+
+        if !self.normalize_description.is_noop() {
+            tx = self.normalize_description.apply_tx(tx);
+        }
+
+        if self.strict {
+            if let Some(violation) = validate_with(&tx, self.timestamp_bounds.as_ref()).into_iter().next() {
+                let err = ParserErr::Rule { violation };
+                return self.recover(err.at_line(line), &mut outcome.issues);
+            }
+        }
+
+        #[cfg(feature = "rules")]
+        if let Some(violation) = self.rules.as_ref().and_then(|rules| rules.check(&tx).into_iter().next()) {
+            let err = ParserErr::Rule { violation };
+            return self.recover(err.at_line(line), &mut outcome.issues);
+        }
+
+        if tx.description.is_empty() {
+            if self.empty_field_policy == EmptyFieldPolicy::Reject {
+                let err = ParserErr::EmptyField { field: "DESCRIPTION" };
+                return self.recover(err.at_line(line), &mut outcome.issues);
+            }
+            warnings.push(ParseWarning::at(line, WarningKind::EmptyDescription));
+        }
+
+        if self.warnings_as_errors && !warnings.is_empty() {
+            let first = warnings.swap_remove(0);
+            let err = ParserErr::Escalated { warning: first.kind };
+            return self.recover(err.at_line(first.line.unwrap_or(line)), &mut outcome.issues);
+        }
+
+        let tx = if self.intern_descriptions { descriptions.intern(tx) } else { tx };
+        outcome.records.push(tx);
+        outcome.warnings.extend(warnings);
+        if self.provenance {
+            outcome.provenance.push(Provenance {
+                source: None,
+                record: pos.record,
+                line,
+                offset: pos.offset,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_budget_sizes_batches() {
+        assert_eq!(MemoryBudget::parse("512M"), Ok(MemoryBudget { bytes: 512 << 20 }));
+        assert_eq!(MemoryBudget::parse("2GiB"), Ok(MemoryBudget { bytes: 2 << 30 }));
+        assert_eq!(MemoryBudget::parse("64kb"), Ok(MemoryBudget { bytes: 64 << 10 }));
+        assert_eq!(MemoryBudget::parse("1048576"), Ok(MemoryBudget { bytes: 1 << 20 }));
+        assert!(MemoryBudget::parse("0").is_err());
+        assert!(MemoryBudget::parse("2 G").is_err());
+        assert!(MemoryBudget::parse("99999999999T").is_err());
+
+        // 64 МБ на 4 потока: по 16 МБ, из них 1 МБ на буфер вывода и по 7,5 МБ на порцию и запас
+        let budget = MemoryBudget::parse("64M").unwrap();
+        assert_eq!(budget.buffer_size(4), 1 << 20);
+        assert_eq!(budget.batch_size(4), 15_360);
+        assert_eq!(MemoryBudget::parse("256K").unwrap().batch_size(8), 100);
+        assert_eq!(MemoryBudget::parse("1T").unwrap().batch_size(1), 1_000_000);
+
+        let mut options = ConvertOptions { jobs: 4, ..ConvertOptions::default() };
+        options.fit_memory(budget);
+        assert_eq!((options.batch_size, options.write.buffer_size), (10_000, Some(1 << 20)));
+        options.fit_memory(MemoryBudget::parse("8M").unwrap());
+        assert_eq!((options.batch_size, options.write.buffer_size), (1_920, Some(128 << 10)));
+    }
+}
@@ -0,0 +1,448 @@
+//! Разбиение одного входа на несколько выходных файлов по шаблону имени.
+//!
+//! Шаблон — путь с подстановками из полей записи, например `out/{type}/{date}.csv`:
+//! каждая запись попадает в файл, имя которого получается из её полей, поэтому один
+//! проход по входу даёт выгрузку, разложенную по разделам. Внутри файла записи идут
+//! в порядке входа. Фигурные скобки в самом пути записываются как `{{` и `}}`.
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::convert::staging::StagedOutputs;
+use crate::convert::{input_size, warning_message, ConvertErr, ConvertReport, LossCheck, Throttle};
+use crate::model::data::{Format, TxData};
+use crate::model::errors::{CommonErr, IoErr};
+use crate::model::resource::Resource;
+use crate::model::time::format_timestamp;
+use crate::parser::{read_batches, write_batches, BatchWriter, ConvertOptions};
+
+/// Сколько выходных файлов может быть открыто одновременно (все файлы шаблона пишутся разом).
+pub const MAX_PARTITIONS: usize = 512;
+
+/// Подстановка в шаблоне имени выходного файла.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placeholder {
+    /// `{type}`: тип операции в нижнем регистре (`deposit`)
+    Type,
+    /// `{status}`: статус в нижнем регистре (`success`)
+    Status,
+    /// `{date}`: дата TIMESTAMP в UTC, `YYYY-MM-DD`
+    Date,
+    /// `{year}`: год TIMESTAMP, `YYYY`
+    Year,
+    /// `{month}`: месяц TIMESTAMP, `MM`
+    Month,
+    /// `{day}`: день месяца TIMESTAMP, `DD`
+    Day,
+    /// `{hour}`: час TIMESTAMP в UTC, `HH`
+    Hour,
+    /// `{from_user}`: FROM_USER_ID
+    FromUser,
+    /// `{to_user}`: TO_USER_ID
+    ToUser,
+}
+
+impl Placeholder {
+    /// Все подстановки в порядке описания.
+    pub const ALL: [Placeholder; 9] = [
+        Placeholder::Type,
+        Placeholder::Status,
+        Placeholder::Date,
+        Placeholder::Year,
+        Placeholder::Month,
+        Placeholder::Day,
+        Placeholder::Hour,
+        Placeholder::FromUser,
+        Placeholder::ToUser,
+    ];
+
+    /// Имя подстановки в шаблоне (без скобок).
+    pub fn name(self) -> &'static str {
+        match self {
+            Placeholder::Type => "type",
+            Placeholder::Status => "status",
+            Placeholder::Date => "date",
+            Placeholder::Year => "year",
+            Placeholder::Month => "month",
+            Placeholder::Day => "day",
+            Placeholder::Hour => "hour",
+            Placeholder::FromUser => "from_user",
+            Placeholder::ToUser => "to_user",
+        }
+    }
+
+    /// Значение подстановки для записи `tx`.
+    pub fn value(self, tx: &TxData) -> String {
+        // `YYYY-MM-DDTHH:MM:SSZ`
+        let time = || format_timestamp(tx.timestamp);
+        match self {
+            Placeholder::Type => tx.tx_type.name().to_lowercase(),
+            Placeholder::Status => tx.status.name().to_lowercase(),
+            Placeholder::Date => time()[..10].to_string(),
+            Placeholder::Year => time()[..4].to_string(),
+            Placeholder::Month => time()[5..7].to_string(),
+            Placeholder::Day => time()[8..10].to_string(),
+            Placeholder::Hour => time()[11..13].to_string(),
+            Placeholder::FromUser => tx.from_user_id.to_string(),
+            Placeholder::ToUser => tx.to_user_id.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field(Placeholder),
+}
+
+/// Шаблон пути выходного файла с подстановками из полей записи (см. описание модуля).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputTemplate {
+    parts: Vec<Part>,
+}
+
+impl OutputTemplate {
+    /// Похож ли путь на шаблон: в нём есть фигурная скобка.
+    pub fn is_template(path: &Path) -> bool {
+        path.to_string_lossy().contains(['{', '}'])
+    }
+
+    /// Разбирает шаблон из пути.
+    ///
+    /// # Errors
+    /// `ConvertErr::Partition`, если подстановка неизвестна, скобка не закрыта,
+    /// путь не в UTF-8 или в нём нет ни одной подстановки.
+    pub fn parse(path: &Path) -> Result<OutputTemplate, ConvertErr> {
+        let Some(text) = path.to_str() else {
+            return Err(partition_err(format!("{} is not valid UTF-8", path.display())));
+        };
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let Some((name, rest)) = chars.as_str().split_once('}') else {
+                        return Err(partition_err(format!("unclosed '{{' in {}", text)));
+                    };
+                    let Some(field) = Placeholder::ALL.into_iter().find(|p| p.name() == name) else {
+                        let known: Vec<&str> = Placeholder::ALL.iter().map(|p| p.name()).collect();
+                        return Err(partition_err(format!("unknown placeholder {{{}}}, expected one of: {}", name, known.join(", "))));
+                    };
+                    chars = rest.chars();
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                '}' => return Err(partition_err(format!("unmatched '}}' in {} (write '}}}}' for a literal brace)", text))),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        if !parts.iter().any(|part| matches!(part, Part::Field(_))) {
+            return Err(partition_err(format!("{} has no placeholders", text)));
+        }
+        Ok(OutputTemplate { parts })
+    }
+
+    /// Путь выходного файла для записи `tx`.
+    pub fn render(&self, tx: &TxData) -> PathBuf {
+        let mut path = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => path.push_str(text),
+                Part::Field(field) => path.push_str(&field.value(tx)),
+            }
+        }
+        path.into()
+    }
+}
+
+/// Шаблон в том виде, в каком его принимает [`OutputTemplate::parse`].
+impl fmt::Display for OutputTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => write!(f, "{}", text.replace('{', "{{").replace('}', "}}"))?,
+                Part::Field(field) => write!(f, "{{{}}}", field.name())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Один файл, записанный [`partition_convert`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionOutput {
+    pub path: PathBuf,
+    pub records: usize,
+    pub bytes: usize,
+}
+
+/// Итог разбиения входа по шаблону.
+#[derive(Debug, Clone)]
+pub struct PartitionReport {
+    /// Итог по всем файлам; `bytes_out` — их суммарный размер
+    pub total: ConvertReport,
+    /// Записанные файлы в порядке путей
+    pub outputs: Vec<PartitionOutput>,
+}
+
+impl AsRef<ConvertReport> for PartitionReport {
+    fn as_ref(&self) -> &ConvertReport {
+        &self.total
+    }
+}
+
+/// Конвертирует `from` в файлы по шаблону `template` (см. описание модуля).
+///
+/// Как [`convert`](crate::convert::convert), читает вход порциями по `options.batch_size` и
+/// применяет `options.transforms`; каталоги выходных файлов создаются по мере надобности.
+/// Контрольные точки, `options.verify` и комментарии текстового формата не поддерживаются.
+/// После прерывания (`options.interrupt`) все начатые файлы дописываются до корректных.
+///
+/// С `options.transactional` файлы пишутся во временные и переносятся на место, только
+/// если записаны все; после ошибки или прерывания прежние файлы остаются как были.
+///
+/// # Errors
+/// * `ConvertErr::Partition` — шаблон даёт больше [`MAX_PARTITIONS`] файлов
+/// * `ConvertErr::Prepare` — вход не удалось прочитать или разобрать, файл — создать или записать
+/// * `ConvertErr::Interrupted` — конвертация остановлена по флагу
+pub fn partition_convert(
+    from: Resource,
+    from_format: Format,
+    template: &OutputTemplate,
+    to_format: Format,
+    options: &ConvertOptions,
+    on_warning: &mut dyn FnMut(String),
+) -> Result<PartitionReport, ConvertErr> {
+    let started = Instant::now();
+    let bytes_in = input_size(&from);
+    let mut losses = LossCheck::new(from_format, to_format, options);
+    let parse = losses.parse_options(&options.parse);
+    let batches = read_batches(from, from_format, &parse, options.batch_size)?.with_window(&options.read);
+
+    // Объявлен раньше писателей, чтобы при ошибке удалиться после них
+    let mut staged = options.transactional.then(StagedOutputs::new);
+    let mut writers: BTreeMap<PathBuf, BatchWriter> = BTreeMap::new();
+    let mut issues = Vec::new();
+    let (mut parsed, mut warnings) = (0, 0);
+    let mut interrupted = false;
+    let mut throttle = options.max_rate.map(Throttle::new);
+
+    for (n, batch) in batches.enumerate() {
+        let mut batch = batch?;
+        parsed += batch.records.len();
+        options.transforms.apply_batch(&mut batch);
+        for warning in &batch.warnings {
+            on_warning(warning_message(warning));
+        }
+        losses.check(&batch, on_warning)?;
+
+        let mut groups: BTreeMap<PathBuf, Vec<TxData>> = BTreeMap::new();
+        for tx in batch.records {
+            groups.entry(template.render(&tx)).or_default().push(tx);
+        }
+        let written: usize = groups.values().map(Vec::len).sum();
+        for (path, txns) in groups {
+            let open = writers.len();
+            let writer = match writers.entry(path) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    if open == MAX_PARTITIONS {
+                        return Err(partition_err(format!(
+                            "{} produces more than {} output files, use a coarser template",
+                            template, MAX_PARTITIONS
+                        )));
+                    }
+                    let path = entry.key().clone();
+                    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                        fs::create_dir_all(dir).map_err(|e| CommonErr::from(IoErr::output(e, Some(dir.to_path_buf()))))?;
+                    }
+                    let path = match &mut staged {
+                        Some(staged) => staged.stage(&path, options.write.append)?,
+                        None => path,
+                    };
+                    entry.insert(write_batches(Resource::File { path }, to_format, &options.write)?)
+                }
+            };
+            writer.write_batch(&txns, &[])?;
+            for warning in writer.take_warnings() {
+                on_warning(warning_message(&warning));
+                warnings += 1;
+            }
+        }
+        warnings += batch.warnings.len();
+        issues.extend(batch.issues);
+
+        interrupted = options.interrupted();
+        if interrupted || (options.flush_every > 0 && (n + 1) % options.flush_every == 0) {
+            for writer in writers.values_mut() {
+                writer.flush()?;
+            }
+        }
+        if interrupted {
+            break;
+        }
+        if let Some(throttle) = &mut throttle {
+            throttle.wait(written);
+        }
+    }
+
+    let mut outputs = Vec::with_capacity(writers.len());
+    for (path, writer) in writers {
+        let records = writer.checkpoint().records as usize;
+        let bytes = writer.finish()?;
+        outputs.push(PartitionOutput { path, records, bytes });
+    }
+    let skipped = issues.len();
+    let total = ConvertReport {
+        records_read: parsed + skipped,
+        records_written: outputs.iter().map(|output| output.records).sum(),
+        skipped,
+        warnings,
+        bytes_in,
+        bytes_out: outputs.iter().map(|output| output.bytes).sum(),
+        elapsed: started.elapsed(),
+        issues,
+    };
+    if interrupted {
+        return Err(ConvertErr::Interrupted { partial: Box::new(total) });
+    }
+    if let Some(staged) = staged {
+        staged.commit()?;
+    }
+    Ok(PartitionReport { total, outputs })
+}
+
+fn partition_err(reason: impl ToString) -> ConvertErr {
+    ConvertErr::Partition {
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use super::*;
+    use crate::model::data::{Status, TxType};
+    use crate::model::tx_id::TxId;
+    use crate::parser::ParseOptions;
+
+    #[test]
+    fn test_template_parse_and_render() {
+        let template = OutputTemplate::parse(Path::new("out/type={type}/{date}-{{raw}}.csv")).unwrap();
+        let tx = TxData {
+            tx_id: TxId::U64(1),
+            tx_type: TxType::Transfer,
+            from_user_id: 10,
+            to_user_id: 20,
+            amount: 100,
+            timestamp: 1_700_000_000_000,
+            status: Status::Pending,
+            description: "x".into(),
+            format: Format::YpBankCsv,
+        };
+        assert_eq!(template.render(&tx), PathBuf::from("out/type=transfer/2023-11-14-{raw}.csv"));
+        assert_eq!(template.to_string(), "out/type={type}/{date}-{{raw}}.csv");
+        let template = OutputTemplate::parse(Path::new("{year}/{month}/{day}T{hour}-{status}-{from_user}-{to_user}.bin")).unwrap();
+        assert_eq!(template.render(&tx), PathBuf::from("2023/11/14T22-pending-10-20.bin"));
+
+        assert!(OutputTemplate::is_template(Path::new("out/{date}.csv")));
+        assert!(!OutputTemplate::is_template(Path::new("out/day.csv")));
+        for (bad, reason) in [
+            ("out/{weekday}.csv", "unknown placeholder {weekday}, expected one of: type, status"),
+            ("out/{date.csv", "unclosed '{' in out/{date.csv"),
+            ("out/date}.csv", "unmatched '}' in out/date}.csv"),
+            ("out/{{date}}.csv", "out/{{date}}.csv has no placeholders"),
+        ] {
+            let Err(ConvertErr::Partition { reason: actual }) = OutputTemplate::parse(Path::new(bad)) else {
+                panic!()
+            };
+            assert!(actual.starts_with(reason), "{}", actual);
+        }
+    }
+
+    #[test]
+    fn test_partition_convert_splits_by_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("in.csv");
+        fs::write(
+            &from,
+            "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+             1,DEPOSIT,0,10,100,1700000000000,SUCCESS,\"a\"\n\
+             2,WITHDRAWAL,10,0,50,1700000000000,SUCCESS,\"b\"\n\
+             oops\n\
+             3,DEPOSIT,0,10,100,1700100000000,SUCCESS,\"c\"\n\
+             4,DEPOSIT,0,11,100,1700000000000,FAILURE,\"d\"\n",
+        )
+        .unwrap();
+        let template = OutputTemplate::parse(&dir.path().join("out/{type}/{date}.csv")).unwrap();
+        let options = ConvertOptions {
+            parse: ParseOptions::collect(),
+            batch_size: 2,
+            ..ConvertOptions::default()
+        };
+
+        let report = partition_convert(Resource::File { path: from }, Format::YpBankCsv, &template, Format::YpBankCsv, &options, &mut |_| {}).unwrap();
+
+        let out = dir.path().join("out");
+        let files: Vec<(PathBuf, usize)> = report.outputs.iter().map(|o| (o.path.strip_prefix(&out).unwrap().to_path_buf(), o.records)).collect();
+        assert_eq!(
+            files,
+            vec![
+                ("deposit/2023-11-14.csv".into(), 2),
+                ("deposit/2023-11-16.csv".into(), 1),
+                ("withdrawal/2023-11-14.csv".into(), 1),
+            ]
+        );
+        assert_eq!((report.total.records_read, report.total.records_written, report.total.skipped), (5, 4, 1));
+        let deposits = fs::read_to_string(out.join("deposit/2023-11-14.csv")).unwrap();
+        assert!(deposits.ends_with(",SUCCESS,a\n4,DEPOSIT,0,11,100,1700000000000,FAILURE,d\n"));
+        assert_eq!(report.total.bytes_out, report.outputs.iter().map(|o| fs::metadata(&o.path).unwrap().len() as usize).sum::<usize>());
+    }
+
+    #[test]
+    fn test_transactional_partition_keeps_previous_outputs_on_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("in.csv");
+        let header = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n";
+        let rows = "1,DEPOSIT,0,10,100,1700000000000,SUCCESS,\"a\"\n2,WITHDRAWAL,10,0,50,1700000000000,SUCCESS,\"b\"\n";
+        let template = OutputTemplate::parse(&dir.path().join("{type}.csv")).unwrap();
+        let options = ConvertOptions {
+            batch_size: 1,
+            transactional: true,
+            ..ConvertOptions::default()
+        };
+        let run = |content: String| {
+            fs::write(&from, content).unwrap();
+            partition_convert(Resource::File { path: from.clone() }, Format::YpBankCsv, &template, Format::YpBankCsv, &options, &mut |_| {})
+        };
+
+        let report = run(format!("{}{}", header, rows)).unwrap();
+        assert_eq!(report.outputs.len(), 2);
+        let deposits = fs::read_to_string(dir.path().join("deposit.csv")).unwrap();
+
+        // Ошибка разбора после того, как оба файла начаты: ни один не заменяется
+        let err = run(format!("{}{}{}oops\n", header, rows.replace(",a", ",changed"), rows)).unwrap_err();
+        assert!(matches!(err, ConvertErr::Prepare { .. }));
+        assert_eq!(fs::read_to_string(dir.path().join("deposit.csv")).unwrap(), deposits);
+        let mut names: Vec<_> = fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        names.sort();
+        assert_eq!(names, ["deposit.csv", "in.csv", "withdrawal.csv"]);
+    }
+}
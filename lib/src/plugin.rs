@@ -0,0 +1,269 @@
+//! Форматы партнёров в виде WASM-модулей, чтобы их можно было поставлять без пересборки CLI.
+//!
+//! Модуль переводит файл партнёра в текстовый формат YPBank и обратно, а записи разбирает
+//! и пишет сама библиотека. Модуль исполняется в песочнице `wasmi`: импортов у него нет
+//! (ни файлов, ни сети, ни часов), память и число шагов ограничены. Модуль экспортирует:
+//!
+//! ```text
+//! memory                              ;; линейная память
+//! alloc(len: i32) -> i32              ;; место под `len` байт входа
+//! decode(ptr: i32, len: i32) -> i64   ;; файл партнёра -> текстовый формат YPBank
+//! encode(ptr: i32, len: i32) -> i64   ;; текстовый формат YPBank -> файл партнёра
+//! ```
+//!
+//! `decode` и `encode` возвращают место результата в памяти как `(ptr << 32) | len`;
+//! отрицательное значение — модуль отверг вход. [`load_plugin`] регистрирует формат
+//! (см. [`register_format`]) под именем файла модуля без расширения.
+
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+use wasmi::{Config, Engine, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+use crate::model::data::{Format, TxData};
+use crate::model::errors::ParserErr;
+use crate::parser::options::{ParseOptions, ParseOutcome, WriteOptions};
+use crate::registry::{register_format, TxFormat};
+
+/// Наибольший размер памяти модуля.
+const MAX_MEMORY: usize = 1 << 30;
+/// Сколько шагов может сделать модуль за один вызов: защита от зацикливания.
+const MAX_FUEL: u64 = 10_000_000_000;
+
+#[derive(Error, Debug)]
+pub enum PluginErr {
+    /// Файл не удалось прочитать
+    #[error("failed to read plugin {}: {err}", path.display())]
+    Io { path: PathBuf, err: std::io::Error },
+    /// Файл — не WASM-модуль с экспортами из описания модуля или он требует импортов
+    #[error("plugin {} is not valid: {reason}", path.display())]
+    Invalid { path: PathBuf, reason: String },
+    /// Имя формата уже занято
+    #[error(transparent)]
+    Register(#[from] ParserErr),
+}
+
+/// Формат из WASM-модуля (см. описание модуля).
+pub struct WasmFormat {
+    name: String,
+    engine: Engine,
+    module: Module,
+}
+
+/// Экземпляр модуля на один вызов: вызовы не делят состояние.
+struct Plugin {
+    store: Store<StoreLimits>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    instance: Instance,
+}
+
+impl WasmFormat {
+    /// Читает и проверяет модуль `path`; имя формата — имя файла без расширения.
+    pub fn load(path: &Path) -> Result<WasmFormat, PluginErr> {
+        let wasm = fs::read(path).map_err(|err| PluginErr::Io { path: path.to_path_buf(), err })?;
+        let invalid = |reason: String| PluginErr::Invalid { path: path.to_path_buf(), reason };
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .ok_or_else(|| invalid("no file name".to_string()))?;
+
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, &wasm).map_err(|e| invalid(e.to_string()))?;
+        let format = WasmFormat { name, engine, module };
+        // Экспорты проверяются сразу, а не при первом файле
+        let mut plugin = format.instantiate().map_err(invalid)?;
+        for export in ["decode", "encode"] {
+            plugin.codec(export).map_err(invalid)?;
+        }
+        Ok(format)
+    }
+
+    /// Имя, под которым формат регистрирует [`load_plugin`].
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn instantiate(&self) -> Result<Plugin, String> {
+        let limits = StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(MAX_FUEL).map_err(|e| e.to_string())?;
+        // Пустой компоновщик: модулю с импортами не из чего их получить
+        let instance = Linker::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| e.to_string())?;
+        let memory = instance.get_memory(&store, "memory").ok_or("no exported memory")?;
+        let alloc = instance.get_typed_func(&store, "alloc").map_err(|e| format!("alloc: {}", e))?;
+        Ok(Plugin { store, memory, alloc, instance })
+    }
+
+    /// Передаёт `input` экспорту `export` и возвращает его результат.
+    fn call(&self, export: &str, input: &[u8]) -> Result<Vec<u8>, ParserErr> {
+        let failed = |reason: String| ParserErr::Plugin { name: self.name.clone(), reason };
+        let mut plugin = self.instantiate().map_err(failed)?;
+        let codec = plugin.codec(export).map_err(failed)?;
+        let len = i32::try_from(input.len()).map_err(|_| failed(format!("input of {} bytes is too large", input.len())))?;
+
+        let ptr = plugin.alloc.call(&mut plugin.store, len).map_err(|e| failed(format!("alloc: {}", e)))?;
+        plugin
+            .memory
+            .write(&mut plugin.store, ptr as u32 as usize, input)
+            .map_err(|e| failed(format!("alloc: {}", e)))?;
+        let result = codec.call(&mut plugin.store, (ptr, len)).map_err(|e| failed(format!("{}: {}", export, e)))?;
+        if result < 0 {
+            return Err(failed(format!("{} rejected the input", export)));
+        }
+
+        let (ptr, len) = ((result >> 32) as usize, (result & 0xffff_ffff) as usize);
+        let mut output = vec![0; len];
+        plugin
+            .memory
+            .read(&plugin.store, ptr, &mut output)
+            .map_err(|e| failed(format!("{}: {}", export, e)))?;
+        Ok(output)
+    }
+}
+
+impl Plugin {
+    fn codec(&mut self, export: &str) -> Result<TypedFunc<(i32, i32), i64>, String> {
+        self.instance
+            .get_typed_func(&self.store, export)
+            .map_err(|e| format!("{}: {}", export, e))
+    }
+}
+
+impl TxFormat for WasmFormat {
+    fn read(&self, mut reader: Box<dyn Read>, options: &ParseOptions) -> Result<ParseOutcome, ParserErr> {
+        let mut input = Vec::new();
+        reader.read_to_end(&mut input)?;
+        let text = self.call("decode", &input)?;
+        Format::YpBankText.read(Box::new(Cursor::new(text)), options)
+    }
+
+    fn write(&self, records: &[TxData], w: &mut dyn Write, options: &WriteOptions) -> Result<(), ParserErr> {
+        let mut text = Vec::new();
+        Format::YpBankText.write(records, &mut text, options)?;
+        w.write_all(&self.call("encode", &text)?)?;
+        Ok(())
+    }
+}
+
+/// Загружает модуль `path` и регистрирует его формат.
+///
+/// # Возвращает
+/// Имя, под которым зарегистрирован формат (см. [`WasmFormat::name`]).
+pub fn load_plugin(path: &Path) -> Result<String, PluginErr> {
+    let format = WasmFormat::load(path)?;
+    let name = format.name().to_string();
+    register_format(&name, Box::new(format))?;
+    Ok(name)
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use super::*;
+    use crate::model::resource::Resource;
+    use crate::parser::options::ConvertOptions;
+    use crate::registry::{convert_with, format_by_name, read_as};
+
+    /// Формат партнёра — текстовый формат YPBank с первой строкой `#PARTNER`:
+    ///
+    /// ```text
+    /// (module
+    ///   (memory (export "memory") 1)
+    ///   (data (i32.const 0) "#PARTNER\n")
+    ///   (global $heap (mut i32) (i32.const 16))
+    ///   (func $alloc (export "alloc") (param $len i32) (result i32) (local $ptr i32) (local $grow i32)
+    ///     (local.set $ptr (global.get $heap))
+    ///     (local.tee $grow (i32.sub (i32.shr_u (i32.add (i32.add (local.get $ptr) (local.get $len))
+    ///       (i32.const 65535)) (i32.const 16)) (memory.size)))
+    ///     (if (i32.gt_s (i32.const 0)) (then
+    ///       (if (i32.eq (memory.grow (local.get $grow)) (i32.const -1)) (then unreachable))))
+    ///     (global.set $heap (i32.add (local.get $ptr) (local.get $len)))
+    ///     (local.get $ptr))
+    ///   (func (export "decode") (param $ptr i32) (param $len i32) (result i64)
+    ///     (if (result i64) (i32.lt_u (local.get $len) (i32.const 9)) (then (i64.const -1))
+    ///       (else (if (result i64) (i64.ne (i64.load (local.get $ptr)) (i64.load (i32.const 0)))
+    ///         (then (i64.const -1))
+    ///         (else (i64.or (i64.shl (i64.extend_i32_u (i32.add (local.get $ptr) (i32.const 9))) (i64.const 32))
+    ///           (i64.extend_i32_u (i32.sub (local.get $len) (i32.const 9)))))))))
+    ///   (func (export "encode") (param $ptr i32) (param $len i32) (result i64) (local $out i32)
+    ///     (local.set $out (call $alloc (i32.add (local.get $len) (i32.const 9))))
+    ///     (memory.copy (local.get $out) (i32.const 0) (i32.const 9))
+    ///     (memory.copy (i32.add (local.get $out) (i32.const 9)) (local.get $ptr) (local.get $len))
+    ///     (i64.or (i64.shl (i64.extend_i32_u (local.get $out)) (i64.const 32))
+    ///       (i64.extend_i32_u (i32.add (local.get $len) (i32.const 9))))))
+    /// ```
+    const PARTNER_WASM: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // типы: (i32) -> i32, (i32, i32) -> i64
+        0x01, 0x0c, 0x02, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7e,
+        // функции alloc, decode, encode
+        0x03, 0x04, 0x03, 0x00, 0x01, 0x01,
+        // память в одну страницу
+        0x05, 0x03, 0x01, 0x00, 0x01,
+        // $heap
+        0x06, 0x06, 0x01, 0x7f, 0x01, 0x41, 0x10, 0x0b,
+        // экспорты
+        0x07, 0x24, 0x04, 0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00, 0x05, 0x61, 0x6c, 0x6c, 0x6f, 0x63,
+        0x00, 0x00, 0x06, 0x64, 0x65, 0x63, 0x6f, 0x64, 0x65, 0x00, 0x01, 0x06, 0x65, 0x6e, 0x63, 0x6f, 0x64, 0x65,
+        0x00, 0x02,
+        // код
+        0x0a, 0x97, 0x01, 0x03,
+        0x34, 0x01, 0x02, 0x7f, 0x23, 0x00, 0x21, 0x01, 0x20, 0x01, 0x20, 0x00, 0x6a, 0x41, 0xff, 0xff, 0x03, 0x6a,
+        0x41, 0x10, 0x76, 0x3f, 0x00, 0x6b, 0x22, 0x02, 0x41, 0x00, 0x4a, 0x04, 0x40, 0x20, 0x02, 0x40, 0x00, 0x41,
+        0x7f, 0x46, 0x04, 0x40, 0x00, 0x0b, 0x0b, 0x20, 0x01, 0x20, 0x00, 0x6a, 0x24, 0x00, 0x20, 0x01, 0x0b,
+        0x2e, 0x00, 0x20, 0x01, 0x41, 0x09, 0x49, 0x04, 0x7e, 0x42, 0x7f, 0x05, 0x20, 0x00, 0x29, 0x00, 0x00, 0x41,
+        0x00, 0x29, 0x00, 0x00, 0x52, 0x04, 0x7e, 0x42, 0x7f, 0x05, 0x20, 0x00, 0x41, 0x09, 0x6a, 0xad, 0x42, 0x20,
+        0x86, 0x20, 0x01, 0x41, 0x09, 0x6b, 0xad, 0x84, 0x0b, 0x0b, 0x0b,
+        0x31, 0x01, 0x01, 0x7f, 0x20, 0x01, 0x41, 0x09, 0x6a, 0x10, 0x00, 0x21, 0x02, 0x20, 0x02, 0x41, 0x00, 0x41,
+        0x09, 0xfc, 0x0a, 0x00, 0x00, 0x20, 0x02, 0x41, 0x09, 0x6a, 0x20, 0x00, 0x20, 0x01, 0xfc, 0x0a, 0x00, 0x00,
+        0x20, 0x02, 0xad, 0x42, 0x20, 0x86, 0x20, 0x01, 0x41, 0x09, 0x6a, 0xad, 0x84, 0x0b,
+        // "#PARTNER\n" с адреса 0
+        0x0b, 0x0f, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x09, 0x23, 0x50, 0x41, 0x52, 0x54, 0x4e, 0x45, 0x52, 0x0a,
+    ];
+
+    #[test]
+    fn test_wasm_plugin_converts_both_ways() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("partner.wasm");
+        fs::write(&path, PARTNER_WASM).unwrap();
+        assert_eq!(load_plugin(&path).unwrap(), "partner");
+        assert!(matches!(load_plugin(&path), Err(PluginErr::Register(ParserErr::DuplicateFormat { .. }))));
+        let partner = format_by_name("partner").unwrap();
+
+        let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,1700000000,SUCCESS,a\n\
+                   2,DEPOSIT,0,10,200,1700000001,SUCCESS,b\n";
+        let out = dir.path().join("out.partner");
+        let outcome = convert_with(
+            Resource::Memory { data: Cursor::new(csv.as_bytes().to_vec()) },
+            &Format::YpBankCsv,
+            Resource::File { path: out.clone() },
+            partner.as_ref(),
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+        assert!(fs::read_to_string(&out).unwrap().starts_with("#PARTNER\nTX_ID: 1\n"));
+
+        let back = read_as(Resource::File { path: out }, partner.as_ref(), &ParseOptions::default()).unwrap();
+        assert_eq!(back.records.len(), 2);
+        assert!(back.records.iter().zip(&outcome.records).all(|(a, b)| a.same_content(b)));
+
+        // Файл без первой строки модуль отвергает
+        let err = read_as(Resource::Memory { data: Cursor::new(b"TX_ID: 1\n".to_vec()) }, partner.as_ref(), &ParseOptions::default());
+        let Err(crate::model::errors::CommonErr::Parser(ParserErr::Plugin { name, reason })) = err else { panic!() };
+        assert_eq!((name.as_str(), reason.as_str()), ("partner", "decode rejected the input"));
+
+        // Модулю без нужных экспортов негде работать
+        let empty = dir.path().join("empty.wasm");
+        fs::write(&empty, &PARTNER_WASM[..8]).unwrap();
+        assert!(matches!(WasmFormat::load(&empty), Err(PluginErr::Invalid { .. })));
+    }
+}
@@ -0,0 +1,31 @@
+//! Стабильная публичная поверхность библиотеки.
+//!
+//! Всё, что нужно внешнему коду для чтения, записи и конвертации транзакций,
+//! импортируется одной строкой:
+//!
+//! ```
+//! use lib::prelude::*;
+//! ```
+//!
+//! Остальное (сравнение, разбиение, журналы, метрики, CLI и т.д.) импортируется из своих модулей.
+//! Элементы, не попавшие в прелюдию и в публичные модули `model`, `parser`, `convert`, `migrate`,
+//! `partition`, `plugin`, `registry`, `compare`, `delta`, `doctor`, `drift`, `interrupt`, `bundle`,
+//! `audit`, `cache`, `bench`, `junit`, `manifest`, `metrics`, `notify`, `preview`, `schema`,
+//! `script`, `rules`, `screening`, `spill`, `stats`, `testing` и `console`, считаются внутренними
+//! и могут меняться без смены мажорной версии.
+
+pub use crate::convert::{convert, CheckpointMode, ConvertErr, ConvertReport};
+pub use crate::model::data::{Format, Status, TxData, TxType};
+pub use crate::model::errors::{CommonErr, IoErr, ParserErr};
+pub use crate::model::resource::Resource;
+pub use crate::model::tx_id::TxId;
+pub use crate::parser::{
+    read, read_batches, read_with, write_batches, write_to_resource, write_with, BatchReader, BatchWriter, ConvertOptions,
+    ParseOptions, ParseOutcome, ReadOptions, WriteOptions,
+};
+#[cfg(feature = "bin")]
+pub use crate::parser::{TxnFromBin, TxnToBin, TxnWriteToBin};
+#[cfg(feature = "csv")]
+pub use crate::parser::{TxnFromCsv, TxnToCsv, TxnWriteToCsv};
+#[cfg(feature = "text")]
+pub use crate::parser::{TxnFromText, TxnToText, TxnWriteToText};
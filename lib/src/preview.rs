@@ -0,0 +1,233 @@
+//! Предпросмотр конвертации: равномерная выборка записей (reservoir sampling) таблицей
+//! в терминале, чтобы ещё до конца долгой конвертации убедиться, что поля легли как надо.
+//!
+//! [`Preview`] подключается последним преобразованием в [`Transforms`] и видит записи такими,
+//! какими они уходят в результат. Выборка показывается один раз: когда она набрана и с начала
+//! прошло [`PREVIEW_DELAY`], а если вход кончился раньше — по [`Preview::finish`].
+
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::model::data::TxData;
+use crate::parser::Transforms;
+
+/// Сколько собирать выборку, прежде чем показать её посреди конвертации.
+pub const PREVIEW_DELAY: Duration = Duration::from_secs(2);
+
+/// Наибольшая ширина колонки описания в таблице; длинные описания обрезаются.
+const DESCRIPTION_WIDTH: usize = 32;
+
+/// Равномерная выборка не больше `size` записей из потока заранее неизвестной длины (алгоритм R).
+#[derive(Debug, Clone)]
+pub struct Reservoir {
+    size: usize,
+    seen: u64,
+    /// Записи выборки с их номерами во входе
+    records: Vec<(u64, TxData)>,
+    rng: u64,
+}
+
+impl Reservoir {
+    /// Пустая выборка размера `size`; одинаковый `seed` на одинаковом потоке даёт одинаковую выборку.
+    pub fn new(size: usize, seed: u64) -> Self {
+        Reservoir {
+            size,
+            seen: 0,
+            records: Vec::with_capacity(size),
+            rng: seed,
+        }
+    }
+
+    /// Учитывает очередную запись потока.
+    pub fn offer(&mut self, tx: &TxData) {
+        self.seen += 1;
+        if self.records.len() < self.size {
+            self.records.push((self.seen, tx.clone()));
+            return;
+        }
+        let slot = self.next() % self.seen;
+        if slot < self.size as u64 {
+            self.records[slot as usize] = (self.seen, tx.clone());
+        }
+    }
+
+    /// Сколько записей прошло через выборку.
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    /// Выборка набрана: в ней `size` записей.
+    pub fn is_full(&self) -> bool {
+        self.records.len() == self.size
+    }
+
+    /// Записи выборки в порядке входа.
+    pub fn records(&self) -> Vec<TxData> {
+        let mut records = self.records.clone();
+        records.sort_by_key(|(n, _)| *n);
+        records.into_iter().map(|(_, tx)| tx).collect()
+    }
+
+    /// splitmix64: быстрый генератор без внешних зависимостей, для выборки его достаточно.
+    fn next(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// Показ выборки: получает её записи в порядке входа.
+pub type ShowPreview = Box<dyn Fn(&[TxData]) + Send + Sync>;
+
+/// Выборка, которую можно наполнять из нескольких потоков конвертации и показать один раз.
+pub struct Preview {
+    started: Instant,
+    state: Mutex<(Reservoir, bool)>,
+    show: ShowPreview,
+}
+
+impl Preview {
+    /// Выборка из `size` записей; `show` получает её в порядке входа.
+    pub fn new(size: usize, show: impl Fn(&[TxData]) + Send + Sync + 'static) -> Self {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        Preview {
+            started: Instant::now(),
+            state: Mutex::new((Reservoir::new(size, seed), false)),
+            show: Box::new(show),
+        }
+    }
+
+    /// `transforms` с этой выборкой в конце цепочки.
+    pub fn install(self: &Arc<Self>, transforms: Transforms) -> Transforms {
+        let preview = Arc::clone(self);
+        transforms.then(move |tx| {
+            preview.observe(&tx);
+            Some(tx)
+        })
+    }
+
+    /// Учитывает запись и показывает выборку, если она набрана и прошло [`PREVIEW_DELAY`].
+    pub fn observe(&self, tx: &TxData) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (reservoir, shown) = &mut *state;
+        if *shown {
+            return;
+        }
+        reservoir.offer(tx);
+        if reservoir.is_full() && self.started.elapsed() >= PREVIEW_DELAY {
+            self.take_and_show(state);
+        }
+    }
+
+    /// Показывает выборку, если её ещё не показали и в ней есть записи.
+    pub fn finish(&self) {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if !state.1 && state.0.seen() > 0 {
+            self.take_and_show(state);
+        }
+    }
+
+    fn take_and_show(&self, mut state: MutexGuard<'_, (Reservoir, bool)>) {
+        state.1 = true;
+        let records = state.0.records();
+        drop(state);
+        (self.show)(&records);
+    }
+}
+
+impl fmt::Debug for Preview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Preview").field("started", &self.started).finish_non_exhaustive()
+    }
+}
+
+/// Таблица записей с колонками [`TxData::FIELDS`], выровненная пробелами.
+pub fn preview_table(records: &[TxData]) -> String {
+    let rows: Vec<[String; 8]> = records
+        .iter()
+        .map(|tx| {
+            [
+                tx.tx_id.to_string(),
+                tx.tx_type.name().to_string(),
+                tx.from_user_id.to_string(),
+                tx.to_user_id.to_string(),
+                tx.amount.to_string(),
+                tx.timestamp.to_string(),
+                tx.status.name().to_string(),
+                truncate(&tx.description),
+            ]
+        })
+        .collect();
+    let mut widths = TxData::FIELDS.map(|field| field.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let header = TxData::FIELDS.map(String::from);
+    let mut table = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row.iter().zip(widths).map(|(cell, width)| format!("{:<width$}", cell)).collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+fn truncate(description: &str) -> String {
+    if description.chars().count() <= DESCRIPTION_WIDTH {
+        return description.to_string();
+    }
+    let mut short: String = description.chars().take(DESCRIPTION_WIDTH - 1).collect();
+    short.push('…');
+    short
+}
+
+#[cfg(all(test, feature = "bench"))]
+mod tests {
+    use super::*;
+    use crate::bench::synthetic_record;
+
+    #[test]
+    fn test_reservoir_samples_whole_stream() {
+        let mut reservoir = Reservoir::new(10, 7);
+        for n in 0..10_000 {
+            reservoir.offer(&synthetic_record(n));
+        }
+        assert_eq!(reservoir.seen(), 10_000);
+        // synthetic_record(n) получает TX_ID 1_000_000_000 + n
+        let ids: Vec<u64> = reservoir.records().iter().map(|tx| tx.tx_id.to_string().parse::<u64>().unwrap() - 1_000_000_000).collect();
+        assert_eq!(ids.len(), 10);
+        // Выборка не сводится к началу потока и идёт в порядке входа
+        assert!(ids.iter().any(|&n| n >= 5_000));
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+
+        // Короткий поток попадает в выборку целиком
+        let mut reservoir = Reservoir::new(10, 7);
+        (0..3).for_each(|n| reservoir.offer(&synthetic_record(n)));
+        assert_eq!(reservoir.records().len(), 3);
+    }
+
+    #[test]
+    fn test_preview_is_shown_once_as_table() {
+        let shown = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&shown);
+        let preview = Arc::new(Preview::new(2, move |records| sink.lock().unwrap().push(preview_table(records))));
+        let transforms = preview.install(Transforms::default());
+        for n in 0..5 {
+            assert!(transforms.apply(synthetic_record(n)).is_some());
+        }
+        preview.finish();
+        preview.finish();
+
+        let shown = shown.lock().unwrap();
+        assert_eq!(shown.len(), 1);
+        let lines: Vec<&str> = shown[0].lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("TX_ID"));
+        assert!(lines[0].ends_with("DESCRIPTION"));
+    }
+}
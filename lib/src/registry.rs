@@ -0,0 +1,210 @@
+//! Форматы, подключаемые во время выполнения.
+//!
+//! Крейт-потребитель реализует [`TxFormat`] для своего формата и регистрирует его под именем
+//! через [`register_format`]; после этого формат читается, пишется и конвертируется теми же
+//! функциями, что и встроенные ([`Format`] тоже реализует [`TxFormat`]):
+//!
+//! ```ignore
+//! register_format("legacy-fixed", Box::new(LegacyFixedWidth))?;
+//! let fixed = format_by_name("legacy-fixed").unwrap();
+//! convert_with(from, &Format::YpBankCsv, to, fixed.as_ref(), &ConvertOptions::default())?;
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::sync::{Arc, RwLock};
+
+use crate::model::data::{Format, TxData};
+use crate::model::errors::{CommonErr, IoErr, ParserErr};
+use crate::model::resource::Resource;
+use crate::parser::io::reader::{open, read_from_resource};
+use crate::parser::io::writer::{sink, write, CountingWriter};
+use crate::parser::options::{ConvertOptions, ParseOptions, ParseOutcome, WriteOptions};
+
+/// Формат сериализации транзакций.
+///
+/// Записи, прочитанные внешним форматом, хранят в `TxData::format` тот встроенный формат,
+/// который выберет реализация; при сравнении записей его стоит игнорировать
+/// (см. `TxData::same_content`).
+pub trait TxFormat: Send + Sync {
+    /// Разбирает все записи потока с настройками `options`.
+    ///
+    /// # Errors
+    /// `ParserErr`, если разбор пришлось прервать.
+    fn read(&self, reader: Box<dyn Read>, options: &ParseOptions) -> Result<ParseOutcome, ParserErr>;
+
+    /// Записывает `records` в поток целым документом.
+    ///
+    /// # Errors
+    /// `ParserErr` при ошибке сериализации или записи.
+    fn write(&self, records: &[TxData], w: &mut dyn Write, options: &WriteOptions) -> Result<(), ParserErr>;
+}
+
+impl TxFormat for Format {
+    fn read(&self, reader: Box<dyn Read>, options: &ParseOptions) -> Result<ParseOutcome, ParserErr> {
+        read_from_resource(reader, *self, options)
+    }
+
+    fn write(&self, records: &[TxData], w: &mut dyn Write, options: &WriteOptions) -> Result<(), ParserErr> {
+        let mut sink = sink(*self, options)?;
+        sink.write_batch(w, records, &[])?;
+        sink.finish(w)
+    }
+}
+
+/// Зарегистрированные форматы по именам.
+static REGISTRY: RwLock<BTreeMap<String, Arc<dyn TxFormat>>> = RwLock::new(BTreeMap::new());
+
+/// Регистрирует формат под именем `name`.
+///
+/// # Errors
+/// `ParserErr::DuplicateFormat`, если имя совпадает с именем встроенного формата
+/// (см. [`Format::name`]) или уже зарегистрировано.
+pub fn register_format(name: &str, format: Box<dyn TxFormat>) -> Result<(), ParserErr> {
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    if Format::from_name(name).is_some() || registry.contains_key(name) {
+        return Err(ParserErr::DuplicateFormat { name: name.to_string() });
+    }
+    registry.insert(name.to_string(), Arc::from(format));
+    Ok(())
+}
+
+/// Встроенный или зарегистрированный формат по имени.
+pub fn format_by_name(name: &str) -> Option<Arc<dyn TxFormat>> {
+    if let Some(format) = Format::from_name(name) {
+        return Some(Arc::new(format));
+    }
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    registry.get(name).cloned()
+}
+
+/// Имена зарегистрированных форматов (без встроенных) по алфавиту.
+pub fn registered_formats() -> Vec<String> {
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    registry.keys().cloned().collect()
+}
+
+/// Читает ресурс в формате `format` (см. [`read_with`](crate::parser::read_with)).
+pub fn read_as(resource: Resource, format: &dyn TxFormat, options: &ParseOptions) -> Result<ParseOutcome, CommonErr> {
+    Ok(format.read(open(resource)?, options)?)
+}
+
+/// Записывает транзакции в ресурс в формате `format`.
+///
+/// # Возвращает
+/// Размер записанных данных.
+pub fn write_as(
+    records: &[TxData],
+    resource: Resource,
+    format: &dyn TxFormat,
+    options: &WriteOptions,
+) -> Result<usize, CommonErr> {
+    let path = match &resource {
+        Resource::File { path } => Some(path.clone()),
+        _ => None,
+    };
+    let mut output = CountingWriter {
+        inner: write(resource)?,
+        written: 0,
+    };
+    format
+        .write(records, &mut output, options)
+        .map_err(|e| match e {
+            ParserErr::Io { source, .. } => CommonErr::IO(IoErr::OutputErr {
+                path: path.clone(),
+                kind: source.kind(),
+                source,
+            }),
+            other => CommonErr::Parser(other),
+        })?;
+    output.flush().map_err(|e| IoErr::output(e, path))?;
+    Ok(output.written)
+}
+
+/// Конвертирует `from` в `to` между любыми форматами, встроенными или внешними.
+///
+/// Вход читается целиком с `options.parse`, в результат попадают записи окна `options.read`
+/// после преобразований `options.transforms`.
+///
+/// # Возвращает
+/// Итог разбора входа; в `records` — записанные записи.
+pub fn convert_with(
+    from: Resource,
+    from_format: &dyn TxFormat,
+    to: Resource,
+    to_format: &dyn TxFormat,
+    options: &ConvertOptions,
+) -> Result<ParseOutcome, CommonErr> {
+    let mut outcome = read_as(from, from_format, &options.parse)?;
+    let window = &options.read;
+    outcome.records = std::mem::take(&mut outcome.records)
+        .into_iter()
+        .skip(window.skip)
+        .take(window.limit.unwrap_or(usize::MAX))
+        .collect();
+    outcome.provenance = std::mem::take(&mut outcome.provenance)
+        .into_iter()
+        .skip(window.skip)
+        .take(window.limit.unwrap_or(usize::MAX))
+        .collect();
+    options.transforms.apply_batch(&mut outcome);
+    write_as(&outcome.records, to, to_format, &options.write)?;
+    Ok(outcome)
+}
+
+#[cfg(all(test, feature = "csv", feature = "text"))]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Текстовый формат с обязательной первой строкой `#TOY`.
+    struct Toy;
+
+    impl TxFormat for Toy {
+        fn read(&self, mut reader: Box<dyn Read>, options: &ParseOptions) -> Result<ParseOutcome, ParserErr> {
+            let mut content = String::new();
+            reader.read_to_string(&mut content)?;
+            let Some(body) = content.strip_prefix("#TOY\n") else {
+                return Err(ParserErr::Malformed { msg: "no #TOY line".to_string() });
+            };
+            Format::YpBankText.read(Box::new(Cursor::new(body.to_string())), options)
+        }
+
+        fn write(&self, records: &[TxData], w: &mut dyn Write, options: &WriteOptions) -> Result<(), ParserErr> {
+            w.write_all(b"#TOY\n")?;
+            Format::YpBankText.write(records, w, options)
+        }
+    }
+
+    #[test]
+    fn test_registered_format_converts_both_ways() {
+        register_format("toy", Box::new(Toy)).unwrap();
+        assert!(matches!(register_format("toy", Box::new(Toy)), Err(ParserErr::DuplicateFormat { .. })));
+        assert!(matches!(
+            register_format("yp-bank-csv", Box::new(Toy)),
+            Err(ParserErr::DuplicateFormat { .. })
+        ));
+        assert!(registered_formats().contains(&"toy".to_string()));
+
+        let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,1700000000,SUCCESS,a\n\
+                   2,DEPOSIT,0,10,200,1700000001,SUCCESS,b\n";
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.toy");
+        let toy = format_by_name("toy").unwrap();
+
+        let outcome = convert_with(
+            Resource::Memory { data: Cursor::new(csv.as_bytes().to_vec()) },
+            &Format::YpBankCsv,
+            Resource::File { path: path.clone() },
+            toy.as_ref(),
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().starts_with("#TOY\nTX_ID: 1\n"));
+
+        let back = read_as(Resource::File { path }, toy.as_ref(), &ParseOptions::default()).unwrap();
+        assert_eq!(back.records.len(), 2);
+        assert!(back.records.iter().zip(&outcome.records).all(|(a, b)| a.same_content(b)));
+    }
+}
@@ -0,0 +1,240 @@
+//! Наборы правил проверки из TOML-файла: эксплуатация ужесточает проверки записей
+//! без изменения кода.
+//!
+//! ```toml
+//! # Допустимые ID пользователей, включительные диапазоны; 0 — внешний счёт и не проверяется
+//! user_ids = [[1, 99_999], [1_000_000, 1_999_999]]
+//!
+//! # Наибольшая сумма по типу операции
+//! [max_amount]
+//! TRANSFER = 1_000_000
+//! WITHDRAWAL = 500_000
+//!
+//! # Статусы, недопустимые для записей с TIMESTAMP раньше метки (число в мс или YYYY-MM-DD)
+//! [stale]
+//! before = "2024-01-01"
+//! forbidden_statuses = ["PENDING"]
+//! ```
+//!
+//! Нарушения — те же [`RuleViolation`], что и у встроенных правил: набор подключается
+//! через `ParseOptions::rules` и отклоняет запись так же, как строгий режим.
+
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+use toml::{Table, Value};
+
+use crate::model::data::{Status, TxData, TxType};
+use crate::model::time::parse_time_bound;
+use crate::model::validation::RuleViolation;
+
+/// Ошибка загрузки набора правил.
+#[derive(Error, Debug)]
+pub enum RulePackErr {
+    /// Файл не удалось прочитать
+    #[error("failed to read rules file {}: {err}", path.display())]
+    Io { path: PathBuf, err: std::io::Error },
+    /// Файл — не TOML
+    #[error("rules file is not valid TOML: {reason}")]
+    Toml { reason: String },
+    /// Ключ с неподходящим значением или неизвестный ключ
+    #[error("invalid rule {key}: {reason}")]
+    Invalid { key: String, reason: String },
+}
+
+/// Статусы, недопустимые для старых записей.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleRule {
+    /// Записи с `TIMESTAMP` меньше этой метки считаются старыми
+    pub before: u64,
+    pub forbidden_statuses: Vec<Status>,
+}
+
+/// Набор дополнительных правил проверки записей.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RulePack {
+    /// Наибольшая допустимая сумма по типу операции
+    pub max_amount: Vec<(TxType, i64)>,
+    /// Допустимые ID отправителя и получателя (кроме 0); пустой список — любые
+    pub user_ids: Vec<RangeInclusive<u64>>,
+    pub stale: Option<StaleRule>,
+}
+
+impl RulePack {
+    /// Читает набор правил из TOML-файла.
+    pub fn load(path: &Path) -> Result<RulePack, RulePackErr> {
+        let text = fs::read_to_string(path).map_err(|err| RulePackErr::Io { path: path.to_path_buf(), err })?;
+        RulePack::from_toml(&text)
+    }
+
+    /// Разбирает набор правил из текста TOML.
+    pub fn from_toml(text: &str) -> Result<RulePack, RulePackErr> {
+        let table: Table = text.parse().map_err(|e: toml::de::Error| RulePackErr::Toml { reason: e.to_string() })?;
+        let mut pack = RulePack::default();
+        for (key, value) in &table {
+            match key.as_str() {
+                "max_amount" => pack.max_amount = max_amount(value)?,
+                "user_ids" => pack.user_ids = user_ids(value)?,
+                "stale" => pack.stale = Some(stale(value)?),
+                _ => return Err(invalid(key, "unknown rule")),
+            }
+        }
+        Ok(pack)
+    }
+
+    /// Проверяет запись по правилам набора и возвращает найденные нарушения.
+    pub fn check(&self, tx: &TxData) -> Vec<RuleViolation> {
+        let mut violations = Vec::new();
+
+        if let Some(&(_, max)) = self.max_amount.iter().find(|(tx_type, _)| *tx_type == tx.tx_type) {
+            if tx.amount > max {
+                violations.push(RuleViolation::AmountAboveMax { tx_type: tx.tx_type, amount: tx.amount, max });
+            }
+        }
+        if !self.user_ids.is_empty() {
+            for (field, user_id) in [("FROM_USER_ID", tx.from_user_id), ("TO_USER_ID", tx.to_user_id)] {
+                if user_id != 0 && !self.user_ids.iter().any(|range| range.contains(&user_id)) {
+                    violations.push(RuleViolation::UserIdOutOfRange { field, user_id });
+                }
+            }
+        }
+        if let Some(stale) = &self.stale {
+            if tx.timestamp < stale.before && stale.forbidden_statuses.contains(&tx.status) {
+                violations.push(RuleViolation::StaleStatus { status: tx.status, timestamp: tx.timestamp });
+            }
+        }
+
+        violations
+    }
+}
+
+fn invalid(key: &str, reason: impl Into<String>) -> RulePackErr {
+    RulePackErr::Invalid { key: key.to_string(), reason: reason.into() }
+}
+
+fn integer(key: &str, value: &Value) -> Result<i64, RulePackErr> {
+    value.as_integer().ok_or_else(|| invalid(key, "expected an integer"))
+}
+
+fn max_amount(value: &Value) -> Result<Vec<(TxType, i64)>, RulePackErr> {
+    let table = value.as_table().ok_or_else(|| invalid("max_amount", "expected a table of TX_TYPE = amount"))?;
+    table
+        .iter()
+        .map(|(name, amount)| {
+            let key = format!("max_amount.{}", name);
+            let tx_type = TxType::ALL.into_iter().find(|t| t.name() == name).ok_or_else(|| invalid(&key, "unknown TX_TYPE"))?;
+            Ok((tx_type, integer(&key, amount)?))
+        })
+        .collect()
+}
+
+fn user_ids(value: &Value) -> Result<Vec<RangeInclusive<u64>>, RulePackErr> {
+    let ranges = value.as_array().ok_or_else(|| invalid("user_ids", "expected a list of [first, last] ranges"))?;
+    ranges
+        .iter()
+        .map(|range| {
+            let bound = |value: &Value| integer("user_ids", value).and_then(|n| u64::try_from(n).map_err(|_| invalid("user_ids", "negative user id")));
+            match range.as_array().map(Vec::as_slice) {
+                Some([first, last]) => {
+                    let (first, last) = (bound(first)?, bound(last)?);
+                    if first > last {
+                        return Err(invalid("user_ids", format!("empty range [{}, {}]", first, last)));
+                    }
+                    Ok(first..=last)
+                },
+                _ => Err(invalid("user_ids", "expected a [first, last] range")),
+            }
+        })
+        .collect()
+}
+
+fn stale(value: &Value) -> Result<StaleRule, RulePackErr> {
+    let table = value.as_table().ok_or_else(|| invalid("stale", "expected a table"))?;
+    let mut before = None;
+    let mut forbidden_statuses = Vec::new();
+    for (key, value) in table {
+        match key.as_str() {
+            "before" => {
+                let bound = match value {
+                    Value::Integer(n) => u64::try_from(*n).map_err(|_| invalid("stale.before", "negative timestamp"))?,
+                    Value::String(s) => parse_time_bound(s).map_err(|reason| invalid("stale.before", reason))?,
+                    _ => return Err(invalid("stale.before", "expected a timestamp or a date")),
+                };
+                before = Some(bound);
+            },
+            "forbidden_statuses" => {
+                let names = value.as_array().ok_or_else(|| invalid("stale.forbidden_statuses", "expected a list of STATUS"))?;
+                for name in names {
+                    let status = Status::ALL
+                        .into_iter()
+                        .find(|s| Some(s.name()) == name.as_str())
+                        .ok_or_else(|| invalid("stale.forbidden_statuses", format!("unknown STATUS {:?}", name)))?;
+                    forbidden_statuses.push(status);
+                }
+            },
+            _ => return Err(invalid(&format!("stale.{}", key), "unknown rule")),
+        }
+    }
+    let before = before.ok_or_else(|| invalid("stale.before", "missing"))?;
+    Ok(StaleRule { before, forbidden_statuses })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::data::Format;
+
+    const RULES: &str = r#"
+        user_ids = [[1, 999]]
+
+        [max_amount]
+        WITHDRAWAL = 500
+
+        [stale]
+        before = "2024-01-01"
+        forbidden_statuses = ["PENDING"]
+    "#;
+
+    fn tx(tx_type: TxType, from: u64, to: u64, amount: i64, status: Status) -> TxData {
+        TxData {
+            tx_id: 1.into(),
+            tx_type,
+            from_user_id: from,
+            to_user_id: to,
+            amount,
+            timestamp: 1700000000000,
+            status,
+            description: "rule".into(),
+            format: Format::YpBankCsv,
+        }
+    }
+
+    #[test]
+    fn test_rule_pack_checks_records() {
+        let pack = RulePack::from_toml(RULES).unwrap();
+        assert!(pack.check(&tx(TxType::Withdrawal, 10, 0, 500, Status::Success)).is_empty());
+        assert!(pack.check(&tx(TxType::Deposit, 0, 10, 1_000_000, Status::Success)).is_empty());
+
+        assert_eq!(
+            pack.check(&tx(TxType::Withdrawal, 1000, 0, 501, Status::Pending)),
+            vec![
+                RuleViolation::AmountAboveMax { tx_type: TxType::Withdrawal, amount: 501, max: 500 },
+                RuleViolation::UserIdOutOfRange { field: "FROM_USER_ID", user_id: 1000 },
+                RuleViolation::StaleStatus { status: Status::Pending, timestamp: 1700000000000 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rule_pack_rejects_bad_rules() {
+        let Err(RulePackErr::Invalid { key, .. }) = RulePack::from_toml("[max_amount]\nREFUND = 1\n") else {
+            panic!("expected an invalid rule");
+        };
+        assert_eq!(key, "max_amount.REFUND");
+        assert!(matches!(RulePack::from_toml("user_ids = [[5, 1]]"), Err(RulePackErr::Invalid { .. })));
+        assert!(matches!(RulePack::from_toml("max_ammount = 1"), Err(RulePackErr::Invalid { .. })));
+        assert!(matches!(RulePack::from_toml("[stale"), Err(RulePackErr::Toml { .. })));
+    }
+}
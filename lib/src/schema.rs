@@ -0,0 +1,315 @@
+//! Машиночитаемое описание форматов для интеграторов, собранное из тех же констант,
+//! которыми пользуются парсеры: JSON Schema записи (с особенностями CSV и текстового
+//! формата), таблица раскладки байтов бинарного формата и таблица возможностей форматов.
+
+use std::io::Write;
+
+use serde_json::{json, Value};
+
+use crate::model::data::{Format, Status, TxData, TxType};
+use crate::model::errors::{CommonErr, IoErr};
+use crate::model::resource::Resource;
+use crate::parser::io::writer::write;
+use crate::parser::options::{BinProfile, WriteOptions};
+
+/// Что описывать.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "console", derive(clap::ValueEnum))]
+pub enum SchemaKind {
+    /// JSON Schema записи, CSV и текстового формата
+    Json,
+    /// Раскладка байтов бинарного формата (таблица Markdown)
+    Bin,
+    /// Возможности форматов: что сохраняется при конвертации и как формат читается (таблица Markdown)
+    Capabilities,
+}
+
+/// JSON Schema (draft 2020-12) одной транзакции: ключи — имена полей спецификации.
+///
+/// Колонки CSV и ключи текстового формата совпадают с `properties` и перечислены
+/// в расширениях `x-ypbank-csv` и `x-ypbank-text`.
+pub fn json_schema() -> Value {
+    let names = |names: Vec<&'static str>| Value::from(names);
+    let unsigned = || json!({ "type": "integer", "minimum": 0, "maximum": u64::MAX });
+    let properties = json!({
+        "TX_ID": {
+            "description": "Transaction id: unsigned number, UUID or non-empty string (binary schema 2 and later)",
+            "oneOf": [
+                { "type": "integer", "minimum": 0, "maximum": u64::MAX },
+                { "type": "string", "format": "uuid" },
+                { "type": "string", "minLength": 1 }
+            ]
+        },
+        "TX_TYPE": { "enum": names(TxType::ALL.map(TxType::name).to_vec()) },
+        "FROM_USER_ID": unsigned(),
+        "TO_USER_ID": unsigned(),
+        "AMOUNT": {
+            "description": "Amount in minor units",
+            "type": "integer",
+            "minimum": i64::MIN,
+            "maximum": i64::MAX
+        },
+        "TIMESTAMP": {
+            "description": "Unix time in milliseconds",
+            "type": "integer",
+            "minimum": 0,
+            "maximum": u64::MAX
+        },
+        "STATUS": { "enum": names(Status::ALL.map(Status::name).to_vec()) },
+        "DESCRIPTION": { "type": "string" }
+    });
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": "urn:ypbank:transaction",
+        "title": "YPBank transaction",
+        "type": "object",
+        "properties": properties,
+        "required": TxData::FIELDS,
+        "additionalProperties": false,
+        "x-ypbank-csv": {
+            "header": TxData::FIELDS.join(","),
+            "columns": TxData::FIELDS,
+            "quote": "\"",
+            "bom": "optional UTF-8 BOM"
+        },
+        "x-ypbank-text": {
+            "keys": TxData::FIELDS,
+            "line": "KEY: value",
+            "quoted": ["DESCRIPTION"],
+            "record_separator": "empty line",
+            "comment_prefix": "#"
+        }
+    })
+}
+
+/// Поле в раскладке байтов бинарного формата.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutField {
+    /// Часть файла: `file header`, `record`, `end`
+    pub section: &'static str,
+    pub name: &'static str,
+    /// Размер в байтах или как его узнать (`varint`, `DESC_LEN`)
+    pub size: String,
+    /// Кодирование и допустимые значения
+    pub encoding: String,
+}
+
+/// Раскладка байтов бинарного файла текущей версии схемы в профиле `profile`.
+pub fn bin_layout(profile: BinProfile) -> Vec<LayoutField> {
+    use crate::migrate::CURRENT_SCHEMA;
+    use crate::parser::concrete::bin_psrser::{
+        status_code, tx_type_code, BIN_MAGIC, DICTIONARY_MAX, FILE_MAGIC, FLAG_DICTIONARY, FLAG_INDEX,
+        FLAG_METADATA, FLAG_SEEK_TABLE, FLAG_ZSTD, ID_STRING, ID_U64, ID_UUID, INDEX_MAGIC, SEEKABLE_MAGIC, SKIPPABLE_MAGIC,
+    };
+
+    let magic = |magic: [u8; 4]| format!("ASCII \"{}\"", String::from_utf8_lossy(&magic));
+    let codes = |codes: Vec<(u8, &str)>| {
+        codes.iter().map(|(code, name)| format!("{} = {}", code, name)).collect::<Vec<_>>().join(", ")
+    };
+    let (int, len, len_size) = match profile {
+        BinProfile::BigEndian => ("big-endian", "u32 big-endian", "4"),
+        BinProfile::CompactLe => ("little-endian", "unsigned LEB128 varint", "varint"),
+    };
+    let field = |section, name, size: &str, encoding: String| LayoutField { section, name, size: size.to_string(), encoding };
+
+    let mut layout = vec![
+        field("file header", "MAGIC", "4", magic(FILE_MAGIC)),
+        field("file header", "VERSION", "1", format!("u8, schema version {}", CURRENT_SCHEMA)),
+        field("file header", "PROFILE", "1", codes(vec![(0, "BigEndian"), (1, "CompactLe")])),
+        field(
+            "file header",
+            "FLAGS",
+            "1",
+            format!(
+                "bit set: {:#04x} = zstd blocks, {:#04x} = index at the end, {:#04x} = description dictionary, \
+                 {:#04x} = seek table of zstd blocks at the end, {:#04x} = producer metadata",
+                FLAG_ZSTD, FLAG_INDEX, FLAG_DICTIONARY, FLAG_SEEK_TABLE, FLAG_METADATA
+            ),
+        ),
+        field("file header", "RESERVED", "1", "0".to_string()),
+        field("metadata", "META_COUNT", "varint", "unsigned LEB128 varint; only with the metadata flag".to_string()),
+        field(
+            "metadata",
+            "META_PAIR",
+            "varint + N, varint + M",
+            "key and value, each length (unsigned LEB128 varint) and UTF-8, META_COUNT times; \
+             keys: producer, created (ms since the Unix epoch), source-sha256"
+                .to_string(),
+        ),
+        field(
+            "dictionary",
+            "DICT_COUNT",
+            "varint",
+            format!("unsigned LEB128 varint, at most {}; only with the dictionary flag", DICTIONARY_MAX),
+        ),
+        field("dictionary", "DICT_ENTRY", "varint + N", "length (unsigned LEB128 varint) and UTF-8, DICT_COUNT times".to_string()),
+    ];
+    if profile == BinProfile::BigEndian {
+        layout.push(field("record", "MAGIC", "4", magic(BIN_MAGIC)));
+    }
+    layout.extend([
+        field("record", "BODY_LEN", len_size, format!("{}, bytes of the fields below", len)),
+        field("record", "TX_ID_KIND", "1", codes(vec![(ID_U64, "u64"), (ID_UUID, "UUID"), (ID_STRING, "string")])),
+        field(
+            "record",
+            "TX_ID",
+            "8, 16 or len + N",
+            format!("u64 or u128 {}; string: length ({}) and UTF-8", int, len),
+        ),
+        field(
+            "record",
+            "TX_TYPE",
+            "1",
+            codes(TxType::ALL.iter().map(|&t| (tx_type_code(t), t.name())).collect()),
+        ),
+        field("record", "FROM_USER_ID", "8", format!("u64 {}", int)),
+        field("record", "TO_USER_ID", "8", format!("u64 {}", int)),
+        field("record", "AMOUNT", "8", format!("i64 {}, minor units", int)),
+        field("record", "TIMESTAMP", "8", format!("u64 {}, Unix time in milliseconds", int)),
+        field(
+            "record",
+            "STATUS",
+            "1",
+            codes(Status::ALL.iter().map(|&s| (status_code(s), s.name())).collect()),
+        ),
+        field(
+            "record",
+            "DESC_LEN",
+            len_size,
+            format!("{}; with the dictionary flag: length << 1, or entry number << 1 | 1", len),
+        ),
+        field("record", "DESCRIPTION", "DESC_LEN", "UTF-8; absent for a dictionary entry".to_string()),
+    ]);
+    let end = match profile {
+        BinProfile::BigEndian => field("end", "INDEX_MAGIC", "4", format!("{}, only with the index flag", magic(INDEX_MAGIC))),
+        BinProfile::CompactLe => field("end", "BODY_LEN", "1", "0: empty record before the index, only with the index flag".to_string()),
+    };
+    layout.push(end);
+    let seek = |name, size, encoding: String| field("seek table", name, size, format!("{}; only with the seek table flag", encoding));
+    layout.extend([
+        seek("SKIPPABLE_MAGIC", "4", format!("u32 little-endian {:#010x}: zstd skippable frame", SKIPPABLE_MAGIC)),
+        seek("FRAME_SIZE", "4", "u32 little-endian, bytes of the fields below".to_string()),
+        seek("BLOCK", "12 × BLOCKS", "block size with its header, decompressed size, record count (u32 little-endian)".to_string()),
+        seek("BLOCKS", "4", "u32 little-endian".to_string()),
+        seek("DESCRIPTOR", "1", "0".to_string()),
+        seek("SEEKABLE_MAGIC", "4", format!("u32 little-endian {:#010x}", SEEKABLE_MAGIC)),
+    ]);
+    layout
+}
+
+/// [`bin_layout`] таблицей Markdown.
+pub fn render_bin_layout(profile: BinProfile) -> String {
+    let mut out = format!("# YPBank binary layout ({:?})\n\n", profile);
+    out.push_str("| Section | Field | Size | Encoding |\n|---|---|---|---|\n");
+    for field in bin_layout(profile) {
+        out.push_str(&format!("| {} | {} | {} | {} |\n", field.section, field.name, field.size, field.encoding));
+    }
+    out
+}
+
+/// [`Format::capabilities`] всех форматов таблицей Markdown; бинарный формат — с заголовком
+/// и индексом и без заголовка (как пишется по умолчанию).
+pub fn render_capabilities() -> String {
+    let mut out = String::from("# YPBank format capabilities\n\n");
+    out.push_str("| Format | Lossless | Comments | Extra columns | Long descriptions | Metadata | Streaming | Append | Random access |\n");
+    out.push_str("|---|---|---|---|---|---|---|---|---|\n");
+    let rows = [
+        (Format::YpBankCsv.name().to_string(), Format::YpBankCsv.capabilities()),
+        (Format::YpBankText.name().to_string(), Format::YpBankText.capabilities()),
+        (format!("{} (--bin-header --bin-index)", Format::YpBankBin.name()), Format::YpBankBin.capabilities()),
+        (format!("{} (no header)", Format::YpBankBin.name()), Format::YpBankBin.capabilities_with(&WriteOptions::default())),
+    ];
+    let mark = |yes: bool| if yes { "yes" } else { "no" };
+    for (name, c) in rows {
+        let cells = [c.lossless, c.keeps_comments, c.keeps_extra_columns, c.long_descriptions, c.metadata, c.streaming, c.append, c.random_access];
+        out.push_str(&format!("| {} | {} |\n", name, cells.map(mark).join(" | ")));
+    }
+    out
+}
+
+/// Описание `kind` в виде текста для вывода; `profile` — для раскладки бинарного формата.
+pub fn render_schema(kind: SchemaKind, profile: BinProfile) -> String {
+    match kind {
+        SchemaKind::Json => format!("{:#}\n", json_schema()),
+        SchemaKind::Bin => render_bin_layout(profile),
+        SchemaKind::Capabilities => render_capabilities(),
+    }
+}
+
+/// Записывает описание `kind` (см. [`render_schema`]) в ресурс `to`.
+///
+/// # Возвращает
+/// Размер записанного описания в байтах.
+pub fn write_schema(kind: SchemaKind, profile: BinProfile, to: Resource) -> Result<usize, CommonErr> {
+    let path = match &to {
+        Resource::File { path } => Some(path.clone()),
+        _ => None,
+    };
+    let schema = render_schema(kind, profile);
+    let mut out = write(to)?;
+    out.write_all(schema.as_bytes())
+        .and_then(|_| out.flush())
+        .map_err(|e| IoErr::output(e, path))?;
+    Ok(schema.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_schema_lists_fields_and_values() {
+        let schema = json_schema();
+        assert_eq!(schema["required"].as_array().unwrap().len(), TxData::FIELDS.len());
+        assert_eq!(schema["properties"]["STATUS"]["enum"], json!(["SUCCESS", "FAILURE", "PENDING"]));
+        assert_eq!(
+            schema["x-ypbank-csv"]["header"],
+            "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION"
+        );
+    }
+
+    /// Раскладка должна совпадать с тем, что пишет сериализатор.
+    #[test]
+    fn test_bin_layout_matches_encoded_record() {
+        use crate::model::data::Format;
+        use crate::model::tx_id::TxId;
+        use crate::parser::concrete::bin_psrser::{tx_type_code, TxnToBin};
+        use crate::parser::options::WriteOptions;
+
+        let tx = TxData {
+            tx_id: TxId::U64(7),
+            tx_type: TxType::Withdrawal,
+            from_user_id: 1,
+            to_user_id: 0,
+            amount: 500,
+            timestamp: 1700000000000,
+            status: Status::Pending,
+            description: "rent".into(),
+            format: Format::YpBankBin,
+        };
+        let options = WriteOptions { bin_header: true, ..WriteOptions::default() };
+        let bytes = TxData::to_bin_many_with(std::slice::from_ref(&tx), &options).unwrap();
+
+        // Заголовок без сведений и словаря и одна запись: TX_ID — u64, описание — 4 байта
+        let layout: Vec<LayoutField> = bin_layout(BinProfile::BigEndian)
+            .into_iter()
+            .filter(|field| field.section != "metadata" && field.section != "dictionary")
+            .collect();
+        let size = |field: &LayoutField| match field.name {
+            "TX_ID" => 8,
+            "DESCRIPTION" => tx.description.len(),
+            _ => field.size.parse::<usize>().unwrap(),
+        };
+        let fixed: usize = layout.iter().filter(|field| field.section == "file header" || field.section == "record").map(size).sum();
+        assert_eq!(bytes.len(), fixed);
+
+        let at = |name: &str| {
+            let index = layout.iter().position(|field| field.section == "record" && field.name == name).unwrap();
+            layout[..index].iter().map(size).sum::<usize>()
+        };
+        assert_eq!(bytes[at("TX_TYPE")], tx_type_code(TxType::Withdrawal));
+        assert_eq!(bytes[at("STATUS")], 2);
+        assert_eq!(&bytes[at("DESCRIPTION")..at("DESCRIPTION") + 4], b"rent");
+    }
+}
@@ -0,0 +1,364 @@
+//! Поиск подозрительной активности по эвристикам с оценкой каждого пользователя.
+//!
+//! [`screen`] ищет два шаблона:
+//! - дробление (structuring): несколько операций пользователя чуть ниже порога отчётности
+//!   за короткое окно;
+//! - быстрый оборот (rapid cycle): поступление на счёт пользователя, почти целиком
+//!   выведенное вскоре после зачисления.
+//!
+//! Каждая находка приносит пользователю баллы; оценка ограничена [`MAX_SCORE`].
+//! Операции со статусом `FAILURE` не учитываются.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use crate::model::data::{Status, TxData, TxType};
+use crate::model::errors::CommonErr;
+use crate::model::resource::Resource;
+use crate::model::tx_id::TxId;
+use crate::parser::io::report::{quote, write_report, ReportFormat};
+
+/// Наибольшая оценка пользователя.
+pub const MAX_SCORE: u32 = 100;
+
+/// Баллы за каждую операцию в найденном дроблении.
+const STRUCTURING_POINTS: u32 = 10;
+
+/// Баллы за каждый быстрый оборот.
+const CYCLE_POINTS: u32 = 20;
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Параметры эвристик.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreeningOptions {
+    /// Порог отчётности в минимальных единицах суммы
+    pub threshold: i64,
+    /// Операция «чуть ниже порога», если она меньше порога не более чем на столько процентов
+    pub margin_percent: u8,
+    /// Окно, в котором ищутся операции чуть ниже порога, в миллисекундах
+    pub structuring_window_ms: u64,
+    /// Сколько операций чуть ниже порога в окне считается дроблением
+    pub structuring_min_count: usize,
+    /// Сколько может пройти от поступления до вывода, в миллисекундах
+    pub cycle_window_ms: u64,
+    /// Какая доля поступления (в процентах) должна быть выведена
+    pub cycle_percent: u8,
+}
+
+impl Default for ScreeningOptions {
+    fn default() -> Self {
+        ScreeningOptions {
+            threshold: 1_000_000,
+            margin_percent: 10,
+            structuring_window_ms: 7 * DAY_MS,
+            structuring_min_count: 3,
+            cycle_window_ms: DAY_MS,
+            cycle_percent: 90,
+        }
+    }
+}
+
+/// Шаблон подозрительной активности.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pattern {
+    /// Несколько операций чуть ниже порога за короткое окно
+    Structuring,
+    /// Поступление, почти целиком выведенное вскоре после зачисления
+    RapidCycle,
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Pattern::Structuring => "structuring",
+            Pattern::RapidCycle => "rapid-cycle",
+        })
+    }
+}
+
+/// Одна находка по пользователю.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    pub pattern: Pattern,
+    /// Баллы, которые находка добавила к оценке
+    pub points: u32,
+    /// Операции находки в порядке времени
+    pub tx_ids: Vec<TxId>,
+    /// Сумма операций дробления или сумма поступления в обороте
+    pub amount: i64,
+    pub first_timestamp: u64,
+    pub last_timestamp: u64,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids: Vec<String> = self.tx_ids.iter().map(TxId::to_string).collect();
+        write!(
+            f,
+            "{} (+{}): amount {} within {}s, TX_ID {}",
+            self.pattern,
+            self.points,
+            self.amount,
+            (self.last_timestamp - self.first_timestamp) / 1000,
+            ids.join(", ")
+        )
+    }
+}
+
+/// Оценка пользователя с находками.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserScore {
+    pub user_id: u64,
+    /// Сумма баллов находок, не больше [`MAX_SCORE`]
+    pub score: u32,
+    pub alerts: Vec<Alert>,
+}
+
+impl fmt::Display for UserScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "user {}: score {}", self.user_id, self.score)?;
+        for alert in &self.alerts {
+            write!(f, "\n  {}", alert)?;
+        }
+        Ok(())
+    }
+}
+
+/// Проверяет записи эвристиками и возвращает пользователей с находками
+/// по убыванию оценки (при равной оценке — по возрастанию ID).
+///
+/// Пользователь 0 — внешний счёт и не оценивается.
+pub fn screen(records: &[TxData], options: &ScreeningOptions) -> Vec<UserScore> {
+    let mut alerts: BTreeMap<u64, Vec<Alert>> = BTreeMap::new();
+    let mut live: Vec<&TxData> = records.iter().filter(|tx| tx.status != Status::Failure).collect();
+    live.sort_by_key(|tx| tx.timestamp);
+
+    find_structuring(&live, options, &mut alerts);
+    find_cycles(&live, options, &mut alerts);
+
+    let mut scores: Vec<UserScore> = alerts
+        .into_iter()
+        .map(|(user_id, alerts)| UserScore {
+            user_id,
+            score: alerts.iter().map(|alert| alert.points).sum::<u32>().min(MAX_SCORE),
+            alerts,
+        })
+        .collect();
+    // Сортировка устойчивая: при равной оценке остаётся порядок ID из BTreeMap
+    scores.sort_by_key(|user| std::cmp::Reverse(user.score));
+    scores
+}
+
+/// Пользователь, чей счёт пополняет операция.
+fn credited(tx: &TxData) -> Option<u64> {
+    match tx.tx_type {
+        TxType::Deposit | TxType::Transfer => Some(tx.to_user_id).filter(|&id| id != 0),
+        TxType::Withdrawal => None,
+    }
+}
+
+/// Пользователь, с чьего счёта уходят средства.
+fn debited(tx: &TxData) -> Option<u64> {
+    match tx.tx_type {
+        TxType::Withdrawal | TxType::Transfer => Some(tx.from_user_id).filter(|&id| id != 0),
+        TxType::Deposit => None,
+    }
+}
+
+/// Пользователь, который инициирует операцию: вносит, переводит или снимает.
+fn actor(tx: &TxData) -> Option<u64> {
+    match tx.tx_type {
+        TxType::Deposit => credited(tx),
+        TxType::Transfer | TxType::Withdrawal => debited(tx),
+    }
+}
+
+fn find_structuring(live: &[&TxData], options: &ScreeningOptions, alerts: &mut BTreeMap<u64, Vec<Alert>>) {
+    let margin = options.threshold / 100 * i64::from(options.margin_percent);
+    let floor = options.threshold - margin;
+    let mut near: BTreeMap<u64, Vec<&TxData>> = BTreeMap::new();
+    for tx in live {
+        if tx.amount >= floor && tx.amount < options.threshold {
+            if let Some(user_id) = actor(tx) {
+                near.entry(user_id).or_default().push(tx);
+            }
+        }
+    }
+
+    for (user_id, txs) in near {
+        // Самое плотное окно: больше всего операций за structuring_window_ms
+        let (mut best, mut start) = (0..0, 0);
+        for end in 0..txs.len() {
+            while txs[end].timestamp - txs[start].timestamp > options.structuring_window_ms {
+                start += 1;
+            }
+            if end + 1 - start > best.len() {
+                best = start..end + 1;
+            }
+        }
+        if best.len() < options.structuring_min_count.max(1) {
+            continue;
+        }
+        let window = &txs[best];
+        alerts.entry(user_id).or_default().push(Alert {
+            pattern: Pattern::Structuring,
+            points: STRUCTURING_POINTS * window.len() as u32,
+            tx_ids: window.iter().map(|tx| tx.tx_id.clone()).collect(),
+            amount: window.iter().map(|tx| tx.amount).fold(0, i64::saturating_add),
+            first_timestamp: window[0].timestamp,
+            last_timestamp: window[window.len() - 1].timestamp,
+        });
+    }
+}
+
+fn find_cycles(live: &[&TxData], options: &ScreeningOptions, alerts: &mut BTreeMap<u64, Vec<Alert>>) {
+    // Вывод засчитывается только одному поступлению
+    let mut used: HashSet<usize> = HashSet::new();
+    for (index, incoming) in live.iter().enumerate() {
+        let Some(user_id) = credited(incoming) else {
+            continue;
+        };
+        let required = incoming.amount.saturating_mul(i64::from(options.cycle_percent)) / 100;
+        let outgoing = live
+            .iter()
+            .enumerate()
+            .skip(index + 1)
+            .take_while(|(_, tx)| tx.timestamp - incoming.timestamp <= options.cycle_window_ms)
+            .find(|(at, tx)| debited(tx) == Some(user_id) && tx.amount >= required && !used.contains(at));
+        if let Some((at, outgoing)) = outgoing {
+            used.insert(at);
+            alerts.entry(user_id).or_default().push(Alert {
+                pattern: Pattern::RapidCycle,
+                points: CYCLE_POINTS,
+                tx_ids: vec![incoming.tx_id.clone(), outgoing.tx_id.clone()],
+                amount: incoming.amount,
+                first_timestamp: incoming.timestamp,
+                last_timestamp: outgoing.timestamp,
+            });
+        }
+    }
+}
+
+/// Записывает оценки [`screen`] в ресурс `resource`.
+///
+/// JSON — массив пользователей с находками, CSV — строка на каждую находку
+/// (`USER_ID,SCORE,PATTERN,POINTS,AMOUNT,FIRST_TIMESTAMP,LAST_TIMESTAMP,TX_IDS`,
+/// TX_ID через пробел). Возвращает размер записанных данных.
+pub fn write_screening_report(scores: &[UserScore], resource: Resource, format: ReportFormat) -> Result<usize, CommonErr> {
+    let content = match format {
+        ReportFormat::Json => render_json(scores),
+        ReportFormat::Csv => render_csv(scores),
+    };
+    write_report(&content, resource)
+}
+
+fn render_json(scores: &[UserScore]) -> String {
+    let users: Vec<serde_json::Value> = scores
+        .iter()
+        .map(|user| {
+            let alerts: Vec<serde_json::Value> = user
+                .alerts
+                .iter()
+                .map(|alert| {
+                    serde_json::json!({
+                        "pattern": alert.pattern.to_string(),
+                        "points": alert.points,
+                        "amount": alert.amount,
+                        "first_timestamp": alert.first_timestamp,
+                        "last_timestamp": alert.last_timestamp,
+                        "tx_ids": alert.tx_ids.iter().map(TxId::to_string).collect::<Vec<_>>(),
+                    })
+                })
+                .collect();
+            serde_json::json!({ "user_id": user.user_id, "score": user.score, "alerts": alerts })
+        })
+        .collect();
+    let mut content = serde_json::Value::Array(users).to_string();
+    content.push('\n');
+    content
+}
+
+fn render_csv(scores: &[UserScore]) -> String {
+    let mut content = String::from("USER_ID,SCORE,PATTERN,POINTS,AMOUNT,FIRST_TIMESTAMP,LAST_TIMESTAMP,TX_IDS\n");
+    for user in scores {
+        for alert in &user.alerts {
+            let ids: Vec<String> = alert.tx_ids.iter().map(TxId::to_string).collect();
+            content.push_str(&format!(
+                "{},{},{},{},{},{},{},{}\n",
+                user.user_id,
+                user.score,
+                alert.pattern,
+                alert.points,
+                alert.amount,
+                alert.first_timestamp,
+                alert.last_timestamp,
+                quote(&ids.join(" "))
+            ));
+        }
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::data::Format;
+
+    const HOUR_MS: u64 = 60 * 60 * 1000;
+
+    fn tx(id: u64, tx_type: TxType, from: u64, to: u64, amount: i64, hours: u64) -> TxData {
+        TxData {
+            tx_id: id.into(),
+            tx_type,
+            from_user_id: from,
+            to_user_id: to,
+            amount,
+            timestamp: 1700000000000 + hours * HOUR_MS,
+            status: Status::Success,
+            description: "screening".into(),
+            format: Format::YpBankCsv,
+        }
+    }
+
+    #[test]
+    fn test_structuring_and_cycles_are_scored() {
+        let mut records = vec![
+            // Пользователь 7 трижды вносит чуть меньше порога за двое суток
+            tx(1, TxType::Deposit, 0, 7, 990_000, 0),
+            tx(2, TxType::Deposit, 0, 7, 950_000, 20),
+            tx(3, TxType::Deposit, 0, 7, 999_999, 44),
+            // Пользователь 8 получает перевод и выводит почти всё через два часа
+            tx(4, TxType::Transfer, 9, 8, 500_000, 1),
+            tx(5, TxType::Withdrawal, 8, 0, 480_000, 3),
+            // Обычные операции: далеко от порога и медленный вывод
+            tx(6, TxType::Deposit, 0, 9, 1_000_000, 2),
+            tx(7, TxType::Withdrawal, 9, 0, 100, 50),
+        ];
+        let mut failed = tx(8, TxType::Deposit, 0, 9, 990_000, 4);
+        failed.status = Status::Failure;
+        records.push(failed);
+
+        let scores = screen(&records, &ScreeningOptions::default());
+        assert_eq!(scores.iter().map(|user| (user.user_id, user.score)).collect::<Vec<_>>(), vec![(7, 30), (8, 20)]);
+
+        let structuring = &scores[0].alerts[0];
+        assert_eq!(structuring.pattern, Pattern::Structuring);
+        assert_eq!(structuring.tx_ids, vec![TxId::U64(1), TxId::U64(2), TxId::U64(3)]);
+        let cycle = &scores[1].alerts[0];
+        assert_eq!(cycle.pattern, Pattern::RapidCycle);
+        assert_eq!((cycle.amount, cycle.last_timestamp - cycle.first_timestamp), (500_000, 2 * HOUR_MS));
+        assert!(scores[1].to_string().starts_with("user 8: score 20\n  rapid-cycle (+20)"));
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let records = [tx(1, TxType::Transfer, 0, 3, 200, 0), tx(2, TxType::Transfer, 3, 4, 200, 1)];
+        let csv = render_csv(&screen(&records, &ScreeningOptions::default()));
+        assert_eq!(
+            csv,
+            "USER_ID,SCORE,PATTERN,POINTS,AMOUNT,FIRST_TIMESTAMP,LAST_TIMESTAMP,TX_IDS\n\
+             3,20,rapid-cycle,20,200,1700000000000,1700003600000,\"1 2\"\n"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Преобразования записей сценарием на Rhai, чтобы разовые исправления данных не требовали
+//! отдельной программы на Rust.
+//!
+//! Сценарий выполняется для каждой записи. Запись доступна в нём как объект `tx` с полями
+//! `tx_id` (число или строка), `tx_type`, `status`, `description` (строки) и `from_user_id`,
+//! `to_user_id`, `amount`, `timestamp` (числа). Изменения полей `tx` попадают в результат,
+//! а сценарий, вернувший `false`, отбрасывает запись:
+//!
+//! ```text
+//! if tx.amount == 0 { return false; }
+//! tx.description = tx.description.to_upper();
+//! tx.timestamp += 3600000;
+//! ```
+//!
+//! Сценарий не может читать файлы и ходить в сеть, а число его шагов на запись ограничено.
+//! Запись, на которой сценарий упал или оставил поле неверного типа, отбрасывается и
+//! учитывается в [`TransformScript::failures`].
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use thiserror::Error;
+
+use crate::model::data::{Status, TxData, TxType};
+use crate::model::tx_id::TxId;
+use crate::parser::Transforms;
+
+/// Сколько шагов сценарий может сделать на одной записи: защита от зацикливания.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+#[derive(Error, Debug)]
+pub enum ScriptErr {
+    /// Файл не удалось прочитать
+    #[error("failed to read transform script {}: {err}", path.display())]
+    Io { path: PathBuf, err: std::io::Error },
+    /// Сценарий не компилируется
+    #[error("transform script is not valid: {reason}")]
+    Invalid { reason: String },
+}
+
+/// Сценарий преобразования записей (см. описание модуля).
+#[derive(Clone)]
+pub struct TransformScript {
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+    failed: Arc<AtomicUsize>,
+    first_failure: Arc<OnceLock<String>>,
+}
+
+impl TransformScript {
+    /// Читает сценарий из файла `path`.
+    pub fn load(path: &Path) -> Result<TransformScript, ScriptErr> {
+        let source = fs::read_to_string(path).map_err(|err| ScriptErr::Io { path: path.to_path_buf(), err })?;
+        TransformScript::parse(&source)
+    }
+
+    /// Компилирует сценарий из текста.
+    pub fn parse(source: &str) -> Result<TransformScript, ScriptErr> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let ast = engine.compile(source).map_err(|e| ScriptErr::Invalid { reason: e.to_string() })?;
+        Ok(TransformScript {
+            engine: Arc::new(engine),
+            ast: Arc::new(ast),
+            failed: Arc::default(),
+            first_failure: Arc::default(),
+        })
+    }
+
+    /// Преобразованная запись; `None`, если сценарий её отбросил или упал на ней.
+    pub fn apply(&self, tx: TxData) -> Option<TxData> {
+        match self.run(tx) {
+            Ok(tx) => tx,
+            Err(reason) => {
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                self.first_failure.get_or_init(|| reason);
+                None
+            }
+        }
+    }
+
+    /// Сколько записей отброшено из-за ошибок сценария и первая из ошибок.
+    pub fn failures(&self) -> (usize, Option<&str>) {
+        (self.failed.load(Ordering::Relaxed), self.first_failure.get().map(String::as_str))
+    }
+
+    /// Добавляет сценарий в конец цепочки `transforms`.
+    pub fn install(&self, transforms: Transforms) -> Transforms {
+        let script = self.clone();
+        transforms.then(move |tx| script.apply(tx))
+    }
+
+    fn run(&self, tx: TxData) -> Result<Option<TxData>, String> {
+        let failed = |reason: String| format!("TX_ID {}: {}", tx.tx_id, reason);
+        let mut scope = Scope::new();
+        scope.push("tx", to_map(&tx));
+        let result = self
+            .engine
+            .eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast)
+            .map_err(|e| failed(e.to_string()))?;
+        if result.as_bool() == Ok(false) {
+            return Ok(None);
+        }
+        let map = scope.get_value::<Map>("tx").ok_or_else(|| failed("tx is no longer an object".to_string()))?;
+        from_map(map, tx.clone()).map(Some).map_err(failed)
+    }
+}
+
+fn to_map(tx: &TxData) -> Map {
+    let mut map = Map::new();
+    let tx_id = match tx.tx_id.as_u64().and_then(|id| i64::try_from(id).ok()) {
+        Some(id) => Dynamic::from(id),
+        None => Dynamic::from(tx.tx_id.to_string()),
+    };
+    map.insert("tx_id".into(), tx_id);
+    map.insert("tx_type".into(), Dynamic::from(tx.tx_type.name().to_string()));
+    map.insert("from_user_id".into(), Dynamic::from(tx.from_user_id as i64));
+    map.insert("to_user_id".into(), Dynamic::from(tx.to_user_id as i64));
+    map.insert("amount".into(), Dynamic::from(tx.amount));
+    map.insert("timestamp".into(), Dynamic::from(tx.timestamp as i64));
+    map.insert("status".into(), Dynamic::from(tx.status.name().to_string()));
+    map.insert("description".into(), Dynamic::from(tx.description.to_string()));
+    map
+}
+
+/// Переносит поля объекта `tx` из сценария в запись `tx`.
+fn from_map(map: Map, mut tx: TxData) -> Result<TxData, String> {
+    let field = |name: &str| map.get(name).cloned().ok_or_else(|| format!("{} was removed", name));
+    let int = |name: &str| field(name)?.as_int().map_err(|actual| format!("{} must be a number, not {}", name, actual));
+    let string = |name: &str| {
+        let value = field(name)?;
+        value.clone().into_string().map_err(|actual| format!("{} must be a string, not {}", name, actual))
+    };
+    let unsigned = |name: &str| u64::try_from(int(name)?).map_err(|_| format!("{} must not be negative", name));
+
+    let tx_id = field("tx_id")?;
+    tx.tx_id = match tx_id.as_int() {
+        Ok(id) => TxId::U64(u64::try_from(id).map_err(|_| "tx_id must not be negative".to_string())?),
+        Err(_) => TxId::parse(&string("tx_id")?).map_err(|e| e.to_string())?,
+    };
+    let tx_type = string("tx_type")?;
+    tx.tx_type = TxType::ALL
+        .into_iter()
+        .find(|t| t.name() == tx_type)
+        .ok_or_else(|| format!("unknown tx_type {}", tx_type))?;
+    let status = string("status")?;
+    tx.status = Status::ALL
+        .into_iter()
+        .find(|s| s.name() == status)
+        .ok_or_else(|| format!("unknown status {}", status))?;
+    tx.from_user_id = unsigned("from_user_id")?;
+    tx.to_user_id = unsigned("to_user_id")?;
+    tx.amount = int("amount")?;
+    tx.timestamp = unsigned("timestamp")?;
+    tx.description = string("description")?.into();
+    Ok(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::data::Format;
+
+    fn tx(id: u64, amount: i64, description: &str) -> TxData {
+        TxData {
+            tx_id: id.into(),
+            tx_type: TxType::Transfer,
+            from_user_id: 10,
+            to_user_id: 11,
+            amount,
+            timestamp: 1700000000000,
+            status: Status::Pending,
+            description: description.into(),
+            format: Format::YpBankCsv,
+        }
+    }
+
+    #[test]
+    fn test_script_drops_and_rewrites_records() {
+        let script = TransformScript::parse(
+            "if tx.amount == 0 { return false; }\n\
+             tx.description = tx.description.to_upper();\n\
+             tx.timestamp += 3600000;\n\
+             tx.status = \"SUCCESS\";\n",
+        )
+        .unwrap();
+        let transforms = script.install(Transforms::default());
+
+        assert_eq!(transforms.apply(tx(1, 0, "zero")), None);
+        let rewritten = transforms.apply(tx(2, 100, "fix me")).unwrap();
+        assert_eq!(
+            (&*rewritten.description, rewritten.timestamp, rewritten.status, rewritten.tx_id),
+            ("FIX ME", 1700003600000, Status::Success, TxId::U64(2))
+        );
+        assert_eq!(script.failures(), (0, None));
+
+        // Запись с полем неверного типа отбрасывается и учитывается
+        let script = TransformScript::parse("if tx.tx_id == 3 { tx.amount = \"many\"; }").unwrap();
+        assert!(script.apply(tx(2, 100, "ok")).is_some());
+        assert_eq!(script.apply(tx(3, 100, "bad")), None);
+        assert_eq!(script.failures(), (1, Some("TX_ID 3: amount must be a number, not string")));
+
+        assert!(matches!(TransformScript::parse("tx.amount = "), Err(ScriptErr::Invalid { .. })));
+        let endless = TransformScript::parse("loop { }").unwrap();
+        assert_eq!(endless.apply(tx(4, 100, "loop")), None);
+        assert_eq!(endless.failures().0, 1);
+    }
+}
@@ -0,0 +1,564 @@
+//! Сортировка и удаление повторов TX_ID для входов больше памяти (внешняя сортировка).
+//!
+//! Вход читается участками по [`SpillOptions::run_records`] записей; каждый участок
+//! сортируется в памяти, а если весь вход в один участок не поместился — сбрасывается
+//! во временный бинарный файл в [`SpillOptions::temp_dir`]. Затем участки сливаются
+//! потоком; больше [`MAX_MERGE_RUNS`] участков сначала сливаются между собой в новые.
+//! Записи с равным ключом сохраняют порядок входа. Временные файлы удаляются вместе
+//! с [`SortedRecords`].
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+
+use crate::model::data::{Format, TxData};
+use crate::model::errors::{CommonErr, IoErr, ParseIssue};
+use crate::model::resource::Resource;
+use crate::model::tx_id::TxId;
+use crate::parser::{read_batches, write_batches, BatchReader, MemoryBudget, ParseOptions, WriteOptions};
+
+/// Сколько участков сливается за один проход.
+pub const MAX_MERGE_RUNS: usize = 64;
+
+/// По какому полю упорядочивать записи; записи с равным полем остаются в порядке входа.
+#[cfg_attr(feature = "console", derive(clap::ValueEnum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    #[default]
+    TxId,
+    Timestamp,
+}
+
+impl SortKey {
+    /// Сравнивает записи по этому полю.
+    pub fn compare(self, a: &TxData, b: &TxData) -> Ordering {
+        match self {
+            SortKey::TxId => a.tx_id.cmp(&b.tx_id),
+            SortKey::Timestamp => a.timestamp.cmp(&b.timestamp),
+        }
+    }
+}
+
+/// Настройки внешней сортировки.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpillOptions {
+    /// Сколько записей сортируется в памяти, прежде чем участок сбрасывается во временный файл
+    pub run_records: usize,
+    /// Каталог временных файлов; `None` — системный (`std::env::temp_dir`)
+    pub temp_dir: Option<PathBuf>,
+}
+
+impl Default for SpillOptions {
+    fn default() -> Self {
+        SpillOptions {
+            run_records: MemoryBudget::BATCH_SIZE_RANGE.1,
+            temp_dir: None,
+        }
+    }
+}
+
+impl SpillOptions {
+    /// Уменьшает участок до бюджета памяти `budget`.
+    pub fn fit_memory(&mut self, budget: MemoryBudget) {
+        self.run_records = self.run_records.min(budget.batch_size(1));
+    }
+
+    /// Сколько записей читается и записывается за раз: при слиянии в памяти
+    /// одновременно по столько записей из каждого участка.
+    fn io_batch(&self) -> usize {
+        (self.run_records / MAX_MERGE_RUNS).max(1)
+    }
+}
+
+/// Настройки [`sort_file`].
+#[derive(Debug, Clone, Default)]
+pub struct SortOptions {
+    /// Поле, по которому упорядочивается результат
+    pub key: SortKey,
+    /// Оставить из записей с одним TX_ID только первую во входе
+    pub dedupe: bool,
+    /// Настройки разбора входа
+    pub parse: ParseOptions,
+    /// Настройки записи результата
+    pub write: WriteOptions,
+    pub spill: SpillOptions,
+}
+
+/// Итог [`sort_file`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortReport {
+    /// Сколько записей принято при разборе
+    pub records_read: usize,
+    pub records_written: usize,
+    /// Сколько записей пропущено из-за ошибок разбора (только с `ErrorMode::Collect`)
+    pub skipped: usize,
+    pub warnings: usize,
+    /// Сколько повторов TX_ID убрано (только с `SortOptions::dedupe`)
+    pub duplicates: usize,
+    /// Сколько участков сброшено во временные файлы; 0 — вход отсортирован в памяти
+    pub runs: usize,
+    /// Размер результата в байтах
+    pub bytes_out: usize,
+    pub elapsed: Duration,
+}
+
+/// Читает вход `from` и упорядочивает его записи по `key` (см. описание модуля).
+///
+/// # Errors
+/// `CommonErr`, если вход не удалось прочитать или разобрать либо временный файл не удалось записать.
+pub fn sort_input(
+    from: Resource,
+    format: Format,
+    key: SortKey,
+    parse: &ParseOptions,
+    options: &SpillOptions,
+) -> Result<SortedRecords, CommonErr> {
+    let mut runs = Runs::new(key, options);
+    let (mut issues, mut warnings) = (Vec::new(), 0);
+    for batch in read_batches(from, format, parse, options.io_batch())? {
+        let batch = batch?;
+        issues.extend(batch.issues);
+        warnings += batch.warnings.len();
+        for tx in batch.records {
+            runs.push(tx)?;
+        }
+    }
+    let mut sorted = runs.finish(format)?;
+    sorted.issues = issues;
+    sorted.warnings = warnings;
+    Ok(sorted)
+}
+
+/// Упорядочивает поток записей `records` по `key`, как [`sort_input`].
+///
+/// # Errors
+/// Первая ошибка потока или `CommonErr::IO`, если временный файл не удалось записать.
+pub fn sort_records(
+    records: impl IntoIterator<Item = Result<TxData, CommonErr>>,
+    format: Format,
+    key: SortKey,
+    options: &SpillOptions,
+) -> Result<SortedRecords, CommonErr> {
+    let mut runs = Runs::new(key, options);
+    for tx in records {
+        runs.push(tx?)?;
+    }
+    runs.finish(format)
+}
+
+/// Сортирует вход `from` и записывает результат в `to`.
+///
+/// С `options.dedupe` вход сначала сортируется по TX_ID, из повторов остаётся первая
+/// запись, а для другого `options.key` результат сортируется ещё раз.
+///
+/// # Errors
+/// `CommonErr`, если вход не удалось прочитать, а результат или временный файл — записать.
+pub fn sort_file(
+    from: Resource,
+    from_format: Format,
+    to: Resource,
+    to_format: Format,
+    options: &SortOptions,
+) -> Result<SortReport, CommonErr> {
+    let started = Instant::now();
+    let first_key = if options.dedupe { SortKey::TxId } else { options.key };
+    let sorted = sort_input(from, from_format, first_key, &options.parse, &options.spill)?;
+    let (records_read, skipped, warnings, mut runs) =
+        (sorted.records, sorted.issues.len(), sorted.warnings, sorted.runs);
+
+    let mut duplicates = 0;
+    let mut last: Option<TxId> = None;
+    let unique = sorted.filter(|tx| match tx {
+        Ok(tx) if options.dedupe && last.as_ref() == Some(&tx.tx_id) => {
+            duplicates += 1;
+            false
+        },
+        Ok(tx) => {
+            last = Some(tx.tx_id.clone());
+            true
+        },
+        Err(_) => true,
+    });
+    let output: Box<dyn Iterator<Item = Result<TxData, CommonErr>> + '_> = if first_key == options.key {
+        Box::new(unique)
+    } else {
+        let resorted = sort_records(unique, from_format, options.key, &options.spill)?;
+        runs += resorted.runs;
+        Box::new(resorted)
+    };
+
+    let mut writer = write_batches(to, to_format, &options.write)?;
+    let mut batch = Vec::new();
+    for tx in output {
+        batch.push(tx?);
+        if batch.len() >= options.spill.io_batch() {
+            writer.write_batch(&batch, &[])?;
+            batch.clear();
+        }
+    }
+    writer.write_batch(&batch, &[])?;
+    let records_written = writer.checkpoint().records as usize;
+    let bytes_out = writer.finish()?;
+
+    Ok(SortReport {
+        records_read,
+        records_written,
+        skipped,
+        warnings,
+        duplicates,
+        runs,
+        bytes_out,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Упорядоченные записи, которые выдают [`sort_input`] и [`sort_records`].
+///
+/// Записи выдаются с форматом входа; временные файлы удаляются при освобождении.
+pub struct SortedRecords {
+    source: Source,
+    format: Format,
+    records: usize,
+    runs: usize,
+    issues: Vec<ParseIssue>,
+    warnings: usize,
+    _dir: Option<TempDir>,
+}
+
+impl SortedRecords {
+    /// Сколько всего записей.
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    /// Сколько участков сброшено во временные файлы; 0 — записи отсортированы в памяти.
+    pub fn spilled_runs(&self) -> usize {
+        self.runs
+    }
+
+    /// Пропущенные при разборе записи (только с `ErrorMode::Collect`).
+    pub fn issues(&self) -> &[ParseIssue] {
+        &self.issues
+    }
+
+    /// Сколько предупреждений выдал разбор.
+    pub fn warnings(&self) -> usize {
+        self.warnings
+    }
+}
+
+impl Iterator for SortedRecords {
+    type Item = Result<TxData, CommonErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tx = match &mut self.source {
+            Source::Memory(records) => Ok(records.next()?),
+            Source::Merge(merge) => merge.next()?,
+        };
+        Some(tx.map(|tx| TxData { format: self.format, ..tx }))
+    }
+}
+
+enum Source {
+    Memory(std::vec::IntoIter<TxData>),
+    Merge(Merge),
+}
+
+/// Участки, набираемые из потока записей.
+struct Runs<'a> {
+    key: SortKey,
+    options: &'a SpillOptions,
+    current: Vec<TxData>,
+    records: usize,
+    files: Vec<PathBuf>,
+    /// Сколько временных файлов создано: по номеру даётся имя следующему
+    created: usize,
+    /// Создаётся при первом сбросе
+    dir: Option<TempDir>,
+}
+
+impl<'a> Runs<'a> {
+    fn new(key: SortKey, options: &'a SpillOptions) -> Self {
+        Runs {
+            key,
+            options,
+            current: Vec::new(),
+            records: 0,
+            files: Vec::new(),
+            created: 0,
+            dir: None,
+        }
+    }
+
+    fn push(&mut self, tx: TxData) -> Result<(), CommonErr> {
+        self.current.push(tx);
+        self.records += 1;
+        if self.current.len() >= self.options.run_records.max(1) {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Сортирует текущий участок и записывает его в новый временный файл.
+    fn spill(&mut self) -> Result<(), CommonErr> {
+        let key = self.key;
+        self.current.sort_by(|a, b| key.compare(a, b));
+        let path = self.next_path()?;
+        let mut writer = write_batches(Resource::File { path: path.clone() }, Format::YpBankBin, &run_write_options())?;
+        writer.write_batch(&self.current, &[])?;
+        writer.finish()?;
+        self.current.clear();
+        self.files.push(path);
+        Ok(())
+    }
+
+    fn next_path(&mut self) -> Result<PathBuf, CommonErr> {
+        let dir = match &mut self.dir {
+            Some(dir) => dir,
+            empty => {
+                let parent = self.options.temp_dir.clone().unwrap_or_else(std::env::temp_dir);
+                let dir = tempfile::Builder::new()
+                    .prefix("ypbank-spill-")
+                    .tempdir_in(&parent)
+                    .map_err(|e| IoErr::output(e, Some(parent)))?;
+                empty.insert(dir)
+            },
+        };
+        self.created += 1;
+        Ok(dir.path().join(format!("run-{}.bin", self.created)))
+    }
+
+    fn finish(mut self, format: Format) -> Result<SortedRecords, CommonErr> {
+        let key = self.key;
+        if self.files.is_empty() {
+            self.current.sort_by(|a, b| key.compare(a, b));
+        } else if !self.current.is_empty() {
+            self.spill()?;
+        }
+        let runs = self.files.len();
+        let source = if runs == 0 {
+            Source::Memory(std::mem::take(&mut self.current).into_iter())
+        } else {
+            // Слишком много участков сначала сливаются по MAX_MERGE_RUNS подряд идущих,
+            // чтобы не держать открытыми сотни файлов; порядок участков сохраняется
+            let batch = self.options.io_batch();
+            while self.files.len() > MAX_MERGE_RUNS {
+                let merged: Vec<PathBuf> = self.files.drain(..MAX_MERGE_RUNS).collect();
+                let path = self.next_path()?;
+                merge_into(&merged, key, batch, &path)?;
+                self.files.insert(0, path);
+            }
+            Source::Merge(Merge::open(&self.files, key, batch)?)
+        };
+        Ok(SortedRecords {
+            source,
+            format,
+            records: self.records,
+            runs,
+            issues: Vec::new(),
+            warnings: 0,
+            _dir: self.dir,
+        })
+    }
+}
+
+/// Заголовок обязателен: без него бинарный формат хранит только числовые TX_ID.
+fn run_write_options() -> WriteOptions {
+    WriteOptions { bin_header: true, ..WriteOptions::default() }
+}
+
+/// Сливает участки `runs` в новый участок `to` и удаляет их.
+fn merge_into(runs: &[PathBuf], key: SortKey, batch: usize, to: &Path) -> Result<(), CommonErr> {
+    let mut writer = write_batches(Resource::File { path: to.to_path_buf() }, Format::YpBankBin, &run_write_options())?;
+    let mut records = Vec::with_capacity(batch);
+    for tx in Merge::open(runs, key, batch)? {
+        records.push(tx?);
+        if records.len() >= batch {
+            writer.write_batch(&records, &[])?;
+            records.clear();
+        }
+    }
+    writer.write_batch(&records, &[])?;
+    writer.finish()?;
+    for run in runs {
+        fs::remove_file(run).map_err(|e| IoErr::output(e, Some(run.clone())))?;
+    }
+    Ok(())
+}
+
+/// Записи файла по одной, хотя читаются порциями.
+pub(crate) struct RecordStream {
+    batches: BatchReader,
+    buffered: std::vec::IntoIter<TxData>,
+}
+
+impl RecordStream {
+    pub(crate) fn new(batches: BatchReader) -> Self {
+        RecordStream { batches, buffered: Vec::new().into_iter() }
+    }
+}
+
+impl Iterator for RecordStream {
+    type Item = Result<TxData, CommonErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(tx) = self.buffered.next() {
+                return Some(Ok(tx));
+            }
+            match self.batches.next()? {
+                Ok(batch) => self.buffered = batch.records.into_iter(),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Очередная запись участка `run` в куче слияния.
+struct Head {
+    tx: TxData,
+    run: usize,
+    key: SortKey,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // При равных ключах первой идёт запись из более раннего участка: сортировка устойчива
+        self.key.compare(&self.tx, &other.tx).then(self.run.cmp(&other.run))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+/// Слияние отсортированных участков.
+struct Merge {
+    runs: Vec<RecordStream>,
+    heap: BinaryHeap<Reverse<Head>>,
+    key: SortKey,
+}
+
+impl Merge {
+    fn open(files: &[PathBuf], key: SortKey, batch: usize) -> Result<Self, CommonErr> {
+        let mut merge = Merge { runs: Vec::new(), heap: BinaryHeap::new(), key };
+        for (run, path) in files.iter().enumerate() {
+            let batches = read_batches(Resource::File { path: path.clone() }, Format::YpBankBin, &ParseOptions::default(), batch)?;
+            merge.runs.push(RecordStream::new(batches));
+            merge.advance(run)?;
+        }
+        Ok(merge)
+    }
+
+    /// Кладёт в кучу следующую запись участка `run`, если она есть.
+    fn advance(&mut self, run: usize) -> Result<(), CommonErr> {
+        if let Some(tx) = self.runs[run].next() {
+            self.heap.push(Reverse(Head { tx: tx?, run, key: self.key }));
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Merge {
+    type Item = Result<TxData, CommonErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse(head) = self.heap.pop()?;
+        Some(self.advance(head.run).map(|_| head.tx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::data::{Status, TxType};
+
+    fn tx(id: u64, timestamp: u64, description: &str) -> TxData {
+        TxData {
+            tx_id: TxId::U64(id),
+            tx_type: TxType::Deposit,
+            from_user_id: 0,
+            to_user_id: 10,
+            amount: 100,
+            timestamp,
+            status: Status::Success,
+            description: description.into(),
+            format: Format::YpBankCsv,
+        }
+    }
+
+    #[test]
+    fn test_spilled_sort_is_stable_and_cleans_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = SpillOptions { run_records: 1, temp_dir: Some(dir.path().to_path_buf()) };
+        // 100 участков по одной записи: сначала сливаются первые 64, потом остальные
+        let records: Vec<TxData> = (0..100).map(|i| tx(i % 7, 100 - i, &i.to_string())).collect();
+
+        let sorted = sort_records(records.iter().cloned().map(Ok), Format::YpBankCsv, SortKey::TxId, &options).unwrap();
+        assert_eq!((sorted.records(), sorted.spilled_runs()), (100, 100));
+        let sorted: Vec<TxData> = sorted.collect::<Result<_, _>>().unwrap();
+
+        let mut expected = records.clone();
+        expected.sort_by(|a, b| a.tx_id.cmp(&b.tx_id));
+        assert_eq!(sorted, expected);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let in_memory = sort_records(records.into_iter().map(Ok), Format::YpBankCsv, SortKey::Timestamp, &SpillOptions::default());
+        let Ok(in_memory) = in_memory else { panic!() };
+        assert_eq!(in_memory.spilled_runs(), 0);
+        assert!(in_memory.map(Result::unwrap).map(|tx| tx.timestamp).eq(1..=100));
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn test_sort_file_dedupes_first_occurrence() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("in.csv");
+        fs::write(
+            &from,
+            "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+             3,DEPOSIT,0,10,100,30,SUCCESS,\"a\"\n\
+             1,DEPOSIT,0,10,100,50,SUCCESS,\"b\"\n\
+             3,DEPOSIT,0,10,100,10,SUCCESS,\"c\"\n\
+             2,DEPOSIT,0,10,100,20,SUCCESS,\"d\"\n",
+        )
+        .unwrap();
+        let to = dir.path().join("out.csv");
+        let options = SortOptions {
+            key: SortKey::Timestamp,
+            dedupe: true,
+            spill: SpillOptions { run_records: 2, temp_dir: Some(dir.path().to_path_buf()) },
+            ..SortOptions::default()
+        };
+
+        let report = sort_file(
+            Resource::File { path: from },
+            Format::YpBankCsv,
+            Resource::File { path: to.clone() },
+            Format::YpBankCsv,
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!((report.records_read, report.records_written, report.duplicates), (4, 3, 1));
+        assert_eq!(report.runs, 4);
+        let written = fs::read_to_string(&to).unwrap();
+        let descriptions: Vec<&str> = written.lines().skip(1).map(|line| line.rsplit(',').next().unwrap()).collect();
+        assert_eq!(descriptions, ["d", "a", "b"]);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
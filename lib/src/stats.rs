@@ -0,0 +1,554 @@
+//! Сводная статистика по файлу транзакций: крупнейшие отправители, получатели и записи,
+//! число и сумма записей по часам или дням, гистограмма сумм.
+//!
+//! Файл читается порциями, а для каждого отчёта хранится не больше N кандидатов
+//! в куче, поэтому память не зависит от числа записей; итоги по пользователям
+//! занимают память пропорционально числу разных пользователей, ряд по времени —
+//! числу интервалов.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::fmt::{self, Write as _};
+
+use crate::model::amount::AmountTotal;
+use crate::model::data::{Format, Status, TxData, TxType};
+use crate::model::errors::CommonErr;
+use crate::model::resource::Resource;
+use crate::model::time::format_timestamp;
+use crate::parser::io::reader::read_batches;
+use crate::parser::options::ParseOptions;
+
+/// Пустые интервалы ряда дописываются, только если всего интервалов не больше этого:
+/// одна метка `0` в файле за 2024 год иначе дала бы полмиллиона пустых часов.
+pub const MAX_FILLED_BUCKETS: u64 = 10_000;
+
+/// Символы спарклайна от меньшего числа записей к большему.
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Символ спарклайна для интервала без записей.
+const EMPTY_SPARK: char = '·';
+
+/// Ширина самого длинного столбца гистограммы в символах.
+const BAR_WIDTH: usize = 40;
+
+/// Длина интервала ряда по времени.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "console", derive(clap::ValueEnum))]
+pub enum Bucket {
+    Hour,
+    Day,
+}
+
+impl Bucket {
+    /// Длина интервала в миллисекундах.
+    pub fn ms(self) -> u64 {
+        match self {
+            Bucket::Hour => 60 * 60 * 1000,
+            Bucket::Day => 24 * 60 * 60 * 1000,
+        }
+    }
+}
+
+impl fmt::Display for Bucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Bucket::Hour => "hour",
+            Bucket::Day => "day",
+        })
+    }
+}
+
+/// Какие отчёты строить; 0, `None` и `false` — отчёт не нужен.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatsOptions {
+    /// Сколько пользователей с наибольшей суммой исходящих операций
+    pub top_senders: usize,
+    /// Сколько пользователей с наибольшей суммой входящих операций
+    pub top_receivers: usize,
+    /// Сколько записей с наибольшим AMOUNT
+    pub largest: usize,
+    /// Число и сумма записей по интервалам времени
+    pub series: Option<Bucket>,
+    /// Гистограмма AMOUNT по десятичным порядкам
+    pub histogram: bool,
+}
+
+/// Итог по одному пользователю.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserTotal {
+    pub user_id: u64,
+    pub total: AmountTotal,
+    /// Сколько операций вошло в итог
+    pub count: usize,
+}
+
+/// Интервал ряда по времени.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeriesBucket {
+    /// Начало интервала, мс Unix
+    pub start: u64,
+    pub count: usize,
+    pub total: AmountTotal,
+}
+
+/// Столбец гистограммы: записи с `low <= AMOUNT < high`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistogramBin {
+    pub low: i64,
+    /// `None` — без верхней границы
+    pub high: Option<i64>,
+    pub count: usize,
+}
+
+/// Результат [`StatsCollector::finish`]; списки крупнейших — по убыванию суммы,
+/// ряд и гистограмма — по возрастанию.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatsReport {
+    /// Сколько записей просмотрено
+    pub records: usize,
+    pub top_senders: Vec<UserTotal>,
+    pub top_receivers: Vec<UserTotal>,
+    /// При равном AMOUNT раньше идёт запись, встретившаяся раньше
+    pub largest: Vec<TxData>,
+    /// Длина интервалов [`series`](Self::series)
+    pub bucket: Option<Bucket>,
+    /// Интервалы от первого до последнего непустого; пустые между ними дописаны
+    /// (если их не больше [`MAX_FILLED_BUCKETS`])
+    pub series: Vec<SeriesBucket>,
+    /// Столбцы от меньших сумм к большим, включая пустые между ними
+    pub histogram: Vec<HistogramBin>,
+}
+
+/// Вид вывода отчётов.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "console", derive(clap::ValueEnum))]
+pub enum StatsFormat {
+    /// Для терминала: ряд по времени — спарклайн, гистограмма — столбцы
+    #[default]
+    Text,
+    /// Таблицы CSV с заголовками, разделённые пустой строкой
+    Csv,
+}
+
+impl StatsReport {
+    /// Отчёты в виде `format`.
+    pub fn render(&self, format: StatsFormat) -> String {
+        match format {
+            StatsFormat::Text => self.to_string(),
+            StatsFormat::Csv => self.to_csv(),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        let mut tables = Vec::new();
+        for (header, users) in [("SENDER_ID", &self.top_senders), ("RECEIVER_ID", &self.top_receivers)] {
+            if !users.is_empty() {
+                let mut table = format!("{},TOTAL,COUNT\n", header);
+                for user in users {
+                    let _ = writeln!(table, "{},{},{}", user.user_id, user.total.get(), user.count);
+                }
+                tables.push(table);
+            }
+        }
+        if !self.largest.is_empty() {
+            let mut table = String::from("TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS\n");
+            for tx in &self.largest {
+                let _ = writeln!(
+                    table,
+                    "\"{}\",{},{},{},{},{},{}",
+                    tx.tx_id.to_string().replace('"', "\"\""),
+                    tx.tx_type.name(),
+                    tx.from_user_id,
+                    tx.to_user_id,
+                    tx.amount,
+                    tx.timestamp,
+                    tx.status.name()
+                );
+            }
+            tables.push(table);
+        }
+        if !self.series.is_empty() {
+            let mut table = String::from("BUCKET_START,COUNT,AMOUNT\n");
+            for bucket in &self.series {
+                let _ = writeln!(table, "{},{},{}", format_timestamp(bucket.start), bucket.count, bucket.total.get());
+            }
+            tables.push(table);
+        }
+        if !self.histogram.is_empty() {
+            let mut table = String::from("AMOUNT_FROM,AMOUNT_TO,COUNT\n");
+            for bin in &self.histogram {
+                let high = bin.high.map(|high| high.to_string()).unwrap_or_default();
+                let _ = writeln!(table, "{},{},{}", bin.low, high, bin.count);
+            }
+            tables.push(table);
+        }
+        tables.join("\n")
+    }
+}
+
+impl fmt::Display for StatsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "records: {}", self.records)?;
+        for (title, users) in [("top senders", &self.top_senders), ("top receivers", &self.top_receivers)] {
+            if !users.is_empty() {
+                write!(f, "\n{}:", title)?;
+            }
+            for (place, user) in users.iter().enumerate() {
+                write!(f, "\n  {}. user {}: {} in {} records", place + 1, user.user_id, user.total.get(), user.count)?;
+            }
+        }
+        if !self.largest.is_empty() {
+            write!(f, "\nlargest records:")?;
+        }
+        for (place, tx) in self.largest.iter().enumerate() {
+            write!(
+                f,
+                "\n  {}. TX_ID {}: {} {} ({} -> {})",
+                place + 1,
+                tx.tx_id,
+                tx.tx_type.name(),
+                tx.amount,
+                tx.from_user_id,
+                tx.to_user_id
+            )?;
+        }
+        if let (Some(bucket), Some(first), Some(last)) = (self.bucket, self.series.first(), self.series.last()) {
+            let empty = self.series.iter().filter(|bucket| bucket.count == 0).count();
+            let max = self.series.iter().map(|bucket| bucket.count).max().unwrap_or(0);
+            write!(
+                f,
+                "\nrecords per {}, {} .. {} ({} buckets, {} empty, max {}):\n  {}",
+                bucket,
+                format_timestamp(first.start),
+                format_timestamp(last.start),
+                self.series.len(),
+                empty,
+                max,
+                sparkline(&self.series)
+            )?;
+        }
+        if !self.histogram.is_empty() {
+            write!(f, "\namount histogram:")?;
+        }
+        let max = self.histogram.iter().map(|bin| bin.count).max().unwrap_or(0);
+        for bin in &self.histogram {
+            let range = match bin.high {
+                Some(high) => format!("[{}, {})", bin.low, high),
+                None => format!("[{}, ...)", bin.low),
+            };
+            let bar = "█".repeat((bin.count * BAR_WIDTH).div_ceil(max.max(1)));
+            write!(f, "\n  {:>24} {:>8} {}", range, bin.count, bar)?;
+        }
+        Ok(())
+    }
+}
+
+/// Спарклайн числа записей по интервалам; пустые интервалы — [`EMPTY_SPARK`].
+fn sparkline(series: &[SeriesBucket]) -> String {
+    let max = series.iter().map(|bucket| bucket.count).max().unwrap_or(0).max(1);
+    series
+        .iter()
+        .map(|bucket| match bucket.count {
+            0 => EMPTY_SPARK,
+            count => SPARKS[(count * SPARKS.len()).div_ceil(max) - 1],
+        })
+        .collect()
+}
+
+/// Столбец гистограммы для суммы: отрицательные суммы, ноль, затем `[10^k, 10^(k+1))`.
+fn histogram_bin(amount: i64) -> (i64, Option<i64>) {
+    match amount {
+        i64::MIN..=-1 => (i64::MIN, Some(0)),
+        0 => (0, Some(1)),
+        _ => {
+            let low = 10_i64.pow(amount.ilog10());
+            (low, low.checked_mul(10))
+        },
+    }
+}
+
+/// Куча, хранящая не больше `n` наибольших элементов.
+struct Top<T: Ord> {
+    n: usize,
+    heap: BinaryHeap<Reverse<T>>,
+}
+
+impl<T: Ord> Top<T> {
+    fn new(n: usize) -> Self {
+        Top { n, heap: BinaryHeap::with_capacity(n.saturating_add(1).min(1024)) }
+    }
+
+    /// Попадёт ли в кучу новый элемент; `beats_min` сравнивает его с наименьшим элементом
+    /// заполненной кучи, так что сам элемент можно не строить.
+    fn admits(&self, beats_min: impl FnOnce(&T) -> bool) -> bool {
+        match self.heap.peek() {
+            _ if self.n == 0 => false,
+            Some(Reverse(min)) if self.heap.len() == self.n => beats_min(min),
+            _ => true,
+        }
+    }
+
+    fn push(&mut self, item: T) {
+        if self.n == 0 {
+            return;
+        }
+        self.heap.push(Reverse(item));
+        if self.heap.len() > self.n {
+            self.heap.pop();
+        }
+    }
+
+    /// Элементы по убыванию.
+    fn into_sorted(self) -> Vec<T> {
+        // Reverse меняет порядок, поэтому возрастание Reverse — убывание элементов
+        self.heap.into_sorted_vec().into_iter().map(|Reverse(item)| item).collect()
+    }
+}
+
+/// Запись-кандидат в [`StatsReport::largest`]: порядок по AMOUNT, при равенстве выше более ранняя.
+struct Largest {
+    seq: usize,
+    tx: TxData,
+}
+
+impl Largest {
+    fn key(&self) -> (i64, Reverse<usize>) {
+        (self.tx.amount, Reverse(self.seq))
+    }
+}
+
+impl PartialEq for Largest {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Largest {}
+
+impl PartialOrd for Largest {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Largest {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Накопитель отчётов: записи подаются по одной через [`push`](Self::push).
+///
+/// Итоги по пользователям учитывают только операции не в статусе `FAILURE`
+/// (без пользователя 0 — внешнего счёта), остальные отчёты — все записи.
+pub struct StatsCollector {
+    options: StatsOptions,
+    records: usize,
+    sent: HashMap<u64, (AmountTotal, usize)>,
+    received: HashMap<u64, (AmountTotal, usize)>,
+    largest: Top<Largest>,
+    series: BTreeMap<u64, (usize, AmountTotal)>,
+    histogram: BTreeMap<i64, (Option<i64>, usize)>,
+}
+
+impl StatsCollector {
+    /// Пустой накопитель для отчётов `options`.
+    pub fn new(options: StatsOptions) -> Self {
+        StatsCollector {
+            options,
+            records: 0,
+            sent: HashMap::new(),
+            received: HashMap::new(),
+            largest: Top::new(options.largest),
+            series: BTreeMap::new(),
+            histogram: BTreeMap::new(),
+        }
+    }
+
+    /// Учитывает запись.
+    pub fn push(&mut self, tx: &TxData) {
+        let seq = self.records;
+        self.records += 1;
+        if self.largest.admits(|min| (tx.amount, Reverse(seq)) > min.key()) {
+            self.largest.push(Largest { seq, tx: tx.clone() });
+        }
+        if let Some(bucket) = self.options.series {
+            let (count, total) = self.series.entry(tx.timestamp - tx.timestamp % bucket.ms()).or_default();
+            *count += 1;
+            total.add(tx.amount);
+        }
+        if self.options.histogram {
+            let (low, high) = histogram_bin(tx.amount);
+            self.histogram.entry(low).or_insert((high, 0)).1 += 1;
+        }
+        if tx.status == Status::Failure {
+            return;
+        }
+        let add = |totals: &mut HashMap<u64, (AmountTotal, usize)>, user_id: u64| {
+            if user_id != 0 {
+                let (total, count) = totals.entry(user_id).or_default();
+                total.add(tx.amount);
+                *count += 1;
+            }
+        };
+        if self.options.top_senders > 0 && tx.tx_type != TxType::Deposit {
+            add(&mut self.sent, tx.from_user_id);
+        }
+        if self.options.top_receivers > 0 && tx.tx_type != TxType::Withdrawal {
+            add(&mut self.received, tx.to_user_id);
+        }
+    }
+
+    /// Отчёты по всем поданным записям.
+    pub fn finish(self) -> StatsReport {
+        StatsReport {
+            records: self.records,
+            top_senders: top_users(self.sent, self.options.top_senders),
+            top_receivers: top_users(self.received, self.options.top_receivers),
+            largest: self.largest.into_sorted().into_iter().map(|largest| largest.tx).collect(),
+            bucket: self.options.series,
+            series: self.options.series.map_or_else(Vec::new, |bucket| fill_series(self.series, bucket)),
+            histogram: fill_histogram(self.histogram),
+        }
+    }
+}
+
+/// `n` пользователей с наибольшим итогом; при равном итоге — с меньшим ID.
+fn top_users(totals: HashMap<u64, (AmountTotal, usize)>, n: usize) -> Vec<UserTotal> {
+    let mut top = Top::new(n);
+    for (user_id, (total, count)) in totals {
+        top.push((total, Reverse(user_id), count));
+    }
+    top.into_sorted()
+        .into_iter()
+        .map(|(total, Reverse(user_id), count)| UserTotal { user_id, total, count })
+        .collect()
+}
+
+fn fill_series(series: BTreeMap<u64, (usize, AmountTotal)>, bucket: Bucket) -> Vec<SeriesBucket> {
+    let step = bucket.ms();
+    let span = match (series.keys().next(), series.keys().next_back()) {
+        (Some(&first), Some(&last)) => first..=last,
+        _ => return Vec::new(),
+    };
+    let to_bucket = |(start, (count, total))| SeriesBucket { start, count, total };
+    if (span.end() - span.start()) / step >= MAX_FILLED_BUCKETS {
+        return series.into_iter().map(to_bucket).collect();
+    }
+    span.step_by(step as usize)
+        .map(|start| to_bucket((start, series.get(&start).copied().unwrap_or_default())))
+        .collect()
+}
+
+fn fill_histogram(histogram: BTreeMap<i64, (Option<i64>, usize)>) -> Vec<HistogramBin> {
+    let (Some(&first), Some(&last)) = (histogram.keys().next(), histogram.keys().next_back()) else {
+        return Vec::new();
+    };
+    let mut bins = Vec::new();
+    let mut low = first;
+    loop {
+        let (high, count) = histogram.get(&low).copied().unwrap_or((histogram_bin(low).1, 0));
+        bins.push(HistogramBin { low, high, count });
+        match high {
+            Some(high) if low < last => low = high,
+            _ => break,
+        }
+    }
+    bins
+}
+
+/// Читает ресурс порциями по `batch_size` записей и строит отчёты `options`.
+///
+/// # Errors
+/// `CommonErr`, если ресурс не удалось прочитать или разобрать.
+pub fn collect_stats(resource: Resource, format: Format, options: StatsOptions, batch_size: usize) -> Result<StatsReport, CommonErr> {
+    let mut collector = StatsCollector::new(options);
+    for batch in read_batches(resource, format, &ParseOptions::default(), batch_size)? {
+        batch?.records.iter().for_each(|tx| collector.push(tx));
+    }
+    Ok(collector.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::tx_id::TxId;
+
+    fn tx(id: u64, tx_type: TxType, from: u64, to: u64, amount: i64, status: Status) -> TxData {
+        TxData {
+            tx_id: id.into(),
+            tx_type,
+            from_user_id: from,
+            to_user_id: to,
+            amount,
+            timestamp: 1700000000000,
+            status,
+            description: "stats".into(),
+            format: Format::YpBankCsv,
+        }
+    }
+
+    #[test]
+    fn test_top_reports() {
+        let records = [
+            tx(1, TxType::Transfer, 1, 2, 300, Status::Success),
+            tx(2, TxType::Transfer, 2, 3, 500, Status::Success),
+            tx(3, TxType::Withdrawal, 1, 0, 300, Status::Success),
+            tx(4, TxType::Deposit, 0, 3, 500, Status::Pending),
+            tx(5, TxType::Transfer, 3, 1, 9_000, Status::Failure),
+            tx(6, TxType::Deposit, 0, 4, 100, Status::Success),
+        ];
+        let options = StatsOptions { top_senders: 2, top_receivers: 1, largest: 3, ..StatsOptions::default() };
+        let mut collector = StatsCollector::new(options);
+        records.iter().for_each(|tx| collector.push(tx));
+        let report = collector.finish();
+
+        assert_eq!(report.records, 6);
+        let users = |users: &[UserTotal]| users.iter().map(|u| (u.user_id, u.total.get(), u.count)).collect::<Vec<_>>();
+        // Отправитель 3 не попадает: его единственный перевод не прошёл
+        assert_eq!(users(&report.top_senders), vec![(1, 600, 2), (2, 500, 1)]);
+        assert_eq!(users(&report.top_receivers), vec![(3, 1000, 2)]);
+        // При равном AMOUNT остаётся более ранняя запись
+        let ids: Vec<&TxId> = report.largest.iter().map(|tx| &tx.tx_id).collect();
+        assert_eq!(ids, vec![&TxId::U64(5), &TxId::U64(2), &TxId::U64(4)]);
+        assert!(report.to_string().contains("top receivers:\n  1. user 3: 1000 in 2 records"));
+    }
+
+    #[test]
+    fn test_series_and_histogram_show_gaps() {
+        let hour = Bucket::Hour.ms();
+        let start = 1700000000000 - 1700000000000 % hour;
+        let at = |id, amount, timestamp| TxData { timestamp, ..tx(id, TxType::Deposit, 0, 1, amount, Status::Success) };
+        let records = [at(1, 5, start), at(2, 50, start + 10), at(3, 700, start + 3 * hour), at(4, 0, start + 3 * hour + 1)];
+        let mut collector = StatsCollector::new(StatsOptions { series: Some(Bucket::Hour), histogram: true, ..StatsOptions::default() });
+        records.iter().for_each(|tx| collector.push(tx));
+        let report = collector.finish();
+
+        let counts: Vec<usize> = report.series.iter().map(|bucket| bucket.count).collect();
+        assert_eq!(counts, vec![2, 0, 0, 2]);
+        assert_eq!(report.series[0].total.get(), 55);
+        assert_eq!(sparkline(&report.series), "█··█");
+
+        let bins: Vec<(i64, Option<i64>, usize)> = report.histogram.iter().map(|bin| (bin.low, bin.high, bin.count)).collect();
+        assert_eq!(bins, vec![(0, Some(1), 1), (1, Some(10), 1), (10, Some(100), 1), (100, Some(1000), 1)]);
+
+        let csv = report.render(StatsFormat::Csv);
+        assert!(csv.starts_with("BUCKET_START,COUNT,AMOUNT\n2023-11-14T22:00:00Z,2,55\n2023-11-14T23:00:00Z,0,0\n"));
+        assert!(csv.contains("\nAMOUNT_FROM,AMOUNT_TO,COUNT\n0,1,1\n"));
+        assert_eq!(histogram_bin(i64::MAX), (1_000_000_000_000_000_000, None));
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn test_collect_stats_reads_in_batches() {
+        let csv = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                   1,DEPOSIT,0,10,100,1633036860000,SUCCESS,\"a\"\n\
+                   2,DEPOSIT,0,11,700,1633036860000,SUCCESS,\"b\"\n\
+                   3,DEPOSIT,0,12,400,1633036860000,SUCCESS,\"c\"\n";
+        let resource = Resource::Memory { data: std::io::Cursor::new(csv.as_bytes().to_vec()) };
+        let report = collect_stats(resource, Format::YpBankCsv, StatsOptions { largest: 2, ..StatsOptions::default() }, 1).unwrap();
+        assert_eq!(report.records, 3);
+        assert_eq!(report.largest.iter().map(|tx| tx.amount).collect::<Vec<_>>(), vec![700, 400]);
+        assert!(report.top_senders.is_empty());
+    }
+}
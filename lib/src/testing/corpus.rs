@@ -0,0 +1,456 @@
+//! Корпус для проверки совместимости между командами: корректные файлы каждого формата
+//! с крайними значениями, длинами и Unicode и намеренно испорченные файлы, которые
+//! любая реализация должна отклонить.
+//!
+//! Рядом с файлами лежит манифест [`CORPUS_MANIFEST`] с ожидаемым итогом для каждого:
+//!
+//! ```json
+//! {"version": 1, "schema": 2, "files": [
+//!   {"path": "valid/unicode.csv", "format": "yp-bank-csv", "expect": "valid", "records": 7, "checks": "…"},
+//!   {"path": "invalid/amount-overflow.csv", "format": "yp-bank-csv", "expect": "invalid", "checks": "…", "error": "…"}
+//! ]}
+//! ```
+//!
+//! `error` — ошибка эталонной реализации, для справки: текст у других реализаций может отличаться.
+
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use super::{same_records, write_options, INVALID_DIR, VALID_DIR};
+use crate::migrate::CURRENT_SCHEMA;
+use crate::model::data::{Format, Status, TxData, TxType};
+use crate::model::errors::{describe_error, CommonErr, IoErr, ParserErr};
+use crate::model::tx_id::TxId;
+use crate::parser::options::{BinProfile, ParseOptions, QuoteStyle, WriteOptions};
+use crate::registry::TxFormat;
+
+/// Версия корпуса: меняется, когда меняется набор файлов или их содержимое.
+pub const CORPUS_VERSION: u64 = 1;
+/// Имя манифеста в каталоге корпуса.
+pub const CORPUS_MANIFEST: &str = "manifest.json";
+
+/// Файл корпуса и ожидаемый итог его чтения.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorpusEntry {
+    /// Путь относительно каталога корпуса, через `/`
+    pub path: String,
+    pub format: Format,
+    pub expect_valid: bool,
+    /// Число записей корректного файла
+    pub records: Option<usize>,
+    /// Что проверяет файл
+    pub checks: String,
+    /// Ошибка эталонной реализации на некорректном файле
+    pub error: Option<String>,
+}
+
+/// Корректный файл: записи и настройки, с которыми он записывается.
+struct ValidCase {
+    name: &'static str,
+    checks: &'static str,
+    records: Vec<TxData>,
+    options: WriteOptions,
+}
+
+/// Некорректный файл: испорченная копия файла с одной записью [`base_record`].
+struct InvalidCase {
+    name: String,
+    checks: &'static str,
+    bytes: Vec<u8>,
+}
+
+/// Записывает корпус форматов `formats` в каталог `dir` (создаёт его при необходимости)
+/// вместе с манифестом.
+///
+/// Каждый файл перед записью проверяется эталонной реализацией: корректный читается
+/// в те же записи, некорректный отклоняется.
+///
+/// # Errors
+/// `CommonErr::IO` при ошибке записи; `CommonErr::Parser`, если формат отключён
+/// при сборке или файл не прошёл проверку.
+pub fn generate_corpus(dir: &Path, formats: &[Format]) -> Result<Vec<CorpusEntry>, CommonErr> {
+    for subdir in [VALID_DIR, INVALID_DIR] {
+        let path = dir.join(subdir);
+        fs::create_dir_all(&path).map_err(|e| IoErr::output(e, Some(path)))?;
+    }
+
+    let mut entries = Vec::new();
+    for &format in formats {
+        for case in valid_cases(format) {
+            let path = format!("{}/{}.{}", VALID_DIR, case.name, format.extension());
+            let mut bytes = Vec::new();
+            format.write(&case.records, &mut bytes, &case.options)?;
+            let read = read_bytes(&bytes, format).map_err(|e| generated_err(&path, describe_error(&e)))?;
+            same_records(&case.records, &read).map_err(|e| generated_err(&path, e))?;
+            write_file(dir, &path, &bytes)?;
+            entries.push(CorpusEntry {
+                path,
+                format,
+                expect_valid: true,
+                records: Some(case.records.len()),
+                checks: case.checks.to_string(),
+                error: None,
+            });
+        }
+        for case in invalid_cases(format)? {
+            let path = format!("{}/{}.{}", INVALID_DIR, case.name, format.extension());
+            let error = match read_bytes(&case.bytes, format) {
+                Ok(records) => return Err(generated_err(&path, format!("accepted with {} records", records.len()))),
+                Err(e) => describe_error(&e),
+            };
+            write_file(dir, &path, &case.bytes)?;
+            entries.push(CorpusEntry {
+                path,
+                format,
+                expect_valid: false,
+                records: None,
+                checks: case.checks.to_string(),
+                error: Some(error),
+            });
+        }
+    }
+
+    write_file(dir, CORPUS_MANIFEST, render_manifest(&entries).as_bytes())?;
+    Ok(entries)
+}
+
+/// Читает манифест корпуса `dir`.
+///
+/// # Возвращает
+/// `None`, если манифеста нет.
+///
+/// # Errors
+/// `CommonErr::IO`, если манифест не удалось прочитать; `ParserErr::Malformed`, если он
+/// повреждён или другой версии.
+pub fn read_manifest(dir: &Path) -> Result<Option<Vec<CorpusEntry>>, CommonErr> {
+    let path = dir.join(CORPUS_MANIFEST);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let data = fs::read(&path).map_err(|e| IoErr::input(e, Some(path)))?;
+    Ok(Some(parse_manifest(&data)?))
+}
+
+fn render_manifest(entries: &[CorpusEntry]) -> String {
+    let files: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            let mut file = serde_json::json!({
+                "path": entry.path,
+                "format": entry.format.name(),
+                "expect": if entry.expect_valid { "valid" } else { "invalid" },
+                "checks": entry.checks,
+            });
+            if let Some(records) = entry.records {
+                file["records"] = records.into();
+            }
+            if let Some(error) = &entry.error {
+                file["error"] = error.as_str().into();
+            }
+            file
+        })
+        .collect();
+    let manifest = serde_json::json!({ "version": CORPUS_VERSION, "schema": CURRENT_SCHEMA, "files": files });
+    serde_json::to_string_pretty(&manifest).unwrap_or_default()
+}
+
+fn parse_manifest(data: &[u8]) -> Result<Vec<CorpusEntry>, ParserErr> {
+    let malformed = |msg: &str| ParserErr::Malformed { msg: format!("{}: {}", CORPUS_MANIFEST, msg) };
+    let manifest: serde_json::Value = serde_json::from_slice(data).map_err(|e| malformed(&e.to_string()))?;
+
+    let version = manifest["version"].as_u64().ok_or_else(|| malformed("no version"))?;
+    if version != CORPUS_VERSION {
+        return Err(malformed(&format!("unsupported version {}", version)));
+    }
+    let files = manifest["files"].as_array().ok_or_else(|| malformed("no files"))?;
+    files
+        .iter()
+        .map(|file| {
+            let text = |key: &str| file[key].as_str().ok_or_else(|| malformed(&format!("file without {}", key)));
+            let expect_valid = match text("expect")? {
+                "valid" => true,
+                "invalid" => false,
+                other => return Err(malformed(&format!("unknown expect {:?}", other))),
+            };
+            Ok(CorpusEntry {
+                path: text("path")?.to_string(),
+                format: Format::from_name(text("format")?).ok_or_else(|| malformed("unknown format"))?,
+                expect_valid,
+                records: file["records"].as_u64().map(|records| records as usize),
+                checks: text("checks")?.to_string(),
+                error: file["error"].as_str().map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+fn write_file(dir: &Path, name: &str, bytes: &[u8]) -> Result<(), CommonErr> {
+    let path = dir.join(name);
+    fs::write(&path, bytes).map_err(|e| IoErr::output(e, Some(path)).into())
+}
+
+fn read_bytes(bytes: &[u8], format: Format) -> Result<Vec<TxData>, ParserErr> {
+    Ok(format.read(Box::new(Cursor::new(bytes.to_vec())), &ParseOptions::default())?.records)
+}
+
+fn generated_err(path: &str, reason: impl Into<String>) -> CommonErr {
+    ParserErr::Malformed { msg: format!("generated corpus file {} failed self-check: {}", path, reason.into()) }.into()
+}
+
+fn record(tx_id: TxId, tx_type: TxType, status: Status, description: &str) -> TxData {
+    TxData {
+        tx_id,
+        tx_type,
+        from_user_id: 1001,
+        to_user_id: 1002,
+        amount: 12345,
+        timestamp: 1700000000000,
+        status,
+        description: description.into(),
+        format: Format::YpBankCsv,
+    }
+}
+
+/// Запись, из файла с которой портятся некорректные файлы: значения полей встречаются
+/// в файле ровно по одному разу.
+fn base_record() -> TxData {
+    record(TxId::U64(7), TxType::Deposit, Status::Success, "base")
+}
+
+fn descriptions(descriptions: &[String]) -> Vec<TxData> {
+    descriptions
+        .iter()
+        .zip(1..)
+        .map(|(description, id)| record(TxId::U64(id), TxType::Transfer, Status::Success, description))
+        .collect()
+}
+
+fn valid_cases(format: Format) -> Vec<ValidCase> {
+    // Только файлы с заголовком имеют текущую версию схемы и хранят нечисловые TX_ID
+    let current = write_options();
+    let case = |name, checks, records| ValidCase { name, checks, records, options: current.clone() };
+
+    let mut extremes = vec![
+        TxData {
+            tx_id: TxId::U64(0),
+            from_user_id: 0,
+            to_user_id: u64::MAX,
+            amount: i64::MIN,
+            timestamp: 0,
+            ..base_record()
+        },
+        TxData {
+            tx_id: TxId::U64(u64::MAX),
+            from_user_id: u64::MAX,
+            to_user_id: 0,
+            amount: i64::MAX,
+            timestamp: u64::MAX,
+            ..base_record()
+        },
+    ];
+    extremes.push(TxData { tx_id: TxId::U64(1), amount: 0, ..base_record() });
+
+    let unicode = [
+        "Перевод за квартиру",
+        "收款 人民币",
+        "تحويل مصرفي",
+        "emoji 💸🏦",
+        "e\u{301} combining vs \u{e9} precomposed",
+        "zero\u{200b}width",
+        "Ω≈ç√∫˜µ≤≥÷",
+    ];
+    let lengths = [
+        String::new(),
+        "x".to_string(),
+        "x".repeat(255),
+        "x".repeat(256),
+        "x".repeat(65_535),
+        "x".repeat(65_536),
+        "ж".repeat(128),
+        "💸".repeat(64),
+    ];
+    let special = [
+        "He said \"hi\"",
+        "a,b,c",
+        "# not a comment",
+        "KEY: value",
+        "back\\slash",
+        "'single'",
+        "\"\"",
+    ];
+
+    let mut cases = vec![
+        case("empty", "file without records", Vec::new()),
+        case("minimal", "one record", vec![base_record()]),
+        case(
+            "types-and-statuses",
+            "every TX_TYPE with every STATUS",
+            TxType::ALL
+                .into_iter()
+                .flat_map(|tx_type| Status::ALL.into_iter().map(move |status| (tx_type, status)))
+                .zip(1..)
+                .map(|((tx_type, status), id)| record(TxId::U64(id), tx_type, status, tx_type.name()))
+                .collect(),
+        ),
+        case("extremes", "smallest and largest TX_ID, user ids, AMOUNT and TIMESTAMP", extremes),
+        case("unicode", "non-ASCII descriptions: scripts, emoji, combining marks, zero-width characters", descriptions(&unicode.map(String::from))),
+        case(
+            "description-lengths",
+            "descriptions of 0, 1, 255, 256, 65535 and 65536 bytes, multi-byte characters at 256 bytes",
+            descriptions(&lengths),
+        ),
+        case("special-characters", "quotes, separators and comment markers in descriptions", descriptions(&special.map(String::from))),
+        case(
+            "tx-id-kinds",
+            "numeric, UUID and string TX_ID (schema 2)",
+            vec![
+                record(TxId::U64(42), TxType::Deposit, Status::Success, "numeric"),
+                record(TxId::Uuid(0x0193_6c2a_5b7e_7c3d_9f1e_2a4b_6c8d_0e1f), TxType::Deposit, Status::Success, "uuid"),
+                record(TxId::String("order-2024-001".to_string()), TxType::Deposit, Status::Success, "string"),
+            ],
+        ),
+    ];
+
+    let three = vec![base_record(), TxData { tx_id: TxId::U64(8), ..base_record() }, TxData { tx_id: TxId::U64(9), ..base_record() }];
+    match format {
+        Format::YpBankCsv => {
+            cases.push(ValidCase { name: "bom", checks: "UTF-8 BOM before the header", records: three, options: WriteOptions { csv_bom: true, ..current } });
+            cases.push(ValidCase {
+                name: "quote-all",
+                checks: "every field quoted, header included",
+                records: vec![base_record()],
+                options: WriteOptions { csv_quote_style: QuoteStyle::Always, ..write_options() },
+            });
+        },
+        Format::YpBankText => {},
+        Format::YpBankBin => {
+            cases.push(ValidCase { name: "legacy-schema-1", checks: "schema 1: records without a file header", records: three.clone(), options: WriteOptions::default() });
+            cases.push(ValidCase {
+                name: "compact-le",
+                checks: "CompactLe profile: little-endian numbers, varint lengths",
+                records: three.clone(),
+                options: WriteOptions { bin_profile: BinProfile::CompactLe, ..current.clone() },
+            });
+            cases.push(ValidCase { name: "indexed", checks: "TX_ID index after the records", records: three.clone(), options: WriteOptions { bin_index: true, ..current.clone() } });
+            #[cfg(feature = "zstd")]
+            cases.push(ValidCase {
+                name: "zstd",
+                checks: "zstd-compressed record blocks",
+                records: three,
+                options: WriteOptions { bin_compression: crate::parser::options::BinCompression::Zstd { level: 3 }, ..current },
+            });
+        },
+    }
+    cases
+}
+
+/// Смещения полей записи схемы 1 (без заголовка файла): MAGIC, BODY_LEN (u32), TX_ID (u64),
+/// TX_TYPE, FROM_USER_ID, TO_USER_ID, AMOUNT, TIMESTAMP, STATUS, DESC_LEN (u32), DESCRIPTION.
+const LEGACY_TX_TYPE: usize = 16;
+const LEGACY_STATUS: usize = 49;
+const LEGACY_DESC_LEN: usize = 50;
+const LEGACY_DESCRIPTION: usize = 54;
+/// Смещение версии схемы в заголовке файла.
+const HEADER_VERSION: usize = 4;
+
+fn invalid_cases(format: Format) -> Result<Vec<InvalidCase>, CommonErr> {
+    let case = |name: &str, checks, bytes| InvalidCase { name: name.to_string(), checks, bytes };
+    let write = |options: &WriteOptions| -> Result<Vec<u8>, ParserErr> {
+        let mut bytes = Vec::new();
+        format.write(&[base_record()], &mut bytes, options)?;
+        Ok(bytes)
+    };
+
+    if format == Format::YpBankBin {
+        let legacy = write(&WriteOptions::default())?;
+        let patched = |at: usize, patch: &[u8]| {
+            let mut bytes = legacy.clone();
+            bytes[at..at + patch.len()].copy_from_slice(patch);
+            bytes
+        };
+        let mut future = write(&write_options())?;
+        future[HEADER_VERSION] = 99;
+        return Ok(vec![
+            case("bad-magic", "record without the YPBN magic", patched(0, b"XXXX")),
+            case("truncated", "file ends inside a record", legacy[..legacy.len() - 2].to_vec()),
+            case("unknown-tx-type", "TX_TYPE code 9", patched(LEGACY_TX_TYPE, &[9])),
+            case("unknown-status", "STATUS code 9", patched(LEGACY_STATUS, &[9])),
+            case("description-length-exceeds-body", "DESC_LEN larger than the record body", patched(LEGACY_DESC_LEN, &u32::MAX.to_be_bytes())),
+            case("invalid-utf8", "DESCRIPTION is not UTF-8", patched(LEGACY_DESCRIPTION, &[0xFF])),
+            case("unsupported-version", "file header with schema version 99", future),
+        ]);
+    }
+
+    let base = write(&write_options())?;
+    let replaced = |from: &str, to: &str| replace_first(&base, from.as_bytes(), to.as_bytes());
+    let mut cases = vec![
+        case("amount-not-a-number", "AMOUNT with a letter", replaced("12345", "12x45")),
+        case("amount-overflow", "AMOUNT above i64::MAX", replaced("12345", "9223372036854775808")),
+        case("empty-amount", "empty AMOUNT", replaced("12345", "")),
+        case("negative-user-id", "negative FROM_USER_ID", replaced("1001", "-1001")),
+        case("user-id-overflow", "TO_USER_ID above u64::MAX", replaced("1002", "18446744073709551616")),
+        case("unknown-tx-type", "TX_TYPE outside the specification", replaced("DEPOSIT", "REFUND")),
+        case("unknown-status", "STATUS outside the specification", replaced("SUCCESS", "DONE")),
+        case("invalid-utf8", "DESCRIPTION is not UTF-8", replace_first(&base, b"base", b"ba\xFFse")),
+    ];
+    match format {
+        Format::YpBankCsv => cases.extend([
+            case("bad-header", "header with a renamed column", replaced("TX_TYPE", "TYPE")),
+            case("missing-column", "record without the STATUS column", replaced(",SUCCESS", "")),
+            case("extra-column", "record with a ninth column", replaced(",SUCCESS", ",SUCCESS,EXTRA")),
+        ]),
+        Format::YpBankText => cases.extend([
+            case("missing-field", "record without STATUS", replaced("STATUS: SUCCESS\n", "")),
+            case("duplicate-key", "AMOUNT given twice", replaced("AMOUNT: 12345\n", "AMOUNT: 12345\nAMOUNT: 12345\n")),
+            case("unknown-key", "key outside the specification", replaced("STATUS: SUCCESS\n", "STATUS: SUCCESS\nCURRENCY: EUR\n")),
+        ]),
+        Format::YpBankBin => {},
+    }
+    Ok(cases)
+}
+
+/// Копия `bytes` с первым вхождением `from`, заменённым на `to`.
+fn replace_first(bytes: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    match bytes.windows(from.len()).position(|window| window == from) {
+        Some(at) => [&bytes[..at], to, &bytes[at + from.len()..]].concat(),
+        None => bytes.to_vec(),
+    }
+}
+
+#[cfg(all(test, feature = "csv", feature = "text", feature = "bin"))]
+mod tests {
+    use super::*;
+    use crate::testing::run_conformance;
+
+    const FORMATS: [Format; 3] = [Format::YpBankCsv, Format::YpBankText, Format::YpBankBin];
+
+    #[test]
+    fn test_generated_corpus_passes_conformance() {
+        let dir = tempfile::tempdir().unwrap();
+        let entries = generate_corpus(dir.path(), &FORMATS).unwrap();
+        assert!(entries.iter().any(|entry| entry.path == "valid/description-lengths.bin" && entry.records == Some(8)));
+        assert!(entries.iter().any(|entry| entry.path == "invalid/amount-overflow.txt" && entry.error.is_some()));
+        assert_eq!(read_manifest(dir.path()).unwrap().unwrap(), entries);
+
+        let cases = run_conformance(dir.path(), None).unwrap();
+        assert_eq!(cases.len(), entries.len());
+        let failed: Vec<_> = cases.iter().filter(|case| !case.passed()).collect();
+        assert!(failed.is_empty(), "{:?}", failed);
+    }
+
+    #[test]
+    fn test_conformance_checks_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        generate_corpus(dir.path(), &[Format::YpBankCsv]).unwrap();
+        fs::remove_file(dir.path().join("valid/unicode.csv")).unwrap();
+        fs::copy(dir.path().join("valid/minimal.csv"), dir.path().join("valid/empty.csv")).unwrap();
+
+        let cases = run_conformance(dir.path(), None).unwrap();
+        let failures: Vec<_> = cases.iter().filter_map(|case| case.failure.as_deref()).collect();
+        assert_eq!(failures, ["manifest expects 0 records, read 1", "listed in the manifest, but missing"]);
+
+        fs::write(dir.path().join(CORPUS_MANIFEST), r#"{"version": 2, "files": []}"#).unwrap();
+        assert!(matches!(read_manifest(dir.path()), Err(CommonErr::Parser(ParserErr::Malformed { .. }))));
+    }
+}
@@ -0,0 +1,297 @@
+//! Проверки на «золотых» файлах: эталонная реализация форматов для тестов этого крейта
+//! и сторонних реализаций [`TxFormat`].
+//!
+//! Каталог для [`run_conformance`]; формат файла определяется по расширению
+//! (см. [`Format::from_extension`]), файлы с другими расширениями пропускаются:
+//!
+//! ```text
+//! corpus/
+//!   valid/          читаются и переписываются без потерь
+//!   invalid/        отклоняются при чтении
+//!   manifest.json   необязательно: ожидаемое число записей (см. [`corpus`])
+//! ```
+//!
+//! ```ignore
+//! #[test]
+//! fn legacy_csv_matches_canonical() {
+//!     assert_roundtrip("tests/golden/records.csv", &Format::YpBankCsv);
+//!     assert_conformance(Path::new("tests/corpus"), Some((Format::YpBankCsv, &LegacyCsv)));
+//! }
+//! ```
+
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use crate::model::data::{Format, TxData};
+use crate::model::errors::{describe_error, CommonErr, IoErr};
+use crate::parser::options::{ParseOptions, WriteOptions};
+use crate::registry::TxFormat;
+
+pub mod corpus;
+pub use corpus::{generate_corpus, read_manifest, CorpusEntry, CORPUS_MANIFEST, CORPUS_VERSION};
+
+/// Подкаталог корпуса с файлами, которые должны читаться.
+pub const VALID_DIR: &str = "valid";
+/// Подкаталог корпуса с файлами, которые должны отклоняться.
+pub const INVALID_DIR: &str = "invalid";
+
+/// Итог проверки одного файла корпуса.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConformanceCase {
+    pub path: PathBuf,
+    pub format: Format,
+    /// Файл из `valid/`: должен читаться; из `invalid/` — отклоняться
+    pub expect_valid: bool,
+    /// Почему проверка не пройдена; `None` — пройдена
+    pub failure: Option<String>,
+}
+
+impl ConformanceCase {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Читает файл `path` форматом `format`, записывает записи им же и читает снова.
+///
+/// # Возвращает
+/// Прочитанные из файла записи или описание первого расхождения.
+pub fn check_roundtrip(path: &Path, format: &dyn TxFormat) -> Result<Vec<TxData>, String> {
+    let records = read_file(path, format)?;
+    let bytes = write_bytes(&records, format)?;
+    let back = read_bytes(bytes, format).map_err(|e| format!("failed to read back written records: {}", e))?;
+    same_records(&records, &back).map_err(|e| format!("records changed after write and read: {}", e))?;
+    Ok(records)
+}
+
+/// [`check_roundtrip`] для тестов: паникует с описанием расхождения.
+pub fn assert_roundtrip(path: impl AsRef<Path>, format: &dyn TxFormat) {
+    let path = path.as_ref();
+    if let Err(reason) = check_roundtrip(path, format) {
+        panic!("roundtrip of {} failed: {}", path.display(), reason);
+    }
+}
+
+/// Проверяет, что реализация `candidate` совместима с эталонной реализацией `canonical`:
+/// читает файл `path` так же и понимает записанное ею в обе стороны.
+pub fn check_compatible(path: &Path, canonical: Format, candidate: &dyn TxFormat) -> Result<(), String> {
+    let expected = read_file(path, &canonical).map_err(|e| format!("canonical {}: {}", canonical.name(), e))?;
+    let actual = read_file(path, candidate).map_err(|e| format!("candidate: {}", e))?;
+    same_records(&expected, &actual).map_err(|e| format!("candidate read differs: {}", e))?;
+
+    let written = write_bytes(&expected, candidate).map_err(|e| format!("candidate: {}", e))?;
+    let back = read_bytes(written, &canonical).map_err(|e| format!("canonical cannot read candidate output: {}", e))?;
+    same_records(&expected, &back).map_err(|e| format!("candidate output differs: {}", e))?;
+
+    let written = write_bytes(&expected, &canonical)?;
+    let back = read_bytes(written, candidate).map_err(|e| format!("candidate cannot read canonical output: {}", e))?;
+    same_records(&expected, &back).map_err(|e| format!("candidate read of canonical output differs: {}", e))
+}
+
+/// [`check_compatible`] для тестов: паникует с описанием расхождения.
+pub fn assert_compatible(path: impl AsRef<Path>, canonical: Format, candidate: &dyn TxFormat) {
+    let path = path.as_ref();
+    if let Err(reason) = check_compatible(path, canonical, candidate) {
+        panic!("{} is not compatible with {}: {}", path.display(), canonical.name(), reason);
+    }
+}
+
+/// Проверяет все файлы корпуса `dir` эталонной реализацией и, если задана, реализацией
+/// `candidate` для файлов её формата.
+///
+/// # Возвращает
+/// Итоги по файлам, отсортированные по пути.
+///
+/// Если в корпусе есть манифест, число записей корректных файлов сверяется с ним,
+/// а перечисленные в нём, но отсутствующие файлы считаются непройденными.
+///
+/// # Errors
+/// `CommonErr::IO`, если каталог не удалось прочитать; `ParserErr::Malformed`, если
+/// манифест повреждён.
+pub fn run_conformance(dir: &Path, candidate: Option<(Format, &dyn TxFormat)>) -> Result<Vec<ConformanceCase>, CommonErr> {
+    let mut manifest = read_manifest(dir)?.unwrap_or_default();
+    let mut cases = Vec::new();
+    for (subdir, expect_valid) in [(VALID_DIR, true), (INVALID_DIR, false)] {
+        let subdir = dir.join(subdir);
+        if !subdir.is_dir() {
+            continue;
+        }
+        let entries = fs::read_dir(&subdir).map_err(|e| IoErr::input(e, Some(subdir.clone())))?;
+        for entry in entries {
+            let path = entry.map_err(|e| IoErr::input(e, Some(subdir.clone())))?.path();
+            let Some(format) = path.extension().and_then(|ext| ext.to_str()).and_then(Format::from_extension) else {
+                continue;
+            };
+            let candidate = candidate.filter(|(candidate_format, _)| *candidate_format == format).map(|(_, c)| c);
+            let mut failure = if expect_valid {
+                check_valid(&path, format, candidate)
+            } else {
+                check_invalid(&path, format, candidate)
+            };
+            let relative = path.strip_prefix(dir).map(|p| p.to_string_lossy().replace('\\', "/")).unwrap_or_default();
+            if let Some(index) = manifest.iter().position(|entry| entry.path == relative) {
+                let entry = manifest.swap_remove(index);
+                if let (Ok(read), Some(expected)) = (&failure, entry.records) {
+                    if *read != expected {
+                        failure = Err(format!("manifest expects {} records, read {}", expected, read));
+                    }
+                }
+            }
+            cases.push(ConformanceCase { path, format, expect_valid, failure: failure.err() });
+        }
+    }
+    cases.extend(manifest.into_iter().map(|entry| ConformanceCase {
+        path: dir.join(&entry.path),
+        format: entry.format,
+        expect_valid: entry.expect_valid,
+        failure: Some("listed in the manifest, but missing".to_string()),
+    }));
+    cases.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(cases)
+}
+
+/// [`run_conformance`] для тестов: паникует со списком непройденных файлов,
+/// а также если в корпусе нет ни одного файла.
+pub fn assert_conformance(dir: &Path, candidate: Option<(Format, &dyn TxFormat)>) {
+    let cases = match run_conformance(dir, candidate) {
+        Ok(cases) => cases,
+        Err(e) => panic!("failed to read conformance corpus {}: {}", dir.display(), e),
+    };
+    if cases.is_empty() {
+        panic!("conformance corpus {} has no files in {}/ or {}/", dir.display(), VALID_DIR, INVALID_DIR);
+    }
+    let failures: Vec<String> = cases
+        .iter()
+        .filter_map(|case| case.failure.as_ref().map(|reason| format!("  {}: {}", case.path.display(), reason)))
+        .collect();
+    if !failures.is_empty() {
+        panic!("{} of {} conformance files failed:\n{}", failures.len(), cases.len(), failures.join("\n"));
+    }
+}
+
+/// # Возвращает
+/// Число записей файла.
+fn check_valid(path: &Path, format: Format, candidate: Option<&dyn TxFormat>) -> Result<usize, String> {
+    let records = check_roundtrip(path, &format)?.len();
+    if let Some(candidate) = candidate {
+        check_compatible(path, format, candidate)?;
+    }
+    Ok(records)
+}
+
+fn check_invalid(path: &Path, format: Format, candidate: Option<&dyn TxFormat>) -> Result<usize, String> {
+    let mut implementations: Vec<(&str, &dyn TxFormat)> = vec![("canonical", &format)];
+    implementations.extend(candidate.map(|candidate| ("candidate", candidate)));
+    for (name, implementation) in implementations {
+        if let Ok(records) = read_file(path, implementation) {
+            return Err(format!("{} accepted an invalid file ({} records)", name, records.len()));
+        }
+    }
+    Ok(0)
+}
+
+fn read_file(path: &Path, format: &dyn TxFormat) -> Result<Vec<TxData>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    read_bytes(bytes, format)
+}
+
+fn read_bytes(bytes: Vec<u8>, format: &dyn TxFormat) -> Result<Vec<TxData>, String> {
+    format
+        .read(Box::new(Cursor::new(bytes)), &ParseOptions::default())
+        .map(|outcome| outcome.records)
+        .map_err(|e| describe_error(&e))
+}
+
+/// Настройки записи текущей версии схемы: только бинарные файлы с заголовком хранят
+/// нечисловые `TX_ID`.
+pub(crate) fn write_options() -> WriteOptions {
+    WriteOptions { bin_header: true, ..WriteOptions::default() }
+}
+
+fn write_bytes(records: &[TxData], format: &dyn TxFormat) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    format
+        .write(records, &mut bytes, &write_options())
+        .map_err(|e| format!("failed to write records: {}", e))?;
+    Ok(bytes)
+}
+
+/// Описание первого расхождения наборов записей без учёта формата, из которого они прочитаны.
+pub(crate) fn same_records(expected: &[TxData], actual: &[TxData]) -> Result<(), String> {
+    if expected.len() != actual.len() {
+        return Err(format!("expected {} records, got {}", expected.len(), actual.len()));
+    }
+    match expected.iter().zip(actual).position(|(a, b)| !a.same_content(b)) {
+        Some(index) => Err(format!("record {}: expected {:?}, got {:?}", index + 1, expected[index], actual[index])),
+        None => Ok(()),
+    }
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    use crate::model::errors::ParserErr;
+    use crate::parser::options::ParseOutcome;
+
+    const CSV: &str = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+                       1,DEPOSIT,0,10,100,1700000000,SUCCESS,\"Записка, с запятой\"\n\
+                       2,WITHDRAWAL,10,0,-5,1700000001,PENDING,\"\"\n";
+
+    /// CSV-реализация, теряющая описания при записи.
+    struct DropsDescriptions;
+
+    impl TxFormat for DropsDescriptions {
+        fn read(&self, reader: Box<dyn Read>, options: &ParseOptions) -> Result<ParseOutcome, ParserErr> {
+            Format::YpBankCsv.read(reader, options)
+        }
+
+        fn write(&self, records: &[TxData], w: &mut dyn Write, options: &WriteOptions) -> Result<(), ParserErr> {
+            let stripped: Vec<TxData> = records.iter().map(|tx| TxData { description: "".into(), ..tx.clone() }).collect();
+            Format::YpBankCsv.write(&stripped, w, options)
+        }
+    }
+
+    fn corpus() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join(VALID_DIR)).unwrap();
+        fs::create_dir(dir.path().join(INVALID_DIR)).unwrap();
+        fs::write(dir.path().join(VALID_DIR).join("records.csv"), CSV).unwrap();
+        fs::write(dir.path().join(VALID_DIR).join("README.md"), "not a corpus file").unwrap();
+        fs::write(dir.path().join(INVALID_DIR).join("bad-amount.csv"), CSV.replace("-5", "five")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_roundtrip_and_conformance_of_canonical_csv() {
+        let dir = corpus();
+        assert_roundtrip(dir.path().join(VALID_DIR).join("records.csv"), &Format::YpBankCsv);
+
+        let cases = run_conformance(dir.path(), None).unwrap();
+        assert_eq!(cases.len(), 2);
+        assert!(cases.iter().all(ConformanceCase::passed));
+        assert!(!cases[0].expect_valid);
+        assert_conformance(dir.path(), Some((Format::YpBankCsv, &Format::YpBankCsv)));
+    }
+
+    #[test]
+    fn test_conformance_reports_incompatible_candidate() {
+        let dir = corpus();
+        let Err(reason) = check_compatible(&dir.path().join(VALID_DIR).join("records.csv"), Format::YpBankCsv, &DropsDescriptions)
+        else {
+            panic!("expected a lost description");
+        };
+        assert!(reason.starts_with("candidate output differs: record 1:"), "{}", reason);
+
+        fs::write(dir.path().join(INVALID_DIR).join("good.csv"), CSV).unwrap();
+        let cases = run_conformance(dir.path(), Some((Format::YpBankCsv, &DropsDescriptions))).unwrap();
+        let failed: Vec<&str> = cases
+            .iter()
+            .filter(|case| !case.passed())
+            .map(|case| case.path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(failed, ["good.csv", "records.csv"]);
+    }
+}
@@ -0,0 +1,215 @@
+//! Снимок публичного API библиотеки.
+//!
+//! Тест компилируется только пока прелюдия и публичные модули экспортируют те же имена
+//! с теми же сигнатурами.
+//! Если он перестал собираться — изменение ломает semver и должно сопровождаться
+//! сменой мажорной версии (или обновлением этого файла в том же ревью).
+#![cfg(all(feature = "bin", feature = "csv", feature = "text"))]
+// Сигнатуры выписаны целиком намеренно — это и есть снимок API.
+#![allow(clippy::type_complexity)]
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+
+use lib::convert::in_place::convert_in_place;
+use lib::convert::many::{convert_many, expand_inputs, ConvertManyReport, FileConvertResult};
+use lib::migrate::{migrate_or_inspect, MigrateReport};
+use lib::model::metadata::ProducerMetadata;
+use lib::model::normalize::{DescriptionNormalization, NormalizeStep};
+use lib::parser::{
+    append_batches, conversion_losses, find_by_id, read_metadata, read_range, Capabilities, Loss, MemoryBudget, TextAnnotation,
+};
+use lib::prelude::*;
+use lib::registry::{convert_with, format_by_name, register_format, TxFormat};
+#[cfg(feature = "bench")]
+use lib::bench::{run_bench, BenchOptions, BenchResult};
+#[cfg(feature = "bundle")]
+use lib::bundle::{unbundle, BundleEntry};
+#[cfg(feature = "compare")]
+use lib::compare::{compare, compare_inputs, CompareErr, CompareOptions, CompareReport};
+#[cfg(feature = "drift")]
+use lib::drift::{observe_shape, schema_diff, Drift, Shape};
+#[cfg(feature = "junit")]
+use lib::junit::{render_junit, TestCase};
+#[cfg(feature = "manifest")]
+use lib::manifest::write_manifest;
+#[cfg(feature = "rules")]
+use lib::model::validation::RuleViolation;
+#[cfg(feature = "report")]
+use lib::model::errors::ParseIssue;
+#[cfg(feature = "report")]
+use lib::parser::{write_issues_report, ReportFormat};
+#[cfg(feature = "partition")]
+use lib::partition::{partition_convert, OutputTemplate, PartitionReport};
+#[cfg(feature = "preview")]
+use lib::preview::preview_table;
+#[cfg(feature = "rules")]
+use lib::rules::{RulePack, RulePackErr};
+#[cfg(feature = "screening")]
+use lib::screening::{screen, ScreeningOptions, UserScore};
+#[cfg(feature = "spill")]
+use lib::spill::{sort_file, SortOptions, SortReport};
+#[cfg(feature = "stats")]
+use lib::stats::{collect_stats, StatsOptions, StatsReport};
+#[cfg(feature = "testing")]
+use lib::testing::{check_roundtrip, run_conformance, ConformanceCase};
+
+#[test]
+fn public_functions_keep_their_signatures() {
+    let _: fn(Resource, Format) -> Result<Vec<TxData>, CommonErr> = read;
+    let _: fn(&[TxData], Resource, Format) -> Result<usize, CommonErr> = write_to_resource;
+    let _: fn(Resource, Format, &ParseOptions) -> Result<ParseOutcome, CommonErr> = read_with;
+    let _: fn(Resource, Format, &TxId) -> Result<Option<TxData>, CommonErr> = find_by_id;
+    let _: fn(Cursor<Vec<u8>>, usize, usize) -> Result<Vec<TxData>, ParserErr> = read_range;
+    let _: fn(&[TxData], &[TextAnnotation], Resource, Format, &WriteOptions) -> Result<usize, CommonErr> =
+        write_with;
+    let _: fn(Resource, Format, &ParseOptions, usize) -> Result<BatchReader, CommonErr> = read_batches;
+    let _: fn(Resource, Format, &WriteOptions) -> Result<BatchWriter, CommonErr> = write_batches;
+    let _: fn(&mut BatchWriter, &[TxData], &[TextAnnotation]) -> Result<(), CommonErr> =
+        BatchWriter::write_batch;
+    let _: fn(BatchWriter) -> Result<usize, CommonErr> = BatchWriter::finish;
+    let _: fn(&str, Box<dyn TxFormat>) -> Result<(), ParserErr> = register_format;
+    let _: fn(&str) -> Option<std::sync::Arc<dyn TxFormat>> = format_by_name;
+    let _: fn(Resource, &dyn TxFormat, Resource, &dyn TxFormat, &ConvertOptions) -> Result<ParseOutcome, CommonErr> =
+        convert_with;
+    #[cfg(feature = "report")]
+    let _: fn(&[ParseIssue], Resource, ReportFormat) -> Result<usize, CommonErr> =
+        write_issues_report;
+    #[cfg(feature = "compare")]
+    let _: fn(&[TxData], &[TxData], &CompareOptions) -> CompareReport = compare;
+    #[cfg(feature = "compare")]
+    let _: fn(Resource, Format, Resource, Format, &CompareOptions) -> Result<CompareReport, CompareErr> = compare_inputs;
+    let _: fn(Resource, Format, Resource, Format, &ConvertOptions, Option<CheckpointMode>, &mut dyn FnMut(String)) -> Result<ConvertReport, ConvertErr> =
+        convert;
+    let _: fn(&[NormalizeStep], Option<usize>) -> DescriptionNormalization = DescriptionNormalization::from_steps;
+    #[cfg(feature = "rules")]
+    let _: fn(&str) -> Result<RulePack, RulePackErr> = RulePack::from_toml;
+    #[cfg(feature = "screening")]
+    let _: fn(&[TxData], &ScreeningOptions) -> Vec<UserScore> = screen;
+    #[cfg(feature = "stats")]
+    let _: fn(Resource, Format, StatsOptions, usize) -> Result<StatsReport, CommonErr> = collect_stats;
+    #[cfg(feature = "junit")]
+    let _: fn(&str, &[TestCase]) -> String = render_junit;
+    let _: fn(&str) -> Result<MemoryBudget, String> = MemoryBudget::parse;
+    #[cfg(feature = "spill")]
+    let _: fn(Resource, Format, Resource, Format, &SortOptions) -> Result<SortReport, CommonErr> = sort_file;
+    #[cfg(feature = "partition")]
+    let _: fn(Resource, Format, &OutputTemplate, Format, &ConvertOptions, &mut dyn FnMut(String)) -> Result<PartitionReport, ConvertErr> =
+        partition_convert;
+    #[cfg(feature = "partition")]
+    let _: fn(&Path) -> Result<OutputTemplate, ConvertErr> = OutputTemplate::parse;
+    let _: fn(&Path, Format, Format, &ConvertOptions, &mut dyn FnMut(String)) -> Result<ConvertReport, ConvertErr> =
+        convert_in_place;
+    let _: fn(&Path) -> Result<Option<Vec<PathBuf>>, ConvertErr> = expand_inputs;
+    let _: fn(
+        Vec<PathBuf>,
+        Format,
+        &Path,
+        Format,
+        &ConvertOptions,
+        &(dyn Fn(&Path) -> Option<String> + Sync),
+        &(dyn Fn(&FileConvertResult) + Sync),
+    ) -> Result<ConvertManyReport, ConvertErr> = convert_many;
+    let _: fn(Resource, Format, Option<Resource>, &WriteOptions) -> Result<MigrateReport, CommonErr> = migrate_or_inspect;
+    #[cfg(feature = "bundle")]
+    let _: fn(&Path, Option<&Path>) -> Result<Vec<BundleEntry>, CommonErr> = unbundle;
+    #[cfg(feature = "preview")]
+    let _: fn(&[TxData]) -> String = preview_table;
+    #[cfg(feature = "drift")]
+    let _: fn(Resource, Format, usize) -> Result<Shape, CommonErr> = observe_shape;
+    #[cfg(feature = "drift")]
+    let _: fn(&Shape, &Shape) -> Vec<Drift> = schema_diff;
+    #[cfg(feature = "manifest")]
+    let _: fn(&Path, Format, &str) -> Result<PathBuf, CommonErr> = write_manifest;
+    let _: fn(Format) -> Capabilities = Format::capabilities;
+    let _: fn(Format, &WriteOptions) -> Capabilities = Format::capabilities_with;
+    let _: fn(Loss, &TxData) -> bool = Loss::affects;
+    let _: fn(Format, &ParseOptions, Format, &WriteOptions) -> Vec<Loss> = conversion_losses;
+    let _: fn(Resource, Format) -> Result<Option<ProducerMetadata>, CommonErr> = read_metadata;
+    let _: fn(PathBuf, Format, &WriteOptions) -> Result<BatchWriter, CommonErr> = append_batches;
+    #[cfg(feature = "bench")]
+    let _: fn(&Path, &BenchOptions, &mut dyn FnMut(&BenchResult)) -> Result<Vec<BenchResult>, ConvertErr> = run_bench;
+    #[cfg(feature = "testing")]
+    let _: fn(&Path, &dyn TxFormat) -> Result<Vec<TxData>, String> = check_roundtrip;
+    #[cfg(feature = "testing")]
+    let _: fn(&Path, Option<(Format, &dyn TxFormat)>) -> Result<Vec<ConformanceCase>, CommonErr> = run_conformance;
+    #[cfg(feature = "rules")]
+    let _: fn(&RulePack, &TxData) -> Vec<RuleViolation> = RulePack::check;
+}
+
+#[test]
+fn prelude_traits_keep_their_signatures() {
+    let _: fn(&[u8]) -> Result<TxData, ParserErr> = <TxData as TxnFromBin>::from_bin;
+    let _: fn(Box<dyn Read>) -> Result<Vec<TxData>, ParserErr> =
+        <TxData as TxnFromBin>::from_bin_reader;
+    let _: fn(&TxData) -> Result<Vec<u8>, ParserErr> = <TxData as TxnToBin>::to_bin;
+    let _: fn(&[TxData]) -> Result<Vec<u8>, ParserErr> = <TxData as TxnToBin>::to_bin_many;
+    let _: fn(&[TxData], &WriteOptions) -> Result<Vec<u8>, ParserErr> =
+        <TxData as TxnToBin>::to_bin_many_with;
+
+    let _: fn(&str) -> Result<TxData, ParserErr> = <TxData as TxnFromCsv>::from_csv;
+    let _: fn(&[String]) -> Result<Vec<TxData>, ParserErr> = <TxData as TxnFromCsv>::from_csv_many;
+    let _: fn(Box<dyn Read>) -> Result<Vec<TxData>, ParserErr> =
+        <TxData as TxnFromCsv>::from_csv_reader;
+    let _: fn(&TxData) -> Result<String, ParserErr> = <TxData as TxnToCsv>::to_csv;
+    let _: fn(&[TxData]) -> Result<String, ParserErr> = <TxData as TxnToCsv>::to_csv_many;
+
+    let _: fn(&HashMap<String, String>) -> Result<TxData, ParserErr> =
+        <TxData as TxnFromText>::from_text;
+    let _: fn(&[String]) -> Result<Vec<TxData>, ParserErr> =
+        <TxData as TxnFromText>::from_text_many;
+    let _: fn(Box<dyn Read>) -> Result<Vec<TxData>, ParserErr> =
+        <TxData as TxnFromText>::from_text_reader;
+    let _: fn(&TxData) -> Result<String, ParserErr> = <TxData as TxnToText>::to_text;
+    let _: fn(&[TxData]) -> Result<String, ParserErr> = <TxData as TxnToText>::to_text_many;
+    let _: fn(&[TxData], &[TextAnnotation]) -> Result<String, ParserErr> =
+        <TxData as TxnToText>::to_text_annotated;
+
+    let _: fn(&TxData, &mut dyn Write) -> Result<(), ParserErr> = <TxData as TxnWriteToBin>::to_bin_writer;
+    let _: fn(&[TxData], &WriteOptions, &mut dyn Write) -> Result<(), ParserErr> =
+        <TxData as TxnWriteToBin>::to_bin_writer_many;
+    let _: fn(&TxData, &mut dyn Write) -> Result<(), ParserErr> = <TxData as TxnWriteToCsv>::to_csv_writer;
+    let _: fn(&[TxData], &mut dyn Write) -> Result<(), ParserErr> =
+        <TxData as TxnWriteToCsv>::to_csv_writer_many;
+    let _: fn(&TxData, &mut dyn Write) -> Result<(), ParserErr> = <TxData as TxnWriteToText>::to_text_writer;
+    let _: fn(&[TxData], &[TextAnnotation], &mut dyn Write) -> Result<(), ParserErr> =
+        <TxData as TxnWriteToText>::to_text_writer_many;
+}
+
+#[test]
+fn prelude_data_types_keep_their_shape() {
+    // Литерал без `..` перестанет компилироваться при добавлении или удалении поля.
+    let tx = TxData {
+        tx_id: TxId::U64(1),
+        tx_type: TxType::Deposit,
+        from_user_id: 0,
+        to_user_id: 1,
+        amount: 100,
+        timestamp: 1700000000,
+        status: Status::Success,
+        description: "api".into(),
+        format: Format::YpBankCsv,
+    };
+
+    // Исчерпывающие match без `_` фиксируют набор вариантов перечислений.
+    match tx.tx_type {
+        TxType::Deposit | TxType::Transfer | TxType::Withdrawal => {}
+    }
+    match tx.status {
+        Status::Success | Status::Failure | Status::Pending => {}
+    }
+    match tx.format {
+        Format::YpBankCsv | Format::YpBankText | Format::YpBankBin => {}
+    }
+}
+
+#[test]
+fn prelude_resources_are_constructible() {
+    let resources = [
+        Resource::Console,
+        Resource::File { path: "records.csv".into() },
+        Resource::Memory { data: Cursor::new(Vec::new()) },
+    ];
+    assert_eq!(resources.len(), 3);
+}